@@ -0,0 +1,133 @@
+//! Crash-safe replacement of persisted files
+//!
+//! Writing a file in place leaves it truncated if the app dies mid-write.
+//! These helpers write a temp file next to the target, sync it, rename it
+//! over the target and sync the directory, so readers see either the old
+//! contents or the new ones, never a mix.
+
+use crate::errors::{AppError, Result};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Replaces `path` with the JSON encoding of `value`
+pub fn atomic_write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    let json = serde_json::to_vec(value)
+        .map_err(|e| AppError::General(format!("Cannot serialize {}: {e}", path.display())))?;
+    atomic_write(path, &json)
+}
+
+/// Replaces `path` with `contents`
+pub fn atomic_write(path: &Path, contents: &[u8]) -> Result<()> {
+    write_then_rename(path, contents, |_| Ok(()))
+}
+
+/// Replaces `path` with the file `produce` writes to the temp path given to it
+///
+/// For contents too large to hold in memory, like a remuxed audiobook. The
+/// target is untouched if `produce` fails.
+pub fn atomic_replace_with(path: &Path, produce: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let temp = temp_path(path);
+    let replaced = produce(&temp)
+        .and_then(|()| Ok(File::open(&temp)?.sync_all()?))
+        .and_then(|()| Ok(fs::rename(&temp, path)?));
+    if let Err(e) = replaced {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    sync_parent_dir(path);
+    Ok(())
+}
+
+/// Temp file a write to `path` goes through
+///
+/// The name is fixed per target, so a temp left behind by a crash is
+/// overwritten by the next write rather than piling up.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{name}.tmp"))
+}
+
+/// Writes and syncs the temp file, calls `before_rename`, then renames it into place
+fn write_then_rename(path: &Path, contents: &[u8], before_rename: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let temp = temp_path(path);
+    let mut file = File::create(&temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    if let Err(e) = before_rename(&temp).and_then(|()| Ok(fs::rename(&temp, path)?)) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    sync_parent_dir(path);
+    Ok(())
+}
+
+/// Makes the rename itself durable; directories can't be synced on Windows
+fn sync_parent_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        if let Err(e) = File::open(parent).and_then(|dir| dir.sync_all()) {
+            log::debug!("Cannot sync directory {}: {e}", parent.display());
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_replaces_contents_without_leaving_temp() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("prefs.json");
+        atomic_write_json(&file, &vec!["first"]).unwrap();
+        atomic_write_json(&file, &vec!["second", "value"]).unwrap();
+
+        let stored: Vec<String> = serde_json::from_slice(&fs::read(&file).unwrap()).unwrap();
+        assert_eq!(stored, ["second", "value"]);
+        assert!(!temp_path(&file).exists());
+    }
+
+    #[test]
+    fn test_crash_before_rename_keeps_previous_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("history.jsonl");
+        atomic_write(&file, b"{\"run\":1}\n").unwrap();
+
+        let crashed = write_then_rename(&file, b"{\"run\":1}\n{\"ru", |temp| {
+            assert_eq!(fs::read(temp).unwrap(), b"{\"run\":1}\n{\"ru");
+            Err(AppError::General("simulated crash".to_string()))
+        });
+        assert!(crashed.is_err());
+        assert_eq!(fs::read(&file).unwrap(), b"{\"run\":1}\n");
+
+        // A temp left by a real crash doesn't block the next write
+        fs::write(temp_path(&file), b"partial").unwrap();
+        atomic_write(&file, b"{\"run\":2}\n").unwrap();
+        assert_eq!(fs::read(&file).unwrap(), b"{\"run\":2}\n");
+    }
+
+    #[test]
+    fn test_replace_with_keeps_target_when_producer_fails() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("book.m4b");
+        fs::write(&file, b"old").unwrap();
+
+        let failed = atomic_replace_with(&file, |temp| {
+            fs::write(temp, b"partial")?;
+            Err(AppError::General("remux failed".to_string()))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read(&file).unwrap(), b"old");
+        assert!(!temp_path(&file).exists());
+
+        atomic_replace_with(&file, |temp| Ok(fs::write(temp, b"new")?)).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), b"new");
+    }
+}
@@ -0,0 +1,213 @@
+//! Chapter suggestions from silences
+//!
+//! A single long recording has no file boundaries to put chapters on, but
+//! narrators usually pause between chapters. FFmpeg's `silencedetect` filter
+//! reports every quiet stretch on stderr; each silence becomes a candidate
+//! chapter start at its midpoint, so the pause is split between the two
+//! chapters. The silences are returned too, so the user can tune the
+//! threshold and minimum length against what was found.
+
+use super::constants::{SILENCE_EDGE_IGNORE_SECS, SILENCE_MAX_SUGGESTIONS, SILENCE_MIN_THRESHOLD_DB};
+use crate::errors::{AppError, Result};
+use crate::ffmpeg::{locate_ffmpeg, FFmpegError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// One quiet stretch reported by `silencedetect`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SilenceSpan {
+    pub start_secs: f64,
+    pub end_secs: f64,
+    pub duration_secs: f64,
+}
+
+/// Candidate chapter starts and the silences they came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SilenceSuggestions {
+    /// Suggested chapter starts in seconds, ascending; the first is always 0
+    pub chapter_starts: Vec<f64>,
+    /// Silences behind the suggestions, in the same order
+    pub silences: Vec<SilenceSpan>,
+    /// Silences found in total, before edges were dropped and the cap applied
+    pub detected_count: usize,
+    /// Whether shorter silences were dropped to stay under the cap
+    pub truncated: bool,
+}
+
+/// Silences in `silencedetect` stderr output
+///
+/// A silence still open at the end of the output runs to the end of the
+/// file and is dropped; it can't start a chapter.
+pub fn parse_silencedetect(stderr: &str) -> Vec<SilenceSpan> {
+    let mut silences = Vec::new();
+    let mut open_start = None;
+    for line in stderr.lines().filter(|line| line.contains("silencedetect")) {
+        if let Some(start) = field_value(line, "silence_start:") {
+            open_start = Some(start);
+        } else if let Some(end) = field_value(line, "silence_end:") {
+            let duration = field_value(line, "silence_duration:");
+            let Some(start) = open_start.take().or(duration.map(|duration| end - duration)) else {
+                continue;
+            };
+            let start = start.max(0.0);
+            silences.push(SilenceSpan { start_secs: start, end_secs: end, duration_secs: duration.unwrap_or(end - start) });
+        }
+    }
+    silences
+}
+
+/// Input duration from FFmpeg's `Duration: HH:MM:SS.ss` header line
+pub fn parse_input_duration(stderr: &str) -> Option<f64> {
+    let line = stderr.lines().find(|line| line.trim_start().starts_with("Duration:"))?;
+    let time = line.trim_start().trim_start_matches("Duration:").split(',').next()?.trim();
+    let mut parts = time.split(':').map(|part| part.parse::<f64>().ok());
+    let (Some(Some(hours)), Some(Some(minutes)), Some(Some(seconds))) = (parts.next(), parts.next(), parts.next()) else {
+        return None;
+    };
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// Turns silences into chapter starts, keeping the longest `max_suggestions`
+///
+/// Silences at the very start or end of the file (`duration_secs`, when
+/// known) are dropped, since they border no chapter.
+pub fn suggest_chapter_starts(silences: &[SilenceSpan], duration_secs: Option<f64>, max_suggestions: usize) -> SilenceSuggestions {
+    let mut inner: Vec<SilenceSpan> = silences.iter()
+        .filter(|silence| silence.start_secs > SILENCE_EDGE_IGNORE_SECS)
+        .filter(|silence| duration_secs.is_none_or(|duration| silence.end_secs < duration - SILENCE_EDGE_IGNORE_SECS))
+        .copied()
+        .collect();
+    let truncated = inner.len() > max_suggestions;
+    if truncated {
+        inner.sort_by(|a, b| b.duration_secs.total_cmp(&a.duration_secs));
+        inner.truncate(max_suggestions);
+        inner.sort_by(|a, b| a.start_secs.total_cmp(&b.start_secs));
+    }
+    let chapter_starts = std::iter::once(0.0)
+        .chain(inner.iter().map(|silence| (silence.start_secs + silence.end_secs) / 2.0))
+        .collect();
+    SilenceSuggestions { chapter_starts, silences: inner, detected_count: silences.len(), truncated }
+}
+
+/// Runs `silencedetect` over `path` and suggests chapter starts
+///
+/// `threshold_db` is the noise floor (e.g. -30); anything quieter for at
+/// least `min_silence_secs` counts as silence.
+pub fn suggest_chapters_from_silence(path: &Path, min_silence_secs: f64, threshold_db: f64) -> Result<SilenceSuggestions> {
+    if !min_silence_secs.is_finite() || min_silence_secs <= 0.0 {
+        return Err(AppError::InvalidInput(format!("Minimum silence must be above 0 seconds, got {min_silence_secs}")));
+    }
+    if !threshold_db.is_finite() || !(SILENCE_MIN_THRESHOLD_DB..=0.0).contains(&threshold_db) {
+        return Err(AppError::InvalidInput(format!(
+            "Silence threshold must be between {SILENCE_MIN_THRESHOLD_DB} and 0 dB, got {threshold_db}"
+        )));
+    }
+    if !path.is_file() {
+        return Err(AppError::FileValidation(format!("File not found: {}", path.display())));
+    }
+
+    let filter = format!("silencedetect=noise={threshold_db}dB:d={min_silence_secs}");
+    let output = Command::new(locate_ffmpeg()?)
+        .args(["-hide_banner", "-nostats", "-nostdin", "-i"])
+        .arg(path)
+        .args(["-vn", "-af", &filter, "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(FFmpegError::ExecutionFailed(stderr.to_string()).into());
+    }
+
+    let silences = parse_silencedetect(&stderr);
+    let suggestions = suggest_chapter_starts(&silences, parse_input_duration(&stderr), SILENCE_MAX_SUGGESTIONS);
+    log::info!(
+        "Found {} silences in {}, suggesting {} chapters",
+        suggestions.detected_count,
+        path.display(),
+        suggestions.chapter_starts.len()
+    );
+    Ok(suggestions)
+}
+
+/// Number following `key` on a `silencedetect` line
+fn field_value(line: &str, key: &str) -> Option<f64> {
+    let (_, rest) = line.split_once(key)?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Captured from `ffmpeg -hide_banner -nostats -i book.mp3 -af silencedetect=noise=-30dB:d=2 -f null -`
+    const CAPTURED: &str = "\
+Input #0, mp3, from 'book.mp3':
+  Metadata:
+    title           : Book
+  Duration: 00:20:05.03, start: 0.025057, bitrate: 64 kb/s
+  Stream #0:0: Audio: mp3, 44100 Hz, mono, fltp, 64 kb/s
+Stream mapping:
+  Stream #0:0 -> #0:0 (mp3 (mp3float) -> pcm_s16le (native))
+Output #0, null, to 'pipe:':
+[silencedetect @ 0x600002b2c000] silence_start: 0
+[silencedetect @ 0x600002b2c000] silence_end: 2.51 | silence_duration: 2.51
+[silencedetect @ 0x600002b2c000] silence_start: 301.204
+[silencedetect @ 0x600002b2c000] silence_end: 304.006 | silence_duration: 2.802
+[out#0/null @ 0x600002f30000] video:0KiB audio:103797KiB subtitle:0KiB other streams:0KiB global headers:0KiB muxing overhead: unknown
+[silencedetect @ 0x600002b2c000] silence_start: 712.5
+[silencedetect @ 0x600002b2c000] silence_end: 716.5 | silence_duration: 4
+[silencedetect @ 0x600002b2c000] silence_start: 1201.9
+[silencedetect @ 0x600002b2c000] silence_end: 1205.03 | silence_duration: 3.13
+size=N/A time=00:20:05.03 bitrate=N/A speed= 512x
+";
+
+    #[test]
+    fn test_parse_captured_silencedetect_output() {
+        let silences = parse_silencedetect(CAPTURED);
+        assert_eq!(silences.len(), 4);
+        assert_eq!(silences[1], SilenceSpan { start_secs: 301.204, end_secs: 304.006, duration_secs: 2.802 });
+        assert_eq!(parse_input_duration(CAPTURED), Some(1205.03));
+
+        // A silence still open at EOF is dropped; an end without a start uses its duration
+        let silences = parse_silencedetect("[silencedetect @ 0x1] silence_end: 10.5 | silence_duration: 2.5\n[silencedetect @ 0x1] silence_start: 20\n");
+        assert_eq!(silences, [SilenceSpan { start_secs: 8.0, end_secs: 10.5, duration_secs: 2.5 }]);
+        assert_eq!(parse_input_duration("no header"), None);
+    }
+
+    #[test]
+    fn test_suggestions_skip_edges_and_use_midpoints() {
+        let suggestions = suggest_chapter_starts(&parse_silencedetect(CAPTURED), Some(1205.03), SILENCE_MAX_SUGGESTIONS);
+        assert_eq!(suggestions.chapter_starts, [0.0, 302.605, 714.5]);
+        assert_eq!(suggestions.silences.len(), 2);
+        assert_eq!(suggestions.detected_count, 4);
+        assert!(!suggestions.truncated);
+    }
+
+    #[test]
+    fn test_suggestions_keep_longest_silences_under_cap() {
+        let silences: Vec<SilenceSpan> = (1..=5)
+            .map(|index| {
+                let start = index as f64 * 100.0;
+                let duration = if index % 2 == 0 { 5.0 } else { 2.0 };
+                SilenceSpan { start_secs: start, end_secs: start + duration, duration_secs: duration }
+            })
+            .collect();
+        let suggestions = suggest_chapter_starts(&silences, None, 2);
+        assert!(suggestions.truncated);
+        assert_eq!(suggestions.chapter_starts, [0.0, 202.5, 402.5]);
+        assert_eq!(suggestions.detected_count, 5);
+    }
+
+    #[test]
+    fn test_rejects_invalid_parameters() {
+        let path = Path::new("../media/01 - Introduction.mp3");
+        assert!(suggest_chapters_from_silence(path, 0.0, -30.0).is_err());
+        assert!(suggest_chapters_from_silence(path, 2.0, 6.0).is_err());
+        assert!(suggest_chapters_from_silence(path, 2.0, -120.0).is_err());
+        assert!(suggest_chapters_from_silence(Path::new("/nonexistent.mp3"), 2.0, -30.0).is_err());
+    }
+}
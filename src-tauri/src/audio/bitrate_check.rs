@@ -0,0 +1,108 @@
+//! Target bitrate check against the source bitrate
+//!
+//! Re-encoding a 32 kbps source at 128 kbps quadruples the size without any
+//! quality gain. When the target exceeds the best input by more than the
+//! configured factor the run is warned about, or refused in strict mode.
+
+use super::constants::DEFAULT_BITRATE_EXCESS_FACTOR;
+use super::{AudioFile, AudioSettings};
+use crate::errors::{AppError, Result};
+
+/// Target bitrate well above anything the inputs provide
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitrateExcess {
+    /// Requested output bitrate in kbps
+    pub target_kbps: u32,
+    /// Highest input bitrate in kbps
+    pub source_kbps: u32,
+}
+
+impl BitrateExcess {
+    /// Human-readable warning
+    pub fn message(&self) -> String {
+        format!(
+            "Target bitrate {} kbps exceeds the best source bitrate of {} kbps; the larger file won't sound better",
+            self.target_kbps, self.source_kbps
+        )
+    }
+}
+
+/// Highest bitrate among the valid inputs, if any reports one
+pub fn max_source_bitrate(files: &[AudioFile]) -> Option<u32> {
+    files.iter()
+        .filter(|f| f.is_valid)
+        .filter_map(|f| f.bitrate)
+        .filter(|&kbps| kbps > 0)
+        .max()
+}
+
+/// Compares the target against the best source using the settings' factor
+pub fn bitrate_excess(settings: &AudioSettings, source_kbps: Option<u32>) -> Option<BitrateExcess> {
+    let factor = settings.bitrate_excess_factor.unwrap_or(DEFAULT_BITRATE_EXCESS_FACTOR);
+    exceeds_source(settings.bitrate, source_kbps?, factor)
+}
+
+/// True excess only when `target > source * factor`
+fn exceeds_source(target_kbps: u32, source_kbps: u32, factor: f32) -> Option<BitrateExcess> {
+    (f64::from(target_kbps) > f64::from(source_kbps) * f64::from(factor))
+        .then_some(BitrateExcess { target_kbps, source_kbps })
+}
+
+/// Errors in strict mode, otherwise returns the excess to warn about
+pub fn check_bitrate_excess(settings: &AudioSettings, files: &[AudioFile]) -> Result<Option<BitrateExcess>> {
+    let excess = bitrate_excess(settings, max_source_bitrate(files));
+    match excess {
+        Some(excess) if settings.strict_bitrate_check => Err(AppError::InvalidInput(format!(
+            "{}. Lower the bitrate or turn off strict bitrate checking to continue",
+            excess.message()
+        ))),
+        _ => Ok(excess),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(bitrate: Option<u32>, is_valid: bool) -> AudioFile {
+        let mut file = AudioFile::new("/books/01.mp3".into());
+        file.bitrate = bitrate;
+        file.is_valid = is_valid;
+        file
+    }
+
+    #[test]
+    fn test_threshold_math() {
+        assert_eq!(exceeds_source(128, 32, 1.5), Some(BitrateExcess { target_kbps: 128, source_kbps: 32 }));
+        assert_eq!(exceeds_source(48, 32, 1.5), None); // exactly at the factor
+        assert!(exceeds_source(49, 32, 1.5).is_some());
+        assert_eq!(exceeds_source(64, 64, 1.0), None);
+        assert_eq!(exceeds_source(64, 128, 1.5), None);
+    }
+
+    #[test]
+    fn test_best_source_ignores_invalid_and_unknown() {
+        let files = [file(Some(32), true), file(Some(320), false), file(None, true), file(Some(48), true)];
+        assert_eq!(max_source_bitrate(&files), Some(48));
+        assert_eq!(max_source_bitrate(&[file(None, true)]), None);
+    }
+
+    #[test]
+    fn test_strict_mode_errors() {
+        let files = [file(Some(32), true)];
+        let mut settings = AudioSettings::audiobook_preset();
+        settings.bitrate = 128;
+        assert!(check_bitrate_excess(&settings, &files).unwrap().is_some());
+
+        settings.bitrate_excess_factor = Some(4.0);
+        assert_eq!(check_bitrate_excess(&settings, &files).unwrap(), None);
+
+        settings.bitrate_excess_factor = None;
+        settings.strict_bitrate_check = true;
+        let err = check_bitrate_excess(&settings, &files).unwrap_err();
+        assert!(matches!(err, AppError::InvalidInput(ref msg) if msg.contains("128 kbps") && msg.contains("32 kbps")));
+
+        settings.bitrate = 48;
+        assert_eq!(check_bitrate_excess(&settings, &files).unwrap(), None);
+    }
+}
@@ -0,0 +1,201 @@
+//! Encode speed calibration
+//!
+//! Encodes a short sample of the first input with the chosen settings and
+//! extrapolates the measured realtime factor to the whole file list, so the
+//! UI can show an expected wall time before a long run starts.
+
+use super::cleanup::{CleanupGuard, ProcessGuard};
+use super::constants::{
+    CALIBRATION_POLL_INTERVAL_MS, CALIBRATION_SAMPLE_SECONDS, ENCODE_FAST_SPEED_FACTOR,
+    ENCODE_SLOW_SPEED_FACTOR, FFMPEG_AUDIO_CODEC, TEMP_DIR_NAME,
+};
+use super::settings::ResolvedAudioSettings;
+use super::{AudioFile, AudioSettings};
+use crate::errors::{AppError, CancelReason, Result};
+use crate::ffmpeg::{locate_ffmpeg, FFmpegError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Bumped by `cancel_calibration`; running calibrations stop when it changes
+static CANCEL_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Coarse encoder speed bucket for display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodeSpeedClass {
+    Fast,
+    Moderate,
+    Slow,
+}
+
+impl EncodeSpeedClass {
+    /// Buckets a realtime speed factor
+    pub fn from_speed_factor(speed_factor: f64) -> Self {
+        if speed_factor >= ENCODE_FAST_SPEED_FACTOR {
+            Self::Fast
+        } else if speed_factor >= ENCODE_SLOW_SPEED_FACTOR {
+            Self::Moderate
+        } else {
+            Self::Slow
+        }
+    }
+}
+
+/// Measured encoder speed and the extrapolated time for the full run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodeEstimate {
+    /// Seconds of audio encoded per second of wall time
+    pub speed_factor: f64,
+    pub speed_class: EncodeSpeedClass,
+    /// Expected wall time for encoding every input, in seconds
+    pub estimated_wall_seconds: f64,
+}
+
+impl EncodeEstimate {
+    /// Extrapolates a measured speed factor to `total_duration` seconds of audio
+    ///
+    /// Returns None when the measurement is unusable (zero or non-finite).
+    pub fn from_speed_factor(total_duration: f64, speed_factor: f64) -> Option<Self> {
+        if !speed_factor.is_finite() || speed_factor <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            speed_factor,
+            speed_class: EncodeSpeedClass::from_speed_factor(speed_factor),
+            estimated_wall_seconds: total_duration.max(0.0) / speed_factor,
+        })
+    }
+}
+
+/// Stops any encode calibration that is still running
+pub fn cancel_calibration() {
+    CANCEL_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Calibrates the encoder on the first valid input and estimates total wall time
+///
+/// Returns None when FFmpeg is unavailable or no input has a known duration.
+pub fn estimate_encode_time(files: &[AudioFile], settings: &AudioSettings) -> Result<Option<EncodeEstimate>> {
+    let valid: Vec<&AudioFile> = files.iter().filter(|f| f.is_valid).collect();
+    let Some((first, first_duration)) = valid
+        .iter()
+        .find_map(|f| f.duration.filter(|d| *d > 0.0).map(|d| (*f, d)))
+    else {
+        return Ok(None);
+    };
+    let Ok(ffmpeg) = locate_ffmpeg() else {
+        return Ok(None);
+    };
+
+    let total_duration: f64 = valid.iter().filter_map(|f| f.duration).sum();
+    let sample_seconds = first_duration.min(CALIBRATION_SAMPLE_SECONDS);
+    let generation = CANCEL_GENERATION.load(Ordering::SeqCst);
+    let resolved = settings.resolve(std::slice::from_ref(first))?;
+    let elapsed = measure_encode(&ffmpeg, &first.path, &resolved, sample_seconds, || {
+        CANCEL_GENERATION.load(Ordering::SeqCst) != generation
+    })?;
+
+    Ok(EncodeEstimate::from_speed_factor(total_duration, sample_seconds / elapsed.as_secs_f64()))
+}
+
+/// Encodes `sample_seconds` of `input` into a throwaway file and times it
+fn measure_encode<F: Fn() -> bool>(
+    ffmpeg: &Path,
+    input: &Path,
+    resolved: &ResolvedAudioSettings,
+    sample_seconds: f64,
+    is_cancelled: F,
+) -> Result<Duration> {
+    let calibration_id = format!("calibration-{}", uuid::Uuid::new_v4());
+    let temp_dir = std::env::temp_dir().join(TEMP_DIR_NAME).join(&calibration_id);
+    std::fs::create_dir_all(&temp_dir)?;
+    let mut cleanup = CleanupGuard::new(calibration_id.clone());
+    cleanup.add_path(&temp_dir);
+
+    let mut cmd = Command::new(ffmpeg);
+    cmd.args(["-v", "error", "-nostdin", "-t", &sample_seconds.to_string(), "-i"])
+        .arg(input)
+        .args([
+            "-vn",
+            "-c:a", FFMPEG_AUDIO_CODEC,
+            "-b:a", &format!("{}k", resolved.bitrate),
+            "-ar", &resolved.sample_rate.to_string(),
+            "-ac", &resolved.channels.to_string(),
+        ]);
+    cmd.arg("-y").arg(temp_dir.join("sample.m4a"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    let started = Instant::now();
+    let child = cmd.spawn().map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))?;
+    let guard = ProcessGuard::new(child, calibration_id, "Encode calibration".to_string());
+    let handle = guard.process_handle();
+
+    loop {
+        if is_cancelled() {
+            return Err(AppError::Cancelled(CancelReason::UserRequested));
+        }
+        let status = {
+            let mut process = handle.lock()
+                .map_err(|_| AppError::General("Failed to acquire process lock".to_string()))?;
+            let status = match process.as_mut() {
+                Some(child) => child.try_wait()?,
+                None => return Err(AppError::General("Calibration process was lost".to_string())),
+            };
+            if status.is_some() {
+                // Already exited, so the guard has nothing left to terminate
+                process.take();
+            }
+            status
+        };
+        match status {
+            Some(status) if status.success() => return Ok(started.elapsed()),
+            Some(status) => {
+                return Err(FFmpegError::ExecutionFailed(format!(
+                    "Calibration encode exited with {status}"
+                )).into());
+            }
+            None => std::thread::sleep(Duration::from_millis(CALIBRATION_POLL_INTERVAL_MS)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_extrapolates_measured_speed() {
+        // 15s sample encoded in 0.5s -> 30x; ten hours of audio takes 20 minutes
+        let speed = 15.0 / 0.5;
+        let estimate = EncodeEstimate::from_speed_factor(36_000.0, speed).unwrap();
+        assert_eq!(estimate.speed_factor, 30.0);
+        assert_eq!(estimate.speed_class, EncodeSpeedClass::Fast);
+        assert!((estimate.estimated_wall_seconds - 1200.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_speed_classes() {
+        assert_eq!(EncodeSpeedClass::from_speed_factor(20.0), EncodeSpeedClass::Fast);
+        assert_eq!(EncodeSpeedClass::from_speed_factor(8.0), EncodeSpeedClass::Moderate);
+        assert_eq!(EncodeSpeedClass::from_speed_factor(1.5), EncodeSpeedClass::Slow);
+    }
+
+    #[test]
+    fn test_unusable_measurement_gives_no_estimate() {
+        assert!(EncodeEstimate::from_speed_factor(100.0, 0.0).is_none());
+        assert!(EncodeEstimate::from_speed_factor(100.0, f64::INFINITY).is_none());
+        assert!(EncodeEstimate::from_speed_factor(100.0, f64::NAN).is_none());
+    }
+
+    #[test]
+    fn test_no_valid_inputs_skips_calibration() {
+        let files = vec![AudioFile::new("missing.mp3".into())];
+        let result = estimate_encode_time(&files, &AudioSettings::default()).unwrap();
+        assert!(result.is_none());
+    }
+}
@@ -0,0 +1,831 @@
+//! Chapter generation and title helpers
+//!
+//! Chapters come either one per input file or at a fixed interval, with
+//! titles from a template, or from an explicit list given with the request. Players collapse or confuse chapters that share
+//! a title, which happens easily when titles come from per-file tags
+//! ("Introduction" on every disc). Disambiguation runs on the final titles
+//! as the last step of generation.
+
+use super::constants::{
+    CHAPTER_SPEC_TOLERANCE_SECS, DEFAULT_CHAPTER_INTERVAL_MINUTES, MIN_CHAPTER_INTERVAL_MINUTES,
+};
+use super::input_spec::TrimSpec;
+use super::mp3_duration::apply_planning_durations;
+use super::{AudioFile, AudioSettings};
+use crate::errors::{AppError, Result};
+use crate::metadata::ChapterInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Where chapter marks are placed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChapterMode {
+    /// One chapter per input file
+    #[default]
+    PerFile,
+    /// A chapter every `interval_minutes`, regardless of file boundaries
+    FixedInterval,
+}
+
+/// What per-file chapter titles are made from when no template is set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChapterTitleSource {
+    /// The input's filename without extension
+    Filename,
+    /// The input's title tag, falling back to the filename
+    #[default]
+    TagTitle,
+    /// "Chapter 01", "Chapter 02", ...
+    Numbered,
+}
+
+impl ChapterTitleSource {
+    /// Per-file title template for this source
+    pub fn template(self) -> &'static str {
+        match self {
+            Self::Filename => "{filename}",
+            Self::TagTitle => "{title}",
+            Self::Numbered => "Chapter {nn}",
+        }
+    }
+}
+
+/// How chapter starts are written in a description's chapter list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChapterTimestampFormat {
+    /// `MM:SS`, or `H:MM:SS` on every line once a chapter starts past an hour
+    #[default]
+    Auto,
+    /// `HH:MM:SS` on every line
+    Hours,
+    /// `MM:SS`, with minutes running past 59
+    Minutes,
+}
+
+/// Which MP4 chapter atoms are written
+///
+/// Players disagree: Apple Books reads the QuickTime chapter track FFmpeg
+/// muxes from ffmetadata, while some Android players only read the Nero
+/// `chpl` atom.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChapterFormat {
+    /// Chapters as FFmpeg muxes them from an ffmetadata file
+    #[default]
+    Ffmetadata,
+    /// Only a Nero `chpl` atom in `moov/udta`
+    Chpl,
+    /// FFmpeg's chapters plus a `chpl` atom
+    Both,
+}
+
+impl ChapterFormat {
+    /// Whether FFmpeg is given the chapters to mux
+    pub fn muxes_with_ffmpeg(self) -> bool {
+        !matches!(self, Self::Chpl)
+    }
+
+    /// Whether a `chpl` atom is written once the file is muxed
+    pub fn writes_chpl(self) -> bool {
+        !matches!(self, Self::Ffmetadata)
+    }
+}
+
+/// How chapters are generated
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterOptions {
+    /// Per-file or fixed-interval chapters
+    #[serde(default)]
+    pub mode: ChapterMode,
+    /// Chapter length for fixed-interval mode (None uses the default of 10)
+    #[serde(default)]
+    pub interval_minutes: Option<f64>,
+    /// Title template with `{n}`, `{nn}` (zero-padded), `{title}` and `{filename}`
+    /// placeholders (None uses "{title}" per file, or the settings' title source,
+    /// and "Chapter {n}" for intervals)
+    #[serde(default)]
+    pub title_template: Option<String>,
+    /// How repeated titles are made unique
+    #[serde(default)]
+    pub duplicate_style: DuplicateTitleStyle,
+}
+
+/// One generated chapter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chapter {
+    /// Chapter title
+    pub title: String,
+    /// Start in seconds from the start of the book
+    pub start_secs: f64,
+    /// End in seconds from the start of the book
+    pub end_secs: f64,
+}
+
+/// A chapter of an explicit list, placed by start time or by the inputs it spans
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterSpec {
+    /// Chapter title
+    pub title: String,
+    /// Start in seconds from the start of the book; the chapter runs until the next one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_secs: Option<f64>,
+    /// Indices of the consecutive inputs the chapter spans, as given in the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_indices: Option<Vec<usize>>,
+}
+
+impl From<&ChapterInfo> for Chapter {
+    fn from(chapter: &ChapterInfo) -> Self {
+        Self {
+            title: chapter.title.clone(),
+            start_secs: chapter.start_ms as f64 / 1000.0,
+            end_secs: chapter.end_ms as f64 / 1000.0,
+        }
+    }
+}
+
+impl From<&Chapter> for ChapterInfo {
+    /// Rounds to the milliseconds the chapters file is written in
+    fn from(chapter: &Chapter) -> Self {
+        Self {
+            title: chapter.title.clone(),
+            start_ms: (chapter.start_secs * 1000.0).round() as u64,
+            end_ms: (chapter.end_secs * 1000.0).round() as u64,
+        }
+    }
+}
+
+/// What a chapter title can draw on for one input
+#[derive(Debug, Clone, PartialEq)]
+struct ChapterSource {
+    /// Title tag, falling back to the filename stem
+    title: String,
+    /// Filename stem
+    filename: String,
+    /// Duration in seconds
+    duration: f64,
+    /// Title from the request, used as-is instead of the template
+    title_override: Option<String>,
+}
+
+impl ChapterSource {
+    fn from_file(file: &AudioFile) -> Self {
+        let filename = file.path.file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let tag_title = file.title.clone().filter(|title| !title.trim().is_empty());
+        Self {
+            title: tag_title.unwrap_or_else(|| filename.clone()),
+            filename,
+            duration: file.duration.unwrap_or(0.0).max(0.0),
+            title_override: file.options.chapter_title.clone(),
+        }
+    }
+}
+
+/// Chapters the valid files would get, without running FFmpeg
+///
+/// Titles come from the title tags found during analysis (or filenames);
+/// invalid files are left out, as they are when processing skips them.
+pub fn generate_chapters(files: &[AudioFile], options: &ChapterOptions) -> Result<Vec<Chapter>> {
+    let sources: Vec<ChapterSource> = files.iter()
+        .filter(|file| file.is_valid)
+        .map(ChapterSource::from_file)
+        .collect();
+    chapters_for_sources(&sources, options)
+}
+
+/// Chapter options a run uses, with the per-file template taken from the title source
+///
+/// None when the settings have no chapter options.
+pub fn effective_chapter_options(settings: &AudioSettings) -> Option<ChapterOptions> {
+    let mut options = settings.chapters.clone()?;
+    if options.mode == ChapterMode::PerFile && options.title_template.is_none() {
+        options.title_template = Some(settings.chapter_title_source.template().to_string());
+    }
+    Some(options)
+}
+
+/// Chapters a run writes into the book, for analyzed inputs in request order
+///
+/// An explicit chapter list in the settings is checked and used as-is;
+/// otherwise chapters are generated by `book_chapters`. Processing and the
+/// chapter preview both call this, so the preview is what the run writes.
+pub fn planned_chapters(files: &[AudioFile], settings: &AudioSettings) -> Result<Vec<Chapter>> {
+    match settings.chapter_specs.as_deref() {
+        Some(specs) => chapters_from_specs(files, specs),
+        None => book_chapters(files, settings),
+    }
+}
+
+/// The chapter table a run would write, in the milliseconds of the chapters file
+///
+/// Input durations are first adjusted as `resolve_plan` adjusts them.
+pub fn preview_chapters(files: &[AudioFile], settings: &AudioSettings) -> Result<Vec<ChapterInfo>> {
+    let mut files = files.to_vec();
+    apply_planning_durations(settings, &mut files);
+    Ok(planned_chapters(&files, settings)?.iter().map(ChapterInfo::from).collect())
+}
+
+/// Chapters written into the merged book
+///
+/// With no chapter options, a book merged from several files gets one
+/// chapter per file titled by `chapter_title_source`, except that a file
+/// with chapters of its own (ID3 `CHAP` frames) brings those instead; a
+/// single input keeps whatever chapters it carries.
+pub fn book_chapters(files: &[AudioFile], settings: &AudioSettings) -> Result<Vec<Chapter>> {
+    match effective_chapter_options(settings) {
+        Some(options) => generate_chapters(files, &options),
+        None if files.iter().filter(|file| file.is_valid).count() > 1 => {
+            let template = settings.chapter_title_source.template().to_string();
+            let options = ChapterOptions { title_template: Some(template), ..ChapterOptions::default() };
+            let per_file = generate_chapters(files, &options)?;
+            Ok(with_embedded_chapters(files, per_file, options.duplicate_style))
+        }
+        None => Ok(files.iter().find(|file| file.is_valid).map(source_chapters).unwrap_or_default()),
+    }
+}
+
+/// Chapters embedded in an input, moved to match its trim
+///
+/// The merge reads inputs through the concat demuxer, which carries no
+/// chapters, so a re-encoded M4B would otherwise lose its own.
+pub fn source_chapters(file: &AudioFile) -> Vec<Chapter> {
+    let chapters: Vec<Chapter> = if file.chapters.is_empty() {
+        crate::metadata::reader::read_chapters(&file.path).iter().map(Chapter::from).collect()
+    } else {
+        file.chapters.iter().map(Chapter::from).collect()
+    };
+    if !chapters.is_empty() {
+        log::info!("Keeping {} chapter(s) of {}", chapters.len(), file.path.display());
+    }
+    trim_chapters(chapters, file.options.trim.as_ref())
+}
+
+/// Replaces the per-file chapter of each input that has embedded chapters with those
+///
+/// `per_file` holds one chapter per valid input, in order. Embedded
+/// chapters are moved to where their input starts in the book and
+/// stretched to cover it without gaps; inputs without any keep their
+/// per-file chapter.
+fn with_embedded_chapters(files: &[AudioFile], per_file: Vec<Chapter>, style: DuplicateTitleStyle) -> Vec<Chapter> {
+    let valid: Vec<&AudioFile> = files.iter().filter(|file| file.is_valid).collect();
+    if valid.iter().all(|file| file.chapters.is_empty()) {
+        return per_file;
+    }
+    let mut chapters = Vec::new();
+    for (file, file_chapter) in valid.into_iter().zip(per_file) {
+        let embedded = trim_chapters(file.chapters.iter().map(Chapter::from).collect(), file.options.trim.as_ref());
+        let starts: Vec<f64> = embedded.iter()
+            .map(|chapter| file_chapter.start_secs + chapter.start_secs)
+            .filter(|&start| start < file_chapter.end_secs)
+            .collect();
+        if starts.is_empty() {
+            chapters.push(file_chapter);
+            continue;
+        }
+        log::info!("Using {} embedded chapter(s) of {}", starts.len(), file.path.display());
+        for (index, chapter) in embedded.into_iter().take(starts.len()).enumerate() {
+            chapters.push(Chapter {
+                title: chapter.title,
+                start_secs: if index == 0 { file_chapter.start_secs } else { starts[index] },
+                end_secs: starts.get(index + 1).copied().unwrap_or(file_chapter.end_secs),
+            });
+        }
+    }
+    let titles: Vec<String> = chapters.iter().map(|chapter| chapter.title.clone()).collect();
+    for (chapter, title) in chapters.iter_mut().zip(disambiguate_titles(&titles, style)) {
+        chapter.title = title;
+    }
+    chapters
+}
+
+/// Clips chapters to the kept part of a trimmed input and shifts them to its start
+///
+/// Chapters that fall entirely outside the kept part are dropped.
+fn trim_chapters(chapters: Vec<Chapter>, trim: Option<&TrimSpec>) -> Vec<Chapter> {
+    let Some(trim) = trim else {
+        return chapters;
+    };
+    let keep_from = trim.start_secs.unwrap_or(0.0);
+    let keep_to = trim.end_secs.unwrap_or(f64::INFINITY);
+    chapters.into_iter()
+        .filter_map(|chapter| {
+            let start_secs = chapter.start_secs.max(keep_from);
+            let end_secs = chapter.end_secs.min(keep_to);
+            (end_secs > start_secs).then_some(Chapter {
+                title: chapter.title,
+                start_secs: start_secs - keep_from,
+                end_secs: end_secs - keep_from,
+            })
+        })
+        .collect()
+}
+
+/// Chapters from an explicit list, checked to cover the book without gaps or overlaps
+///
+/// `files` are the inputs as given, so input indices match the request;
+/// invalid inputs take no time. Errors name the offending chapter's index.
+pub fn chapters_from_specs(files: &[AudioFile], specs: &[ChapterSpec]) -> Result<Vec<Chapter>> {
+    if specs.is_empty() {
+        return Err(AppError::InvalidInput("Chapter list is empty".to_string()));
+    }
+    let durations: Vec<f64> = files.iter()
+        .map(|file| if file.is_valid { file.duration.unwrap_or(0.0).max(0.0) } else { 0.0 })
+        .collect();
+    let total: f64 = durations.iter().sum();
+    let bounds = specs.iter().enumerate()
+        .map(|(index, spec)| spec_bounds(index, spec, &durations, total))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut chapters = Vec::with_capacity(specs.len());
+    let mut previous_end = 0.0;
+    for (index, (spec, &(start, end))) in specs.iter().zip(&bounds).enumerate() {
+        if start > previous_end + CHAPTER_SPEC_TOLERANCE_SECS {
+            return Err(AppError::InvalidInput(format!(
+                "Chapter at index {index} starts at {start:.3}s, leaving a gap after the previous chapter ends at {previous_end:.3}s"
+            )));
+        }
+        if start < previous_end - CHAPTER_SPEC_TOLERANCE_SECS {
+            return Err(AppError::InvalidInput(format!(
+                "Chapter at index {index} starts at {start:.3}s, overlapping the previous chapter that ends at {previous_end:.3}s"
+            )));
+        }
+        let end = end.unwrap_or_else(|| bounds.get(index + 1).map_or(total, |&(next_start, _)| next_start));
+        if end - start < CHAPTER_SPEC_TOLERANCE_SECS {
+            return Err(AppError::InvalidInput(format!(
+                "Chapter at index {index} is empty: it starts at {start:.3}s and ends at {end:.3}s"
+            )));
+        }
+        chapters.push(Chapter { title: spec.title.clone(), start_secs: start, end_secs: end });
+        previous_end = end;
+    }
+    if previous_end < total - CHAPTER_SPEC_TOLERANCE_SECS {
+        return Err(AppError::InvalidInput(format!(
+            "Chapter at index {} ends at {previous_end:.3}s, before the end of the book at {total:.3}s",
+            specs.len() - 1
+        )));
+    }
+    Ok(chapters)
+}
+
+/// Start of the chapter at `index`, and its end when it spans inputs
+fn spec_bounds(index: usize, spec: &ChapterSpec, durations: &[f64], total: f64) -> Result<(f64, Option<f64>)> {
+    let invalid = |reason: String| AppError::InvalidInput(format!("Chapter at index {index}: {reason}"));
+    if spec.title.trim().is_empty() {
+        return Err(invalid("title is empty".to_string()));
+    }
+    match (spec.start_secs, spec.input_indices.as_deref()) {
+        (Some(start), None) => {
+            if !start.is_finite() || start < 0.0 || start >= total {
+                return Err(invalid(format!("start {start}s is outside the book (0 to {total:.3}s)")));
+            }
+            Ok((start, None))
+        }
+        (None, Some(indices)) => {
+            let (Some(&first), Some(&last)) = (indices.first(), indices.last()) else {
+                return Err(invalid("input list is empty".to_string()));
+            };
+            if indices.windows(2).any(|pair| pair[1] != pair[0] + 1) {
+                return Err(invalid(format!("inputs {indices:?} are not consecutive and in order")));
+            }
+            if last >= durations.len() {
+                return Err(invalid(format!("input {last} does not exist ({} inputs)", durations.len())));
+            }
+            let start: f64 = durations[..first].iter().sum();
+            Ok((start, Some(start + durations[first..=last].iter().sum::<f64>())))
+        }
+        _ => Err(invalid("set exactly one of startSecs and inputIndices".to_string())),
+    }
+}
+
+/// FFmpeg metadata file (`;FFMETADATA1`) declaring `chapters` in milliseconds
+pub fn ffmetadata(chapters: &[Chapter]) -> String {
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for chapter in chapters.iter().map(ChapterInfo::from) {
+        metadata.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            chapter.start_ms,
+            chapter.end_ms,
+            escape_ffmetadata(&chapter.title),
+        ));
+    }
+    metadata
+}
+
+/// Backslash-escapes the characters FFmpeg metadata files treat specially
+fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn chapters_for_sources(sources: &[ChapterSource], options: &ChapterOptions) -> Result<Vec<Chapter>> {
+    let mut chapters = match options.mode {
+        ChapterMode::PerFile => per_file_chapters(sources, options.title_template.as_deref().unwrap_or("{title}")),
+        ChapterMode::FixedInterval => {
+            let minutes = options.interval_minutes.unwrap_or(DEFAULT_CHAPTER_INTERVAL_MINUTES);
+            if !minutes.is_finite() || minutes < MIN_CHAPTER_INTERVAL_MINUTES {
+                return Err(AppError::InvalidInput(format!(
+                    "Chapter interval must be at least {MIN_CHAPTER_INTERVAL_MINUTES} minute(s), got: {minutes}"
+                )));
+            }
+            interval_chapters(sources, minutes * 60.0, options.title_template.as_deref().unwrap_or("Chapter {n}"))
+        }
+    };
+    let titles: Vec<String> = chapters.iter().map(|chapter| chapter.title.clone()).collect();
+    for (chapter, title) in chapters.iter_mut().zip(disambiguate_titles(&titles, options.duplicate_style)) {
+        chapter.title = title;
+    }
+    Ok(chapters)
+}
+
+fn per_file_chapters(sources: &[ChapterSource], template: &str) -> Vec<Chapter> {
+    let mut start = 0.0;
+    sources.iter().enumerate()
+        .map(|(index, source)| {
+            let end = start + source.duration;
+            let title = source.title_override.clone()
+                .unwrap_or_else(|| render_title(template, index, sources.len(), source));
+            let chapter = Chapter { title, start_secs: start, end_secs: end };
+            start = end;
+            chapter
+        })
+        .collect()
+}
+
+fn interval_chapters(sources: &[ChapterSource], interval_secs: f64, template: &str) -> Vec<Chapter> {
+    let total: f64 = sources.iter().map(|source| source.duration).sum();
+    let count = (total / interval_secs).ceil() as usize;
+    (0..count)
+        .map(|index| {
+            let start = index as f64 * interval_secs;
+            let source = source_at(sources, start);
+            Chapter {
+                title: source.map_or_else(|| format!("Chapter {}", index + 1), |source| render_title(template, index, count, source)),
+                start_secs: start,
+                end_secs: (start + interval_secs).min(total),
+            }
+        })
+        .collect()
+}
+
+/// The input playing at `position` seconds into the book
+fn source_at(sources: &[ChapterSource], position: f64) -> Option<&ChapterSource> {
+    let mut end = 0.0;
+    sources.iter().find(|source| {
+        end += source.duration;
+        position < end
+    })
+}
+
+/// Fills a title template for the chapter at `index` of `count`
+fn render_title(template: &str, index: usize, count: usize, source: &ChapterSource) -> String {
+    let width = count.to_string().len().max(2);
+    template
+        .replace("{nn}", &format!("{:0width$}", index + 1))
+        .replace("{n}", &(index + 1).to_string())
+        .replace("{title}", &source.title)
+        .replace("{filename}", &source.filename)
+}
+
+/// How repeated chapter titles are made unique
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DuplicateTitleStyle {
+    /// Later repeats become "Title (2)", "Title (3)", ...
+    #[default]
+    SuffixNumber,
+    /// Every repeat, the first included, becomes "03 - Title" using its position
+    PrefixIndex,
+}
+
+/// Makes chapter titles unique, comparing case-insensitively
+///
+/// Titles that are already unique are left alone. Generated suffixes skip
+/// numbers that an existing title already uses.
+pub fn disambiguate_titles(titles: &[String], style: DuplicateTitleStyle) -> Vec<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for title in titles {
+        *counts.entry(title.to_lowercase()).or_default() += 1;
+    }
+
+    match style {
+        DuplicateTitleStyle::SuffixNumber => suffix_duplicates(titles, &counts),
+        DuplicateTitleStyle::PrefixIndex => prefix_duplicates(titles, &counts),
+    }
+}
+
+fn suffix_duplicates(titles: &[String], counts: &HashMap<String, usize>) -> Vec<String> {
+    let mut used: HashSet<String> = counts.keys().cloned().collect();
+    let mut seen: HashSet<String> = HashSet::new();
+
+    titles.iter().map(|title| {
+        let key = title.to_lowercase();
+        if counts[&key] == 1 || seen.insert(key) {
+            return title.clone();
+        }
+        let candidate = (2..)
+            .map(|n| format!("{title} ({n})"))
+            .find(|candidate| !used.contains(&candidate.to_lowercase()))
+            .unwrap_or_else(|| title.clone());
+        used.insert(candidate.to_lowercase());
+        candidate
+    }).collect()
+}
+
+fn prefix_duplicates(titles: &[String], counts: &HashMap<String, usize>) -> Vec<String> {
+    let width = titles.len().to_string().len().max(2);
+    titles.iter().enumerate().map(|(index, title)| {
+        if counts[&title.to_lowercase()] > 1 {
+            format!("{:0width$} - {title}", index + 1)
+        } else {
+            title.clone()
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::file_list::FileListInfo;
+    use std::path::PathBuf;
+
+    fn titles(list: &[&str]) -> Vec<String> {
+        list.iter().map(|t| t.to_string()).collect()
+    }
+
+    fn source(title: &str, duration: f64) -> ChapterSource {
+        ChapterSource {
+            title: title.to_string(),
+            filename: title.to_lowercase().replace(' ', "_"),
+            duration,
+            title_override: None,
+        }
+    }
+
+    fn starts(chapters: &[Chapter]) -> Vec<f64> {
+        chapters.iter().map(|chapter| chapter.start_secs).collect()
+    }
+
+    #[test]
+    fn test_per_file_timestamps_match_file_list_durations() {
+        let mut files = Vec::new();
+        for (name, duration) in [("01 Opening", 600.5), ("02 Middle", 1200.25), ("03 End", 300.0)] {
+            let mut file = AudioFile::new(format!("/missing/{name}.mp3").into());
+            file.duration = Some(duration);
+            file.is_valid = true;
+            files.push(file);
+        }
+        let mut invalid = AudioFile::new("/missing/broken.mp3".into());
+        invalid.duration = Some(999.0);
+        files.insert(1, invalid);
+        let info = FileListInfo {
+            total_duration: 2100.75,
+            total_size: 0.0,
+            valid_count: 3,
+            invalid_count: 1,
+            error_counts: Default::default(),
+            input_stats: None,
+            disc_groups: Vec::new(),
+            files,
+        };
+
+        let chapters = generate_chapters(&info.files, &ChapterOptions::default()).unwrap();
+        assert_eq!(starts(&chapters), [0.0, 600.5, 1800.75]);
+        assert_eq!(chapters.last().unwrap().end_secs, info.total_duration);
+        let titles: Vec<&str> = chapters.iter().map(|chapter| chapter.title.as_str()).collect();
+        assert_eq!(titles, ["01 Opening", "02 Middle", "03 End"]);
+    }
+
+    #[test]
+    fn test_fixed_interval_chapters() {
+        let sources = [source("Disc 1", 900.0), source("Disc 2", 700.0)];
+        let options = ChapterOptions {
+            mode: ChapterMode::FixedInterval,
+            interval_minutes: Some(10.0),
+            title_template: Some("{nn}: {title}".to_string()),
+            ..ChapterOptions::default()
+        };
+        let chapters = chapters_for_sources(&sources, &options).unwrap();
+        assert_eq!(starts(&chapters), [0.0, 600.0, 1200.0]);
+        assert_eq!(chapters[2].end_secs, 1600.0);
+        let titles: Vec<&str> = chapters.iter().map(|chapter| chapter.title.as_str()).collect();
+        assert_eq!(titles, ["01: Disc 1", "02: Disc 1", "03: Disc 2"]);
+
+        let too_short = ChapterOptions { interval_minutes: Some(0.1), ..options };
+        assert!(chapters_for_sources(&sources, &too_short).is_err());
+    }
+
+    #[test]
+    fn test_templates_overrides_and_disambiguation() {
+        let mut sources = vec![source("Introduction", 10.0), source("Introduction", 20.0), source("Part One", 30.0)];
+        sources[2].title_override = Some("Prologue".to_string());
+        let options = ChapterOptions { title_template: Some("{title}".to_string()), ..ChapterOptions::default() };
+        let titles: Vec<String> = chapters_for_sources(&sources, &options).unwrap().into_iter().map(|c| c.title).collect();
+        assert_eq!(titles, ["Introduction", "Introduction (2)", "Prologue"]);
+
+        let options = ChapterOptions { title_template: Some("{n}. {filename}".to_string()), ..ChapterOptions::default() };
+        let titles: Vec<String> = chapters_for_sources(&sources[..2], &options).unwrap().into_iter().map(|c| c.title).collect();
+        assert_eq!(titles, ["1. introduction", "2. introduction"]);
+    }
+
+    #[test]
+    fn test_book_chapters_default_to_filenames() {
+        let files: Vec<AudioFile> = [("/missing/01 Opening.mp3", 61.5), ("/missing/02 Middle.mp3", 30.0)].iter()
+            .map(|(path, duration)| {
+                let mut file = AudioFile::new(PathBuf::from(path));
+                file.duration = Some(*duration);
+                file.is_valid = true;
+                file
+            })
+            .collect();
+        let mut settings = AudioSettings::default();
+        let chapters = book_chapters(&files, &settings).unwrap();
+        let titles: Vec<&str> = chapters.iter().map(|chapter| chapter.title.as_str()).collect();
+        assert_eq!(titles, ["01 Opening", "02 Middle"]);
+        assert_eq!(starts(&chapters), [0.0, 61.5]);
+
+        assert!(book_chapters(&files[..1], &settings).unwrap().is_empty());
+        settings.chapters = Some(ChapterOptions::default());
+        assert_eq!(book_chapters(&files[..1], &settings).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_embedded_chapters_merge_with_per_file_chapters() {
+        let info = |title: &str, start_ms, end_ms| ChapterInfo { title: title.to_string(), start_ms, end_ms };
+        let mut files: Vec<AudioFile> = [("/missing/Intro.mp3", 60.0), ("/missing/02 Episode.mp3", 300.0), ("/missing/03 Outro.mp3", 30.0)].iter()
+            .map(|(path, duration)| {
+                let mut file = AudioFile::new(PathBuf::from(path));
+                file.duration = Some(*duration);
+                file.is_valid = true;
+                file
+            })
+            .collect();
+        // Chapters from CHAP frames, the first starting after a short sting
+        files[1].chapters = vec![info("Cold Open", 2_000, 90_000), info("Intro", 90_000, 200_000), info("Interview", 200_000, 300_000)];
+
+        let chapters = book_chapters(&files, &AudioSettings::default()).unwrap();
+        let titles: Vec<&str> = chapters.iter().map(|chapter| chapter.title.as_str()).collect();
+        assert_eq!(titles, ["Intro", "Cold Open", "Intro (2)", "Interview", "03 Outro"]);
+        assert_eq!(starts(&chapters), [0.0, 60.0, 150.0, 260.0, 360.0]);
+        assert_eq!(chapters[3].end_secs, 360.0);
+
+        // A trim moves the embedded chapters with the kept part
+        files[1].options.trim = Some(TrimSpec { start_secs: Some(100.0), end_secs: None });
+        files[1].duration = Some(200.0);
+        let chapters = book_chapters(&files, &AudioSettings::default()).unwrap();
+        assert_eq!(starts(&chapters), [0.0, 60.0, 160.0, 260.0]);
+
+        // Explicit chapter options are honoured as given
+        let settings = AudioSettings { chapters: Some(ChapterOptions::default()), ..AudioSettings::default() };
+        assert_eq!(book_chapters(&files, &settings).unwrap().len(), 3);
+
+        // A single input keeps its embedded chapters without probing
+        files[1].options.trim = None;
+        let single = book_chapters(&files[1..2], &AudioSettings::default()).unwrap();
+        assert_eq!(starts(&single), [2.0, 90.0, 200.0]);
+    }
+
+    #[test]
+    fn test_trim_chapters_clips_and_shifts() {
+        let chapter = |title: &str, start_secs, end_secs| Chapter { title: title.to_string(), start_secs, end_secs };
+        let chapters = vec![chapter("Credits", 0.0, 10.0), chapter("One", 10.0, 70.0), chapter("Two", 70.0, 130.0)];
+        assert_eq!(trim_chapters(chapters.clone(), None), chapters);
+
+        let trim = TrimSpec { start_secs: Some(10.0), end_secs: Some(100.0) };
+        assert_eq!(trim_chapters(chapters.clone(), Some(&trim)), [chapter("One", 0.0, 60.0), chapter("Two", 60.0, 90.0)]);
+        let trim = TrimSpec { start_secs: Some(5.0), end_secs: None };
+        assert_eq!(trim_chapters(chapters, Some(&trim))[0], chapter("Credits", 0.0, 5.0));
+    }
+
+    #[test]
+    fn test_title_sources_with_and_without_title_tags() {
+        let files: Vec<AudioFile> = [("/missing/12_the_return.mp3", Some("Chapter 12 – The Return")), ("/missing/13_departure.mp3", None)]
+            .iter()
+            .map(|(path, title)| {
+                let mut file = AudioFile::new(PathBuf::from(path));
+                file.duration = Some(60.0);
+                file.is_valid = true;
+                file.title = title.map(str::to_string);
+                file
+            })
+            .collect();
+        let titles = |settings: &AudioSettings| -> Vec<String> {
+            book_chapters(&files, settings).unwrap().into_iter().map(|chapter| chapter.title).collect()
+        };
+
+        let mut settings = AudioSettings::default();
+        assert_eq!(settings.chapter_title_source, ChapterTitleSource::TagTitle);
+        assert_eq!(titles(&settings), ["Chapter 12 – The Return", "13_departure"]);
+        settings.chapter_title_source = ChapterTitleSource::Filename;
+        assert_eq!(titles(&settings), ["12_the_return", "13_departure"]);
+        settings.chapter_title_source = ChapterTitleSource::Numbered;
+        assert_eq!(titles(&settings), ["Chapter 01", "Chapter 02"]);
+
+        settings.chapters = Some(ChapterOptions::default());
+        assert_eq!(titles(&settings), ["Chapter 01", "Chapter 02"]);
+        settings.chapters = Some(ChapterOptions { title_template: Some("{title}".to_string()), ..ChapterOptions::default() });
+        assert_eq!(titles(&settings), ["Chapter 12 – The Return", "13_departure"]);
+    }
+
+    #[test]
+    fn test_chapter_specs_cover_the_book() {
+        let files: Vec<AudioFile> = (0..6)
+            .map(|index| {
+                let mut file = AudioFile::new(PathBuf::from(format!("/missing/{index:03}.mp3")));
+                file.duration = Some(10.0);
+                file.is_valid = index != 4;
+                file
+            })
+            .collect();
+        let by_inputs = |title: &str, indices: &[usize]| ChapterSpec {
+            title: title.to_string(),
+            start_secs: None,
+            input_indices: Some(indices.to_vec()),
+        };
+        let at = |title: &str, start: f64| ChapterSpec { title: title.to_string(), start_secs: Some(start), input_indices: None };
+
+        let chapters = chapters_from_specs(&files, &[by_inputs("One", &[0, 1]), at("Two", 20.0), by_inputs("Three", &[4, 5])]).unwrap();
+        assert_eq!(starts(&chapters), [0.0, 20.0, 40.0]);
+        assert_eq!(chapters[1].end_secs, 40.0);
+        assert_eq!(chapters[2].end_secs, 50.0);
+        assert_eq!(chapters[2].title, "Three");
+
+        let error = |specs: &[ChapterSpec]| chapters_from_specs(&files, specs).unwrap_err().to_string();
+        assert!(error(&[]).contains("empty"));
+        assert!(error(&[by_inputs("One", &[0, 1]), by_inputs("Two", &[3, 4, 5])]).contains("index 1 starts at 30.000s, leaving a gap"));
+        assert!(error(&[by_inputs("One", &[0, 1]), at("Two", 15.0)]).contains("index 1 starts at 15.000s, overlapping"));
+        assert!(error(&[at("One", 0.0), by_inputs("Two", &[0, 1, 2])]).contains("index 0 is empty"));
+        assert!(error(&[by_inputs("One", &[0, 1, 2])]).contains("index 0 ends at 30.000s, before the end"));
+        assert!(error(&[at("One", 5.0)]).contains("index 0 starts at 5.000s"));
+        assert!(error(&[at("One", 0.0), by_inputs("Two", &[2, 1])]).contains("index 1: inputs [2, 1] are not consecutive"));
+        assert!(error(&[at("One", 0.0), by_inputs("Two", &[6])]).contains("index 1: input 6 does not exist"));
+        assert!(error(&[at("One", 0.0), at("", 10.0)]).contains("index 1: title is empty"));
+        let both = ChapterSpec { start_secs: Some(0.0), ..by_inputs("One", &[0]) };
+        assert!(error(&[both]).contains("index 0: set exactly one"));
+    }
+
+    #[test]
+    fn test_ffmetadata_chapters() {
+        let chapters = [
+            Chapter { title: "Part 1; Arrival".to_string(), start_secs: 0.0, end_secs: 61.5 },
+            Chapter { title: "x=y #2".to_string(), start_secs: 61.5, end_secs: 91.0004 },
+        ];
+        assert_eq!(
+            ffmetadata(&chapters),
+            ";FFMETADATA1\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=61500\ntitle=Part 1\\; Arrival\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=61500\nEND=91000\ntitle=x\\=y \\#2\n"
+        );
+    }
+
+    #[test]
+    fn test_unique_titles_unchanged() {
+        let input = titles(&["Prologue", "Chapter 1", "Epilogue"]);
+        assert_eq!(disambiguate_titles(&input, DuplicateTitleStyle::SuffixNumber), input);
+        assert_eq!(disambiguate_titles(&input, DuplicateTitleStyle::PrefixIndex), input);
+    }
+
+    #[test]
+    fn test_suffix_numbers_case_insensitive_repeats() {
+        let input = titles(&["Introduction", "Part 1", "INTRODUCTION", "introduction"]);
+        assert_eq!(
+            disambiguate_titles(&input, DuplicateTitleStyle::SuffixNumber),
+            titles(&["Introduction", "Part 1", "INTRODUCTION (2)", "introduction (3)"])
+        );
+    }
+
+    #[test]
+    fn test_suffix_skips_already_numbered_titles() {
+        let input = titles(&["Introduction", "Introduction (2)", "Introduction"]);
+        assert_eq!(
+            disambiguate_titles(&input, DuplicateTitleStyle::SuffixNumber),
+            titles(&["Introduction", "Introduction (2)", "Introduction (3)"])
+        );
+    }
+
+    #[test]
+    fn test_prefix_index_marks_every_repeat() {
+        let input = titles(&["Introduction", "Part 1", "introduction"]);
+        assert_eq!(
+            disambiguate_titles(&input, DuplicateTitleStyle::PrefixIndex),
+            titles(&["01 - Introduction", "Part 1", "03 - introduction"])
+        );
+    }
+
+    #[test]
+    fn test_prefix_width_follows_chapter_count() {
+        let mut input = vec!["Track".to_string(); 2];
+        input.extend((0..98).map(|i| format!("Chapter {i}")));
+        let result = disambiguate_titles(&input, DuplicateTitleStyle::PrefixIndex);
+        assert_eq!(result[0], "001 - Track");
+        assert_eq!(result[1], "002 - Track");
+    }
+}
@@ -48,6 +48,9 @@ pub const PROCESS_KILL_RETRY_DELAY_MS: u64 = 100;
 pub const MAX_CLEANUP_RETRIES: u32 = 3;
 
 // Time calculation multipliers
+/// Shortest gap between two FFmpeg progress lines written to the log
+pub const PROGRESS_LOG_SAMPLE_INTERVAL_MS: u64 = 1000;
+
 /// Progress percentage calculation range (maps file progress to UI progress)
 pub const PROGRESS_RANGE_MULTIPLIER: f64 = 70.0;
 
@@ -66,15 +69,27 @@ pub const SECONDS_PER_MINUTE: f64 = 60.0;
 /// FFmpeg concat demuxer format
 pub const FFMPEG_CONCAT_FORMAT: &str = "concat";
 
+/// FFmpeg metadata file format, used for chapter marks
+pub const FFMPEG_METADATA_FORMAT: &str = "ffmetadata";
+
 /// FFmpeg safe mode for concat demuxer
 pub const FFMPEG_CONCAT_SAFE_MODE: &str = "0";
 
 /// FFmpeg audio codec for AAC encoding
 pub const FFMPEG_AUDIO_CODEC: &str = "libfdk_aac";
 
+/// AAC profile of the encoded audio; libfdk_aac's default, as no profile is passed
+pub const FFMPEG_AUDIO_PROFILE: &str = "aac_low";
+
 /// FFmpeg progress output pipe
 pub const FFMPEG_PROGRESS_PIPE: &str = "pipe:2";
 
+/// FFmpeg muxer for M4B files (MP4 with the iTunes brand)
+pub const FFMPEG_M4B_MUXER: &str = "ipod";
+
+/// FFmpeg flag value keeping version strings and timestamps out of the output
+pub const FFMPEG_BITEXACT_FLAGS: &str = "+bitexact";
+
 // Default values
 /// Default bitrate in kbps
 pub const DEFAULT_BITRATE: u32 = 64;
@@ -82,10 +97,259 @@ pub const DEFAULT_BITRATE: u32 = 64;
 /// Default sample rate in Hz
 pub const DEFAULT_SAMPLE_RATE: u32 = 22050;
 
+/// Default factor by which the target bitrate may exceed the best source bitrate
+pub const DEFAULT_BITRATE_EXCESS_FACTOR: f32 = 1.5;
+
+/// Largest per-input gain adjustment in dB, either direction
+pub const MAX_INPUT_GAIN_DB: f32 = 24.0;
+
 // File extensions
 /// Default output file extension
 pub const DEFAULT_OUTPUT_EXTENSION: &str = "m4b";
 
+/// Output extension accepted with `allow_m4a_extension`, for players that reject `.m4b`
+pub const M4A_OUTPUT_EXTENSION: &str = "m4a";
+
+// Waveform peak extraction
+/// Maximum number of peak buckets returned for a waveform preview
+pub const MAX_PEAK_BUCKETS: u32 = 2000;
+
+/// Sample rate FFmpeg decodes to for peak extraction in Hz
+pub const PEAK_SAMPLE_RATE: u32 = 8000;
+
+/// Size of each read from FFmpeg's PCM stream in bytes
+pub const PEAK_READ_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Maximum number of cached peak results kept in memory
+pub const PEAK_CACHE_CAPACITY: usize = 256;
+
+// Segmented merging
+/// Default input count above which files are merged in segments
+pub const DEFAULT_SEGMENT_THRESHOLD: usize = 500;
+
+/// Smallest allowed segment threshold
+pub const MIN_SEGMENT_THRESHOLD: usize = 2;
+
+/// Largest accepted concat input probe size in MB
+pub const MAX_PROBE_SIZE_MB: u32 = 1024;
+
+/// Largest accepted concat input analyze duration in seconds
+pub const MAX_ANALYZE_DURATION_SECS: u32 = 600;
+
+/// Input start times closer to zero than this carry no codec delay to trim
+pub const CODEC_DELAY_EPSILON_SECS: f64 = 0.0005;
+
+/// Extension of intermediate segment outputs
+pub const SEGMENT_OUTPUT_EXTENSION: &str = "m4a";
+
+/// Filename of the concat list joining the segment outputs
+pub const SEGMENT_JOIN_LIST_FILENAME: &str = "segments.txt";
+
+// Output filenames
+/// Longest filename most filesystems accept, in bytes
+pub const MAX_FILENAME_BYTES: usize = 255;
+
+// Session timelines
+/// Number of recent session timelines kept queryable
+pub const MAX_TRACKED_TIMELINES: usize = 16;
+
+/// Number of warnings a session keeps for progress snapshots
+pub const MAX_SNAPSHOT_WARNINGS: usize = 50;
+
+// Progress telemetry
+/// Shortest gap between two telemetry points in milliseconds
+pub const TELEMETRY_INTERVAL_MS: u64 = 2_000;
+
+/// Number of telemetry points a session keeps, an hour at the default interval
+pub const TELEMETRY_MAX_POINTS: usize = 1_800;
+
+// Dual-mono detection
+/// Length of the decoded window compared per file in seconds
+pub const DUAL_MONO_WINDOW_SECONDS: u32 = 30;
+
+/// Furthest into a file the window starts, to skip silent intros, in seconds
+pub const DUAL_MONO_MAX_SKIP_SECONDS: f64 = 60.0;
+
+/// Sample rate the window is decoded at in Hz
+pub const DUAL_MONO_SAMPLE_RATE: u32 = 8000;
+
+/// Minimum inter-channel correlation for dual-mono
+pub const DUAL_MONO_CORRELATION_THRESHOLD: f64 = 0.999;
+
+/// Maximum L-R energy relative to total energy for dual-mono
+pub const DUAL_MONO_DIFFERENCE_THRESHOLD: f64 = 0.001;
+
+// Accurate MP3 durations
+/// Bytes read after the ID3v2 tag when looking for a Xing/Info or VBRI header
+pub const MP3_HEADER_SCAN_BYTES: usize = 8 * 1024;
+
+/// Maximum number of decoded MP3 durations kept in memory
+pub const MEASURED_DURATION_CACHE_CAPACITY: usize = 1024;
+
+/// Share of the MP3 stream that may fail to parse as frames before a frame count is distrusted
+pub const MP3_MAX_UNSYNCED_FRACTION: f64 = 0.1;
+
+// Series tags
+/// Freeform MP4 atom holding the series name, as Audiobookshelf and Plex read it
+pub const MP4_SERIES_ATOM: &str = "----:com.apple.iTunes:SERIES";
+
+/// Freeform MP4 atom holding the position within the series
+pub const MP4_SERIES_PART_ATOM: &str = "----:com.apple.iTunes:SERIES-PART";
+
+// Identifier tags
+/// Freeform MP4 atom holding the ISBN, as Audiobookshelf reads it
+pub const MP4_ISBN_ATOM: &str = "----:com.apple.iTunes:ISBN";
+
+/// Freeform MP4 atom holding the Audible ASIN
+pub const MP4_ASIN_ATOM: &str = "----:com.apple.iTunes:ASIN";
+
+/// Tag key holding the ISBN in formats with free-form keys (Vorbis, APE)
+pub const ISBN_TAG_KEY: &str = "ISBN";
+
+/// Tag key holding the ASIN in formats with free-form keys (Vorbis, APE)
+pub const ASIN_TAG_KEY: &str = "ASIN";
+
+// Encoder provenance
+/// Application name the encoder tag starts with
+pub const PROVENANCE_TOOL_NAME: &str = "Audiobook Boss";
+
+// Apple media kind
+/// MP4 atom holding the media kind Apple players sort a file under
+pub const MP4_MEDIA_KIND_ATOM: [u8; 4] = *b"stik";
+
+/// `stik` value for an audiobook; without it Apple Books lists an M4B as music
+pub const MP4_MEDIA_KIND_AUDIOBOOK: u8 = 2;
+
+/// MP4 data type code of a big-endian signed integer
+pub const MP4_SIGNED_INT_CODE: u32 = 21;
+
+/// MP4 flag atom marking the file as part of a gapless album
+pub const MP4_GAPLESS_ATOM: [u8; 4] = *b"pgap";
+
+// Cover art quality
+/// Minimum cover width and height in pixels
+pub const COVER_MIN_DIMENSION: u32 = 500;
+
+/// Largest tolerated deviation from a square aspect ratio (0.05 = 5%)
+pub const COVER_MAX_ASPECT_DEVIATION: f64 = 0.05;
+
+/// Largest cover image size in bytes before warning
+pub const COVER_MAX_BYTES: usize = 2 * 1024 * 1024;
+
+/// Width and height of a generated placeholder cover in pixels
+pub const PLACEHOLDER_COVER_SIZE: u32 = 1400;
+
+/// Space left clear around a placeholder cover's text in pixels
+pub const PLACEHOLDER_COVER_MARGIN: u32 = 120;
+
+/// JPEG quality (1-100) of a generated placeholder cover
+pub const PLACEHOLDER_COVER_JPEG_QUALITY: u8 = 90;
+
+/// FFmpeg `-q:v` for covers converted to JPEG (2-31, lower is better)
+pub const COVER_JPEG_QSCALE: &str = "2";
+
+/// Largest cover art embedded by processing, unless the settings set another limit
+pub const MAX_EMBEDDED_COVER_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Longest sides tried, largest first, when shrinking a cover under the limit
+pub const COVER_DOWNSCALE_SIDES: [u32; 5] = [3000, 2000, 1400, 1000, 600];
+
+// Processing history
+/// File in the app data directory holding one JSON record per run
+pub const PROCESSING_HISTORY_FILENAME: &str = "processing-history.jsonl";
+
+// Path access policy
+/// File in the app data directory holding the path policy preference
+pub const PATH_POLICY_CONFIG_FILENAME: &str = "path-policy.json";
+
+/// Version of the plan fingerprint; bump when its fields change
+pub const PLAN_HASH_VERSION: u32 = 1;
+
+/// Extension appended to an output's filename for its plan hash sidecar
+pub const PLAN_HASH_SIDECAR_EXTENSION: &str = "planhash";
+
+// Chapters
+/// Default chapter length in fixed-interval mode
+pub const DEFAULT_CHAPTER_INTERVAL_MINUTES: f64 = 10.0;
+
+/// Shortest allowed chapter length in fixed-interval mode
+pub const MIN_CHAPTER_INTERVAL_MINUTES: f64 = 1.0;
+
+/// Slack allowed where one chapter of an explicit list meets the next
+pub const CHAPTER_SPEC_TOLERANCE_SECS: f64 = 0.001;
+
+// Publishing the output
+/// Chunk size for copying the output to another volume
+pub const PUBLISH_COPY_CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+/// Extension appended to the destination filename while the copy is in progress
+pub const PUBLISH_STAGING_EXTENSION: &str = "partial";
+
+/// Network filesystems the output is copied to once instead of renamed into place
+pub const NETWORK_FILESYSTEMS: [&str; 10] = [
+    "cifs", "smb2", "smb3", "smbfs", "nfs", "nfs4", "afpfs", "webdav", "davfs", "fuse.sshfs",
+];
+
+// Post-processing hook
+/// Time the post-processing hook may run before it is killed
+pub const POST_HOOK_TIMEOUT_SECS: u64 = 60;
+
+/// Interval between checks for the post-processing hook exiting
+pub const POST_HOOK_POLL_INTERVAL_MS: u64 = 50;
+
+/// Bytes of the hook's stdout kept in the history record
+pub const POST_HOOK_STDOUT_TAIL_BYTES: usize = 2048;
+
+// CUE sheet export
+/// Extension of the CUE sheet written next to the output
+pub const CUE_EXTENSION: &str = "cue";
+
+/// File type declared for the output in the CUE sheet
+pub const CUE_FILE_TYPE: &str = "MP4";
+
+/// CUE time frames per second (`MM:SS:FF`)
+pub const CUE_FRAMES_PER_SECOND: u64 = 75;
+
+// Metadata sidecar export
+/// Extension of the JSON metadata sidecar
+pub const METADATA_JSON_EXTENSION: &str = "json";
+
+/// Extension of the OPF metadata sidecar
+pub const METADATA_OPF_EXTENSION: &str = "opf";
+
+// Self test
+/// Length of each synthesized self-test input in seconds
+pub const SELF_TEST_TONE_SECS: f64 = 3.0;
+
+/// Tone frequency of each self-test input in Hz, one input per entry
+pub const SELF_TEST_TONE_FREQUENCIES: [u32; 2] = [440, 660];
+
+/// Width and height of the generated self-test cover in pixels
+pub const SELF_TEST_COVER_SIZE: u32 = 600;
+
+/// Largest accepted difference between the self-test output and input durations in seconds
+pub const SELF_TEST_DURATION_TOLERANCE_SECS: f64 = 0.5;
+
+/// Title written to the self-test book and checked on the way back
+pub const SELF_TEST_TITLE: &str = "Audiobook Boss Self Test";
+
+/// Author written to the self-test book and checked on the way back
+pub const SELF_TEST_AUTHOR: &str = "Audiobook Boss";
+
+// Large outputs
+/// Output size above which MP4 needs 64-bit offsets and FAT32 can't store the file (4 GiB)
+pub const LARGE_OUTPUT_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Filesystems warned about when the projected output is over `LARGE_OUTPUT_BYTES`
+pub const SIZE_LIMITED_FILESYSTEMS: [&str; 5] = ["vfat", "fat", "fat32", "msdos", "exfat"];
+
+/// Largest shortfall of the decoded length of a large output against its container duration in seconds
+pub const LARGE_OUTPUT_DURATION_TOLERANCE_SECS: f64 = 1.0;
+
+// Queue disk space forecast
+/// Free space kept clear on an output volume when forecasting queued jobs (1 GiB)
+pub const QUEUE_DISK_RESERVE_BYTES: u64 = 1024 * 1024 * 1024;
+
 // Temporary file names
 /// Temporary concat list filename
 pub const TEMP_CONCAT_FILENAME: &str = "concat.txt";
@@ -93,6 +357,15 @@ pub const TEMP_CONCAT_FILENAME: &str = "concat.txt";
 /// Temporary merged output filename
 pub const TEMP_MERGED_FILENAME: &str = "merged.m4b";
 
+/// Temporary run manifest filename
+pub const TEMP_MANIFEST_FILENAME: &str = "manifest.json";
+
+/// Temporary FFmpeg metadata file declaring the book's chapters
+pub const TEMP_CHAPTERS_FILENAME: &str = "chapters.txt";
+
+/// Marker recording which process owns a session temp directory
+pub const TEMP_OWNER_MARKER_FILENAME: &str = "owner.json";
+
 /// Temporary directory name
 pub const TEMP_DIR_NAME: &str = "audiobook-boss";
 
@@ -141,13 +414,15 @@ pub const STEREO_CHANNELS: u8 = 2;
 
 // Progress calculation weights
 /// Weight for merging stage in progress calculations
-#[allow(dead_code)]
 pub const PROGRESS_MERGING_WEIGHT: f32 = 15.0;
 
 /// Weight for metadata writing in progress calculations
 #[allow(dead_code)]
 pub const PROGRESS_METADATA_WEIGHT: f32 = 5.0;
 
+/// Weight of the newest FFmpeg speed reading in the smoothed speed (0-1)
+pub const PROGRESS_SPEED_SMOOTHING: f64 = 0.3;
+
 // Image format validation
 /// JPEG file header signature
 #[allow(dead_code)]
@@ -167,4 +442,98 @@ pub const MIN_PNG_SIZE: usize = 8;
 
 /// Minimum WebP file size in bytes
 #[allow(dead_code)]
-pub const MIN_WEBP_SIZE: usize = 12;
\ No newline at end of file
+pub const MIN_WEBP_SIZE: usize = 12;
+// Encode speed calibration
+/// Seconds of the first input encoded to measure encoder speed
+pub const CALIBRATION_SAMPLE_SECONDS: f64 = 15.0;
+
+/// Interval between cancellation checks while the calibration encode runs
+pub const CALIBRATION_POLL_INTERVAL_MS: u64 = 50;
+
+/// Realtime factor at or above which encoding is classed as fast
+pub const ENCODE_FAST_SPEED_FACTOR: f64 = 20.0;
+
+/// Realtime factor below which encoding is classed as slow
+pub const ENCODE_SLOW_SPEED_FACTOR: f64 = 5.0;
+
+// App shutdown
+/// Time active sessions get to stop before the app exits anyway
+pub const SHUTDOWN_TIMEOUT_SECS: u64 = 5;
+
+/// Interval between checks for sessions still running during shutdown
+pub const SHUTDOWN_POLL_INTERVAL_MS: u64 = 100;
+
+// Description import
+/// Largest description file accepted for import in bytes
+pub const DESCRIPTION_FILE_MAX_BYTES: u64 = 64 * 1024;
+
+/// Characters of a description kept in the MP4 comment (©cmt); players
+/// truncate it around 255 bytes, so the full text goes to the long description (ldes)
+pub const DESCRIPTION_MP4_COMMENT_MAX_CHARS: usize = 255;
+
+/// Characters of a description kept in the ID3v2 comment (COMM); the full
+/// text goes to an unsynchronized lyrics (USLT) frame
+pub const DESCRIPTION_ID3V2_COMMENT_MAX_CHARS: usize = 255;
+
+/// Longest description a chapter list may be appended up to; Apple Books
+/// and most podcast players cut long descriptions at 4000 characters
+pub const DESCRIPTION_WITH_CHAPTERS_MAX_CHARS: usize = 4000;
+
+// Input errors
+/// Extensions of audiobook formats that are always DRM-protected (Audible, FairPlay)
+pub const DRM_EXTENSIONS: &[&str] = &["aa", "aax", "aaxc", "m4p"];
+
+/// MP4 sample entry codec tags of encrypted audio, as reported by ffprobe
+pub const DRM_CODEC_TAGS: &[&str] = &["drms", "drmi", "aavd", "enca"];
+
+// Input stability
+/// Wait between the two size/mtime checks that detect files still being written
+pub const FILE_STABILITY_CHECK_MS: u64 = 300;
+
+// Per-file analysis timeout
+/// Default time one file's probe may take before it is marked `timeout`
+pub const ANALYSIS_TIMEOUT_SECS: u64 = 10;
+
+/// Interval between checks for a time-limited ffprobe exiting
+pub const PROBE_POLL_INTERVAL_MS: u64 = 20;
+
+// Multi-disc ordering
+/// Words that mark a disc number in a folder or file name ("CD2", "Disc 01")
+pub const DEFAULT_DISC_MARKERS: [&str; 3] = ["cd", "disc", "disk"];
+
+/// Characters allowed between a disc marker and its number
+pub const DISC_NUMBER_SEPARATORS: [char; 5] = [' ', '_', '-', '.', '#'];
+
+// Metadata normalization
+/// Earliest publication year accepted for a metadata write
+pub const MIN_METADATA_YEAR: u32 = 1400;
+/// Latest publication year accepted for a metadata write
+pub const MAX_METADATA_YEAR: u32 = 2100;
+
+// Metadata write retry
+/// Attempts at the final tag write before the run completes with metadata pending
+pub const METADATA_WRITE_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry of a busy tag write; each later retry waits longer
+pub const METADATA_RETRY_DELAY_MS: u64 = 250;
+
+// Background verification
+/// Books verified at the same time; kept low so verification stays in the background
+pub const VERIFICATION_MAX_CONCURRENT: usize = 1;
+
+/// Interval between checks for a running encode before a verification starts
+pub const VERIFICATION_BUSY_POLL_MS: u64 = 1000;
+
+// File hashing
+/// Bytes read per chunk when hashing a file; cancellation is checked between chunks
+pub const HASH_CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+// Silence-based chapter suggestions
+/// Most chapter starts suggested from silences; the longest silences are kept
+pub const SILENCE_MAX_SUGGESTIONS: usize = 500;
+
+/// Quietest noise floor accepted for silence detection in dB
+pub const SILENCE_MIN_THRESHOLD_DB: f64 = -90.0;
+
+/// Silences this close to the start or end of the file don't suggest a chapter
+pub const SILENCE_EDGE_IGNORE_SECS: f64 = 1.0;
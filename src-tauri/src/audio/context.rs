@@ -5,10 +5,11 @@
 //! and improving code organization.
 
 use super::{AudioSettings, ProcessingStage};
+use super::preferences::PreferenceSnapshot;
 use super::session::ProcessingSession;
 use crate::errors::Result;
+use super::progress_sink::ProgressSink;
 use std::sync::Arc;
-use tauri::Window;
 
 /// Groups core processing dependencies together
 /// 
@@ -16,36 +17,49 @@ use tauri::Window;
 /// reducing the need to pass multiple parameters through function calls.
 #[derive(Clone, Debug)]
 pub struct ProcessingContext {
-    /// Tauri window for event emission
-    pub window: Window,
+    /// Destination for progress and completion events
+    pub sink: Arc<dyn ProgressSink>,
     /// Processing session with state management
     pub session: Arc<ProcessingSession>,
     /// Audio processing settings
     pub settings: AudioSettings,
+    /// Preferences the run reads, so changing them doesn't affect it
+    pub preferences: PreferenceSnapshot,
 }
 
 impl ProcessingContext {
     /// Creates a new ProcessingContext with the given components
-    pub fn new(window: Window, session: Arc<ProcessingSession>, settings: AudioSettings) -> Self {
+    ///
+    /// The preferences are captured as set now; see `with_preferences`.
+    pub fn new(sink: Arc<dyn ProgressSink>, session: Arc<ProcessingSession>, settings: AudioSettings) -> Self {
         Self {
-            window,
+            sink,
             session,
             settings,
+            preferences: PreferenceSnapshot::current(),
         }
     }
+
+    /// Runs with preferences captured earlier, such as when a job was queued
+    pub fn with_preferences(mut self, preferences: PreferenceSnapshot) -> Self {
+        self.preferences = preferences;
+        self
+    }
+
+    /// Creates a ProcessingContext that emits to a Tauri window
+    #[cfg(feature = "gui")]
+    pub fn for_window(window: tauri::Window, session: Arc<ProcessingSession>, settings: AudioSettings) -> Self {
+        Self::new(Arc::new(window), session, settings)
+    }
     
     /// Emits an event to the frontend
     pub fn emit_event<S: serde::Serialize + Clone>(&self, event_name: &str, payload: S) -> Result<()> {
-        use tauri::Emitter;
-        self.window
-            .emit(event_name, payload)
+        self.sink
+            .emit(event_name, &payload)
             .map_err(|e| crate::errors::AppError::General(format!(
-                "Failed to emit event '{}' for session {}: {}",
-                event_name,
-                self.session.id(),
-                e
-            )))?;
-        Ok(())
+                "{e} (session {})",
+                self.session.id()
+            )))
     }
     
     /// Checks if the current processing has been cancelled
@@ -53,6 +67,16 @@ impl ProcessingContext {
         self.session.is_cancelled()
     }
     
+    /// Checks for a cancellation or soft stop between units of work
+    pub fn check_unit_boundary(&self) -> crate::errors::Result<()> {
+        self.session.check_unit_boundary()
+    }
+    
+    /// Error to return once a cancellation has been noticed
+    pub fn cancellation_error(&self) -> crate::errors::AppError {
+        self.session.cancellation_error()
+    }
+    
     /// Checks if processing is currently active
     pub fn is_processing(&self) -> bool {
         self.session.is_processing()
@@ -86,7 +110,7 @@ impl ProcessingContext {
 
 /// Builder pattern for ProcessingContext
 pub struct ProcessingContextBuilder {
-    window: Option<Window>,
+    sink: Option<Arc<dyn ProgressSink>>,
     session: Option<Arc<ProcessingSession>>,
     settings: Option<AudioSettings>,
 }
@@ -95,17 +119,23 @@ impl ProcessingContextBuilder {
     /// Creates a new builder instance
     pub fn new() -> Self {
         Self {
-            window: None,
+            sink: None,
             session: None,
             settings: None,
         }
     }
     
-    /// Sets the Tauri window
-    pub fn window(mut self, window: Window) -> Self {
-        self.window = Some(window);
+    /// Sets the event sink
+    pub fn sink(mut self, sink: Arc<dyn ProgressSink>) -> Self {
+        self.sink = Some(sink);
         self
     }
+
+    /// Sets a Tauri window as the event sink
+    #[cfg(feature = "gui")]
+    pub fn window(self, window: tauri::Window) -> Self {
+        self.sink(Arc::new(window))
+    }
     
     /// Sets the processing session
     pub fn session(mut self, session: Arc<ProcessingSession>) -> Self {
@@ -124,9 +154,9 @@ impl ProcessingContextBuilder {
     /// # Errors
     /// Returns an error if any required field is missing
     pub fn build(self) -> Result<ProcessingContext> {
-        let window = self.window
+        let sink = self.sink
             .ok_or_else(|| crate::errors::AppError::InvalidInput(
-                "Failed to build ProcessingContext: Tauri window is required for event emission".to_string()
+                "Failed to build ProcessingContext: Event sink is required for event emission".to_string()
             ))?;
         let session = self.session
             .ok_or_else(|| crate::errors::AppError::InvalidInput(
@@ -137,7 +167,7 @@ impl ProcessingContextBuilder {
                 "Failed to build ProcessingContext: Audio settings are required for processing configuration".to_string()
             ))?;
             
-        Ok(ProcessingContext::new(window, session, settings))
+        Ok(ProcessingContext::new(sink, session, settings))
     }
 }
 
@@ -0,0 +1,114 @@
+//! CUE sheet export next to the output
+//!
+//! One TRACK per chapter, indexed at the chapter starts the merged book was
+//! written with, so the sheet and the embedded chapters agree. CUE times
+//! are `MM:SS:FF` with 75 frames per second; minutes go past 99 for long
+//! books, which the common readers accept. CUE has no escape syntax of its
+//! own, so quotes inside values are backslash-escaped as most tools expect.
+
+use super::chapters::Chapter;
+use super::constants::{CUE_EXTENSION, CUE_FILE_TYPE, CUE_FRAMES_PER_SECOND};
+use crate::errors::Result;
+use crate::metadata::AudiobookMetadata;
+use std::path::{Path, PathBuf};
+
+/// CUE sheet for `audio_file` with a track per chapter
+///
+/// Without chapters the whole book is one track, titled like the book.
+pub fn cue_sheet(chapters: &[Chapter], metadata: Option<&AudiobookMetadata>, audio_file: &str) -> String {
+    let performer = metadata.and_then(|metadata| metadata.author.as_deref());
+    let title = metadata.and_then(|metadata| metadata.title.as_deref());
+    let mut sheet = String::new();
+    if let Some(performer) = performer {
+        sheet.push_str(&format!("PERFORMER {}\n", quoted(performer)));
+    }
+    if let Some(title) = title {
+        sheet.push_str(&format!("TITLE {}\n", quoted(title)));
+    }
+    sheet.push_str(&format!("FILE {} {CUE_FILE_TYPE}\n", quoted(audio_file)));
+
+    let whole_book = [Chapter {
+        title: title.unwrap_or(audio_file).to_string(),
+        start_secs: 0.0,
+        end_secs: 0.0,
+    }];
+    let tracks = if chapters.is_empty() { &whole_book[..] } else { chapters };
+    for (index, chapter) in tracks.iter().enumerate() {
+        sheet.push_str(&format!("  TRACK {:02} AUDIO\n", index + 1));
+        sheet.push_str(&format!("    TITLE {}\n", quoted(&chapter.title)));
+        if let Some(performer) = performer {
+            sheet.push_str(&format!("    PERFORMER {}\n", quoted(performer)));
+        }
+        sheet.push_str(&format!("    INDEX 01 {}\n", cue_time(chapter.start_secs)));
+    }
+    sheet
+}
+
+/// Writes `{output stem}.cue` next to `output`
+pub fn write_cue_sheet(output: &Path, chapters: &[Chapter], metadata: Option<&AudiobookMetadata>) -> Result<PathBuf> {
+    let audio_file = output.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let destination = output.with_extension(CUE_EXTENSION);
+    std::fs::write(&destination, cue_sheet(chapters, metadata, &audio_file))?;
+    Ok(destination)
+}
+
+/// `MM:SS:FF` for a position in seconds, rounded to the nearest frame
+fn cue_time(secs: f64) -> String {
+    let frames = (secs.max(0.0) * CUE_FRAMES_PER_SECOND as f64).round() as u64;
+    let seconds = frames / CUE_FRAMES_PER_SECOND;
+    format!("{:02}:{:02}:{:02}", seconds / 60, seconds % 60, frames % CUE_FRAMES_PER_SECOND)
+}
+
+/// Double-quoted CUE value; quotes and backslashes are escaped, line breaks become spaces
+fn quoted(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace(['\r', '\n'], " ");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapters_from_durations(titles: &[&str], durations: &[f64]) -> Vec<Chapter> {
+        let mut start = 0.0;
+        titles.iter().zip(durations)
+            .map(|(title, duration)| {
+                let chapter = Chapter { title: title.to_string(), start_secs: start, end_secs: start + duration };
+                start += duration;
+                chapter
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cue_indexes_from_cumulative_durations() {
+        let chapters = chapters_from_durations(&["Opening", "The \"Long\" Night", "Dawn"], &[61.5, 3663.54, 10.0]);
+        let metadata = AudiobookMetadata {
+            title: Some("Dune".to_string()),
+            author: Some("Frank Herbert".to_string()),
+            ..AudiobookMetadata::new()
+        };
+        assert_eq!(
+            cue_sheet(&chapters, Some(&metadata), "Dune.m4b"),
+            "PERFORMER \"Frank Herbert\"\n\
+             TITLE \"Dune\"\n\
+             FILE \"Dune.m4b\" MP4\n  \
+               TRACK 01 AUDIO\n    TITLE \"Opening\"\n    PERFORMER \"Frank Herbert\"\n    INDEX 01 00:00:00\n  \
+               TRACK 02 AUDIO\n    TITLE \"The \\\"Long\\\" Night\"\n    PERFORMER \"Frank Herbert\"\n    INDEX 01 01:01:38\n  \
+               TRACK 03 AUDIO\n    TITLE \"Dawn\"\n    PERFORMER \"Frank Herbert\"\n    INDEX 01 62:05:03\n"
+        );
+    }
+
+    #[test]
+    fn test_cue_without_chapters_or_metadata() {
+        assert_eq!(
+            cue_sheet(&[], None, "Book.m4b"),
+            "FILE \"Book.m4b\" MP4\n  TRACK 01 AUDIO\n    TITLE \"Book.m4b\"\n    INDEX 01 00:00:00\n"
+        );
+        assert_eq!(cue_time(59.999), "01:00:00");
+        assert_eq!(quoted("a\\b\nc"), "\"a\\\\b c\"");
+    }
+}
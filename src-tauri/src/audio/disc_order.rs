@@ -0,0 +1,276 @@
+//! Book order for inputs spread over disc folders
+//!
+//! Rips of multi-disc books come as `CD1/01.mp3`, `CD2/01.mp3`, ... and a
+//! filename sort interleaves the discs. Imported files are ordered by disc
+//! number, then by filename in natural order ("2" before "10"). The disc
+//! number comes from the nearest folder named with a disc marker, or from
+//! the filename when the discs share one folder ("Book - Disc 2 - 03.mp3").
+//!
+//! Markers are plain words matched case-insensitively and followed by an
+//! optional separator and the number; the app has no regex engine, so the
+//! configurable set is a word list rather than patterns.
+
+use super::constants::{DEFAULT_DISC_MARKERS, DISC_NUMBER_SEPARATORS};
+use super::AudioFile;
+use crate::errors::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+/// Disc markers chosen in preferences, if any
+static DISC_MARKERS: OnceLock<Mutex<Option<Vec<String>>>> = OnceLock::new();
+
+/// Stores the words that mark a disc number, or restores the defaults with None
+///
+/// # Errors
+/// `InvalidInput` for an empty list or a marker that isn't all letters
+pub fn set_disc_markers(markers: Option<Vec<String>>) -> Result<()> {
+    let markers = match markers {
+        Some(markers) => Some(normalize_markers(markers)?),
+        None => None,
+    };
+    let mut current = DISC_MARKERS.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Disc marker preference poisoned".to_string()))?;
+    *current = markers;
+    Ok(())
+}
+
+/// Words currently marking a disc number, lowercase
+pub fn disc_markers() -> Vec<String> {
+    DISC_MARKERS.get_or_init(Default::default).lock().ok()
+        .and_then(|markers| markers.clone())
+        .unwrap_or_else(|| DEFAULT_DISC_MARKERS.iter().map(|m| m.to_string()).collect())
+}
+
+fn normalize_markers(markers: Vec<String>) -> Result<Vec<String>> {
+    if markers.is_empty() {
+        return Err(AppError::InvalidInput("At least one disc marker is required".to_string()));
+    }
+    markers.into_iter()
+        .map(|marker| {
+            let marker = marker.trim().to_lowercase();
+            if marker.is_empty() || !marker.chars().all(char::is_alphabetic) {
+                return Err(AppError::InvalidInput(format!("Disc marker must be a word, got: \"{marker}\"")));
+            }
+            Ok(marker)
+        })
+        .collect()
+}
+
+/// Files of one disc, for "Disc 1 (12 files)"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscGroup {
+    /// Disc number as named
+    pub disc: u32,
+    /// Inputs on the disc
+    pub file_count: usize,
+}
+
+/// Disc number of a file: the nearest marked folder, else the filename
+pub fn disc_number(path: &Path, markers: &[String]) -> Option<u32> {
+    let folders = path.parent()
+        .into_iter()
+        .flat_map(Path::ancestors)
+        .filter_map(|folder| folder.file_name());
+    folders
+        .filter_map(|name| marked_number(&name.to_string_lossy(), markers))
+        .next()
+        .or_else(|| marked_number(&path.file_stem()?.to_string_lossy(), markers))
+}
+
+/// Number following the first marker that starts a word in `name`
+fn marked_number(name: &str, markers: &[String]) -> Option<u32> {
+    let lower = name.to_lowercase();
+    markers.iter().find_map(|marker| {
+        lower.match_indices(marker.as_str()).find_map(|(start, _)| {
+            let starts_word = lower[..start].chars().next_back().is_none_or(|c| !c.is_alphanumeric());
+            let rest = lower[start + marker.len()..].trim_start_matches(DISC_NUMBER_SEPARATORS);
+            let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+            starts_word.then(|| digits.parse().ok()).flatten()
+        })
+    })
+}
+
+/// Discs found among the files, in disc order; empty unless there are two or more
+pub fn disc_groups(files: &[AudioFile], markers: &[String]) -> Vec<DiscGroup> {
+    let mut counts = BTreeMap::new();
+    for file in files {
+        if let Some(disc) = disc_number(&file.path, markers) {
+            *counts.entry(disc).or_insert(0) += 1;
+        }
+    }
+    if counts.len() < 2 {
+        return Vec::new();
+    }
+    counts.into_iter().map(|(disc, file_count)| DiscGroup { disc, file_count }).collect()
+}
+
+/// Orders files by disc, then by filename in natural order
+///
+/// Lists with fewer than two discs keep their order. Files without a disc
+/// number in a multi-disc list go first, like an intro beside the disc
+/// folders.
+pub fn sort_by_disc(files: &mut [AudioFile], markers: &[String]) {
+    if disc_groups(files, markers).is_empty() {
+        return;
+    }
+    files.sort_by_cached_key(|file| {
+        let name = file.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        (disc_number(&file.path, markers).unwrap_or(0), NaturalKey(name), file.path.clone())
+    });
+}
+
+/// Text compared with digit runs as numbers, ignoring case
+#[derive(Debug, PartialEq, Eq)]
+struct NaturalKey(String);
+
+impl Ord for NaturalKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        natural_cmp(&self.0, &other.0)
+    }
+}
+
+impl PartialOrd for NaturalKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares names so "Track 2" sorts before "Track 10"
+///
+/// Digit runs compare by value, then the shorter (less padded) run first;
+/// other characters compare case-insensitively.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (digit_run(&mut a), digit_run(&mut b));
+                let (x_value, y_value) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = x_value.len().cmp(&y_value.len())
+                    .then_with(|| x_value.cmp(y_value))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn digit_run(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut run = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        run.push(c);
+    }
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn defaults() -> Vec<String> {
+        DEFAULT_DISC_MARKERS.iter().map(|m| m.to_string()).collect()
+    }
+
+    fn files(paths: &[&str]) -> Vec<AudioFile> {
+        paths.iter().map(|path| AudioFile::new(PathBuf::from(path))).collect()
+    }
+
+    fn names(files: &[AudioFile]) -> Vec<String> {
+        files.iter().map(|file| file.path.to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn test_disc_folder_variants() {
+        let markers = defaults();
+        for (path, disc) in [
+            ("/books/Dune/CD1/01.mp3", Some(1)),
+            ("/books/Dune/cd 02/01.mp3", Some(2)),
+            ("/books/Dune/Disc 01/Track 3.mp3", Some(1)),
+            ("/books/Dune/Disc10/Track 3.mp3", Some(10)),
+            ("/books/Dune/Disk_3/01.mp3", Some(3)),
+            ("/books/Dune (Disc 4)/Part A/01.mp3", Some(4)),
+            ("/books/Discworld 3/01.mp3", None),
+            ("/books/Dune/ABCD1/01.mp3", None),
+            ("/books/Dune/01.mp3", None),
+        ] {
+            assert_eq!(disc_number(Path::new(path), &markers), disc, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_disc_numbers_in_flat_filenames() {
+        let markers = defaults();
+        assert_eq!(disc_number(Path::new("/books/Dune - Disc 2 - 03.mp3"), &markers), Some(2));
+        assert_eq!(disc_number(Path::new("/books/Dune_CD03_Track01.mp3"), &markers), Some(3));
+        // A marked folder wins over the filename
+        assert_eq!(disc_number(Path::new("/books/CD1/Dune - Disc 2.mp3"), &markers), Some(1));
+    }
+
+    #[test]
+    fn test_discs_are_ordered_before_filenames() {
+        let markers = defaults();
+        let mut list = files(&[
+            "/Dune/CD1/01.mp3", "/Dune/CD10/01.mp3", "/Dune/CD2/01.mp3",
+            "/Dune/CD1/10.mp3", "/Dune/CD2/02.mp3", "/Dune/CD1/2.mp3",
+        ]);
+        sort_by_disc(&mut list, &markers);
+        assert_eq!(names(&list), [
+            "/Dune/CD1/01.mp3", "/Dune/CD1/2.mp3", "/Dune/CD1/10.mp3",
+            "/Dune/CD2/01.mp3", "/Dune/CD2/02.mp3", "/Dune/CD10/01.mp3",
+        ]);
+        assert_eq!(disc_groups(&list, &markers), [
+            DiscGroup { disc: 1, file_count: 3 },
+            DiscGroup { disc: 2, file_count: 2 },
+            DiscGroup { disc: 10, file_count: 1 },
+        ]);
+
+        let mut flat = files(&["/Dune/Disc 2 - 01.mp3", "/Dune/Disc 1 - 02.mp3", "/Dune/Disc 1 - 01.mp3"]);
+        sort_by_disc(&mut flat, &markers);
+        assert_eq!(names(&flat), ["/Dune/Disc 1 - 01.mp3", "/Dune/Disc 1 - 02.mp3", "/Dune/Disc 2 - 01.mp3"]);
+    }
+
+    #[test]
+    fn test_single_disc_lists_keep_their_order() {
+        let markers = defaults();
+        let order = ["/Dune/CD1/b.mp3", "/Dune/CD1/a.mp3"];
+        let mut one_disc = files(&order);
+        sort_by_disc(&mut one_disc, &markers);
+        assert_eq!(names(&one_disc), order);
+        assert!(disc_groups(&one_disc, &markers).is_empty());
+    }
+
+    #[test]
+    fn test_configured_markers() {
+        let markers = normalize_markers(vec![" Teil ".to_string()]).unwrap();
+        assert_eq!(disc_number(Path::new("/Dune/Teil 2/01.mp3"), &markers), Some(2));
+        assert_eq!(disc_number(Path::new("/Dune/CD 2/01.mp3"), &markers), None);
+        assert!(normalize_markers(Vec::new()).is_err());
+        assert!(normalize_markers(vec!["cd\\d+".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("Track 2", "Track 10"), Ordering::Less);
+        assert_eq!(natural_cmp("track 02", "Track 2"), Ordering::Greater);
+        assert_eq!(natural_cmp("a", "B"), Ordering::Less);
+        assert_eq!(natural_cmp("01", "01"), Ordering::Equal);
+    }
+}
@@ -0,0 +1,130 @@
+//! Free space on the volume holding an output
+//!
+//! There is no portable std call for free space, so this asks the
+//! platform's own tool, like the filesystem lookup in `large_output`:
+//! `df -Pk` on Unix and `fsutil volume diskfree` on Windows. The volume is
+//! named by its mount point (or drive), which is what queued jobs are
+//! grouped by when forecasting.
+
+use std::path::Path;
+
+/// Free space on one volume
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeSpace {
+    /// Mount point, or drive on Windows (e.g. "/Volumes/BOOKS", "D:")
+    pub volume: String,
+    /// Bytes the current user can still write
+    pub available_bytes: u64,
+}
+
+/// Free space on the volume holding `path`
+///
+/// `path` need not exist; its nearest existing ancestor is looked up.
+/// Returns None when the space can't be determined.
+pub fn volume_space(path: &Path) -> Option<VolumeSpace> {
+    let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+    let space = platform_volume_space(existing);
+    if space.is_none() {
+        log::debug!("Cannot determine the free space of {}", existing.display());
+    }
+    space
+}
+
+#[cfg(unix)]
+fn platform_volume_space(path: &Path) -> Option<VolumeSpace> {
+    let output = std::process::Command::new("df").arg("-Pk").arg(path).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    volume_space_from_df_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(windows)]
+fn platform_volume_space(path: &Path) -> Option<VolumeSpace> {
+    use std::path::Component;
+    let path = std::fs::canonicalize(path).ok()?;
+    let Some(Component::Prefix(prefix)) = path.components().next() else {
+        return None;
+    };
+    let drive = prefix.as_os_str().to_string_lossy().trim_start_matches(r"\\?\").to_string();
+    let output = std::process::Command::new("fsutil")
+        .args(["volume", "diskfree", &drive])
+        .output()
+        .ok()?;
+    let available_bytes = available_from_fsutil_output(&String::from_utf8_lossy(&output.stdout))?;
+    Some(VolumeSpace { volume: drive, available_bytes })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn platform_volume_space(_path: &Path) -> Option<VolumeSpace> {
+    None
+}
+
+/// Volume and available space from POSIX `df -Pk` output
+///
+/// The data line reads `device blocks used available capacity mount point`,
+/// sizes in KiB; the mount point may contain spaces.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn volume_space_from_df_output(output: &str) -> Option<VolumeSpace> {
+    let line = output.lines().nth(1)?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let available_kib: u64 = fields.get(3)?.parse().ok()?;
+    let volume = fields.get(5..).filter(|mount| !mount.is_empty())?.join(" ");
+    Some(VolumeSpace { volume, available_bytes: available_kib.saturating_mul(1024) })
+}
+
+/// Bytes available to the user from `fsutil volume diskfree` output
+///
+/// Prefers the quota-aware "avail free" line; digit grouping depends on
+/// the locale, so every non-digit before the parenthesized size is dropped.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn available_from_fsutil_output(output: &str) -> Option<u64> {
+    let value = |wanted: &str| output.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.to_ascii_lowercase().contains(wanted))
+        .and_then(|(_, value)| {
+            let digits: String = value.split('(').next()?.chars().filter(char::is_ascii_digit).collect();
+            digits.parse().ok()
+        });
+    value("avail free bytes")
+        .or_else(|| value("quota free bytes"))
+        .or_else(|| value("free bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_volume_space_from_df_output() {
+        let df = "Filesystem     1024-blocks      Used Available Capacity Mounted on\n\
+                  /dev/sdb1        976762584 512000000 464762584      53% /media/user/MY BOOKS\n";
+        assert_eq!(volume_space_from_df_output(df), Some(VolumeSpace {
+            volume: "/media/user/MY BOOKS".to_string(),
+            available_bytes: 464_762_584 * 1024,
+        }));
+        assert_eq!(volume_space_from_df_output("Filesystem 1024-blocks Used Available Capacity Mounted on\n"), None);
+    }
+
+    #[test]
+    fn test_available_from_fsutil_output() {
+        let modern = "Total free bytes                : 46,193,897,472 ( 43.0 GB)\r\n\
+                      Total bytes                     : 255,230,586,880 (237.7 GB)\r\n\
+                      Total quota free bytes          : 40,000,000,000 ( 37.3 GB)\r\n";
+        assert_eq!(available_from_fsutil_output(modern), Some(40_000_000_000));
+        let legacy = "Total # of free bytes        : 46193897472\r\n\
+                      Total # of bytes             : 255230586880\r\n\
+                      Total # of avail free bytes  : 46193897000\r\n";
+        assert_eq!(available_from_fsutil_output(legacy), Some(46_193_897_000));
+        assert_eq!(available_from_fsutil_output("Error: Access is denied."), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_volume_space_of_missing_path_uses_ancestor() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let missing = temp_dir.path().join("not").join("yet").join("book.m4b");
+        let space = volume_space(&missing);
+        assert_eq!(space.as_ref().map(|s| &s.volume), volume_space(temp_dir.path()).as_ref().map(|s| &s.volume));
+    }
+}
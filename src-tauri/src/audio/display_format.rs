@@ -0,0 +1,199 @@
+//! Locale-aware formatting of durations, sizes and percentages
+//!
+//! Result strings and the metrics summary are read by users, so numbers in
+//! them follow the display locale the frontend sets: its decimal separator
+//! and unit abbreviations. English, German, French and Spanish are
+//! supported; any other locale falls back to English.
+
+use super::constants::SECONDS_PER_MINUTE;
+use crate::errors::{AppError, Result};
+use std::sync::{Mutex, OnceLock};
+
+/// Locale chosen in preferences
+static DISPLAY_LOCALE: OnceLock<Mutex<DisplayLocale>> = OnceLock::new();
+
+/// Supported display locales
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayLocale {
+    #[default]
+    En,
+    De,
+    Fr,
+    Es,
+}
+
+/// Separators and unit abbreviations of one locale
+struct Units {
+    decimal: char,
+    /// Between a number and its time unit
+    time_gap: &'static str,
+    hours: &'static str,
+    minutes: &'static str,
+    seconds: &'static str,
+    /// Byte, kilobyte, megabyte and gigabyte, in that order
+    sizes: [&'static str; 4],
+    /// Between a number and the percent sign
+    percent_gap: &'static str,
+}
+
+impl DisplayLocale {
+    /// Locale for a BCP 47 tag such as "de-AT" or "fr_CA", English when unsupported
+    pub fn from_tag(tag: &str) -> Self {
+        let language = tag.split(['-', '_']).next().unwrap_or_default().to_ascii_lowercase();
+        match language.as_str() {
+            "de" => Self::De,
+            "fr" => Self::Fr,
+            "es" => Self::Es,
+            _ => Self::En,
+        }
+    }
+
+    /// Language tag of the locale
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::De => "de",
+            Self::Fr => "fr",
+            Self::Es => "es",
+        }
+    }
+
+    fn units(self) -> Units {
+        match self {
+            Self::En => Units {
+                decimal: '.', time_gap: "", hours: "h", minutes: "m", seconds: "s",
+                sizes: ["B", "KB", "MB", "GB"], percent_gap: "",
+            },
+            Self::De => Units {
+                decimal: ',', time_gap: " ", hours: "Std.", minutes: "Min.", seconds: "Sek.",
+                sizes: ["B", "KB", "MB", "GB"], percent_gap: " ",
+            },
+            Self::Fr => Units {
+                decimal: ',', time_gap: " ", hours: "h", minutes: "min", seconds: "s",
+                sizes: ["o", "Ko", "Mo", "Go"], percent_gap: " ",
+            },
+            Self::Es => Units {
+                decimal: ',', time_gap: " ", hours: "h", minutes: "min", seconds: "s",
+                sizes: ["B", "KB", "MB", "GB"], percent_gap: " ",
+            },
+        }
+    }
+}
+
+/// Stores the display locale, returning the supported locale it resolved to
+pub fn set_display_locale(tag: &str) -> Result<DisplayLocale> {
+    let locale = DisplayLocale::from_tag(tag);
+    let mut current = DISPLAY_LOCALE.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Display locale preference poisoned".to_string()))?;
+    *current = locale;
+    Ok(locale)
+}
+
+/// The display locale set in preferences, English by default
+pub fn display_locale() -> DisplayLocale {
+    DISPLAY_LOCALE.get_or_init(Default::default).lock()
+        .map(|locale| *locale)
+        .unwrap_or_default()
+}
+
+/// Formats numbers for one locale
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayFormatter {
+    locale: DisplayLocale,
+}
+
+impl DisplayFormatter {
+    /// Formatter for a given locale
+    pub fn new(locale: DisplayLocale) -> Self {
+        Self { locale }
+    }
+
+    /// Formatter for the display locale in preferences
+    pub fn current() -> Self {
+        Self::new(display_locale())
+    }
+
+    /// `value` with `decimals` digits after the locale's decimal separator
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let formatted = format!("{value:.decimals$}");
+        match self.locale.units().decimal {
+            '.' => formatted,
+            separator => formatted.replace('.', &separator.to_string()),
+        }
+    }
+
+    /// Duration as seconds, minutes and seconds, or hours and minutes
+    pub fn duration(&self, seconds: f64) -> String {
+        let units = self.locale.units();
+        let gap = units.time_gap;
+        let seconds = seconds.max(0.0);
+        if seconds < SECONDS_PER_MINUTE {
+            format!("{}{gap}{}", self.number(seconds, 0), units.seconds)
+        } else if seconds < SECONDS_PER_MINUTE * 60.0 {
+            let minutes = (seconds / SECONDS_PER_MINUTE) as u32;
+            let remaining = self.number(seconds % SECONDS_PER_MINUTE, 0);
+            format!("{minutes}{gap}{} {remaining}{gap}{}", units.minutes, units.seconds)
+        } else {
+            let minutes_total = (seconds / SECONDS_PER_MINUTE) as u64;
+            format!("{}{gap}{} {}{gap}{}", minutes_total / 60, units.hours, minutes_total % 60, units.minutes)
+        }
+    }
+
+    /// Byte count in the largest binary unit that keeps it at or above 1
+    pub fn size(&self, bytes: f64) -> String {
+        let units = self.locale.units();
+        let mut value = bytes.max(0.0);
+        let mut unit = 0;
+        while value >= 1024.0 && unit < units.sizes.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        let decimals = if unit == 0 { 0 } else { 2 };
+        format!("{} {}", self.number(value, decimals), units.sizes[unit])
+    }
+
+    /// Percentage with one decimal
+    pub fn percent(&self, value: f64) -> String {
+        format!("{}{}%", self.number(value, 1), self.locale.units().percent_gap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_locales(format: impl Fn(&DisplayFormatter) -> String) -> Vec<String> {
+        [DisplayLocale::En, DisplayLocale::De, DisplayLocale::Fr, DisplayLocale::Es]
+            .map(|locale| format(&DisplayFormatter::new(locale)))
+            .to_vec()
+    }
+
+    #[test]
+    fn test_locale_tags() {
+        assert_eq!(DisplayLocale::from_tag("de-AT"), DisplayLocale::De);
+        assert_eq!(DisplayLocale::from_tag("FR_ca"), DisplayLocale::Fr);
+        assert_eq!(DisplayLocale::from_tag("es"), DisplayLocale::Es);
+        assert_eq!(DisplayLocale::from_tag("ja-JP"), DisplayLocale::En);
+        assert_eq!(DisplayLocale::from_tag(""), DisplayLocale::En);
+    }
+
+    #[test]
+    fn test_durations_per_locale() {
+        assert_eq!(all_locales(|f| f.duration(52_320.0)), ["14h 32m", "14 Std. 32 Min.", "14 h 32 min", "14 h 32 min"]);
+        assert_eq!(all_locales(|f| f.duration(125.0)), ["2m 5s", "2 Min. 5 Sek.", "2 min 5 s", "2 min 5 s"]);
+        assert_eq!(all_locales(|f| f.duration(30.0)), ["30s", "30 Sek.", "30 s", "30 s"]);
+    }
+
+    #[test]
+    fn test_sizes_per_locale() {
+        assert_eq!(all_locales(|f| f.size(8_388_608.0)), ["8.00 MB", "8,00 MB", "8,00 Mo", "8,00 MB"]);
+        assert_eq!(all_locales(|f| f.size(512.0)), ["512 B", "512 B", "512 o", "512 B"]);
+        assert_eq!(DisplayFormatter::new(DisplayLocale::En).size(1.5 * 1_073_741_824.0), "1.50 GB");
+    }
+
+    #[test]
+    fn test_percentages_and_numbers_per_locale() {
+        assert_eq!(all_locales(|f| f.percent(42.5)), ["42.5%", "42,5 %", "42,5 %", "42,5 %"]);
+        assert_eq!(all_locales(|f| f.number(1.25, 1)), ["1.2", "1,2", "1,2", "1,2"]);
+    }
+}
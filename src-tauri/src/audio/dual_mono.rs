@@ -0,0 +1,211 @@
+//! Dual-mono detection for stereo inputs
+//!
+//! Some MP3s are mono recordings stored as two identical channels. Encoding
+//! those as stereo wastes half the bitrate, so an optional analysis decodes
+//! a short window per stereo file and compares the channels.
+
+use super::constants::{
+    DUAL_MONO_CORRELATION_THRESHOLD, DUAL_MONO_DIFFERENCE_THRESHOLD, DUAL_MONO_MAX_SKIP_SECONDS,
+    DUAL_MONO_SAMPLE_RATE, DUAL_MONO_WINDOW_SECONDS,
+};
+use super::{AudioFile, AudioSettings, ChannelConfig};
+use crate::errors::Result;
+use crate::ffmpeg::{locate_ffmpeg, FFmpegError};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// How closely the two channels of a stereo window match
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelSimilarity {
+    /// Pearson correlation between left and right (-1..1)
+    pub correlation: f64,
+    /// Energy of L-R relative to the energy of both channels (0 = identical)
+    pub difference_ratio: f64,
+}
+
+impl ChannelSimilarity {
+    /// True when the channels are effectively the same signal
+    pub fn is_dual_mono(&self) -> bool {
+        self.correlation >= DUAL_MONO_CORRELATION_THRESHOLD
+            && self.difference_ratio <= DUAL_MONO_DIFFERENCE_THRESHOLD
+    }
+}
+
+/// Compares the channels of interleaved stereo PCM
+///
+/// Returns None when either channel is silent, since correlation is
+/// undefined and a silent window says nothing about the file.
+pub fn channel_similarity(interleaved: &[f32]) -> Option<ChannelSimilarity> {
+    let frames = interleaved.len() / 2;
+    if frames == 0 {
+        return None;
+    }
+
+    let (mut sum_l, mut sum_r) = (0.0f64, 0.0f64);
+    for frame in interleaved.chunks_exact(2) {
+        sum_l += f64::from(frame[0]);
+        sum_r += f64::from(frame[1]);
+    }
+    let (mean_l, mean_r) = (sum_l / frames as f64, sum_r / frames as f64);
+
+    let (mut cov, mut var_l, mut var_r) = (0.0f64, 0.0f64, 0.0f64);
+    let (mut diff_energy, mut energy) = (0.0f64, 0.0f64);
+    for frame in interleaved.chunks_exact(2) {
+        let (l, r) = (f64::from(frame[0]), f64::from(frame[1]));
+        cov += (l - mean_l) * (r - mean_r);
+        var_l += (l - mean_l).powi(2);
+        var_r += (r - mean_r).powi(2);
+        diff_energy += (l - r).powi(2);
+        energy += l * l + r * r;
+    }
+
+    if var_l <= f64::EPSILON || var_r <= f64::EPSILON {
+        return None;
+    }
+    Some(ChannelSimilarity {
+        correlation: cov / (var_l.sqrt() * var_r.sqrt()),
+        difference_ratio: diff_energy / energy,
+    })
+}
+
+/// Decodes a short stereo window of a file and checks it for dual-mono
+///
+/// The window starts a little way in to skip silent intros.
+pub fn detect_dual_mono(path: &Path, duration: f64) -> Result<Option<bool>> {
+    let skip = (duration * 0.1).min(DUAL_MONO_MAX_SKIP_SECONDS);
+    let output = Command::new(locate_ffmpeg()?)
+        .args(["-v", "error", "-nostdin", "-ss", &format!("{skip:.3}"), "-t", &DUAL_MONO_WINDOW_SECONDS.to_string(), "-i"])
+        .arg(path)
+        .args(["-vn", "-ac", "2", "-ar", &DUAL_MONO_SAMPLE_RATE.to_string(), "-f", "f32le", "pipe:1"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))?;
+
+    if !output.status.success() {
+        return Err(FFmpegError::ExecutionFailed(
+            String::from_utf8_lossy(&output.stderr).to_string()
+        ).into());
+    }
+
+    let samples: Vec<f32> = output.stdout.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    Ok(channel_similarity(&samples).map(|s| s.is_dual_mono()))
+}
+
+/// Runs dual-mono detection on every valid stereo file
+///
+/// Failures are logged and leave the file undetermined.
+pub fn annotate_dual_mono(files: &mut [AudioFile]) {
+    for file in files.iter_mut().filter(|f| f.is_valid && f.channels == Some(2)) {
+        let duration = file.duration.unwrap_or(0.0);
+        match detect_dual_mono(&file.path, duration) {
+            Ok(result) => file.dual_mono = result,
+            Err(e) => log::warn!("Dual-mono detection failed for {}: {e}", file.path.display()),
+        }
+    }
+}
+
+/// True when there is stereo input and every stereo file is dual-mono
+pub fn all_stereo_inputs_dual_mono(files: &[AudioFile]) -> bool {
+    let mut stereo = files.iter().filter(|f| f.channels == Some(2)).peekable();
+    stereo.peek().is_some() && stereo.all(|f| f.dual_mono == Some(true))
+}
+
+/// Whether `apply_auto_dual_mono` would decode any of these inputs
+pub fn dual_mono_detection_applies(settings: &AudioSettings, files: &[AudioFile]) -> bool {
+    settings.auto_dual_mono
+        && matches!(settings.channels, ChannelConfig::Stereo)
+        && files.iter().any(|f| f.is_valid && f.channels == Some(2))
+}
+
+/// Switches stereo output to mono when `auto_dual_mono` is set and all inputs are dual-mono
+pub fn apply_auto_dual_mono(settings: &mut AudioSettings, files: &mut [AudioFile]) {
+    if !dual_mono_detection_applies(settings, files) {
+        return;
+    }
+    annotate_dual_mono(files);
+    if all_stereo_inputs_dual_mono(files) {
+        log::info!("All stereo inputs are dual-mono, encoding mono output");
+        settings.channels = ChannelConfig::Mono;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn interleave(left: &[f32], right: &[f32]) -> Vec<f32> {
+        left.iter().zip(right).flat_map(|(&l, &r)| [l, r]).collect()
+    }
+
+    fn tone(freq: f32, len: usize) -> Vec<f32> {
+        (0..len).map(|i| (i as f32 * freq * 0.001).sin() * 0.5).collect()
+    }
+
+    #[test]
+    fn test_identical_channels_are_dual_mono() {
+        let mono = tone(440.0, 4000);
+        let similarity = channel_similarity(&interleave(&mono, &mono)).unwrap();
+        assert!((similarity.correlation - 1.0).abs() < 1e-9);
+        assert_eq!(similarity.difference_ratio, 0.0);
+        assert!(similarity.is_dual_mono());
+    }
+
+    #[test]
+    fn test_true_stereo_is_not_dual_mono() {
+        let left = tone(440.0, 4000);
+        let right = tone(660.0, 4000);
+        let similarity = channel_similarity(&interleave(&left, &right)).unwrap();
+        assert!(similarity.correlation < 0.5);
+        assert!(!similarity.is_dual_mono());
+    }
+
+    #[test]
+    fn test_level_difference_is_not_dual_mono() {
+        // Perfectly correlated but panned: one channel much quieter
+        let left = tone(440.0, 4000);
+        let right: Vec<f32> = left.iter().map(|s| s * 0.5).collect();
+        let similarity = channel_similarity(&interleave(&left, &right)).unwrap();
+        assert!(similarity.correlation > 0.999);
+        assert!(!similarity.is_dual_mono());
+    }
+
+    #[test]
+    fn test_near_identical_with_codec_noise_is_dual_mono() {
+        let left = tone(440.0, 4000);
+        let right: Vec<f32> = left.iter().enumerate()
+            .map(|(i, s)| s + if i % 2 == 0 { 1e-4 } else { -1e-4 })
+            .collect();
+        assert!(channel_similarity(&interleave(&left, &right)).unwrap().is_dual_mono());
+    }
+
+    #[test]
+    fn test_silence_is_undetermined() {
+        assert!(channel_similarity(&[0.0; 200]).is_none());
+        assert!(channel_similarity(&[]).is_none());
+    }
+
+    #[test]
+    fn test_all_stereo_inputs_dual_mono() {
+        let file = |channels, dual_mono| {
+            let mut f = AudioFile::new(PathBuf::from("a.mp3"));
+            f.channels = Some(channels);
+            f.dual_mono = dual_mono;
+            f
+        };
+        assert!(all_stereo_inputs_dual_mono(&[file(2, Some(true)), file(1, None)]));
+        assert!(!all_stereo_inputs_dual_mono(&[file(2, Some(true)), file(2, None)]));
+        assert!(!all_stereo_inputs_dual_mono(&[file(2, Some(false))]));
+        assert!(!all_stereo_inputs_dual_mono(&[file(1, None)]));
+    }
+
+    #[test]
+    fn test_auto_dual_mono_disabled_leaves_settings() {
+        let mut settings = AudioSettings::high_quality_preset();
+        let mut files = vec![AudioFile::new(PathBuf::from("a.mp3"))];
+        apply_auto_dual_mono(&mut settings, &mut files);
+        assert!(matches!(settings.channels, ChannelConfig::Stereo));
+    }
+}
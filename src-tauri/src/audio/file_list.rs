@@ -1,11 +1,44 @@
 //! File list management and validation
 
-use super::AudioFile;
+use super::constants::{ANALYSIS_TIMEOUT_SECS, DRM_CODEC_TAGS, DRM_EXTENSIONS, FILE_STABILITY_CHECK_MS};
+use super::input_stats::InputDurationStats;
+use super::disc_order::{disc_groups, disc_markers, DiscGroup};
+use super::{AnalysisDepth, AudioFile, FileErrorKind, InvalidInputPolicy, SyncStatus};
 use crate::errors::{AppError, Result};
+use crate::metadata::id3_chapters::read_id3_chapters;
+use lofty::config::ParseOptions;
+use lofty::error::{ErrorKind as LoftyErrorKind, LoftyError};
+use lofty::file::{AudioFile as LoftyAudioFile, FileType, TaggedFile, TaggedFileExt};
+use lofty::prelude::Accessor;
 use lofty::probe::Probe;
-use lofty::file::AudioFile as LoftyAudioFile;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+/// Per-file probe time limit chosen in preferences, if any
+static ANALYSIS_TIMEOUT: OnceLock<Mutex<Option<Duration>>> = OnceLock::new();
+
+/// Stores how long one file's probe may take, or restores the default with None
+pub fn set_analysis_timeout(timeout: Option<Duration>) -> Result<()> {
+    if timeout.is_some_and(|timeout| timeout.is_zero()) {
+        return Err(AppError::InvalidInput("Analysis timeout must be greater than zero".to_string()));
+    }
+    let mut current = ANALYSIS_TIMEOUT.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Analysis timeout preference poisoned".to_string()))?;
+    *current = timeout;
+    Ok(())
+}
+
+/// How long one file's probe may take before the file is marked `timeout`
+pub fn analysis_timeout() -> Duration {
+    ANALYSIS_TIMEOUT.get_or_init(Default::default).lock().ok()
+        .and_then(|timeout| *timeout)
+        .unwrap_or(Duration::from_secs(ANALYSIS_TIMEOUT_SECS))
+}
 
 /// Summary information for a file list
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -21,9 +54,77 @@ pub struct FileListInfo {
     pub valid_count: usize,
     /// Number of invalid files
     pub invalid_count: usize,
+    /// Number of invalid files of each kind
+    #[serde(default)]
+    pub error_counts: BTreeMap<FileErrorKind, usize>,
+    /// Duration spread of the valid files; None when none has a duration
+    #[serde(default)]
+    pub input_stats: Option<InputDurationStats>,
+    /// Files per disc when the inputs span two or more disc folders
+    #[serde(default)]
+    pub disc_groups: Vec<DiscGroup>,
+}
+
+/// An input left out of processing under `InvalidInputPolicy::SkipWithWarning`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedInput {
+    /// Path of the skipped file
+    pub path: PathBuf,
+    /// Why the file failed validation
+    pub error: Option<String>,
+}
+
+/// Applies the invalid-input policy to an analyzed file list
+///
+/// With `Fail` the list is returned untouched so validation rejects it as
+/// before. With `SkipWithWarning` invalid files are removed and returned
+/// separately; it is still an error when no valid file remains.
+pub fn apply_invalid_input_policy(
+    files: Vec<AudioFile>,
+    policy: InvalidInputPolicy,
+) -> Result<(Vec<AudioFile>, Vec<SkippedInput>)> {
+    if policy == InvalidInputPolicy::Fail {
+        return Ok((files, Vec::new()));
+    }
+
+    let (valid, invalid): (Vec<AudioFile>, Vec<AudioFile>) = files.into_iter()
+        .partition(|f| f.is_valid);
+    if valid.is_empty() {
+        return Err(AppError::InvalidInput(format!(
+            "No valid files to process ({} invalid files skipped)", invalid.len()
+        )));
+    }
+
+    let skipped = invalid.into_iter()
+        .map(|f| SkippedInput { path: f.path, error: f.error })
+        .collect();
+    Ok((valid, skipped))
+}
+
+/// Size and modification time of a file at one moment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileSnapshot {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileSnapshot {
+    fn from_metadata(metadata: &fs::Metadata) -> Self {
+        Self { len: metadata.len(), modified: metadata.modified().ok() }
+    }
+
+    fn take(path: &Path) -> Option<Self> {
+        fs::metadata(path).ok().map(|metadata| Self::from_metadata(&metadata))
+    }
 }
 
 /// Validates a list of file paths and returns audio file information
+///
+/// Every file is stat'ed once up front and again during validation after a
+/// single short wait; files whose size or mtime moved in between are still
+/// being downloaded or copied and are reported as `still_syncing` rather
+/// than probed.
 pub fn validate_audio_files<P: AsRef<Path>>(
     file_paths: &[P]
 ) -> Result<Vec<AudioFile>> {
@@ -33,88 +134,247 @@ pub fn validate_audio_files<P: AsRef<Path>>(
         ));
     }
 
+    let snapshots: Vec<Option<FileSnapshot>> = file_paths.iter()
+        .map(|path| FileSnapshot::take(path.as_ref()))
+        .collect();
+    if snapshots.iter().any(Option::is_some) {
+        std::thread::sleep(Duration::from_millis(FILE_STABILITY_CHECK_MS));
+    }
+
     let mut audio_files = Vec::new();
     
-    for path in file_paths {
-        let audio_file = validate_single_file(path.as_ref())?;
+    for (path, snapshot) in file_paths.iter().zip(snapshots) {
+        let audio_file = validate_single_file(path.as_ref(), snapshot)?;
         audio_files.push(audio_file);
     }
     
     Ok(audio_files)
 }
 
+/// A validation failure: its kind and the message shown for it
+#[derive(Debug)]
+struct FileError {
+    kind: FileErrorKind,
+    message: String,
+}
+
+impl FileError {
+    fn new(kind: FileErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into() }
+    }
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl AudioFile {
+    fn fail(&mut self, error: FileError) {
+        self.is_valid = false;
+        self.error_kind = Some(error.kind);
+        self.error = Some(error.message);
+    }
+}
+
 /// Validates a single audio file
-fn validate_single_file(path: &Path) -> Result<AudioFile> {
+///
+/// `earlier` is the file's state before the stability wait, if taken.
+fn validate_single_file(path: &Path, earlier: Option<FileSnapshot>) -> Result<AudioFile> {
     let mut audio_file = AudioFile::new(path.to_path_buf());
     
     // Check if file exists
     if !path.exists() {
-        audio_file.error = Some(format!("File not found: {}", path.display()));
+        audio_file.fail(FileError::new(FileErrorKind::NotFound, format!("File not found: {}", path.display())));
         return Ok(audio_file);
     }
     
     // Get file size
-    match fs::metadata(path) {
-        Ok(metadata) => audio_file.size = Some(metadata.len() as f64),
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
         Err(e) => {
-            audio_file.error = Some(format!("Cannot read file metadata: {e}"));
+            audio_file.fail(FileError::new(io_error_kind(&e), format!("Cannot read file metadata: {e}")));
             return Ok(audio_file);
         }
+    };
+    audio_file.size = Some(metadata.len() as f64);
+
+    // Probing a partial file would only report it as broken
+    if is_dataless(&metadata) {
+        audio_file.sync_status = Some(SyncStatus::Dataless);
+        audio_file.fail(FileError::new(
+            FileErrorKind::StillSyncing,
+            format!("File is in cloud storage and not downloaded: {}", path.display()),
+        ));
+        return Ok(audio_file);
+    }
+    if earlier.is_some_and(|snapshot| snapshot != FileSnapshot::from_metadata(&metadata)) {
+        audio_file.sync_status = Some(SyncStatus::StillSyncing);
+        audio_file.fail(FileError::new(
+            FileErrorKind::StillSyncing,
+            format!("File is still being written or synced: {}", path.display()),
+        ));
+        return Ok(audio_file);
     }
     
+    Ok(probe_with_timeout(audio_file, probe_audio_file, analysis_timeout()))
+}
+
+/// Fills in what probing tells about a file that passed the stat checks
+///
+/// Takes the time limit its ffprobe fallbacks are held to.
+type Prober = fn(AudioFile, Duration) -> AudioFile;
+
+/// Runs `prober` on a worker thread, giving up on it after `timeout`
+///
+/// Lofty can spin for minutes on some damaged files and can't be
+/// interrupted, so the worker is abandoned rather than joined and the file
+/// is marked `timeout`; any ffprobe it started is killed at the same limit.
+fn probe_with_timeout(audio_file: AudioFile, prober: Prober, timeout: Duration) -> AudioFile {
+    let (sender, receiver) = mpsc::channel();
+    let pending = audio_file.clone();
+    let spawned = std::thread::Builder::new()
+        .name("file-probe".to_string())
+        .spawn(move || {
+            // The receiver is gone when the probe was given up on
+            let _ = sender.send(prober(pending, timeout));
+        });
+    if let Err(e) = spawned {
+        log::warn!("Cannot start a probe thread, probing {} inline: {e}", audio_file.path.display());
+        return prober(audio_file, timeout);
+    }
+    let mut audio_file = audio_file;
+    match receiver.recv_timeout(timeout) {
+        Ok(probed) => return probed,
+        Err(RecvTimeoutError::Timeout) => {
+            log::warn!("Gave up probing {} after {:.1} s", audio_file.path.display(), timeout.as_secs_f64());
+            audio_file.fail(FileError::new(
+                FileErrorKind::Timeout,
+                format!("Analysis timed out after {:.0} s; the file may be damaged: {}", timeout.as_secs_f64(), audio_file.path.display()),
+            ));
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            audio_file.fail(FileError::new(
+                FileErrorKind::Unknown,
+                format!("Analysis failed unexpectedly: {}", audio_file.path.display()),
+            ));
+        }
+    }
+    audio_file
+}
+
+/// The probe `validate_single_file` runs: Lofty for format and properties,
+/// ffprobe for codecs and DRM Lofty can't tell apart
+fn probe_audio_file(mut audio_file: AudioFile, timeout: Duration) -> AudioFile {
+    let path = audio_file.path.clone();
+    let path = path.as_path();
     // Validate audio format and get comprehensive metadata
     match validate_audio_format(path) {
-        Ok((format, duration, bitrate, sample_rate, channels)) => {
+        Ok((format, duration, bitrate, sample_rate, channels, title)) => {
             audio_file.format = Some(format);
+            audio_file.title = title;
             audio_file.duration = Some(duration);
             audio_file.bitrate = bitrate;
             audio_file.sample_rate = sample_rate;
             audio_file.channels = channels;
+            audio_file.codec = detect_codec(path, timeout);
             audio_file.is_valid = true;
+            if audio_file.format.as_deref() == Some("MP3") {
+                audio_file.chapters = read_id3_chapters(path);
+            }
+            // Lofty reads the properties of encrypted MP4s but not their codec
+            if audio_file.codec.is_none() && audio_file.format.as_deref() == Some("M4A/M4B") && is_drm_protected(path, timeout) {
+                audio_file.fail(drm_error(path));
+            }
         }
         Err(e) => {
-            audio_file.error = Some(e.to_string());
+            let e = match e.kind {
+                FileErrorKind::Corrupt if is_drm_protected(path, timeout) => drm_error(path),
+                _ => e,
+            };
+            audio_file.fail(e);
         }
     }
     
-    Ok(audio_file)
+    audio_file
+}
+
+/// Whether ffprobe reports an encrypted audio stream; the fallback when Lofty can't tell
+fn is_drm_protected(path: &Path, timeout: Duration) -> bool {
+    match crate::ffmpeg::probe::probe_codec_tag(path, timeout) {
+        Ok(tag) => tag.is_some_and(|tag| DRM_CODEC_TAGS.contains(&tag.as_str())),
+        Err(e) => {
+            log::debug!("ffprobe codec tag lookup failed for {}: {e}", path.display());
+            false
+        }
+    }
+}
+
+fn drm_error(path: &Path) -> FileError {
+    FileError::new(FileErrorKind::DrmProtected, format!("File is DRM-protected and can't be converted: {}", path.display()))
+}
+
+/// Kind of a failure to open or read a file
+fn io_error_kind(error: &io::Error) -> FileErrorKind {
+    match error.kind() {
+        io::ErrorKind::NotFound => FileErrorKind::NotFound,
+        io::ErrorKind::PermissionDenied => FileErrorKind::PermissionDenied,
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData => FileErrorKind::Corrupt,
+        _ => FileErrorKind::Unknown,
+    }
+}
+
+/// Kind of a Lofty read failure
+///
+/// The format was already accepted by extension, so content Lofty can't
+/// recognize or parse is damaged rather than unsupported; so are read
+/// errors past the end of a truncated file.
+fn lofty_error_kind(error: &LoftyError) -> FileErrorKind {
+    match error.kind() {
+        LoftyErrorKind::Io(e) => match io_error_kind(e) {
+            FileErrorKind::Unknown => FileErrorKind::Corrupt,
+            kind => kind,
+        },
+        LoftyErrorKind::Alloc(_) | LoftyErrorKind::Fmt(_) => FileErrorKind::Unknown,
+        _ => FileErrorKind::Corrupt,
+    }
+}
+
+/// Whether the file is an iCloud/File Provider placeholder with no local data
+///
+/// Evicted files have zero allocated size and carry `SF_DATALESS`.
+#[cfg(target_os = "macos")]
+fn is_dataless(metadata: &fs::Metadata) -> bool {
+    use std::os::macos::fs::MetadataExt;
+    const SF_DATALESS: u32 = 0x4000_0000;
+    metadata.st_flags() & SF_DATALESS != 0
+}
+
+#[cfg(not(target_os = "macos"))]
+fn is_dataless(_metadata: &fs::Metadata) -> bool {
+    false
 }
 
 /// Validates audio format using Lofty and returns comprehensive metadata
-type AudioProperties = (String, f64, Option<u32>, Option<u32>, Option<u32>);
+type AudioProperties = (String, f64, Option<u32>, Option<u32>, Option<u32>, Option<String>);
 
-fn validate_audio_format(path: &Path) -> Result<AudioProperties> {
-    // First check if we support the file extension
-    let format = match path.extension().and_then(|s| s.to_str()) {
-        Some("mp3") => "MP3",
-        Some("m4a") | Some("m4b") => "M4A/M4B",
-        Some("aac") => "AAC",
-        Some("wav") => "WAV", 
-        Some("flac") => "FLAC",
-        Some(ext) => return Err(AppError::InvalidInput(
-            format!("Unsupported audio format: {ext}")
-        )),
-        None => return Err(AppError::InvalidInput(
-            "Cannot determine file format - file has no extension".to_string()
-        )),
-    };
+fn validate_audio_format(path: &Path) -> std::result::Result<AudioProperties, FileError> {
+    let format = format_from_extension(path)?;
     
     // Try to read the file with Lofty
-    let tagged_file = match Probe::open(path) {
-        Ok(probe) => match probe.read() {
-            Ok(file) => file,
-            Err(e) => return Err(AppError::Metadata(e)),
-        },
-        Err(e) => return Err(AppError::Metadata(e)),
-    };
+    let tagged_file = Probe::open(path)
+        .and_then(|probe| probe.read())
+        .map_err(|e| FileError::new(lofty_error_kind(&e), AppError::Metadata(e).to_string()))?;
     
     let properties = tagged_file.properties();
     let duration = properties.duration().as_secs_f64();
     
     // Validate that we got a reasonable duration
     if duration <= 0.0 {
-        return Err(AppError::InvalidInput(
-            "Audio file has invalid duration (0 seconds)".to_string()
+        return Err(FileError::new(
+            FileErrorKind::Corrupt,
+            AppError::InvalidInput("Audio file has invalid duration (0 seconds)".to_string()).to_string(),
         ));
     }
     
@@ -123,19 +383,157 @@ fn validate_audio_format(path: &Path) -> Result<AudioProperties> {
     let sample_rate = properties.sample_rate();
     let channels = properties.channels().map(|ch| ch as u32);
     
-    Ok((format.to_string(), duration, bitrate, sample_rate, channels))
+    Ok((format.to_string(), duration, bitrate, sample_rate, channels, title_tag(&tagged_file)))
+}
+
+/// Display format for a supported extension
+fn format_from_extension(path: &Path) -> std::result::Result<&'static str, FileError> {
+    let format = match path.extension().and_then(|s| s.to_str()) {
+        Some("mp3") => "MP3",
+        Some("m4a") | Some("m4b") => "M4A/M4B",
+        Some("aac") => "AAC",
+        Some("wav") => "WAV", 
+        Some("flac") => "FLAC",
+        Some(ext) if DRM_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()) => return Err(drm_error(path)),
+        Some(ext) => return Err(FileError::new(
+            FileErrorKind::Unsupported,
+            AppError::InvalidInput(format!("Unsupported audio format: {ext}")).to_string(),
+        )),
+        None => return Err(FileError::new(
+            FileErrorKind::Unsupported,
+            AppError::InvalidInput("Cannot determine file format - file has no extension".to_string()).to_string(),
+        )),
+    };
+    Ok(format)
+}
+
+/// Title tag of the primary tag, or of the first tag when there is no primary one
+fn title_tag(tagged_file: &TaggedFile) -> Option<String> {
+    tagged_file.primary_tag()
+        .or_else(|| tagged_file.first_tag())
+        .and_then(|tag| tag.title().map(|title| title.trim().to_string()))
+        .filter(|title| !title.is_empty())
+}
+
+/// Detects the codec of a file's audio stream
+///
+/// Uses Lofty's file type (and the MP4 sample description for M4A/M4B),
+/// falling back to ffprobe for anything Lofty can't tell apart.
+fn detect_codec(path: &Path, timeout: Duration) -> Option<String> {
+    let file_type = Probe::open(path).ok()?.guess_file_type().ok()?.file_type();
+    let codec = match file_type {
+        Some(FileType::Mpeg) => Some("mp3".to_string()),
+        Some(FileType::Aac) => Some("aac".to_string()),
+        Some(FileType::Flac) => Some("flac".to_string()),
+        Some(FileType::Mp4) => mp4_codec(path),
+        _ => None,
+    };
+    codec.or_else(|| match crate::ffmpeg::probe::probe_codec_name(path, timeout) {
+        Ok(name) => name,
+        Err(e) => {
+            log::debug!("ffprobe codec lookup failed for {}: {e}", path.display());
+            None
+        }
+    })
+}
+
+/// Reads the codec (and AAC profile) from an MP4 file's sample description
+fn mp4_codec(path: &Path) -> Option<String> {
+    use lofty::mp4::{AudioObjectType, Mp4Codec, Mp4File};
+
+    let mut file = fs::File::open(path).ok()?;
+    let mp4 = Mp4File::read_from(&mut file, ParseOptions::new()).ok()?;
+    let properties = mp4.properties();
+    let codec = match properties.codec() {
+        Mp4Codec::AAC => match properties.audio_object_type() {
+            Some(AudioObjectType::SpectralBandReplication) => "aac_he",
+            Some(AudioObjectType::ParametricStereo) => "aac_he_v2",
+            _ => "aac",
+        },
+        Mp4Codec::ALAC => "alac",
+        Mp4Codec::MP3 => "mp3",
+        Mp4Codec::FLAC => "flac",
+        _ => return None,
+    };
+    Some(codec.to_string())
 }
 
 /// Gets comprehensive information about a file list
+///
+/// Always a `Full` analysis; processing goes through here.
 pub fn get_file_list_info<P: AsRef<Path>>(
     file_paths: &[P]
 ) -> Result<FileListInfo> {
-    let files = validate_audio_files(file_paths)?;
-    
+    analyze_file_list(file_paths, AnalysisDepth::Full)
+}
+
+/// Gets information about a file list, analyzed to `depth`
+///
+/// `Quick` skips the stability wait and audio-property probing: each file
+/// is stat'ed and its tags read, so long lists come back in milliseconds.
+pub fn analyze_file_list<P: AsRef<Path>>(
+    file_paths: &[P],
+    depth: AnalysisDepth,
+) -> Result<FileListInfo> {
+    let files = match depth {
+        AnalysisDepth::Full => validate_audio_files(file_paths)?,
+        AnalysisDepth::Quick => {
+            if file_paths.is_empty() {
+                return Err(AppError::InvalidInput(
+                    "No files provided for validation".to_string()
+                ));
+            }
+            file_paths.iter().map(|path| scan_single_file(path.as_ref())).collect()
+        }
+    };
+    Ok(summarize_files(files))
+}
+
+/// Stats a file and reads its title tag without probing audio properties
+///
+/// Only a file that can't be stat'ed or has an unsupported extension is
+/// marked invalid; duration, bitrate and the rest stay unset until a
+/// `Full` analysis.
+fn scan_single_file(path: &Path) -> AudioFile {
+    let mut audio_file = AudioFile::new(path.to_path_buf());
+    audio_file.analysis_depth = AnalysisDepth::Quick;
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            audio_file.fail(FileError::new(io_error_kind(&e), format!("Cannot read file metadata: {e}")));
+            return audio_file;
+        }
+    };
+    audio_file.size = Some(metadata.len() as f64);
+    match format_from_extension(path) {
+        Ok(format) => audio_file.format = Some(format.to_string()),
+        Err(e) => {
+            audio_file.fail(e);
+            return audio_file;
+        }
+    }
+    audio_file.is_valid = true;
+
+    // Reading tags would download an evicted cloud file
+    if is_dataless(&metadata) {
+        audio_file.sync_status = Some(SyncStatus::Dataless);
+        return audio_file;
+    }
+    audio_file.title = Probe::open(path)
+        .map(|probe| probe.options(ParseOptions::new().read_properties(false)))
+        .and_then(|probe| probe.read())
+        .ok()
+        .and_then(|tagged_file| title_tag(&tagged_file));
+    audio_file
+}
+
+/// Totals and error counts for analyzed files
+pub fn summarize_files(files: Vec<AudioFile>) -> FileListInfo {
     let mut total_duration = 0.0;
     let mut total_size = 0.0;
     let mut valid_count = 0;
     let mut invalid_count = 0;
+    let mut error_counts = BTreeMap::new();
     
     for file in &files {
         if file.is_valid {
@@ -144,16 +542,20 @@ pub fn get_file_list_info<P: AsRef<Path>>(
             valid_count += 1;
         } else {
             invalid_count += 1;
+            *error_counts.entry(file.error_kind.unwrap_or(FileErrorKind::Unknown)).or_insert(0) += 1;
         }
     }
     
-    Ok(FileListInfo {
+    FileListInfo {
+        input_stats: InputDurationStats::from_files(&files),
+        disc_groups: disc_groups(&files, &disc_markers()),
         files,
         total_duration,
         total_size,
         valid_count,
         invalid_count,
-    })
+        error_counts,
+    }
 }
 
 #[cfg(test)]
@@ -191,12 +593,263 @@ mod tests {
         assert!(result[0].error.is_some());
     }
 
+    #[test]
+    fn test_failures_map_to_error_kinds() {
+        let temp_dir = TempDir::new().unwrap();
+        let write = |name: &str, contents: &[u8]| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            path
+        };
+        let files = [
+            temp_dir.path().join("missing.mp3"),
+            write("notes.txt", b"not audio"),
+            write("no_extension", b"not audio"),
+            write("garbage.mp3", b"not audio data"),
+            write("empty.m4b", b""),
+            write("Dune.aax", b"encrypted"),
+            write("Song.M4P", b"encrypted"),
+        ];
+        let kinds: Vec<Option<FileErrorKind>> = validate_audio_files(&files).unwrap().iter().map(|file| file.error_kind).collect();
+        use FileErrorKind::*;
+        assert_eq!(kinds, [
+            Some(NotFound), Some(Unsupported), Some(Unsupported), Some(Corrupt), Some(Corrupt),
+            Some(DrmProtected), Some(DrmProtected),
+        ]);
+
+        let io = |kind| io_error_kind(&io::Error::from(kind));
+        assert_eq!(io(io::ErrorKind::NotFound), NotFound);
+        assert_eq!(io(io::ErrorKind::PermissionDenied), PermissionDenied);
+        assert_eq!(io(io::ErrorKind::UnexpectedEof), Corrupt);
+        assert_eq!(io(io::ErrorKind::Interrupted), Unknown);
+        let lofty = |kind| lofty_error_kind(&LoftyError::new(kind));
+        assert_eq!(lofty(LoftyErrorKind::UnknownFormat), Corrupt);
+        assert_eq!(lofty(LoftyErrorKind::Io(io::Error::from(io::ErrorKind::PermissionDenied))), PermissionDenied);
+        assert_eq!(lofty(LoftyErrorKind::Io(io::Error::from(io::ErrorKind::InvalidInput))), Corrupt);
+        assert_eq!(lofty(LoftyErrorKind::SizeMismatch), Corrupt);
+        assert_eq!(lofty(LoftyErrorKind::BadAtom("stsd")), Corrupt);
+
+        let info = get_file_list_info(&files).unwrap();
+        assert_eq!(info.invalid_count, 7);
+        assert_eq!(info.error_counts, BTreeMap::from([(NotFound, 1), (Unsupported, 2), (Corrupt, 2), (DrmProtected, 2)]));
+        assert_eq!(serde_json::to_value(&info).unwrap()["errorCounts"]["drm_protected"], 2);
+        assert_eq!(serde_json::to_value(&info.files[5]).unwrap()["errorKind"], "drm_protected");
+    }
+
+    #[test]
+    fn test_growing_file_is_still_syncing() {
+        use std::io::Write;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("downloading.mp3");
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(b"ID3").unwrap();
+
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let done = Arc::clone(&done);
+            std::thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    file.write_all(&[0u8; 512]).unwrap();
+                    file.flush().unwrap();
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+            })
+        };
+
+        let result = validate_audio_files(&[&path]).unwrap();
+        done.store(true, Ordering::SeqCst);
+        writer.join().unwrap();
+
+        assert!(!result[0].is_valid);
+        assert_eq!(result[0].sync_status, Some(SyncStatus::StillSyncing));
+        assert_eq!(result[0].error_kind, Some(FileErrorKind::StillSyncing));
+        assert!(result[0].error.as_ref().unwrap().contains("still being written"));
+        assert_eq!(serde_json::to_value(&result[0]).unwrap()["syncStatus"], "still_syncing");
+
+        // Once the writer stops, the file is probed and reported invalid as usual
+        let settled = validate_audio_files(&[&path]).unwrap();
+        assert_eq!(settled[0].sync_status, None);
+        assert!(!settled[0].is_valid);
+    }
+
     #[test]
     fn test_get_file_list_info_empty() {
         let result = get_file_list_info::<&str>(&[]);
         assert!(result.is_err());
     }
 
+    fn mixed_files() -> Vec<AudioFile> {
+        let temp_dir = TempDir::new().unwrap();
+        let garbage = temp_dir.path().join("garbage.mp3");
+        fs::write(&garbage, b"not audio").unwrap();
+        let mut files = validate_audio_files(&[garbage.as_path(), Path::new("missing.mp3")]).unwrap();
+        let mut valid = AudioFile::new(PathBuf::from("good.mp3"));
+        valid.is_valid = true;
+        valid.duration = Some(12.0);
+        files.insert(1, valid);
+        files
+    }
+
+    #[test]
+    fn test_fail_policy_keeps_invalid_files() {
+        let (files, skipped) = apply_invalid_input_policy(mixed_files(), InvalidInputPolicy::Fail).unwrap();
+        assert_eq!(files.len(), 3);
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn test_skip_policy_removes_invalid_files() {
+        let (files, skipped) = apply_invalid_input_policy(mixed_files(), InvalidInputPolicy::SkipWithWarning).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, PathBuf::from("good.mp3"));
+        assert_eq!(skipped.len(), 2);
+        assert!(skipped.iter().all(|s| s.error.is_some()));
+        assert_eq!(skipped[1].path, PathBuf::from("missing.mp3"));
+    }
+
+    #[test]
+    fn test_skip_policy_fails_without_valid_files() {
+        let mut files = mixed_files();
+        files.remove(1);
+        let result = apply_invalid_input_policy(files, InvalidInputPolicy::SkipWithWarning);
+        assert!(result.unwrap_err().to_string().contains("2 invalid files skipped"));
+    }
+
+    #[test]
+    fn test_detect_codec_mp3() {
+        let path = Path::new("../media/01 - Introduction.mp3");
+        if !path.exists() {
+            eprintln!("Skipping test - media file not found");
+            return;
+        }
+        assert_eq!(detect_codec(path, Duration::from_secs(ANALYSIS_TIMEOUT_SECS)).as_deref(), Some("mp3"));
+
+        let file = validate_single_file(path, None).unwrap();
+        assert_eq!(file.codec.as_deref(), Some("mp3"));
+        assert_eq!(file.format.as_deref(), Some("MP3"));
+    }
+
+    #[test]
+    fn test_title_tag_read_during_validation() {
+        let source = Path::new("../media/01 - Introduction.mp3");
+        if !source.exists() {
+            eprintln!("Skipping test - media file not found");
+            return;
+        }
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let tagged = temp_dir.path().join("12_the_return.mp3");
+        let untagged = temp_dir.path().join("13_departure.mp3");
+        for path in [&tagged, &untagged] {
+            fs::copy(source, path).unwrap();
+        }
+        let title = crate::metadata::AudiobookMetadata {
+            title: Some("Chapter 12 – The Return".to_string()),
+            ..crate::metadata::AudiobookMetadata::new()
+        };
+        crate::metadata::write_metadata(&tagged, &title).unwrap();
+        for tag_type in [lofty::tag::TagType::Id3v2, lofty::tag::TagType::Id3v1] {
+            tag_type.remove_from_path(&untagged).unwrap();
+        }
+
+        let info = get_file_list_info(&[tagged, untagged]).unwrap();
+        assert_eq!(info.files[0].title.as_deref(), Some("Chapter 12 – The Return"));
+        assert_eq!(info.files[1].title, None);
+    }
+
+    #[test]
+    fn test_quick_analysis_reads_tags_without_probing() {
+        use crate::audio::mp3_duration::fixtures::cbr_mp3;
+        use lofty::config::WriteOptions;
+        use lofty::id3::v2::Id3v2Tag;
+        use lofty::prelude::TagExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let tagged = temp_dir.path().join("01.mp3");
+        fs::write(&tagged, cbr_mp3(200)).unwrap();
+        let mut tag = Id3v2Tag::default();
+        tag.set_title("Prologue".to_string());
+        tag.save_to_path(&tagged, WriteOptions::default()).unwrap();
+        let garbage = temp_dir.path().join("02.mp3");
+        fs::write(&garbage, b"not audio").unwrap();
+        let notes = temp_dir.path().join("notes.txt");
+        fs::write(&notes, b"notes").unwrap();
+        let paths = [tagged, garbage, notes, temp_dir.path().join("missing.mp3")];
+
+        let full = analyze_file_list(&paths, AnalysisDepth::Full).unwrap();
+        let quick = analyze_file_list(&paths, AnalysisDepth::Quick).unwrap();
+        assert!(full.files[0].duration.is_some() && full.files[0].bitrate.is_some());
+        assert_eq!(quick.files[0].title.as_deref(), Some("Prologue"));
+        assert_eq!(quick.files[0].format.as_deref(), Some("MP3"));
+        assert_eq!(quick.files[0].size, full.files[0].size);
+        assert_eq!((quick.files[0].duration, quick.files[0].bitrate, quick.files[0].sample_rate), (None, None, None));
+        assert_eq!(quick.files[0].codec, None);
+
+        // Unreadable audio only shows up when probed
+        assert!(!full.files[1].is_valid);
+        assert!(quick.files[1].is_valid);
+        assert_eq!(quick.files[1].error_kind, None);
+        assert_eq!(quick.files[2].error_kind, Some(FileErrorKind::Unsupported));
+        assert_eq!(quick.files[3].error_kind, Some(FileErrorKind::NotFound));
+        assert_eq!((quick.valid_count, quick.invalid_count, quick.total_duration), (2, 2, 0.0));
+
+        assert!(quick.files.iter().all(|file| file.analysis_depth == AnalysisDepth::Quick));
+        assert_eq!(serde_json::to_value(&quick.files[0]).unwrap()["analysisDepth"], "Quick");
+        assert!(serde_json::to_value(&full.files[0]).unwrap().get("analysisDepth").is_none());
+    }
+
+    #[test]
+    fn test_id3_chapters_read_during_validation() {
+        use crate::metadata::id3_chapters::fixtures::{chap, tag};
+        let source = Path::new("../media/01 - Introduction.mp3");
+        if !source.exists() {
+            eprintln!("Skipping test - media file not found");
+            return;
+        }
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let plain = temp_dir.path().join("plain.mp3");
+        fs::copy(source, &plain).unwrap();
+        lofty::tag::TagType::Id3v2.remove_from_path(&plain).unwrap();
+        let chaptered = temp_dir.path().join("chaptered.mp3");
+        let mut bytes = tag(&[chap("ch0", 0, 4_000, Some("Cold Open"), 3), chap("ch1", 4_000, 9_000, Some("Interview"), 3)], 3);
+        bytes.extend(fs::read(&plain).unwrap());
+        fs::write(&chaptered, bytes).unwrap();
+
+        let info = get_file_list_info(&[chaptered, plain]).unwrap();
+        assert!(info.files.iter().all(|file| file.is_valid));
+        let titles: Vec<&str> = info.files[0].chapters.iter().map(|chapter| chapter.title.as_str()).collect();
+        assert_eq!(titles, ["Cold Open", "Interview"]);
+        assert!(info.files[1].chapters.is_empty());
+    }
+
+    #[test]
+    fn test_detect_codec_aac_in_m4a() {
+        let Ok(ffmpeg) = crate::ffmpeg::locate_ffmpeg() else {
+            eprintln!("Skipping test - FFmpeg not found");
+            return;
+        };
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("tone.m4a");
+        let status = std::process::Command::new(ffmpeg)
+            .args(["-v", "error", "-f", "lavfi", "-i", "sine=frequency=440:duration=1", "-c:a", "aac", "-y"])
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        assert_eq!(detect_codec(&path, Duration::from_secs(ANALYSIS_TIMEOUT_SECS)).as_deref(), Some("aac"));
+    }
+
+    #[test]
+    fn test_detect_codec_unreadable_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("garbage.m4a");
+        fs::write(&path, b"not audio").unwrap();
+        assert_eq!(detect_codec(&path, Duration::from_secs(ANALYSIS_TIMEOUT_SECS)), None);
+    }
+
     #[test]
     fn test_debug_m4b_filename_issues() {
         // Test various M4B filename scenarios that might cause issues
@@ -252,7 +905,7 @@ mod tests {
         println!("Testing real MP3 file: {}", test_mp3);
         
         // Test the validate_single_file function directly
-        let result = validate_single_file(std::path::Path::new(test_mp3));
+        let result = validate_single_file(std::path::Path::new(test_mp3), None);
         println!("validate_single_file result: {:?}", result);
         
         // Test JSON serialization to see field names
@@ -293,7 +946,7 @@ mod tests {
         
         // Test our format validation specifically
         match validate_audio_format(std::path::Path::new(test_mp3)) {
-            Ok((format, duration, bitrate, sample_rate, channels)) => {
+            Ok((format, duration, bitrate, sample_rate, channels, _title)) => {
                 println!("  validate_audio_format SUCCESS: format={}, duration={}, bitrate={:?}, sample_rate={:?}, channels={:?}", 
                          format, duration, bitrate, sample_rate, channels);
             }
@@ -388,4 +1041,40 @@ mod tests {
             }
         }
     }
+
+    fn stuck_prober(mut audio_file: AudioFile, _timeout: Duration) -> AudioFile {
+        std::thread::sleep(Duration::from_secs(2));
+        audio_file.is_valid = true;
+        audio_file
+    }
+
+    fn quick_prober(mut audio_file: AudioFile, _timeout: Duration) -> AudioFile {
+        audio_file.format = Some("MP3".to_string());
+        audio_file.is_valid = true;
+        audio_file
+    }
+
+    #[test]
+    fn test_stuck_probe_marks_file_timed_out() {
+        let started = std::time::Instant::now();
+        let file = probe_with_timeout(AudioFile::new(PathBuf::from("/books/damaged.mp3")), stuck_prober, Duration::from_millis(50));
+        assert!(started.elapsed() < Duration::from_secs(1), "took {:?}", started.elapsed());
+        assert!(!file.is_valid);
+        assert_eq!(file.error_kind, Some(FileErrorKind::Timeout));
+        assert!(file.error.as_deref().is_some_and(|error| error.contains("/books/damaged.mp3")));
+    }
+
+    #[test]
+    fn test_quick_probe_result_passes_through() {
+        let file = probe_with_timeout(AudioFile::new(PathBuf::from("/books/fine.mp3")), quick_prober, Duration::from_secs(5));
+        assert!(file.is_valid);
+        assert_eq!(file.format.as_deref(), Some("MP3"));
+        assert_eq!(file.error_kind, None);
+    }
+
+    #[test]
+    fn test_zero_analysis_timeout_rejected() {
+        assert!(set_analysis_timeout(Some(Duration::ZERO)).is_err());
+        assert_eq!(analysis_timeout(), Duration::from_secs(ANALYSIS_TIMEOUT_SECS));
+    }
 }
@@ -0,0 +1,156 @@
+//! Output filename sanitizing for cross-platform safety
+//!
+//! Titles like `He said: "Go!"` make filenames that are fine on macOS but
+//! invalid on Windows or SMB shares. `sanitize_filename` rewrites a single
+//! path component so it is valid for the chosen target.
+
+use super::constants::MAX_FILENAME_BYTES;
+
+/// Characters Windows and SMB shares reject in a path component
+const WINDOWS_FORBIDDEN: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Device names Windows reserves regardless of extension
+const WINDOWS_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Replacement for characters that can't appear in a filename
+const REPLACEMENT: char = '_';
+
+/// Which filesystems a generated filename must be valid on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilenameTarget {
+    /// Valid on Windows, SMB shares, macOS and Linux
+    CrossPlatform,
+    /// Valid on the local filesystem only
+    Native,
+}
+
+impl FilenameTarget {
+    /// Picks the target from the `strict_cross_platform` preference
+    ///
+    /// Windows has no looser native rules, so it is always cross-platform.
+    pub fn from_preference(strict_cross_platform: bool) -> Self {
+        if strict_cross_platform || cfg!(windows) {
+            FilenameTarget::CrossPlatform
+        } else {
+            FilenameTarget::Native
+        }
+    }
+
+    fn is_forbidden(self, c: char) -> bool {
+        match self {
+            FilenameTarget::CrossPlatform => c.is_control() || WINDOWS_FORBIDDEN.contains(&c),
+            // ':' is the legacy HFS separator and shows up as '/' in Finder
+            FilenameTarget::Native => c == '\0' || c == '/' || (cfg!(target_os = "macos") && c == ':'),
+        }
+    }
+}
+
+/// Sanitizes a single filename (no directories) for the given target
+///
+/// Forbidden characters become `_`, surrounding whitespace is trimmed and,
+/// for cross-platform targets, trailing dots/spaces and reserved device
+/// names are fixed up. Never returns an empty string.
+pub fn sanitize_filename(name: &str, target: FilenameTarget) -> String {
+    let replaced: String = name.chars()
+        .map(|c| if target.is_forbidden(c) { REPLACEMENT } else { c })
+        .collect();
+    let mut sanitized = replaced.trim().to_string();
+
+    if target == FilenameTarget::CrossPlatform {
+        sanitized.truncate(sanitized.trim_end_matches(['.', ' ']).len());
+        if is_reserved_device_name(&sanitized) {
+            sanitized.insert(0, REPLACEMENT);
+        }
+    }
+
+    truncate_to_bytes(&mut sanitized, MAX_FILENAME_BYTES);
+    if sanitized.is_empty() {
+        sanitized.push(REPLACEMENT);
+    }
+    sanitized
+}
+
+/// True when the part before the first dot is a Windows device name
+fn is_reserved_device_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    WINDOWS_RESERVED.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+/// Shortens to at most `max` bytes, keeping the extension when there is one
+fn truncate_to_bytes(name: &mut String, max: usize) {
+    if name.len() <= max {
+        return;
+    }
+    let extension = name.rfind('.')
+        .filter(|&dot| dot > 0 && name.len() - dot <= max / 2)
+        .map(|dot| name.split_off(dot))
+        .unwrap_or_default();
+    let mut end = max - extension.len();
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    name.truncate(end);
+    name.push_str(&extension);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRICT: FilenameTarget = FilenameTarget::CrossPlatform;
+
+    #[test]
+    fn test_forbidden_characters_are_replaced() {
+        assert_eq!(sanitize_filename(r#"He said: "Go!".m4b"#, STRICT), "He said_ _Go!_.m4b");
+        for c in ['<', '>', ':', '"', '/', '\\', '|', '?', '*', '\u{0}', '\u{1f}'] {
+            assert_eq!(sanitize_filename(&format!("a{c}b"), STRICT), "a_b", "char {c:?}");
+        }
+    }
+
+    #[test]
+    fn test_trailing_dots_and_spaces_are_removed() {
+        assert_eq!(sanitize_filename("Volume 1... ", STRICT), "Volume 1");
+        assert_eq!(sanitize_filename("  padded  ", STRICT), "padded");
+        assert_eq!(sanitize_filename("...", STRICT), "_");
+        assert_eq!(sanitize_filename("", STRICT), "_");
+    }
+
+    #[test]
+    fn test_reserved_device_names() {
+        for name in ["CON", "con", "PRN", "AUX", "NUL", "COM1", "com9", "LPT1", "LPT9"] {
+            assert_eq!(sanitize_filename(name, STRICT), format!("_{name}"));
+        }
+        assert_eq!(sanitize_filename("nul.m4b", STRICT), "_nul.m4b");
+        assert_eq!(sanitize_filename("CON .m4b", STRICT), "_CON .m4b");
+        // Only exact device names are reserved
+        assert_eq!(sanitize_filename("Console.m4b", STRICT), "Console.m4b");
+        assert_eq!(sanitize_filename("COM10.m4b", STRICT), "COM10.m4b");
+    }
+
+    #[test]
+    fn test_native_target_keeps_windows_only_characters() {
+        let native = sanitize_filename("What? <Really>", FilenameTarget::Native);
+        assert_eq!(native, "What? <Really>");
+        assert_eq!(sanitize_filename("a/b", FilenameTarget::Native), "a_b");
+        assert_eq!(sanitize_filename("CON", FilenameTarget::Native), "CON");
+    }
+
+    #[test]
+    fn test_long_names_keep_extension_and_char_boundaries() {
+        let long = format!("{}.m4b", "é".repeat(200));
+        let sanitized = sanitize_filename(&long, STRICT);
+        assert!(sanitized.len() <= MAX_FILENAME_BYTES);
+        assert!(sanitized.ends_with(".m4b"));
+    }
+
+    #[test]
+    fn test_from_preference() {
+        assert_eq!(FilenameTarget::from_preference(true), FilenameTarget::CrossPlatform);
+        let expected = if cfg!(windows) { FilenameTarget::CrossPlatform } else { FilenameTarget::Native };
+        assert_eq!(FilenameTarget::from_preference(false), expected);
+    }
+}
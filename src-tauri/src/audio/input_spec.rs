@@ -0,0 +1,264 @@
+//! Typed processing inputs with per-file options
+//!
+//! Processing requests used to carry bare path strings. An input is now an
+//! `InputFileSpec` with optional ordering, gain, trims and a chapter title;
+//! a plain string still deserializes as a spec with only the path set, so
+//! existing callers keep working and both forms can be mixed in one array.
+
+use super::constants::MAX_INPUT_GAIN_DB;
+use super::AudioFile;
+use crate::errors::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// One input file of a processing request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "InputFileArg")]
+#[serde(rename_all = "camelCase")]
+pub struct InputFileSpec {
+    /// Path of the audio file
+    pub path: PathBuf,
+    /// Position in the book; inputs without one keep their list position
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<u32>,
+    /// Per-file options applied during processing
+    #[serde(flatten)]
+    pub options: InputOptions,
+}
+
+/// Per-file processing options carried from the request to the merge plan
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct InputOptions {
+    /// Volume adjustment in dB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gain_db: Option<f32>,
+    /// Portion of the file to keep
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trim: Option<TrimSpec>,
+    /// Chapter title overriding the one derived from tags or the filename
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chapter_title: Option<String>,
+}
+
+impl InputOptions {
+    /// True when no option is set
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Start and end trims in seconds from the start of the file
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TrimSpec {
+    /// Seconds to skip at the start
+    #[serde(default)]
+    pub start_secs: Option<f64>,
+    /// Position to stop at
+    #[serde(default)]
+    pub end_secs: Option<f64>,
+}
+
+/// Wire forms of an input: a bare path or a full spec
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InputFileArg {
+    Path(PathBuf),
+    Spec(InputFileObject),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct InputFileObject {
+    path: PathBuf,
+    #[serde(default)]
+    order: Option<u32>,
+    #[serde(default)]
+    gain_db: Option<f32>,
+    #[serde(default)]
+    trim: Option<TrimSpec>,
+    #[serde(default)]
+    chapter_title: Option<String>,
+}
+
+impl From<InputFileArg> for InputFileSpec {
+    fn from(arg: InputFileArg) -> Self {
+        match arg {
+            InputFileArg::Path(path) => InputFileSpec::from(path),
+            InputFileArg::Spec(spec) => InputFileSpec {
+                path: spec.path,
+                order: spec.order,
+                options: InputOptions {
+                    gain_db: spec.gain_db,
+                    trim: spec.trim,
+                    chapter_title: spec.chapter_title,
+                },
+            },
+        }
+    }
+}
+
+impl From<PathBuf> for InputFileSpec {
+    fn from(path: PathBuf) -> Self {
+        Self { path, order: None, options: InputOptions::default() }
+    }
+}
+
+impl InputFileSpec {
+    /// Checks the options are usable
+    pub fn validate(&self) -> Result<()> {
+        let name = self.path.display();
+        if self.path.as_os_str().is_empty() {
+            return Err(AppError::InvalidInput("Input path is empty".to_string()));
+        }
+        if let Some(gain) = self.options.gain_db {
+            if !gain.is_finite() || gain.abs() > MAX_INPUT_GAIN_DB {
+                return Err(AppError::InvalidInput(format!(
+                    "Gain for {name} must be between -{MAX_INPUT_GAIN_DB} and {MAX_INPUT_GAIN_DB} dB, got: {gain}"
+                )));
+            }
+        }
+        if let Some(trim) = &self.options.trim {
+            trim.validate().map_err(|reason| AppError::InvalidInput(format!("Invalid trim for {name}: {reason}")))?;
+        }
+        if self.options.chapter_title.as_deref().is_some_and(|title| title.trim().is_empty()) {
+            return Err(AppError::InvalidInput(format!("Chapter title for {name} is empty")));
+        }
+        Ok(())
+    }
+}
+
+impl TrimSpec {
+    fn validate(&self) -> std::result::Result<(), &'static str> {
+        let valid = |secs: Option<f64>| secs.is_none_or(|secs| secs.is_finite() && secs >= 0.0);
+        if !valid(self.start_secs) || !valid(self.end_secs) {
+            return Err("times must be non-negative seconds");
+        }
+        match (self.start_secs, self.end_secs) {
+            (Some(start), Some(end)) if end <= start => Err("end must be after start"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Validates the specs and returns them in book order
+///
+/// An input's order defaults to its list position; explicit orders must be
+/// unique, and ties with a default position keep list order.
+pub fn resolve_input_order(specs: Vec<InputFileSpec>) -> Result<Vec<InputFileSpec>> {
+    if specs.is_empty() {
+        return Err(AppError::InvalidInput("No files provided".to_string()));
+    }
+    let mut seen = HashSet::new();
+    for spec in &specs {
+        spec.validate()?;
+        if let Some(order) = spec.order {
+            if !seen.insert(order) {
+                return Err(AppError::InvalidInput(format!("Input order {order} is used more than once")));
+            }
+        }
+    }
+    let mut indexed: Vec<(usize, InputFileSpec)> = specs.into_iter().enumerate().collect();
+    indexed.sort_by_key(|(index, spec)| spec.order.map_or(*index as u64, u64::from));
+    Ok(indexed.into_iter().map(|(_, spec)| spec).collect())
+}
+
+/// Attaches each spec's options to its analyzed file
+///
+/// `files` must come from analyzing the specs' paths in the same order.
+pub fn attach_input_options(files: &mut [AudioFile], specs: &[InputFileSpec]) {
+    for (file, spec) in files.iter_mut().zip(specs) {
+        file.options = spec.options.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(value: serde_json::Value) -> serde_json::Result<Vec<InputFileSpec>> {
+        serde_json::from_value(value)
+    }
+
+    #[test]
+    fn test_plain_strings_still_deserialize() {
+        let specs = parse(json!(["/books/01.mp3", "/books/02.mp3"])).unwrap();
+        assert_eq!(specs, [InputFileSpec::from(PathBuf::from("/books/01.mp3")), InputFileSpec::from(PathBuf::from("/books/02.mp3"))]);
+        assert!(specs.iter().all(|spec| spec.options.is_empty()));
+    }
+
+    #[test]
+    fn test_full_and_mixed_specs() {
+        let specs = parse(json!([
+            "/books/01.mp3",
+            {
+                "path": "/books/02.mp3",
+                "order": 0,
+                "gainDb": -3.5,
+                "trim": {"startSecs": 1.5},
+                "chapterTitle": "Prologue"
+            },
+            {"path": "/books/03.mp3"}
+        ])).unwrap();
+        assert_eq!(specs[0].path, PathBuf::from("/books/01.mp3"));
+        assert_eq!(specs[1].order, Some(0));
+        assert_eq!(specs[1].options.gain_db, Some(-3.5));
+        assert_eq!(specs[1].options.trim, Some(TrimSpec { start_secs: Some(1.5), end_secs: None }));
+        assert_eq!(specs[1].options.chapter_title.as_deref(), Some("Prologue"));
+        assert_eq!(specs[2], InputFileSpec::from(PathBuf::from("/books/03.mp3")));
+    }
+
+    #[test]
+    fn test_malformed_specs_are_rejected() {
+        assert!(parse(json!([{"order": 1}])).is_err()); // no path
+        assert!(parse(json!([{"path": "/a.mp3", "gainDB": 2.0}])).is_err()); // misspelled field
+        assert!(parse(json!([{"path": "/a.mp3", "trim": {"start": 2.0}}])).is_err());
+        assert!(parse(json!([{"path": "/a.mp3", "order": -1}])).is_err());
+        assert!(parse(json!([42])).is_err());
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let spec = InputFileSpec {
+            path: PathBuf::from("/books/01.mp3"),
+            order: Some(2),
+            options: InputOptions { gain_db: Some(1.0), trim: None, chapter_title: Some("One".to_string()) },
+        };
+        let value = serde_json::to_value(&spec).unwrap();
+        assert_eq!(value, json!({"path": "/books/01.mp3", "order": 2, "gainDb": 1.0, "chapterTitle": "One"}));
+        assert_eq!(serde_json::from_value::<InputFileSpec>(value).unwrap(), spec);
+    }
+
+    #[test]
+    fn test_validation() {
+        let spec = |options: InputOptions| InputFileSpec { path: PathBuf::from("/a.mp3"), order: None, options };
+        assert!(spec(InputOptions { gain_db: Some(30.0), ..Default::default() }).validate().is_err());
+        assert!(spec(InputOptions { gain_db: Some(f32::NAN), ..Default::default() }).validate().is_err());
+        assert!(spec(InputOptions { chapter_title: Some("  ".to_string()), ..Default::default() }).validate().is_err());
+        let trim = |start_secs, end_secs| spec(InputOptions { trim: Some(TrimSpec { start_secs, end_secs }), ..Default::default() });
+        assert!(trim(Some(5.0), Some(5.0)).validate().is_err());
+        assert!(trim(Some(-1.0), None).validate().is_err());
+        assert!(trim(Some(1.0), Some(10.0)).validate().is_ok());
+        assert!(InputFileSpec::from(PathBuf::new()).validate().is_err());
+    }
+
+    #[test]
+    fn test_resolve_input_order() {
+        let specs = parse(json!([
+            "/books/a.mp3",
+            {"path": "/books/b.mp3", "order": 0},
+            "/books/c.mp3",
+            {"path": "/books/d.mp3", "order": 5}
+        ])).unwrap();
+        let ordered: Vec<PathBuf> = resolve_input_order(specs).unwrap().into_iter().map(|spec| spec.path).collect();
+        // a keeps position 0 and stays ahead of b's explicit 0; c keeps 2
+        assert_eq!(ordered, ["/books/a.mp3", "/books/b.mp3", "/books/c.mp3", "/books/d.mp3"].map(PathBuf::from));
+
+        let duplicate = parse(json!([{"path": "/a.mp3", "order": 1}, {"path": "/b.mp3", "order": 1}])).unwrap();
+        assert!(resolve_input_order(duplicate).unwrap_err().to_string().contains("more than once"));
+        assert!(resolve_input_order(Vec::new()).is_err());
+    }
+}
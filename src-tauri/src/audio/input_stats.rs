@@ -0,0 +1,112 @@
+//! Duration statistics of a book's inputs
+//!
+//! The pre-flight list, the plan and the completion summary all show how
+//! the inputs contribute to the book: "23 files, longest 48 min, shortest
+//! 2 min, average 19 min". Only valid inputs with a known duration count.
+
+use super::AudioFile;
+use serde::{Deserialize, Serialize};
+
+/// Count and spread of input durations, in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputDurationStats {
+    /// Inputs with a known duration
+    pub count: usize,
+    /// Shortest input
+    pub min_secs: f64,
+    /// Longest input
+    pub max_secs: f64,
+    /// Average input
+    pub mean_secs: f64,
+    /// Middle input; the average of the two middle ones for an even count
+    pub median_secs: f64,
+    /// All inputs together
+    pub total_secs: f64,
+}
+
+impl InputDurationStats {
+    /// Statistics of the valid files' durations; None when no file has one
+    pub fn from_files(files: &[AudioFile]) -> Option<Self> {
+        let durations: Vec<f64> = files.iter()
+            .filter(|f| f.is_valid)
+            .filter_map(|f| f.duration)
+            .collect();
+        duration_stats(&durations)
+    }
+}
+
+/// Statistics of `durations`; None when empty
+///
+/// Negative and non-finite values are ignored, as a missing duration would be.
+pub fn duration_stats(durations: &[f64]) -> Option<InputDurationStats> {
+    let mut sorted: Vec<f64> = durations.iter().copied()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .collect();
+    sorted.sort_by(f64::total_cmp);
+    let (&min_secs, &max_secs) = (sorted.first()?, sorted.last()?);
+    let count = sorted.len();
+    let total_secs: f64 = sorted.iter().sum();
+    let middle = count / 2;
+    let median_secs = if count.is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    };
+    Some(InputDurationStats {
+        count,
+        min_secs,
+        max_secs,
+        mean_secs: total_secs / count as f64,
+        median_secs,
+        total_secs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_single_input() {
+        let stats = duration_stats(&[125.0]).unwrap();
+        assert_eq!(stats, InputDurationStats {
+            count: 1,
+            min_secs: 125.0,
+            max_secs: 125.0,
+            mean_secs: 125.0,
+            median_secs: 125.0,
+            total_secs: 125.0,
+        });
+        assert_eq!(duration_stats(&[]), None);
+        assert_eq!(duration_stats(&[f64::NAN, -1.0]), None);
+    }
+
+    #[test]
+    fn test_skewed_inputs() {
+        // Short credits around long chapters: the median stays with the chapters
+        let stats = duration_stats(&[2880.0, 30.0, 2700.0, 45.0, 2940.0]).unwrap();
+        assert_eq!((stats.count, stats.min_secs, stats.max_secs), (5, 30.0, 2940.0));
+        assert_eq!(stats.total_secs, 8595.0);
+        assert_eq!(stats.mean_secs, 1719.0);
+        assert_eq!(stats.median_secs, 2700.0);
+
+        let even = duration_stats(&[60.0, 10.0, 600.0, 20.0]).unwrap();
+        assert_eq!(even.median_secs, 40.0);
+    }
+
+    #[test]
+    fn test_only_valid_files_with_durations_count() {
+        let file = |duration: Option<f64>, is_valid: bool| {
+            let mut file = AudioFile::new(PathBuf::from("part.mp3"));
+            file.duration = duration;
+            file.is_valid = is_valid;
+            file
+        };
+        let files = [file(Some(100.0), true), file(Some(5000.0), false), file(None, true), file(Some(300.0), true)];
+        let stats = InputDurationStats::from_files(&files).unwrap();
+        assert_eq!((stats.count, stats.total_secs, stats.median_secs), (2, 400.0, 200.0));
+        assert_eq!(InputDurationStats::from_files(&files[1..3]), None);
+    }
+}
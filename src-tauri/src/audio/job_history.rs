@@ -0,0 +1,311 @@
+//! Processing history
+//!
+//! Every finished run (successful or not) appends one record to a JSON
+//! lines file under the app data directory, so the UI can list past jobs
+//! by label rather than session UUID.
+
+use super::constants::PROCESSING_HISTORY_FILENAME;
+use super::input_stats::InputDurationStats;
+use super::post_hook::HookOutcome;
+use super::session::ProcessingSession;
+use super::timeline::StageTiming;
+use super::verification::VerificationOutcome;
+use crate::atomic_write::atomic_write;
+use crate::errors::{AppError, CancelReason, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Process-wide history, initialized once the app data directory is known
+static GLOBAL_PROCESSING_HISTORY: OnceLock<ProcessingHistory> = OnceLock::new();
+
+/// Outcome of one processing run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRecord {
+    /// Session that ran the job
+    pub session_id: String,
+    /// Job label, if the session had one
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Job notes, if the session had them
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Requested output path
+    pub output_path: PathBuf,
+    /// Unix timestamp (seconds) when the run ended
+    pub finished_at: u64,
+    /// Whether the output was produced
+    pub succeeded: bool,
+    /// Completion message or error text
+    pub message: String,
+    /// Why the run was cancelled, if it was
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancel_reason: Option<CancelReason>,
+    /// Hash of the run's inputs, settings and metadata (see `plan_hash`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_hash: Option<String>,
+    /// Result of the post-processing hook, if one ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_process_hook: Option<HookOutcome>,
+    /// Result of background verification, once it has run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<VerificationOutcome>,
+    /// Duration spread of the inputs, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_stats: Option<InputDurationStats>,
+    /// Time spent in each stage, as in the completion payload
+    #[serde(default)]
+    pub timeline: Vec<StageTiming>,
+}
+
+impl HistoryRecord {
+    /// Builds a record for a session's result
+    pub fn from_result(session: &ProcessingSession, output_path: &Path, result: &Result<String>) -> Self {
+        let label = session.label();
+        Self {
+            session_id: session.id(),
+            label: label.as_ref().map(|l| l.label.clone()),
+            notes: label.and_then(|l| l.notes),
+            output_path: output_path.to_path_buf(),
+            finished_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            succeeded: result.is_ok(),
+            message: match result {
+                Ok(message) => message.clone(),
+                Err(e) => e.to_string(),
+            },
+            cancel_reason: result.as_ref().err().and_then(AppError::cancel_reason),
+            plan_hash: None,
+            post_process_hook: None,
+            verification: None,
+            input_stats: None,
+            timeline: session.timeline(),
+        }
+    }
+}
+
+/// Append-only history file
+#[derive(Debug, Clone)]
+pub struct ProcessingHistory {
+    file: PathBuf,
+}
+
+impl ProcessingHistory {
+    /// Creates a history backed by the given file
+    pub fn new<P: AsRef<Path>>(file: P) -> Self {
+        Self { file: file.as_ref().to_path_buf() }
+    }
+
+    /// Appends a record
+    pub fn append(&self, record: &HistoryRecord) -> Result<()> {
+        if let Some(parent) = self.file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_string(record)
+            .map_err(|e| AppError::General(format!("Cannot serialize history record: {e}")))?;
+        line.push('\n');
+        OpenOptions::new().create(true).append(true).open(&self.file)?
+            .write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Forces appended records to disk
+    pub fn sync(&self) -> Result<()> {
+        if self.file.exists() {
+            OpenOptions::new().append(true).open(&self.file)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Reads all records, oldest first, skipping lines that don't parse
+    pub fn records(&self) -> Result<Vec<HistoryRecord>> {
+        if !self.file.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.file)?;
+        Ok(content.lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    log::warn!("Skipping unreadable history record: {e}");
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Attaches a verification outcome to the session's record
+    ///
+    /// Rewrites the file; lines that don't parse are kept as they are.
+    /// Returns whether a record for the session was found.
+    pub fn set_verification(&self, session_id: &str, outcome: &VerificationOutcome) -> Result<bool> {
+        if !self.file.exists() {
+            return Ok(false);
+        }
+        let content = fs::read_to_string(&self.file)?;
+        let mut found = false;
+        let mut rewritten = String::with_capacity(content.len());
+        for line in content.lines() {
+            match serde_json::from_str::<HistoryRecord>(line) {
+                Ok(mut record) if record.session_id == session_id => {
+                    found = true;
+                    record.verification = Some(outcome.clone());
+                    rewritten.push_str(&serde_json::to_string(&record)
+                        .map_err(|e| AppError::General(format!("Cannot serialize history record: {e}")))?);
+                }
+                _ => rewritten.push_str(line),
+            }
+            rewritten.push('\n');
+        }
+        if found {
+            atomic_write(&self.file, rewritten.as_bytes())?;
+        }
+        Ok(found)
+    }
+}
+
+/// Initializes the process-wide history under the given app data directory
+pub fn init_processing_history<P: AsRef<Path>>(app_data_dir: P) {
+    let history = ProcessingHistory::new(app_data_dir.as_ref().join(PROCESSING_HISTORY_FILENAME));
+    if GLOBAL_PROCESSING_HISTORY.set(history).is_err() {
+        log::warn!("Processing history already initialized");
+    }
+}
+
+/// Records a run in the process-wide history, if available
+///
+/// Failures are logged so a broken history never affects the run itself.
+pub fn record_run(record: &HistoryRecord) {
+    let Some(history) = GLOBAL_PROCESSING_HISTORY.get() else {
+        return;
+    };
+    if let Err(e) = history.append(record) {
+        log::warn!("Failed to record processing history: {e}");
+    }
+}
+
+/// Attaches a verification outcome in the process-wide history, if available
+pub fn record_verification(session_id: &str, outcome: &VerificationOutcome) {
+    let Some(history) = GLOBAL_PROCESSING_HISTORY.get() else {
+        return;
+    };
+    match history.set_verification(session_id, outcome) {
+        Ok(true) => {}
+        Ok(false) => log::warn!("No history record for verified session {session_id}"),
+        Err(e) => log::warn!("Failed to record verification: {e}"),
+    }
+}
+
+/// Syncs the process-wide history to disk before the app exits
+pub fn flush_processing_history() {
+    if let Some(Err(e)) = GLOBAL_PROCESSING_HISTORY.get().map(ProcessingHistory::sync) {
+        log::warn!("Failed to flush processing history: {e}");
+    }
+}
+
+/// Returns all recorded runs, or none when history isn't initialized
+pub fn processing_history() -> Result<Vec<HistoryRecord>> {
+    match GLOBAL_PROCESSING_HISTORY.get() {
+        Some(history) => history.records(),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::session::JobLabel;
+    use crate::audio::timeline::TimelineStage;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_includes_label_and_notes() {
+        let session = ProcessingSession::new();
+        let output = Path::new("/books/Dune.m4b");
+        session.set_label(JobLabel::new(None, Some("lower bitrate".to_string()), output));
+
+        session.start_stage(TimelineStage::Analyzing);
+        session.start_stage(TimelineStage::Converting);
+        session.finish_timeline();
+
+        let record = HistoryRecord::from_result(&session, output, &Ok("done".to_string()));
+        assert_eq!(record.session_id, session.id());
+        assert_eq!(record.timeline, session.timeline());
+        let stages: Vec<TimelineStage> = record.timeline.iter().map(|timing| timing.stage).collect();
+        assert_eq!(stages, [TimelineStage::Analyzing, TimelineStage::Converting]);
+        assert!(record.timeline.iter().all(|timing| timing.finished));
+        assert_eq!(serde_json::to_value(&record).unwrap()["timeline"][1]["stage"], "converting");
+        assert_eq!(record.label.as_deref(), Some("Dune"));
+        assert_eq!(record.notes.as_deref(), Some("lower bitrate"));
+        assert!(record.succeeded);
+
+        let failed = HistoryRecord::from_result(&session, output, &Err(AppError::General("boom".to_string())));
+        assert!(!failed.succeeded);
+        assert!(failed.message.contains("boom"));
+        assert_eq!(failed.cancel_reason, None);
+    }
+
+    #[test]
+    fn test_record_keeps_cancel_reason() {
+        let session = ProcessingSession::new();
+        session.cancel(CancelReason::Shutdown);
+        let record = HistoryRecord::from_result(&session, Path::new("/books/a.m4b"), &Err(session.cancellation_error()));
+        assert_eq!(record.cancel_reason, Some(CancelReason::Shutdown));
+
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["cancelReason"], "shutdown");
+    }
+
+    #[test]
+    fn test_append_and_read_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let history = ProcessingHistory::new(temp_dir.path().join("nested").join("history.jsonl"));
+        assert!(history.records().unwrap().is_empty());
+
+        let session = ProcessingSession::new();
+        let first = HistoryRecord::from_result(&session, Path::new("a.m4b"), &Ok("ok".to_string()));
+        let second = HistoryRecord::from_result(&session, Path::new("b.m4b"), &Ok("ok".to_string()));
+        history.append(&first).unwrap();
+        history.append(&second).unwrap();
+
+        assert_eq!(history.records().unwrap(), vec![first, second]);
+    }
+
+    #[test]
+    fn test_unreadable_lines_are_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("history.jsonl");
+        fs::write(&file, "not json\n{\"sessionId\":\"x\",\"outputPath\":\"a.m4b\",\"finishedAt\":1,\"succeeded\":true,\"message\":\"ok\"}\n").unwrap();
+
+        let records = ProcessingHistory::new(&file).records().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].label, None);
+    }
+
+    #[test]
+    fn test_set_verification_updates_only_that_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("history.jsonl");
+        let history = ProcessingHistory::new(&file);
+        let (first, second) = (ProcessingSession::new(), ProcessingSession::new());
+        history.append(&HistoryRecord::from_result(&first, Path::new("a.m4b"), &Ok("ok".to_string()))).unwrap();
+        fs::write(&file, format!("{}not json\n", fs::read_to_string(&file).unwrap())).unwrap();
+        history.append(&HistoryRecord::from_result(&second, Path::new("b.m4b"), &Ok("ok".to_string()))).unwrap();
+
+        let outcome = VerificationOutcome { passed: false, error: Some("truncated".to_string()), verified_at: 7 };
+        assert!(history.set_verification(&second.id(), &outcome).unwrap());
+        assert!(!history.set_verification("unknown", &outcome).unwrap());
+
+        let records = history.records().unwrap();
+        assert_eq!(records[0].verification, None);
+        assert_eq!(records[1].verification, Some(outcome));
+        assert!(fs::read_to_string(&file).unwrap().contains("not json\n"));
+    }
+}
@@ -0,0 +1,520 @@
+//! Batch job queue
+//!
+//! Several books can be queued and then processed one after another. Jobs
+//! are plain data until they run, so batch-wide helpers like series
+//! auto-numbering are simple transformations applied at enqueue time.
+//!
+//! Each job's own preflight only sees that job, so several queued books
+//! can each fit on the output drive yet fill it together. The registry
+//! therefore forecasts disk space per volume: a job needs room for its own
+//! output plus every running or earlier queued job writing to the same
+//! volume, with `QUEUE_DISK_RESERVE_BYTES` kept clear.
+//!
+//! A job also carries the preferences in effect when it was queued, so
+//! changing them while books wait doesn't reach some jobs and not others.
+//! `refresh_job_preferences` moves a queued job onto the current ones.
+//!
+//! A job that fails is kept with its error rather than dropped, and the
+//! queue carries on with the next one; `retry_failed_job` queues it again.
+
+use super::AudioSettings;
+use super::constants::QUEUE_DISK_RESERVE_BYTES;
+use super::disk_space::volume_space;
+use super::file_list::get_file_list_info;
+use super::large_output::projected_output_bytes;
+use super::output_dir::resolve_output_path;
+use super::preferences::PreferenceSnapshot;
+use super::session::JobLabel;
+use crate::errors::{AppError, ErrorPayload, Result};
+use crate::metadata::AudiobookMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+/// Everything needed to process one audiobook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudiobookJob {
+    /// Input files in merge order
+    pub file_paths: Vec<String>,
+    /// Output settings for this book
+    pub settings: AudioSettings,
+    /// Metadata written to the output
+    pub metadata: Option<AudiobookMetadata>,
+    /// Label shown instead of the job ID (defaults to the output filename stem)
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Free-text notes
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+/// Fills series name and index across a batch in queue order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeriesAutoNumber {
+    /// Series name given to every job without one
+    pub series: String,
+    /// Index of the first job in the batch
+    pub start: u32,
+}
+
+/// A job waiting in the queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedJob {
+    /// Queue-assigned job ID
+    pub id: String,
+    pub job: AudiobookJob,
+    /// Preferences taken when the job was queued or last refreshed
+    #[serde(default)]
+    pub preferences: PreferenceSnapshot,
+}
+
+/// A job taken off the queue that failed, with its error
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedJob {
+    pub queued: QueuedJob,
+    pub error: ErrorPayload,
+}
+
+/// What the queue does with a job the forecast says won't fit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiskSpacePolicy {
+    /// Log a warning and start the job anyway
+    #[default]
+    Warn,
+    /// Move the job to the back of the queue and go on with the others
+    Defer,
+}
+
+/// Expected output of a queued or running job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobEstimate {
+    pub id: String,
+    /// Volume the output is written to; None when it can't be determined
+    pub volume: Option<String>,
+    /// Projected output size in bytes
+    pub output_bytes: u64,
+}
+
+/// Disk space forecast for one queued job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpaceForecast {
+    pub job_id: String,
+    pub volume: Option<String>,
+    /// This job's output plus the running and earlier queued jobs on the same volume
+    pub required_bytes: u64,
+    /// Free space on the volume now, when known
+    pub available_bytes: Option<u64>,
+    /// Whether the required bytes plus the reserve fit; true when the free space is unknown
+    pub fits: bool,
+}
+
+impl SpaceForecast {
+    /// Warning for a forecast that doesn't fit
+    pub fn warning(&self) -> Option<String> {
+        if self.fits {
+            return None;
+        }
+        Some(format!(
+            "Queued jobs need about {:.1} GB on {} but only {:.1} GB is free",
+            self.required_bytes as f64 / 1e9,
+            self.volume.as_deref().unwrap_or("the output volume"),
+            self.available_bytes.unwrap_or_default() as f64 / 1e9,
+        ))
+    }
+}
+
+/// Jobs waiting to be processed, oldest first
+static JOB_QUEUE: OnceLock<Mutex<VecDeque<QueuedJob>>> = OnceLock::new();
+
+/// Jobs that failed while the queue ran, oldest first
+static FAILED_JOBS: OnceLock<Mutex<Vec<FailedJob>>> = OnceLock::new();
+
+/// Estimates of the queued jobs being processed right now
+static RUNNING_JOBS: OnceLock<Mutex<Vec<JobEstimate>>> = OnceLock::new();
+
+fn job_queue() -> Result<std::sync::MutexGuard<'static, VecDeque<QueuedJob>>> {
+    JOB_QUEUE.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Job queue poisoned".to_string()))
+}
+
+fn failed_job_list() -> Result<std::sync::MutexGuard<'static, Vec<FailedJob>>> {
+    FAILED_JOBS.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Failed job list poisoned".to_string()))
+}
+
+fn running_jobs() -> Result<std::sync::MutexGuard<'static, Vec<JobEstimate>>> {
+    RUNNING_JOBS.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Running job list poisoned".to_string()))
+}
+
+/// Sets series and series index on each job unless its metadata already has them
+///
+/// The index follows queue position, so an explicit index on one job does
+/// not shift the numbers of the jobs after it.
+pub fn apply_series_autonumber(jobs: &mut [AudiobookJob], autonumber: &SeriesAutoNumber) {
+    for (position, job) in jobs.iter_mut().enumerate() {
+        let metadata = job.metadata.get_or_insert_with(AudiobookMetadata::new);
+        if metadata.series.is_none() {
+            metadata.series = Some(autonumber.series.clone());
+        }
+        if metadata.series_index.is_none() {
+            metadata.series_index = Some((autonumber.start as usize + position) as f32);
+        }
+    }
+}
+
+/// Adds a batch of jobs to the queue and returns their IDs in order
+pub fn enqueue_batch(
+    jobs: Vec<AudiobookJob>,
+    series_autonumber: Option<&SeriesAutoNumber>,
+) -> Result<Vec<String>> {
+    enqueue_batch_with(jobs, series_autonumber, PreferenceSnapshot::current())
+}
+
+/// Adds a batch of jobs like `enqueue_batch`, each with `preferences`
+fn enqueue_batch_with(
+    mut jobs: Vec<AudiobookJob>,
+    series_autonumber: Option<&SeriesAutoNumber>,
+    preferences: PreferenceSnapshot,
+) -> Result<Vec<String>> {
+    if let Some(autonumber) = series_autonumber {
+        apply_series_autonumber(&mut jobs, autonumber);
+    }
+
+    for job in &mut jobs {
+        job.settings.output_path = resolve_output_path(&job.settings.output_path)?;
+    }
+
+    let mut queue = job_queue()?;
+    let ids = jobs.into_iter().map(|mut job| {
+        let default_label = JobLabel::new(job.label.take(), job.notes.take(), &job.settings.output_path);
+        job.label = Some(default_label.label);
+        job.notes = default_label.notes;
+        let id = uuid::Uuid::new_v4().to_string();
+        queue.push_back(QueuedJob { id: id.clone(), job, preferences: preferences.clone() });
+        id
+    }).collect();
+    Ok(ids)
+}
+
+/// Removes and returns the oldest queued job
+pub fn next_job() -> Result<Option<QueuedJob>> {
+    Ok(job_queue()?.pop_front())
+}
+
+/// Returns the queued jobs without removing them
+pub fn queued_jobs() -> Result<Vec<QueuedJob>> {
+    Ok(job_queue()?.iter().cloned().collect())
+}
+
+/// Gives a queued job the preferences as set now, replacing its snapshot
+pub fn refresh_job_preferences(id: &str) -> Result<()> {
+    refresh_job_preferences_with(id, PreferenceSnapshot::current())
+}
+
+/// Replaces the preference snapshot of a queued job with `preferences`
+fn refresh_job_preferences_with(id: &str, preferences: PreferenceSnapshot) -> Result<()> {
+    let mut queue = job_queue()?;
+    let queued = queue.iter_mut()
+        .find(|queued| queued.id == id)
+        .ok_or_else(|| AppError::InvalidInput(format!("No queued job with ID {id}")))?;
+    queued.preferences = preferences;
+    Ok(())
+}
+
+/// Puts a job taken with `next_job` back at the end of the queue
+pub fn defer_job(queued: QueuedJob) -> Result<()> {
+    job_queue()?.push_back(queued);
+    Ok(())
+}
+
+/// Puts a job taken with `next_job` back at the front of the queue
+pub fn return_job(queued: QueuedJob) -> Result<()> {
+    job_queue()?.push_front(queued);
+    Ok(())
+}
+
+/// Keeps a job taken with `next_job` as failed with `error`
+pub fn fail_job(queued: QueuedJob, error: &AppError) -> Result<()> {
+    log::warn!("Queued job {} failed: {error}", queued.id);
+    failed_job_list()?.push(FailedJob { queued, error: ErrorPayload::from(error) });
+    Ok(())
+}
+
+/// Returns the failed jobs, oldest first
+pub fn failed_jobs() -> Result<Vec<FailedJob>> {
+    Ok(failed_job_list()?.clone())
+}
+
+/// Moves a failed job back to the end of the queue
+pub fn retry_failed_job(id: &str) -> Result<()> {
+    let failed = {
+        let mut failed = failed_job_list()?;
+        let index = failed.iter().position(|failed| failed.queued.id == id)
+            .ok_or_else(|| AppError::InvalidInput(format!("No failed job with ID {id}")))?;
+        failed.remove(index)
+    };
+    job_queue()?.push_back(failed.queued);
+    Ok(())
+}
+
+/// Forecasts each queued job in order, counting running and earlier jobs on its volume
+///
+/// Jobs on an unknown volume are never grouped and always fit.
+pub fn forecast_queue(
+    running: &[JobEstimate],
+    queued: &[JobEstimate],
+    available: &HashMap<String, u64>,
+    reserve_bytes: u64,
+) -> Vec<SpaceForecast> {
+    let mut committed: HashMap<&str, u64> = HashMap::new();
+    for job in running {
+        if let Some(volume) = &job.volume {
+            *committed.entry(volume).or_default() += job.output_bytes;
+        }
+    }
+    queued.iter().map(|job| {
+        let required_bytes = match &job.volume {
+            Some(volume) => {
+                let total = committed.entry(volume).or_default();
+                *total += job.output_bytes;
+                *total
+            }
+            None => job.output_bytes,
+        };
+        let available_bytes = job.volume.as_ref().and_then(|volume| available.get(volume)).copied();
+        SpaceForecast {
+            job_id: job.id.clone(),
+            volume: job.volume.clone(),
+            required_bytes,
+            available_bytes,
+            fits: available_bytes.is_none_or(|available| required_bytes.saturating_add(reserve_bytes) <= available),
+        }
+    }).collect()
+}
+
+/// Estimates a job's output and looks up the free space where it goes
+///
+/// The size is projected from the inputs' duration at the job's bitrate,
+/// or taken as the inputs' total size when no duration can be read.
+fn estimate_job(queued: &QueuedJob, available: &mut HashMap<String, u64>) -> JobEstimate {
+    let output_bytes = match get_file_list_info(&queued.job.file_paths) {
+        Ok(info) if info.total_duration > 0.0 => projected_output_bytes(info.total_duration, queued.job.settings.bitrate),
+        Ok(info) => info.total_size as u64,
+        Err(e) => {
+            log::debug!("Cannot estimate the output of job {}: {e}", queued.id);
+            0
+        }
+    };
+    let space = volume_space(&queued.job.settings.output_path);
+    if let Some(space) = &space {
+        available.insert(space.volume.clone(), space.available_bytes);
+    }
+    JobEstimate { id: queued.id.clone(), volume: space.map(|space| space.volume), output_bytes }
+}
+
+/// Forecast for every queued job, after the jobs running now
+pub fn forecast_queued_jobs() -> Result<Vec<SpaceForecast>> {
+    let running = running_jobs()?.clone();
+    let mut available = HashMap::new();
+    let queued: Vec<JobEstimate> = queued_jobs()?.iter().map(|queued| estimate_job(queued, &mut available)).collect();
+    Ok(forecast_queue(&running, &queued, &available, QUEUE_DISK_RESERVE_BYTES))
+}
+
+/// A queued job counted as running until dropped
+pub struct RunningJob {
+    id: String,
+}
+
+impl Drop for RunningJob {
+    fn drop(&mut self) {
+        if let Ok(mut running) = running_jobs() {
+            running.retain(|job| job.id != self.id);
+        }
+    }
+}
+
+/// Forecasts the job about to start against the jobs already running
+pub fn preflight_job(queued: &QueuedJob) -> Result<(JobEstimate, SpaceForecast)> {
+    let mut available = HashMap::new();
+    let estimate = estimate_job(queued, &mut available);
+    let running = running_jobs()?.clone();
+    let forecast = forecast_queue(&running, std::slice::from_ref(&estimate), &available, QUEUE_DISK_RESERVE_BYTES)
+        .pop()
+        .ok_or_else(|| AppError::General("Empty disk space forecast".to_string()))?;
+    Ok((estimate, forecast))
+}
+
+/// Counts a job as running until the returned handle is dropped
+pub fn start_running(estimate: JobEstimate) -> Result<RunningJob> {
+    let id = estimate.id.clone();
+    running_jobs()?.push(estimate);
+    Ok(RunningJob { id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(metadata: Option<AudiobookMetadata>) -> AudiobookJob {
+        AudiobookJob {
+            file_paths: vec!["book/01.mp3".to_string()],
+            settings: AudioSettings::default(),
+            metadata,
+            label: None,
+            notes: None,
+        }
+    }
+
+    fn autonumber(start: u32) -> SeriesAutoNumber {
+        SeriesAutoNumber { series: "The Expanse".to_string(), start }
+    }
+
+    #[test]
+    fn test_autonumber_fills_in_queue_order() {
+        let mut jobs = vec![job(None), job(Some(AudiobookMetadata::new())), job(None)];
+        apply_series_autonumber(&mut jobs, &autonumber(1));
+
+        let filled: Vec<(Option<String>, Option<f32>)> = jobs.iter()
+            .map(|j| j.metadata.as_ref().map(|m| (m.series.clone(), m.series_index)).unwrap())
+            .collect();
+        let series = Some("The Expanse".to_string());
+        assert_eq!(filled, vec![
+            (series.clone(), Some(1.0)),
+            (series.clone(), Some(2.0)),
+            (series, Some(3.0)),
+        ]);
+    }
+
+    #[test]
+    fn test_autonumber_start_offset() {
+        let mut jobs = vec![job(None), job(None)];
+        apply_series_autonumber(&mut jobs, &autonumber(4));
+        let indices: Vec<Option<f32>> = jobs.iter().map(|j| j.metadata.as_ref().unwrap().series_index).collect();
+        assert_eq!(indices, vec![Some(4.0), Some(5.0)]);
+    }
+
+    #[test]
+    fn test_autonumber_keeps_explicit_values() {
+        let mut explicit = AudiobookMetadata::new();
+        explicit.series = Some("Other Series".to_string());
+        explicit.series_index = Some(7.5);
+        let mut index_only = AudiobookMetadata::new();
+        index_only.series_index = Some(10.0);
+
+        let mut jobs = vec![job(Some(explicit)), job(Some(index_only)), job(None)];
+        apply_series_autonumber(&mut jobs, &autonumber(1));
+
+        let first = jobs[0].metadata.as_ref().unwrap();
+        assert_eq!((first.series.as_deref(), first.series_index), (Some("Other Series"), Some(7.5)));
+        let second = jobs[1].metadata.as_ref().unwrap();
+        assert_eq!((second.series.as_deref(), second.series_index), (Some("The Expanse"), Some(10.0)));
+        // Explicit values don't shift later positions
+        assert_eq!(jobs[2].metadata.as_ref().unwrap().series_index, Some(3.0));
+    }
+
+    #[test]
+    fn test_enqueue_batch_applies_autonumber() {
+        let ids = enqueue_batch(vec![job(None), job(None)], Some(&autonumber(2))).unwrap();
+        assert_eq!(ids.len(), 2);
+
+        let queued = queued_jobs().unwrap();
+        let second = queued.iter().find(|q| q.id == ids[1]).unwrap();
+        assert_eq!(second.job.metadata.as_ref().unwrap().series_index, Some(3.0));
+        // Unlabelled jobs get the output filename stem
+        assert_eq!(second.job.label.as_deref(), Some("output"));
+    }
+
+    #[test]
+    fn test_queued_job_keeps_preferences_until_refreshed() {
+        use crate::metadata::profile::MetadataProfile;
+        let preferences = |narrator: &str| PreferenceSnapshot {
+            metadata_profile: MetadataProfile {
+                metadata: AudiobookMetadata { narrator: Some(narrator.to_string()), ..AudiobookMetadata::new() },
+                locked_fields: Vec::new(),
+            },
+            post_process_hook: None,
+        };
+        let narrator = |id: &str| queued_jobs().unwrap().into_iter()
+            .find(|queued| queued.id == id)
+            .and_then(|queued| queued.preferences.metadata_profile.metadata.narrator);
+
+        let id = enqueue_batch_with(vec![job(None)], None, preferences("Queued Narrator")).unwrap().remove(0);
+        let later = enqueue_batch_with(vec![job(None)], None, preferences("Later Narrator")).unwrap().remove(0);
+        assert_eq!(narrator(&id).as_deref(), Some("Queued Narrator"));
+        assert_eq!(narrator(&later).as_deref(), Some("Later Narrator"));
+
+        refresh_job_preferences_with(&id, preferences("Later Narrator")).unwrap();
+        assert_eq!(narrator(&id).as_deref(), Some("Later Narrator"));
+        assert!(refresh_job_preferences_with("no-such-job", preferences("Later Narrator")).is_err());
+    }
+
+    const GB: u64 = 1_000_000_000;
+
+    fn estimate(id: &str, volume: Option<&str>, gb: u64) -> JobEstimate {
+        JobEstimate { id: id.to_string(), volume: volume.map(str::to_string), output_bytes: gb * GB }
+    }
+
+    #[test]
+    fn test_forecast_groups_jobs_by_volume() {
+        let available = HashMap::from([("/Volumes/NAS".to_string(), 10 * GB), ("/".to_string(), 100 * GB)]);
+        let running = [estimate("running", Some("/Volumes/NAS"), 3)];
+        let queued = [
+            estimate("a", Some("/Volumes/NAS"), 4),
+            estimate("b", Some("/"), 50),
+            estimate("c", Some("/Volumes/NAS"), 2),
+            estimate("d", None, 500),
+        ];
+
+        let forecast = forecast_queue(&running, &queued, &available, GB);
+        let required: Vec<u64> = forecast.iter().map(|f| f.required_bytes / GB).collect();
+        assert_eq!(required, [7, 50, 9, 500]);
+        let fits: Vec<bool> = forecast.iter().map(|f| f.fits).collect();
+        // c needs 9 GB plus the 1 GB reserve on a volume with 10 GB free
+        assert_eq!(fits, [true, true, true, true]);
+        assert_eq!(forecast[3].available_bytes, None);
+
+        let tighter = forecast_queue(&running, &queued, &available, 2 * GB);
+        assert!(tighter[0].fits);
+        assert!(!tighter[2].fits);
+        assert!(tighter[2].warning().unwrap().contains("about 9.0 GB on /Volumes/NAS but only 10.0 GB is free"));
+        assert_eq!(tighter[0].warning(), None);
+    }
+
+    #[test]
+    fn test_forecast_of_unknown_space_fits() {
+        let queued = [estimate("a", Some("/mnt/usb"), 64)];
+        let forecast = forecast_queue(&[], &queued, &HashMap::new(), GB);
+        assert!(forecast[0].fits);
+        assert_eq!(forecast[0].available_bytes, None);
+    }
+
+    #[test]
+    fn test_failed_job_is_kept_until_retried() {
+        let id = enqueue_batch(vec![job(None)], None).unwrap().remove(0);
+        let queued = queued_jobs().unwrap().into_iter().find(|queued| queued.id == id).unwrap();
+        job_queue().unwrap().retain(|queued| queued.id != id);
+
+        fail_job(queued, &AppError::FileValidation("Missing input".to_string())).unwrap();
+        let failed = failed_jobs().unwrap().into_iter().find(|failed| failed.queued.id == id).unwrap();
+        assert_eq!(failed.error.code, crate::errors::ErrorCode::FileValidation);
+        assert!(!queued_jobs().unwrap().iter().any(|queued| queued.id == id));
+
+        retry_failed_job(&id).unwrap();
+        assert!(!failed_jobs().unwrap().iter().any(|failed| failed.queued.id == id));
+        assert!(queued_jobs().unwrap().iter().any(|queued| queued.id == id));
+        assert!(retry_failed_job(&id).is_err());
+        job_queue().unwrap().retain(|queued| queued.id != id);
+    }
+
+    #[test]
+    fn test_running_job_is_counted_until_dropped() {
+        let running = start_running(estimate("running-test-job", Some("/Volumes/BOOKS"), 5)).unwrap();
+        let counted = |id: &str| running_jobs().unwrap().iter().any(|job| job.id == id);
+        assert!(counted("running-test-job"));
+        drop(running);
+        assert!(!counted("running-test-job"));
+    }
+}
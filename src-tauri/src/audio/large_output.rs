@@ -0,0 +1,199 @@
+//! Outputs over 4 GB
+//!
+//! Long books at high bitrates can pass 4 GiB. FFmpeg's MP4 muxer switches
+//! to 64-bit chunk offsets and a 64-bit `mdat` size on its own when the
+//! output is seekable, which the temp output always is, but FAT32 cannot
+//! store such a file at all and exFAT sticks and older players often choke
+//! on it. The projected size is checked against the filesystem of the
+//! output volume before the run, so the user hears about it up front.
+
+use super::constants::{LARGE_OUTPUT_BYTES, SIZE_LIMITED_FILESYSTEMS};
+use std::path::Path;
+
+/// Output size in bytes for `duration_secs` of audio at `bitrate_kbps`
+pub fn projected_output_bytes(duration_secs: f64, bitrate_kbps: u32) -> u64 {
+    (duration_secs.max(0.0) * bitrate_kbps as f64 * 125.0) as u64
+}
+
+/// Whether an output of `bytes` needs 64-bit MP4 offsets
+pub fn is_large_output(bytes: u64) -> bool {
+    bytes > LARGE_OUTPUT_BYTES
+}
+
+/// Warning for a projected output over 4 GB on a FAT32 or exFAT volume
+pub fn large_output_warning(projected_bytes: u64, filesystem: Option<&str>) -> Option<String> {
+    let filesystem = filesystem?;
+    if !is_large_output(projected_bytes) || !is_size_limited(filesystem) {
+        return None;
+    }
+    Some(format!(
+        "Projected output is about {:.1} GB, over the 4 GB a {} volume or many players can handle; choose another destination or a lower bitrate",
+        projected_bytes as f64 / 1e9,
+        filesystem.to_ascii_uppercase(),
+    ))
+}
+
+/// Whether `filesystem` is one of the FAT family
+pub fn is_size_limited(filesystem: &str) -> bool {
+    SIZE_LIMITED_FILESYSTEMS.contains(&filesystem.to_ascii_lowercase().as_str())
+}
+
+/// Filesystem type of the volume holding `path`, lowercase (e.g. "vfat", "exfat", "apfs")
+///
+/// `path` need not exist; its nearest existing ancestor is looked up.
+/// Returns None when the filesystem can't be determined.
+pub fn filesystem_of(path: &Path) -> Option<String> {
+    let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+    let existing = std::fs::canonicalize(existing).ok()?;
+    let filesystem = platform_filesystem(&existing);
+    if filesystem.is_none() {
+        log::debug!("Cannot determine the filesystem of {}", existing.display());
+    }
+    filesystem
+}
+
+#[cfg(target_os = "linux")]
+fn platform_filesystem(path: &Path) -> Option<String> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    filesystem_from_proc_mounts(&mounts, path)
+}
+
+#[cfg(target_os = "macos")]
+fn platform_filesystem(path: &Path) -> Option<String> {
+    let output = std::process::Command::new("mount").output().ok()?;
+    filesystem_from_mount_output(&String::from_utf8_lossy(&output.stdout), path)
+}
+
+#[cfg(windows)]
+fn platform_filesystem(path: &Path) -> Option<String> {
+    use std::path::Component;
+    let Some(Component::Prefix(prefix)) = path.components().next() else {
+        return None;
+    };
+    let drive = prefix.as_os_str().to_string_lossy().trim_start_matches(r"\\?\").to_string();
+    let output = std::process::Command::new("fsutil")
+        .args(["fsinfo", "volumeinfo", &format!("{drive}\\")])
+        .output()
+        .ok()?;
+    filesystem_from_fsutil_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn platform_filesystem(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Filesystem of the longest mount point containing `path`, from `/proc/self/mounts`
+///
+/// Mount points escape spaces and other separators as octal (`\040`).
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn filesystem_from_proc_mounts(mounts: &str, path: &Path) -> Option<String> {
+    mounts.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (_device, mount_point, filesystem) = (fields.next()?, fields.next()?, fields.next()?);
+            Some((unescape_octal(mount_point), filesystem))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, filesystem)| filesystem.to_ascii_lowercase())
+}
+
+/// Filesystem of the longest mount point containing `path`, from macOS `mount` output
+///
+/// Lines read `/dev/disk4s1 on /Volumes/BOOKS (msdos, local, nodev)`.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn filesystem_from_mount_output(output: &str, path: &Path) -> Option<String> {
+    output.lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (mount_point, options) = rest.rsplit_once(" (")?;
+            let filesystem = options.split(',').next()?.trim_end_matches(')').trim();
+            Some((mount_point, filesystem))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, filesystem)| filesystem.to_ascii_lowercase())
+}
+
+/// Filesystem from `fsutil fsinfo volumeinfo` output (`File System Name : FAT32`)
+#[cfg_attr(not(windows), allow(dead_code))]
+fn filesystem_from_fsutil_output(output: &str) -> Option<String> {
+    output.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("File System Name"))
+        .map(|(_, value)| value.trim().to_ascii_lowercase())
+        .filter(|filesystem| !filesystem.is_empty())
+}
+
+/// Decodes `\NNN` octal escapes in a `/proc/self/mounts` field
+fn unescape_octal(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = bytes.get(index + 1..index + 4)
+            .filter(|_| bytes[index] == b'\\')
+            .and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 4;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_output_warning() {
+        // 40 hours at 256 kbps is about 4.6 GB
+        let projected = projected_output_bytes(40.0 * 3600.0, 256);
+        assert_eq!(projected, 4_608_000_000);
+        assert!(is_large_output(projected));
+        assert!(large_output_warning(projected, Some("vfat")).unwrap().contains("about 4.6 GB, over the 4 GB a VFAT volume"));
+        assert!(large_output_warning(projected, Some("exfat")).is_some());
+        assert_eq!(large_output_warning(projected, Some("ext4")), None);
+        assert_eq!(large_output_warning(projected, None), None);
+        assert_eq!(large_output_warning(projected_output_bytes(20.0 * 3600.0, 64), Some("vfat")), None);
+    }
+
+    #[test]
+    fn test_filesystem_from_proc_mounts() {
+        let mounts = "/dev/nvme0n1p2 / ext4 rw,relatime 0 0\n\
+                      /dev/sdb1 /media/user/MY\\040BOOKS vfat rw,nosuid 0 0\n\
+                      /dev/sdc1 /media/user/EXT exfat rw 0 0\n";
+        let lookup = |path: &str| filesystem_from_proc_mounts(mounts, Path::new(path));
+        assert_eq!(lookup("/media/user/MY BOOKS/Dune.m4b").as_deref(), Some("vfat"));
+        assert_eq!(lookup("/media/user/EXT/a/b").as_deref(), Some("exfat"));
+        assert_eq!(lookup("/media/user/EXTRA").as_deref(), Some("ext4"));
+        assert_eq!(lookup("/home/user").as_deref(), Some("ext4"));
+    }
+
+    #[test]
+    fn test_filesystem_from_mount_and_fsutil_output() {
+        let mount = "/dev/disk3s1s1 on / (apfs, sealed, local, read-only, journaled)\n\
+                     /dev/disk4s1 on /Volumes/MY BOOKS (msdos, local, nodev, nosuid, noowners)\n";
+        assert_eq!(filesystem_from_mount_output(mount, Path::new("/Volumes/MY BOOKS/x")).as_deref(), Some("msdos"));
+        assert_eq!(filesystem_from_mount_output(mount, Path::new("/Users/me")).as_deref(), Some("apfs"));
+
+        let fsutil = "Volume Name : BOOKS\r\nVolume Serial Number : 0x1234\r\nFile System Name : FAT32\r\n";
+        assert_eq!(filesystem_from_fsutil_output(fsutil).as_deref(), Some("fat32"));
+        assert_eq!(filesystem_from_fsutil_output("Error: Access is denied."), None);
+    }
+
+    #[test]
+    fn test_filesystem_of_missing_path_uses_ancestor() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let missing = temp_dir.path().join("not").join("yet").join("book.m4b");
+        assert_eq!(filesystem_of(&missing), filesystem_of(temp_dir.path()));
+    }
+}
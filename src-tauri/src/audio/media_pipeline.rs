@@ -6,15 +6,50 @@
 //! The `MediaProcessingPlan` struct holds inputs, outputs, and metadata for
 //! processing operations, following mentor recommendations for abstraction.
 
-use super::{AudioSettings, SampleRateConfig};
+use super::AudioSettings;
+use super::bitrate_check::bitrate_excess;
+use super::input_spec::InputOptions;
+use super::large_output::{is_large_output, projected_output_bytes};
 use super::constants::*;
 use super::context::ProcessingContext;
-use super::processor::{detect_input_sample_rate, create_session_from_legacy_state};
-use super::progress_monitor::{setup_process_execution, monitor_process_with_progress, finalize_process_execution};
+use super::settings::ResolvedAudioSettings;
+#[cfg(feature = "gui")]
+use super::processor::create_session_from_legacy_state;
+use super::progress_monitor::{setup_process_execution, monitor_process_with_progress, finalize_process_execution, InputLabels, ProgressPass};
 use crate::errors::Result;
+use crate::ffmpeg::BinaryOrigin;
+use crate::metadata::{read_metadata, AudiobookMetadata};
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
+/// How the inputs are fed to FFmpeg
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum MergeInputMode {
+    /// Concat demuxer reading the list file; needs identical stream parameters
+    ConcatDemuxer,
+    /// One input per file joined by the concat filter; handles mixed codecs
+    ConcatFilter,
+}
+
+/// Picks the concat demuxer when inputs share codec, sample rate and channels
+///
+/// Files with an unknown codec don't count as a mismatch, so the demuxer
+/// stays the default when analysis couldn't tell.
+pub fn select_merge_mode(files: &[super::AudioFile]) -> MergeInputMode {
+    let mut valid = files.iter().filter(|f| f.is_valid);
+    let Some(first) = valid.next() else { return MergeInputMode::ConcatDemuxer };
+    let mismatch = valid.any(|f| {
+        let codec_differs = matches!((&f.codec, &first.codec), (Some(a), Some(b)) if a != b);
+        codec_differs || f.sample_rate != first.sample_rate || f.channels != first.channels
+    });
+    if mismatch {
+        MergeInputMode::ConcatFilter
+    } else {
+        MergeInputMode::ConcatDemuxer
+    }
+}
+
 /// Media processing plan that encapsulates inputs, outputs, and metadata
 /// 
 /// This struct follows the mentor's recommendation to use a `MediaProcessingPlan`
@@ -27,10 +62,24 @@ pub struct MediaProcessingPlan {
     pub output_path: PathBuf,
     /// Audio processing settings
     pub settings: AudioSettings,
+    /// Encoder values the settings resolved to for these inputs
+    pub resolved: ResolvedAudioSettings,
     /// Input file paths for sample rate detection
     pub input_file_paths: Vec<PathBuf>,
     /// Total duration for progress tracking
     pub total_duration: f64,
+    /// Demuxer or filter-graph concatenation
+    pub input_mode: MergeInputMode,
+    /// Highest input bitrate in kbps, for the bitrate warning in the report
+    pub source_bitrate_kbps: Option<u32>,
+    /// Per-file options, one entry per input path
+    pub input_options: Vec<InputOptions>,
+    /// What converting progress names as the input being encoded
+    pub progress_labels: InputLabels,
+    /// Where the FFmpeg binary came from, resolved when the plan is made
+    pub ffmpeg_origin: Option<BinaryOrigin>,
+    /// FFmpeg metadata file whose chapters are mapped into the output
+    pub chapters_file: Option<PathBuf>,
 }
 
 impl MediaProcessingPlan {
@@ -39,6 +88,7 @@ impl MediaProcessingPlan {
         input_concat_file: PathBuf,
         output_path: PathBuf,
         settings: AudioSettings,
+        resolved: ResolvedAudioSettings,
         input_file_paths: Vec<PathBuf>,
         total_duration: f64,
     ) -> Self {
@@ -46,11 +96,48 @@ impl MediaProcessingPlan {
             input_concat_file,
             output_path,
             settings,
+            resolved,
             input_file_paths,
             total_duration,
+            input_mode: MergeInputMode::ConcatDemuxer,
+            source_bitrate_kbps: None,
+            input_options: Vec::new(),
+            progress_labels: InputLabels::default(),
+            ffmpeg_origin: crate::ffmpeg::locate_ffmpeg_with_origin().ok().map(|(_, origin)| origin),
+            chapters_file: None,
         }
     }
 
+    /// Sets how inputs are concatenated
+    pub fn with_input_mode(mut self, input_mode: MergeInputMode) -> Self {
+        self.input_mode = input_mode;
+        self
+    }
+
+    /// Sets the labels progress shows for the input being encoded
+    pub fn with_progress_labels(mut self, labels: InputLabels) -> Self {
+        self.progress_labels = labels;
+        self
+    }
+
+    /// Sets the highest input bitrate
+    pub fn with_source_bitrate(mut self, source_bitrate_kbps: Option<u32>) -> Self {
+        self.source_bitrate_kbps = source_bitrate_kbps;
+        self
+    }
+
+    /// Sets the FFmpeg metadata file the output's chapters come from
+    pub fn with_chapters_file(mut self, chapters_file: PathBuf) -> Self {
+        self.chapters_file = Some(chapters_file);
+        self
+    }
+
+    /// Takes the per-file options of the analyzed inputs
+    pub fn with_input_options(mut self, files: &[super::AudioFile]) -> Self {
+        self.input_options = files.iter().map(|f| f.options.clone()).collect();
+        self
+    }
+
     /// Helper function to calculate total duration from AudioFile list
     /// Handles Option<f64> duration fields properly
     pub fn calculate_total_duration(files: &[super::AudioFile]) -> f64 {
@@ -59,14 +146,65 @@ impl MediaProcessingPlan {
             .sum()
     }
 
+    /// One-line summary of the plan for the log, noting non-default input probing
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "{} input(s) via {:?}, {} kbps, {:.1}s total",
+            self.input_file_paths.len(),
+            self.input_mode,
+            self.resolved.bitrate,
+            self.total_duration,
+        );
+        let mut probing = Vec::new();
+        if let Some(mb) = self.settings.probe_size_mb {
+            probing.push(format!("probe size {mb} MB"));
+        }
+        if let Some(secs) = self.settings.analyze_duration_secs {
+            probing.push(format!("analyze duration {secs}s"));
+        }
+        if !probing.is_empty() {
+            let applied = match self.input_mode {
+                MergeInputMode::ConcatDemuxer => "non-default",
+                MergeInputMode::ConcatFilter => "not applied with the concat filter",
+            };
+            report.push_str(&format!("; {} ({applied})", probing.join(", ")));
+        }
+        let with_options = self.input_options.iter().filter(|options| !options.is_empty()).count();
+        if with_options > 0 {
+            report.push_str(&format!("; {with_options} input(s) with per-file options"));
+        }
+        if let Some(origin) = self.ffmpeg_origin {
+            report.push_str(&format!("; {origin} FFmpeg"));
+        }
+        let projected_bytes = projected_output_bytes(self.total_duration, self.resolved.bitrate);
+        if is_large_output(projected_bytes) {
+            report.push_str(&format!("; large output (~{:.1} GB, 64-bit MP4 offsets)", projected_bytes as f64 / 1e9));
+        }
+        if let Some(excess) = bitrate_excess(&self.settings, self.source_bitrate_kbps) {
+            report.push_str(&format!("; warning: {}", excess.message()));
+        }
+        report
+    }
+
     /// Builds FFmpeg command for this processing plan
     pub fn build_ffmpeg_command(&self) -> Result<Command> {
-        build_merge_command(
-            &self.input_concat_file,
-            &self.output_path,
-            &self.settings,
-            &self.input_file_paths,
-        )
+        match self.input_mode {
+            MergeInputMode::ConcatDemuxer => build_merge_command(
+                &self.input_concat_file,
+                &self.output_path,
+                &self.settings,
+                &self.resolved,
+                &self.input_file_paths,
+                self.chapters_file.as_deref(),
+            ),
+            MergeInputMode::ConcatFilter => build_filter_merge_command(
+                &self.output_path,
+                &self.settings,
+                &self.resolved,
+                &self.input_file_paths,
+                self.chapters_file.as_deref(),
+            ),
+        }
     }
 
     /// Executes the processing plan with context-based progress tracking
@@ -74,8 +212,18 @@ impl MediaProcessingPlan {
         &self,
         context: &ProcessingContext,
     ) -> Result<()> {
+        self.execute_pass_with_context(context, ProgressPass::Single).await
+    }
+
+    /// Executes the plan as one pass of a larger (segmented) merge
+    pub async fn execute_pass_with_context(
+        &self,
+        context: &ProcessingContext,
+        pass: ProgressPass,
+    ) -> Result<()> {
+        log::info!("Merge plan: {}", self.report());
         let cmd = self.build_ffmpeg_command()?;
-        execute_ffmpeg_with_progress_context(cmd, context, self.total_duration).await
+        execute_ffmpeg_pass_with_context(cmd, context, self.total_duration, pass, Some(&self.progress_labels)).await
     }
 
 
@@ -89,32 +237,278 @@ pub fn build_merge_command(
     concat_file: &Path,
     output: &Path,
     settings: &AudioSettings,
+    resolved: &ResolvedAudioSettings,
     file_paths: &[PathBuf],
+    chapters_file: Option<&Path>,
 ) -> Result<Command> {
     let ffmpeg_path = crate::ffmpeg::locate_ffmpeg()?;
-    
-    // Resolve sample rate (auto-detect if needed)
-    let sample_rate = match &settings.sample_rate {
-        SampleRateConfig::Explicit(rate) => *rate,
-        SampleRateConfig::Auto => detect_input_sample_rate(file_paths)?,
-    };
-    
     let mut cmd = Command::new(ffmpeg_path);
-    cmd.args([
-        "-f", FFMPEG_CONCAT_FORMAT,
-        "-safe", FFMPEG_CONCAT_SAFE_MODE,
-        "-i", &concat_file.to_string_lossy(),
+    cmd.args(merge_args(concat_file, output, settings, resolved, file_paths, chapters_file));
+    cmd.stderr(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    Ok(cmd)
+}
+
+/// Arguments of the concat demuxer merge
+fn merge_args(
+    concat_file: &Path,
+    output: &Path,
+    settings: &AudioSettings,
+    resolved: &ResolvedAudioSettings,
+    file_paths: &[PathBuf],
+    chapters_file: Option<&Path>,
+) -> Vec<OsString> {
+    let mut args: Vec<OsString> = Vec::new();
+    args.extend(concat_input_args(concat_file, settings).into_iter().map(OsString::from));
+    args.extend(chapter_input_args(chapters_file).into_iter().map(OsString::from));
+    args.extend([
         "-vn",  // Disable video processing (ignore album artwork)
         "-map", "0:a",  // Only map audio streams
-        "-map_metadata", "0",  // Preserve metadata from first input
+    ].map(OsString::from));
+    args.extend(metadata_map_args(settings, file_paths.first()).into_iter().map(OsString::from));
+    args.extend(chapter_map_args(chapters_file, 1).into_iter().map(OsString::from));
+    args.extend(reproducible_args(settings.reproducible).into_iter().map(OsString::from));
+    args.extend(encode_args(resolved, output));
+    args
+}
+
+/// Output options encoding with the resolved settings, ending with the output path
+fn encode_args(resolved: &ResolvedAudioSettings, output: &Path) -> Vec<OsString> {
+    [
         "-c:a", FFMPEG_AUDIO_CODEC,
-        "-b:a", &format!("{}k", settings.bitrate),
-        "-ar", &sample_rate.to_string(),
-        "-ac", &settings.channels.channel_count().to_string(),
+        "-b:a", &format!("{}k", resolved.bitrate),
+        "-ar", &resolved.sample_rate.to_string(),
+        "-ac", &resolved.channels.to_string(),
         "-progress", FFMPEG_PROGRESS_PIPE,  // Enable progress output to stderr
         "-nostats",  // Disable normal stats output to avoid interference
         "-y",  // Overwrite output file
         &output.to_string_lossy(),
+    ]
+    .map(OsString::from)
+    .to_vec()
+}
+
+/// Input options followed by the concat list input
+///
+/// `-probesize` and `-analyzeduration` are input options, so they only take
+/// effect when placed before the `-i` they apply to.
+fn concat_input_args(concat_file: &Path, settings: &AudioSettings) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(mb) = settings.probe_size_mb {
+        args.push("-probesize".to_string());
+        args.push((u64::from(mb) * 1024 * 1024).to_string());
+    }
+    if let Some(secs) = settings.analyze_duration_secs {
+        args.push("-analyzeduration".to_string());
+        args.push((u64::from(secs) * 1_000_000).to_string());
+    }
+    args.extend(["-f", FFMPEG_CONCAT_FORMAT, "-safe", FFMPEG_CONCAT_SAFE_MODE, "-i"].map(String::from));
+    args.push(concat_file.to_string_lossy().to_string());
+    args
+}
+
+/// Input options reading the chapters metadata file, when there is one
+fn chapter_input_args(chapters_file: Option<&Path>) -> Vec<String> {
+    chapters_file
+        .map(|file| vec!["-f".to_string(), FFMPEG_METADATA_FORMAT.to_string(), "-i".to_string(), file.to_string_lossy().to_string()])
+        .unwrap_or_default()
+}
+
+/// Output options taking chapters from input `input_index`, the chapters metadata file
+fn chapter_map_args(chapters_file: Option<&Path>, input_index: usize) -> Vec<String> {
+    chapters_file
+        .map(|_| vec!["-map_chapters".to_string(), input_index.to_string()])
+        .unwrap_or_default()
+}
+
+/// Output options for `reproducible` runs: bit-exact muxer and encoder, no creation time
+///
+/// An empty `-metadata` value removes the key, so a creation time copied
+/// by `-map_metadata` is dropped too.
+fn reproducible_args(reproducible: bool) -> Vec<String> {
+    if !reproducible {
+        return Vec::new();
+    }
+    [
+        "-fflags", FFMPEG_BITEXACT_FLAGS,
+        "-flags:a", FFMPEG_BITEXACT_FLAGS,
+        "-metadata", "creation_time=",
+        "-metadata:s:a", "creation_time=",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// Output metadata options: inherited from the first input, or book-level fields only
+///
+/// `-map_metadata 0` copies everything the first input carries, including
+/// track-scoped tags like "Track 01" or "Disc 1" as the book title. With
+/// `strip_track_scoped_metadata` nothing is copied and the first input's
+/// book-level fields (album as title, artist, album artist, genre, year)
+/// are set explicitly instead. Metadata passed with the request is written
+/// over either result in the WritingMetadata stage, so the setting only
+/// shows when no metadata (or only some fields) is given.
+fn metadata_map_args(settings: &AudioSettings, first_input: Option<&PathBuf>) -> Vec<String> {
+    if !settings.strip_track_scoped_metadata {
+        return ["-map_metadata", "0"].map(String::from).to_vec();
+    }
+    let mut args = ["-map_metadata", "-1"].map(String::from).to_vec();
+    let inherited = first_input.and_then(|path| match read_metadata(path) {
+        Ok(metadata) => Some(metadata),
+        Err(e) => {
+            log::debug!("Cannot read book-level tags from {}: {e}", path.display());
+            None
+        }
+    });
+    if let Some(metadata) = inherited {
+        for (key, value) in book_level_fields(&metadata) {
+            args.push("-metadata".to_string());
+            args.push(format!("{key}={value}"));
+        }
+    }
+    args
+}
+
+/// FFmpeg metadata keys and values that describe the whole book
+fn book_level_fields(metadata: &AudiobookMetadata) -> Vec<(&'static str, String)> {
+    [
+        ("title", metadata.album.clone()),
+        ("album", metadata.album.clone()),
+        ("artist", metadata.author.clone()),
+        ("album_artist", metadata.narrator.clone()),
+        ("genre", metadata.genre.clone()),
+        ("date", metadata.year.map(|year| year.to_string())),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key, value?)))
+    .collect()
+}
+
+/// Builds FFmpeg command that joins inputs with the concat filter
+///
+/// Each input is resampled to the target rate and layout first, so files
+/// with different codecs or stream parameters decode into one stream. With
+/// `gapless_join`, each input's codec delay is trimmed so joins are seamless.
+pub fn build_filter_merge_command(
+    output: &Path,
+    settings: &AudioSettings,
+    resolved: &ResolvedAudioSettings,
+    file_paths: &[PathBuf],
+    chapters_file: Option<&Path>,
+) -> Result<Command> {
+    let ffmpeg_path = crate::ffmpeg::locate_ffmpeg()?;
+    let start_times = if settings.gapless_join {
+        probe_start_times(file_paths)
+    } else {
+        vec![None; file_paths.len()]
+    };
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(filter_merge_args(output, settings, resolved, file_paths, &start_times, chapters_file));
+    cmd.stderr(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    Ok(cmd)
+}
+
+/// Arguments of the concat filter merge, given each input's stream start time
+fn filter_merge_args(
+    output: &Path,
+    settings: &AudioSettings,
+    resolved: &ResolvedAudioSettings,
+    file_paths: &[PathBuf],
+    start_times: &[Option<f64>],
+    chapters_file: Option<&Path>,
+) -> Vec<OsString> {
+    let mut args: Vec<OsString> = Vec::new();
+    for path in file_paths {
+        args.push("-i".into());
+        args.push(path.into());
+    }
+    args.extend(chapter_input_args(chapters_file).into_iter().map(OsString::from));
+    let graph = concat_filter_graph(start_times, resolved.sample_rate, resolved.channels, settings.gapless_join);
+    args.extend(["-filter_complex".into(), graph.into(), "-map".into(), "[out]".into()]);
+    args.extend(metadata_map_args(settings, file_paths.first()).into_iter().map(OsString::from));
+    args.extend(chapter_map_args(chapters_file, file_paths.len()).into_iter().map(OsString::from));
+    args.extend(reproducible_args(settings.reproducible).into_iter().map(OsString::from));
+    args.extend(encode_args(resolved, output));
+    args
+}
+
+/// Start time of each input's audio stream; unknown when ffprobe fails
+fn probe_start_times(file_paths: &[PathBuf]) -> Vec<Option<f64>> {
+    file_paths.iter()
+        .map(|path| match crate::ffmpeg::probe::probe_start_time(path) {
+            Ok(start_time) => start_time,
+            Err(e) => {
+                log::debug!("ffprobe start time lookup failed for {}: {e}", path.display());
+                None
+            }
+        })
+        .collect()
+}
+
+/// Filters removing an input's codec delay, given its stream start time
+///
+/// Priming samples with negative timestamps are cut off with `atrim`; a
+/// stream starting late (priming already skipped by the decoder) is moved
+/// back to zero so it doesn't leave a gap at the join.
+fn codec_delay_trim(start_time: Option<f64>) -> &'static str {
+    match start_time {
+        Some(secs) if secs <= -CODEC_DELAY_EPSILON_SECS => "atrim=start=0,asetpts=PTS-STARTPTS,",
+        Some(secs) if secs >= CODEC_DELAY_EPSILON_SECS => "asetpts=PTS-STARTPTS,",
+        _ => "",
+    }
+}
+
+/// Filter graph normalizing every input and concatenating them into `[out]`
+///
+/// `gapless` trims each input's codec delay and lets `aresample` pad or
+/// drop the remaining sub-frame drift between inputs.
+fn concat_filter_graph(start_times: &[Option<f64>], sample_rate: u32, channels: u8, gapless: bool) -> String {
+    let layout = if channels == MONO_CHANNELS { "mono" } else { "stereo" };
+    let inputs = start_times.len();
+    let mut graph: String = start_times.iter().enumerate()
+        .map(|(i, &start_time)| if gapless {
+            let trim = codec_delay_trim(start_time);
+            format!("[{i}:a]{trim}aresample={sample_rate}:async=1,aformat=channel_layouts={layout}[a{i}];")
+        } else {
+            format!("[{i}:a]aresample={sample_rate},aformat=channel_layouts={layout}[a{i}];")
+        })
+        .collect();
+    graph.extend((0..inputs).map(|i| format!("[a{i}]")));
+    graph.push_str(&format!("concat=n={inputs}:v=0:a=1[out]"));
+    graph
+}
+
+/// Builds FFmpeg command that stream-copies segment outputs into one file
+///
+/// Segments are already encoded with the target settings, so no re-encode
+/// happens here; metadata comes from the first segment and chapters from
+/// `chapters_file` when given. `reproducible` adds the same bit-exact
+/// options as the encode.
+pub fn build_segment_join_command(
+    join_list: &Path,
+    output: &Path,
+    chapters_file: Option<&Path>,
+    reproducible: bool,
+) -> Result<Command> {
+    let ffmpeg_path = crate::ffmpeg::locate_ffmpeg()?;
+    
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args([
+        "-f", FFMPEG_CONCAT_FORMAT,
+        "-safe", FFMPEG_CONCAT_SAFE_MODE,
+        "-i", &join_list.to_string_lossy(),
+    ]);
+    cmd.args(chapter_input_args(chapters_file));
+    cmd.args(["-map", "0:a", "-map_metadata", "0"]);
+    cmd.args(chapter_map_args(chapters_file, 1));
+    cmd.args(reproducible_args(reproducible));
+    cmd.args([
+        "-c", "copy",
+        "-progress", FFMPEG_PROGRESS_PIPE,
+        "-nostats",
+        "-y",
+        &output.to_string_lossy(),
     ]);
     
     cmd.stderr(Stdio::piped());
@@ -132,10 +526,27 @@ pub async fn execute_ffmpeg_with_progress_context(
     context: &ProcessingContext,
     total_duration: f64,
 ) -> Result<()> {
-    log::debug!("Starting FFmpeg execution with progress tracking");
+    execute_ffmpeg_pass_with_context(cmd, context, total_duration, ProgressPass::Single, None).await
+}
+
+/// Executes one FFmpeg pass, mapping its progress onto the whole merge
+///
+/// `labels` name the input being encoded in progress events.
+pub async fn execute_ffmpeg_pass_with_context(
+    cmd: Command,
+    context: &ProcessingContext,
+    total_duration: f64,
+    pass: ProgressPass,
+    labels: Option<&InputLabels>,
+) -> Result<()> {
+    log::debug!("Starting FFmpeg execution with progress tracking ({pass:?})");
     
     // Set up process execution
     let mut execution = setup_process_execution(cmd, context)?;
+    execution.pass = pass;
+    if let Some(labels) = labels {
+        execution.labels = labels.clone();
+    }
     
     // Monitor process with progress updates
     monitor_process_with_progress(&mut execution, context, total_duration)?;
@@ -151,6 +562,7 @@ pub async fn execute_ffmpeg_with_progress_context(
 /// 
 /// ADAPTER FUNCTION: Maintains backward compatibility by converting parameters
 /// to use the new context-based approach internally.
+#[cfg(feature = "gui")]
 #[deprecated = "Use execute_ffmpeg_with_progress_context for new code - this adapter maintains compatibility"]
 #[allow(dead_code)]
 pub async fn execute_with_progress_events(
@@ -161,7 +573,7 @@ pub async fn execute_with_progress_events(
 ) -> Result<()> {
     // Convert legacy parameters to context-based approach
     let session = create_session_from_legacy_state(state)?;
-    let context = ProcessingContext::new(window.clone(), session, AudioSettings::default());
+    let context = ProcessingContext::for_window(window.clone(), session, AudioSettings::default());
     // Note: We use default settings here since they're not available in the legacy adapter
     
     execute_ffmpeg_with_progress_context(cmd, &context, total_duration).await
@@ -179,5 +591,390 @@ pub fn build_merge_command_legacy(
     settings: &AudioSettings,
     file_paths: &[PathBuf],
 ) -> Result<Command> {
-    build_merge_command(concat_file, output, settings, file_paths)
+    let files: Vec<super::AudioFile> = file_paths.iter().cloned().map(super::AudioFile::new).collect();
+    let resolved = settings.resolve(&files)?;
+    build_merge_command(concat_file, output, settings, &resolved, file_paths, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{AudioFile, SampleRateConfig};
+
+    fn argv(args: &[OsString]) -> Vec<String> {
+        args.iter().map(|a| a.to_string_lossy().to_string()).collect()
+    }
+
+    fn file(codec: Option<&str>, sample_rate: u32, channels: u32) -> AudioFile {
+        let mut file = AudioFile::new(PathBuf::from("a.mp3"));
+        file.codec = codec.map(str::to_string);
+        file.sample_rate = Some(sample_rate);
+        file.channels = Some(channels);
+        file.is_valid = true;
+        file
+    }
+
+    #[test]
+    fn test_homogeneous_inputs_use_demuxer() {
+        let files = [file(Some("mp3"), 44100, 2), file(Some("mp3"), 44100, 2)];
+        assert_eq!(select_merge_mode(&files), MergeInputMode::ConcatDemuxer);
+        assert_eq!(select_merge_mode(&[]), MergeInputMode::ConcatDemuxer);
+    }
+
+    #[test]
+    fn test_mixed_inputs_use_filter() {
+        let mixed_codec = [file(Some("mp3"), 44100, 2), file(Some("aac"), 44100, 2)];
+        assert_eq!(select_merge_mode(&mixed_codec), MergeInputMode::ConcatFilter);
+        let mixed_profile = [file(Some("aac"), 44100, 2), file(Some("aac_he"), 44100, 2)];
+        assert_eq!(select_merge_mode(&mixed_profile), MergeInputMode::ConcatFilter);
+        let mixed_rate = [file(Some("mp3"), 44100, 2), file(Some("mp3"), 22050, 2)];
+        assert_eq!(select_merge_mode(&mixed_rate), MergeInputMode::ConcatFilter);
+    }
+
+    #[test]
+    fn test_unknown_codec_is_not_a_mismatch() {
+        let files = [file(Some("mp3"), 44100, 2), file(None, 44100, 2)];
+        assert_eq!(select_merge_mode(&files), MergeInputMode::ConcatDemuxer);
+    }
+
+    #[test]
+    fn test_probe_options_precede_input() {
+        let mut settings = AudioSettings::audiobook_preset();
+        let list = Path::new("/tmp/concat.txt");
+        assert_eq!(concat_input_args(list, &settings), ["-f", "concat", "-safe", "0", "-i", "/tmp/concat.txt"]);
+
+        settings.sample_rate = SampleRateConfig::Explicit(44100);
+        settings.probe_size_mb = Some(50);
+        settings.analyze_duration_secs = Some(100);
+        let args = concat_input_args(list, &settings);
+        let input = args.iter().position(|a| a == "-i").unwrap();
+        let probesize = args.iter().position(|a| a == "-probesize").unwrap();
+        let analyzeduration = args.iter().position(|a| a == "-analyzeduration").unwrap();
+        assert!(probesize < input && analyzeduration < input);
+        assert_eq!(args[probesize + 1], "52428800");
+        assert_eq!(args[analyzeduration + 1], "100000000");
+        assert_eq!(args[input + 1], "/tmp/concat.txt");
+
+        let resolved = settings.resolve(&[]).unwrap();
+        let Ok(cmd) = build_merge_command(list, Path::new("/tmp/out.m4b"), &settings, &resolved, &[], None) else {
+            eprintln!("Skipping command check - FFmpeg not found");
+            return;
+        };
+        let argv: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy().to_string()).collect();
+        assert_eq!(argv[..args.len()], args[..]);
+    }
+
+    #[test]
+    fn test_plan_report_notes_probe_settings() {
+        let mut settings = AudioSettings::audiobook_preset();
+        let plan = |settings: &AudioSettings| MediaProcessingPlan::new(
+            PathBuf::from("concat.txt"), PathBuf::from("out.m4b"), settings.clone(),
+            ResolvedAudioSettings { bitrate: settings.bitrate, sample_rate: 22050, sample_rate_detected: true, channels: 1 },
+            vec![PathBuf::from("a.mp3")], 60.0,
+        );
+        assert!(!plan(&settings).report().contains("probe"));
+
+        settings.probe_size_mb = Some(50);
+        assert!(plan(&settings).report().contains("probe size 50 MB (non-default)"));
+        let filter = plan(&settings).with_input_mode(MergeInputMode::ConcatFilter);
+        assert!(filter.report().contains("not applied"));
+
+        settings.bitrate = 128;
+        assert!(!plan(&settings).with_source_bitrate(Some(96)).report().contains("warning"));
+        assert!(plan(&settings).with_source_bitrate(Some(64)).report().contains("warning: Target bitrate 128 kbps"));
+
+        let mut trimmed = file(Some("mp3"), 44100, 2);
+        trimmed.options.gain_db = Some(-2.0);
+        let files = [file(Some("mp3"), 44100, 2), trimmed];
+        let with_options = plan(&settings).with_input_options(&files);
+        assert_eq!(with_options.input_options.len(), 2);
+        assert!(with_options.report().contains("1 input(s) with per-file options"));
+        assert!(!with_options.report().contains("large output"));
+
+        let mut long_book = plan(&settings);
+        long_book.total_duration = 40.0 * 3600.0;
+        long_book.resolved.bitrate = 256;
+        assert!(long_book.report().contains("large output (~4.6 GB, 64-bit MP4 offsets)"));
+    }
+
+    #[test]
+    fn test_merge_argv_golden() {
+        let mut settings = AudioSettings::default();
+        settings.bitrate = 64;
+        settings.probe_size_mb = Some(1);
+        let resolved = ResolvedAudioSettings { bitrate: 64, sample_rate: 22050, sample_rate_detected: true, channels: 1 };
+        let inputs = [PathBuf::from("/nonexistent/01.mp3")];
+        let args = merge_args(
+            Path::new("/tmp/concat.txt"), Path::new("/tmp/out.m4b"), &settings, &resolved, &inputs, Some(Path::new("/tmp/chapters.txt")),
+        );
+        assert_eq!(argv(&args), [
+            "-probesize", "1048576", "-f", "concat", "-safe", "0", "-i", "/tmp/concat.txt",
+            "-f", "ffmetadata", "-i", "/tmp/chapters.txt",
+            "-vn", "-map", "0:a", "-map_metadata", "-1", "-map_chapters", "1",
+            "-c:a", "libfdk_aac", "-b:a", "64k", "-ar", "22050", "-ac", "1",
+            "-progress", "pipe:2", "-nostats", "-y", "/tmp/out.m4b",
+        ]);
+    }
+
+    #[test]
+    fn test_filter_merge_argv_golden() {
+        let mut settings = AudioSettings::default();
+        settings.gapless_join = false;
+        settings.reproducible = true;
+        let resolved = ResolvedAudioSettings { bitrate: 96, sample_rate: 44100, sample_rate_detected: false, channels: 2 };
+        let inputs = [PathBuf::from("/nonexistent/01.mp3"), PathBuf::from("/nonexistent/02.m4a")];
+        let args = filter_merge_args(Path::new("/tmp/out.m4b"), &settings, &resolved, &inputs, &[None, None], None);
+        assert_eq!(argv(&args), [
+            "-i", "/nonexistent/01.mp3", "-i", "/nonexistent/02.m4a",
+            "-filter_complex",
+            "[0:a]aresample=44100,aformat=channel_layouts=stereo[a0];\
+             [1:a]aresample=44100,aformat=channel_layouts=stereo[a1];\
+             [a0][a1]concat=n=2:v=0:a=1[out]",
+            "-map", "[out]", "-map_metadata", "-1",
+            "-fflags", "+bitexact", "-flags:a", "+bitexact", "-metadata", "creation_time=", "-metadata:s:a", "creation_time=",
+            "-c:a", "libfdk_aac", "-b:a", "96k", "-ar", "44100", "-ac", "2",
+            "-progress", "pipe:2", "-nostats", "-y", "/tmp/out.m4b",
+        ]);
+    }
+
+    #[test]
+    fn test_concat_filter_graph() {
+        assert_eq!(
+            concat_filter_graph(&[None, None], 22050, 1, false),
+            "[0:a]aresample=22050,aformat=channel_layouts=mono[a0];\
+             [1:a]aresample=22050,aformat=channel_layouts=mono[a1];\
+             [a0][a1]concat=n=2:v=0:a=1[out]"
+        );
+    }
+
+    #[test]
+    fn test_codec_delay_trim() {
+        // LAME MP3: 1105 priming samples at 44.1 kHz skipped by the decoder
+        assert_eq!(codec_delay_trim(Some(0.025057)), "asetpts=PTS-STARTPTS,");
+        // AAC in MP4: 1024 priming samples at 48 kHz before zero
+        assert_eq!(codec_delay_trim(Some(-0.021333)), "atrim=start=0,asetpts=PTS-STARTPTS,");
+        assert_eq!(codec_delay_trim(Some(0.0)), "");
+        assert_eq!(codec_delay_trim(Some(0.0001)), "");
+        assert_eq!(codec_delay_trim(None), "");
+    }
+
+    #[test]
+    fn test_gapless_concat_filter_graph() {
+        assert_eq!(
+            concat_filter_graph(&[Some(0.025057), Some(-0.021333), None], 44100, 2, true),
+            "[0:a]asetpts=PTS-STARTPTS,aresample=44100:async=1,aformat=channel_layouts=stereo[a0];\
+             [1:a]atrim=start=0,asetpts=PTS-STARTPTS,aresample=44100:async=1,aformat=channel_layouts=stereo[a1];\
+             [2:a]aresample=44100:async=1,aformat=channel_layouts=stereo[a2];\
+             [a0][a1][a2]concat=n=3:v=0:a=1[out]"
+        );
+    }
+
+    /// FFmpeg with the AAC encoder, or None to skip
+    fn ffmpeg_with_encoder() -> Option<PathBuf> {
+        let ffmpeg = crate::ffmpeg::locate_ffmpeg().ok()?;
+        let encoders = Command::new(&ffmpeg).args(["-hide_banner", "-encoders"]).output().ok()?;
+        String::from_utf8_lossy(&encoders.stdout).contains(FFMPEG_AUDIO_CODEC).then_some(ffmpeg)
+    }
+
+    #[test]
+    fn test_reproducible_args() {
+        assert!(reproducible_args(false).is_empty());
+        assert_eq!(
+            reproducible_args(true),
+            ["-fflags", "+bitexact", "-flags:a", "+bitexact", "-metadata", "creation_time=", "-metadata:s:a", "creation_time="]
+        );
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let join_list = temp_dir.path().join("join.txt");
+        let output = temp_dir.path().join("book.m4b");
+        let Ok(cmd) = build_segment_join_command(&join_list, &output, None, true) else {
+            eprintln!("Skipping argv check - FFmpeg not found");
+            return;
+        };
+        let argv: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy().to_string()).collect();
+        let flags = argv.iter().position(|a| a == "-fflags").unwrap();
+        assert!(flags < argv.iter().position(|a| a == "-progress").unwrap());
+    }
+
+    /// SHA-256 of a file's bytes, hex encoded
+    fn file_sha256(path: &Path) -> String {
+        use sha2::{Digest, Sha256};
+        Sha256::digest(std::fs::read(path).unwrap()).iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    /// Encodes the same input twice with `reproducible` and compares the outputs
+    ///
+    /// Skips when the encoded audio packets themselves differ, since then the
+    /// encoder build is nondeterministic and no flag can fix it.
+    #[test]
+    fn test_reproducible_output_is_byte_identical() {
+        let Some(ffmpeg) = ffmpeg_with_encoder() else {
+            eprintln!("Skipping test - FFmpeg with AAC encoder not found");
+            return;
+        };
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let input = temp_dir.path().join("01.mp3");
+        let status = Command::new(&ffmpeg)
+            .args(["-v", "error", "-f", "lavfi", "-i", "sine=frequency=440:duration=1", "-metadata", "album=Dune", "-y"])
+            .arg(&input)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let list = temp_dir.path().join("concat.txt");
+        std::fs::write(&list, format!("file '{}'\n", input.display())).unwrap();
+
+        let mut settings = AudioSettings::default();
+        settings.sample_rate = SampleRateConfig::Explicit(22050);
+        settings.reproducible = true;
+        let metadata = AudiobookMetadata {
+            title: Some("Dune".to_string()),
+            author: Some("Frank Herbert".to_string()),
+            ..AudiobookMetadata::new()
+        };
+        let mut outputs = Vec::new();
+        for run in 0..2 {
+            let output = temp_dir.path().join(format!("book-{run}.m4b"));
+            let result = build_merge_command(&list, &output, &settings, &settings.resolve(&[]).unwrap(), std::slice::from_ref(&input), None)
+                .unwrap()
+                .output()
+                .unwrap();
+            assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+            let options = crate::metadata::writer::MetadataWriteOptions { reproducible: true, ..Default::default() };
+            crate::metadata::writer::write_metadata_with(&output, &metadata, options).unwrap();
+            outputs.push(output);
+        }
+
+        if file_sha256(&outputs[0]) != file_sha256(&outputs[1]) {
+            let packets = |path: &Path| {
+                let out = Command::new(&ffmpeg)
+                    .args(["-v", "error", "-i"])
+                    .arg(path)
+                    .args(["-map", "0:a", "-c", "copy", "-f", "md5", "-"])
+                    .output()
+                    .unwrap();
+                String::from_utf8_lossy(&out.stdout).to_string()
+            };
+            if packets(&outputs[0]) != packets(&outputs[1]) {
+                eprintln!("Skipping test - the AAC encoder is nondeterministic in this FFmpeg build");
+                return;
+            }
+            panic!("Reproducible outputs differ outside the audio data");
+        }
+    }
+
+    #[test]
+    fn test_metadata_map_args() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing.mp3");
+        let mut settings = AudioSettings::audiobook_preset();
+        assert!(settings.strip_track_scoped_metadata);
+        assert_eq!(metadata_map_args(&settings, Some(&missing)), ["-map_metadata", "-1"]);
+
+        settings.strip_track_scoped_metadata = false;
+        assert_eq!(metadata_map_args(&settings, Some(&missing)), ["-map_metadata", "0"]);
+
+        let metadata = AudiobookMetadata {
+            title: Some("Track 01".to_string()),
+            album: Some("Dune".to_string()),
+            author: Some("Frank Herbert".to_string()),
+            year: Some(1965),
+            ..AudiobookMetadata::new()
+        };
+        assert_eq!(
+            book_level_fields(&metadata),
+            [("title", "Dune"), ("album", "Dune"), ("artist", "Frank Herbert"), ("date", "1965")]
+                .map(|(key, value)| (key, value.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_track_scoped_tags_in_output() {
+        let Some(ffmpeg) = ffmpeg_with_encoder() else {
+            eprintln!("Skipping test - FFmpeg with AAC encoder not found");
+            return;
+        };
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let input = temp_dir.path().join("01.mp3");
+        let status = Command::new(&ffmpeg)
+            .args(["-v", "error", "-f", "lavfi", "-i", "sine=frequency=440:duration=0.5"])
+            .args(["-metadata", "title=Track 01", "-metadata", "album=Dune", "-metadata", "artist=Frank Herbert", "-y"])
+            .arg(&input)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let list = temp_dir.path().join("concat.txt");
+        std::fs::write(&list, format!("file '{}'\n", input.display())).unwrap();
+
+        let mut settings = AudioSettings::default();
+        settings.sample_rate = SampleRateConfig::Explicit(22050);
+        let mut titles = Vec::new();
+        for strip in [true, false] {
+            settings.strip_track_scoped_metadata = strip;
+            let output = temp_dir.path().join(format!("book-{strip}.m4b"));
+            let cmd = build_merge_command(&list, &output, &settings, &settings.resolve(&[]).unwrap(), std::slice::from_ref(&input), None).unwrap();
+            let argv: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy().to_string()).collect();
+            let map = argv.iter().position(|a| a == "-map_metadata").unwrap();
+            assert_eq!(argv[map + 1], if strip { "-1" } else { "0" });
+
+            let result = build_merge_command(&list, &output, &settings, &settings.resolve(&[]).unwrap(), std::slice::from_ref(&input), None).unwrap().output().unwrap();
+            assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+            let tags = read_metadata(&output).unwrap();
+            assert_eq!(tags.album.as_deref(), Some("Dune"));
+            assert_eq!(tags.author.as_deref(), Some("Frank Herbert"));
+            titles.push(tags.title);
+        }
+        assert_eq!(titles, [Some("Dune".to_string()), Some("Track 01".to_string())]);
+    }
+
+    #[test]
+    fn test_chapters_at_input_boundaries() {
+        use crate::audio::chapters::{book_chapters, ffmetadata};
+
+        let Some(ffmpeg) = ffmpeg_with_encoder() else {
+            eprintln!("Skipping test - FFmpeg with AAC encoder not found");
+            return;
+        };
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut files = Vec::new();
+        for (name, duration) in [("01 Arrival", 1.0), ("02 Departure", 1.5)] {
+            let path = temp_dir.path().join(format!("{name}.mp3"));
+            let status = Command::new(&ffmpeg)
+                .args(["-v", "error", "-f", "lavfi", "-i", &format!("sine=frequency=440:duration={duration}"), "-y"])
+                .arg(&path)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let mut file = AudioFile::new(path);
+            file.duration = Some(duration);
+            file.is_valid = true;
+            files.push(file);
+        }
+        let paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+        let list = temp_dir.path().join("concat.txt");
+        std::fs::write(&list, paths.iter().map(|p| format!("file '{}'\n", p.display())).collect::<String>()).unwrap();
+        let chapters_file = temp_dir.path().join("chapters.txt");
+        std::fs::write(&chapters_file, ffmetadata(&book_chapters(&files, &AudioSettings::default()).unwrap())).unwrap();
+
+        let mut settings = AudioSettings::default();
+        settings.sample_rate = SampleRateConfig::Explicit(22050);
+        let outputs = [
+            (temp_dir.path().join("demuxer.m4b"), MergeInputMode::ConcatDemuxer),
+            (temp_dir.path().join("filter.m4b"), MergeInputMode::ConcatFilter),
+        ];
+        for (output, mode) in outputs {
+            let plan = MediaProcessingPlan::new(list.clone(), output.clone(), settings.clone(), settings.resolve(&files).unwrap(), paths.clone(), 2.5)
+                .with_input_mode(mode)
+                .with_chapters_file(chapters_file.clone());
+            let result = plan.build_ffmpeg_command().unwrap().output().unwrap();
+            assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+
+            let probed = crate::ffmpeg::probe::probe_raw(&output).unwrap();
+            let chapters = probed["chapters"].as_array().unwrap();
+            let secs = |value: &serde_json::Value| value.as_str().unwrap().parse::<f64>().unwrap();
+            let starts: Vec<f64> = chapters.iter().map(|chapter| secs(&chapter["start_time"])).collect();
+            let titles: Vec<&str> = chapters.iter().map(|chapter| chapter["tags"]["title"].as_str().unwrap()).collect();
+            assert_eq!(titles, ["01 Arrival", "02 Departure"], "{mode:?}");
+            assert!(starts[0].abs() < 1.0 && (starts[1] - 1.0).abs() < 1.0, "{mode:?} starts {starts:?}");
+        }
+    }
 }
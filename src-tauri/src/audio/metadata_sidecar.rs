@@ -0,0 +1,291 @@
+//! Metadata sidecar written next to the output
+//!
+//! Library servers such as Audiobookshelf read book details from a file
+//! beside the audio. `Json` writes `{output stem}.json` in Audiobookshelf's
+//! `metadata.json` shape, with the chapters and the encode's technical
+//! details added; `Opf` writes `{output stem}.opf`, an OPF package with
+//! Dublin Core fields (title, creators, date, identifiers) and Calibre's
+//! series tags. Chapters and technical details exist only in the JSON.
+
+use super::chapters::Chapter;
+use super::constants::{METADATA_JSON_EXTENSION, METADATA_OPF_EXTENSION};
+use crate::errors::{AppError, Result};
+use crate::metadata::AudiobookMetadata;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// File format of the metadata sidecar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SidecarFormat {
+    /// `metadata.json` as Audiobookshelf reads it
+    Json,
+    /// OPF package with Dublin Core fields
+    Opf,
+}
+
+impl SidecarFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Json => METADATA_JSON_EXTENSION,
+            Self::Opf => METADATA_OPF_EXTENSION,
+        }
+    }
+}
+
+/// How the output was encoded, for the JSON sidecar
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarAudio {
+    /// Length of the book in seconds
+    pub duration_secs: f64,
+    /// AAC bitrate in kbps
+    pub bitrate_kbps: u32,
+    /// Sample rate in Hz, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    /// Number of channels
+    pub channels: u8,
+}
+
+/// Chapter as Audiobookshelf's `metadata.json` lists it, in seconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct SidecarChapter<'a> {
+    id: usize,
+    start: f64,
+    end: f64,
+    title: &'a str,
+}
+
+/// Book details in Audiobookshelf's `metadata.json` shape
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonSidecar<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subtitle: Option<&'a str>,
+    authors: Vec<&'a str>,
+    narrators: Vec<&'a str>,
+    series: Vec<String>,
+    genres: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    published_year: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    isbn: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    asin: Option<&'a str>,
+    chapters: Vec<SidecarChapter<'a>>,
+    audio: &'a SidecarAudio,
+}
+
+/// Writes the sidecar next to `output` and returns its path
+pub fn write_metadata_sidecar(
+    output: &Path,
+    format: SidecarFormat,
+    metadata: &AudiobookMetadata,
+    chapters: &[Chapter],
+    audio: &SidecarAudio,
+) -> Result<PathBuf> {
+    let contents = match format {
+        SidecarFormat::Json => json_sidecar(metadata, chapters, audio)?,
+        SidecarFormat::Opf => opf_sidecar(metadata),
+    };
+    let destination = output.with_extension(format.extension());
+    std::fs::write(&destination, contents)?;
+    Ok(destination)
+}
+
+/// Series as Audiobookshelf writes it, "Name #index"
+fn series_entry(metadata: &AudiobookMetadata) -> Option<String> {
+    let name = metadata.series.as_deref()?;
+    Some(match metadata.series_index {
+        Some(index) => format!("{name} #{index}"),
+        None => name.to_string(),
+    })
+}
+
+fn json_sidecar(metadata: &AudiobookMetadata, chapters: &[Chapter], audio: &SidecarAudio) -> Result<String> {
+    let sidecar = JsonSidecar {
+        title: metadata.title.as_deref(),
+        subtitle: metadata.subtitle.as_deref(),
+        authors: metadata.author.as_deref().into_iter().collect(),
+        narrators: metadata.narrator.as_deref().into_iter().collect(),
+        series: series_entry(metadata).into_iter().collect(),
+        genres: metadata.genre.as_deref().into_iter().collect(),
+        published_year: metadata.year.map(|year| year.to_string()),
+        description: metadata.description.as_deref(),
+        isbn: metadata.isbn.as_deref(),
+        asin: metadata.asin.as_deref(),
+        chapters: chapters.iter().enumerate()
+            .map(|(id, chapter)| SidecarChapter {
+                id,
+                start: chapter.start_secs,
+                end: chapter.end_secs,
+                title: &chapter.title,
+            })
+            .collect(),
+        audio,
+    };
+    serde_json::to_string_pretty(&sidecar)
+        .map_err(|e| AppError::General(format!("Failed to serialize metadata sidecar: {e}")))
+}
+
+fn opf_sidecar(metadata: &AudiobookMetadata) -> String {
+    let mut fields = Vec::new();
+    let mut element = |tag: &str, attributes: &str, value: Option<&str>| {
+        if let Some(value) = value.filter(|value| !value.trim().is_empty()) {
+            fields.push(format!("    <{tag}{attributes}>{}</{tag}>", xml_escape(value)));
+        }
+    };
+    element("dc:title", "", metadata.title.as_deref());
+    element("dc:creator", r#" opf:role="aut""#, metadata.author.as_deref());
+    element("dc:creator", r#" opf:role="nrt""#, metadata.narrator.as_deref());
+    element("dc:date", "", metadata.year.map(|year| year.to_string()).as_deref());
+    element("dc:subject", "", metadata.genre.as_deref());
+    element("dc:description", "", metadata.description.as_deref());
+    element("dc:identifier", r#" opf:scheme="ISBN""#, metadata.isbn.as_deref());
+    element("dc:identifier", r#" opf:scheme="ASIN""#, metadata.asin.as_deref());
+    if let Some(series) = metadata.series.as_deref() {
+        fields.push(format!(r#"    <meta name="calibre:series" content="{}"/>"#, xml_escape(series)));
+        if let Some(index) = metadata.series_index {
+            fields.push(format!(r#"    <meta name="calibre:series_index" content="{index}"/>"#));
+        }
+    }
+    let mut opf = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    opf.push_str("<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"2.0\">\n");
+    opf.push_str("  <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:opf=\"http://www.idpf.org/2007/opf\">\n");
+    for field in fields {
+        opf.push_str(&field);
+        opf.push('\n');
+    }
+    opf.push_str("  </metadata>\n</package>\n");
+    opf
+}
+
+/// Escapes text for element content and double-quoted attributes
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .chars()
+        .filter(|&c| c == '\t' || c == '\n' || c == '\r' || c >= ' ')
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use tempfile::TempDir;
+
+    fn book() -> AudiobookMetadata {
+        AudiobookMetadata {
+            title: Some("Dune".to_string()),
+            author: Some("Frank Herbert".to_string()),
+            narrator: Some("Scott Brick & Simon Vance".to_string()),
+            year: Some(1965),
+            genre: Some("Science Fiction".to_string()),
+            series: Some("Dune <Chronicles>".to_string()),
+            series_index: Some(1.0),
+            description: Some("Spice \"must\" flow\u{1}".to_string()),
+            isbn: Some("978-0441013593".to_string()),
+            ..AudiobookMetadata::new()
+        }
+    }
+
+    fn audio() -> SidecarAudio {
+        SidecarAudio { duration_secs: 75600.5, bitrate_kbps: 64, sample_rate: Some(22050), channels: 1 }
+    }
+
+    fn chapters() -> Vec<Chapter> {
+        vec![
+            Chapter { title: "Book One".to_string(), start_secs: 0.0, end_secs: 1800.0 },
+            Chapter { title: "Book Two".to_string(), start_secs: 1800.0, end_secs: 75600.5 },
+        ]
+    }
+
+    #[test]
+    fn test_json_sidecar_schema() {
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("Dune.m4b");
+        let path = write_metadata_sidecar(&output, SidecarFormat::Json, &book(), &chapters(), &audio()).unwrap();
+        assert_eq!(path, temp_dir.path().join("Dune.json"));
+
+        let json: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        // Unset fields (subtitle, asin) are left out
+        assert_eq!(keys, [
+            "audio", "authors", "chapters", "description", "genres", "isbn",
+            "narrators", "publishedYear", "series", "title",
+        ]);
+        assert_eq!(json["title"], "Dune");
+        assert_eq!(json["authors"], serde_json::json!(["Frank Herbert"]));
+        assert_eq!(json["series"], serde_json::json!(["Dune <Chronicles> #1"]));
+        assert_eq!(json["publishedYear"], "1965");
+        assert_eq!(json["chapters"][1], serde_json::json!({ "id": 1, "start": 1800.0, "end": 75600.5, "title": "Book Two" }));
+        assert_eq!(json["audio"], serde_json::json!({ "durationSecs": 75600.5, "bitrateKbps": 64, "sampleRate": 22050, "channels": 1 }));
+    }
+
+    #[test]
+    fn test_json_sidecar_without_metadata() {
+        let json: Value = serde_json::from_str(&json_sidecar(&AudiobookMetadata::new(), &[], &audio()).unwrap()).unwrap();
+        assert_eq!(json["authors"], serde_json::json!([]));
+        assert!(json.get("title").is_none());
+    }
+
+    /// Checks the document is well-formed: balanced tags, quoted attributes,
+    /// and no bare `&` or `<` in text
+    fn parse_xml(xml: &str) -> Vec<(String, String)> {
+        let body = xml.strip_prefix("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n").expect("XML declaration");
+        let mut open: Vec<String> = Vec::new();
+        let mut elements = Vec::new();
+        let mut rest = body;
+        while let Some(start) = rest.find('<') {
+            let text = &rest[..start];
+            assert!(!text.contains('>'), "bare > in {text:?}");
+            for (index, _) in text.match_indices('&') {
+                let entity = &text[index..];
+                assert!(["&amp;", "&lt;", "&gt;", "&quot;"].iter().any(|e| entity.starts_with(e)), "bare & in {text:?}");
+            }
+            if let Some(name) = open.last() {
+                if !text.trim().is_empty() {
+                    elements.push((name.clone(), text.to_string()));
+                }
+            }
+            let end = rest[start..].find('>').expect("unterminated tag") + start;
+            let tag = &rest[start + 1..end];
+            assert_eq!(tag.matches('"').count() % 2, 0, "unbalanced quotes in <{tag}>");
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop().as_deref(), Some(name), "mismatched </{name}>");
+            } else if !tag.ends_with('/') {
+                open.push(tag.split_whitespace().next().unwrap().to_string());
+            }
+            rest = &rest[end + 1..];
+        }
+        assert!(open.is_empty(), "unclosed {open:?}");
+        assert!(rest.trim().is_empty());
+        elements
+    }
+
+    #[test]
+    fn test_opf_sidecar_parses() {
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("Dune.m4b");
+        let path = write_metadata_sidecar(&output, SidecarFormat::Opf, &book(), &chapters(), &audio()).unwrap();
+        assert_eq!(path, temp_dir.path().join("Dune.opf"));
+
+        let opf = std::fs::read_to_string(&path).unwrap();
+        let elements = parse_xml(&opf);
+        assert!(elements.contains(&("dc:title".to_string(), "Dune".to_string())));
+        assert!(elements.contains(&("dc:creator".to_string(), "Frank Herbert".to_string())));
+        assert!(elements.contains(&("dc:creator".to_string(), "Scott Brick &amp; Simon Vance".to_string())));
+        assert!(elements.contains(&("dc:description".to_string(), "Spice &quot;must&quot; flow".to_string())));
+        assert!(opf.contains(r#"<meta name="calibre:series" content="Dune &lt;Chronicles&gt;"/>"#));
+
+        parse_xml(&opf_sidecar(&AudiobookMetadata::new()));
+    }
+}
@@ -3,6 +3,7 @@
 //! This module provides metrics tracking for audio processing operations,
 //! including throughput calculation and performance monitoring.
 
+use super::display_format::DisplayFormatter;
 use std::time::{Duration, Instant};
 
 /// Metrics tracker for audio processing operations
@@ -51,30 +52,37 @@ impl ProcessingMetrics {
         }
     }
 
-    /// Formats a summary of processing metrics
+    /// Formats a summary of processing metrics in the display locale
     pub fn format_summary(&self) -> String {
+        self.format_summary_with(&DisplayFormatter::current())
+    }
+
+    fn format_summary_with(&self, format: &DisplayFormatter) -> String {
         let elapsed = self.elapsed();
-        let throughput = self.throughput_mbps();
-        let audio_hours = self.total_duration.as_secs_f64() / 3600.0;
-        let mb_processed = self.bytes_processed as f64 / 1_048_576.0;
+        let throughput_bytes = self.throughput_mbps() * 1_048_576.0;
         
         format!(
             "Processing Complete:\n\
              - Files processed: {}\n\
-             - Audio duration: {:.2} hours\n\
-             - Data processed: {:.2} MB\n\
-             - Time elapsed: {}m {}s\n\
-             - Throughput: {:.2} MB/s",
+             - Audio duration: {}\n\
+             - Data processed: {}\n\
+             - Time elapsed: {}\n\
+             - Throughput: {}/s",
             self.files_processed,
-            audio_hours,
-            mb_processed,
-            elapsed.as_secs() / 60,
-            elapsed.as_secs() % 60,
-            throughput
+            format.duration(self.total_duration.as_secs_f64()),
+            format.size(self.bytes_processed as f64),
+            format.duration(elapsed.as_secs() as f64),
+            format.size(throughput_bytes),
         )
     }
 }
 
+impl Default for ProcessingMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,8 +143,21 @@ mod tests {
         let summary = metrics.format_summary();
         
         assert!(summary.contains("Files processed: 2"));
-        assert!(summary.contains("Audio duration: 1.50 hours"));
+        assert!(summary.contains("Audio duration: 1h 30m"));
         assert!(summary.contains("Data processed: 8.00 MB"));
         assert!(summary.contains("Throughput:"));
     }
+
+    #[test]
+    fn test_format_summary_follows_locale() {
+        use crate::audio::display_format::DisplayLocale;
+        let mut metrics = ProcessingMetrics::new();
+        metrics.update_file_processed(Duration::from_secs(5400), 8_388_608);
+
+        let summary = metrics.format_summary_with(&DisplayFormatter::new(DisplayLocale::De));
+        assert!(summary.contains("Audio duration: 1 Std. 30 Min."));
+        assert!(summary.contains("Data processed: 8,00 MB"));
+        let summary = metrics.format_summary_with(&DisplayFormatter::new(DisplayLocale::Fr));
+        assert!(summary.contains("Data processed: 8,00 Mo"));
+    }
 }
\ No newline at end of file
@@ -6,18 +6,56 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use self::constants::{DEFAULT_BITRATE, DEFAULT_SAMPLE_RATE, DEFAULT_OUTPUT_EXTENSION};
+use self::chapters::{ChapterFormat, ChapterOptions, ChapterSpec, ChapterTimestampFormat, ChapterTitleSource};
+use self::input_spec::InputOptions;
+use crate::metadata::ChapterInfo;
 
+pub mod analysis;
+pub mod bitrate_check;
+pub mod calibration;
+pub mod chapters;
 pub mod cleanup;
 pub mod constants;
 pub mod context;
+pub mod cue_sheet;
+pub mod disc_order;
+pub mod disk_space;
+pub mod display_format;
+pub mod dual_mono;
 pub mod file_list;
+pub mod filename;
+pub mod input_spec;
+pub mod input_stats;
+pub mod job_history;
+pub mod jobs;
+pub mod large_output;
 pub mod media_pipeline;
+pub mod metadata_sidecar;
 pub mod metrics;
+pub mod mp3_duration;
+pub mod output_dir;
+pub mod peaks;
+pub mod plan_hash;
+pub mod plan_resolution;
+pub mod post_hook;
+pub mod preferences;
 pub mod processor;
+pub mod publish;
 pub mod progress;
+pub mod progress_log;
 pub mod progress_monitor;
+pub mod progress_sink;
+pub mod progress_snapshot;
+pub mod segments;
+pub mod self_test;
 pub mod session;
 pub mod settings;
+pub mod shutdown;
+pub mod telemetry;
+pub mod temp_layout;
+pub mod timeline;
+pub mod transcripts;
+pub mod verification;
 
 /// Represents an audio file with metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,18 +67,94 @@ pub struct AudioFile {
     pub size: Option<f64>,
     /// Duration in seconds (None if unavailable)
     pub duration: Option<f64>,
+    /// Duration the MP3 header implied, set when `duration` was measured by
+    /// decoding or counting frames because the file has no Xing/VBRI header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_duration: Option<f64>,
     /// Audio format (None if unavailable)
     pub format: Option<String>,
+    /// Codec of the audio stream, e.g. "mp3", "aac", "aac_he", "alac" (None if unknown)
+    #[serde(default)]
+    pub codec: Option<String>,
     /// Bitrate in kbps (None if unavailable)
     pub bitrate: Option<u32>,
     /// Sample rate in Hz (None if unavailable)
     pub sample_rate: Option<u32>,
     /// Number of channels (None if unavailable)
     pub channels: Option<u32>,
+    /// Title tag (None if untagged or unreadable)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Chapters embedded in the file (ID3v2 `CHAP` frames of an MP3), ordered by start time
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chapters: Vec<ChapterInfo>,
     /// Validation status
     pub is_valid: bool,
     /// Error message if validation failed
     pub error: Option<String>,
+    /// Kind of validation failure, for grouping; `error` has the detail
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<FileErrorKind>,
+    /// Whether a stereo file is dual-mono (None if not analyzed or undetermined)
+    #[serde(default)]
+    pub dual_mono: Option<bool>,
+    /// Set when the file isn't fully on disk yet, so analysis should be retried later
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync_status: Option<SyncStatus>,
+    /// Per-file options from the processing request
+    #[serde(default, skip_serializing_if = "InputOptions::is_empty")]
+    pub options: InputOptions,
+    /// How far the file was analyzed; after `Quick`, `is_valid` only means nothing ruled it out
+    #[serde(default, skip_serializing_if = "AnalysisDepth::is_full")]
+    pub analysis_depth: AnalysisDepth,
+}
+
+/// How much of each file analysis reads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnalysisDepth {
+    /// Stat and tags only: no duration, bitrate or validity check, for listing files quickly
+    Quick,
+    /// Probe audio properties and validate the file; what processing always uses
+    #[default]
+    Full,
+}
+
+impl AnalysisDepth {
+    fn is_full(&self) -> bool {
+        *self == Self::Full
+    }
+}
+
+/// Why a file failed validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileErrorKind {
+    /// The path doesn't exist
+    NotFound,
+    /// Not an audio format the app reads
+    Unsupported,
+    /// A supported format that can't be decoded (truncated, damaged or empty)
+    Corrupt,
+    /// Encrypted audio such as Audible AAX or FairPlay M4P
+    DrmProtected,
+    /// The file can't be opened with the user's permissions
+    PermissionDenied,
+    /// Not fully on disk yet; see `sync_status`
+    StillSyncing,
+    /// Analysis didn't finish within the per-file time limit
+    Timeout,
+    /// Anything else
+    Unknown,
+}
+
+/// Why a file couldn't be analyzed yet, as opposed to being invalid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    /// Size or modification time changed during validation (download or copy in progress)
+    StillSyncing,
+    /// Cloud placeholder whose contents were evicted from local storage (macOS dataless file)
+    Dataless,
 }
 
 impl AudioFile {
@@ -50,12 +164,21 @@ impl AudioFile {
             path,
             size: None,
             duration: None,
+            header_duration: None,
             format: None,
+            codec: None,
             bitrate: None,
             sample_rate: None,
             channels: None,
+            title: None,
+            chapters: Vec::new(),
             is_valid: false,
             error: None,
+            error_kind: None,
+            dual_mono: None,
+            sync_status: None,
+            options: InputOptions::default(),
+            analysis_depth: AnalysisDepth::Full,
         }
     }
 }
@@ -82,6 +205,113 @@ pub struct AudioSettings {
     pub sample_rate: SampleRateConfig,
     /// Output file path
     pub output_path: PathBuf,
+    /// Input count above which files are merged in segments (None uses the default of 500)
+    #[serde(default)]
+    pub segment_threshold: Option<usize>,
+    /// Require output filenames valid on Windows/SMB even when not on Windows
+    #[serde(default)]
+    pub strict_cross_platform: bool,
+    /// Encode mono when every stereo input is detected as dual-mono
+    #[serde(default)]
+    pub auto_dual_mono: bool,
+    /// Decode MP3s without a Xing/VBRI header to plan with their true duration
+    #[serde(default)]
+    pub accurate_mp3_durations: bool,
+    /// Refuse to process when the cover art has quality warnings
+    #[serde(default)]
+    pub enforce_cover_quality: bool,
+    /// Render a title and author cover when the book has no artwork anywhere
+    #[serde(default)]
+    pub generate_placeholder_cover: bool,
+    /// What to do when some inputs fail validation
+    #[serde(default)]
+    pub invalid_input_policy: InvalidInputPolicy,
+    /// FFmpeg `-probesize` for the concat input in MB (None keeps FFmpeg's default)
+    #[serde(default)]
+    pub probe_size_mb: Option<u32>,
+    /// FFmpeg `-analyzeduration` for the concat input in seconds (None keeps FFmpeg's default)
+    #[serde(default)]
+    pub analyze_duration_secs: Option<u32>,
+    /// Trim each input's codec delay when joining with the concat filter
+    #[serde(default = "default_true")]
+    pub gapless_join: bool,
+    /// How far the target bitrate may exceed the best source before warning (None uses 1.5)
+    #[serde(default)]
+    pub bitrate_excess_factor: Option<f32>,
+    /// Refuse to process when the target bitrate exceeds the source by more than the factor
+    #[serde(default)]
+    pub strict_bitrate_check: bool,
+    /// Copy `.srt`/`.vtt`/`.txt` transcripts of the inputs next to the output
+    #[serde(default)]
+    pub copy_transcripts: bool,
+    /// Don't copy the first input's track tags ("Track 01") into the book; keep only book-level fields
+    #[serde(default = "default_true")]
+    pub strip_track_scoped_metadata: bool,
+    /// Chapter generation; None gives a multi-file book one chapter per file.
+    /// With options set, converting progress names the active chapter
+    #[serde(default)]
+    pub chapters: Option<ChapterOptions>,
+    /// What per-file chapter titles are made from when no title template is set
+    #[serde(default)]
+    pub chapter_title_source: ChapterTitleSource,
+    /// Chapter list given with the processing request, replacing generated chapters
+    #[serde(skip)]
+    pub chapter_specs: Option<Vec<ChapterSpec>>,
+    /// Accept a `.m4a` output path; the file is the same as a `.m4b`
+    #[serde(default)]
+    pub allow_m4a_extension: bool,
+    /// Decode the finished book in the background and record the result in history
+    #[serde(default)]
+    pub verify_output: bool,
+    /// Byte-identical output for identical inputs and settings: bit-exact
+    /// FFmpeg flags, no creation time, and tags in a fixed order
+    #[serde(default)]
+    pub reproducible: bool,
+    /// Fail the run when the output container can't hold a metadata field,
+    /// instead of writing the rest and warning
+    #[serde(default)]
+    pub strict_metadata_fields: bool,
+    /// Write `{output stem}.cue` next to the output with a track per chapter
+    #[serde(default)]
+    pub export_cue: bool,
+    /// Append a plain-text chapter list to the description, for players without chapter navigation
+    #[serde(default)]
+    pub append_chapter_list_to_description: bool,
+    /// How chapter starts are written in that list
+    #[serde(default)]
+    pub chapter_list_timestamp_format: ChapterTimestampFormat,
+    /// Which MP4 chapter atoms the output gets
+    #[serde(default)]
+    pub chapter_format: ChapterFormat,
+    /// Record the app version and encoder settings in the output's encoder tag
+    #[serde(default = "default_true")]
+    pub write_encoding_info: bool,
+    /// Embed the first input's artwork when no cover was picked
+    #[serde(default = "default_true")]
+    pub auto_cover_from_input: bool,
+    /// Largest cover art embedded, in bytes; None for `MAX_EMBEDDED_COVER_BYTES`
+    #[serde(default)]
+    pub max_cover_bytes: Option<u64>,
+    /// Refuse a cover over the limit instead of shrinking it
+    #[serde(default)]
+    pub strict_cover_size: bool,
+    /// Write `{output stem}.json` or `.opf` with the book's metadata next to the output
+    #[serde(default)]
+    pub metadata_sidecar: Option<metadata_sidecar::SidecarFormat>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Handling of invalid inputs when processing starts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InvalidInputPolicy {
+    /// Any invalid input fails the whole run
+    #[default]
+    Fail,
+    /// Invalid inputs are left out and reported in a warning
+    SkipWithWarning,
 }
 
 /// Channel configuration options
@@ -95,13 +325,43 @@ pub enum ChannelConfig {
 
 impl AudioSettings {
     /// Creates default audio settings
-    #[allow(dead_code)]
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Self {
         Self {
             bitrate: DEFAULT_BITRATE,
             channels: ChannelConfig::Mono,
             sample_rate: SampleRateConfig::Explicit(DEFAULT_SAMPLE_RATE),
-            output_path: PathBuf::from(format!("output.{DEFAULT_OUTPUT_EXTENSION}")),
+            output_path: output_dir::default_output_dir().join(format!("output.{DEFAULT_OUTPUT_EXTENSION}")),
+            segment_threshold: None,
+            strict_cross_platform: false,
+            auto_dual_mono: false,
+            accurate_mp3_durations: false,
+            enforce_cover_quality: false,
+            generate_placeholder_cover: false,
+            invalid_input_policy: InvalidInputPolicy::Fail,
+            probe_size_mb: None,
+            analyze_duration_secs: None,
+            gapless_join: true,
+            bitrate_excess_factor: None,
+            strict_bitrate_check: false,
+            copy_transcripts: false,
+            strip_track_scoped_metadata: true,
+            chapters: None,
+            chapter_title_source: ChapterTitleSource::TagTitle,
+            chapter_specs: None,
+            allow_m4a_extension: false,
+            verify_output: false,
+            reproducible: false,
+            strict_metadata_fields: false,
+            export_cue: false,
+            append_chapter_list_to_description: false,
+            chapter_list_timestamp_format: ChapterTimestampFormat::Auto,
+            chapter_format: ChapterFormat::Ffmetadata,
+            write_encoding_info: true,
+            auto_cover_from_input: true,
+            max_cover_bytes: None,
+            strict_cover_size: false,
+            metadata_sidecar: None,
         }
     }
 }
@@ -109,7 +369,8 @@ impl AudioSettings {
 /// Progress information for audio processing
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessingProgress {
-    /// Current stage of processing
+    /// Current stage of processing, flattened into `stage` and `reason`
+    #[serde(flatten)]
     pub stage: ProcessingStage,
     /// Overall progress percentage (0-100)
     pub progress: f32,
@@ -121,10 +382,20 @@ pub struct ProcessingProgress {
     pub total_files: usize,
     /// Estimated time remaining in seconds
     pub eta_seconds: Option<f64>,
+    /// Smoothed encoding speed as a multiple of realtime
+    #[serde(default)]
+    pub speed: Option<f64>,
+    /// Bytes FFmpeg has written to the current output so far
+    #[serde(default)]
+    pub bytes_written: Option<u64>,
 }
 
 /// Processing stage enumeration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Serializes as `{"stage": "<snake_case>"}`, plus `"reason"` for `Failed`,
+/// so progress structs and events flatten it into the same wire shape.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "StageRepr", try_from = "StageRepr")]
 pub enum ProcessingStage {
     /// Analyzing input files
     Analyzing,
@@ -140,12 +411,69 @@ pub enum ProcessingStage {
     Failed(String),
 }
 
+impl ProcessingStage {
+    /// Canonical snake_case name used on the wire
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Analyzing => "analyzing",
+            Self::Converting => "converting",
+            Self::Merging => "merging",
+            Self::WritingMetadata => "writing_metadata",
+            Self::Completed => "completed",
+            Self::Failed(_) => "failed",
+        }
+    }
+}
+
+impl std::fmt::Display for ProcessingStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Wire form of `ProcessingStage`
+#[derive(Serialize, Deserialize)]
+struct StageRepr {
+    stage: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+impl From<ProcessingStage> for StageRepr {
+    fn from(stage: ProcessingStage) -> Self {
+        let name = stage.as_str().to_string();
+        let reason = match stage {
+            ProcessingStage::Failed(reason) => Some(reason),
+            _ => None,
+        };
+        Self { stage: name, reason }
+    }
+}
+
+impl TryFrom<StageRepr> for ProcessingStage {
+    type Error = String;
+
+    fn try_from(repr: StageRepr) -> std::result::Result<Self, Self::Error> {
+        Ok(match repr.stage.as_str() {
+            "analyzing" => Self::Analyzing,
+            "converting" => Self::Converting,
+            "merging" => Self::Merging,
+            "writing_metadata" => Self::WritingMetadata,
+            "completed" => Self::Completed,
+            "failed" => Self::Failed(repr.reason.unwrap_or_default()),
+            other => return Err(format!("unknown processing stage: {other}")),
+        })
+    }
+}
+
 // Re-export main functions for convenience
-pub use file_list::get_file_list_info;
+pub use file_list::{analyze_file_list, get_file_list_info};
 pub use settings::validate_audio_settings;
 #[allow(unused_imports)] // ProgressEmitter and ProgressEvent are new infrastructure for future use
 pub use progress::{ProgressReporter, ProgressEmitter, ProgressEvent};
-#[allow(deprecated)]
+pub use progress_sink::{NullSink, ProgressSink};
+#[allow(deprecated, unused_imports)] // Commands now build the context themselves to attach job labels
+#[cfg(feature = "gui")]
 pub use processor::process_audiobook_with_events;
 #[allow(unused_imports)] // Context structures are designed for future use
 pub use context::{ProcessingContext, ProcessingContextBuilder, ProgressContext, ProgressContextBuilder};
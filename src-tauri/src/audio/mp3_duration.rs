@@ -0,0 +1,533 @@
+//! Accurate durations for MP3s without a VBR header
+//!
+//! An MP3 records its length only in a Xing/Info or VBRI header inside the
+//! first frame. Without one, Lofty estimates the duration from the file
+//! size and the first frame's bitrate, which is wrong for VBR files and
+//! throws off progress percentages and the output duration check. The
+//! optional accurate mode decodes such files with `-f null` and plans with
+//! the decoded length instead. Measurements are cached per file size/mtime.
+
+use super::constants::{MEASURED_DURATION_CACHE_CAPACITY, MP3_HEADER_SCAN_BYTES, MP3_MAX_UNSYNCED_FRACTION};
+use super::verification::decoded_secs;
+use super::{AudioFile, AudioSettings};
+use crate::errors::{AppError, Result};
+use crate::ffmpeg::{locate_ffmpeg, FFmpegError};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Offset of a VBRI header from the start of its frame
+const VBRI_OFFSET: usize = 36;
+
+/// Identifies a cached measurement; size and mtime invalidate edited files
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct DurationCacheKey {
+    path: PathBuf,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+static MEASURED_DURATIONS: OnceLock<Mutex<HashMap<DurationCacheKey, f64>>> = OnceLock::new();
+
+/// Layer III bitrates in kbps by index, for MPEG-1 and for MPEG-2/2.5
+const MPEG1_BITRATES: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+const MPEG2_BITRATES: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+/// The fields of a Layer III frame header that size and time the frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FrameHeader {
+    mpeg1: bool,
+    mono: bool,
+    sample_rate: u32,
+    /// Samples per channel in the frame
+    samples: u32,
+    /// Frame length in bytes, header included
+    len: usize,
+}
+
+impl FrameHeader {
+    /// None when `bytes` don't start with a valid MPEG audio Layer III frame header
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 4 || bytes[0] != 0xFF || bytes[1] & 0xE0 != 0xE0 {
+            return None;
+        }
+        let version = (bytes[1] >> 3) & 0b11;
+        let layer = (bytes[1] >> 1) & 0b11;
+        let bitrate_index = usize::from(bytes[2] >> 4);
+        let rate_index = (bytes[2] >> 2) & 0b11;
+        if version == 0b01 || layer != 0b01 || bitrate_index == 0 || bitrate_index == 0b1111 || rate_index == 0b11 {
+            return None;
+        }
+        let mpeg1 = version == 0b11;
+        let base_rate = [44100, 48000, 32000][usize::from(rate_index)];
+        let sample_rate = match version {
+            0b11 => base_rate,
+            0b10 => base_rate / 2,
+            _ => base_rate / 4,
+        };
+        let (samples, bitrate) = if mpeg1 {
+            (1152, MPEG1_BITRATES[bitrate_index])
+        } else {
+            (576, MPEG2_BITRATES[bitrate_index])
+        };
+        let padding = usize::from((bytes[2] >> 1) & 1);
+        let len = (samples / 8 * bitrate * 1000 / sample_rate) as usize + padding;
+        Some(Self { mpeg1, mono: bytes[3] >> 6 == 0b11, sample_rate, samples, len })
+    }
+
+    /// Length of the side information following the header
+    fn side_info_len(&self) -> usize {
+        match (self.mpeg1, self.mono) {
+            (true, true) => 17,
+            (true, false) => 32,
+            (false, true) => 9,
+            (false, false) => 17,
+        }
+    }
+}
+
+/// Offset and header of the first Layer III frame in `data`
+fn first_frame(data: &[u8]) -> Option<(usize, FrameHeader)> {
+    (0..data.len().saturating_sub(3))
+        .find_map(|offset| FrameHeader::parse(&data[offset..]).map(|header| (offset, header)))
+}
+
+/// Whether the first Layer III frame in `data` carries a Xing/Info or VBRI header
+///
+/// `data` starts after any ID3v2 tag. None when no frame header is found.
+pub fn has_vbr_header(data: &[u8]) -> Option<bool> {
+    let (start, header) = first_frame(data)?;
+    let tag_at = |offset: usize| data.get(start + offset..start + offset + 4);
+    let xing = tag_at(4 + header.side_info_len()).is_some_and(|tag| tag == b"Xing" || tag == b"Info");
+    Some(xing || tag_at(VBRI_OFFSET) == Some(b"VBRI".as_slice()))
+}
+
+/// Offset where the audio stream starts, past any ID3v2 tag
+fn stream_offset(file: &mut File) -> Result<u64> {
+    let mut header = [0u8; 10];
+    file.seek(SeekFrom::Start(0))?;
+    Ok(match file.read_exact(&mut header) {
+        Ok(()) if header.starts_with(b"ID3") => {
+            let size = header[6..10].iter().fold(0u64, |size, byte| (size << 7) | u64::from(byte & 0x7F));
+            let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+            10 + size + footer
+        }
+        _ => 0,
+    })
+}
+
+/// Reads the start of the audio stream, past any ID3v2 tag
+fn read_stream_start(path: &Path) -> Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let offset = stream_offset(&mut file)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::with_capacity(MP3_HEADER_SCAN_BYTES);
+    file.take(MP3_HEADER_SCAN_BYTES as u64).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Offset where the audio stream ends, before any trailing ID3v1 and APEv2 tags
+fn stream_end(file: &mut File) -> Result<u64> {
+    let mut end = file.seek(SeekFrom::End(0))?;
+    if end >= 128 {
+        let mut marker = [0u8; 3];
+        file.seek(SeekFrom::Start(end - 128))?;
+        file.read_exact(&mut marker)?;
+        if &marker == b"TAG" {
+            end -= 128;
+        }
+    }
+    if end >= 32 {
+        let mut footer = [0u8; 32];
+        file.seek(SeekFrom::Start(end - 32))?;
+        file.read_exact(&mut footer)?;
+        if footer.starts_with(b"APETAGEX") {
+            // The size covers items and footer; a header, when flagged, comes on top
+            let field = |at: usize| u64::from(u32::from_le_bytes([footer[at], footer[at + 1], footer[at + 2], footer[at + 3]]));
+            let header = if field(20) & (1 << 31) != 0 { 32 } else { 0 };
+            end = end.saturating_sub(field(12) + header);
+        }
+    }
+    Ok(end)
+}
+
+/// Reads the frame header at `pos`, leaving the reader just past it
+fn header_at<R: Read + Seek>(reader: &mut BufReader<R>, cursor: &mut u64, pos: u64) -> Result<Option<FrameHeader>> {
+    reader.seek_relative(pos as i64 - *cursor as i64)?;
+    let mut header = [0u8; 4];
+    reader.read_exact(&mut header)?;
+    *cursor = pos + 4;
+    Ok(FrameHeader::parse(&header))
+}
+
+/// Exact length of an MP3 without a VBR header, from its frame headers
+///
+/// Frames times samples per frame over the rate: what a decoder outputs.
+/// Walks every frame header up to any trailing ID3v1 or APEv2 tag, so the
+/// file is read once. Bytes that don't start a frame, like a corrupt frame
+/// mid-file, are skipped until two consecutive frames line up again, the
+/// way a decoder resyncs. None when no frame is found or more than
+/// `MP3_MAX_UNSYNCED_FRACTION` of the stream had to be skipped.
+pub fn frame_count_duration(path: &Path) -> Result<Option<f64>> {
+    let mut file = File::open(path)?;
+    let end = stream_end(&mut file)?;
+    let stream = stream_offset(&mut file)?;
+    file.seek(SeekFrom::Start(stream))?;
+    let mut start = Vec::with_capacity(MP3_HEADER_SCAN_BYTES);
+    (&mut file).take(MP3_HEADER_SCAN_BYTES as u64).read_to_end(&mut start)?;
+    let Some((offset, first)) = first_frame(&start) else { return Ok(None) };
+
+    let mut reader = BufReader::new(file);
+    let mut cursor = reader.seek(SeekFrom::Start(0))?;
+    let same_stream = |frame: &FrameHeader| frame.sample_rate == first.sample_rate && frame.mpeg1 == first.mpeg1;
+    let mut pos = stream + offset as u64;
+    let (mut samples, mut skipped, mut synced) = (0u64, 0u64, true);
+    while pos + 4 <= end {
+        let frame = header_at(&mut reader, &mut cursor, pos)?.filter(same_stream);
+        // Out of sync, a header only counts when the next frame follows it
+        let next = pos + frame.map_or(0, |frame| frame.len as u64);
+        let confirmed = frame.is_some()
+            && (synced || next + 4 > end || header_at(&mut reader, &mut cursor, next)?.is_some_and(|next| same_stream(&next)));
+        match frame.filter(|_| confirmed) {
+            Some(frame) => {
+                samples += u64::from(frame.samples);
+                pos = next;
+                synced = true;
+            }
+            None => {
+                skipped += 1;
+                pos += 1;
+                synced = false;
+            }
+        }
+    }
+    let stream_len = end.saturating_sub(stream + offset as u64);
+    if skipped > 0 {
+        log::warn!("Skipped {skipped} unparseable byte(s) counting frames of {}", path.display());
+    }
+    if samples == 0 || skipped as f64 > stream_len as f64 * MP3_MAX_UNSYNCED_FRACTION {
+        return Ok(None);
+    }
+    Ok(Some(samples as f64 / f64::from(first.sample_rate)))
+}
+
+/// True when an MP3's duration is an estimate because it has no VBR header
+///
+/// A file without any recognisable frame is left to the decoder to reject.
+pub fn lacks_vbr_header(path: &Path) -> Result<bool> {
+    Ok(has_vbr_header(&read_stream_start(path)?) == Some(false))
+}
+
+fn cache_key(path: &Path) -> Result<DurationCacheKey> {
+    let metadata = std::fs::metadata(path)?;
+    Ok(DurationCacheKey {
+        path: std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+    })
+}
+
+fn cached_duration(key: &DurationCacheKey) -> Option<f64> {
+    MEASURED_DURATIONS.get()?.lock().ok()?.get(key).copied()
+}
+
+fn store_duration(key: DurationCacheKey, duration: f64) {
+    let Ok(mut cache) = MEASURED_DURATIONS.get_or_init(Default::default).lock() else { return };
+    if cache.len() >= MEASURED_DURATION_CACHE_CAPACITY {
+        cache.clear();
+    }
+    cache.insert(key, duration);
+}
+
+/// Decodes the whole file and returns the decoded length in seconds
+fn decode_duration(path: &Path) -> Result<f64> {
+    let output = Command::new(locate_ffmpeg()?)
+        .args(["-v", "error", "-nostdin", "-nostats", "-progress", "pipe:1", "-i"])
+        .arg(path)
+        .args(["-vn", "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))?;
+    if !output.status.success() {
+        return Err(FFmpegError::ExecutionFailed(
+            String::from_utf8_lossy(&output.stderr).to_string()
+        ).into());
+    }
+    decoded_secs(&String::from_utf8_lossy(&output.stdout))
+        .filter(|secs| *secs > 0.0)
+        .ok_or_else(|| AppError::FileValidation(format!("No audio decoded from {}", path.display())))
+}
+
+/// Measures the true duration of a file by decoding it, cached per file size/mtime
+pub fn measure_duration(path: &Path) -> Result<f64> {
+    let key = cache_key(path)?;
+    if let Some(duration) = cached_duration(&key) {
+        return Ok(duration);
+    }
+    let duration = decode_duration(path)?;
+    log::info!("Measured {duration:.3}s by decoding {}", path.display());
+    store_duration(key, duration);
+    Ok(duration)
+}
+
+/// Replaces the estimated duration of every valid MP3 without a VBR header with its decoded length
+///
+/// The estimate moves to `header_duration`, which flags the file. Failures
+/// are logged and keep the estimate.
+pub fn annotate_measured_durations(files: &mut [AudioFile]) {
+    for file in files.iter_mut().filter(|f| is_estimated_mp3(f)) {
+        let measured = match lacks_vbr_header(&file.path) {
+            Ok(false) => continue,
+            Ok(true) => measure_duration(&file.path),
+            Err(e) => Err(e),
+        };
+        match measured {
+            Ok(duration) => {
+                file.header_duration = file.duration;
+                file.duration = Some(duration);
+            }
+            Err(e) => log::warn!("Duration measurement failed for {}: {e}", file.path.display()),
+        }
+    }
+}
+
+/// Replaces estimated input durations with the ones a run plans with
+///
+/// `resolve_plan` and the chapter preview both call this, so previewed
+/// chapter times are the ones the run writes.
+pub fn apply_planning_durations(settings: &AudioSettings, files: &mut [AudioFile]) {
+    apply_accurate_durations(settings, files);
+    apply_frame_durations_for_mixed_rates(files);
+}
+
+/// Valid MP3 whose duration is still lofty's estimate
+fn is_estimated_mp3(file: &AudioFile) -> bool {
+    file.is_valid && file.header_duration.is_none() && file.format.as_deref() == Some("MP3")
+}
+
+/// Whether `apply_accurate_durations` may decode any of these inputs
+///
+/// Doesn't read the files, so an MP3 that turns out to have a VBR header counts.
+pub fn duration_measurement_applies(settings: &AudioSettings, files: &[AudioFile]) -> bool {
+    settings.accurate_mp3_durations && files.iter().any(is_estimated_mp3)
+}
+
+/// Measures MP3 durations before planning when `accurate_mp3_durations` is set
+pub fn apply_accurate_durations(settings: &AudioSettings, files: &mut [AudioFile]) {
+    if settings.accurate_mp3_durations {
+        annotate_measured_durations(files);
+    }
+}
+
+/// Counts the frames of MP3s without a VBR header when the inputs mix sample rates
+///
+/// Lofty estimates those durations from the nominal bitrate. Unpadded
+/// frames run short of it by a fraction that depends on the rate (about
+/// 0.5% at 22.05 kHz, 0.2% at 44.1 kHz), so once the inputs are resampled
+/// into one book the estimates no longer agree with the encoded positions
+/// and chapter starts drift. Like a decoded measurement, the estimate moves
+/// to `header_duration`.
+pub fn apply_frame_durations_for_mixed_rates(files: &mut [AudioFile]) {
+    let mut rates = files.iter().filter(|f| f.is_valid).filter_map(|f| f.sample_rate);
+    let Some(first_rate) = rates.next() else { return };
+    if rates.all(|rate| rate == first_rate) {
+        return;
+    }
+    for file in files.iter_mut().filter(|f| is_estimated_mp3(f)) {
+        let counted = match lacks_vbr_header(&file.path) {
+            Ok(false) => continue,
+            Ok(true) => frame_count_duration(&file.path),
+            Err(e) => Err(e),
+        };
+        match counted {
+            Ok(Some(duration)) => {
+                file.header_duration = file.duration;
+                file.duration = Some(duration);
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Frame count failed for {}: {e}", file.path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod fixtures {
+    /// Frame length of 128 kbps MPEG-1 Layer III at 44.1 kHz without padding
+    pub const FRAME_LEN: usize = 417;
+
+    /// Seconds of audio in `frames` frames of 1152 samples at 44.1 kHz
+    pub fn cbr_secs(frames: usize) -> f64 {
+        frames as f64 * 1152.0 / 44100.0
+    }
+
+    /// A silent CBR MP3 of `frames` 128 kbps stereo frames, without a VBR header
+    pub fn cbr_mp3(frames: usize) -> Vec<u8> {
+        let mut frame = vec![0u8; FRAME_LEN];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        frame.repeat(frames)
+    }
+
+    /// A silent CBR MP3 of `frames` 64 kbps mono MPEG-2 frames at 22.05 kHz,
+    /// 208 bytes each without padding
+    pub fn cbr_mp3_22k(frames: usize) -> Vec<u8> {
+        let mut frame = vec![0u8; 208];
+        frame[..4].copy_from_slice(&[0xFF, 0xF3, 0x80, 0xC0]);
+        frame.repeat(frames)
+    }
+
+    /// Seconds of audio in `frames` frames of 576 samples at 22.05 kHz
+    pub fn cbr_secs_22k(frames: usize) -> f64 {
+        frames as f64 * 576.0 / 22050.0
+    }
+
+    /// The same stream with the first frame byte-patched to 32 kbps (and cut
+    /// to that frame length), so the header implies about four times the
+    /// real duration
+    pub fn corrupted_cbr_mp3(frames: usize) -> Vec<u8> {
+        let mut data = cbr_mp3(frames);
+        data[2] = 0x10;
+        data.drain(104..FRAME_LEN);
+        data
+    }
+
+    /// Caches `secs` as the decoded length of `path`, standing in for FFmpeg
+    pub fn seed_measured_duration(path: &std::path::Path, secs: f64) {
+        super::store_duration(super::cache_key(path).unwrap(), secs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{cbr_mp3, cbr_mp3_22k, cbr_secs, cbr_secs_22k, corrupted_cbr_mp3, FRAME_LEN};
+    use super::*;
+    use crate::audio::file_list::get_file_list_info;
+    use tempfile::TempDir;
+
+    fn write_corrupted(dir: &TempDir, name: &str, frames: usize) -> PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, corrupted_cbr_mp3(frames)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_detects_vbr_headers() {
+        let mut stream = cbr_mp3(2);
+        assert_eq!(has_vbr_header(&stream), Some(false));
+        stream[36..40].copy_from_slice(b"Xing");
+        assert_eq!(has_vbr_header(&stream), Some(true));
+
+        // Mono MPEG-1 keeps its side information short; VBRI has a fixed offset
+        let mut mono = cbr_mp3(2);
+        mono[3] = 0xC0;
+        mono[21..25].copy_from_slice(b"Info");
+        assert_eq!(has_vbr_header(&mono), Some(true));
+        let mut vbri = cbr_mp3(2);
+        vbri[VBRI_OFFSET..VBRI_OFFSET + 4].copy_from_slice(b"VBRI");
+        assert_eq!(has_vbr_header(&[&[0u8; 7][..], &vbri].concat()), Some(true));
+
+        assert_eq!(has_vbr_header(b"not an mp3 at all"), None);
+    }
+
+    #[test]
+    fn test_header_scan_skips_id3_tag() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tagged.mp3");
+        let mut data = b"ID3\x03\x00\x00\x00\x00\x01\x00".to_vec();
+        data.extend([0u8; 128]);
+        data.extend(cbr_mp3(4));
+        std::fs::write(&path, &data).unwrap();
+        assert!(lacks_vbr_header(&path).unwrap());
+
+        data[10 + 128 + 36..10 + 128 + 40].copy_from_slice(b"Info");
+        std::fs::write(&path, &data).unwrap();
+        assert!(!lacks_vbr_header(&path).unwrap());
+    }
+
+    #[test]
+    fn test_frame_count_resyncs_past_a_corrupt_frame() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("damaged.mp3");
+        let mut data = cbr_mp3(300);
+        // Frame 150's header overwritten, as by a bad sector
+        data[150 * FRAME_LEN..150 * FRAME_LEN + 4].copy_from_slice(&[0x12, 0x34, 0x56, 0x78]);
+        let ape_footer = [b"APETAGEX".as_slice(), &2000u32.to_le_bytes(), &64u32.to_le_bytes(), &0u32.to_le_bytes(), &(1u32 << 31).to_le_bytes(), &[0u8; 8]].concat();
+        let tags = [vec![0xFFu8; 32 + 32], ape_footer, b"TAG".to_vec(), vec![0xFF; 125]].concat();
+        std::fs::write(&path, [data.clone(), tags].concat()).unwrap();
+        assert_eq!(stream_end(&mut File::open(&path).unwrap()).unwrap(), data.len() as u64);
+        assert_eq!(frame_count_duration(&path).unwrap(), Some(cbr_secs(299)));
+
+        // Mostly garbage: the count can't be trusted
+        let garbage = [cbr_mp3(10), vec![0x55; 100 * FRAME_LEN]].concat();
+        std::fs::write(&path, garbage).unwrap();
+        assert_eq!(frame_count_duration(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn test_corrected_duration_is_used_for_planning() {
+        let dir = TempDir::new().unwrap();
+        let path = write_corrupted(&dir, "corrupted.mp3", 300);
+        let estimate = get_file_list_info(&[&path]).unwrap().files[0].duration.unwrap();
+        assert!(estimate > cbr_secs(300) * 2.0, "header implies {estimate}s");
+
+        // Stands in for the decode, so the planning side runs without FFmpeg
+        store_duration(cache_key(&path).unwrap(), cbr_secs(300));
+        let mut settings = AudioSettings::default();
+        settings.accurate_mp3_durations = true;
+        let files = get_file_list_info(&[&path]).unwrap().files;
+        let resolution = crate::audio::plan_resolution::resolve_plan(files, &settings).unwrap();
+        assert_eq!(resolution.files[0].duration, Some(cbr_secs(300)));
+        assert_eq!(resolution.files[0].header_duration, Some(estimate));
+
+        // Off by default
+        let files = get_file_list_info(&[&path]).unwrap().files;
+        let resolution = crate::audio::plan_resolution::resolve_plan(files, &AudioSettings::default()).unwrap();
+        assert_eq!(resolution.files[0].duration, Some(estimate));
+        assert_eq!(resolution.files[0].header_duration, None);
+    }
+
+    #[test]
+    fn test_decoding_measures_true_duration() {
+        if locate_ffmpeg().is_err() {
+            eprintln!("Skipping: FFmpeg not available");
+            return;
+        }
+        let dir = TempDir::new().unwrap();
+        let path = write_corrupted(&dir, "decoded.mp3", 300);
+        let mut files = get_file_list_info(&[&path]).unwrap().files;
+        annotate_measured_durations(&mut files);
+        let measured = files[0].duration.unwrap();
+        assert!((measured - cbr_secs(300)).abs() < 0.1, "decoded {measured}s");
+        assert!(files[0].header_duration.is_some());
+    }
+
+    #[test]
+    fn test_mixed_rate_chapters_follow_frame_counts() {
+        let dir = TempDir::new().unwrap();
+        let first = dir.path().join("01 - Low.mp3");
+        let second = dir.path().join("02 - High.mp3");
+        std::fs::write(&first, cbr_mp3_22k(2297)).unwrap();
+        std::fs::write(&second, [cbr_mp3(2297), b"TAG".to_vec(), vec![0u8; 125]].concat()).unwrap();
+        let boundaries = [cbr_secs_22k(2297), cbr_secs_22k(2297) + cbr_secs(2297)];
+
+        // Lofty's bitrate estimate alone puts the second chapter over 250 ms early
+        let files = get_file_list_info(&[&first, &second]).unwrap().files;
+        assert!(boundaries[0] - files[0].duration.unwrap() > 0.25);
+
+        let resolution = crate::audio::plan_resolution::resolve_plan(files, &AudioSettings::default()).unwrap();
+        let chapters = &resolution.chapters;
+        assert_eq!(chapters.len(), 2);
+        assert!((chapters[1].start_secs - boundaries[0]).abs() < 0.25, "{chapters:?}");
+        assert!((chapters[1].end_secs - boundaries[1]).abs() < 0.25, "{chapters:?}");
+        assert_eq!(resolution.files[0].duration, Some(boundaries[0]));
+        assert!(resolution.files[0].header_duration.is_some());
+
+        // Inputs at one rate keep Lofty's durations
+        let files = get_file_list_info(&[&first]).unwrap().files;
+        let resolution = crate::audio::plan_resolution::resolve_plan(files, &AudioSettings::default()).unwrap();
+        assert_eq!(resolution.files[0].header_duration, None);
+    }
+}
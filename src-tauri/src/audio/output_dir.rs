@@ -0,0 +1,113 @@
+//! Default output directory preference and output path resolution
+//!
+//! A bare filename as output path would land in the process working
+//! directory, which on macOS is inside the app bundle. Output paths must be
+//! absolute, or relative to an output directory the user chose explicitly.
+
+use crate::errors::{AppError, Result};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Output directory chosen in preferences, if any
+static DEFAULT_OUTPUT_DIR: OnceLock<Mutex<Option<PathBuf>>> = OnceLock::new();
+
+/// Stores the user's default output directory
+pub fn set_default_output_dir(dir: &Path) -> Result<()> {
+    if !dir.is_absolute() {
+        return Err(AppError::InvalidInput(
+            format!("Default output directory must be absolute, got: {}", dir.display())
+        ));
+    }
+    if !dir.is_dir() {
+        return Err(AppError::FileValidation(
+            format!("Default output directory does not exist: {}", dir.display())
+        ));
+    }
+    let mut current = DEFAULT_OUTPUT_DIR.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Output directory preference poisoned".to_string()))?;
+    *current = Some(dir.to_path_buf());
+    Ok(())
+}
+
+/// The output directory set in preferences, if the user chose one
+pub fn configured_output_dir() -> Option<PathBuf> {
+    DEFAULT_OUTPUT_DIR.get_or_init(Default::default).lock().ok()?.clone()
+}
+
+/// Directory presets write into: the preference, else the home directory
+pub fn default_output_dir() -> PathBuf {
+    configured_output_dir()
+        .or_else(home_dir)
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+fn home_dir() -> Option<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+}
+
+/// Makes an output path absolute against the configured output directory
+pub fn resolve_output_path(path: &Path) -> Result<PathBuf> {
+    resolve_against(path, configured_output_dir().as_deref())
+}
+
+/// Resolves `path` against an explicit base directory, rejecting relative
+/// paths when there is none
+fn resolve_against(path: &Path, base_dir: Option<&Path>) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    match base_dir {
+        Some(base) if base.is_absolute() => Ok(base.join(path)),
+        _ => Err(AppError::InvalidInput(format!(
+            "Output path must be absolute, got: {}. Choose an output folder or set a default output directory",
+            path.display()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioSettings;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_relative_path_rejected_without_base() {
+        let result = resolve_against(Path::new("audiobook.m4b"), None);
+        assert!(matches!(&result, Err(AppError::InvalidInput(msg)) if msg.contains("must be absolute")));
+    }
+
+    #[test]
+    fn test_relative_path_resolved_against_base() {
+        let temp_dir = TempDir::new().unwrap();
+        let resolved = resolve_against(Path::new("Dune.m4b"), Some(temp_dir.path())).unwrap();
+        assert_eq!(resolved, temp_dir.path().join("Dune.m4b"));
+
+        let absolute = temp_dir.path().join("abs.m4b");
+        assert_eq!(resolve_against(&absolute, None).unwrap(), absolute);
+    }
+
+    #[test]
+    fn test_presets_use_default_output_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        set_default_output_dir(temp_dir.path()).unwrap();
+
+        for settings in [
+            AudioSettings::audiobook_preset(),
+            AudioSettings::high_quality_preset(),
+            AudioSettings::low_bandwidth_preset(),
+        ] {
+            assert!(settings.output_path.is_absolute());
+            assert_eq!(settings.output_path.parent(), Some(temp_dir.path()));
+        }
+    }
+
+    #[test]
+    fn test_default_output_dir_must_exist_and_be_absolute() {
+        assert!(set_default_output_dir(Path::new("relative/dir")).is_err());
+        assert!(set_default_output_dir(Path::new("/nonexistent/output/dir")).is_err());
+    }
+}
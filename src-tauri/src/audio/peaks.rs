@@ -0,0 +1,257 @@
+//! Waveform peak extraction for file list previews
+//!
+//! FFmpeg decodes the input to mono f32 PCM on stdout; samples are folded
+//! into a fixed number of buckets as they stream in, so memory stays bounded
+//! by the bucket count no matter how long the file is.
+
+use super::constants::{MAX_PEAK_BUCKETS, PEAK_CACHE_CAPACITY, PEAK_READ_CHUNK_BYTES, PEAK_SAMPLE_RATE};
+use crate::errors::{AppError, CancelReason, Result};
+use crate::ffmpeg::{locate_ffmpeg, FFmpegError};
+use lofty::file::AudioFile;
+use lofty::probe::Probe;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Identifies a cached peak result; size and mtime invalidate edited files
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PeakCacheKey {
+    path: PathBuf,
+    size: u64,
+    modified: Option<SystemTime>,
+    buckets: u32,
+}
+
+static PEAK_CACHE: OnceLock<Mutex<HashMap<PeakCacheKey, Vec<f32>>>> = OnceLock::new();
+
+/// Bumped by `cancel_peak_extraction`; running extractions stop when it changes
+static CANCEL_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Folds streamed samples into max-abs peak buckets
+#[derive(Debug)]
+pub struct PeakAccumulator {
+    peaks: Vec<f32>,
+    expected_samples: u64,
+    samples_seen: u64,
+}
+
+impl PeakAccumulator {
+    /// Creates an accumulator for `buckets` peaks over `expected_samples`
+    pub fn new(buckets: u32, expected_samples: u64) -> Self {
+        Self {
+            peaks: vec![0.0; buckets.max(1) as usize],
+            expected_samples: expected_samples.max(1),
+            samples_seen: 0,
+        }
+    }
+
+    /// Adds a run of samples; anything past the expected length lands in the last bucket
+    pub fn push(&mut self, samples: &[f32]) {
+        let buckets = self.peaks.len() as u64;
+        for sample in samples {
+            let index = (self.samples_seen * buckets / self.expected_samples).min(buckets - 1);
+            let peak = &mut self.peaks[index as usize];
+            let magnitude = if sample.is_finite() { sample.abs() } else { 0.0 };
+            if magnitude > *peak {
+                *peak = magnitude;
+            }
+            self.samples_seen += 1;
+        }
+    }
+
+    /// Returns the peaks scaled so the loudest bucket is 1.0
+    pub fn finish(self) -> Vec<f32> {
+        let loudest = self.peaks.iter().copied().fold(0.0_f32, f32::max);
+        if loudest <= 0.0 {
+            return self.peaks;
+        }
+        self.peaks.into_iter()
+            .map(|peak| (peak / loudest).clamp(0.0, 1.0))
+            .collect()
+    }
+}
+
+/// Decodes little-endian f32 samples, returning how many bytes were consumed
+fn decode_f32le(bytes: &[u8], samples: &mut Vec<f32>) -> usize {
+    samples.clear();
+    let whole = bytes.len() - bytes.len() % 4;
+    samples.extend(bytes[..whole].chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])));
+    whole
+}
+
+/// Clamps the requested bucket count to the supported range
+fn validate_buckets(buckets: u32) -> Result<u32> {
+    if buckets == 0 {
+        return Err(AppError::InvalidInput("Peak bucket count must be at least 1".to_string()));
+    }
+    Ok(buckets.min(MAX_PEAK_BUCKETS))
+}
+
+fn cache_key(path: &Path, buckets: u32) -> Result<PeakCacheKey> {
+    let metadata = std::fs::metadata(path)?;
+    Ok(PeakCacheKey {
+        path: std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+        buckets,
+    })
+}
+
+fn cached_peaks(key: &PeakCacheKey) -> Option<Vec<f32>> {
+    PEAK_CACHE.get()?.lock().ok()?.get(key).cloned()
+}
+
+fn store_peaks(key: PeakCacheKey, peaks: &[f32]) {
+    let Ok(mut cache) = PEAK_CACHE.get_or_init(Default::default).lock() else { return };
+    if cache.len() >= PEAK_CACHE_CAPACITY {
+        cache.clear();
+    }
+    cache.insert(key, peaks.to_vec());
+}
+
+/// Requests that every running peak extraction stops
+pub fn cancel_peak_extraction() {
+    CANCEL_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Extracts normalized 0..1 waveform peaks for a single audio file
+///
+/// Results are cached per file size/mtime and bucket count. Buckets are
+/// capped at `MAX_PEAK_BUCKETS`.
+pub fn extract_peaks<P: AsRef<Path>>(path: P, buckets: u32) -> Result<Vec<f32>> {
+    let path = path.as_ref();
+    let buckets = validate_buckets(buckets)?;
+    if !path.is_file() {
+        return Err(AppError::FileValidation(format!(
+            "Audio file not found: {}", path.display()
+        )));
+    }
+
+    let key = cache_key(path, buckets)?;
+    if let Some(peaks) = cached_peaks(&key) {
+        return Ok(peaks);
+    }
+
+    let generation = CANCEL_GENERATION.load(Ordering::SeqCst);
+    let peaks = decode_peaks(path, buckets, || {
+        CANCEL_GENERATION.load(Ordering::SeqCst) != generation
+    })?;
+    store_peaks(key, &peaks);
+    Ok(peaks)
+}
+
+/// Streams FFmpeg's PCM output through a `PeakAccumulator`
+fn decode_peaks<F: Fn() -> bool>(path: &Path, buckets: u32, is_cancelled: F) -> Result<Vec<f32>> {
+    let duration = Probe::open(path)?.read()?.properties().duration().as_secs_f64();
+    let expected_samples = (duration * f64::from(PEAK_SAMPLE_RATE)).ceil() as u64;
+
+    let mut child = Command::new(locate_ffmpeg()?)
+        .args(["-v", "error", "-nostdin", "-i"])
+        .arg(path)
+        .args(["-vn", "-ac", "1", "-ar", &PEAK_SAMPLE_RATE.to_string(), "-f", "f32le", "pipe:1"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))?;
+
+    let mut stdout = child.stdout.take()
+        .ok_or_else(|| FFmpegError::ExecutionFailed("FFmpeg stdout unavailable".to_string()))?;
+    let mut accumulator = PeakAccumulator::new(buckets, expected_samples);
+    let mut buffer = vec![0u8; PEAK_READ_CHUNK_BYTES];
+    let mut pending = 0;
+    let mut samples = Vec::with_capacity(PEAK_READ_CHUNK_BYTES / 4);
+
+    loop {
+        if is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(AppError::Cancelled(CancelReason::UserRequested));
+        }
+        let read = stdout.read(&mut buffer[pending..])?;
+        if read == 0 {
+            break;
+        }
+        let filled = pending + read;
+        let consumed = decode_f32le(&buffer[..filled], &mut samples);
+        accumulator.push(&samples);
+        buffer.copy_within(consumed..filled, 0);
+        pending = filled - consumed;
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(FFmpegError::ExecutionFailed(format!(
+            "Peak extraction failed for {}: {status}", path.display()
+        )).into());
+    }
+    Ok(accumulator.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peaks_take_max_abs_per_bucket() {
+        let mut accumulator = PeakAccumulator::new(4, 8);
+        accumulator.push(&[0.1, -0.2, 0.4, -0.1, 0.0, 0.05, -0.8, 0.3]);
+        let peaks = accumulator.finish();
+        let expected = [0.25, 0.5, 0.0625, 1.0];
+        assert_eq!(peaks.len(), expected.len());
+        for (peak, want) in peaks.iter().zip(expected) {
+            assert!((peak - want).abs() < 1e-6, "{peak} != {want}");
+        }
+    }
+
+    #[test]
+    fn test_peaks_across_chunk_boundaries_match_single_push() {
+        let samples: Vec<f32> = (0..1000).map(|i| ((i as f32) * 0.01).sin()).collect();
+        let mut whole = PeakAccumulator::new(10, 1000);
+        whole.push(&samples);
+        let mut chunked = PeakAccumulator::new(10, 1000);
+        for chunk in samples.chunks(7) {
+            chunked.push(chunk);
+        }
+        assert_eq!(whole.finish(), chunked.finish());
+    }
+
+    #[test]
+    fn test_silence_and_overflow_samples() {
+        let silent = PeakAccumulator::new(3, 30);
+        assert_eq!(silent.finish(), vec![0.0; 3]);
+
+        // More samples than expected fold into the final bucket
+        let mut overflow = PeakAccumulator::new(2, 2);
+        overflow.push(&[0.5, 0.25, 1.0, f32::NAN]);
+        assert_eq!(overflow.finish(), vec![0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_decode_f32le_keeps_partial_sample() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&0.5f32.to_le_bytes());
+        bytes.extend_from_slice(&(-1.0f32).to_le_bytes());
+        bytes.extend_from_slice(&[0x00, 0x01]);
+        let mut samples = Vec::new();
+        assert_eq!(decode_f32le(&bytes, &mut samples), 8);
+        assert_eq!(samples, vec![0.5, -1.0]);
+    }
+
+    #[test]
+    fn test_bucket_validation() {
+        assert!(matches!(validate_buckets(0), Err(AppError::InvalidInput(_))));
+        assert_eq!(validate_buckets(50).unwrap(), 50);
+        assert_eq!(validate_buckets(10_000).unwrap(), MAX_PEAK_BUCKETS);
+    }
+
+    #[test]
+    fn test_extract_peaks_missing_file() {
+        let result = extract_peaks("nonexistent.mp3", 100);
+        assert!(matches!(result, Err(AppError::FileValidation(_))));
+    }
+}
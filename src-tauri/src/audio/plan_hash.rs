@@ -0,0 +1,387 @@
+//! Fingerprint of everything that determines a run's output
+//!
+//! The canonical input paths with their sizes and modification times, the
+//! settings that affect the encode and the metadata are serialized into a
+//! JSON document with a pinned field order and hashed with SHA-256. The
+//! hash is stored in the run's history record and in a sidecar next to the
+//! output, so a rerun of the same plan can be detected before encoding.
+//! Generated chapters are derived from the inputs, so only an explicit
+//! chapter list needs an entry of its own.
+
+use super::constants::{PLAN_HASH_SIDECAR_EXTENSION, PLAN_HASH_VERSION};
+use super::job_history::{processing_history, HistoryRecord};
+use super::output_dir::resolve_output_path;
+use super::chapters::{ChapterFormat, ChapterSpec, ChapterTimestampFormat};
+use super::{AudioSettings, InvalidInputPolicy, SampleRateConfig};
+use crate::errors::{AppError, Result};
+use crate::metadata::AudiobookMetadata;
+use crate::util::hashing::sha256_hex;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Document that is hashed; field order is part of the hash
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlanFingerprint<'a> {
+    version: u32,
+    inputs: Vec<InputFingerprint>,
+    settings: SettingsFingerprint,
+    metadata: Option<MetadataFingerprint<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InputFingerprint {
+    path: String,
+    size: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+}
+
+/// Settings that change the output; UI-only preferences are left out
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SettingsFingerprint {
+    bitrate: u32,
+    channels: u8,
+    /// None for auto-detection, which is fixed by the hashed inputs
+    sample_rate: Option<u32>,
+    output_path: String,
+    auto_dual_mono: bool,
+    invalid_input_policy: InvalidInputPolicy,
+    probe_size_mb: Option<u32>,
+    analyze_duration_secs: Option<u32>,
+    gapless_join: bool,
+    strip_track_scoped_metadata: bool,
+    /// Left out when off so hashes from before the setting still match
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    reproducible: bool,
+    /// Decoded MP3 durations move generated chapters; left out when off
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    accurate_mp3_durations: bool,
+    /// Explicit chapter list; left out when chapters are generated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chapters: Option<Vec<ChapterSpec>>,
+    /// Timestamp format of the chapter list in the description; left out when there is no list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chapter_list: Option<ChapterTimestampFormat>,
+    /// MP4 chapter atoms; left out for the default so older hashes still match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chapter_format: Option<ChapterFormat>,
+    /// Placeholder cover fallback; left out when off
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    generate_placeholder_cover: bool,
+    /// Encoder tag left as FFmpeg wrote it; left out for the default of recording the settings
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    omit_encoding_info: bool,
+    /// First input's artwork left out of a coverless book; left out for the default of carrying it
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    omit_input_cover: bool,
+    /// Embedded cover size limit; left out for the default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_cover_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MetadataFingerprint<'a> {
+    title: Option<&'a str>,
+    /// Left out when unset so hashes from before the field still match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subtitle: Option<&'a str>,
+    author: Option<&'a str>,
+    album: Option<&'a str>,
+    narrator: Option<&'a str>,
+    year: Option<u32>,
+    genre: Option<&'a str>,
+    description: Option<&'a str>,
+    series: Option<&'a str>,
+    series_index: Option<f32>,
+    /// Track, disc, sort and identifier fields are left out when unset, like the subtitle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    track_number: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    track_total: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    disc_number: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    disc_total: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sort_title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sort_author: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    isbn: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    asin: Option<&'a str>,
+    cover_sha256: Option<String>,
+}
+
+/// Computes the plan hash for a set of inputs, settings and metadata
+pub fn plan_hash<P: AsRef<Path>>(
+    file_paths: &[P],
+    settings: &AudioSettings,
+    metadata: Option<&AudiobookMetadata>,
+) -> Result<String> {
+    let inputs = file_paths.iter()
+        .map(|path| input_fingerprint(path.as_ref()))
+        .collect::<Result<Vec<_>>>()?;
+    let fingerprint = PlanFingerprint {
+        version: PLAN_HASH_VERSION,
+        inputs,
+        settings: settings_fingerprint(settings)?,
+        metadata: metadata.map(metadata_fingerprint),
+    };
+    Ok(sha256_hex(canonical_json(&fingerprint)?.as_bytes()))
+}
+
+fn canonical_json(fingerprint: &PlanFingerprint) -> Result<String> {
+    serde_json::to_string(fingerprint)
+        .map_err(|e| AppError::General(format!("Cannot serialize plan fingerprint: {e}")))
+}
+
+fn input_fingerprint(path: &Path) -> Result<InputFingerprint> {
+    let canonical = std::fs::canonicalize(path)
+        .map_err(|e| AppError::FileValidation(format!("Cannot resolve input {}: {e}", path.display())))?;
+    let metadata = std::fs::metadata(&canonical)?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok(InputFingerprint {
+        path: portable_path(&canonical),
+        size: metadata.len(),
+        modified_secs: modified.as_secs(),
+        modified_nanos: modified.subsec_nanos(),
+    })
+}
+
+fn settings_fingerprint(settings: &AudioSettings) -> Result<SettingsFingerprint> {
+    Ok(SettingsFingerprint {
+        bitrate: settings.bitrate,
+        channels: settings.channels.channel_count(),
+        sample_rate: match settings.sample_rate {
+            SampleRateConfig::Auto => None,
+            SampleRateConfig::Explicit(rate) => Some(rate),
+        },
+        output_path: portable_path(&resolve_output_path(&settings.output_path)?),
+        auto_dual_mono: settings.auto_dual_mono,
+        invalid_input_policy: settings.invalid_input_policy,
+        probe_size_mb: settings.probe_size_mb,
+        analyze_duration_secs: settings.analyze_duration_secs,
+        gapless_join: settings.gapless_join,
+        strip_track_scoped_metadata: settings.strip_track_scoped_metadata,
+        reproducible: settings.reproducible,
+        accurate_mp3_durations: settings.accurate_mp3_durations,
+        chapters: settings.chapter_specs.clone(),
+        chapter_list: settings.append_chapter_list_to_description.then_some(settings.chapter_list_timestamp_format),
+        chapter_format: (settings.chapter_format != ChapterFormat::Ffmetadata).then_some(settings.chapter_format),
+        generate_placeholder_cover: settings.generate_placeholder_cover,
+        omit_encoding_info: !settings.write_encoding_info,
+        omit_input_cover: !settings.auto_cover_from_input,
+        max_cover_bytes: settings.max_cover_bytes,
+    })
+}
+
+fn metadata_fingerprint(metadata: &AudiobookMetadata) -> MetadataFingerprint<'_> {
+    MetadataFingerprint {
+        title: metadata.title.as_deref(),
+        subtitle: metadata.subtitle.as_deref(),
+        author: metadata.author.as_deref(),
+        album: metadata.album.as_deref(),
+        narrator: metadata.narrator.as_deref(),
+        year: metadata.year,
+        genre: metadata.genre.as_deref(),
+        description: metadata.description.as_deref(),
+        series: metadata.series.as_deref(),
+        series_index: metadata.series_index,
+        track_number: metadata.track_number,
+        track_total: metadata.track_total,
+        disc_number: metadata.disc_number,
+        disc_total: metadata.disc_total,
+        sort_title: metadata.sort_title.as_deref(),
+        sort_author: metadata.sort_author.as_deref(),
+        isbn: metadata.isbn.as_deref(),
+        asin: metadata.asin.as_deref(),
+        cover_sha256: metadata.cover_art.as_deref().map(sha256_hex),
+    }
+}
+
+/// Path text with `/` separators and without the Windows verbatim prefix
+fn portable_path(path: &Path) -> String {
+    let text = path.to_string_lossy();
+    text.strip_prefix(r"\\?\").unwrap_or(&text).replace('\\', "/")
+}
+
+/// Sidecar holding the plan hash next to an output: `Book.m4b.planhash`
+pub fn sidecar_path(output: &Path) -> PathBuf {
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{PLAN_HASH_SIDECAR_EXTENSION}"));
+    output.with_file_name(name)
+}
+
+/// Writes the plan hash sidecar for a finished output
+pub fn write_sidecar(output: &Path, hash: &str) -> Result<()> {
+    std::fs::write(sidecar_path(output), hash)?;
+    Ok(())
+}
+
+fn read_sidecar(output: &Path) -> Option<String> {
+    std::fs::read_to_string(sidecar_path(output)).ok().map(|hash| hash.trim().to_string())
+}
+
+/// Finds a previous successful run of the same plan whose output is still in place
+pub fn check_already_processed<P: AsRef<Path>>(
+    file_paths: &[P],
+    settings: &AudioSettings,
+    metadata: Option<&AudiobookMetadata>,
+) -> Result<Option<HistoryRecord>> {
+    let hash = plan_hash(file_paths, settings, metadata)?;
+    Ok(find_matching_run(processing_history()?, &hash))
+}
+
+/// Newest successful record with `hash` whose output still carries it
+fn find_matching_run(records: Vec<HistoryRecord>, hash: &str) -> Option<HistoryRecord> {
+    records.into_iter().rev().find(|record| {
+        record.succeeded
+            && record.plan_hash.as_deref() == Some(hash)
+            && record.output_path.is_file()
+            && read_sidecar(&record.output_path).as_deref() == Some(hash)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::session::ProcessingSession;
+    use tempfile::TempDir;
+
+    fn settings(output: &Path) -> AudioSettings {
+        let mut settings = AudioSettings::audiobook_preset();
+        settings.output_path = output.to_path_buf();
+        settings
+    }
+
+    #[test]
+    fn test_canonical_json_field_order() {
+        let metadata = AudiobookMetadata {
+            title: Some("Dune".to_string()),
+            series_index: Some(1.5),
+            cover_art: Some(vec![1, 2, 3]),
+            ..AudiobookMetadata::new()
+        };
+        let fingerprint = PlanFingerprint {
+            version: 1,
+            inputs: vec![InputFingerprint { path: "/books/01.mp3".to_string(), size: 10, modified_secs: 20, modified_nanos: 30 }],
+            settings: SettingsFingerprint {
+                bitrate: 64,
+                channels: 1,
+                sample_rate: None,
+                output_path: "/out/Dune.m4b".to_string(),
+                auto_dual_mono: false,
+                invalid_input_policy: InvalidInputPolicy::Fail,
+                probe_size_mb: None,
+                analyze_duration_secs: Some(100),
+                gapless_join: true,
+                strip_track_scoped_metadata: true,
+                reproducible: false,
+                accurate_mp3_durations: false,
+                chapters: None,
+                chapter_list: None,
+                chapter_format: None,
+                generate_placeholder_cover: false,
+                omit_encoding_info: false,
+                omit_input_cover: false,
+                max_cover_bytes: None,
+            },
+            metadata: Some(metadata_fingerprint(&metadata)),
+        };
+        let json = canonical_json(&fingerprint).unwrap();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"version":1,"inputs":[{"path":"/books/01.mp3","size":10,"modifiedSecs":20,"modifiedNanos":30}],"#,
+                r#""settings":{"bitrate":64,"channels":1,"sampleRate":null,"outputPath":"/out/Dune.m4b","autoDualMono":false,"#,
+                r#""invalidInputPolicy":"Fail","probeSizeMb":null,"analyzeDurationSecs":100,"gaplessJoin":true,"stripTrackScopedMetadata":true},"#,
+                r#""metadata":{"title":"Dune","author":null,"album":null,"narrator":null,"year":null,"genre":null,"#,
+                r#""description":null,"series":null,"seriesIndex":1.5,"#,
+                r#""coverSha256":"039058c6f2c0cb492c533b0a4d14ef77cc0f78abccced5287d84a1a2011cfb81"}}"#,
+            )
+        );
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn test_plan_hash_tracks_inputs_settings_and_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let input = temp_dir.path().join("01.mp3");
+        std::fs::write(&input, b"audio").unwrap();
+        let output = temp_dir.path().join("Dune.m4b");
+        let base = plan_hash(&[&input], &settings(&output), None).unwrap();
+        assert_eq!(base.len(), 64);
+        assert_eq!(plan_hash(&[&input], &settings(&output), None).unwrap(), base);
+
+        let mut other = settings(&output);
+        other.bitrate = 96;
+        assert_ne!(plan_hash(&[&input], &other, None).unwrap(), base);
+        let mut reproducible = settings(&output);
+        reproducible.reproducible = true;
+        assert_ne!(plan_hash(&[&input], &reproducible, None).unwrap(), base);
+        let mut accurate = settings(&output);
+        accurate.accurate_mp3_durations = true;
+        assert_ne!(plan_hash(&[&input], &accurate, None).unwrap(), base);
+        let mut chapters = settings(&output);
+        chapters.chapter_specs = Some(vec![ChapterSpec { title: "Dune".to_string(), start_secs: Some(0.0), input_indices: None }]);
+        assert_ne!(plan_hash(&[&input], &chapters, None).unwrap(), base);
+        let mut chapter_list = settings(&output);
+        chapter_list.append_chapter_list_to_description = true;
+        assert_ne!(plan_hash(&[&input], &chapter_list, None).unwrap(), base);
+        let mut chapter_format = settings(&output);
+        chapter_format.chapter_format = ChapterFormat::Both;
+        assert_ne!(plan_hash(&[&input], &chapter_format, None).unwrap(), base);
+        let mut placeholder = settings(&output);
+        placeholder.generate_placeholder_cover = true;
+        assert_ne!(plan_hash(&[&input], &placeholder, None).unwrap(), base);
+        let mut no_encoding_info = settings(&output);
+        no_encoding_info.write_encoding_info = false;
+        assert_ne!(plan_hash(&[&input], &no_encoding_info, None).unwrap(), base);
+        let mut no_input_cover = settings(&output);
+        no_input_cover.auto_cover_from_input = false;
+        assert_ne!(plan_hash(&[&input], &no_input_cover, None).unwrap(), base);
+        let mut small_cover = settings(&output);
+        small_cover.max_cover_bytes = Some(1024 * 1024);
+        assert_ne!(plan_hash(&[&input], &small_cover, None).unwrap(), base);
+        let metadata = AudiobookMetadata { title: Some("Dune".to_string()), ..AudiobookMetadata::new() };
+        let titled = plan_hash(&[&input], &settings(&output), Some(&metadata)).unwrap();
+        assert_ne!(titled, base);
+        let subtitled = AudiobookMetadata { subtitle: Some("Book One".to_string()), ..metadata.clone() };
+        assert_ne!(plan_hash(&[&input], &settings(&output), Some(&subtitled)).unwrap(), titled);
+        let numbered = AudiobookMetadata { track_number: Some(2), sort_title: Some("Dune".to_string()), ..metadata.clone() };
+        assert_ne!(plan_hash(&[&input], &settings(&output), Some(&numbered)).unwrap(), titled);
+        let identified = AudiobookMetadata { asin: Some("B002V1OF70".to_string()), ..metadata.clone() };
+        assert_ne!(plan_hash(&[&input], &settings(&output), Some(&identified)).unwrap(), titled);
+
+        std::fs::write(&input, b"longer audio").unwrap();
+        assert_ne!(plan_hash(&[&input], &settings(&output), None).unwrap(), base);
+        assert!(plan_hash(&[temp_dir.path().join("missing.mp3")], &settings(&output), None).is_err());
+    }
+
+    #[test]
+    fn test_matching_run_needs_output_and_sidecar() {
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("Dune.m4b");
+        let session = ProcessingSession::new();
+        let mut record = HistoryRecord::from_result(&session, &output, &Ok("done".to_string()));
+        record.plan_hash = Some("abc".to_string());
+        let mut failed = record.clone();
+        failed.succeeded = false;
+
+        assert_eq!(find_matching_run(vec![record.clone()], "abc"), None);
+        std::fs::write(&output, b"m4b").unwrap();
+        assert_eq!(find_matching_run(vec![record.clone()], "abc"), None);
+
+        write_sidecar(&output, "abc").unwrap();
+        assert_eq!(sidecar_path(&output), temp_dir.path().join("Dune.m4b.planhash"));
+        assert_eq!(find_matching_run(vec![record.clone(), failed.clone()], "abc"), Some(record.clone()));
+        assert_eq!(find_matching_run(vec![failed], "abc"), None);
+        assert_eq!(find_matching_run(vec![record], "def"), None);
+    }
+}
@@ -0,0 +1,431 @@
+//! Pre-flight resolution of the processing plan
+//!
+//! Several choices are made automatically once a run starts: invalid
+//! inputs are skipped, dual-mono stereo is encoded as mono, an `auto`
+//! sample rate is detected, mixed inputs force the concat filter graph,
+//! and chapter marks are placed (or an explicit chapter list is checked).
+//! `preview_plan` makes those choices up front so the UI can show them
+//! before the user commits, and the run builds its FFmpeg plans from
+//! `resolve_plan`'s resolution instead of deciding again.
+//!
+//! Two annotations need FFmpeg to decode audio: dual-mono detection and,
+//! with `accurate_mp3_durations`, measuring MP3 durations. The run makes
+//! them; the preview never spawns FFmpeg and lists them in
+//! `deferred_checks` instead, so its channel layout and chapter times can
+//! still change when they run.
+
+use super::bitrate_check::{check_bitrate_excess, max_source_bitrate, BitrateExcess};
+use super::chapters::{planned_chapters, Chapter};
+use super::constants::DEFAULT_SEGMENT_THRESHOLD;
+use super::dual_mono::{apply_auto_dual_mono, dual_mono_detection_applies};
+use super::mp3_duration::{apply_frame_durations_for_mixed_rates, apply_planning_durations, duration_measurement_applies};
+use super::file_list::{apply_invalid_input_policy, SkippedInput};
+use super::input_stats::InputDurationStats;
+use super::large_output::{filesystem_of, large_output_warning, projected_output_bytes};
+use super::media_pipeline::{select_merge_mode, MediaProcessingPlan, MergeInputMode};
+use super::publish::{PublishStrategy, is_network_filesystem};
+use super::segments::SegmentPlan;
+use super::settings::ResolvedAudioSettings;
+use super::{AudioFile, AudioSettings, ChannelConfig, SampleRateConfig};
+use crate::errors::Result;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// A decode-based check the preview leaves for the run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum DeferredCheck {
+    /// Whether every stereo input is dual-mono, for `auto_dual_mono`
+    DualMonoDetection,
+    /// Decoded durations of MP3s without a VBR header, for `accurate_mp3_durations`
+    Mp3DurationMeasurement,
+}
+
+impl DeferredCheck {
+    fn message(self) -> &'static str {
+        match self {
+            Self::DualMonoDetection => "Dual-mono detection runs when processing starts; stereo output may switch to mono",
+            Self::Mp3DurationMeasurement => "MP3 durations are measured when processing starts; chapter times may shift slightly",
+        }
+    }
+}
+
+/// Every automatic decision of a run, made before it starts
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanResolution {
+    /// Inputs that will be encoded, in book order
+    #[serde(skip)]
+    pub files: Vec<AudioFile>,
+    /// Settings with the sample rate and channels resolved
+    #[serde(skip)]
+    pub settings: AudioSettings,
+    /// Encoder values every FFmpeg plan of the run uses
+    #[serde(skip)]
+    pub resolved: ResolvedAudioSettings,
+    /// Segments the inputs are merged in, when the list is over the threshold
+    #[serde(skip)]
+    pub segment_plan: Option<SegmentPlan>,
+    /// Output sample rate in Hz
+    pub sample_rate: u32,
+    /// Whether the sample rate was detected from the inputs (`auto`)
+    pub sample_rate_detected: bool,
+    /// Output channel layout
+    pub channels: ChannelConfig,
+    /// Whether stereo output was switched to mono because every stereo input is dual-mono
+    pub mono_from_dual_mono: bool,
+    /// How the inputs are concatenated when merged in one pass
+    pub input_mode: MergeInputMode,
+    /// How each segment's inputs are concatenated; empty when not segmented
+    pub segment_input_modes: Vec<MergeInputMode>,
+    /// Highest input bitrate in kbps
+    pub source_bitrate_kbps: Option<u32>,
+    /// Chapters written into the merged book; empty keeps a single input's own
+    pub chapters: Vec<Chapter>,
+    /// Inputs left out under the SkipWithWarning policy
+    pub skipped_inputs: Vec<SkippedInput>,
+    /// Target bitrate above the sources, warned about rather than refused
+    #[serde(skip)]
+    pub bitrate_excess: Option<BitrateExcess>,
+    /// Duration spread of the inputs that will be encoded
+    pub input_stats: Option<InputDurationStats>,
+    /// Expected output size in bytes, from the total duration and bitrate
+    pub projected_output_bytes: u64,
+    /// Projected output too large for the destination's filesystem
+    #[serde(skip)]
+    pub output_size_warning: Option<String>,
+    /// How the output is moved to its destination; copied once onto network shares
+    pub publish_strategy: PublishStrategy,
+    /// Checks that need FFmpeg and were left for the run; always empty for the run itself
+    pub deferred_checks: Vec<DeferredCheck>,
+    /// Human-readable warnings about the decisions above
+    pub warnings: Vec<String>,
+}
+
+/// Resolves the plan a run executes, decoding inputs where a check needs it
+///
+/// Fails where the run would fail before encoding: no valid inputs under
+/// the skip policy, a strict bitrate check, an undetectable sample rate, or
+/// an explicit chapter list that leaves gaps or overlaps.
+pub fn resolve_plan(files: Vec<AudioFile>, settings: &AudioSettings) -> Result<PlanResolution> {
+    resolve(files, settings, true)
+}
+
+/// Resolves the plan for display without spawning FFmpeg
+///
+/// Fails as `resolve_plan` does. Decode-based checks are listed in
+/// `deferred_checks` rather than run.
+pub fn preview_plan(files: Vec<AudioFile>, settings: &AudioSettings) -> Result<PlanResolution> {
+    resolve(files, settings, false)
+}
+
+fn resolve(mut files: Vec<AudioFile>, settings: &AudioSettings, decode: bool) -> Result<PlanResolution> {
+    let mut deferred_checks = Vec::new();
+    if decode {
+        apply_planning_durations(settings, &mut files);
+    } else {
+        if duration_measurement_applies(settings, &files) {
+            deferred_checks.push(DeferredCheck::Mp3DurationMeasurement);
+        }
+        apply_frame_durations_for_mixed_rates(&mut files);
+    }
+    // Explicit chapter specs index the inputs as requested, before any are skipped
+    let chapters = planned_chapters(&files, settings)?;
+    let (mut files, skipped_inputs) = apply_invalid_input_policy(files, settings.invalid_input_policy)?;
+    let mut settings = settings.clone();
+    let requested_stereo = matches!(settings.channels, ChannelConfig::Stereo);
+    if decode {
+        apply_auto_dual_mono(&mut settings, &mut files);
+    } else if dual_mono_detection_applies(&settings, &files) {
+        deferred_checks.push(DeferredCheck::DualMonoDetection);
+    }
+    let bitrate_excess = check_bitrate_excess(&settings, &files)?;
+
+    let resolved = settings.resolve(&files)?;
+    settings.sample_rate = SampleRateConfig::Explicit(resolved.sample_rate);
+
+    let threshold = settings.segment_threshold.unwrap_or(DEFAULT_SEGMENT_THRESHOLD);
+    let segment_plan = SegmentPlan::plan(&files, threshold);
+    let segment_input_modes = segment_plan.iter()
+        .flat_map(|plan| &plan.segments)
+        .map(|segment| select_merge_mode(&files[segment.files.clone()]))
+        .collect();
+
+    let projected_output_bytes = projected_output_bytes(MediaProcessingPlan::calculate_total_duration(&files), resolved.bitrate);
+    let destination_filesystem = filesystem_of(&settings.output_path);
+    let output_size_warning = large_output_warning(projected_output_bytes, destination_filesystem.as_deref());
+    let publish_strategy = PublishStrategy::for_destination(&settings.output_path, destination_filesystem.as_deref());
+
+    let mut warnings = Vec::new();
+    if !skipped_inputs.is_empty() {
+        warnings.push(format!("Skipping {} invalid input file(s)", skipped_inputs.len()));
+    }
+    if let Some(excess) = &bitrate_excess {
+        warnings.push(excess.message());
+    }
+    warnings.extend(output_size_warning.clone());
+    warnings.extend(deferred_checks.iter().map(|check| check.message().to_string()));
+    if publish_strategy == PublishStrategy::CopyOnce {
+        let share = destination_filesystem.filter(|filesystem| is_network_filesystem(filesystem))
+            .map(|filesystem| format!(" ({filesystem})"))
+            .unwrap_or_default();
+        log::info!("Output is on a network share{share}; it will be encoded locally and copied once");
+        warnings.push(format!("Output is on a network share{share}; the finished book is copied there once"));
+    }
+
+    Ok(PlanResolution {
+        input_mode: select_merge_mode(&files),
+        source_bitrate_kbps: max_source_bitrate(&files),
+        input_stats: InputDurationStats::from_files(&files),
+        channels: settings.channels.clone(),
+        mono_from_dual_mono: requested_stereo && matches!(settings.channels, ChannelConfig::Mono),
+        sample_rate: resolved.sample_rate,
+        sample_rate_detected: resolved.sample_rate_detected,
+        resolved,
+        segment_input_modes,
+        segment_plan,
+        chapters,
+        skipped_inputs,
+        bitrate_excess,
+        projected_output_bytes,
+        output_size_warning,
+        publish_strategy,
+        deferred_checks,
+        warnings,
+        files,
+        settings,
+    })
+}
+
+impl PlanResolution {
+    /// Plan merging every input in one FFmpeg pass
+    pub fn media_plan(&self, concat_list: PathBuf, output: PathBuf, total_duration: f64) -> MediaProcessingPlan {
+        MediaProcessingPlan::new(
+            concat_list,
+            output,
+            self.settings.clone(),
+            self.resolved,
+            self.files.iter().map(|f| f.path.clone()).collect(),
+            total_duration,
+        ).with_input_mode(self.input_mode)
+        .with_source_bitrate(self.source_bitrate_kbps)
+        .with_input_options(&self.files)
+    }
+
+    /// Plan encoding segment `index` of the segment plan, if there is one
+    pub fn segment_media_plan(&self, index: usize, concat_list: PathBuf, output: PathBuf) -> Option<MediaProcessingPlan> {
+        let segment_plan = self.segment_plan.as_ref()?;
+        let segment = segment_plan.segments.get(index)?;
+        let files = &self.files[segment.files.clone()];
+        Some(MediaProcessingPlan::new(
+            concat_list,
+            output,
+            self.settings.clone(),
+            self.resolved,
+            files.iter().map(|f| f.path.clone()).collect(),
+            segment_plan.total_duration,
+        ).with_input_mode(self.segment_input_modes[index])
+        .with_source_bitrate(max_source_bitrate(files))
+        .with_input_options(files))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::InvalidInputPolicy;
+    use std::path::Path;
+
+    fn input(name: &str, codec: &str, sample_rate: u32, bitrate: u32) -> AudioFile {
+        let mut file = AudioFile::new(PathBuf::from(name));
+        file.is_valid = true;
+        file.duration = Some(60.0);
+        file.codec = Some(codec.to_string());
+        file.sample_rate = Some(sample_rate);
+        file.channels = Some(1);
+        file.bitrate = Some(bitrate);
+        file
+    }
+
+    fn settings() -> AudioSettings {
+        let mut settings = AudioSettings::default();
+        settings.channels = ChannelConfig::Mono;
+        settings.sample_rate = SampleRateConfig::Explicit(22050);
+        settings.bitrate = 128;
+        settings
+    }
+
+    #[test]
+    fn test_resolution_reports_decisions_and_warnings() {
+        let mut broken = AudioFile::new(PathBuf::from("broken.mp3"));
+        broken.error = Some("Unsupported format".to_string());
+        let files = vec![input("01.mp3", "mp3", 44100, 64), broken, input("02.m4a", "aac", 44100, 64)];
+        let mut settings = settings();
+        settings.invalid_input_policy = InvalidInputPolicy::SkipWithWarning;
+
+        let resolution = resolve_plan(files, &settings).unwrap();
+        assert_eq!(resolution.files.len(), 2);
+        assert_eq!(resolution.skipped_inputs[0].path, PathBuf::from("broken.mp3"));
+        assert_eq!(resolution.input_mode, MergeInputMode::ConcatFilter);
+        assert_eq!((resolution.sample_rate, resolution.sample_rate_detected), (22050, false));
+        assert!(!resolution.mono_from_dual_mono);
+        assert!(resolution.segment_input_modes.is_empty());
+        let titles: Vec<&str> = resolution.chapters.iter().map(|chapter| chapter.title.as_str()).collect();
+        assert_eq!(titles, ["01", "02"]);
+        assert_eq!(resolution.warnings.len(), 2);
+        assert!(resolution.warnings[1].contains("exceeds the best source bitrate"));
+
+        let json = serde_json::to_value(&resolution).unwrap();
+        assert_eq!(json["inputMode"], "ConcatFilter");
+        assert_eq!(json["sampleRate"], 22050);
+        assert_eq!(json["projectedOutputBytes"], 1_920_000);
+        assert!(json.get("settings").is_none());
+    }
+
+    #[test]
+    fn test_chapter_preview_matches_processing() {
+        use crate::audio::chapters::{ffmetadata, preview_chapters, ChapterMode, ChapterOptions, ChapterSpec, ChapterTitleSource};
+        use crate::metadata::ChapterInfo;
+        let mut broken = AudioFile::new(PathBuf::from("broken.mp3"));
+        broken.error = Some("Unsupported format".to_string());
+        let mut podcast = input("02 Episode.mp3", "mp3", 44100, 64);
+        podcast.chapters = vec![
+            ChapterInfo { title: "Cold Open".to_string(), start_ms: 0, end_ms: 20_000 },
+            ChapterInfo { title: "Interview".to_string(), start_ms: 20_000, end_ms: 60_000 },
+        ];
+        podcast.duration = Some(60.0004);
+        let files = vec![input("01 Intro.mp3", "mp3", 44100, 64), broken, podcast, input("03 Outro.mp3", "mp3", 44100, 64)];
+
+        let mut base = settings();
+        base.invalid_input_policy = InvalidInputPolicy::SkipWithWarning;
+        let variants = [
+            base.clone(),
+            AudioSettings { chapter_title_source: ChapterTitleSource::Numbered, ..base.clone() },
+            AudioSettings {
+                chapters: Some(ChapterOptions { mode: ChapterMode::FixedInterval, interval_minutes: Some(1.5), ..ChapterOptions::default() }),
+                ..base.clone()
+            },
+            AudioSettings {
+                chapter_specs: Some(vec![
+                    ChapterSpec { title: "Part One".to_string(), start_secs: None, input_indices: Some(vec![0, 1, 2]) },
+                    ChapterSpec { title: "Part Two".to_string(), start_secs: Some(120.0004), input_indices: None },
+                ]),
+                ..base.clone()
+            },
+        ];
+        for settings in variants {
+            let preview = preview_chapters(&files, &settings).unwrap();
+            let resolution = resolve_plan(files.clone(), &settings).unwrap();
+            assert!(!preview.is_empty());
+            assert_eq!(preview, resolution.chapters.iter().map(ChapterInfo::from).collect::<Vec<_>>());
+
+            // The chapters file carries the previewed milliseconds
+            let written = ffmetadata(&resolution.chapters);
+            for chapter in &preview {
+                assert!(written.contains(&format!("START={}\nEND={}\n", chapter.start_ms, chapter.end_ms)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_chapter_preview_matches_processing_with_adjusted_durations() {
+        use crate::audio::chapters::{planned_chapters, preview_chapters};
+        use crate::audio::file_list::get_file_list_info;
+        use crate::audio::mp3_duration::fixtures::{cbr_mp3, cbr_mp3_22k, corrupted_cbr_mp3, cbr_secs, seed_measured_duration};
+        use crate::metadata::ChapterInfo;
+        let dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, data: Vec<u8>| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, data).unwrap();
+            path
+        };
+
+        // Mixed sample rates: frame counts replace Lofty's estimates
+        let low = write("01 - Low.mp3", cbr_mp3_22k(2297));
+        let high = write("02 - High.mp3", cbr_mp3(2297));
+        // No VBR header: the decoded length replaces the header's estimate
+        let headerless = write("03 - Headerless.mp3", corrupted_cbr_mp3(300));
+        seed_measured_duration(&headerless, cbr_secs(300));
+        let outro = write("04 - Outro.mp3", cbr_mp3(300));
+        let mut accurate = AudioSettings::default();
+        accurate.accurate_mp3_durations = true;
+
+        for (paths, settings) in [(vec![low, high], AudioSettings::default()), (vec![headerless, outro], accurate)] {
+            let files = get_file_list_info(&paths).unwrap().files;
+            let preview = preview_chapters(&files, &settings).unwrap();
+            let resolution = resolve_plan(files.clone(), &settings).unwrap();
+            assert_eq!(preview, resolution.chapters.iter().map(ChapterInfo::from).collect::<Vec<_>>());
+            let unadjusted: Vec<ChapterInfo> = planned_chapters(&files, &settings).unwrap().iter().map(ChapterInfo::from).collect();
+            assert_ne!(preview, unadjusted, "{paths:?}");
+        }
+    }
+
+    #[test]
+    fn test_strict_bitrate_check_fails_preflight() {
+        let mut settings = settings();
+        settings.strict_bitrate_check = true;
+        assert!(resolve_plan(vec![input("01.mp3", "mp3", 44100, 64)], &settings).is_err());
+    }
+
+    #[test]
+    fn test_executed_plan_matches_preview() {
+        let files = vec![input("01.mp3", "mp3", 44100, 64), input("02.mp3", "mp3", 44100, 96)];
+        let resolution = resolve_plan(files, &settings()).unwrap();
+
+        let plan = resolution.media_plan(PathBuf::from("concat.txt"), PathBuf::from("merged.m4b"), 120.0);
+        assert_eq!(plan.input_mode, resolution.input_mode);
+        assert!(matches!(plan.settings.sample_rate, SampleRateConfig::Explicit(rate) if rate == resolution.sample_rate));
+        assert_eq!(plan.resolved, resolution.resolved);
+        assert_eq!((plan.resolved.sample_rate, plan.resolved.channels), (22050, 1));
+        assert_eq!(plan.source_bitrate_kbps, Some(96));
+        assert_eq!(plan.input_file_paths, [Path::new("01.mp3"), Path::new("02.mp3")]);
+        assert!(resolution.segment_media_plan(0, PathBuf::new(), PathBuf::new()).is_none());
+    }
+
+    #[test]
+    fn test_preview_defers_decoding_checks() {
+        let mut stereo = input("01.mp3", "mp3", 44100, 64);
+        stereo.channels = Some(2);
+        stereo.format = Some("MP3".to_string());
+        let mut settings = settings();
+        settings.channels = ChannelConfig::Stereo;
+        settings.auto_dual_mono = true;
+        settings.accurate_mp3_durations = true;
+
+        let preview = preview_plan(vec![stereo.clone()], &settings).unwrap();
+        assert_eq!(preview.deferred_checks, [DeferredCheck::Mp3DurationMeasurement, DeferredCheck::DualMonoDetection]);
+        assert!(matches!(preview.channels, ChannelConfig::Stereo));
+        assert_eq!(preview.files[0].header_duration, None);
+        assert!(preview.warnings.iter().any(|warning| warning.contains("Dual-mono detection runs")));
+        let json = serde_json::to_value(&preview).unwrap();
+        assert_eq!(json["deferredChecks"][1], "DualMonoDetection");
+
+        // Mono output or no estimated MP3 leaves nothing to defer
+        settings.channels = ChannelConfig::Mono;
+        settings.accurate_mp3_durations = false;
+        assert!(preview_plan(vec![stereo], &settings).unwrap().deferred_checks.is_empty());
+    }
+
+    #[test]
+    fn test_segment_plans_match_preview() {
+        let files = vec![
+            input("01.mp3", "mp3", 44100, 64),
+            input("02.mp3", "mp3", 44100, 64),
+            input("03.mp3", "mp3", 44100, 64),
+            input("04.m4a", "aac", 22050, 64),
+        ];
+        let mut settings = settings();
+        settings.segment_threshold = Some(2);
+
+        let resolution = resolve_plan(files, &settings).unwrap();
+        let segment_count = resolution.segment_plan.as_ref().unwrap().segment_count();
+        assert_eq!(resolution.segment_input_modes, [MergeInputMode::ConcatDemuxer, MergeInputMode::ConcatFilter]);
+        assert_eq!(segment_count, resolution.segment_input_modes.len());
+        for index in 0..segment_count {
+            let plan = resolution.segment_media_plan(index, PathBuf::new(), PathBuf::new()).unwrap();
+            assert_eq!(plan.input_mode, resolution.segment_input_modes[index]);
+            assert!(matches!(plan.settings.sample_rate, SampleRateConfig::Explicit(22050)));
+            assert_eq!(plan.resolved.sample_rate, 22050);
+            assert_eq!(plan.input_file_paths.len(), 2);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Post-processing hook preference and runner
+//!
+//! After a book is published, an optional user executable runs with the
+//! output path as its only argument and the run's history record as JSON on
+//! stdin (e.g. to trigger a library rescan). It is spawned directly, never
+//! through a shell, and is killed along with anything it started after a
+//! timeout. Its outcome is recorded
+//! in history; a failing hook is a warning, never a failed book.
+
+use super::constants::{POST_HOOK_POLL_INTERVAL_MS, POST_HOOK_STDOUT_TAIL_BYTES, POST_HOOK_TIMEOUT_SECS};
+use super::job_history::HistoryRecord;
+use crate::errors::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Hook executable chosen in preferences, if any
+static POST_PROCESS_HOOK: OnceLock<Mutex<Option<PathBuf>>> = OnceLock::new();
+
+/// Stores the hook executable, or clears it with None
+pub fn set_post_process_hook(hook: Option<&Path>) -> Result<()> {
+    if let Some(hook) = hook {
+        validate_hook(hook)?;
+    }
+    let mut current = POST_PROCESS_HOOK.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Post-processing hook preference poisoned".to_string()))?;
+    *current = hook.map(Path::to_path_buf);
+    Ok(())
+}
+
+/// The hook executable set in preferences, if any
+pub fn post_process_hook() -> Option<PathBuf> {
+    POST_PROCESS_HOOK.get_or_init(Default::default).lock().ok()?.clone()
+}
+
+fn validate_hook(hook: &Path) -> Result<()> {
+    if !hook.is_absolute() {
+        return Err(AppError::InvalidInput(
+            format!("Post-processing hook must be an absolute path, got: {}", hook.display())
+        ));
+    }
+    if !hook.is_file() {
+        return Err(AppError::FileValidation(
+            format!("Post-processing hook not found: {}", hook.display())
+        ));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if std::fs::metadata(hook)?.permissions().mode() & 0o111 == 0 {
+            return Err(AppError::InvalidInput(
+                format!("Post-processing hook is not executable: {}", hook.display())
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// What happened when the hook ran
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookOutcome {
+    /// Exit code, None when the hook didn't start, was killed or died from a signal
+    pub exit_code: Option<i32>,
+    /// Whether the hook was killed for exceeding the timeout
+    pub timed_out: bool,
+    /// Last part of the hook's stdout
+    pub stdout_tail: String,
+    /// Why the hook couldn't be run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl HookOutcome {
+    fn not_started(error: &AppError) -> Self {
+        Self { exit_code: None, timed_out: false, stdout_tail: String::new(), error: Some(error.to_string()) }
+    }
+
+    /// Warning to report for this outcome, None when the hook succeeded
+    pub fn warning(&self) -> Option<String> {
+        if let Some(error) = &self.error {
+            return Some(format!("Post-processing hook failed to run: {error}"));
+        }
+        if self.timed_out {
+            return Some("Post-processing hook timed out and was stopped".to_string());
+        }
+        match self.exit_code {
+            Some(0) => None,
+            Some(code) => Some(format!("Post-processing hook exited with code {code}")),
+            None => Some("Post-processing hook was terminated by a signal".to_string()),
+        }
+    }
+}
+
+/// Runs the hook configured for a finished run, if one is set
+///
+/// `hook` comes from the run's preference snapshot, not the current preference.
+pub async fn run_configured_hook(hook: Option<PathBuf>, record: &HistoryRecord) -> Option<HookOutcome> {
+    let hook = hook?;
+    let output = record.output_path.clone();
+    let summary = match serde_json::to_vec(record) {
+        Ok(summary) => summary,
+        Err(e) => return Some(HookOutcome::not_started(&AppError::General(format!("Cannot serialize run summary: {e}")))),
+    };
+    let timeout = Duration::from_secs(POST_HOOK_TIMEOUT_SECS);
+    let outcome = tokio::task::spawn_blocking(move || run_hook(&hook, &output, &summary, timeout)).await
+        .unwrap_or_else(|e| Err(AppError::General(format!("Post-processing hook task failed: {e}"))));
+    Some(outcome.unwrap_or_else(|e| HookOutcome::not_started(&e)))
+}
+
+/// Spawns `hook` with `output` as argument and `summary` on stdin, waiting at most `timeout`
+///
+/// On unix the hook gets its own process group, and the whole group is
+/// killed on timeout, so processes it started can't outlive it. Stdin is
+/// written and stdout drained on their own threads; neither is waited for
+/// past the deadline.
+pub fn run_hook(hook: &Path, output: &Path, summary: &[u8], timeout: Duration) -> Result<HookOutcome> {
+    let mut command = Command::new(hook);
+    command.arg(output)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command.spawn()
+        .map_err(|e| AppError::General(format!("Cannot start {}: {e}", hook.display())))?;
+    let started = Instant::now();
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let summary = summary.to_vec();
+        std::thread::spawn(move || {
+            // A hook that ignores stdin may exit before reading it
+            if let Err(e) = stdin.write_all(&summary) {
+                log::debug!("Post-processing hook did not read its stdin: {e}");
+            }
+        });
+    }
+    // Drain stdout concurrently so a chatty hook can't block on a full pipe
+    let stdout = Arc::new(Mutex::new(Vec::new()));
+    let stdout_reader = child.stdout.take().map(|mut pipe| {
+        let stdout = Arc::clone(&stdout);
+        std::thread::spawn(move || {
+            let mut chunk = [0u8; 4096];
+            while let Ok(read @ 1..) = pipe.read(&mut chunk) {
+                if let Ok(mut stdout) = stdout.lock() {
+                    stdout.extend_from_slice(&chunk[..read]);
+                }
+            }
+        })
+    });
+
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if started.elapsed() >= timeout {
+            timed_out = true;
+            kill_process_group(&mut child);
+            let _ = child.wait();
+            break None;
+        }
+        std::thread::sleep(Duration::from_millis(POST_HOOK_POLL_INTERVAL_MS));
+    };
+
+    // A process the hook left running may still hold stdout open
+    if let Some(reader) = stdout_reader {
+        while !reader.is_finished() && started.elapsed() < timeout {
+            std::thread::sleep(Duration::from_millis(POST_HOOK_POLL_INTERVAL_MS));
+        }
+    }
+    let stdout = stdout.lock().map(|stdout| stdout.clone()).unwrap_or_default();
+    let tail_start = stdout.len().saturating_sub(POST_HOOK_STDOUT_TAIL_BYTES);
+    Ok(HookOutcome {
+        exit_code: status.and_then(|status| status.code()),
+        timed_out,
+        stdout_tail: String::from_utf8_lossy(&stdout[tail_start..]).into_owned(),
+        error: None,
+    })
+}
+
+/// Kills the hook and, on unix, every process in its group
+fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    if let Ok(group) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: kill has no memory effects; the group was created for the hook
+        unsafe {
+            libc::kill(-group, libc::SIGKILL);
+        }
+    }
+    let _ = child.kill();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    fn script(dir: &Path, name: &str, body: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_hook_receives_output_and_summary() {
+        let temp_dir = TempDir::new().unwrap();
+        let hook = script(temp_dir.path(), "hook.sh", "cat > \"$1.stdin\"\nprintf '%s' \"$1\" > \"$1.arg\"\necho rescanned");
+        // Shell metacharacters must reach the hook verbatim
+        let output = temp_dir.path().join("Dune; $(touch pwned).m4b");
+
+        let outcome = run_hook(&hook, &output, br#"{"succeeded":true}"#, Duration::from_secs(10)).unwrap();
+        assert_eq!(outcome, HookOutcome { exit_code: Some(0), timed_out: false, stdout_tail: "rescanned\n".to_string(), error: None });
+        assert_eq!(outcome.warning(), None);
+
+        let stdin_copy = PathBuf::from(format!("{}.stdin", output.display()));
+        assert_eq!(std::fs::read_to_string(stdin_copy).unwrap(), r#"{"succeeded":true}"#);
+        let arg_copy = PathBuf::from(format!("{}.arg", output.display()));
+        assert_eq!(std::fs::read_to_string(arg_copy).unwrap(), output.to_string_lossy());
+        assert!(!temp_dir.path().join("pwned").exists());
+    }
+
+    #[test]
+    fn test_failing_and_slow_hooks_become_warnings() {
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("book.m4b");
+
+        let failing = script(temp_dir.path(), "fail.sh", "echo oops\nexit 3");
+        let outcome = run_hook(&failing, &output, b"{}", Duration::from_secs(10)).unwrap();
+        assert_eq!(outcome.exit_code, Some(3));
+        assert!(outcome.warning().unwrap().contains("code 3"));
+
+        // sleep runs as a grandchild holding stdout open
+        let slow = script(temp_dir.path(), "slow.sh", "sleep 10\necho late");
+        let started = Instant::now();
+        let outcome = run_hook(&slow, &output, b"{}", Duration::from_millis(200)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+        assert!(outcome.timed_out);
+        assert!(outcome.warning().unwrap().contains("timed out"));
+    }
+
+    #[test]
+    fn test_hook_ignoring_a_large_summary_cannot_stall() {
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("book.m4b");
+        // Writes more than a pipe holds before reading stdin, then never reads it
+        let chatty = script(temp_dir.path(), "chatty.sh", "head -c 200000 /dev/zero\nsleep 10");
+        let summary = vec![b' '; 1 << 20];
+
+        let started = Instant::now();
+        let outcome = run_hook(&chatty, &output, &summary, Duration::from_millis(500)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3), "took {:?}", started.elapsed());
+        assert!(outcome.timed_out);
+        assert_eq!(outcome.stdout_tail.len(), POST_HOOK_STDOUT_TAIL_BYTES);
+    }
+
+    #[test]
+    fn test_hook_preference_validation() {
+        let temp_dir = TempDir::new().unwrap();
+        let plain = temp_dir.path().join("notes.txt");
+        std::fs::write(&plain, b"text").unwrap();
+        assert!(validate_hook(&plain).unwrap_err().to_string().contains("not executable"));
+        assert!(validate_hook(Path::new("relative/hook.sh")).is_err());
+        assert!(validate_hook(&temp_dir.path().join("missing.sh")).is_err());
+        assert!(validate_hook(&script(temp_dir.path(), "ok.sh", "true")).is_ok());
+    }
+}
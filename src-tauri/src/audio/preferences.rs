@@ -0,0 +1,34 @@
+//! Preferences a run reads, captured once per job
+//!
+//! Preferences are process-wide and can change while books are queued or
+//! encoding. A run reads them from a snapshot instead: queued jobs take
+//! theirs when enqueued, direct runs when they start. A change therefore
+//! applies to jobs queued after it and never to a run halfway through; a
+//! queued job adopts the current preferences only when refreshed.
+
+use super::post_hook::post_process_hook;
+use crate::metadata::profile::{default_metadata_profile, MetadataProfile};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Preferences in effect for one job
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreferenceSnapshot {
+    /// Metadata profile layered under the book's metadata
+    #[serde(default)]
+    pub metadata_profile: MetadataProfile,
+    /// Executable run after the book is finished
+    #[serde(default)]
+    pub post_process_hook: Option<PathBuf>,
+}
+
+impl PreferenceSnapshot {
+    /// The preferences as set right now
+    pub fn current() -> Self {
+        Self {
+            metadata_profile: default_metadata_profile(),
+            post_process_hook: post_process_hook(),
+        }
+    }
+}
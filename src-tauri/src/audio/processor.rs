@@ -1,17 +1,52 @@
 //! Core audio processing and merge implementation
 
-use super::{AudioFile, AudioSettings, ProgressReporter, ProcessingStage, CleanupGuard};
+use super::{AudioFile, AudioSettings, ProgressReporter, ProcessingStage, SampleRateConfig};
+use super::bitrate_check::BitrateExcess;
+use super::chapters::{effective_chapter_options, ffmetadata, Chapter};
+use super::cue_sheet::write_cue_sheet;
 use super::constants::*;
 use super::context::ProcessingContext;
-use super::media_pipeline::MediaProcessingPlan;
+use super::media_pipeline::{MediaProcessingPlan, build_segment_join_command, execute_ffmpeg_pass_with_context};
+use super::progress_monitor::{InputLabels, ProgressPass};
+use super::file_list::SkippedInput;
+use super::input_stats::InputDurationStats;
+use super::job_history::{record_run, HistoryRecord};
+use super::plan_hash::{plan_hash, write_sidecar};
+use super::plan_resolution::{resolve_plan, PlanResolution};
+use super::post_hook::run_configured_hook;
+use super::progress::ProgressEmitter;
+use super::large_output::filesystem_of;
+use super::publish::{publish_output, PublishStrategy};
+use super::segments::SegmentPlan;
+use super::temp_layout::TempLayout;
+use super::timeline::TimelineStage;
+use super::transcripts::publish_transcripts;
+use super::verification::enqueue_verification;
+use super::metadata_sidecar::{write_metadata_sidecar, SidecarAudio, SidecarFormat};
 use super::metrics::ProcessingMetrics;
+use super::session::{register_active_session, unregister_active_session};
+#[cfg(feature = "gui")]
 use super::session::ProcessingSession;
 use crate::errors::{AppError, Result};
-use crate::metadata::{AudiobookMetadata, write_metadata};
+use crate::metadata::{AudiobookMetadata, ChapterInfo, write_metadata};
+use crate::metadata::chpl::write_chpl;
+use crate::metadata::description::append_chapter_list;
+use crate::metadata::identifiers::validate_metadata;
+use crate::metadata::normalize::normalize_metadata;
+use crate::metadata::cover_convert::{fit_cover_to_limit, megabytes};
+use crate::metadata::cover_quality::enforce_cover_quality;
+use crate::metadata::local_cover::find_local_cover;
+use crate::metadata::placeholder_cover::render_placeholder_cover;
+use crate::metadata::profile::{merge_metadata, MetadataProfile};
+use crate::metadata::provenance::EncodingInfo;
+use crate::metadata::reader::read_metadata;
+use crate::metadata::write_retry::{is_busy_error, write_tags_with_retry};
+use crate::metadata::writer::MetadataWriteOptions;
 use lofty::probe::Probe;
 use lofty::file::AudioFile as LoftyAudioFile;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+#[cfg(feature = "gui")]
 use std::process::Command;
 use std::time::Duration;
 
@@ -92,17 +127,15 @@ pub async fn process_audiobook(
     
     // Stage 1: Analyze files
     reporter.set_stage(ProcessingStage::Analyzing);
-    let temp_dir = create_temp_directory()?;
-    let concat_file = create_concat_file(&files, &temp_dir)?;
+    let layout = create_temp_directory()?;
+    let concat_file = create_concat_file(&files, &layout)?;
     
     // Stage 2: Convert and merge files
     reporter.set_stage(ProcessingStage::Converting);
     
     // Create a temporary context for the legacy function
     // Note: This is a simplified approach for the deprecated function
-    let temp_output = concat_file.parent()
-        .ok_or_else(|| AppError::FileValidation("Invalid concat file path".to_string()))?
-        .join(TEMP_MERGED_FILENAME);
+    let temp_output = layout.merged_output();
     
     // Extract file paths and create media processing plan
     let file_paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
@@ -112,6 +145,7 @@ pub async fn process_audiobook(
         concat_file.clone(),
         temp_output.clone(),
         settings.clone(),
+        settings.resolve(&files)?,
         file_paths,
         total_duration,
     );
@@ -145,7 +179,7 @@ pub async fn process_audiobook(
     let final_output = move_to_final_location(merged_output, &settings.output_path)?;
     
     // Cleanup
-    cleanup_temp_directory(temp_dir)?;
+    cleanup_temp_directory(layout.dir().to_path_buf())?;
     
     reporter.complete();
     Ok(format!("Successfully created audiobook: {}", final_output.display()))
@@ -177,16 +211,194 @@ fn validate_processing_inputs(
     Ok(())
 }
 
+/// Payload of the `processing-warning` event; `skipped_inputs` is empty except for skipped inputs
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProcessingWarning<'a> {
+    message: String,
+    skipped_inputs: &'a [SkippedInput],
+}
+
+/// Logs a warning, keeps it for the completion event and emits it as `processing-warning`
+fn report_warning(context: &ProcessingContext, message: String, skipped_inputs: &[SkippedInput]) {
+    log::warn!("{message}");
+    if let Ok(mut progress) = context.session.progress().lock() {
+        progress.record_warning(message.clone());
+    }
+    if let Err(e) = context.emit_event("processing-warning", ProcessingWarning { message, skipped_inputs }) {
+        log::warn!("{e}");
+    }
+}
+
+/// Warns when invalid inputs were skipped
+fn warn_skipped_inputs(context: &ProcessingContext, skipped_inputs: &[SkippedInput]) {
+    if !skipped_inputs.is_empty() {
+        report_warning(context, format!("Skipping {} invalid input file(s)", skipped_inputs.len()), skipped_inputs);
+    }
+}
+
+/// Warns when the target bitrate exceeds the sources
+///
+/// Strict mode already refused the run while resolving the plan.
+fn warn_bitrate_excess(context: &ProcessingContext, excess: Option<&BitrateExcess>) {
+    if let Some(excess) = excess {
+        report_warning(context, excess.message(), &[]);
+    }
+}
+
+/// Warns when the projected output is too large for the destination's filesystem
+fn warn_output_size(context: &ProcessingContext, warning: Option<&str>) {
+    if let Some(message) = warning {
+        report_warning(context, message.to_string(), &[]);
+    }
+}
+
+/// Rejects cover art with quality warnings when `enforce_cover_quality` is set
+fn check_cover_quality(settings: &AudioSettings, metadata: Option<&AudiobookMetadata>) -> Result<()> {
+    match metadata.and_then(|m| m.cover_art.as_deref()) {
+        Some(cover) if settings.enforce_cover_quality => enforce_cover_quality(cover),
+        _ => Ok(()),
+    }
+}
+
+/// Shrinks cover art over the embedded size limit, or refuses it with `strict_cover_size`
+fn fit_cover_stage(context: &ProcessingContext, metadata: Option<AudiobookMetadata>) -> Result<Option<AudiobookMetadata>> {
+    let Some(mut metadata) = metadata else {
+        return Ok(None);
+    };
+    let Some(cover) = metadata.cover_art.as_deref() else {
+        return Ok(Some(metadata));
+    };
+    let limit = cover_limit(&context.settings);
+    if let Some(reduced) = fit_cover_to_limit(cover, limit, context.settings.strict_cover_size)? {
+        record_cover_reduction(context, cover.len(), reduced.len(), limit);
+        metadata.cover_art = Some(reduced);
+    }
+    Ok(Some(metadata))
+}
+
+fn cover_limit(settings: &AudioSettings) -> u64 {
+    settings.max_cover_bytes.unwrap_or(MAX_EMBEDDED_COVER_BYTES)
+}
+
+fn record_cover_reduction(context: &ProcessingContext, original: usize, reduced: usize, limit: u64) {
+    let warning = format!(
+        "Cover art reduced from {} to {} to fit the {} limit",
+        megabytes(original as u64), megabytes(reduced as u64), megabytes(limit)
+    );
+    report_warning(context, warning, &[]);
+}
+
+/// Layers the run's metadata profile under the request and the first input's tags
+///
+/// Without a profile the requested metadata is used as is. The inherited
+/// layer follows `strip_track_scoped_metadata` like the FFmpeg pass does:
+/// only book-level fields, with the album as title.
+fn apply_metadata_profile(
+    settings: &AudioSettings,
+    profile: &MetadataProfile,
+    files: &[AudioFile],
+    metadata: Option<AudiobookMetadata>,
+) -> Option<AudiobookMetadata> {
+    if profile.is_empty() {
+        return metadata;
+    }
+    log::info!("Applying the default metadata profile");
+    let inherited = files.iter()
+        .find(|f| f.is_valid)
+        .and_then(|f| read_metadata(&f.path).ok())
+        .map(|tags| inherited_tags(tags, settings.strip_track_scoped_metadata));
+    Some(merge_metadata(metadata.as_ref(), inherited.as_ref(), profile))
+}
+
+/// Tags of the first input that may carry over to the book
+fn inherited_tags(tags: AudiobookMetadata, book_level_only: bool) -> AudiobookMetadata {
+    if !book_level_only {
+        return AudiobookMetadata { cover_art: None, ..tags };
+    }
+    AudiobookMetadata {
+        title: tags.album.clone(),
+        album: tags.album,
+        author: tags.author,
+        narrator: tags.narrator,
+        genre: tags.genre,
+        year: tags.year,
+        ..AudiobookMetadata::new()
+    }
+}
+
+/// Fills in cover art the request didn't pick, before the cover checks run
+///
+/// With `auto_cover_from_input`, art embedded in the first input is used;
+/// the FFmpeg pass drops attached pictures, so a book made from MP3s with
+/// embedded art would otherwise come out coverless. With
+/// `generate_placeholder_cover`, a conventional cover file next to the
+/// inputs comes first, then that input art, then a placeholder rendered from
+/// the title and author. A request without metadata takes the first input's
+/// tags along with its art, the same tags the FFmpeg pass copies, since the
+/// art is written together with them.
+fn fill_missing_cover(
+    context: &ProcessingContext,
+    files: &[AudioFile],
+    metadata: Option<AudiobookMetadata>,
+) -> Option<AudiobookMetadata> {
+    if metadata.as_ref().is_some_and(|m| m.cover_art.is_some()) {
+        return metadata;
+    }
+    let settings = &context.settings;
+    let input_tags = if settings.auto_cover_from_input { first_input_tags(context, files) } else { None };
+    let input_cover = input_tags.as_ref().and_then(|tags| tags.cover_art.clone());
+    let mut metadata = match metadata {
+        Some(metadata) => metadata,
+        None => inherited_tags(input_tags.filter(|tags| tags.cover_art.is_some())?, settings.strip_track_scoped_metadata),
+    };
+    if !settings.generate_placeholder_cover {
+        if input_cover.is_some() {
+            log::info!("Using the first input's cover art");
+        }
+        metadata.cover_art = input_cover;
+        return Some(metadata);
+    }
+    let inputs: Vec<&Path> = files.iter().filter(|f| f.is_valid).map(|f| f.path.as_path()).collect();
+    let local = find_local_cover(&inputs).ok().flatten()
+        .and_then(|path| std::fs::read(path).ok());
+    metadata.cover_art = local
+        .or(input_cover)
+        .or_else(|| {
+            let stem = Path::new(&settings.output_path).file_stem().map(|s| s.to_string_lossy());
+            let title = metadata.title.as_deref().or(stem.as_deref()).unwrap_or_default();
+            log::info!("No cover art found, rendering a placeholder for '{title}'");
+            match render_placeholder_cover(title, metadata.author.as_deref()) {
+                Ok(cover) => Some(cover),
+                Err(e) => {
+                    report_warning(context, format!("Cannot render a placeholder cover: {e}"), &[]);
+                    None
+                }
+            }
+        });
+    Some(metadata)
+}
+
+/// Tags of the first valid input, or None with a warning when they can't be read
+fn first_input_tags(context: &ProcessingContext, files: &[AudioFile]) -> Option<AudiobookMetadata> {
+    let first = files.iter().find(|f| f.is_valid)?;
+    match read_metadata(&first.path) {
+        Ok(tags) => Some(tags),
+        Err(e) => {
+            report_warning(context, format!("Cannot read the first input's cover art: {e}"), &[]);
+            None
+        }
+    }
+}
+
 /// Creates temporary directory for processing with session isolation
-fn create_temp_directory_with_session(session_id: &str) -> Result<PathBuf> {
-    let temp_dir = std::env::temp_dir()
-        .join(TEMP_DIR_NAME)
-        .join(session_id);
-    std::fs::create_dir_all(&temp_dir)
-        .map_err(|e| AppError::FileValidation(
-            format!("Cannot create session temp directory: {e}")
-        ))?;
-    Ok(temp_dir)
+///
+/// Fails if another run left files under the same session id, unless
+/// `clean_existing` is set, which removes them first.
+fn create_temp_directory_with_session(session_id: &str, clean_existing: bool) -> Result<TempLayout> {
+    let layout = TempLayout::for_session(session_id);
+    layout.claim(clean_existing)?;
+    Ok(layout)
 }
 
 /// Creates temporary directory for processing (ADAPTER)
@@ -194,31 +406,63 @@ fn create_temp_directory_with_session(session_id: &str) -> Result<PathBuf> {
 /// ADAPTER FUNCTION: Maintains backward compatibility by using a default
 /// session ID. New code should use create_temp_directory_with_session.
 #[deprecated = "Use create_temp_directory_with_session for session isolation"]
-fn create_temp_directory() -> Result<PathBuf> {
+fn create_temp_directory() -> Result<TempLayout> {
     let default_session = "default-session";
-    create_temp_directory_with_session(default_session)
+    create_temp_directory_with_session(default_session, false)
 }
 
 /// Creates FFmpeg concat file for merging
 fn create_concat_file(
     files: &[AudioFile],
-    temp_dir: &Path
+    layout: &TempLayout
 ) -> Result<PathBuf> {
-    let concat_file = temp_dir.join(TEMP_CONCAT_FILENAME);
-    
+    let concat_file = layout.concat_list();
+    write_concat_list(&concat_file, files.iter().map(|f| f.path.as_path()))?;
+    Ok(concat_file)
+}
+
+/// Writes an FFmpeg concat demuxer list for the given paths
+fn write_concat_list<'a>(
+    concat_file: &Path,
+    paths: impl IntoIterator<Item = &'a Path>,
+) -> Result<()> {
     let mut content = String::new();
-    for file in files {
+    for path in paths {
         // Escape file paths for FFmpeg
-        let escaped_path = file.path.to_string_lossy().replace('\'', "'\"'\"'");
+        let escaped_path = path.to_string_lossy().replace('\'', "'\"'\"'");
         content.push_str(&format!("file '{escaped_path}'\n"));
     }
     
-    std::fs::write(&concat_file, content)
+    std::fs::write(concat_file, content)
         .map_err(|e| AppError::FileValidation(
             format!("Cannot write concat file: {e}")
-        ))?;
-    
-    Ok(concat_file)
+        ))
+}
+
+/// Contents of a session's temp manifest
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TempManifest<'a> {
+    session_id: String,
+    output_path: &'a Path,
+    inputs: Vec<&'a Path>,
+}
+
+/// Records what the session is building next to its intermediates
+///
+/// Only used to diagnose leftover temp directories, so failures are logged.
+fn write_temp_manifest(layout: &TempLayout, context: &ProcessingContext, files: &[AudioFile]) {
+    let manifest = TempManifest {
+        session_id: context.session.id(),
+        output_path: &context.settings.output_path,
+        inputs: files.iter().map(|f| f.path.as_path()).collect(),
+    };
+    let written = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| e.to_string())
+        .and_then(|json| std::fs::write(layout.manifest(), json).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        log::warn!("Cannot write temp manifest: {e}");
+    }
 }
 
 /// Moves temporary output to final location
@@ -226,27 +470,46 @@ fn move_to_final_location(
     temp_output: PathBuf,
     final_path: &Path
 ) -> Result<PathBuf> {
-    // Ensure parent directory exists
-    if let Some(parent) = final_path.parent() {
-        std::fs::create_dir_all(parent)
-            .map_err(|e| AppError::FileValidation(
-                format!("Cannot create output directory: {e}")
-            ))?;
+    let strategy = PublishStrategy::for_destination(final_path, filesystem_of(final_path).as_deref());
+    publish_output(&temp_output, final_path, strategy, |_, _| {}, || false)?;
+    Ok(final_path.to_path_buf())
+}
+
+/// Moves the output into place, reporting copy progress when it crosses volumes
+fn publish_with_progress(context: &ProcessingContext, temp_output: &Path, strategy: PublishStrategy) -> Result<PathBuf> {
+    const MB: u64 = 1024 * 1024;
+    let final_path = &context.settings.output_path;
+    let emitter = ProgressEmitter::for_context(context);
+    let file_name = final_path.file_name().map(|name| name.to_string_lossy().to_string());
+    let on_progress = |copied: u64, total: u64| {
+        let fraction = if total == 0 { 1.0 } else { copied as f32 / total as f32 };
+        emitter.emit_custom(
+            ProcessingStage::Merging,
+            PROGRESS_MERGING_START + PROGRESS_MERGING_WEIGHT * fraction,
+            &format!("Copying output to its destination ({} of {} MB)", copied / MB, total / MB),
+            file_name.clone(),
+            None,
+        );
+    };
+    if !publish_output(temp_output, final_path, strategy, on_progress, || context.is_cancelled())? {
+        return Err(context.cancellation_error());
     }
-    
-    std::fs::rename(&temp_output, final_path)
-        .map_err(|e| AppError::FileValidation(
-            format!("Cannot move file to final location: {e}")
-        ))?;
-    
     Ok(final_path.to_path_buf())
 }
 
 /// Session data for audiobook processing workflow
 struct ProcessingWorkflow {
-    temp_dir: PathBuf,
-    concat_file: PathBuf,
+    layout: TempLayout,
     total_duration: f64,
+    skipped_inputs: Vec<SkippedInput>,
+    /// Valid inputs with their durations, in book order
+    inputs: Vec<(PathBuf, f64)>,
+    /// Chapters written into the merged output
+    chapters: Vec<Chapter>,
+    /// How the finished output reaches its destination
+    publish_strategy: PublishStrategy,
+    /// Duration spread of the inputs, reported on completion
+    input_stats: Option<InputDurationStats>,
 }
 
 /// Validates inputs and emits progress
@@ -260,7 +523,7 @@ fn validate_inputs_with_progress(
     validate_processing_inputs(files, &context.settings)?;
     
     if context.is_cancelled() {
-        return Err(AppError::InvalidInput("Processing was cancelled".to_string()));
+        return Err(context.cancellation_error());
     }
     
     Ok(())
@@ -274,22 +537,28 @@ fn prepare_workspace(
     let mut emitter = ProgressReporter::new(1); // Single file processing
     
     emitter.set_stage(ProcessingStage::Analyzing);
-    let temp_dir = create_temp_directory_with_session(&context.session.id())?;
-    let concat_file = create_concat_file(files, &temp_dir)?;
+    let layout = create_temp_directory_with_session(&context.session.id(), false)?;
+    create_concat_file(files, &layout)?;
+    write_temp_manifest(&layout, context, files);
     
-    let total_duration: f64 = files.iter()
+    let inputs: Vec<(PathBuf, f64)> = files.iter()
         .filter(|f| f.is_valid)
-        .map(|f| f.duration.unwrap_or(0.0))
-        .sum();
+        .map(|f| (f.path.clone(), f.duration.unwrap_or(0.0)))
+        .collect();
+    let total_duration: f64 = inputs.iter().map(|(_, duration)| duration).sum();
     
     if context.is_cancelled() {
-        return Err(AppError::InvalidInput("Processing was cancelled".to_string()));
+        return Err(context.cancellation_error());
     }
     
     Ok(ProcessingWorkflow {
-        temp_dir,
-        concat_file,
+        layout,
         total_duration,
+        skipped_inputs: Vec::new(),
+        inputs,
+        chapters: Vec::new(),
+        publish_strategy: PublishStrategy::default(),
+        input_stats: None,
     })
 }
 
@@ -306,7 +575,7 @@ fn validate_and_prepare(
 async fn execute_processing(
     context: &ProcessingContext,
     workflow: &ProcessingWorkflow,
-    files: &[AudioFile],
+    resolution: &PlanResolution,
     reporter: &mut ProgressReporter,
 ) -> Result<PathBuf> {
     let mut emitter = ProgressReporter::new(1); // Single file processing
@@ -314,44 +583,114 @@ async fn execute_processing(
     // Stage 2: Convert and merge files
     reporter.set_stage(ProcessingStage::Converting);
     emitter.set_stage(ProcessingStage::Converting);
+    context.session.start_stage(TimelineStage::Converting);
     
     // Log basic info for debugging
     log::info!("Starting FFmpeg merge - Total duration: {:.2}s, Bitrate: {}k", 
               workflow.total_duration, context.settings.bitrate);
     
     let merged_output = merge_audio_files_with_context(
-        &workflow.concat_file,
+        &workflow.layout,
         context,
         reporter,
         workflow.total_duration,
-        files
+        resolution,
     ).await?;
     
     if context.is_cancelled() {
-        return Err(AppError::InvalidInput("Processing was cancelled".to_string()));
+        return Err(context.cancellation_error());
     }
     
     Ok(merged_output)
 }
 
-/// Writes metadata if provided
+/// Outcome of the metadata stage of a run that went on to completion
+#[derive(Debug, Default)]
+struct MetadataStage {
+    /// Tags still to be written with `retry_metadata_write`, the file stayed busy
+    pending: bool,
+    /// Fields the output container could not hold
+    warnings: Vec<String>,
+}
+
+/// Writes metadata and cover art if provided
+///
+/// Busy-file errors are retried; if the file stays busy the encode is kept
+/// and the metadata is reported as pending, to be written later with
+/// `retry_metadata_write`. Fields the container can't hold become warnings
+/// unless `strict_metadata_fields` is set. With
+/// `append_chapter_list_to_description`, `chapters` are listed at the end
+/// of the description. With `write_encoding_info`, the encoder tag records
+/// the app version and encoder settings.
 fn write_metadata_stage(
     context: &ProcessingContext,
     merged_output: &PathBuf,
-    metadata: Option<AudiobookMetadata>,
+    metadata: Option<&AudiobookMetadata>,
+    chapters: &[Chapter],
     reporter: &mut ProgressReporter,
-) -> Result<()> {
+) -> Result<MetadataStage> {
+    let mut stage = MetadataStage::default();
     if let Some(metadata) = metadata {
+        // Settings are resolved by now, so the encoding info has the sample rate
+        let metadata = &AudiobookMetadata {
+            encoding_info: context.settings.write_encoding_info
+                .then(|| EncodingInfo::for_settings(&context.settings)),
+            ..with_chapter_list(metadata, chapters, &context.settings)
+        };
         let mut emitter = ProgressReporter::new(1); // Single file processing
         reporter.set_stage(ProcessingStage::WritingMetadata);
         emitter.set_stage(ProcessingStage::WritingMetadata);
-        write_metadata(merged_output, &metadata)?;
+        context.session.start_stage(TimelineStage::WritingMetadata);
+        let options = MetadataWriteOptions {
+            reproducible: context.settings.reproducible,
+            strict: context.settings.strict_metadata_fields,
+            ..MetadataWriteOptions::default()
+        };
+        match write_tags_with_retry(merged_output, metadata, options) {
+            Ok(report) => {
+                stage.warnings = report.warnings();
+                for warning in &stage.warnings {
+                    report_warning(context, warning.clone(), &[]);
+                }
+            }
+            Err(e) if is_busy_error(&e) => {
+                report_warning(context, format!("Metadata not written, file stayed busy: {e}"), &[]);
+                stage.pending = true;
+            }
+            Err(e) => return Err(e),
+        }
         
         if context.is_cancelled() {
-            return Err(AppError::InvalidInput("Processing was cancelled".to_string()));
+            return Err(context.cancellation_error());
         }
     }
-    Ok(())
+    Ok(stage)
+}
+
+/// `metadata` with the chapter list appended to its description, when the settings ask for it
+fn with_chapter_list(metadata: &AudiobookMetadata, chapters: &[Chapter], settings: &AudioSettings) -> AudiobookMetadata {
+    let mut metadata = metadata.clone();
+    if settings.append_chapter_list_to_description {
+        let chapters: Vec<ChapterInfo> = chapters.iter().map(ChapterInfo::from).collect();
+        metadata.description = append_chapter_list(
+            metadata.description.as_deref(),
+            &chapters,
+            settings.chapter_list_timestamp_format,
+            DESCRIPTION_WITH_CHAPTERS_MAX_CHARS,
+        );
+    }
+    metadata
+}
+
+/// Adds a Nero `chpl` atom when the chapter format asks for one
+///
+/// Runs after the tags are written so the tag writer can't drop it.
+fn write_chpl_stage(context: &ProcessingContext, merged_output: &Path, chapters: &[Chapter]) -> Result<()> {
+    if chapters.is_empty() || !context.settings.chapter_format.writes_chpl() {
+        return Ok(());
+    }
+    let chapters: Vec<ChapterInfo> = chapters.iter().map(ChapterInfo::from).collect();
+    write_chpl(merged_output, &chapters)
 }
 
 /// Completes processing with file movement and cleanup
@@ -359,24 +698,99 @@ fn complete_processing(
     context: &ProcessingContext,
     workflow: ProcessingWorkflow,
     merged_output: PathBuf,
+    metadata: MetadataStage,
+    book: Option<&AudiobookMetadata>,
     reporter: &mut ProgressReporter,
 ) -> Result<String> {
     let mut emitter = ProgressReporter::new(1); // Single file processing
+    let workflow_skipped = workflow.skipped_inputs;
     
     emitter.set_stage(ProcessingStage::Completed);
-    let final_output = move_to_final_location(merged_output, &context.settings.output_path)?;
+    context.session.start_stage(TimelineStage::Publish);
+    let final_output = publish_with_progress(context, &merged_output, workflow.publish_strategy)?;
+    if context.settings.copy_transcripts {
+        copy_transcripts(context, &workflow.inputs, &final_output);
+    }
+    if context.settings.export_cue {
+        export_cue_sheet(context, &workflow.chapters, book, &final_output);
+    }
+    if let Some(format) = context.settings.metadata_sidecar {
+        export_metadata_sidecar(context, format, workflow.total_duration, &workflow.chapters, book, &final_output);
+    }
     
     if context.is_cancelled() {
-        return Err(AppError::InvalidInput("Processing was cancelled".to_string()));
+        return Err(context.cancellation_error());
     }
     
     // Cleanup stage - no specific stage for this
-    cleanup_temp_directory_with_session(&context.session.id(), workflow.temp_dir)?;
+    context.session.start_stage(TimelineStage::Cleanup);
+    cleanup_temp_directory_with_session(&context.session.id(), &workflow.layout)?;
+    context.session.finish_timeline();
     
     reporter.complete();
     emitter.complete();
     
-    Ok(format!("Successfully created audiobook: {}", final_output.display()))
+    let mut message = format!("Successfully created audiobook: {}", final_output.display());
+    if metadata.pending {
+        message.push_str(" (metadata pending, file was busy)");
+    }
+    ProgressEmitter::for_context(context).emit_complete_with_timeline(
+        &message,
+        context.session.timeline(),
+        workflow_skipped,
+        metadata.pending,
+        metadata.warnings,
+        workflow.input_stats,
+    );
+    Ok(message)
+}
+
+/// Copies the inputs' transcripts next to the output; failures are only warnings
+fn copy_transcripts(context: &ProcessingContext, inputs: &[(PathBuf, f64)], output: &Path) {
+    match publish_transcripts(inputs, output) {
+        Ok(Some(transcript)) => log::info!("Transcript written to {}", transcript.display()),
+        Ok(None) => log::debug!("No transcripts found next to the inputs"),
+        Err(e) => {
+            report_warning(context, format!("Cannot copy transcripts: {e}"), &[]);
+        }
+    }
+}
+
+/// Writes a CUE sheet next to the output; failures are only warnings
+fn export_cue_sheet(context: &ProcessingContext, chapters: &[Chapter], book: Option<&AudiobookMetadata>, output: &Path) {
+    match write_cue_sheet(output, chapters, book) {
+        Ok(sheet) => log::info!("CUE sheet written to {}", sheet.display()),
+        Err(e) => {
+            report_warning(context, format!("Cannot write CUE sheet: {e}"), &[]);
+        }
+    }
+}
+
+/// Writes the metadata sidecar next to the output; failures are only warnings
+fn export_metadata_sidecar(
+    context: &ProcessingContext,
+    format: SidecarFormat,
+    duration_secs: f64,
+    chapters: &[Chapter],
+    book: Option<&AudiobookMetadata>,
+    output: &Path,
+) {
+    let audio = SidecarAudio {
+        duration_secs,
+        bitrate_kbps: context.settings.bitrate,
+        sample_rate: match context.settings.sample_rate {
+            SampleRateConfig::Explicit(rate) => Some(rate),
+            SampleRateConfig::Auto => None,
+        },
+        channels: context.settings.channels.channel_count(),
+    };
+    let empty = AudiobookMetadata::new();
+    match write_metadata_sidecar(output, format, book.unwrap_or(&empty), chapters, &audio) {
+        Ok(sidecar) => log::info!("Metadata sidecar written to {}", sidecar.display()),
+        Err(e) => {
+            report_warning(context, format!("Cannot write metadata sidecar: {e}"), &[]);
+        }
+    }
 }
 
 /// Finalizes processing with metadata and cleanup
@@ -387,8 +801,9 @@ async fn finalize_processing(
     metadata: Option<AudiobookMetadata>,
     reporter: &mut ProgressReporter,
 ) -> Result<String> {
-    write_metadata_stage(context, &merged_output, metadata, reporter)?;
-    complete_processing(context, workflow, merged_output, reporter)
+    let stage = write_metadata_stage(context, &merged_output, metadata.as_ref(), &workflow.chapters, reporter)?;
+    write_chpl_stage(context, &merged_output, &workflow.chapters)?;
+    complete_processing(context, workflow, merged_output, stage, metadata.as_ref(), reporter)
 }
 
 /// Main function to process audiobook with context-based architecture
@@ -399,20 +814,89 @@ pub async fn process_audiobook_with_context(
     context: ProcessingContext,
     files: Vec<AudioFile>,
     metadata: Option<AudiobookMetadata>,
+) -> Result<String> {
+    let session = context.session.clone();
+    let output_path = context.settings.output_path.clone();
+    let verify_output = context.settings.verify_output;
+    let hook = context.preferences.post_process_hook.clone();
+    let emitter = ProgressEmitter::for_context(&context);
+    let file_paths: Vec<&Path> = files.iter().map(|f| f.path.as_path()).collect();
+    let input_stats = InputDurationStats::from_files(&files);
+    let hash = plan_hash(&file_paths, &context.settings, metadata.as_ref())
+        .inspect_err(|e| log::warn!("Cannot compute plan hash: {e}"))
+        .ok();
+    register_active_session(&session);
+    
+    let result = run_processing_pipeline(context.clone(), files, metadata).await;
+    unregister_active_session(&session.id());
+    match (&result, &hash) {
+        (Err(e), _) => emitter.emit_failed(e),
+        (Ok(_), Some(hash)) => {
+            if let Err(e) = write_sidecar(&output_path, hash) {
+                log::warn!("Cannot write plan hash sidecar: {e}");
+            }
+        }
+        (Ok(_), None) => {}
+    }
+    let mut record = HistoryRecord::from_result(&session, &output_path, &result);
+    record.plan_hash = hash;
+    record.input_stats = input_stats;
+    if result.is_ok() {
+        record.post_process_hook = run_configured_hook(hook, &record).await;
+        if let Some(warning) = record.post_process_hook.as_ref().and_then(|outcome| outcome.warning()) {
+            report_warning(&context, warning, &[]);
+        }
+    }
+    record_run(&record);
+    if result.is_ok() && verify_output {
+        enqueue_verification(&session.id(), &output_path);
+    }
+    result
+}
+
+/// Runs every processing stage for one audiobook
+async fn run_processing_pipeline(
+    mut context: ProcessingContext,
+    files: Vec<AudioFile>,
+    metadata: Option<AudiobookMetadata>,
 ) -> Result<String> {
     let mut reporter = ProgressReporter::new(files.len());
     let mut metrics = ProcessingMetrics::new();
+    if let Ok(mut progress) = context.session.progress().lock() {
+        progress.set_total_files(files.len());
+    }
     
     // Stage 1: Validate and prepare
     reporter.set_stage(ProcessingStage::Analyzing);
-    let workflow = validate_and_prepare(&context, &files)?;
+    context.session.start_stage(TimelineStage::Analyzing);
+    let end_timeline = |_: &AppError| context.session.finish_timeline();
+    let resolution = resolve_plan(files, &context.settings).inspect_err(end_timeline)?;
+    warn_skipped_inputs(&context, &resolution.skipped_inputs);
+    context.settings = resolution.settings.clone();
+    // Normalized before layering, so a blank form field doesn't hide inherited or profile values
+    let metadata = metadata.as_ref().map(normalize_metadata).transpose().inspect_err(end_timeline)?;
+    let metadata = apply_metadata_profile(&context.settings, &context.preferences.metadata_profile, &resolution.files, metadata);
+    let metadata = fill_missing_cover(&context, &resolution.files, metadata);
+    check_cover_quality(&context.settings, metadata.as_ref()).inspect_err(end_timeline)?;
+    let metadata = fit_cover_stage(&context, metadata).inspect_err(end_timeline)?;
+    // Refuse a bad year, ISBN or ASIN before encoding rather than at the final tag write
+    let metadata = metadata.as_ref().map(normalize_metadata).transpose().inspect_err(end_timeline)?;
+    metadata.as_ref().map_or(Ok(()), validate_metadata).inspect_err(end_timeline)?;
+    warn_bitrate_excess(&context, resolution.bitrate_excess.as_ref());
+    warn_output_size(&context, resolution.output_size_warning.as_deref());
+    let files = &resolution.files;
+    let mut workflow = validate_and_prepare(&context, files).inspect_err(end_timeline)?;
+    workflow.skipped_inputs = resolution.skipped_inputs.clone();
+    workflow.chapters = resolution.chapters.clone();
+    workflow.publish_strategy = resolution.publish_strategy;
+    workflow.input_stats = resolution.input_stats;
     
     // Update metrics with file information
-    for file in &files {
+    for file in files {
         if file.is_valid {
             if let Some(duration) = file.duration {
                 // Estimate file size based on duration and bitrate
-                let estimated_bytes = (duration * context.settings.bitrate as f64 * 125.0) as usize;
+                let estimated_bytes = (duration * resolution.resolved.bitrate as f64 * 125.0) as usize;
                 metrics.update_file_processed(
                     Duration::from_secs_f64(duration),
                     estimated_bytes
@@ -422,10 +906,12 @@ pub async fn process_audiobook_with_context(
     }
     
     // Stage 2: Execute processing
-    let merged_output = execute_processing(&context, &workflow, &files, &mut reporter).await?;
+    let merged_output = execute_processing(&context, &workflow, &resolution, &mut reporter).await
+        .inspect_err(end_timeline)?;
     
     // Stage 3: Finalize with metadata and cleanup
-    let result = finalize_processing(&context, workflow, merged_output, metadata, &mut reporter).await?;
+    let result = finalize_processing(&context, workflow, merged_output, metadata, &mut reporter).await
+        .inspect_err(end_timeline)?;
     
     // Log final metrics summary
     log::info!("{}", metrics.format_summary());
@@ -434,6 +920,7 @@ pub async fn process_audiobook_with_context(
 }
 
 /// Creates processing session from legacy state
+#[cfg(feature = "gui")]
 pub fn create_session_from_legacy_state(
     state: &tauri::State<'_, crate::ProcessingState>,
 ) -> Result<std::sync::Arc<ProcessingSession>> {
@@ -455,6 +942,9 @@ pub fn create_session_from_legacy_state(
         *new_is_processing = *old_is_processing;
         *new_is_cancelled = *old_is_cancelled;
     }
+    if let Some(reason) = state.cancel_reason.lock().ok().and_then(|reason| *reason) {
+        session.cancel(reason);
+    }
     
     Ok(session)
 }
@@ -465,7 +955,9 @@ pub fn create_session_from_legacy_state(
 /// the old parameter-based approach to use the new ProcessingContext internally.
 /// 
 /// All existing code calling this function will continue to work unchanged.
+#[cfg(feature = "gui")]
 #[deprecated = "Use process_audiobook_with_context for new code - this adapter maintains compatibility"]
+#[allow(dead_code)]
 pub async fn process_audiobook_with_events(
     window: tauri::Window,
     state: tauri::State<'_, crate::ProcessingState>,
@@ -474,46 +966,101 @@ pub async fn process_audiobook_with_events(
     metadata: Option<AudiobookMetadata>,
 ) -> Result<String> {
     let session = create_session_from_legacy_state(&state)?;
-    let context = ProcessingContext::new(window, session, settings);
+    let context = ProcessingContext::for_window(window, session, settings);
     
     // Delegate to the new context-based function
     process_audiobook_with_context(context, files, metadata).await
 }
 
 /// Merges audio files with context-based progress tracking
+///
+/// FFmpeg plans come from the pre-flight resolution, so the run encodes
+/// exactly what `resolve_plan` previewed.
 async fn merge_audio_files_with_context(
-    concat_file: &Path,
+    layout: &TempLayout,
     context: &ProcessingContext,
     _reporter: &mut ProgressReporter,
     total_duration: f64,
-    files: &[AudioFile],
+    resolution: &PlanResolution,
 ) -> Result<PathBuf> {
-    let temp_output = concat_file.parent()
-        .ok_or_else(|| AppError::FileValidation("Invalid concat file path".to_string()))?
-        .join(TEMP_MERGED_FILENAME);
-    
-    // Extract file paths and settings from context
-    let file_paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
-    let settings = &context.settings;
+    let temp_output = layout.merged_output();
+    let labels = match &context.settings.chapter_specs {
+        Some(_) => InputLabels::for_chapters(&resolution.chapters),
+        None => InputLabels::for_files(&resolution.files, effective_chapter_options(&context.settings).as_ref()),
+    };
+    
+    let chapters_file = write_chapters_metadata(layout, resolution)?;
+    if let Some(segment_plan) = &resolution.segment_plan {
+        merge_in_segments(segment_plan, layout, context, resolution, &labels, chapters_file.as_deref()).await?;
+        return Ok(temp_output);
+    }
     
-    // Create media processing plan and execute using new pipeline
-    let plan = MediaProcessingPlan::new(
-        concat_file.to_path_buf(),
-        temp_output.clone(),
-        settings.clone(),
-        file_paths,
-        total_duration,
-    );
+    let mut plan = resolution.media_plan(layout.concat_list(), temp_output.clone(), total_duration)
+        .with_progress_labels(labels);
+    if let Some(chapters_file) = chapters_file {
+        plan = plan.with_chapters_file(chapters_file);
+    }
     
     plan.execute_with_context(context).await?;
     
     Ok(temp_output)
 }
 
+/// Writes the resolved chapters as an FFmpeg metadata file, if FFmpeg muxes any
+fn write_chapters_metadata(layout: &TempLayout, resolution: &PlanResolution) -> Result<Option<PathBuf>> {
+    if resolution.chapters.is_empty() || !resolution.settings.chapter_format.muxes_with_ffmpeg() {
+        return Ok(None);
+    }
+    let path = layout.chapters_metadata();
+    std::fs::write(&path, ffmetadata(&resolution.chapters))?;
+    log::info!("Writing {} chapter(s) into the merged output", resolution.chapters.len());
+    Ok(Some(path))
+}
+
+/// Merges a large input list segment by segment, then joins the segments
+///
+/// The resolution's sample rate is explicit, so every segment encodes identically.
+/// Each segment starts at a unit boundary, where a soft stop ends the run
+/// after the segment before it. Segments alone aren't a book, so nothing
+/// is published then.
+async fn merge_in_segments(
+    segment_plan: &SegmentPlan,
+    layout: &TempLayout,
+    context: &ProcessingContext,
+    resolution: &PlanResolution,
+    labels: &InputLabels,
+    chapters_file: Option<&Path>,
+) -> Result<()> {
+    log::info!("Merging {} files in {} segments", resolution.files.len(), segment_plan.segment_count());
+    let mut segment_outputs = Vec::with_capacity(segment_plan.segment_count());
+    for segment in &segment_plan.segments {
+        context.check_unit_boundary()?;
+        
+        let segment_paths = resolution.files[segment.files.clone()].iter().map(|f| f.path.as_path());
+        let segment_list = layout.segment_concat_list(segment.index);
+        write_concat_list(&segment_list, segment_paths)?;
+        
+        let segment_output = layout.segment_output(segment.index);
+        let plan = resolution.segment_media_plan(segment.index, segment_list, segment_output.clone())
+            .ok_or_else(|| AppError::General(format!("Segment {} missing from the plan", segment.index)))?
+            .with_progress_labels(labels.clone());
+        let pass = ProgressPass::Segment { offset: segment.start_offset as f32 };
+        plan.execute_pass_with_context(context, pass).await?;
+        segment_outputs.push(segment_output);
+    }
+    
+    context.session.start_stage(TimelineStage::Merging);
+    let join_list = layout.segment_join_list();
+    write_concat_list(&join_list, segment_outputs.iter().map(PathBuf::as_path))?;
+    let cmd = build_segment_join_command(&join_list, &layout.merged_output(), chapters_file, context.settings.reproducible)?;
+    execute_ffmpeg_pass_with_context(cmd, context, segment_plan.total_duration, ProgressPass::Join, None).await
+}
+
 /// Merges audio files with progress tracking and event emission (ADAPTER)
 /// 
 /// ADAPTER FUNCTION: Maintains backward compatibility by converting parameters
 /// to use the new context-based approach internally.
+#[cfg(feature = "gui")]
 #[deprecated = "Use merge_audio_files_with_context for new code - this adapter maintains compatibility"]
 #[allow(dead_code)]
 async fn merge_audio_files_with_events(
@@ -538,10 +1085,13 @@ async fn merge_audio_files_with_events(
         *new_is_cancelled = *old_is_cancelled;
     }
     
-    let context = ProcessingContext::new(window.clone(), session, settings.clone());
+    let context = ProcessingContext::for_window(window.clone(), session, settings.clone());
     
     // Delegate to new context-based function
-    merge_audio_files_with_context(concat_file, &context, reporter, total_duration, files).await
+    let layout = TempLayout::new(concat_file.parent()
+        .ok_or_else(|| AppError::FileValidation("Invalid concat file path".to_string()))?);
+    let resolution = resolve_plan(files.to_vec(), settings)?;
+    merge_audio_files_with_context(&layout, &context, reporter, total_duration, &resolution).await
 }
 
 
@@ -570,6 +1120,7 @@ async fn merge_audio_files_with_events(
 /// 
 /// ADAPTER FUNCTION: Maintains backward compatibility by converting parameters
 /// to use the new context-based approach internally.
+#[cfg(feature = "gui")]
 #[deprecated = "Use execute_with_progress_context for new code - this adapter maintains compatibility"]
 #[allow(dead_code)]
 async fn execute_with_progress_events(
@@ -593,7 +1144,7 @@ async fn execute_with_progress_events(
     }
     
     // Create a minimal settings for context (we only need it for structure)
-    let context = ProcessingContext::new(window.clone(), session, AudioSettings::default());
+    let context = ProcessingContext::for_window(window.clone(), session, AudioSettings::default());
     
     // Use media pipeline for FFmpeg execution
     use super::media_pipeline::execute_ffmpeg_with_progress_context;
@@ -601,13 +1152,11 @@ async fn execute_with_progress_events(
 }
 
 /// Cleans up session-specific temporary directory using CleanupGuard
-fn cleanup_temp_directory_with_session(session_id: &str, temp_dir: PathBuf) -> Result<()> {
-    log::debug!("Cleaning up temporary directory for session {}: {}", session_id, temp_dir.display());
-    let mut guard = CleanupGuard::new(session_id.to_string());
-    guard.add_path(&temp_dir);
-    guard.cleanup_now()
+fn cleanup_temp_directory_with_session(session_id: &str, layout: &TempLayout) -> Result<()> {
+    log::debug!("Cleaning up temporary directory for session {}: {}", session_id, layout.dir().display());
+    layout.remove(session_id)
         .map_err(|e| {
-            log::warn!("Failed to cleanup temporary directory '{}': {}", temp_dir.display(), e);
+            log::warn!("Failed to cleanup temporary directory '{}': {}", layout.dir().display(), e);
             e
         })
 }
@@ -6,16 +6,27 @@
 
 use super::{ProcessingProgress, ProcessingStage};
 use super::constants::*;
-use serde::Serialize;
+use super::context::ProcessingContext;
+use super::display_format::DisplayFormatter;
+use super::file_list::SkippedInput;
+use super::input_stats::InputDurationStats;
+use super::progress_snapshot::SharedProgress;
+use super::session::JobLabel;
+use super::telemetry::TelemetryPoint;
+use super::timeline::StageTiming;
+use crate::errors::{AppError, CancelReason};
+use serde::{Deserialize, Serialize};
+use super::progress_sink::ProgressSink;
+use std::sync::Arc;
 use std::time::Instant;
-use tauri::{Emitter, Window};
 
 /// Progress event structure for frontend communication
 /// Extracted from processor.rs to centralize progress event handling
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ProgressEvent {
-    /// Current processing stage name
-    pub stage: String,
+    /// Current processing stage, flattened into `stage` and `reason`
+    #[serde(flatten)]
+    pub stage: ProcessingStage,
     /// Progress percentage (0-100)
     pub percentage: f32,
     /// Human-readable status message
@@ -24,21 +35,88 @@ pub struct ProgressEvent {
     pub current_file: Option<String>,
     /// Estimated time remaining in seconds
     pub eta_seconds: Option<f64>,
+    /// Stage timings, only sent with the completion event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeline: Option<Vec<StageTiming>>,
+    /// Inputs left out by the skip policy, only sent with the completion event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped_inputs: Option<Vec<SkippedInput>>,
+    /// Job label, when the session has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Job notes, when the session has them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Why the run was cancelled, only sent with a cancellation failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cancel_reason: Option<CancelReason>,
+    /// Set on the completion event when the output was kept without its tags
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub metadata_pending: bool,
+    /// Warnings of a completed run, such as metadata fields the container could not hold
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<String>>,
+    /// Duration spread of the book's inputs, only sent with the completion event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_stats: Option<InputDurationStats>,
 }
 
 /// Centralized progress event emitter
 /// Eliminates duplicate progress emission code throughout the codebase
 #[allow(dead_code)] // New infrastructure - will be used when processor.rs is refactored
 pub struct ProgressEmitter {
-    /// Reference to the Tauri window for event emission
-    window: Window,
+    /// Destination for progress events
+    sink: Arc<dyn ProgressSink>,
+    /// Label and notes attached to every event
+    label: Option<JobLabel>,
+    /// Session progress updated with every event, for late subscribers
+    progress: Option<SharedProgress>,
 }
 
 #[allow(dead_code)] // New infrastructure - methods will be used when processor.rs is refactored
 impl ProgressEmitter {
     /// Creates a new progress emitter
-    pub fn new(window: Window) -> Self {
-        Self { window }
+    pub fn new(sink: Arc<dyn ProgressSink>) -> Self {
+        Self { sink, label: None, progress: None }
+    }
+
+    /// Creates an emitter that tags events with the session's label
+    pub fn for_context(context: &ProcessingContext) -> Self {
+        Self {
+            sink: context.sink.clone(),
+            label: context.session.label(),
+            progress: Some(context.session.progress().clone()),
+        }
+    }
+
+    /// Label and notes fields for an event
+    fn label_fields(&self) -> (Option<String>, Option<String>) {
+        match &self.label {
+            Some(job) => (Some(job.label.clone()), job.notes.clone()),
+            None => (None, None),
+        }
+    }
+
+    /// Keeps the session's latest progress in step with emitted events
+    fn record_progress(&self, stage: &ProcessingStage, percentage: f32, current_file: &Option<String>, eta_seconds: Option<f64>) {
+        if let Some(mut progress) = self.progress.as_ref().and_then(|p| p.lock().ok()) {
+            progress.record(stage.clone(), percentage, current_file.clone(), eta_seconds);
+        }
+    }
+
+    /// Keeps the session's latest speed and output size in step with the encoder
+    pub fn record_throughput(&self, speed: Option<f64>, bytes_written: Option<u64>) {
+        if let Some(mut progress) = self.progress.as_ref().and_then(|p| p.lock().ok()) {
+            progress.set_throughput(speed, bytes_written);
+        }
+    }
+
+    /// Emits a telemetry point and keeps it for late subscribers
+    pub fn emit_telemetry(&self, point: TelemetryPoint) {
+        if let Some(mut progress) = self.progress.as_ref().and_then(|p| p.lock().ok()) {
+            progress.record_telemetry(point.clone());
+        }
+        let _ = self.sink.emit("processing-telemetry", &point);
     }
 
     /// Emits a progress event for analyzing stage start
@@ -136,6 +214,61 @@ impl ProgressEmitter {
         );
     }
 
+    /// Emits the completion event along with the session's stage timings,
+    /// any inputs that were skipped, whether metadata is still pending and
+    /// any metadata warnings and the spread of input durations
+    pub fn emit_complete_with_timeline(
+        &self,
+        message: &str,
+        timeline: Vec<StageTiming>,
+        skipped_inputs: Vec<SkippedInput>,
+        metadata_pending: bool,
+        warnings: Vec<String>,
+        input_stats: Option<InputDurationStats>,
+    ) {
+        self.record_progress(&ProcessingStage::Completed, PROGRESS_COMPLETE, &None, None);
+        let (label, notes) = self.label_fields();
+        let event = ProgressEvent {
+            stage: ProcessingStage::Completed,
+            percentage: PROGRESS_COMPLETE,
+            message: message.to_string(),
+            current_file: None,
+            eta_seconds: None,
+            timeline: Some(timeline),
+            skipped_inputs: (!skipped_inputs.is_empty()).then_some(skipped_inputs),
+            label,
+            notes,
+            cancel_reason: None,
+            metadata_pending,
+            warnings: (!warnings.is_empty()).then_some(warnings),
+            input_stats,
+        };
+        let _ = self.sink.emit("processing-progress", &event);
+    }
+
+    /// Emits the failure event for a run that ended with `error`
+    pub fn emit_failed(&self, error: &AppError) {
+        let stage = ProcessingStage::Failed(error.to_string());
+        self.record_progress(&stage, 0.0, &None, None);
+        let (label, notes) = self.label_fields();
+        let event = ProgressEvent {
+            stage,
+            percentage: 0.0,
+            message: error.to_string(),
+            current_file: None,
+            eta_seconds: None,
+            timeline: None,
+            skipped_inputs: None,
+            label,
+            notes,
+            cancel_reason: error.cancel_reason(),
+            metadata_pending: false,
+            warnings: None,
+            input_stats: None,
+        };
+        let _ = self.sink.emit("processing-progress", &event);
+    }
+
     /// Emits a custom progress event with all parameters
     pub fn emit_custom(
         &self,
@@ -157,24 +290,25 @@ impl ProgressEmitter {
         current_file: Option<String>,
         eta_seconds: Option<f64>,
     ) {
-        let stage_str = match stage {
-            ProcessingStage::Analyzing => "analyzing",
-            ProcessingStage::Converting => "converting",
-            ProcessingStage::Merging => "merging",
-            ProcessingStage::WritingMetadata => "writing_metadata",
-            ProcessingStage::Completed => "completed",
-            ProcessingStage::Failed(_) => "failed",
-        };
-
+        self.record_progress(&stage, percentage, &current_file, eta_seconds);
+        let (label, notes) = self.label_fields();
         let event = ProgressEvent {
-            stage: stage_str.to_string(),
+            stage,
             percentage,
             message: message.to_string(),
             current_file,
             eta_seconds,
+            timeline: None,
+            skipped_inputs: None,
+            label,
+            notes,
+            cancel_reason: None,
+            metadata_pending: false,
+            warnings: None,
+            input_stats: None,
         };
 
-        let _ = self.window.emit("processing-progress", &event);
+        let _ = self.sink.emit("processing-progress", &event);
     }
 
     /// Calculates progress percentage within a stage range
@@ -193,15 +327,9 @@ impl ProgressEmitter {
         start_percentage + (progress_ratio * range)
     }
 
-    /// Formats estimated time remaining into a human-readable string
+    /// Formats estimated time remaining in the display locale
     pub fn format_eta(seconds: f64) -> String {
-        if seconds < SECONDS_PER_MINUTE {
-            format!("{seconds:.0}s")
-        } else {
-            let minutes = (seconds / SECONDS_PER_MINUTE) as u32;
-            let remaining_seconds = seconds % SECONDS_PER_MINUTE;
-            format!("{minutes}m {remaining_seconds:.0}s")
-        }
+        DisplayFormatter::current().duration(seconds)
     }
 }
 
@@ -303,6 +431,8 @@ impl ProgressReporter {
             files_completed: self.files_completed,
             total_files: self.total_files,
             eta_seconds: self.estimate_time_remaining(),
+            speed: None,
+            bytes_written: None,
         }
     }
     
@@ -430,6 +560,41 @@ mod tests {
         assert_eq!(ProgressEmitter::format_eta(125.0), "2m 5s");
     }
 
+    #[test]
+    fn test_progress_event_label_serialization() {
+        let mut event = ProgressEvent {
+            stage: ProcessingStage::Converting,
+            percentage: 40.0,
+            message: "Converting".to_string(),
+            current_file: None,
+            eta_seconds: None,
+            timeline: None,
+            skipped_inputs: None,
+            label: None,
+            notes: None,
+            cancel_reason: None,
+            metadata_pending: false,
+            warnings: None,
+            input_stats: None,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert!(json.get("label").is_none());
+        assert!(json.get("metadata_pending").is_none());
+
+        event.label = Some("Dune".to_string());
+        event.notes = Some("re-encode".to_string());
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["label"], "Dune");
+        assert_eq!(json["notes"], "re-encode");
+
+        event.metadata_pending = true;
+        assert_eq!(serde_json::to_value(&event).unwrap()["metadata_pending"], true);
+
+        assert!(serde_json::to_value(&event).unwrap().get("warnings").is_none());
+        event.warnings = Some(vec!["Metadata field 'year' was not written".to_string()]);
+        assert_eq!(serde_json::to_value(&event).unwrap()["warnings"][0], "Metadata field 'year' was not written");
+    }
+
     #[test]
     fn test_progress_reporter_new() {
         let reporter = ProgressReporter::new(5);
@@ -483,4 +648,62 @@ mod tests {
         assert_eq!(parse_ffmpeg_progress("out_time_us=1000000").unwrap(), 1.0);
         assert_eq!(parse_ffmpeg_progress("out_time_us=60000000").unwrap(), 60.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_stage_wire_contract() {
+        let stages = [
+            (ProcessingStage::Analyzing, "analyzing"),
+            (ProcessingStage::Converting, "converting"),
+            (ProcessingStage::Merging, "merging"),
+            (ProcessingStage::WritingMetadata, "writing_metadata"),
+            (ProcessingStage::Completed, "completed"),
+            (ProcessingStage::Failed("disk full".to_string()), "failed"),
+        ];
+        for (stage, name) in stages {
+            // Exhaustive so a new variant can't be added without a contract entry
+            let reason = match &stage {
+                ProcessingStage::Analyzing
+                | ProcessingStage::Converting
+                | ProcessingStage::Merging
+                | ProcessingStage::WritingMetadata
+                | ProcessingStage::Completed => None,
+                ProcessingStage::Failed(reason) => Some(reason.clone()),
+            };
+            assert_eq!(stage.to_string(), name);
+
+            let progress = ProcessingProgress {
+                stage: stage.clone(),
+                progress: 10.0,
+                current_file: None,
+                files_completed: 0,
+                total_files: 1,
+                eta_seconds: None,
+                speed: None,
+                bytes_written: None,
+            };
+            let event = ProgressEvent {
+                stage: stage.clone(),
+                percentage: 10.0,
+                message: String::new(),
+                current_file: None,
+                eta_seconds: None,
+                timeline: None,
+                skipped_inputs: None,
+                label: None,
+                notes: None,
+                cancel_reason: None,
+                metadata_pending: false,
+                warnings: None,
+                input_stats: None,
+            };
+            for json in [serde_json::to_value(&progress).unwrap(), serde_json::to_value(&event).unwrap()] {
+                assert_eq!(json["stage"], name);
+                assert_eq!(json.get("reason").and_then(|r| r.as_str()), reason.as_deref());
+            }
+
+            let parsed: ProcessingProgress =
+                serde_json::from_value(serde_json::to_value(&progress).unwrap()).unwrap();
+            assert_eq!(parsed.stage, stage);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Sampled logging of FFmpeg progress output
+//!
+//! FFmpeg writes a progress line several times a second; logging each one
+//! writes megabytes per book and slows slow disks. Progress-class lines are
+//! sampled to at most one per interval, with the lines skipped in between
+//! counted on the next one written. Warnings and errors are never held
+//! back, and write any pending progress line first so the log stays in
+//! order.
+
+use super::constants::PROGRESS_LOG_SAMPLE_INTERVAL_MS;
+use std::time::{Duration, Instant};
+
+/// Log target of sampled progress lines, so log filters can tell them apart
+pub const PROGRESS_LOG_TARGET: &str = "audiobook_boss::progress";
+
+/// Coalesces progress log lines of one FFmpeg run
+#[derive(Debug)]
+pub struct ProgressLog {
+    interval: Duration,
+    last_write: Option<Instant>,
+    /// Latest line held back, with the number of lines it stands for
+    pending: Option<(String, u64)>,
+}
+
+impl Default for ProgressLog {
+    fn default() -> Self {
+        Self::with_interval(Duration::from_millis(PROGRESS_LOG_SAMPLE_INTERVAL_MS))
+    }
+}
+
+impl ProgressLog {
+    /// Sampler writing at most one progress line per `interval`
+    pub fn with_interval(interval: Duration) -> Self {
+        Self { interval, last_write: None, pending: None }
+    }
+
+    /// Logs a progress line at debug level, unless one was written within the interval
+    pub fn progress(&mut self, line: &str) {
+        self.progress_at(line, Instant::now());
+    }
+
+    fn progress_at(&mut self, line: &str, now: Instant) {
+        let skipped = self.pending.take().map_or(0, |(_, count)| count);
+        let due = self.last_write.is_none_or(|last| now.duration_since(last) >= self.interval);
+        if due {
+            self.last_write = Some(now);
+            write_progress(line, skipped);
+        } else {
+            self.pending = Some((line.to_string(), skipped + 1));
+        }
+    }
+
+    /// Logs a warning immediately
+    pub fn warn(&mut self, message: &str) {
+        self.flush();
+        log::warn!(target: PROGRESS_LOG_TARGET, "{message}");
+    }
+
+    /// Logs an error immediately
+    pub fn error(&mut self, message: &str) {
+        self.flush();
+        log::error!(target: PROGRESS_LOG_TARGET, "{message}");
+    }
+
+    /// Writes the progress line still held back, e.g. when the run ends
+    pub fn flush(&mut self) {
+        if let Some((line, count)) = self.pending.take() {
+            self.last_write = Some(Instant::now());
+            write_progress(&line, count - 1);
+        }
+    }
+}
+
+fn write_progress(line: &str, skipped: u64) {
+    if skipped == 0 {
+        log::debug!(target: PROGRESS_LOG_TARGET, "{line}");
+    } else {
+        log::debug!(target: PROGRESS_LOG_TARGET, "{line} ({skipped} earlier lines coalesced)");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use std::sync::{Mutex, Once};
+
+    /// Records what reaches the logger facade under the progress target
+    struct RecordingLogger {
+        records: Mutex<Vec<(Level, String)>>,
+    }
+
+    impl Log for RecordingLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.target() == PROGRESS_LOG_TARGET
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                self.records.lock().unwrap().push((record.level(), record.args().to_string()));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: RecordingLogger = RecordingLogger { records: Mutex::new(Vec::new()) };
+    /// Tests share the global logger, so they run one at a time
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn take_records() -> Vec<(Level, String)> {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&LOGGER).unwrap();
+            log::set_max_level(LevelFilter::Trace);
+        });
+        std::mem::take(&mut *LOGGER.records.lock().unwrap())
+    }
+
+    #[test]
+    fn test_burst_is_sampled_per_interval() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        take_records();
+        let mut log = ProgressLog::default();
+        let start = Instant::now();
+        // 100 lines over 2.5 seconds
+        for i in 0..100u64 {
+            log.progress_at(&format!("out_time_ms={i}"), start + Duration::from_millis(i * 25));
+        }
+        log.flush();
+
+        let records = take_records();
+        let lines: Vec<&str> = records.iter().map(|(_, line)| line.as_str()).collect();
+        assert_eq!(lines, [
+            "out_time_ms=0",
+            "out_time_ms=40 (39 earlier lines coalesced)",
+            "out_time_ms=80 (39 earlier lines coalesced)",
+            "out_time_ms=99 (18 earlier lines coalesced)",
+        ]);
+        assert!(records.iter().all(|(level, _)| *level == Level::Debug));
+    }
+
+    #[test]
+    fn test_warnings_and_errors_flush_immediately() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        take_records();
+        let mut log = ProgressLog::with_interval(Duration::from_secs(3600));
+        log.progress("frame=1");
+        log.progress("frame=2");
+        log.progress("frame=3");
+        log.warn("Guessed channel layout");
+        log.error("Invalid data found when processing input");
+        log.error("Invalid data found when processing input");
+        log.flush();
+
+        let records = take_records();
+        assert_eq!(records, [
+            (Level::Debug, "frame=1".to_string()),
+            (Level::Debug, "frame=3 (1 earlier lines coalesced)".to_string()),
+            (Level::Warn, "Guessed channel layout".to_string()),
+            (Level::Error, "Invalid data found when processing input".to_string()),
+            (Level::Error, "Invalid data found when processing input".to_string()),
+        ]);
+    }
+}
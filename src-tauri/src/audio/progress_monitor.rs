@@ -4,11 +4,17 @@
 //! including FFmpeg process monitoring, progress calculation, display formatting,
 //! and process lifecycle management.
 
+use super::chapters::{generate_chapters, Chapter, ChapterOptions};
 use super::constants::*;
 use super::context::ProcessingContext;
+use super::display_format::DisplayFormatter;
+use super::AudioFile;
 use super::progress::ProgressEmitter;
+use super::progress_log::ProgressLog;
+use super::session::ProcessingSession;
+use super::telemetry::{unix_time_ms, TelemetrySampler};
 use crate::errors::{AppError, Result};
-use crate::ffmpeg::FFmpegError;
+use crate::ffmpeg::{locate_ffmpeg_with_origin, unknown_encoder_error, BinaryOrigin, FFmpegError};
 use std::io::{BufRead, BufReader};
 use std::process::{Command, Child};
 
@@ -16,6 +22,93 @@ use std::process::{Command, Child};
 const MIN_PROGRESS_UPDATES_FOR_ESTIMATION: i32 = 5;
 const MIN_PROGRESS_RATIO_FOR_ESTIMATION: f64 = 0.1;
 
+/// Which part of a merge an FFmpeg run covers, for progress reporting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressPass {
+    /// The whole merge in one run
+    Single,
+    /// One segment of a segmented merge, starting `offset` seconds into the output
+    Segment { offset: f32 },
+    /// Stream-copy join of segment outputs; only completion is reported
+    Join,
+}
+
+impl ProgressPass {
+    /// Maps a parsed FFmpeg progress value onto the whole merge
+    ///
+    /// Returns None for values this pass should not report.
+    pub fn adjust(self, progress_time: f32) -> Option<f32> {
+        let is_completion = progress_time == PROGRESS_COMPLETE;
+        match self {
+            ProgressPass::Single => Some(progress_time),
+            ProgressPass::Segment { .. } if is_completion => None,
+            ProgressPass::Segment { offset } => Some(offset + progress_time),
+            ProgressPass::Join if is_completion => Some(progress_time),
+            ProgressPass::Join => None,
+        }
+    }
+}
+
+/// Names the input (or chapter) playing at a point of the merged output
+///
+/// Converting progress maps FFmpeg's output time onto these spans to show
+/// what is being encoded: the chapter title when chapters are on, the
+/// input's filename otherwise.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputLabels {
+    /// End of each span in seconds from the start of the book, ascending
+    ends: Vec<f64>,
+    /// Label of each span
+    labels: Vec<String>,
+    /// Whether the labels are chapter titles
+    chapters: bool,
+}
+
+impl InputLabels {
+    /// Labels for the merge inputs, from `chapters` when they are enabled
+    pub fn for_files(files: &[AudioFile], chapters: Option<&ChapterOptions>) -> Self {
+        if let Some(options) = chapters {
+            match generate_chapters(files, options) {
+                Ok(chapters) => return Self::for_chapters(&chapters),
+                Err(e) => log::warn!("Progress falls back to filenames, chapters unavailable: {e}"),
+            }
+        }
+        let mut end = 0.0;
+        let (ends, labels) = files.iter()
+            .filter(|file| file.is_valid)
+            .map(|file| {
+                end += file.duration.unwrap_or(0.0).max(0.0);
+                let name = file.path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+                (end, name)
+            })
+            .unzip();
+        Self { ends, labels, chapters: false }
+    }
+
+    /// Labels from chapters already placed, such as an explicit chapter list
+    pub fn for_chapters(chapters: &[Chapter]) -> Self {
+        Self {
+            ends: chapters.iter().map(|chapter| chapter.end_secs).collect(),
+            labels: chapters.iter().map(|chapter| chapter.title.clone()).collect(),
+            chapters: true,
+        }
+    }
+
+    /// Label of the span containing `secs`; times past the end map to the last span
+    pub fn label_at(&self, secs: f64) -> Option<&str> {
+        let index = self.ends.partition_point(|&end| end <= secs).min(self.labels.len().checked_sub(1)?);
+        Some(&self.labels[index])
+    }
+
+    /// Message and current file for converting progress at `secs`
+    fn converting_status(&self, secs: f64) -> (String, Option<String>) {
+        match self.label_at(secs) {
+            Some(title) if self.chapters => (format!("Encoding: {title}"), Some(title.to_string())),
+            label => ("Converting and merging audio files...".to_string(), label.map(str::to_string)),
+        }
+    }
+}
+
 /// Process execution state for tracking progress
 pub struct ProcessExecution {
     pub child: Child,
@@ -23,6 +116,50 @@ pub struct ProcessExecution {
     pub last_progress_time: f32,
     pub estimated_total_time: f64,
     pub progress_count: i32,
+    pub pass: ProgressPass,
+    pub labels: InputLabels,
+    pub log: ProgressLog,
+    pub throughput: Throughput,
+    pub telemetry: TelemetrySampler,
+    /// Where the FFmpeg binary came from, when it could be located
+    pub ffmpeg_origin: Option<BinaryOrigin>,
+}
+
+/// Encoder speed, bitrate and output size seen so far in one FFmpeg run
+///
+/// `-progress` reports speed and size on their own lines, separate from
+/// `out_time_us`, so they are kept here between progress updates. Speed is
+/// smoothed because single readings swing widely at the start of a run.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Throughput {
+    /// Smoothed speed as a multiple of realtime
+    pub speed: Option<f64>,
+    /// Bytes written to the output so far
+    pub bytes_written: Option<u64>,
+    /// Output bitrate in kbit/s from the latest progress block
+    pub bitrate_kbps: Option<f64>,
+}
+
+impl Throughput {
+    /// Takes in a speed, size or bitrate line; returns whether anything changed
+    pub fn observe(&mut self, line: &str) -> bool {
+        if let Some(reading) = parse_speed_multiplier(line).filter(|speed| *speed > 0.0) {
+            self.speed = Some(match self.speed {
+                Some(speed) => speed + PROGRESS_SPEED_SMOOTHING * (reading - speed),
+                None => reading,
+            });
+            return true;
+        }
+        if let Some(bytes) = parse_total_size(line) {
+            self.bytes_written = Some(bytes);
+            return true;
+        }
+        if let Some(bitrate) = parse_bitrate_kbps(line) {
+            self.bitrate_kbps = Some(bitrate);
+            return true;
+        }
+        false
+    }
 }
 
 /// Sets up FFmpeg process and initial state
@@ -33,7 +170,7 @@ pub fn setup_process_execution(
     let child = cmd.spawn()
         .map_err(|_| AppError::FFmpeg(FFmpegError::ExecutionFailed("Failed to start FFmpeg".to_string())))?;
     
-    let emitter = ProgressEmitter::new(context.window.clone());
+    let emitter = ProgressEmitter::for_context(context);
     
     Ok(ProcessExecution {
         child,
@@ -41,6 +178,12 @@ pub fn setup_process_execution(
         last_progress_time: 0.0,
         estimated_total_time: 0.0,
         progress_count: 0,
+        pass: ProgressPass::Single,
+        labels: InputLabels::default(),
+        log: ProgressLog::default(),
+        throughput: Throughput::default(),
+        telemetry: TelemetrySampler::default(),
+        ffmpeg_origin: locate_ffmpeg_with_origin().ok().map(|(_, origin)| origin),
     })
 }
 
@@ -70,31 +213,50 @@ pub fn handle_progress_line(
     _context: &ProcessingContext,
     total_duration: f64,
 ) -> Result<()> {
-    let speed_multiplier = parse_speed_multiplier(line);
+    if execution.throughput.observe(line) {
+        execution.emitter.record_throughput(execution.throughput.speed, execution.throughput.bytes_written);
+    }
+    if line.starts_with("progress=") {
+        if let Some(point) = execution.telemetry.sample(unix_time_ms(), &execution.throughput) {
+            execution.emitter.emit_telemetry(point);
+        }
+    }
 
     // Parse progress from FFmpeg output and emit events
-    if let Some(progress_time) = crate::audio::progress::parse_ffmpeg_progress(line) {
+    let progress_time = crate::audio::progress::parse_ffmpeg_progress(line)
+        .and_then(|time| execution.pass.adjust(time));
+    if let Some(progress_time) = progress_time {
         process_progress_update_context(
             progress_time,
             &mut execution.last_progress_time,
             &mut execution.progress_count,
             &mut execution.estimated_total_time,
             total_duration,
-            speed_multiplier,
+            execution.throughput.speed,
+            &execution.labels,
             &execution.emitter,
         )?;
     }
     
+    if let Some(error) = execution.ffmpeg_origin.and_then(|origin| unknown_encoder_error(line, origin)) {
+        execution.log.error(&format!("FFmpeg critical error: {line}"));
+        return Err(AppError::FFmpeg(error));
+    }
+
     // Check for errors (but ignore case-insensitive matches in file paths)
     if (line.contains("Error") || line.contains("error")) && 
        !line.contains("Output") && !line.contains("Input") {
-        log::error!("FFmpeg error line: {line}");
+        execution.log.error(&format!("FFmpeg error line: {line}"));
         if line.contains("No such file") || line.contains("Invalid data") {
-            log::error!("FFmpeg critical error: {line}");
+            execution.log.error(&format!("FFmpeg critical error: {line}"));
             return Err(AppError::FFmpeg(FFmpegError::ExecutionFailed(
                 format!("FFmpeg failed to process audio files: {line}")
             )));
         }
+    } else if line.contains("Warning") || line.contains("warning") {
+        execution.log.warn(&format!("FFmpeg warning: {line}"));
+    } else {
+        execution.log.progress(line);
     }
     
     Ok(())
@@ -105,10 +267,12 @@ pub fn finalize_process_execution(
     mut execution: ProcessExecution,
     context: &ProcessingContext,
 ) -> Result<()> {
+    execution.log.flush();
+    
     // Check if process was cancelled before waiting
     if context.is_cancelled() {
         log::info!("Processing cancelled before FFmpeg completion");
-        return Err(AppError::InvalidInput("Processing was cancelled by user before FFmpeg completion".to_string()));
+        return Err(context.cancellation_error());
     }
     
     // Wait for completion only if not cancelled
@@ -123,9 +287,17 @@ pub fn finalize_process_execution(
         let exit_code = status.code()
             .map(|c| format!(" (exit code: {c})"))
             .unwrap_or_default();
-        let msg = format!("FFmpeg process failed during audio conversion{exit_code}");
-        log::error!("{msg}");
-        return Err(AppError::FFmpeg(FFmpegError::ExecutionFailed(msg)));
+        let message = format!("FFmpeg process failed during audio conversion{exit_code}");
+        return Err(AppError::FFmpeg(match execution.ffmpeg_origin {
+            Some(origin) => {
+                log::error!("{message} ({origin} FFmpeg)");
+                FFmpegError::ProcessFailed { message, origin }
+            }
+            None => {
+                log::error!("{message}");
+                FFmpegError::ExecutionFailed(message)
+            }
+        }));
     }
     
     Ok(())
@@ -136,7 +308,16 @@ pub fn check_cancellation_and_kill_context(
     context: &ProcessingContext,
     child: &mut Child,
 ) -> Result<()> {
-    if context.is_cancelled() {
+    check_cancellation_and_kill_session(&context.session, child)
+}
+
+/// Kills the child once the session is cancelled and returns the session's
+/// cancellation error, so the reason reaches the final error
+fn check_cancellation_and_kill_session(
+    session: &ProcessingSession,
+    child: &mut Child,
+) -> Result<()> {
+    if session.is_cancelled() {
         log::debug!("Cancellation detected, killing FFmpeg process...");
         let _ = child.kill();
         
@@ -151,12 +332,13 @@ pub fn check_cancellation_and_kill_context(
                 log::warn!("FFmpeg process may not have terminated cleanly");
             }
         }
-        return Err(AppError::InvalidInput("Processing was cancelled".to_string()));
+        return Err(session.cancellation_error());
     }
     Ok(())
 }
 
 /// Processes progress update and emits events (context-based)
+#[allow(clippy::too_many_arguments)]
 pub fn process_progress_update_context(
     progress_time: f32,
     last_progress_time: &mut f32,
@@ -164,6 +346,7 @@ pub fn process_progress_update_context(
     estimated_total_time: &mut f64,
     total_duration: f64,
     speed_multiplier: Option<f64>,
+    labels: &InputLabels,
     emitter: &ProgressEmitter,
 ) -> Result<()> {
     if progress_time == PROGRESS_COMPLETE {
@@ -188,10 +371,11 @@ pub fn process_progress_update_context(
             None
         };
         
+        let (message, current_file) = labels.converting_status(f64::from(progress_time));
         emitter.emit_converting_progress(
             progress_percentage.min(PROGRESS_CONVERTING_MAX as f64) as f32,
-            "Converting and merging audio files...",
-            None,
+            &message,
+            current_file,
             eta_seconds,
         );
     }
@@ -241,9 +425,7 @@ pub fn calculate_and_display_progress(
         let eta_text = if let Some(speed) = speed_multiplier {
             let remaining_time = (estimated_total_time - progress_time as f64) / speed;
             if remaining_time > 0.0 {
-                let minutes = (remaining_time / SECONDS_PER_MINUTE) as u32;
-                let seconds = (remaining_time % SECONDS_PER_MINUTE) as u32;
-                format!(" [ETA: {minutes}m {seconds}s]")
+                format!(" [ETA: {}]", DisplayFormatter::current().duration(remaining_time.floor()))
             } else {
                 String::new()
             }
@@ -267,8 +449,8 @@ pub fn display_progress_with_duration(
 ) -> f64 {
     let percentage = PROGRESS_CONVERTING_START as f64 + (file_progress * PROGRESS_RANGE_MULTIPLIER);
     
-    eprint!("\rConverting: {:.1}% ({:.1}s / {:.1}s){}{}", 
-        file_progress * 100.0, 
+    eprint!("\rConverting: {} ({:.1}s / {:.1}s){}{}", 
+        DisplayFormatter::current().percent(file_progress * 100.0), 
         progress_time, 
         estimated_total_time,
         speed_text,
@@ -299,12 +481,23 @@ pub fn parse_speed_multiplier(line: &str) -> Option<f64> {
     }
 }
 
+/// Parses the output size from a `-progress` `total_size=` line
+pub fn parse_total_size(line: &str) -> Option<u64> {
+    line.trim().strip_prefix("total_size=")?.parse().ok()
+}
+
+/// Parses the bitrate from a `-progress` `bitrate=  64.1kbits/s` line
+pub fn parse_bitrate_kbps(line: &str) -> Option<f64> {
+    line.trim().strip_prefix("bitrate=")?.trim().strip_suffix("kbits/s")?.parse().ok()
+}
+
 // ADAPTER FUNCTIONS for backward compatibility
 
 /// Processes progress update and emits events (ADAPTER)
 /// 
 /// ADAPTER FUNCTION: Maintains backward compatibility by converting parameters
 /// to use the new ProgressEmitter approach internally.
+#[cfg(feature = "gui")]
 #[deprecated = "Use process_progress_update_context for new code - this adapter maintains compatibility"]
 #[allow(dead_code)]
 pub fn process_progress_update(
@@ -316,7 +509,7 @@ pub fn process_progress_update(
     speed_multiplier: Option<f64>,
     window: &tauri::Window,
 ) -> Result<()> {
-    let emitter = ProgressEmitter::new(window.clone());
+    let emitter = ProgressEmitter::new(std::sync::Arc::new(window.clone()));
     process_progress_update_context(
         progress_time,
         last_progress_time,
@@ -324,6 +517,7 @@ pub fn process_progress_update(
         estimated_total_time,
         total_duration,
         speed_multiplier,
+        &InputLabels::default(),
         &emitter,
     )
 }
@@ -332,6 +526,7 @@ pub fn process_progress_update(
 /// 
 /// ADAPTER FUNCTION: Maintains backward compatibility by converting parameters
 /// to use the new context-based approach internally.
+#[cfg(feature = "gui")]
 #[deprecated = "Use check_cancellation_and_kill_context for new code - this adapter maintains compatibility"]
 #[allow(dead_code)]
 pub fn check_cancellation_and_kill(
@@ -360,3 +555,107 @@ pub fn check_cancellation_and_kill(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_pass_reports_everything() {
+        assert_eq!(ProgressPass::Single.adjust(12.5), Some(12.5));
+        assert_eq!(ProgressPass::Single.adjust(PROGRESS_COMPLETE), Some(PROGRESS_COMPLETE));
+    }
+
+    #[test]
+    fn test_segment_pass_offsets_time_and_hides_completion() {
+        let pass = ProgressPass::Segment { offset: 300.0 };
+        assert_eq!(pass.adjust(12.5), Some(312.5));
+        assert_eq!(pass.adjust(PROGRESS_COMPLETE), None);
+    }
+
+    #[test]
+    fn test_join_pass_only_reports_completion() {
+        assert_eq!(ProgressPass::Join.adjust(12.5), None);
+        assert_eq!(ProgressPass::Join.adjust(PROGRESS_COMPLETE), Some(PROGRESS_COMPLETE));
+    }
+
+    fn labelled_files() -> Vec<AudioFile> {
+        [("/books/01 - Opening.mp3", 100.0), ("/books/02 - The Long Night.mp3", 250.5), ("/books/03 - Dawn.mp3", 50.0)]
+            .into_iter()
+            .map(|(path, duration)| {
+                let mut file = AudioFile::new(path.into());
+                file.duration = Some(duration);
+                file.is_valid = true;
+                file
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_filename_labels_at_boundaries() {
+        let labels = InputLabels::for_files(&labelled_files(), None);
+        let at = |secs| labels.converting_status(secs);
+        assert_eq!(at(0.0), ("Converting and merging audio files...".to_string(), Some("01 - Opening.mp3".to_string())));
+        assert_eq!(at(99.9).1.as_deref(), Some("01 - Opening.mp3"));
+        assert_eq!(at(100.0).1.as_deref(), Some("02 - The Long Night.mp3"));
+        assert_eq!(at(350.5).1.as_deref(), Some("03 - Dawn.mp3"));
+        assert_eq!(at(1000.0).1.as_deref(), Some("03 - Dawn.mp3"));
+        assert_eq!(InputLabels::default().converting_status(5.0).1, None);
+    }
+
+    #[test]
+    fn test_chapter_labels_at_boundaries() {
+        let mut files = labelled_files();
+        files[1].options.chapter_title = Some("The Long Night".to_string());
+        let options = ChapterOptions { title_template: Some("Chapter {n}".to_string()), ..Default::default() };
+        let labels = InputLabels::for_files(&files, Some(&options));
+        let titles: Vec<String> = [0.0, 99.9, 100.0, 350.4, 350.5].into_iter()
+            .map(|secs| labels.converting_status(secs).0)
+            .collect();
+        assert_eq!(titles, [
+            "Encoding: Chapter 1",
+            "Encoding: Chapter 1",
+            "Encoding: The Long Night",
+            "Encoding: The Long Night",
+            "Encoding: Chapter 3",
+        ]);
+        assert_eq!(labels.converting_status(120.0).1.as_deref(), Some("The Long Night"));
+
+        // Segment passes report whole-book times, so offsets land in the right chapter
+        let time = ProgressPass::Segment { offset: 300.0 }.adjust(60.0).unwrap();
+        assert_eq!(labels.label_at(f64::from(time)), Some("Chapter 3"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cancel_reason_reaches_monitor_error() {
+        use crate::errors::CancelReason;
+        let session = ProcessingSession::new();
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        assert!(check_cancellation_and_kill_session(&session, &mut child).is_ok());
+
+        session.cancel(CancelReason::Watchdog);
+        let error = check_cancellation_and_kill_session(&session, &mut child).unwrap_err();
+        assert!(matches!(error, AppError::Cancelled(CancelReason::Watchdog)));
+        assert!(child.try_wait().unwrap().is_some(), "child should be killed");
+    }
+
+    #[test]
+    fn test_throughput_smooths_speed_and_tracks_size() {
+        let mut throughput = Throughput::default();
+        assert!(!throughput.observe("out_time_us=1000000"));
+        assert!(!throughput.observe("total_size=N/A"));
+        assert!(!throughput.observe("speed=N/A"));
+        assert!(!throughput.observe("bitrate=N/A"));
+        assert_eq!(throughput, Throughput::default());
+
+        assert!(throughput.observe("speed=10x"));
+        assert_eq!(throughput.speed, Some(10.0));
+        assert!(throughput.observe("speed=20.0x"));
+        assert_eq!(throughput.speed, Some(13.0));
+        assert!(throughput.observe("total_size=4194304"));
+        assert_eq!(throughput.bytes_written, Some(4_194_304));
+        assert!(throughput.observe("bitrate=  64.1kbits/s"));
+        assert_eq!(throughput.bitrate_kbps, Some(64.1));
+    }
+}
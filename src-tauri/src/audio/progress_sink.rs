@@ -0,0 +1,85 @@
+//! Destination for the events a processing run emits
+//!
+//! The pipeline emits progress, completion and verification events through
+//! a `ProgressSink` so it runs with or without the GUI. The Tauri window and
+//! app handle are sinks when the `gui` feature is on; library consumers pass
+//! their own sink or `NullSink`.
+
+use crate::errors::{AppError, Result};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Receives events by name with a JSON payload
+pub trait ProgressSink: Send + Sync + std::fmt::Debug {
+    /// Delivers one event; an error means the event was dropped
+    fn emit_value(&self, event: &str, payload: serde_json::Value) -> std::result::Result<(), String>;
+}
+
+impl dyn ProgressSink {
+    /// Serializes `payload` and delivers it as `event`
+    pub fn emit<S: Serialize>(&self, event: &str, payload: &S) -> Result<()> {
+        let value = serde_json::to_value(payload)
+            .map_err(|e| AppError::General(format!("Failed to serialize event '{event}': {e}")))?;
+        self.emit_value(event, value)
+            .map_err(|e| AppError::General(format!("Failed to emit event '{event}': {e}")))
+    }
+}
+
+/// Sink that drops every event
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullSink;
+
+impl ProgressSink for NullSink {
+    fn emit_value(&self, _event: &str, _payload: serde_json::Value) -> std::result::Result<(), String> {
+        Ok(())
+    }
+}
+
+/// A shared `NullSink`
+pub fn null_sink() -> Arc<dyn ProgressSink> {
+    Arc::new(NullSink)
+}
+
+#[cfg(feature = "gui")]
+impl ProgressSink for tauri::Window {
+    fn emit_value(&self, event: &str, payload: serde_json::Value) -> std::result::Result<(), String> {
+        tauri::Emitter::emit(self, event, payload).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(feature = "gui")]
+impl ProgressSink for tauri::AppHandle {
+    fn emit_value(&self, event: &str, payload: serde_json::Value) -> std::result::Result<(), String> {
+        tauri::Emitter::emit(self, event, payload).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct RecordingSink {
+        events: Mutex<Vec<(String, serde_json::Value)>>,
+    }
+
+    impl ProgressSink for RecordingSink {
+        fn emit_value(&self, event: &str, payload: serde_json::Value) -> std::result::Result<(), String> {
+            self.events.lock().map_err(|e| e.to_string())?.push((event.to_string(), payload));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_emit_serializes_payload() {
+        let recording = Arc::new(RecordingSink::default());
+        let sink: Arc<dyn ProgressSink> = recording.clone();
+        sink.emit("processing-progress", &serde_json::json!({ "percentage": 50.0 }))
+            .expect("emit");
+        let events = recording.events.lock().expect("lock");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, "processing-progress");
+        assert_eq!(events[0].1["percentage"], 50.0);
+    }
+}
@@ -0,0 +1,225 @@
+//! Latest progress retained per session
+//!
+//! Live progress goes out as events, so a webview that reloads mid-run
+//! misses everything before the reload. Each session keeps its latest
+//! `ProcessingProgress`, a bounded list of warnings and the recent
+//! telemetry points here; a late subscriber gets them (plus the stage
+//! timeline) as one snapshot and then follows the live events.
+
+use super::constants::{MAX_SNAPSHOT_WARNINGS, MAX_TRACKED_TIMELINES, TELEMETRY_MAX_POINTS};
+use super::telemetry::TelemetryPoint;
+use super::timeline::{session_timeline, StageTiming};
+use super::{ProcessingProgress, ProcessingStage};
+use crate::errors::{AppError, Result};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Progress state kept by a running session
+#[derive(Debug, Default)]
+pub struct SessionProgress {
+    latest: Option<ProcessingProgress>,
+    total_files: usize,
+    speed: Option<f64>,
+    bytes_written: Option<u64>,
+    warnings: VecDeque<String>,
+    telemetry: VecDeque<TelemetryPoint>,
+}
+
+impl SessionProgress {
+    /// Sets the input count reported in later progress updates
+    pub fn set_total_files(&mut self, total_files: usize) {
+        self.total_files = total_files;
+        if let Some(latest) = self.latest.as_mut() {
+            latest.total_files = total_files;
+        }
+    }
+
+    /// Sets the encoder speed and output size reported in later progress updates
+    pub fn set_throughput(&mut self, speed: Option<f64>, bytes_written: Option<u64>) {
+        self.speed = speed;
+        self.bytes_written = bytes_written;
+        if let Some(latest) = self.latest.as_mut() {
+            latest.speed = speed;
+            latest.bytes_written = bytes_written;
+        }
+    }
+
+    /// Replaces the latest progress with the values of an emitted event
+    pub fn record(
+        &mut self,
+        stage: ProcessingStage,
+        progress: f32,
+        current_file: Option<String>,
+        eta_seconds: Option<f64>,
+    ) {
+        let files_completed = match stage {
+            ProcessingStage::Completed => self.total_files,
+            _ => 0,
+        };
+        self.latest = Some(ProcessingProgress {
+            stage,
+            progress,
+            current_file,
+            files_completed,
+            total_files: self.total_files,
+            eta_seconds,
+            speed: self.speed,
+            bytes_written: self.bytes_written,
+        });
+    }
+
+    /// Adds a warning, dropping the oldest beyond `MAX_SNAPSHOT_WARNINGS`
+    pub fn record_warning(&mut self, warning: String) {
+        if self.warnings.len() >= MAX_SNAPSHOT_WARNINGS {
+            self.warnings.pop_front();
+        }
+        self.warnings.push_back(warning);
+    }
+
+    /// Adds a telemetry point, dropping the oldest beyond `TELEMETRY_MAX_POINTS`
+    pub fn record_telemetry(&mut self, point: TelemetryPoint) {
+        if self.telemetry.len() >= TELEMETRY_MAX_POINTS {
+            self.telemetry.pop_front();
+        }
+        self.telemetry.push_back(point);
+    }
+
+    /// Latest recorded progress, if any event has been emitted
+    pub fn latest(&self) -> Option<ProcessingProgress> {
+        self.latest.clone()
+    }
+}
+
+/// Progress shared between a session, its emitters and the registry
+pub type SharedProgress = Arc<Mutex<SessionProgress>>;
+
+/// Everything a late subscriber needs to rebuild the progress view
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressSnapshot {
+    pub session_id: String,
+    /// None until the session has emitted its first progress event
+    pub progress: Option<ProcessingProgress>,
+    pub timeline: Vec<StageTiming>,
+    pub warnings: Vec<String>,
+    /// Recent telemetry points, oldest first
+    pub telemetry: Vec<TelemetryPoint>,
+}
+
+/// Most recent session progress, newest last
+static RECENT_PROGRESS: OnceLock<Mutex<VecDeque<(String, SharedProgress)>>> = OnceLock::new();
+
+/// Makes a session's progress queryable by ID, evicting the oldest
+pub fn register_progress(session_id: String, progress: SharedProgress) {
+    let Ok(mut recent) = RECENT_PROGRESS.get_or_init(Default::default).lock() else { return };
+    recent.retain(|(id, _)| *id != session_id);
+    if recent.len() >= MAX_TRACKED_TIMELINES {
+        recent.pop_front();
+    }
+    recent.push_back((session_id, progress));
+}
+
+/// Builds a snapshot for a session, or the most recent one when None
+pub fn progress_snapshot(session_id: Option<&str>) -> Result<ProgressSnapshot> {
+    let (session_id, progress) = {
+        let recent = RECENT_PROGRESS.get_or_init(Default::default).lock()
+            .map_err(|_| AppError::General("Progress registry poisoned".to_string()))?;
+        let entry = match session_id {
+            Some(id) => recent.iter().rev().find(|(entry_id, _)| entry_id == id),
+            None => recent.back(),
+        };
+        let (id, progress) = entry.ok_or_else(|| AppError::InvalidInput(match session_id {
+            Some(id) => format!("No progress recorded for session: {id}"),
+            None => "No processing session has run yet".to_string(),
+        }))?;
+        (id.clone(), Arc::clone(progress))
+    };
+
+    let (latest, warnings, telemetry) = {
+        let progress = progress.lock()
+            .map_err(|_| AppError::General("Session progress poisoned".to_string()))?;
+        (progress.latest(), progress.warnings.iter().cloned().collect(), progress.telemetry.iter().cloned().collect())
+    };
+    let timeline = session_timeline(Some(&session_id)).unwrap_or_default();
+
+    Ok(ProgressSnapshot { session_id, progress: latest, timeline, warnings, telemetry })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::session::ProcessingSession;
+    use crate::audio::timeline::TimelineStage;
+
+    #[test]
+    fn test_late_subscriber_gets_in_flight_state() {
+        let session = ProcessingSession::new();
+        session.start_stage(TimelineStage::Analyzing);
+        session.start_stage(TimelineStage::Converting);
+        if let Ok(mut progress) = session.progress().lock() {
+            progress.set_total_files(3);
+            progress.record_warning("Skipping 1 invalid input file(s)".to_string());
+            progress.record(ProcessingStage::Converting, 5.0, None, None);
+            progress.set_throughput(Some(12.5), Some(1024));
+            progress.record(ProcessingStage::Converting, 42.5, Some("02.mp3".to_string()), Some(90.0));
+        }
+
+        let snapshot = progress_snapshot(Some(&session.id())).unwrap();
+        assert_eq!(snapshot.session_id, session.id());
+        let latest = snapshot.progress.unwrap();
+        assert_eq!(latest.stage, ProcessingStage::Converting);
+        assert_eq!(latest.progress, 42.5);
+        assert_eq!(latest.current_file.as_deref(), Some("02.mp3"));
+        assert_eq!(latest.total_files, 3);
+        assert_eq!(latest.eta_seconds, Some(90.0));
+        assert_eq!((latest.speed, latest.bytes_written), (Some(12.5), Some(1024)));
+        assert_eq!(snapshot.warnings, vec!["Skipping 1 invalid input file(s)".to_string()]);
+        let stages: Vec<_> = snapshot.timeline.iter().map(|t| t.stage).collect();
+        assert_eq!(stages, vec![TimelineStage::Analyzing, TimelineStage::Converting]);
+    }
+
+    #[test]
+    fn test_snapshot_before_first_event() {
+        let session = ProcessingSession::new();
+        let snapshot = progress_snapshot(Some(&session.id())).unwrap();
+        assert!(snapshot.progress.is_none());
+        assert!(snapshot.warnings.is_empty());
+        assert!(snapshot.telemetry.is_empty());
+        assert!(progress_snapshot(Some("no-such-session")).is_err());
+    }
+
+    #[test]
+    fn test_warnings_are_bounded() {
+        let mut progress = SessionProgress::default();
+        for i in 0..MAX_SNAPSHOT_WARNINGS + 5 {
+            progress.record_warning(format!("warning {i}"));
+        }
+        assert_eq!(progress.warnings.len(), MAX_SNAPSHOT_WARNINGS);
+        assert_eq!(progress.warnings.front().map(String::as_str), Some("warning 5"));
+    }
+
+    #[test]
+    fn test_telemetry_ring_is_bounded() {
+        let session = ProcessingSession::new();
+        if let Ok(mut progress) = session.progress().lock() {
+            for i in 0..TELEMETRY_MAX_POINTS as u64 + 10 {
+                let point = TelemetryPoint { timestamp_ms: i * 2_000, speed: Some(20.0), bitrate_kbps: Some(64.0), bytes_written: Some(i * 16_000) };
+                progress.record_telemetry(point);
+            }
+        }
+
+        let telemetry = progress_snapshot(Some(&session.id())).unwrap().telemetry;
+        assert_eq!(telemetry.len(), TELEMETRY_MAX_POINTS);
+        assert_eq!(telemetry.first().map(|point| point.timestamp_ms), Some(20_000));
+        assert_eq!(telemetry.last().map(|point| point.timestamp_ms), Some((TELEMETRY_MAX_POINTS as u64 + 9) * 2_000));
+    }
+
+    #[test]
+    fn test_completed_counts_every_file() {
+        let mut progress = SessionProgress::default();
+        progress.set_total_files(4);
+        progress.record(ProcessingStage::Completed, 100.0, None, None);
+        assert_eq!(progress.latest().unwrap().files_completed, 4);
+    }
+}
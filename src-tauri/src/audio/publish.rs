@@ -0,0 +1,311 @@
+//! Moving the finished output into place
+//!
+//! A rename is instant, but the temp directory is often on another volume
+//! than the output (an external drive, a NAS), where renaming fails. The
+//! fallback copies in chunks to a staging file next to the destination,
+//! reporting bytes copied and checking for cancellation between chunks,
+//! then renames the staging file into place. The destination therefore
+//! never holds a partial book, and a cancelled copy leaves nothing behind.
+//!
+//! Network shares (SMB in particular) can refuse or botch renaming a file
+//! that was just written and may still be open on the server. For those
+//! destinations the output is copied once, straight to its final name,
+//! with no rename on the share; a cancelled or failed copy is still removed.
+
+use super::constants::{NETWORK_FILESYSTEMS, PUBLISH_COPY_CHUNK_BYTES, PUBLISH_STAGING_EXTENSION};
+use crate::errors::{AppError, Result};
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// How the finished output is moved to its destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize)]
+pub enum PublishStrategy {
+    /// Rename into place, copying to a staging file across volumes
+    #[default]
+    Rename,
+    /// Copy once to the final name, never renaming on the destination
+    CopyOnce,
+}
+
+impl PublishStrategy {
+    /// Strategy for a destination on `filesystem`
+    pub fn for_filesystem(filesystem: Option<&str>) -> Self {
+        match filesystem {
+            Some(filesystem) if is_network_filesystem(filesystem) => Self::CopyOnce,
+            _ => Self::Rename,
+        }
+    }
+
+    /// Strategy for `output_path` on `filesystem`; UNC paths are always shares
+    pub fn for_destination(output_path: &Path, filesystem: Option<&str>) -> Self {
+        if is_unc_path(output_path) {
+            return Self::CopyOnce;
+        }
+        Self::for_filesystem(filesystem)
+    }
+}
+
+/// Whether `filesystem` is a network share (`cifs`, `smbfs`, `nfs`, ...)
+pub fn is_network_filesystem(filesystem: &str) -> bool {
+    NETWORK_FILESYSTEMS.contains(&filesystem.to_ascii_lowercase().as_str())
+}
+
+/// Whether `path` is a Windows UNC path (`\\server\share\...`)
+fn is_unc_path(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    if let Some(verbatim) = path.strip_prefix(r"\\?\") {
+        return verbatim.get(..4).is_some_and(|prefix| prefix.eq_ignore_ascii_case(r"UNC\"));
+    }
+    path.starts_with(r"\\") && !path.starts_with(r"\\.\")
+}
+
+/// How a chunked copy ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyOutcome {
+    /// Every byte was copied
+    Completed(u64),
+    /// Cancelled between chunks; the partial destination was removed
+    Cancelled,
+}
+
+/// Copies `src` to `dst` in `chunk_size` pieces
+///
+/// `on_progress` gets the bytes copied so far and the total after every
+/// chunk; `is_cancelled` is checked before each chunk.
+pub fn copy_in_chunks<P, C>(
+    src: &Path,
+    dst: &Path,
+    chunk_size: usize,
+    mut on_progress: P,
+    is_cancelled: C,
+) -> Result<CopyOutcome>
+where
+    P: FnMut(u64, u64),
+    C: Fn() -> bool,
+{
+    let mut reader = File::open(src)?;
+    let total = reader.metadata()?.len();
+    let mut writer = File::create(dst)?;
+    let result = copy_loop(&mut reader, &mut writer, total, chunk_size.max(1), &mut on_progress, &is_cancelled);
+    drop(writer);
+    if !matches!(result, Ok(CopyOutcome::Completed(_))) {
+        if let Err(e) = std::fs::remove_file(dst) {
+            log::warn!("Cannot remove partial copy {}: {e}", dst.display());
+        }
+    }
+    result
+}
+
+fn copy_loop(
+    reader: &mut File,
+    writer: &mut File,
+    total: u64,
+    chunk_size: usize,
+    on_progress: &mut impl FnMut(u64, u64),
+    is_cancelled: &impl Fn() -> bool,
+) -> Result<CopyOutcome> {
+    let mut buffer = vec![0u8; chunk_size];
+    let mut copied = 0u64;
+    loop {
+        if is_cancelled() {
+            return Ok(CopyOutcome::Cancelled);
+        }
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+        on_progress(copied, total);
+    }
+    writer.sync_all()?;
+    Ok(CopyOutcome::Completed(copied))
+}
+
+/// Staging file the fallback copy writes to: `Book.m4b.partial`
+pub fn staging_path(final_path: &Path) -> PathBuf {
+    let mut name = final_path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{PUBLISH_STAGING_EXTENSION}"));
+    final_path.with_file_name(name)
+}
+
+/// Moves `temp_output` to `final_path` with `strategy`, copying across volumes when needed
+///
+/// Returns false when a copy was cancelled; `temp_output` is then left in
+/// place for the temp cleanup.
+pub fn publish_output<P, C>(
+    temp_output: &Path,
+    final_path: &Path,
+    strategy: PublishStrategy,
+    on_progress: P,
+    is_cancelled: C,
+) -> Result<bool>
+where
+    P: FnMut(u64, u64),
+    C: Fn() -> bool,
+{
+    if let Some(parent) = final_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AppError::FileValidation(
+                format!("Cannot create output directory: {e}")
+            ))?;
+    }
+
+    if strategy == PublishStrategy::CopyOnce {
+        log::info!("Output is on a network share, copying {} once without renaming", final_path.display());
+        return copy_and_remove_temp(temp_output, final_path, on_progress, is_cancelled);
+    }
+
+    match std::fs::rename(temp_output, final_path) {
+        Ok(()) => return Ok(true),
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            log::info!("Output is on another volume, copying {}", final_path.display());
+        }
+        Err(e) => return Err(AppError::FileValidation(
+            format!("Cannot move file to final location: {e}")
+        )),
+    }
+
+    let staging = staging_path(final_path);
+    let outcome = copy_in_chunks(temp_output, &staging, PUBLISH_COPY_CHUNK_BYTES, on_progress, is_cancelled)
+        .map_err(|e| AppError::FileValidation(format!("Cannot copy file to final location: {e}")))?;
+    if outcome == CopyOutcome::Cancelled {
+        return Ok(false);
+    }
+    std::fs::rename(&staging, final_path)
+        .map_err(|e| AppError::FileValidation(
+            format!("Cannot move file to final location: {e}")
+        ))?;
+    remove_temp_output(temp_output);
+    Ok(true)
+}
+
+/// Copies `temp_output` straight to `final_path`, then removes the temp copy
+fn copy_and_remove_temp<P, C>(temp_output: &Path, final_path: &Path, on_progress: P, is_cancelled: C) -> Result<bool>
+where
+    P: FnMut(u64, u64),
+    C: Fn() -> bool,
+{
+    let outcome = copy_in_chunks(temp_output, final_path, PUBLISH_COPY_CHUNK_BYTES, on_progress, is_cancelled)
+        .map_err(|e| AppError::FileValidation(format!("Cannot copy file to final location: {e}")))?;
+    if outcome == CopyOutcome::Cancelled {
+        return Ok(false);
+    }
+    remove_temp_output(temp_output);
+    Ok(true)
+}
+
+fn remove_temp_output(temp_output: &Path) {
+    if let Err(e) = std::fs::remove_file(temp_output) {
+        log::warn!("Cannot remove temp output {}: {e}", temp_output.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use tempfile::TempDir;
+
+    fn source(dir: &Path, len: usize) -> (PathBuf, Vec<u8>) {
+        let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        let path = dir.join("merged.m4b");
+        std::fs::write(&path, &data).unwrap();
+        (path, data)
+    }
+
+    #[test]
+    fn test_copy_reports_progress_per_chunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let (src, data) = source(temp_dir.path(), 10_000);
+        let dst = temp_dir.path().join("copy.m4b");
+
+        let mut reports = Vec::new();
+        let outcome = copy_in_chunks(&src, &dst, 4096, |copied, total| reports.push((copied, total)), || false).unwrap();
+        assert_eq!(outcome, CopyOutcome::Completed(10_000));
+        assert_eq!(reports, [(4096, 10_000), (8192, 10_000), (10_000, 10_000)]);
+        assert_eq!(std::fs::read(&dst).unwrap(), data);
+    }
+
+    #[test]
+    fn test_cancel_mid_copy_removes_partial() {
+        let temp_dir = TempDir::new().unwrap();
+        let (src, _) = source(temp_dir.path(), 10_000);
+        let dst = temp_dir.path().join("copy.m4b");
+
+        let chunks = Cell::new(0);
+        let outcome = copy_in_chunks(&src, &dst, 1000, |_, _| chunks.set(chunks.get() + 1), || chunks.get() >= 3).unwrap();
+        assert_eq!(outcome, CopyOutcome::Cancelled);
+        assert_eq!(chunks.get(), 3);
+        assert!(!dst.exists());
+        assert!(src.exists());
+    }
+
+    #[test]
+    fn test_copy_error_removes_partial() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing.m4b");
+        let dst = temp_dir.path().join("copy.m4b");
+        assert!(copy_in_chunks(&missing, &dst, 1000, |_, _| {}, || false).is_err());
+        assert!(!dst.exists());
+    }
+
+    #[test]
+    fn test_publish_renames_on_same_volume() {
+        let temp_dir = TempDir::new().unwrap();
+        let (src, data) = source(temp_dir.path(), 100);
+        let final_path = temp_dir.path().join("out").join("Book.m4b");
+        assert!(publish_output(&src, &final_path, PublishStrategy::Rename, |_, _| panic!("no copy expected"), || false).unwrap());
+        assert_eq!(std::fs::read(&final_path).unwrap(), data);
+        assert!(!src.exists());
+        assert_eq!(staging_path(&final_path), temp_dir.path().join("out").join("Book.m4b.partial"));
+    }
+
+    #[test]
+    fn test_copy_once_publishes_without_staging() {
+        let temp_dir = TempDir::new().unwrap();
+        let (src, data) = source(temp_dir.path(), 10_000);
+        let final_path = temp_dir.path().join("share").join("Book.m4b");
+
+        let mut copied = 0;
+        assert!(publish_output(&src, &final_path, PublishStrategy::CopyOnce, |bytes, _| copied = bytes, || false).unwrap());
+        assert_eq!(copied, 10_000);
+        assert_eq!(std::fs::read(&final_path).unwrap(), data);
+        assert!(!staging_path(&final_path).exists());
+        assert!(!src.exists());
+    }
+
+    #[test]
+    fn test_cancelled_copy_once_leaves_nothing_at_destination() {
+        let temp_dir = TempDir::new().unwrap();
+        let (src, _) = source(temp_dir.path(), 10_000);
+        let final_path = temp_dir.path().join("share").join("Book.m4b");
+
+        assert!(!publish_output(&src, &final_path, PublishStrategy::CopyOnce, |_, _| {}, || true).unwrap());
+        assert!(!final_path.exists());
+        assert!(src.exists());
+    }
+
+    #[test]
+    fn test_publish_strategy_detection() {
+        for filesystem in ["cifs", "smb3", "SMBFS", "nfs4", "fuse.sshfs"] {
+            assert!(is_network_filesystem(filesystem), "{filesystem}");
+            assert_eq!(PublishStrategy::for_filesystem(Some(filesystem)), PublishStrategy::CopyOnce);
+        }
+        for filesystem in [Some("ext4"), Some("apfs"), Some("exfat"), None] {
+            assert_eq!(PublishStrategy::for_filesystem(filesystem), PublishStrategy::Rename);
+        }
+
+        assert!(is_unc_path(Path::new(r"\\nas\books\Book.m4b")));
+        assert!(is_unc_path(Path::new(r"\\?\UNC\nas\books\Book.m4b")));
+        assert!(!is_unc_path(Path::new(r"\\?\C:\Books\Book.m4b")));
+        assert!(!is_unc_path(Path::new(r"C:\Books\Book.m4b")));
+        assert!(!is_unc_path(Path::new("/Volumes/books/Book.m4b")));
+        assert_eq!(PublishStrategy::for_destination(Path::new(r"\\nas\books\Book.m4b"), None), PublishStrategy::CopyOnce);
+        assert_eq!(PublishStrategy::for_destination(Path::new("/mnt/nas/Book.m4b"), Some("cifs")), PublishStrategy::CopyOnce);
+        assert_eq!(PublishStrategy::for_destination(Path::new("/home/me/Book.m4b"), Some("ext4")), PublishStrategy::Rename);
+    }
+}
@@ -0,0 +1,189 @@
+//! Segmented merging for very large input lists
+//!
+//! The concat demuxer struggles with lists of well over a thousand tiny
+//! files. Above a threshold the inputs are split into segments that are each
+//! encoded to an intermediate M4A with the target settings; the intermediates
+//! are then stream-copied into the final output.
+//!
+//! The first segment takes its tags from the first input the same way an
+//! unsegmented merge does (see `strip_track_scoped_metadata`) and the join
+//! keeps them from the first segment, so tags and chapters end up the same.
+
+use super::AudioFile;
+use std::ops::Range;
+
+/// A contiguous run of input files merged into one intermediate output
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputSegment {
+    /// Position of this segment in the plan
+    pub index: usize,
+    /// Indices of the input files in this segment
+    pub files: Range<usize>,
+    /// Start of this segment within the final output in seconds
+    pub start_offset: f64,
+    /// Combined duration of the segment's inputs in seconds
+    pub duration: f64,
+}
+
+/// Split of the input list into balanced segments
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentPlan {
+    /// Segments in output order
+    pub segments: Vec<InputSegment>,
+    /// Total duration of all inputs in seconds
+    pub total_duration: f64,
+}
+
+impl SegmentPlan {
+    /// Plans segments when `files` exceeds `threshold`, otherwise returns None
+    ///
+    /// Segments are balanced so the last one isn't left with a handful of files.
+    pub fn plan(files: &[AudioFile], threshold: usize) -> Option<Self> {
+        let threshold = threshold.max(1);
+        if files.len() <= threshold {
+            return None;
+        }
+
+        let segment_count = files.len().div_ceil(threshold);
+        let segment_size = files.len().div_ceil(segment_count);
+        let mut segments = Vec::with_capacity(segment_count);
+        let mut start_offset = 0.0;
+
+        for (index, start) in (0..files.len()).step_by(segment_size).enumerate() {
+            let end = (start + segment_size).min(files.len());
+            let duration: f64 = files[start..end].iter()
+                .filter_map(|f| f.duration)
+                .sum();
+            segments.push(InputSegment { index, files: start..end, start_offset, duration });
+            start_offset += duration;
+        }
+
+        Some(Self { segments, total_duration: start_offset })
+    }
+
+    /// Number of segments in the plan
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::temp_layout::TempLayout;
+    use std::path::{Path, PathBuf};
+
+    fn files_with_durations(count: usize, duration: f64) -> Vec<AudioFile> {
+        (0..count).map(|i| {
+            let mut file = AudioFile::new(PathBuf::from(format!("{i:04}.mp3")));
+            file.duration = Some(duration);
+            file.is_valid = true;
+            file
+        }).collect()
+    }
+
+    #[test]
+    fn test_no_plan_at_or_below_threshold() {
+        assert!(SegmentPlan::plan(&files_with_durations(500, 1.0), 500).is_none());
+        assert!(SegmentPlan::plan(&[], 500).is_none());
+    }
+
+    #[test]
+    fn test_segments_are_balanced_and_cover_all_files() {
+        let files = files_with_durations(1400, 2.0);
+        let plan = SegmentPlan::plan(&files, 500).unwrap();
+
+        assert_eq!(plan.segment_count(), 3);
+        let sizes: Vec<usize> = plan.segments.iter().map(|s| s.files.len()).collect();
+        assert_eq!(sizes, vec![467, 467, 466]);
+        assert_eq!(plan.segments[0].files.start, 0);
+        assert_eq!(plan.segments[2].files.end, 1400);
+        for pair in plan.segments.windows(2) {
+            assert_eq!(pair[0].files.end, pair[1].files.start);
+        }
+    }
+
+    #[test]
+    fn test_offsets_accumulate_durations() {
+        let mut files = files_with_durations(5, 10.0);
+        files[3].duration = None;
+        let plan = SegmentPlan::plan(&files, 2).unwrap();
+
+        let offsets: Vec<f64> = plan.segments.iter().map(|s| s.start_offset).collect();
+        assert_eq!(offsets, vec![0.0, 20.0, 30.0]);
+        assert_eq!(plan.total_duration, 40.0);
+    }
+
+    /// FFmpeg with the pipeline's AAC encoder, or None to skip
+    fn ffmpeg_with_encoder() -> Option<PathBuf> {
+        let ffmpeg = crate::ffmpeg::locate_ffmpeg().ok()?;
+        let encoders = std::process::Command::new(&ffmpeg)
+            .args(["-hide_banner", "-encoders"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&encoders.stdout)
+            .contains(crate::audio::constants::FFMPEG_AUDIO_CODEC)
+            .then_some(ffmpeg)
+    }
+
+    fn write_list(list: &Path, paths: &[PathBuf]) {
+        let content: String = paths.iter()
+            .map(|p| format!("file '{}'\n", p.display()))
+            .collect();
+        std::fs::write(list, content).unwrap();
+    }
+
+    #[test]
+    fn test_segmented_merge_with_micro_fixtures() {
+        use crate::audio::media_pipeline::{build_merge_command, build_segment_join_command};
+        use crate::audio::{AudioSettings, SampleRateConfig};
+        use lofty::file::AudioFile as _;
+
+        let Some(ffmpeg) = ffmpeg_with_encoder() else {
+            eprintln!("Skipping test - FFmpeg with AAC encoder not found");
+            return;
+        };
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut files = Vec::new();
+        for i in 0..9 {
+            let path = temp_dir.path().join(format!("micro-{i:02}.mp3"));
+            let status = std::process::Command::new(&ffmpeg)
+                .args(["-v", "error", "-f", "lavfi", "-i", "sine=frequency=440:duration=0.5", "-y"])
+                .arg(&path)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let mut file = AudioFile::new(path);
+            file.duration = Some(0.5);
+            file.is_valid = true;
+            files.push(file);
+        }
+
+        let plan = SegmentPlan::plan(&files, 4).unwrap();
+        assert_eq!(plan.segment_count(), 3);
+        let layout = TempLayout::new(temp_dir.path());
+        let mut settings = AudioSettings::default();
+        settings.sample_rate = SampleRateConfig::Explicit(22050);
+
+        let mut outputs = Vec::new();
+        for segment in &plan.segments {
+            let paths: Vec<PathBuf> = files[segment.files.clone()].iter().map(|f| f.path.clone()).collect();
+            let list = layout.segment_concat_list(segment.index);
+            write_list(&list, &paths);
+            let output = layout.segment_output(segment.index);
+            let result = build_merge_command(&list, &output, &settings, &settings.resolve(&[]).unwrap(), &paths, None).unwrap().output().unwrap();
+            assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+            outputs.push(output);
+        }
+
+        let join_list = layout.segment_join_list();
+        write_list(&join_list, &outputs);
+        let final_output = temp_dir.path().join("joined.m4b");
+        let result = build_segment_join_command(&join_list, &final_output, None, false).unwrap().output().unwrap();
+        assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+
+        let tagged = lofty::probe::Probe::open(&final_output).unwrap().read().unwrap();
+        let duration = tagged.properties().duration().as_secs_f64();
+        assert!((duration - plan.total_duration).abs() < 0.5, "duration {duration}");
+    }
+}
@@ -0,0 +1,262 @@
+//! End-to-end self test of the processing engine
+//!
+//! Synthesizes two short tones and a cover with FFmpeg, runs them through
+//! `process_audiobook_with_context` with a null sink, then checks the
+//! output's duration and tags. Every step is timed and reported, and the
+//! report names the first step that failed, so support can tell at a glance
+//! which stage is broken on a machine. The temp directory is always removed.
+
+use super::constants::{
+    FFMPEG_AUDIO_CODEC, SELF_TEST_AUTHOR, SELF_TEST_COVER_SIZE, SELF_TEST_DURATION_TOLERANCE_SECS,
+    SELF_TEST_TITLE, SELF_TEST_TONE_FREQUENCIES, SELF_TEST_TONE_SECS, TEMP_DIR_NAME,
+};
+use super::file_list::get_file_list_info;
+use super::processor::process_audiobook_with_context;
+use super::progress_sink::null_sink;
+use super::session::{JobLabel, ProcessingSession};
+use super::{AudioFile, AudioSettings, ProcessingContext};
+use crate::errors::{AppError, Result};
+use crate::ffmpeg::{locate_ffmpeg, FFmpegError};
+use crate::metadata::{read_metadata, AudiobookMetadata};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// One step of the self test, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelfTestStep {
+    SynthesizeInputs,
+    GenerateCover,
+    Analyze,
+    Process,
+    VerifyDuration,
+    VerifyTags,
+    Cleanup,
+}
+
+/// Outcome and timing of one step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestStepOutcome {
+    pub step: SelfTestStep,
+    pub passed: bool,
+    pub duration_ms: u64,
+    /// Why the step failed
+    pub error: Option<String>,
+}
+
+/// Result of a self test; steps after a failure are not run, except cleanup
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    /// Whether every step passed
+    pub passed: bool,
+    /// First step that failed
+    pub failed_step: Option<SelfTestStep>,
+    pub steps: Vec<SelfTestStepOutcome>,
+}
+
+impl SelfTestReport {
+    /// Runs a synchronous step and records it
+    fn step<T>(&mut self, step: SelfTestStep, run: impl FnOnce() -> Result<T>) -> Option<T> {
+        let started = Instant::now();
+        let result = run();
+        self.record(step, started, result)
+    }
+
+    /// Records the outcome of a step that started at `started`
+    fn record<T>(&mut self, step: SelfTestStep, started: Instant, result: Result<T>) -> Option<T> {
+        let duration_ms = started.elapsed().as_millis() as u64;
+        let (value, error) = match result {
+            Ok(value) => (Some(value), None),
+            Err(e) => {
+                log::warn!("Self test step {step:?} failed: {e}");
+                self.failed_step.get_or_insert(step);
+                (None, Some(e.to_string()))
+            }
+        };
+        self.steps.push(SelfTestStepOutcome { step, passed: error.is_none(), duration_ms, error });
+        value
+    }
+}
+
+/// Runs the whole pipeline on synthesized inputs and reports each step
+///
+/// Never fails itself; a missing FFmpeg shows up as a failed first step.
+pub async fn run_self_test() -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+    let dir = std::env::temp_dir()
+        .join(TEMP_DIR_NAME)
+        .join(format!("self-test-{}", uuid::Uuid::new_v4()));
+    run_steps(&dir, &mut report).await;
+    report.step(SelfTestStep::Cleanup, || remove_test_dir(&dir));
+    report.passed = report.failed_step.is_none();
+    log::info!("Self test {}", if report.passed { "passed" } else { "failed" });
+    report
+}
+
+async fn run_steps(dir: &Path, report: &mut SelfTestReport) -> Option<()> {
+    let ffmpeg = report.step(SelfTestStep::SynthesizeInputs, || {
+        std::fs::create_dir_all(dir)?;
+        let ffmpeg = locate_ffmpeg()?;
+        synthesize_inputs(&ffmpeg, dir).map(|inputs| (ffmpeg, inputs))
+    });
+    let (ffmpeg, inputs) = ffmpeg?;
+    let cover = report.step(SelfTestStep::GenerateCover, || generate_cover(&ffmpeg, dir))?;
+    let files = report.step(SelfTestStep::Analyze, || analyze(&inputs))?;
+
+    let output = dir.join("Self Test.m4b");
+    let started = Instant::now();
+    let result = process(files, cover, &output).await;
+    report.record(SelfTestStep::Process, started, result)?;
+
+    report.step(SelfTestStep::VerifyDuration, || verify_duration(&output))?;
+    report.step(SelfTestStep::VerifyTags, || verify_tags(&output))
+}
+
+/// Runs FFmpeg with `args`, failing with its stderr
+fn run_ffmpeg(ffmpeg: &Path, args: &[&str], output: &Path) -> Result<()> {
+    let result = Command::new(ffmpeg)
+        .args(["-v", "error", "-nostdin"])
+        .args(args)
+        .arg("-y")
+        .arg(output)
+        .output()
+        .map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))?;
+    if !result.status.success() {
+        return Err(FFmpegError::ExecutionFailed(String::from_utf8_lossy(&result.stderr).trim().to_string()).into());
+    }
+    Ok(())
+}
+
+fn synthesize_inputs(ffmpeg: &Path, dir: &Path) -> Result<Vec<PathBuf>> {
+    SELF_TEST_TONE_FREQUENCIES.iter().enumerate()
+        .map(|(index, frequency)| {
+            let input = dir.join(format!("{:02} - Tone.m4a", index + 1));
+            let source = format!("sine=frequency={frequency}:duration={SELF_TEST_TONE_SECS}");
+            run_ffmpeg(ffmpeg, &["-f", "lavfi", "-i", &source, "-c:a", FFMPEG_AUDIO_CODEC], &input)?;
+            Ok(input)
+        })
+        .collect()
+}
+
+fn generate_cover(ffmpeg: &Path, dir: &Path) -> Result<Vec<u8>> {
+    let cover = dir.join("cover.jpg");
+    let source = format!("color=c=navy:s={SELF_TEST_COVER_SIZE}x{SELF_TEST_COVER_SIZE}");
+    run_ffmpeg(ffmpeg, &["-f", "lavfi", "-i", &source, "-frames:v", "1"], &cover)?;
+    Ok(std::fs::read(cover)?)
+}
+
+fn analyze(inputs: &[PathBuf]) -> Result<Vec<AudioFile>> {
+    let info = get_file_list_info(inputs)?;
+    if let Some(invalid) = info.files.iter().find(|file| !file.is_valid) {
+        return Err(AppError::FileValidation(format!(
+            "Synthesized input {} is invalid: {}",
+            invalid.path.display(),
+            invalid.error.as_deref().unwrap_or("unknown error")
+        )));
+    }
+    Ok(info.files)
+}
+
+async fn process(files: Vec<AudioFile>, cover: Vec<u8>, output: &Path) -> Result<()> {
+    let settings = AudioSettings { output_path: output.to_path_buf(), ..AudioSettings::default() };
+    let session = Arc::new(ProcessingSession::new());
+    session.set_label(JobLabel::new(Some("Self test".to_string()), None, output));
+    let metadata = AudiobookMetadata {
+        title: Some(SELF_TEST_TITLE.to_string()),
+        author: Some(SELF_TEST_AUTHOR.to_string()),
+        cover_art: Some(cover),
+        ..AudiobookMetadata::new()
+    };
+    let context = ProcessingContext::new(null_sink(), session, settings);
+    process_audiobook_with_context(context, files, Some(metadata)).await?;
+    Ok(())
+}
+
+fn verify_duration(output: &Path) -> Result<()> {
+    let expected = SELF_TEST_TONE_SECS * SELF_TEST_TONE_FREQUENCIES.len() as f64;
+    let info = get_file_list_info(&[output])?;
+    let duration = info.files.first().and_then(|file| file.duration).unwrap_or(0.0);
+    if (duration - expected).abs() > SELF_TEST_DURATION_TOLERANCE_SECS {
+        return Err(AppError::General(format!(
+            "Output is {duration:.2}s long, expected {expected:.2}s"
+        )));
+    }
+    Ok(())
+}
+
+fn verify_tags(output: &Path) -> Result<()> {
+    let metadata = read_metadata(output)?;
+    let mut missing = Vec::new();
+    if metadata.title.as_deref() != Some(SELF_TEST_TITLE) {
+        missing.push("title");
+    }
+    if metadata.author.as_deref() != Some(SELF_TEST_AUTHOR) {
+        missing.push("author");
+    }
+    if metadata.cover_art.is_none() {
+        missing.push("cover");
+    }
+    if !missing.is_empty() {
+        return Err(AppError::General(format!("Output is missing its {}", missing.join(", "))));
+    }
+    Ok(())
+}
+
+fn remove_test_dir(dir: &Path) -> Result<()> {
+    if dir.exists() {
+        std::fs::remove_dir_all(dir)
+            .map_err(|e| AppError::ResourceCleanup(format!("Cannot remove {}: {e}", dir.display())))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(report: &SelfTestReport) -> Vec<SelfTestStep> {
+        report.steps.iter().map(|outcome| outcome.step).collect()
+    }
+
+    #[tokio::test]
+    async fn test_self_test_runs_every_step_or_pinpoints_failure() {
+        let report = run_self_test().await;
+        if locate_ffmpeg().is_err() {
+            assert!(!report.passed);
+            assert_eq!(report.failed_step, Some(SelfTestStep::SynthesizeInputs));
+            assert_eq!(steps(&report), [SelfTestStep::SynthesizeInputs, SelfTestStep::Cleanup]);
+            assert!(report.steps[0].error.is_some());
+            assert!(report.steps[1].passed);
+            return;
+        }
+        assert!(report.passed, "{report:#?}");
+        assert_eq!(report.failed_step, None);
+        assert_eq!(steps(&report), [
+            SelfTestStep::SynthesizeInputs,
+            SelfTestStep::GenerateCover,
+            SelfTestStep::Analyze,
+            SelfTestStep::Process,
+            SelfTestStep::VerifyDuration,
+            SelfTestStep::VerifyTags,
+            SelfTestStep::Cleanup,
+        ]);
+    }
+
+    #[test]
+    fn test_report_keeps_first_failure() {
+        let mut report = SelfTestReport::default();
+        assert_eq!(report.step(SelfTestStep::Analyze, || Ok(3)), Some(3));
+        assert_eq!(report.step::<()>(SelfTestStep::Process, || Err(AppError::General("encode".to_string()))), None);
+        report.step::<()>(SelfTestStep::Cleanup, || Err(AppError::General("busy".to_string())));
+        assert_eq!(report.failed_step, Some(SelfTestStep::Process));
+        assert_eq!(report.steps[1].error.as_deref(), Some("Operation failed: encode"));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["failedStep"], "process");
+        assert_eq!(json["steps"][0]["durationMs"], 0);
+    }
+}
@@ -5,9 +5,39 @@
 
 #![allow(dead_code)] // TODO: Remove when session management is fully integrated
 
+use super::progress_snapshot::{register_progress, SharedProgress};
+use super::timeline::{register_timeline, SharedTimeline, StageTiming, TimelineStage};
+use crate::errors::{AppError, CancelReason, Result};
 use crate::ProcessingState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
 use uuid::Uuid;
 
+/// Human-readable name and notes for a job, shown instead of its UUID
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobLabel {
+    /// Short name for the job
+    pub label: String,
+    /// Free-text notes
+    pub notes: Option<String>,
+}
+
+impl JobLabel {
+    /// Builds a label, defaulting to the output filename stem when none is given
+    pub fn new(label: Option<String>, notes: Option<String>, output_path: &Path) -> Self {
+        let label = label
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .unwrap_or_else(|| output_path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default());
+        let notes = notes.filter(|n| !n.trim().is_empty());
+        Self { label, notes }
+    }
+}
+
 /// A unique processing session that wraps ProcessingState
 /// 
 /// Each session has a unique UUID identifier and provides
@@ -18,14 +48,31 @@ pub struct ProcessingSession {
     id: Uuid,
     /// The underlying processing state
     state: ProcessingState,
+    /// Stage timings, also reachable through the timeline registry
+    timeline: SharedTimeline,
+    /// Label and notes given when the job was created
+    label: Mutex<Option<JobLabel>>,
+    /// Latest progress and warnings, also reachable through the progress registry
+    progress: SharedProgress,
+    /// Stop at the next unit boundary instead of mid-unit
+    soft_stop: Mutex<bool>,
 }
 
 impl ProcessingSession {
     /// Creates a new processing session with a unique ID
     pub fn new() -> Self {
+        let id = Uuid::new_v4();
+        let timeline = SharedTimeline::default();
+        register_timeline(id.to_string(), timeline.clone());
+        let progress = SharedProgress::default();
+        register_progress(id.to_string(), progress.clone());
         Self {
-            id: Uuid::new_v4(),
+            id,
             state: ProcessingState::default(),
+            timeline,
+            label: Mutex::new(None),
+            progress,
+            soft_stop: Mutex::new(false),
         }
     }
 
@@ -52,11 +99,98 @@ impl ProcessingSession {
             .unwrap_or(false)
     }
 
+    /// Requests cancellation; the first reason given is the one kept
+    pub fn cancel(&self, reason: CancelReason) {
+        if let Ok(mut current) = self.state.cancel_reason.lock() {
+            current.get_or_insert(reason);
+        }
+        if let Ok(mut is_cancelled) = self.state.is_cancelled.lock() {
+            *is_cancelled = true;
+        }
+    }
+
+    /// Why the session was cancelled, if it was
+    pub fn cancel_reason(&self) -> Option<CancelReason> {
+        self.state.cancel_reason.lock().ok().and_then(|reason| *reason)
+    }
+
+    /// Error returned by whichever stage notices the cancellation
+    ///
+    /// A bare cancelled flag (set without a reason) counts as a user request.
+    pub fn cancellation_error(&self) -> AppError {
+        AppError::Cancelled(self.cancel_reason().unwrap_or(CancelReason::UserRequested))
+    }
+
+    /// Asks the run to stop at the next unit boundary, finishing the unit in progress
+    pub fn request_soft_stop(&self) {
+        if let Ok(mut soft_stop) = self.soft_stop.lock() {
+            *soft_stop = true;
+        }
+    }
+
+    /// Checks if a soft stop has been requested
+    pub fn is_soft_stop_requested(&self) -> bool {
+        self.soft_stop.lock().map(|guard| *guard).unwrap_or(false)
+    }
+
+    /// Checks for a stop between units of work, such as merge segments
+    ///
+    /// A cancellation ends the run as usual. A soft stop becomes a
+    /// cancellation with `CancelReason::SoftStopped` here, so the run ends
+    /// before its next unit and records why.
+    pub fn check_unit_boundary(&self) -> Result<()> {
+        if !self.is_cancelled() && self.is_soft_stop_requested() {
+            self.cancel(CancelReason::SoftStopped);
+        }
+        if self.is_cancelled() {
+            return Err(self.cancellation_error());
+        }
+        Ok(())
+    }
+
     /// Gets a reference to the underlying ProcessingState
     pub fn state(&self) -> &ProcessingState {
         &self.state
     }
 
+    /// Records the start of a pipeline stage, ending the previous one
+    pub fn start_stage(&self, stage: TimelineStage) {
+        if let Ok(mut timeline) = self.timeline.lock() {
+            timeline.start(stage);
+        }
+    }
+
+    /// Ends the running stage once processing stops
+    pub fn finish_timeline(&self) {
+        if let Ok(mut timeline) = self.timeline.lock() {
+            timeline.finish();
+        }
+    }
+
+    /// Gets the stage timings recorded so far
+    pub fn timeline(&self) -> Vec<StageTiming> {
+        self.timeline.lock()
+            .map(|timeline| timeline.snapshot())
+            .unwrap_or_default()
+    }
+
+    /// Gets the shared latest-progress state
+    pub fn progress(&self) -> &SharedProgress {
+        &self.progress
+    }
+
+    /// Attaches a label and notes to the session
+    pub fn set_label(&self, label: JobLabel) {
+        if let Ok(mut current) = self.label.lock() {
+            *current = Some(label);
+        }
+    }
+
+    /// Gets the session's label and notes, if set
+    pub fn label(&self) -> Option<JobLabel> {
+        self.label.lock().ok().and_then(|label| label.clone())
+    }
+
     /// Gets a mutable reference to the underlying ProcessingState
     pub fn state_mut(&mut self) -> &mut ProcessingState {
         &mut self.state
@@ -69,6 +203,62 @@ impl Default for ProcessingSession {
     }
 }
 
+/// Sessions currently running, by ID
+static ACTIVE_SESSIONS: OnceLock<Mutex<HashMap<String, Arc<ProcessingSession>>>> = OnceLock::new();
+
+fn active_sessions() -> Result<std::sync::MutexGuard<'static, HashMap<String, Arc<ProcessingSession>>>> {
+    ACTIVE_SESSIONS.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Active session registry poisoned".to_string()))
+}
+
+/// Makes a running session reachable for per-session commands
+pub fn register_active_session(session: &Arc<ProcessingSession>) {
+    if let Ok(mut sessions) = active_sessions() {
+        sessions.insert(session.id(), Arc::clone(session));
+    }
+}
+
+/// Removes a session once its run has ended
+pub fn unregister_active_session(session_id: &str) {
+    if let Ok(mut sessions) = active_sessions() {
+        sessions.remove(session_id);
+    }
+}
+
+/// Number of sessions still running
+pub fn active_session_count() -> usize {
+    active_sessions().map(|sessions| sessions.len()).unwrap_or(0)
+}
+
+/// Cancels one running session
+pub fn cancel_active_session(session_id: &str, reason: CancelReason) -> Result<()> {
+    let sessions = active_sessions()?;
+    let session = sessions.get(session_id).ok_or_else(|| AppError::InvalidInput(
+        format!("No active session: {session_id}")
+    ))?;
+    session.cancel(reason);
+    Ok(())
+}
+
+/// Asks one running session to stop after its current unit
+pub fn soft_stop_active_session(session_id: &str) -> Result<()> {
+    let sessions = active_sessions()?;
+    let session = sessions.get(session_id).ok_or_else(|| AppError::InvalidInput(
+        format!("No active session: {session_id}")
+    ))?;
+    session.request_soft_stop();
+    Ok(())
+}
+
+/// Cancels every running session and returns how many there were
+pub fn cancel_all_active_sessions(reason: CancelReason) -> Result<usize> {
+    let sessions = active_sessions()?;
+    for session in sessions.values() {
+        session.cancel(reason);
+    }
+    Ok(sessions.len())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,6 +279,19 @@ mod tests {
         assert!(!session.is_cancelled());
     }
 
+    #[test]
+    fn test_session_timeline_is_registered() {
+        let session = ProcessingSession::new();
+        session.start_stage(TimelineStage::Analyzing);
+        session.start_stage(TimelineStage::Converting);
+        session.finish_timeline();
+
+        let stages: Vec<_> = session.timeline().iter().map(|t| t.stage).collect();
+        assert_eq!(stages, vec![TimelineStage::Analyzing, TimelineStage::Converting]);
+        let registered = super::super::timeline::session_timeline(Some(&session.id())).unwrap();
+        assert_eq!(registered, session.timeline());
+    }
+
     #[test]
     fn test_session_id_format() {
         let session = ProcessingSession::new();
@@ -98,4 +301,69 @@ mod tests {
         assert_eq!(id.len(), 36);
         assert_eq!(id.chars().filter(|&c| c == '-').count(), 4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_session_label() {
+        let session = ProcessingSession::new();
+        assert!(session.label().is_none());
+
+        let label = JobLabel::new(Some("Dune".to_string()), Some("re-encode".to_string()), Path::new("out.m4b"));
+        session.set_label(label.clone());
+        assert_eq!(session.label(), Some(label));
+    }
+
+    #[test]
+    fn test_job_label_defaults_to_output_stem() {
+        let output = Path::new("/books/Dune - Frank Herbert.m4b");
+        assert_eq!(JobLabel::new(None, None, output).label, "Dune - Frank Herbert");
+        assert_eq!(JobLabel::new(Some("  ".to_string()), None, output).label, "Dune - Frank Herbert");
+        assert_eq!(JobLabel::new(None, Some(" ".to_string()), output).notes, None);
+    }
+
+    #[test]
+    fn test_first_cancel_reason_wins() {
+        let session = ProcessingSession::new();
+        session.cancel(CancelReason::Quota);
+        session.cancel(CancelReason::UserRequested);
+        assert!(session.is_cancelled());
+        assert_eq!(session.cancel_reason(), Some(CancelReason::Quota));
+        assert!(matches!(session.cancellation_error(), AppError::Cancelled(CancelReason::Quota)));
+    }
+
+    #[test]
+    fn test_cancel_active_session_by_id() {
+        let session = Arc::new(ProcessingSession::new());
+        register_active_session(&session);
+        cancel_active_session(&session.id(), CancelReason::Timeout).unwrap();
+        assert_eq!(session.cancel_reason(), Some(CancelReason::Timeout));
+
+        unregister_active_session(&session.id());
+        assert!(cancel_active_session(&session.id(), CancelReason::Timeout).is_err());
+    }
+
+    #[test]
+    fn test_soft_stop_takes_effect_at_unit_boundary() {
+        let session = Arc::new(ProcessingSession::new());
+        register_active_session(&session);
+        assert!(session.check_unit_boundary().is_ok());
+
+        soft_stop_active_session(&session.id()).unwrap();
+        // The unit in progress keeps running: FFmpeg only watches the cancelled flag
+        assert!(!session.is_cancelled());
+        let error = session.check_unit_boundary().unwrap_err();
+        assert!(matches!(error, AppError::Cancelled(CancelReason::SoftStopped)));
+        assert_eq!(session.cancel_reason(), Some(CancelReason::SoftStopped));
+
+        unregister_active_session(&session.id());
+        assert!(soft_stop_active_session(&session.id()).is_err());
+    }
+
+    #[test]
+    fn test_cancel_before_soft_stop_keeps_its_reason() {
+        let session = ProcessingSession::new();
+        session.cancel(CancelReason::UserRequested);
+        session.request_soft_stop();
+        let error = session.check_unit_boundary().unwrap_err();
+        assert!(matches!(error, AppError::Cancelled(CancelReason::UserRequested)));
+    }
+}
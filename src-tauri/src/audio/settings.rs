@@ -1,17 +1,101 @@
 //! Audio processing settings validation and management
 
-use super::{AudioSettings, ChannelConfig, SampleRateConfig};
+use super::{AudioFile, AudioSettings, ChannelConfig, InvalidInputPolicy, SampleRateConfig};
+use super::chapters::{ChapterFormat, ChapterTimestampFormat, ChapterTitleSource};
+use super::constants::{
+    DEFAULT_OUTPUT_EXTENSION, M4A_OUTPUT_EXTENSION, MAX_ANALYZE_DURATION_SECS, MAX_PROBE_SIZE_MB, MIN_SEGMENT_THRESHOLD,
+};
+use super::filename::{sanitize_filename, FilenameTarget};
+use super::output_dir::{default_output_dir, resolve_output_path};
+use super::processor::detect_input_sample_rate;
 use crate::errors::{AppError, Result};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// Settings with every automatic value decided, as FFmpeg is given them
+///
+/// Produced only by `AudioSettings::resolve`, so the encode, the plan
+/// preview and the estimates all resolve `auto` values the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedAudioSettings {
+    /// Output bitrate in kbps
+    pub bitrate: u32,
+    /// Output sample rate in Hz
+    pub sample_rate: u32,
+    /// Whether the sample rate was detected from the inputs (`auto`)
+    pub sample_rate_detected: bool,
+    /// Output channel count
+    pub channels: u8,
+}
 
 /// Validates audio processing settings
 pub fn validate_audio_settings(settings: &AudioSettings) -> Result<()> {
     validate_bitrate(settings.bitrate)?;
     validate_sample_rate_config(&settings.sample_rate)?;
-    validate_output_path(&settings.output_path)?;
+    let output_path = resolve_output_path(&settings.output_path)?;
+    validate_output_path(&output_path, settings.allow_m4a_extension)?;
+    validate_output_filename(&output_path, FilenameTarget::from_preference(settings.strict_cross_platform))?;
+    validate_segment_threshold(settings.segment_threshold)?;
+    validate_probe_limit("Probe size", settings.probe_size_mb, MAX_PROBE_SIZE_MB, "MB")?;
+    validate_probe_limit("Analyze duration", settings.analyze_duration_secs, MAX_ANALYZE_DURATION_SECS, "seconds")?;
+    validate_bitrate_excess_factor(settings.bitrate_excess_factor)?;
+    validate_max_cover_bytes(settings.max_cover_bytes)?;
+    Ok(())
+}
+
+/// Validates the embedded cover limit, if one is set, is not zero
+fn validate_max_cover_bytes(limit: Option<u64>) -> Result<()> {
+    match limit {
+        Some(0) => Err(AppError::InvalidInput("Cover size limit must be greater than 0 bytes".to_string())),
+        _ => Ok(()),
+    }
+}
+
+/// Validates the bitrate excess factor, if one is set, is at least 1
+fn validate_bitrate_excess_factor(factor: Option<f32>) -> Result<()> {
+    match factor {
+        Some(value) if !(value.is_finite() && value >= 1.0) => Err(AppError::InvalidInput(
+            format!("Bitrate excess factor must be at least 1.0, got: {value}")
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Validates the output filename is already safe for the target filesystems
+fn validate_output_filename(path: &Path, target: FilenameTarget) -> Result<()> {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Err(AppError::InvalidInput(
+            format!("Output path has no valid filename: {}", path.display())
+        ));
+    };
+    let sanitized = sanitize_filename(name, target);
+    if sanitized != name {
+        return Err(AppError::InvalidInput(
+            format!("Output filename \"{name}\" is not portable, try \"{sanitized}\"")
+        ));
+    }
     Ok(())
 }
 
+/// Validates the segmented merge threshold, if one is set
+fn validate_segment_threshold(threshold: Option<usize>) -> Result<()> {
+    match threshold {
+        Some(value) if value < MIN_SEGMENT_THRESHOLD => Err(AppError::InvalidInput(
+            format!("Segment threshold must be at least {MIN_SEGMENT_THRESHOLD} files, got: {value}")
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Validates an optional input probing limit is between 1 and `max`
+fn validate_probe_limit(name: &str, value: Option<u32>, max: u32, unit: &str) -> Result<()> {
+    match value {
+        Some(value) if !(1..=max).contains(&value) => Err(AppError::InvalidInput(
+            format!("{name} must be between 1-{max} {unit}, got: {value}")
+        )),
+        _ => Ok(()),
+    }
+}
+
 /// Validates bitrate is within acceptable range
 fn validate_bitrate(bitrate: u32) -> Result<()> {
     if !(32..=128).contains(&bitrate) {
@@ -42,7 +126,11 @@ fn validate_explicit_sample_rate(sample_rate: u32) -> Result<()> {
 }
 
 /// Validates output path is writable
-fn validate_output_path<P: AsRef<Path>>(path: P) -> Result<()> {
+///
+/// The extension must be `.m4b`, or `.m4a` when `allow_m4a` is set. The
+/// encode always goes to a `.m4b` temp file, so the rest of the pipeline
+/// doesn't tell the two apart.
+fn validate_output_path<P: AsRef<Path>>(path: P, allow_m4a: bool) -> Result<()> {
     let path = path.as_ref();
     
     // Check if parent directory exists
@@ -55,26 +143,81 @@ fn validate_output_path<P: AsRef<Path>>(path: P) -> Result<()> {
     }
     
     // Check file extension
+    let expected = if allow_m4a { ".m4b or .m4a" } else { ".m4b" };
     match path.extension().and_then(|s| s.to_str()) {
-        Some("m4b") => Ok(()),
+        Some(DEFAULT_OUTPUT_EXTENSION) => Ok(()),
+        Some(M4A_OUTPUT_EXTENSION) if allow_m4a => Ok(()),
         Some(ext) => Err(AppError::InvalidInput(
-            format!("Output must be .m4b file, got: .{ext}")
+            format!("Output must be {expected} file, got: .{ext}")
         )),
         None => Err(AppError::InvalidInput(
-            "Output file must have .m4b extension".to_string()
+            format!("Output file must have {expected} extension")
         )),
     }
 }
 
 impl AudioSettings {
+    /// Decides every automatic value for `files`, without changing the settings
+    ///
+    /// An `auto` sample rate is the most common rate among the inputs; an
+    /// explicit one never looks at them. Fails when `auto` can't read a rate
+    /// from any input.
+    pub fn resolve(&self, files: &[AudioFile]) -> Result<ResolvedAudioSettings> {
+        let (sample_rate, sample_rate_detected) = match self.sample_rate {
+            SampleRateConfig::Explicit(rate) => (rate, false),
+            SampleRateConfig::Auto => {
+                let paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+                (detect_input_sample_rate(&paths)?, true)
+            }
+        };
+        Ok(ResolvedAudioSettings {
+            bitrate: self.bitrate,
+            sample_rate,
+            sample_rate_detected,
+            channels: self.channels.channel_count(),
+        })
+    }
+
     /// Creates settings optimized for audiobooks
+    ///
+    /// Presets write into the default output directory from preferences.
     #[allow(dead_code)]
     pub fn audiobook_preset() -> Self {
         Self {
             bitrate: 64,  // Good quality for speech
             channels: ChannelConfig::Mono,  // Most audiobooks are mono
             sample_rate: SampleRateConfig::Auto,  // Auto-detect from input
-            output_path: "audiobook.m4b".into(),
+            output_path: default_output_dir().join("audiobook.m4b"),
+            segment_threshold: None,
+            strict_cross_platform: false,
+            auto_dual_mono: false,
+            accurate_mp3_durations: false,
+            enforce_cover_quality: false,
+            generate_placeholder_cover: false,
+            invalid_input_policy: InvalidInputPolicy::Fail,
+            probe_size_mb: None,
+            analyze_duration_secs: None,
+            gapless_join: true,
+            bitrate_excess_factor: None,
+            strict_bitrate_check: false,
+            copy_transcripts: false,
+            strip_track_scoped_metadata: true,
+            chapters: None,
+            chapter_title_source: ChapterTitleSource::TagTitle,
+            chapter_specs: None,
+            allow_m4a_extension: false,
+            verify_output: false,
+            reproducible: false,
+            strict_metadata_fields: false,
+            export_cue: false,
+            append_chapter_list_to_description: false,
+            chapter_list_timestamp_format: ChapterTimestampFormat::Auto,
+            chapter_format: ChapterFormat::Ffmetadata,
+            write_encoding_info: true,
+            auto_cover_from_input: true,
+            max_cover_bytes: None,
+            strict_cover_size: false,
+            metadata_sidecar: None,
         }
     }
     
@@ -85,7 +228,37 @@ impl AudioSettings {
             bitrate: 128,
             channels: ChannelConfig::Stereo,
             sample_rate: SampleRateConfig::Explicit(44100),
-            output_path: "audiobook_hq.m4b".into(),
+            output_path: default_output_dir().join("audiobook_hq.m4b"),
+            segment_threshold: None,
+            strict_cross_platform: false,
+            auto_dual_mono: false,
+            accurate_mp3_durations: false,
+            enforce_cover_quality: false,
+            generate_placeholder_cover: false,
+            invalid_input_policy: InvalidInputPolicy::Fail,
+            probe_size_mb: None,
+            analyze_duration_secs: None,
+            gapless_join: true,
+            bitrate_excess_factor: None,
+            strict_bitrate_check: false,
+            copy_transcripts: false,
+            strip_track_scoped_metadata: true,
+            chapters: None,
+            chapter_title_source: ChapterTitleSource::TagTitle,
+            chapter_specs: None,
+            allow_m4a_extension: false,
+            verify_output: false,
+            reproducible: false,
+            strict_metadata_fields: false,
+            export_cue: false,
+            append_chapter_list_to_description: false,
+            chapter_list_timestamp_format: ChapterTimestampFormat::Auto,
+            chapter_format: ChapterFormat::Ffmetadata,
+            write_encoding_info: true,
+            auto_cover_from_input: true,
+            max_cover_bytes: None,
+            strict_cover_size: false,
+            metadata_sidecar: None,
         }
     }
     
@@ -96,7 +269,37 @@ impl AudioSettings {
             bitrate: 32,
             channels: ChannelConfig::Mono,
             sample_rate: SampleRateConfig::Explicit(22050),
-            output_path: "audiobook_low.m4b".into(),
+            output_path: default_output_dir().join("audiobook_low.m4b"),
+            segment_threshold: None,
+            strict_cross_platform: false,
+            auto_dual_mono: false,
+            accurate_mp3_durations: false,
+            enforce_cover_quality: false,
+            generate_placeholder_cover: false,
+            invalid_input_policy: InvalidInputPolicy::Fail,
+            probe_size_mb: None,
+            analyze_duration_secs: None,
+            gapless_join: true,
+            bitrate_excess_factor: None,
+            strict_bitrate_check: false,
+            copy_transcripts: false,
+            strip_track_scoped_metadata: true,
+            chapters: None,
+            chapter_title_source: ChapterTitleSource::TagTitle,
+            chapter_specs: None,
+            allow_m4a_extension: false,
+            verify_output: false,
+            reproducible: false,
+            strict_metadata_fields: false,
+            export_cue: false,
+            append_chapter_list_to_description: false,
+            chapter_list_timestamp_format: ChapterTimestampFormat::Auto,
+            chapter_format: ChapterFormat::Ffmetadata,
+            write_encoding_info: true,
+            auto_cover_from_input: true,
+            max_cover_bytes: None,
+            strict_cover_size: false,
+            metadata_sidecar: None,
         }
     }
 }
@@ -162,14 +365,27 @@ mod tests {
     fn test_validate_output_path_valid() {
         let temp_dir = TempDir::new().unwrap();
         let output_path = temp_dir.path().join("test.m4b");
-        assert!(validate_output_path(&output_path).is_ok());
+        assert!(validate_output_path(&output_path, false).is_ok());
+        assert!(validate_output_path(&output_path, true).is_ok());
+    }
+
+    #[test]
+    fn test_validate_output_path_m4a() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_path = temp_dir.path().join("test.m4a");
+        let error_msg = validate_output_path(&output_path, false).unwrap_err().to_string();
+        assert!(error_msg.contains("Output must be .m4b file, got: .m4a"));
+        assert!(validate_output_path(&output_path, true).is_ok());
+
+        let error_msg = validate_output_path(temp_dir.path().join("test.mp3"), true).unwrap_err().to_string();
+        assert!(error_msg.contains(".m4b or .m4a"));
     }
 
     #[test]
     fn test_validate_output_path_invalid_extension() {
         let temp_dir = TempDir::new().unwrap();
         let output_path = temp_dir.path().join("test.mp3");
-        let result = validate_output_path(&output_path);
+        let result = validate_output_path(&output_path, false);
         assert!(result.is_err());
         let error_msg = result.unwrap_err().to_string();
         assert!(error_msg.contains(".m4b"));
@@ -177,11 +393,46 @@ mod tests {
 
     #[test]
     fn test_validate_output_path_nonexistent_dir() {
-        let result = validate_output_path("/nonexistent/dir/test.m4b");
+        let result = validate_output_path("/nonexistent/dir/test.m4b", false);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("does not exist"));
     }
 
+    #[test]
+    fn test_validate_output_filename() {
+        let strict = FilenameTarget::CrossPlatform;
+        assert!(validate_output_filename(Path::new("/out/Book (2020).m4b"), strict).is_ok());
+        let result = validate_output_filename(Path::new("/out/CON.m4b"), strict);
+        assert!(result.unwrap_err().to_string().contains("_CON.m4b"));
+        assert!(validate_output_filename(Path::new("/out/What?.m4b"), strict).is_err());
+        if !cfg!(windows) {
+            assert!(validate_output_filename(Path::new("/out/What?.m4b"), FilenameTarget::Native).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_validate_segment_threshold() {
+        assert!(validate_segment_threshold(None).is_ok());
+        assert!(validate_segment_threshold(Some(MIN_SEGMENT_THRESHOLD)).is_ok());
+        assert!(validate_segment_threshold(Some(1)).is_err());
+    }
+
+    #[test]
+    fn test_validate_max_cover_bytes() {
+        assert!(validate_max_cover_bytes(None).is_ok());
+        assert!(validate_max_cover_bytes(Some(1)).is_ok());
+        assert!(validate_max_cover_bytes(Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_validate_probe_limit() {
+        assert!(validate_probe_limit("Probe size", None, MAX_PROBE_SIZE_MB, "MB").is_ok());
+        assert!(validate_probe_limit("Probe size", Some(MAX_PROBE_SIZE_MB), MAX_PROBE_SIZE_MB, "MB").is_ok());
+        assert!(validate_probe_limit("Probe size", Some(0), MAX_PROBE_SIZE_MB, "MB").is_err());
+        let err = validate_probe_limit("Analyze duration", Some(601), MAX_ANALYZE_DURATION_SECS, "seconds").unwrap_err();
+        assert!(err.to_string().contains("between 1-600 seconds"));
+    }
+
     #[test]
     fn test_audiobook_preset() {
         let settings = AudioSettings::audiobook_preset();
@@ -190,6 +441,37 @@ mod tests {
         assert!(matches!(settings.sample_rate, SampleRateConfig::Auto));
     }
 
+    #[test]
+    fn test_resolve_explicit_settings_ignores_inputs() {
+        let mut settings = AudioSettings::high_quality_preset();
+        settings.bitrate = 96;
+        let missing = [AudioFile::new(PathBuf::from("/nonexistent/01.mp3"))];
+        let resolved = settings.resolve(&missing).unwrap();
+        assert_eq!(resolved, ResolvedAudioSettings { bitrate: 96, sample_rate: 44100, sample_rate_detected: false, channels: 2 });
+        assert_eq!(settings.resolve(&[]).unwrap(), resolved);
+        assert!(matches!(settings.sample_rate, SampleRateConfig::Explicit(44100)));
+    }
+
+    #[test]
+    fn test_resolve_auto_sample_rate_from_inputs() {
+        let settings = AudioSettings::audiobook_preset();
+        assert!(settings.resolve(&[]).unwrap_err().to_string().contains("no input files"));
+        let missing = [AudioFile::new(PathBuf::from("/nonexistent/01.mp3"))];
+        assert!(settings.resolve(&missing).is_err());
+
+        let media = Path::new("../media/01 - Introduction.mp3");
+        if !media.exists() {
+            eprintln!("Skipping detection check - test media not found");
+            return;
+        }
+        let inputs = [AudioFile::new(media.to_path_buf())];
+        let resolved = settings.resolve(&inputs).unwrap();
+        assert!(resolved.sample_rate_detected);
+        assert!(resolved.sample_rate > 0);
+        assert_eq!((resolved.bitrate, resolved.channels), (64, 1));
+        assert!(matches!(settings.sample_rate, SampleRateConfig::Auto));
+    }
+
     #[test]
     fn test_channel_config_methods() {
         assert_eq!(ChannelConfig::Mono.channel_count(), 1);
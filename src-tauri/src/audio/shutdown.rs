@@ -0,0 +1,231 @@
+//! Graceful shutdown of running sessions
+//!
+//! When the app is asked to exit, every active session is cancelled with
+//! `CancelReason::Shutdown`. The pipeline then kills its FFmpeg child,
+//! removes its temp directory and records the run, while the coordinator
+//! waits a bounded time. After that, stores are flushed and exit proceeds,
+//! whether or not every session stopped in time.
+
+use super::constants::{SHUTDOWN_POLL_INTERVAL_MS, SHUTDOWN_TIMEOUT_SECS};
+use super::session::{active_session_count, cancel_all_active_sessions};
+use crate::errors::CancelReason;
+use std::time::{Duration, Instant};
+
+/// Time source for the shutdown wait, replaceable in tests
+pub trait Clock {
+    /// Time elapsed since some fixed start
+    fn elapsed(&self) -> Duration;
+    /// Blocks for `duration`
+    fn sleep(&self, duration: Duration);
+}
+
+/// Wall clock
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// The set of sessions a shutdown has to stop
+pub trait ShutdownSessions {
+    /// Cancels every session, returning how many were running
+    fn cancel_all(&self, reason: CancelReason) -> usize;
+    /// Number of sessions that haven't stopped yet
+    fn active_count(&self) -> usize;
+}
+
+/// Sessions in the process-wide active session registry
+pub struct ActiveSessions;
+
+impl ShutdownSessions for ActiveSessions {
+    fn cancel_all(&self, reason: CancelReason) -> usize {
+        cancel_all_active_sessions(reason).unwrap_or_else(|e| {
+            log::warn!("Could not cancel sessions for shutdown: {e}");
+            0
+        })
+    }
+
+    fn active_count(&self) -> usize {
+        active_session_count()
+    }
+}
+
+/// How a shutdown ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownOutcome {
+    /// Nothing was running
+    Idle,
+    /// Every session stopped before the timeout
+    Graceful,
+    /// The timeout passed with sessions still running
+    Forced { remaining: usize },
+}
+
+/// Cancels sessions, waits for them, then flushes state
+pub struct ShutdownCoordinator<C: Clock> {
+    clock: C,
+    timeout: Duration,
+    poll_interval: Duration,
+}
+
+impl ShutdownCoordinator<SystemClock> {
+    /// Coordinator using the wall clock and the default timeout
+    pub fn new() -> Self {
+        Self::with_clock(
+            SystemClock::new(),
+            Duration::from_secs(SHUTDOWN_TIMEOUT_SECS),
+            Duration::from_millis(SHUTDOWN_POLL_INTERVAL_MS),
+        )
+    }
+}
+
+impl Default for ShutdownCoordinator<SystemClock> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Clock> ShutdownCoordinator<C> {
+    pub fn with_clock(clock: C, timeout: Duration, poll_interval: Duration) -> Self {
+        Self { clock, timeout, poll_interval }
+    }
+
+    /// Runs the shutdown sequence; `flush` runs last on every path
+    pub fn run<F: FnOnce()>(&self, sessions: &dyn ShutdownSessions, flush: F) -> ShutdownOutcome {
+        let outcome = self.stop_sessions(sessions);
+        match outcome {
+            ShutdownOutcome::Forced { remaining } => {
+                log::warn!("Exiting with {remaining} session(s) still stopping");
+            }
+            _ => log::info!("Shutdown: {outcome:?}"),
+        }
+        flush();
+        outcome
+    }
+
+    fn stop_sessions(&self, sessions: &dyn ShutdownSessions) -> ShutdownOutcome {
+        if sessions.cancel_all(CancelReason::Shutdown) == 0 {
+            return ShutdownOutcome::Idle;
+        }
+        let deadline = self.clock.elapsed() + self.timeout;
+        loop {
+            let remaining = sessions.active_count();
+            if remaining == 0 {
+                return ShutdownOutcome::Graceful;
+            }
+            if self.clock.elapsed() >= deadline {
+                return ShutdownOutcome::Forced { remaining };
+            }
+            self.clock.sleep(self.poll_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Clock that only moves when slept on
+    #[derive(Default)]
+    struct FakeClock {
+        now: Cell<Duration>,
+    }
+
+    impl Clock for FakeClock {
+        fn elapsed(&self) -> Duration {
+            self.now.get()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration);
+        }
+    }
+
+    /// Sessions that stop after a number of status checks
+    struct MockSessions {
+        running: Cell<usize>,
+        checks_until_stopped: Option<usize>,
+        checks: Cell<usize>,
+        reason: Cell<Option<CancelReason>>,
+    }
+
+    impl MockSessions {
+        fn new(running: usize, checks_until_stopped: Option<usize>) -> Self {
+            Self { running: Cell::new(running), checks_until_stopped, checks: Cell::new(0), reason: Cell::new(None) }
+        }
+    }
+
+    impl ShutdownSessions for MockSessions {
+        fn cancel_all(&self, reason: CancelReason) -> usize {
+            self.reason.set(Some(reason));
+            self.running.get()
+        }
+
+        fn active_count(&self) -> usize {
+            self.checks.set(self.checks.get() + 1);
+            if self.checks_until_stopped.is_some_and(|n| self.checks.get() > n) {
+                self.running.set(0);
+            }
+            self.running.get()
+        }
+    }
+
+    fn coordinator() -> ShutdownCoordinator<FakeClock> {
+        ShutdownCoordinator::with_clock(FakeClock::default(), Duration::from_secs(5), Duration::from_millis(100))
+    }
+
+    #[test]
+    fn test_graceful_when_sessions_stop_in_time() {
+        let sessions = MockSessions::new(2, Some(3));
+        let flushed = Cell::new(false);
+        let coordinator = coordinator();
+
+        let outcome = coordinator.run(&sessions, || flushed.set(true));
+        assert_eq!(outcome, ShutdownOutcome::Graceful);
+        assert_eq!(sessions.reason.get(), Some(CancelReason::Shutdown));
+        assert_eq!(coordinator.clock.elapsed(), Duration::from_millis(300));
+        assert!(flushed.get());
+    }
+
+    #[test]
+    fn test_forced_after_timeout_still_flushes() {
+        let sessions = MockSessions::new(1, None);
+        let flushed = Cell::new(false);
+        let coordinator = coordinator();
+
+        let outcome = coordinator.run(&sessions, || flushed.set(true));
+        assert_eq!(outcome, ShutdownOutcome::Forced { remaining: 1 });
+        assert_eq!(coordinator.clock.elapsed(), Duration::from_secs(5));
+        assert!(flushed.get());
+    }
+
+    #[test]
+    fn test_idle_exits_without_waiting() {
+        let sessions = MockSessions::new(0, None);
+        let coordinator = coordinator();
+        assert_eq!(coordinator.run(&sessions, || {}), ShutdownOutcome::Idle);
+        assert_eq!(coordinator.clock.elapsed(), Duration::ZERO);
+        assert_eq!(sessions.checks.get(), 0);
+    }
+}
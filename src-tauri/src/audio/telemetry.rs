@@ -0,0 +1,97 @@
+//! Encoder telemetry for the live speed and bitrate graph
+//!
+//! FFmpeg writes a `-progress` block roughly twice a second; graphing every
+//! block would flood the webview for no visible gain. The sampler passes at
+//! most one point per `TELEMETRY_INTERVAL_MS`, and the session keeps the
+//! last `TELEMETRY_MAX_POINTS` so a reloaded webview can redraw the graph
+//! from its progress snapshot.
+
+use super::constants::TELEMETRY_INTERVAL_MS;
+use super::progress_monitor::Throughput;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One point of the graph, sent as `processing-telemetry`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryPoint {
+    /// Unix timestamp in milliseconds
+    pub timestamp_ms: u64,
+    /// Smoothed speed as a multiple of realtime
+    pub speed: Option<f64>,
+    /// Output bitrate of the latest progress block
+    pub bitrate_kbps: Option<f64>,
+    pub bytes_written: Option<u64>,
+}
+
+/// Thins progress blocks down to one point per interval
+#[derive(Debug, Default)]
+pub struct TelemetrySampler {
+    last_sample_ms: Option<u64>,
+}
+
+impl TelemetrySampler {
+    /// Point for the end of a progress block at `now_ms`, if one is due
+    ///
+    /// Nothing is sampled before the encoder has reported anything.
+    pub fn sample(&mut self, now_ms: u64, throughput: &Throughput) -> Option<TelemetryPoint> {
+        if throughput.speed.is_none() && throughput.bitrate_kbps.is_none() && throughput.bytes_written.is_none() {
+            return None;
+        }
+        if self.last_sample_ms.is_some_and(|last| now_ms.saturating_sub(last) < TELEMETRY_INTERVAL_MS) {
+            return None;
+        }
+        self.last_sample_ms = Some(now_ms);
+        Some(TelemetryPoint {
+            timestamp_ms: now_ms,
+            speed: throughput.speed,
+            bitrate_kbps: throughput.bitrate_kbps,
+            bytes_written: throughput.bytes_written,
+        })
+    }
+}
+
+/// Current Unix time in milliseconds
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds a synthetic `-progress` stream, one block every 500 ms
+    fn sample_stream(blocks: u64) -> Vec<TelemetryPoint> {
+        let mut throughput = Throughput::default();
+        let mut sampler = TelemetrySampler::default();
+        let mut points = Vec::new();
+        for block in 0..blocks {
+            throughput.observe(&format!("bitrate={:.1}kbits/s", 60.0 + block as f64));
+            throughput.observe(&format!("total_size={}", block * 32_000));
+            throughput.observe("speed=20x");
+            points.extend(sampler.sample(1_000_000 + block * 500, &throughput));
+        }
+        points
+    }
+
+    #[test]
+    fn test_sampler_keeps_one_point_per_interval() {
+        let points = sample_stream(20);
+        let timestamps: Vec<u64> = points.iter().map(|point| point.timestamp_ms).collect();
+        assert_eq!(timestamps, [1_000_000, 1_002_000, 1_004_000, 1_006_000, 1_008_000]);
+        assert_eq!(points[1].bitrate_kbps, Some(64.0));
+        assert_eq!(points[1].bytes_written, Some(128_000));
+        assert_eq!(points[1].speed, Some(20.0));
+    }
+
+    #[test]
+    fn test_sampler_waits_for_readings() {
+        let mut sampler = TelemetrySampler::default();
+        assert_eq!(sampler.sample(0, &Throughput::default()), None);
+        let throughput = Throughput { speed: Some(5.0), ..Throughput::default() };
+        assert!(sampler.sample(10, &throughput).is_some());
+        // A clock stepping backwards doesn't emit early
+        assert_eq!(sampler.sample(5, &throughput), None);
+        assert!(sampler.sample(10 + TELEMETRY_INTERVAL_MS, &throughput).is_some());
+    }
+}
@@ -0,0 +1,258 @@
+//! Names of the files a session writes to its temp directory
+//!
+//! Every intermediate lives under `<temp>/audiobook-boss/<session id>/` and
+//! carries its stage (and segment index) in its name, e.g. `concat-0002.txt`
+//! and `segment-0002.m4a`. A leftover directory from a crashed run then
+//! shows which stage it reached, and segmented merges never reuse a name.
+//! The `TEMP_*` constants stay the base names the numbered ones derive from.
+//!
+//! A session claims its directory with an owner marker holding the PID and
+//! process start time. Leftovers from another run under the same id (the
+//! "default-session" adapter, deterministic ids) are refused or cleaned
+//! first, never merged into this run's concat.
+
+use super::cleanup::CleanupGuard;
+use super::constants::{
+    SEGMENT_JOIN_LIST_FILENAME, SEGMENT_OUTPUT_EXTENSION, TEMP_CHAPTERS_FILENAME, TEMP_CONCAT_FILENAME, TEMP_DIR_NAME,
+    TEMP_MANIFEST_FILENAME, TEMP_MERGED_FILENAME, TEMP_OWNER_MARKER_FILENAME,
+};
+use crate::errors::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// When this process first touched a temp directory, in Unix milliseconds
+static PROCESS_STARTED_MS: OnceLock<u64> = OnceLock::new();
+
+/// Process that created a session temp directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TempOwner {
+    /// Process id
+    pub pid: u32,
+    /// Process start time in Unix milliseconds, telling apart reused PIDs
+    pub started_at_ms: u64,
+}
+
+impl TempOwner {
+    /// The running process
+    pub fn current() -> Self {
+        let started_at_ms = *PROCESS_STARTED_MS.get_or_init(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64)
+        });
+        Self { pid: std::process::id(), started_at_ms }
+    }
+}
+
+/// File names inside one session's temp directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TempLayout {
+    dir: PathBuf,
+}
+
+impl TempLayout {
+    /// Layout for a session under the system temp directory
+    pub fn for_session(session_id: &str) -> Self {
+        Self::new(std::env::temp_dir().join(TEMP_DIR_NAME).join(session_id))
+    }
+
+    /// Layout rooted at `dir`
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The session temp directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Creates the session temp directory
+    pub fn create(&self) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| AppError::FileValidation(
+                format!("Cannot create session temp directory: {e}")
+            ))
+    }
+
+    /// Creates the session temp directory and marks it as this process's
+    ///
+    /// A non-empty directory owned by another process (or with no marker)
+    /// is an error, unless `clean_existing` is set, in which case its
+    /// contents are removed first.
+    pub fn claim(&self, clean_existing: bool) -> Result<()> {
+        let owner = TempOwner::current();
+        if self.holds_foreign_files(owner) {
+            if !clean_existing {
+                return Err(AppError::FileValidation(format!(
+                    "Session temp directory {} holds files from another run; remove it or retry with a new session",
+                    self.dir.display()
+                )));
+            }
+            log::warn!("Removing leftover session temp directory {}", self.dir.display());
+            std::fs::remove_dir_all(&self.dir)
+                .map_err(|e| AppError::FileValidation(
+                    format!("Cannot clean session temp directory: {e}")
+                ))?;
+        }
+        self.create()?;
+        let marker = serde_json::to_vec(&owner)
+            .map_err(|e| AppError::General(format!("Cannot serialize temp directory owner: {e}")))?;
+        std::fs::write(self.owner_marker(), marker)
+            .map_err(|e| AppError::FileValidation(
+                format!("Cannot mark session temp directory: {e}")
+            ))
+    }
+
+    /// Owner recorded in the directory's marker, if readable
+    pub fn owner(&self) -> Option<TempOwner> {
+        let marker = std::fs::read(self.owner_marker()).ok()?;
+        serde_json::from_slice(&marker).ok()
+    }
+
+    fn holds_foreign_files(&self, owner: TempOwner) -> bool {
+        let is_empty = std::fs::read_dir(&self.dir).map_or(true, |mut entries| entries.next().is_none());
+        !is_empty && self.owner() != Some(owner)
+    }
+
+    /// Marker naming the process that owns the directory
+    pub fn owner_marker(&self) -> PathBuf {
+        self.dir.join(TEMP_OWNER_MARKER_FILENAME)
+    }
+
+    /// Concat list of every input
+    pub fn concat_list(&self) -> PathBuf {
+        self.dir.join(TEMP_CONCAT_FILENAME)
+    }
+
+    /// Concat list of one segment's inputs
+    pub fn segment_concat_list(&self, index: usize) -> PathBuf {
+        self.dir.join(numbered(TEMP_CONCAT_FILENAME, index))
+    }
+
+    /// Intermediate output of one segment
+    pub fn segment_output(&self, index: usize) -> PathBuf {
+        self.dir.join(numbered(&format!("segment.{SEGMENT_OUTPUT_EXTENSION}"), index))
+    }
+
+    /// Concat list joining the segment outputs
+    pub fn segment_join_list(&self) -> PathBuf {
+        self.dir.join(SEGMENT_JOIN_LIST_FILENAME)
+    }
+
+    /// Merged output before metadata is written and it moves into place
+    pub fn merged_output(&self) -> PathBuf {
+        self.dir.join(TEMP_MERGED_FILENAME)
+    }
+
+    /// FFmpeg metadata file with the chapters mapped into the merged output
+    pub fn chapters_metadata(&self) -> PathBuf {
+        self.dir.join(TEMP_CHAPTERS_FILENAME)
+    }
+
+    /// Description of the run, kept for diagnosing leftover directories
+    pub fn manifest(&self) -> PathBuf {
+        self.dir.join(TEMP_MANIFEST_FILENAME)
+    }
+
+    /// Removes the session temp directory and everything in it
+    pub fn remove(&self, session_id: &str) -> Result<()> {
+        let mut guard = CleanupGuard::new(session_id.to_string());
+        guard.add_path(&self.dir);
+        guard.cleanup_now()
+    }
+}
+
+/// Inserts a zero-padded index before the extension: `concat.txt` -> `concat-0003.txt`
+fn numbered(base: &str, index: usize) -> String {
+    match base.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem}-{index:04}.{extension}"),
+        None => format!("{base}-{index:04}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_layout_names() {
+        let layout = TempLayout::new("/tmp/audiobook-boss/session-1");
+        let dir = Path::new("/tmp/audiobook-boss/session-1");
+        assert_eq!(layout.concat_list(), dir.join("concat.txt"));
+        assert_eq!(layout.segment_concat_list(2), dir.join("concat-0002.txt"));
+        assert_eq!(layout.segment_output(2), dir.join("segment-0002.m4a"));
+        assert_eq!(layout.segment_join_list(), dir.join("segments.txt"));
+        assert_eq!(layout.merged_output(), dir.join("merged.m4b"));
+        assert_eq!(layout.manifest(), dir.join("manifest.json"));
+        assert_eq!(layout.chapters_metadata(), dir.join("chapters.txt"));
+        assert_eq!(layout.owner_marker(), dir.join("owner.json"));
+
+        let session = TempLayout::for_session("abc");
+        assert_eq!(session.dir(), std::env::temp_dir().join(TEMP_DIR_NAME).join("abc"));
+    }
+
+    #[test]
+    fn test_claim_refuses_leftovers_from_another_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let layout = TempLayout::new(temp_dir.path().join("default-session"));
+        layout.create().unwrap();
+        let leftover = layout.concat_list();
+        std::fs::write(&leftover, b"file '/old/run.mp3'").unwrap();
+
+        // No marker
+        assert!(layout.claim(false).unwrap_err().to_string().contains("another run"));
+        assert!(leftover.exists());
+
+        // Marker from another process
+        let stranger = TempOwner { pid: std::process::id().wrapping_add(1), started_at_ms: 1 };
+        std::fs::write(layout.owner_marker(), serde_json::to_vec(&stranger).unwrap()).unwrap();
+        assert!(layout.claim(false).is_err());
+        assert_eq!(layout.owner(), Some(stranger));
+    }
+
+    #[test]
+    fn test_claim_cleans_first_and_reclaims_own_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let layout = TempLayout::new(temp_dir.path().join("default-session"));
+        layout.create().unwrap();
+        std::fs::write(layout.concat_list(), b"file '/old/run.mp3'").unwrap();
+
+        layout.claim(true).unwrap();
+        assert!(!layout.concat_list().exists());
+        assert_eq!(layout.owner(), Some(TempOwner::current()));
+
+        // The owning process may claim again without losing its files
+        std::fs::write(layout.concat_list(), b"file '/new/run.mp3'").unwrap();
+        layout.claim(false).unwrap();
+        assert!(layout.concat_list().exists());
+
+        // An empty directory is free to take
+        let empty = TempLayout::new(temp_dir.path().join("empty"));
+        empty.create().unwrap();
+        empty.claim(false).unwrap();
+    }
+
+    #[test]
+    fn test_remove_deletes_everything_generated() {
+        let temp_dir = TempDir::new().unwrap();
+        let layout = TempLayout::new(temp_dir.path().join("session-1"));
+        layout.create().unwrap();
+
+        let mut generated = vec![layout.concat_list(), layout.segment_join_list(), layout.merged_output(), layout.manifest()];
+        for index in 0..3 {
+            generated.push(layout.segment_concat_list(index));
+            generated.push(layout.segment_output(index));
+        }
+        for path in &generated {
+            assert_eq!(path.parent(), Some(layout.dir()));
+            std::fs::write(path, b"data").unwrap();
+        }
+        assert_eq!(std::fs::read_dir(layout.dir()).unwrap().count(), generated.len());
+
+        layout.remove("session-1").unwrap();
+        assert!(!layout.dir().exists());
+        assert!(temp_dir.path().exists());
+    }
+}
@@ -0,0 +1,195 @@
+//! Per-stage timing for processing sessions
+//!
+//! Each session records when every pipeline stage started and ended so we
+//! can tell whether time goes to decoding, encoding, metadata or moving
+//! files. Recent timelines stay queryable by session ID after the session
+//! itself has been dropped.
+
+use super::constants::MAX_TRACKED_TIMELINES;
+use crate::errors::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Pipeline stages that are timed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineStage {
+    /// Validating inputs and preparing the workspace
+    Analyzing,
+    /// Encoding inputs (or segments) with FFmpeg
+    Converting,
+    /// Joining segment outputs and verifying the merge
+    Merging,
+    /// Writing tags and cover art
+    WritingMetadata,
+    /// Moving the finished file to its final location
+    Publish,
+    /// Removing temporary files
+    Cleanup,
+}
+
+/// Start and duration of one stage, relative to the session start
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageTiming {
+    /// Stage being timed
+    pub stage: TimelineStage,
+    /// Milliseconds from session start to stage start
+    pub started_at_ms: u64,
+    /// Milliseconds spent in the stage so far
+    pub duration_ms: u64,
+    /// False while the stage is still running
+    pub finished: bool,
+}
+
+/// Records stage transitions for a single session
+#[derive(Debug)]
+pub struct StageTimeline {
+    started: Instant,
+    /// (stage, start offset, end offset when finished)
+    entries: Vec<(TimelineStage, Duration, Option<Duration>)>,
+}
+
+impl StageTimeline {
+    /// Creates an empty timeline starting now
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    fn starting_at(started: Instant) -> Self {
+        Self { started, entries: Vec::new() }
+    }
+
+    /// Starts a stage, ending whichever stage was running
+    pub fn start(&mut self, stage: TimelineStage) {
+        self.start_at(stage, Instant::now());
+    }
+
+    /// Ends the running stage, if any
+    pub fn finish(&mut self) {
+        self.finish_at(Instant::now());
+    }
+
+    fn start_at(&mut self, stage: TimelineStage, now: Instant) {
+        self.finish_at(now);
+        let offset = now.saturating_duration_since(self.started);
+        self.entries.push((stage, offset, None));
+    }
+
+    fn finish_at(&mut self, now: Instant) {
+        if let Some((_, _, end @ None)) = self.entries.last_mut() {
+            *end = Some(now.saturating_duration_since(self.started));
+        }
+    }
+
+    /// Returns the timings recorded so far
+    pub fn snapshot(&self) -> Vec<StageTiming> {
+        self.snapshot_at(Instant::now())
+    }
+
+    fn snapshot_at(&self, now: Instant) -> Vec<StageTiming> {
+        let elapsed = now.saturating_duration_since(self.started);
+        self.entries.iter().map(|&(stage, start, end)| StageTiming {
+            stage,
+            started_at_ms: start.as_millis() as u64,
+            duration_ms: end.unwrap_or(elapsed).saturating_sub(start).as_millis() as u64,
+            finished: end.is_some(),
+        }).collect()
+    }
+}
+
+impl Default for StageTimeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Timeline shared between a session and the registry
+pub type SharedTimeline = Arc<Mutex<StageTimeline>>;
+
+/// Most recent session timelines, newest last
+static RECENT_TIMELINES: OnceLock<Mutex<VecDeque<(String, SharedTimeline)>>> = OnceLock::new();
+
+/// Makes a session's timeline queryable by ID, evicting the oldest
+pub fn register_timeline(session_id: String, timeline: SharedTimeline) {
+    let Ok(mut recent) = RECENT_TIMELINES.get_or_init(Default::default).lock() else { return };
+    recent.retain(|(id, _)| *id != session_id);
+    if recent.len() >= MAX_TRACKED_TIMELINES {
+        recent.pop_front();
+    }
+    recent.push_back((session_id, timeline));
+}
+
+/// Looks up a timeline by session ID, or the most recent one when None
+pub fn session_timeline(session_id: Option<&str>) -> Result<Vec<StageTiming>> {
+    let recent = RECENT_TIMELINES.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Timeline registry poisoned".to_string()))?;
+    let entry = match session_id {
+        Some(id) => recent.iter().rev().find(|(entry_id, _)| entry_id == id),
+        None => recent.back(),
+    };
+    let (_, timeline) = entry.ok_or_else(|| AppError::InvalidInput(match session_id {
+        Some(id) => format!("No timeline recorded for session: {id}"),
+        None => "No processing session has run yet".to_string(),
+    }))?;
+    let timeline = timeline.lock()
+        .map_err(|_| AppError::General("Session timeline poisoned".to_string()))?;
+    Ok(timeline.snapshot())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starting_a_stage_ends_the_previous_one() {
+        let origin = Instant::now();
+        let mut timeline = StageTimeline::starting_at(origin);
+        timeline.start_at(TimelineStage::Analyzing, origin);
+        timeline.start_at(TimelineStage::Converting, origin + Duration::from_millis(150));
+        timeline.finish_at(origin + Duration::from_millis(1150));
+
+        let timings = timeline.snapshot_at(origin + Duration::from_secs(5));
+        assert_eq!(timings, vec![
+            StageTiming { stage: TimelineStage::Analyzing, started_at_ms: 0, duration_ms: 150, finished: true },
+            StageTiming { stage: TimelineStage::Converting, started_at_ms: 150, duration_ms: 1000, finished: true },
+        ]);
+    }
+
+    #[test]
+    fn test_running_stage_reports_elapsed_time() {
+        let origin = Instant::now();
+        let mut timeline = StageTimeline::starting_at(origin);
+        timeline.start_at(TimelineStage::WritingMetadata, origin + Duration::from_millis(10));
+
+        let timings = timeline.snapshot_at(origin + Duration::from_millis(60));
+        assert_eq!(timings[0].duration_ms, 50);
+        assert!(!timings[0].finished);
+    }
+
+    #[test]
+    fn test_finish_without_stage_is_noop() {
+        let mut timeline = StageTimeline::new();
+        timeline.finish();
+        assert!(timeline.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_registry_lookup_by_id() {
+        let timeline: SharedTimeline = Arc::default();
+        timeline.lock().unwrap().start(TimelineStage::Publish);
+        register_timeline("timeline-test-session".to_string(), timeline);
+
+        let timings = session_timeline(Some("timeline-test-session")).unwrap();
+        assert_eq!(timings[0].stage, TimelineStage::Publish);
+        assert!(session_timeline(Some("no-such-session")).is_err());
+    }
+
+    #[test]
+    fn test_stage_serializes_snake_case() {
+        let json = serde_json::to_string(&TimelineStage::WritingMetadata).unwrap();
+        assert_eq!(json, "\"writing_metadata\"");
+    }
+}
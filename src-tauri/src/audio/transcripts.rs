@@ -0,0 +1,295 @@
+//! Transcript passthrough next to the output
+//!
+//! A transcript sharing its stem with an input (`01.mp3` + `01.srt`) is
+//! picked up for that input; without any, a single `.srt`/`.vtt` beside the
+//! inputs counts as the whole book's. Timed parts are joined into one SRT,
+//! each shifted by the duration of the inputs before it and renumbered.
+//! Plain text parts are joined into a `.txt` when there's nothing timed.
+
+use crate::errors::{AppError, Result};
+use std::path::{Path, PathBuf};
+
+/// Transcript formats in order of preference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptKind {
+    /// SubRip `.srt`
+    Srt,
+    /// WebVTT `.vtt`
+    Vtt,
+    /// Untimed `.txt`
+    Text,
+}
+
+impl TranscriptKind {
+    const ALL: [TranscriptKind; 3] = [TranscriptKind::Srt, TranscriptKind::Vtt, TranscriptKind::Text];
+
+    fn extension(self) -> &'static str {
+        match self {
+            TranscriptKind::Srt => "srt",
+            TranscriptKind::Vtt => "vtt",
+            TranscriptKind::Text => "txt",
+        }
+    }
+
+    fn is_timed(self) -> bool {
+        self != TranscriptKind::Text
+    }
+}
+
+/// One timed subtitle entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cue {
+    /// Start in milliseconds
+    pub start_ms: u64,
+    /// End in milliseconds
+    pub end_ms: u64,
+    /// Cue text, possibly several lines
+    pub text: String,
+}
+
+/// A transcript file and where its input starts in the book
+#[derive(Debug, Clone, PartialEq)]
+struct TranscriptPart {
+    path: PathBuf,
+    kind: TranscriptKind,
+    offset_ms: u64,
+}
+
+/// Copies the inputs' transcripts next to `output` as `{output stem}.srt` (or `.txt`)
+///
+/// `inputs` pairs each input with its duration in seconds, in book order.
+/// Returns the written file, or None when no input has a transcript.
+pub fn publish_transcripts(inputs: &[(PathBuf, f64)], output: &Path) -> Result<Option<PathBuf>> {
+    let offsets = cumulative_offsets_ms(inputs.iter().map(|(_, duration)| *duration));
+    let mut parts: Vec<TranscriptPart> = inputs.iter().zip(offsets)
+        .filter_map(|((input, _), offset_ms)| {
+            find_transcript(input).map(|(path, kind)| TranscriptPart { path, kind, offset_ms })
+        })
+        .collect();
+    if parts.is_empty() {
+        parts.extend(whole_book_transcript(inputs).map(|(path, kind)| TranscriptPart { path, kind, offset_ms: 0 }));
+    }
+    if parts.is_empty() {
+        return Ok(None);
+    }
+
+    let timed = parts.iter().any(|part| part.kind.is_timed());
+    let (contents, extension) = if timed {
+        let skipped = parts.iter().filter(|part| !part.kind.is_timed()).count();
+        if skipped > 0 {
+            log::warn!("Skipping {skipped} plain text transcript(s) that can't be merged with timed ones");
+        }
+        (merge_timed(&parts)?, TranscriptKind::Srt.extension())
+    } else {
+        (merge_text(&parts)?, TranscriptKind::Text.extension())
+    };
+
+    let destination = output.with_extension(extension);
+    if parts.len() == 1 && parts[0].path == destination {
+        // The only transcript already sits where it would be written
+        return Ok(Some(destination));
+    }
+    std::fs::write(&destination, contents)?;
+    Ok(Some(destination))
+}
+
+/// Transcript next to `input` with the same stem, preferring SRT over VTT over text
+fn find_transcript(input: &Path) -> Option<(PathBuf, TranscriptKind)> {
+    TranscriptKind::ALL.iter()
+        .map(|&kind| (input.with_extension(kind.extension()), kind))
+        .find(|(path, _)| path != input && path.is_file())
+}
+
+/// The only timed transcript in the inputs' shared directory, if exactly one exists
+fn whole_book_transcript(inputs: &[(PathBuf, f64)]) -> Option<(PathBuf, TranscriptKind)> {
+    let dir = inputs.first()?.0.parent()?;
+    if inputs.iter().any(|(input, _)| input.parent() != Some(dir)) {
+        return None;
+    }
+    let mut candidates = std::fs::read_dir(dir).ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| {
+            let extension = path.extension()?.to_str()?.to_lowercase();
+            let kind = [TranscriptKind::Srt, TranscriptKind::Vtt].into_iter()
+                .find(|kind| kind.extension() == extension)?;
+            path.is_file().then_some((path, kind))
+        });
+    let only = candidates.next()?;
+    candidates.next().is_none().then_some(only)
+}
+
+/// Start of each input in milliseconds; summed in seconds first so rounding doesn't drift
+fn cumulative_offsets_ms<I: IntoIterator<Item = f64>>(durations: I) -> Vec<u64> {
+    let mut elapsed = 0.0;
+    durations.into_iter()
+        .map(|duration| {
+            let offset = (elapsed * 1000.0_f64).round() as u64;
+            elapsed += duration.max(0.0);
+            offset
+        })
+        .collect()
+}
+
+fn read_transcript(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path)?;
+    let text = String::from_utf8(bytes).map_err(|_| AppError::FileValidation(
+        format!("Transcript is not UTF-8 text: {}", path.display())
+    ))?;
+    Ok(text.strip_prefix('\u{FEFF}').unwrap_or(&text).replace("\r\n", "\n"))
+}
+
+fn merge_timed(parts: &[TranscriptPart]) -> Result<String> {
+    let mut cues = Vec::new();
+    for part in parts.iter().filter(|part| part.kind.is_timed()) {
+        cues.extend(parse_cues(&read_transcript(&part.path)?).into_iter().map(|cue| Cue {
+            start_ms: cue.start_ms + part.offset_ms,
+            end_ms: cue.end_ms + part.offset_ms,
+            text: cue.text,
+        }));
+    }
+    Ok(format_srt(&cues))
+}
+
+fn merge_text(parts: &[TranscriptPart]) -> Result<String> {
+    let texts = parts.iter()
+        .map(|part| read_transcript(&part.path).map(|text| text.trim().to_string()))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(format!("{}\n", texts.join("\n\n")))
+}
+
+/// Parses the cues of an SRT or WebVTT document
+///
+/// Blocks without a timing line (the `WEBVTT` header, `NOTE` and `STYLE`
+/// blocks) are skipped, as are cue identifiers and VTT cue settings.
+pub fn parse_cues(document: &str) -> Vec<Cue> {
+    document.split("\n\n")
+        .filter_map(|block| {
+            let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+            let (start, end) = lines.next()?.split_once("-->")?;
+            let start_ms = parse_timestamp(start.trim())?;
+            let end_ms = parse_timestamp(end.split_whitespace().next()?)?;
+            let text = lines.collect::<Vec<_>>().join("\n");
+            Some(Cue { start_ms, end_ms, text })
+        })
+        .collect()
+}
+
+/// Parses `HH:MM:SS,mmm`, `HH:MM:SS.mmm` or `MM:SS.mmm` into milliseconds
+pub fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let (clock, millis) = timestamp.split_once([',', '.'])?;
+    if millis.len() != 3 {
+        return None;
+    }
+    let millis: u64 = millis.parse().ok()?;
+    let fields = clock.split(':')
+        .map(|field| field.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let (hours, minutes, seconds) = match fields[..] {
+        [hours, minutes, seconds] => (hours, minutes, seconds),
+        [minutes, seconds] => (0, minutes, seconds),
+        _ => return None,
+    };
+    if minutes >= 60 || seconds >= 60 {
+        return None;
+    }
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + millis)
+}
+
+/// Formats milliseconds as an SRT timestamp: `HH:MM:SS,mmm`
+pub fn format_srt_timestamp(ms: u64) -> String {
+    let (seconds, millis) = (ms / 1000, ms % 1000);
+    format!("{:02}:{:02}:{:02},{millis:03}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Writes cues as an SRT document numbered from 1
+pub fn format_srt(cues: &[Cue]) -> String {
+    cues.iter().enumerate()
+        .map(|(i, cue)| format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            format_srt_timestamp(cue.start_ms),
+            format_srt_timestamp(cue.end_ms),
+            cue.text
+        ))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn cue(start_ms: u64, end_ms: u64, text: &str) -> Cue {
+        Cue { start_ms, end_ms, text: text.to_string() }
+    }
+
+    #[test]
+    fn test_timestamps() {
+        assert_eq!(parse_timestamp("01:02:03,456"), Some(3_723_456));
+        assert_eq!(parse_timestamp("01:02:03.456"), Some(3_723_456));
+        assert_eq!(parse_timestamp("02:03.456"), Some(123_456));
+        assert_eq!(parse_timestamp("00:61:00,000"), None);
+        assert_eq!(parse_timestamp("00:00:01,5"), None);
+        assert_eq!(format_srt_timestamp(3_723_456), "01:02:03,456");
+        assert_eq!(format_srt_timestamp(100 * 3_600_000 + 5), "100:00:00,005");
+    }
+
+    #[test]
+    fn test_cumulative_offsets() {
+        assert_eq!(cumulative_offsets_ms([10.0, 20.5, 30.0]), [0, 10_000, 30_500]);
+        // Rounding each duration would drift by 1 ms per file
+        assert_eq!(cumulative_offsets_ms([0.0004; 4]), [0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn test_parse_srt_and_vtt() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,500\nHello\nthere\n\n2\n00:00:03,000 --> 00:00:04,000\nBye\n";
+        assert_eq!(parse_cues(srt), [cue(1000, 2500, "Hello\nthere"), cue(3000, 4000, "Bye")]);
+
+        let vtt = "WEBVTT\n\nNOTE made by hand\n\nintro\n00:01.000 --> 00:02.000 align:start\nHi\n";
+        assert_eq!(parse_cues(vtt), [cue(1000, 2000, "Hi")]);
+    }
+
+    #[test]
+    fn test_merge_offsets_and_renumbers() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        std::fs::write(dir.join("01.srt"), "1\n00:00:01,000 --> 00:00:02,000\nOne\n").unwrap();
+        std::fs::write(dir.join("03.vtt"), "WEBVTT\n\n00:00.500 --> 00:01.000\nThree\n").unwrap();
+        let inputs = [
+            (dir.join("01.mp3"), 60.0),
+            (dir.join("02.mp3"), 90.25), // no transcript, only shifts later parts
+            (dir.join("03.mp3"), 30.0),
+        ];
+
+        let written = publish_transcripts(&inputs, &dir.join("out").join("Book.m4b"));
+        assert!(written.is_err()); // output directory doesn't exist
+
+        std::fs::create_dir(dir.join("out")).unwrap();
+        let written = publish_transcripts(&inputs, &dir.join("out").join("Book.m4b")).unwrap().unwrap();
+        assert_eq!(written, dir.join("out").join("Book.srt"));
+        assert_eq!(
+            std::fs::read_to_string(written).unwrap(),
+            "1\n00:00:01,000 --> 00:00:02,000\nOne\n\n2\n00:02:30,750 --> 00:02:31,250\nThree\n\n"
+        );
+    }
+
+    #[test]
+    fn test_whole_book_and_text_transcripts() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let inputs = [(dir.join("01.mp3"), 60.0), (dir.join("02.mp3"), 60.0)];
+        let output = dir.join("Book.m4b");
+        assert_eq!(publish_transcripts(&inputs, &output).unwrap(), None);
+
+        std::fs::write(dir.join("full book.srt"), "1\n00:01:30,000 --> 00:01:31,000\nMiddle\n").unwrap();
+        assert_eq!(publish_transcripts(&inputs, &output).unwrap(), Some(dir.join("Book.srt")));
+        assert!(std::fs::read_to_string(dir.join("Book.srt")).unwrap().contains("00:01:30,000"));
+
+        // Per-input text transcripts win over the whole-book file
+        std::fs::write(dir.join("01.txt"), "Part one.\n").unwrap();
+        std::fs::write(dir.join("02.txt"), "\u{FEFF}Part two.\r\n").unwrap();
+        assert_eq!(publish_transcripts(&inputs, &output).unwrap(), Some(dir.join("Book.txt")));
+        assert_eq!(std::fs::read_to_string(dir.join("Book.txt")).unwrap(), "Part one.\n\nPart two.\n");
+    }
+}
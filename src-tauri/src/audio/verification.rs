@@ -0,0 +1,388 @@
+//! Background verification of finished books
+//!
+//! A full decode catches truncated or corrupt output, but run inline it
+//! doubles processing time. With `verify_output` set, a finished book is
+//! queued instead: a background worker decodes it once no encode is
+//! running, records the outcome on the run's history record and emits
+//! `verification-complete`. The book is usable as soon as it is published.
+//! Books over 4 GB are also checked to decode for their full duration,
+//! since a broken 64-bit offset can cut the readable audio short without
+//! any decoder error.
+
+use super::constants::{LARGE_OUTPUT_DURATION_TOLERANCE_SECS, VERIFICATION_BUSY_POLL_MS, VERIFICATION_MAX_CONCURRENT};
+use super::job_history::record_verification;
+use super::large_output::is_large_output;
+use super::progress_sink::ProgressSink;
+use super::session::active_session_count;
+use crate::errors::{AppError, Result};
+use crate::ffmpeg::{locate_ffmpeg, FFmpegError};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Process-wide queue, started once the app handle is available
+static VERIFICATION_QUEUE: OnceLock<VerificationQueue> = OnceLock::new();
+
+/// A published book waiting to be verified
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationTask {
+    /// Session that produced the book
+    pub session_id: String,
+    /// The published book
+    pub output_path: PathBuf,
+}
+
+/// Result of verifying one book
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationOutcome {
+    /// Whether the whole file decoded without errors
+    pub passed: bool,
+    /// What the decoder reported, when verification failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unix timestamp (seconds) of the verification
+    pub verified_at: u64,
+}
+
+impl VerificationOutcome {
+    fn from_result(result: Result<()>) -> Self {
+        Self {
+            passed: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+            verified_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// Payload of the `verification-complete` event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationComplete {
+    /// Session that produced the book
+    pub session_id: String,
+    /// The verified book
+    pub output_path: PathBuf,
+    /// What verification found
+    pub outcome: VerificationOutcome,
+}
+
+/// Decodes the whole book and fails on any decoder error
+///
+/// Uses a single decoder thread so it stays light next to other work.
+/// Books over 4 GB must also decode to the end of their container duration.
+pub fn verify_audiobook(path: &Path) -> Result<()> {
+    if !path.is_file() {
+        return Err(AppError::FileValidation(format!("Output not found: {}", path.display())));
+    }
+    let large = is_large_output(std::fs::metadata(path)?.len());
+    let mut cmd = Command::new(locate_ffmpeg()?);
+    cmd.args(["-v", "error", "-nostdin", "-threads", "1", "-i"])
+        .arg(path)
+        .args(["-map", "0:a", "-f", "null", "-"]);
+    if large {
+        cmd.args(["-progress", "pipe:1", "-nostats"]);
+    }
+    let output = cmd
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))?;
+
+    let errors = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if !output.status.success() || !errors.is_empty() {
+        return Err(FFmpegError::ExecutionFailed(format!("Verification decode failed: {errors}")).into());
+    }
+    if large {
+        let expected = container_duration_secs(path)?;
+        check_full_duration(decoded_secs(&String::from_utf8_lossy(&output.stdout)), expected)?;
+    }
+    Ok(())
+}
+
+/// Duration the container declares, in seconds
+fn container_duration_secs(path: &Path) -> Result<f64> {
+    use lofty::file::AudioFile as _;
+    let tagged = lofty::probe::Probe::open(path)?.read()?;
+    Ok(tagged.properties().duration().as_secs_f64())
+}
+
+/// Last decoded position in seconds from FFmpeg `-progress` output
+pub fn decoded_secs(progress: &str) -> Option<f64> {
+    progress.lines()
+        .filter_map(|line| line.trim().strip_prefix("out_time_us="))
+        .filter_map(|value| value.parse::<i64>().ok())
+        .next_back()
+        .map(|us| us as f64 / 1_000_000.0)
+}
+
+/// Fails when the decode stopped short of the container duration
+fn check_full_duration(decoded: Option<f64>, expected: f64) -> Result<()> {
+    let decoded = decoded.unwrap_or(0.0);
+    if decoded + LARGE_OUTPUT_DURATION_TOLERANCE_SECS < expected {
+        return Err(AppError::FileValidation(format!(
+            "Only {decoded:.1}s of {expected:.1}s decoded; the large output is truncated or has broken offsets"
+        )));
+    }
+    Ok(())
+}
+
+/// Worker pool running verifications one task at a time per worker
+pub struct VerificationQueue {
+    sender: Mutex<Sender<VerificationTask>>,
+}
+
+impl VerificationQueue {
+    /// Starts `workers` threads
+    ///
+    /// Before each task a worker waits, polling every `poll_interval`, until
+    /// `is_busy` is false, so verification never competes with an encode.
+    pub fn start<V, B, D>(workers: usize, poll_interval: Duration, verify: V, is_busy: B, on_done: D) -> Self
+    where
+        V: Fn(&Path) -> Result<()> + Send + Sync + 'static,
+        B: Fn() -> bool + Send + Sync + 'static,
+        D: Fn(VerificationComplete) + Send + Sync + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let verify = Arc::new(verify);
+        let is_busy = Arc::new(is_busy);
+        let on_done = Arc::new(on_done);
+        for index in 0..workers.max(1) {
+            let (receiver, verify, is_busy, on_done) = (receiver.clone(), verify.clone(), is_busy.clone(), on_done.clone());
+            let spawned = std::thread::Builder::new()
+                .name(format!("verification-{index}"))
+                .spawn(move || run_worker(&receiver, poll_interval, &*verify, &*is_busy, &*on_done));
+            if let Err(e) = spawned {
+                log::warn!("Cannot start verification worker: {e}");
+            }
+        }
+        Self { sender: Mutex::new(sender) }
+    }
+
+    /// Queues a book for verification
+    pub fn enqueue(&self, task: VerificationTask) -> Result<()> {
+        self.sender.lock()
+            .map_err(|_| AppError::General("Verification queue poisoned".to_string()))?
+            .send(task)
+            .map_err(|_| AppError::General("Verification workers have stopped".to_string()))
+    }
+}
+
+fn run_worker(
+    receiver: &Mutex<Receiver<VerificationTask>>,
+    poll_interval: Duration,
+    verify: &dyn Fn(&Path) -> Result<()>,
+    is_busy: &dyn Fn() -> bool,
+    on_done: &dyn Fn(VerificationComplete),
+) {
+    loop {
+        // Hold the lock only while taking a task, so other workers keep going
+        let task = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+        let Ok(task) = task else {
+            return;
+        };
+        while is_busy() {
+            std::thread::sleep(poll_interval);
+        }
+        log::info!("Verifying {}", task.output_path.display());
+        let outcome = VerificationOutcome::from_result(verify(&task.output_path));
+        on_done(VerificationComplete { session_id: task.session_id, output_path: task.output_path, outcome });
+    }
+}
+
+/// Starts the process-wide queue, reporting results to history and the frontend
+pub fn init_verification_queue(sink: Arc<dyn ProgressSink>) {
+    let queue = VerificationQueue::start(
+        VERIFICATION_MAX_CONCURRENT,
+        Duration::from_millis(VERIFICATION_BUSY_POLL_MS),
+        verify_audiobook,
+        || active_session_count() > 0,
+        move |complete| {
+            if !complete.outcome.passed {
+                log::warn!("Verification failed for {}: {:?}", complete.output_path.display(), complete.outcome.error);
+            }
+            record_verification(&complete.session_id, &complete.outcome);
+            if let Err(e) = sink.emit("verification-complete", &complete) {
+                log::warn!("Cannot emit verification result: {e}");
+            }
+        },
+    );
+    if VERIFICATION_QUEUE.set(queue).is_err() {
+        log::warn!("Verification queue already initialized");
+    }
+}
+
+/// Queues a published book on the process-wide queue
+pub fn enqueue_verification(session_id: &str, output_path: &Path) {
+    let Some(queue) = VERIFICATION_QUEUE.get() else {
+        log::warn!("Verification queue not running, skipping {}", output_path.display());
+        return;
+    };
+    let task = VerificationTask { session_id: session_id.to_string(), output_path: output_path.to_path_buf() };
+    if let Err(e) = queue.enqueue(task) {
+        log::warn!("Cannot queue verification: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    const POLL: Duration = Duration::from_millis(5);
+
+    fn task(name: &str) -> VerificationTask {
+        VerificationTask { session_id: name.to_string(), output_path: PathBuf::from(format!("/books/{name}.m4b")) }
+    }
+
+    fn collect(results: &Receiver<VerificationComplete>, count: usize) -> Vec<VerificationComplete> {
+        let mut collected: Vec<VerificationComplete> = (0..count)
+            .map(|_| results.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        collected.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        collected
+    }
+
+    #[test]
+    fn test_full_duration_check() {
+        let progress = "out_time_us=1000000\nprogress=continue\nout_time_us=N/A\nout_time_us=107999500000\nprogress=end\n";
+        assert_eq!(decoded_secs(progress), Some(107999.5));
+        assert_eq!(decoded_secs("progress=end"), None);
+        assert!(check_full_duration(Some(107999.5), 108000.0).is_ok());
+        let error = check_full_duration(Some(54000.0), 108000.0).unwrap_err().to_string();
+        assert!(error.contains("Only 54000.0s of 108000.0s decoded"));
+        assert!(check_full_duration(None, 108000.0).is_err());
+    }
+
+    /// Encodes a book over 4 GB (about 31 hours at 320 kbps) and verifies it
+    #[test]
+    #[ignore = "encodes a 4+ GB file; run with --ignored"]
+    fn test_huge_output_encodes_and_verifies() {
+        use crate::audio::media_pipeline::build_merge_command;
+        use crate::audio::settings::ResolvedAudioSettings;
+        use crate::audio::AudioSettings;
+
+        let ffmpeg = locate_ffmpeg().unwrap();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let hour = temp_dir.path().join("hour.wav");
+        let status = Command::new(&ffmpeg)
+            .args(["-v", "error", "-f", "lavfi", "-i", "anoisesrc=duration=3600:sample_rate=48000", "-ac", "2", "-y"])
+            .arg(&hour)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let list = temp_dir.path().join("concat.txt");
+        std::fs::write(&list, format!("file '{}'\n", hour.display()).repeat(31)).unwrap();
+
+        let output = temp_dir.path().join("huge.m4b");
+        let resolved = ResolvedAudioSettings { bitrate: 320, sample_rate: 48000, sample_rate_detected: false, channels: 2 };
+        let result = build_merge_command(&list, &output, &AudioSettings::default(), &resolved, &[hour], None)
+            .unwrap()
+            .output()
+            .unwrap();
+        assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+        assert!(is_large_output(std::fs::metadata(&output).unwrap().len()));
+        verify_audiobook(&output).unwrap();
+    }
+
+    #[test]
+    fn test_outcomes_reach_on_done() {
+        let (done, results) = mpsc::channel();
+        let done = Mutex::new(done);
+        let queue = VerificationQueue::start(
+            1,
+            POLL,
+            |path: &Path| match path.to_string_lossy().contains("broken") {
+                true => Err(AppError::General("truncated".to_string())),
+                false => Ok(()),
+            },
+            || false,
+            move |complete| done.lock().unwrap().send(complete).unwrap(),
+        );
+        for name in ["a-good", "b-broken", "c-good"] {
+            queue.enqueue(task(name)).unwrap();
+        }
+
+        let results = collect(&results, 3);
+        let passed: Vec<(&str, bool)> = results.iter().map(|r| (r.session_id.as_str(), r.outcome.passed)).collect();
+        assert_eq!(passed, [("a-good", true), ("b-broken", false), ("c-good", true)]);
+        assert!(results[1].outcome.error.as_deref().unwrap().contains("truncated"));
+        assert_eq!(results[1].output_path, PathBuf::from("/books/b-broken.m4b"));
+    }
+
+    #[test]
+    fn test_worker_waits_while_an_encode_runs() {
+        let busy = Arc::new(AtomicBool::new(true));
+        let verified = Arc::new(AtomicUsize::new(0));
+        let (done, results) = mpsc::channel();
+        let done = Mutex::new(done);
+        let counter = verified.clone();
+        let queue = VerificationQueue::start(
+            1,
+            POLL,
+            move |_: &Path| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            },
+            {
+                let busy = busy.clone();
+                move || busy.load(Ordering::SeqCst)
+            },
+            move |complete| done.lock().unwrap().send(complete).unwrap(),
+        );
+        queue.enqueue(task("a")).unwrap();
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(verified.load(Ordering::SeqCst), 0, "verification must wait for the encode");
+        busy.store(false, Ordering::SeqCst);
+        assert!(collect(&results, 1)[0].outcome.passed);
+        assert_eq!(verified.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_concurrency_limit() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (done, results) = mpsc::channel();
+        let done = Mutex::new(done);
+        let (running_in, peak_in) = (running.clone(), peak.clone());
+        let queue = VerificationQueue::start(
+            2,
+            POLL,
+            move |_: &Path| {
+                let now = running_in.fetch_add(1, Ordering::SeqCst) + 1;
+                peak_in.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(30));
+                running_in.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            },
+            || false,
+            move |complete| done.lock().unwrap().send(complete).unwrap(),
+        );
+        let started = Instant::now();
+        for index in 0..6 {
+            queue.enqueue(task(&format!("book-{index}"))).unwrap();
+        }
+
+        assert_eq!(collect(&results, 6).len(), 6);
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+        // Two workers finish six 30 ms tasks in about 90 ms, not 180 ms
+        assert!(started.elapsed() < Duration::from_millis(170));
+    }
+
+    #[test]
+    fn test_missing_output_fails_verification() {
+        let error = verify_audiobook(Path::new("/nonexistent/book.m4b")).unwrap_err();
+        assert!(error.to_string().contains("Output not found"));
+    }
+}
@@ -0,0 +1,13 @@
+//! Writes the TypeScript declarations of the IPC contracts
+//!
+//! `cargo run --features ts-export --bin generate_ts_types [output]`, run
+//! from `src-tauri`; the output defaults to `../src/types/contracts.ts`.
+
+use std::path::PathBuf;
+
+fn main() -> std::io::Result<()> {
+    let output = std::env::args_os().nth(1).map_or_else(|| PathBuf::from("../src/types/contracts.ts"), PathBuf::from);
+    std::fs::write(&output, audiobook_boss_lib::contracts::typescript_definitions())?;
+    println!("Wrote {}", output.display());
+    Ok(())
+}
@@ -1,12 +1,15 @@
 // Basic Tauri commands module
 // This module contains simple commands for testing Tauri integration
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use crate::ffmpeg;
-use crate::errors::{AppError, Result};
-use crate::metadata::{AudiobookMetadata, read_metadata, write_metadata};
-use crate::audio::{AudioSettings, file_list::FileListInfo};
+use crate::errors::{AppError, CancelReason, Result};
+use crate::metadata::{AudiobookMetadata, read_metadata};
+use crate::metadata::history::{global_history, snapshot_before_write};
+use crate::audio::{AnalysisDepth, AudioSettings, chapters::{ChapterFormat, ChapterSpec}, file_list::FileListInfo, session::JobLabel};
+use crate::audio::input_spec::{attach_input_options, resolve_input_order, InputFileSpec};
 use crate::audio::constants::*;
+use crate::util::path_policy::check_granted;
 
 /// Simple ping command that returns "pong"
 /// Used for testing basic Tauri command functionality
@@ -29,6 +32,7 @@ pub fn validate_files(file_paths: Vec<String>) -> Result<String> {
     if file_paths.is_empty() {
         return Err(AppError::InvalidInput("No files provided for validation".to_string()));
     }
+    check_granted(&file_paths)?;
 
     let mut validated_count = 0;
     let mut missing_files = Vec::new();
@@ -61,6 +65,50 @@ pub fn get_ffmpeg_version() -> Result<String> {
     Ok(ffmpeg::command::FFmpegCommand::version()?)
 }
 
+/// Reports which FFmpeg is in use (bundled, legacy, system or override)
+/// and whether it has the AAC encoder the app needs
+#[tauri::command]
+pub fn get_ffmpeg_diagnostics() -> Result<ffmpeg::command::FFmpegDiagnostics> {
+    Ok(ffmpeg::command::diagnostics(FFMPEG_AUDIO_CODEC)?)
+}
+
+/// Runs the whole pipeline on two synthesized tones and reports each step
+/// For support and CI: the report names the first step that failed
+#[tauri::command]
+pub async fn run_self_test() -> Result<crate::audio::self_test::SelfTestReport> {
+    Ok(crate::audio::self_test::run_self_test().await)
+}
+
+/// Sets the FFmpeg binary used instead of the bundled or system one, or clears it with null
+/// The binary must have been opened in the app, like any other command path
+#[tauri::command]
+pub fn set_ffmpeg_override(path: Option<String>) -> Result<()> {
+    if let Some(path) = &path {
+        check_granted(&[path])?;
+    }
+    let path = path.map(PathBuf::from);
+    if let Some(path) = path.as_deref().filter(|path| !path.is_file()) {
+        return Err(AppError::FileValidation(format!("FFmpeg override not found: {}", path.display())));
+    }
+    ffmpeg::set_ffmpeg_override(path.as_deref());
+    Ok(())
+}
+
+/// Returns ffprobe's raw JSON report (format, streams, chapters) for a file
+/// Escape hatch for debugging inputs the analysis doesn't explain
+#[tauri::command]
+pub fn probe_file_raw(file_path: String) -> Result<serde_json::Value> {
+    check_granted(&[&file_path])?;
+    let path = PathBuf::from(&file_path);
+    if !path.exists() {
+        return Err(AppError::FileValidation(format!("File not found: {file_path}")));
+    }
+    if !path.is_file() {
+        return Err(AppError::FileValidation(format!("Path is not a file: {file_path}")));
+    }
+    Ok(ffmpeg::probe::probe_raw(&path)?)
+}
+
 /// Basic merge command for two audio files
 /// Merges files to a fixed output location for testing
 #[tauri::command]
@@ -68,6 +116,7 @@ pub fn merge_audio_files(
     file1: String, 
     file2: String
 ) -> Result<String> {
+    check_granted(&[&file1, &file2])?;
     let input1 = PathBuf::from(&file1);
     let input2 = PathBuf::from(&file2);
     
@@ -143,6 +192,12 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_probe_file_raw_nonexistent() {
+        let result = probe_file_raw("nonexistent.mp3".to_string());
+        assert!(matches!(result, Err(AppError::FileValidation(_))));
+    }
+
     #[test]
     fn test_merge_audio_files_nonexistent() {
         let result = merge_audio_files(
@@ -155,20 +210,46 @@ mod tests {
 }
 
 /// Reads metadata from an audio file
-/// Returns metadata as JSON-serializable struct
+/// Returns metadata as JSON-serializable struct; with `include_cover` false the
+/// cover bytes are left out, for callers that fetch it with `read_cover_art`
 #[tauri::command]
-pub fn read_audio_metadata(file_path: String) -> Result<AudiobookMetadata> {
-    read_metadata(&file_path)
+pub fn read_audio_metadata(file_path: String, include_cover: Option<bool>) -> Result<AudiobookMetadata> {
+    check_granted(&[&file_path])?;
+    let mut metadata = read_metadata(&file_path)?;
+    if !include_cover.unwrap_or(true) {
+        metadata.cover_art = None;
+    }
+    Ok(metadata)
+}
+
+/// Reads a file's cover art as base64 with its MIME type and dimensions
+/// Far smaller over IPC than the byte array `read_audio_metadata` returns
+#[tauri::command]
+pub fn read_cover_art(file_path: String) -> Result<Option<crate::metadata::cover_payload::CoverArt>> {
+    check_granted(&[&file_path])?;
+    crate::metadata::cover_payload::read_cover_art(&file_path)
 }
 
 /// Writes metadata to an existing M4B file
-/// Accepts file path and metadata object
+/// Fields the container can't hold are listed in the report; with `strict`
+/// they fail the write instead. Cover art and tags the app doesn't manage
+/// are kept unless `clear_unknown` is set (pictures are kept either way)
 #[tauri::command]
 pub fn write_audio_metadata(
     file_path: String,
-    metadata: AudiobookMetadata
-) -> Result<()> {
-    write_metadata(&file_path, &metadata)
+    metadata: AudiobookMetadata,
+    strict: Option<bool>,
+    clear_unknown: Option<bool>,
+) -> Result<crate::metadata::writer::MetadataWriteReport> {
+    use crate::metadata::writer::{write_metadata_with, MetadataWriteOptions};
+    check_granted(&[&file_path])?;
+    snapshot_before_write(&file_path);
+    let options = MetadataWriteOptions {
+        strict: strict.unwrap_or(false),
+        clear_unknown: clear_unknown.unwrap_or(false),
+        ..MetadataWriteOptions::default()
+    };
+    write_metadata_with(&file_path, &metadata, options)
 }
 
 /// Writes cover art to an M4B file
@@ -179,15 +260,109 @@ pub fn write_cover_art(
     cover_data: Vec<u8>
 ) -> Result<()> {
     use crate::metadata::writer::write_cover_art as write_cover;
+    check_granted(&[&file_path])?;
+    snapshot_before_write(&file_path);
     write_cover(&file_path, &cover_data)
 }
 
+/// Removes embedded cover art from a file, keeping its other tags
+/// Removes front covers only unless `all_pictures` is set; undo restores the cover
+#[tauri::command]
+pub fn remove_cover_art(file_path: String, all_pictures: Option<bool>) -> Result<()> {
+    check_granted(&[&file_path])?;
+    snapshot_before_write(&file_path);
+    crate::metadata::writer::remove_cover_art(&file_path, all_pictures.unwrap_or(false))
+}
+
+/// Replaces the chapters of an M4B file, remuxing without re-encoding
+/// Rejects chapters that overlap or are out of order; `chapter_format` defaults to FFmpeg's chapters
+#[tauri::command]
+pub fn write_audio_chapters(
+    file_path: String,
+    chapters: Vec<crate::metadata::ChapterInfo>,
+    chapter_format: Option<ChapterFormat>,
+) -> Result<()> {
+    check_granted(&[&file_path])?;
+    crate::metadata::chapter_remux::write_chapters(&file_path, &chapters, chapter_format.unwrap_or_default())
+}
+
+/// Parses a CUE sheet into a chapter list for `process_audiobook_files`
+/// Lets the frontend show the breakpoints before processing
+#[tauri::command]
+pub fn read_cue_sheet(file_path: String) -> Result<Vec<ChapterSpec>> {
+    check_granted(&[&file_path])?;
+    crate::metadata::cue::parse_cue_sheet(&file_path)
+}
+
+/// Writes metadata and cover art to an output completed with metadata pending
+/// Retries while the file is briefly held by another application; pass the
+/// run's `reproducible` and `strictMetadataFields` settings to write the tags the same way
+#[tauri::command]
+pub fn retry_metadata_write(
+    output_path: String,
+    metadata: AudiobookMetadata,
+    reproducible: Option<bool>,
+    strict: Option<bool>,
+) -> Result<crate::metadata::writer::MetadataWriteReport> {
+    check_granted(&[&output_path])?;
+    snapshot_before_write(&output_path);
+    let options = crate::metadata::writer::MetadataWriteOptions {
+        reproducible: reproducible.unwrap_or(false),
+        strict: strict.unwrap_or(false),
+        ..crate::metadata::writer::MetadataWriteOptions::default()
+    };
+    crate::metadata::write_retry::write_tags_with_retry(&output_path, &metadata, options)
+}
+
+/// Reverts the most recent metadata or cover write to a file
+/// Returns the restored metadata
+#[tauri::command]
+pub fn undo_metadata_write(file_path: String) -> Result<AudiobookMetadata> {
+    check_granted(&[&file_path])?;
+    let history = global_history()
+        .ok_or_else(|| AppError::General("Metadata history is not available".to_string()))?;
+    history.undo(&file_path)
+}
+
+/// Best-effort check whether another application has the file open
+/// Returns None when this platform can't tell, so unknown isn't shown as free
+#[tauri::command]
+pub fn is_file_in_use(file_path: String) -> Result<Option<bool>> {
+    check_granted(&[&file_path])?;
+    let path = PathBuf::from(&file_path);
+    if !path.exists() {
+        return Err(AppError::FileValidation(format!("File not found: {file_path}")));
+    }
+    Ok(crate::metadata::file_in_use::file_in_use(&path))
+}
+
+/// Reports cover art dimensions, aspect ratio, size and quality warnings
+#[tauri::command]
+pub fn assess_cover_art(data: Vec<u8>) -> Result<crate::metadata::cover_quality::CoverAssessment> {
+    crate::metadata::cover_quality::assess_cover_art(&data)
+}
+
+/// Finds a conventional cover image (cover/folder/front) next to the inputs
+/// Returns its path for loading with load_cover_art_file, which is granted
+/// along with the inputs it was found beside
+#[tauri::command]
+pub fn find_local_cover(file_paths: Vec<String>) -> Result<Option<String>> {
+    check_granted(&file_paths)?;
+    let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
+    let cover = crate::metadata::local_cover::find_local_cover(&paths)?;
+    if let (Some(cover), Some(policy)) = (cover.as_deref(), crate::util::path_policy::path_policy()) {
+        policy.grant(cover)?;
+    }
+    Ok(cover.map(|path| path.to_string_lossy().to_string()))
+}
+
 /// Loads image file from disk and returns as byte array
 /// Supports common image formats: jpg, jpeg, png, webp
 #[tauri::command]
 pub async fn load_cover_art_file(file_path: String) -> Result<Vec<u8>> {
     use std::fs;
     
+    check_granted(&[&file_path])?;
     let path = PathBuf::from(&file_path);
     
     // Validate file exists
@@ -227,6 +402,13 @@ pub async fn load_cover_art_file(file_path: String) -> Result<Vec<u8>> {
     Ok(image_data)
 }
 
+/// Loads a book description from a .txt or .md file as plain text
+#[tauri::command]
+pub fn load_description_file(file_path: String) -> Result<String> {
+    check_granted(&[&file_path])?;
+    crate::metadata::description::load_description_file(&file_path)
+}
+
 /// Validates image format by checking file headers
 fn validate_image_format(data: &[u8], extension: &str) -> Result<()> {
     if data.len() < MIN_IMAGE_SIZE {
@@ -268,7 +450,7 @@ mod metadata_tests {
 
     #[test]
     fn test_read_metadata_nonexistent() {
-        let result = read_audio_metadata("nonexistent.m4b".to_string());
+        let result = read_audio_metadata("nonexistent.m4b".to_string(), None);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("File not found"));
     }
@@ -276,11 +458,17 @@ mod metadata_tests {
     #[test]
     fn test_write_metadata_nonexistent() {
         let metadata = AudiobookMetadata::new();
-        let result = write_audio_metadata("nonexistent.m4b".to_string(), metadata);
+        let result = write_audio_metadata("nonexistent.m4b".to_string(), metadata, None, None);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("File not found"));
     }
 
+    #[test]
+    fn test_undo_metadata_write_without_history() {
+        let result = undo_metadata_write("nonexistent.m4b".to_string());
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_write_cover_art_nonexistent() {
         let cover_data = vec![0u8; 100];
@@ -310,26 +498,283 @@ mod metadata_tests {
         assert!(result.unwrap_err().to_string().contains("Unsupported image format"));
     }
 
+    #[test]
+    fn test_is_file_in_use_nonexistent() {
+        let result = is_file_in_use("nonexistent.m4b".to_string());
+        assert!(matches!(result, Err(AppError::FileValidation(_))));
+    }
+
+    #[test]
+    fn test_assess_cover_art_rejects_non_image() {
+        assert!(assess_cover_art(vec![0, 1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_find_local_cover_without_candidates() {
+        let result = find_local_cover(vec!["nonexistent/dir/01.mp3".to_string()]);
+        assert_eq!(result.unwrap(), None);
+    }
+
     #[test]
     fn test_read_metadata_invalid_file() {
         let temp_dir = TempDir::new().unwrap();
         let file_path = temp_dir.path().join("invalid.txt");
         fs::write(&file_path, b"not audio").unwrap();
         
-        let result = read_audio_metadata(file_path.to_string_lossy().to_string());
+        let result = read_audio_metadata(file_path.to_string_lossy().to_string(), None);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("metadata error"));
     }
 }
 
 /// Validates and analyzes a list of audio files
-/// Returns comprehensive file information including duration and size
+/// Returns comprehensive file information including duration and size;
+/// with `depth` Quick, only sizes and tags (defaults to Full).
+/// Files spread over two or more disc folders come back in disc order
 #[tauri::command]
-pub fn analyze_audio_files(file_paths: Vec<String>) -> Result<FileListInfo> {
+pub fn analyze_audio_files(file_paths: Vec<String>, depth: Option<AnalysisDepth>) -> Result<FileListInfo> {
+    use crate::audio::disc_order::{disc_markers, sort_by_disc};
+    check_granted(&file_paths)?;
     let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
-    crate::audio::get_file_list_info(&paths)
+    let mut info = crate::audio::analyze_file_list(&paths, depth.unwrap_or_default())?;
+    sort_by_disc(&mut info.files, &disc_markers());
+    Ok(info)
+}
+
+/// Analyzes audio files and checks stereo inputs for dual-mono
+/// Decodes a short window per stereo file, so it is slower than plain analysis
+#[tauri::command]
+pub async fn detect_dual_mono(file_paths: Vec<String>) -> Result<FileListInfo> {
+    check_granted(&file_paths)?;
+    tokio::task::spawn_blocking(move || {
+        let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
+        let mut info = crate::audio::get_file_list_info(&paths)?;
+        crate::audio::dual_mono::annotate_dual_mono(&mut info.files);
+        Ok(info)
+    })
+    .await
+    .map_err(|e| AppError::General(format!("Dual-mono detection task failed: {e}")))?
 }
 
+/// Analyzes audio files, measuring MP3s without a Xing/VBRI header by decoding them
+/// Slower than plain analysis; flagged files carry the header's estimate in `headerDuration`
+#[tauri::command]
+pub async fn measure_mp3_durations(file_paths: Vec<String>) -> Result<FileListInfo> {
+    check_granted(&file_paths)?;
+    tokio::task::spawn_blocking(move || {
+        let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
+        let mut files = crate::audio::file_list::validate_audio_files(&paths)?;
+        crate::audio::mp3_duration::annotate_measured_durations(&mut files);
+        Ok(crate::audio::file_list::summarize_files(files))
+    })
+    .await
+    .map_err(|e| AppError::General(format!("Duration measurement task failed: {e}")))?
+}
+
+/// Extracts normalized waveform peaks for a file list preview
+/// Decoding runs off the async runtime; buckets are capped at 2000
+#[tauri::command]
+pub async fn extract_peaks(file_path: String, buckets: u32) -> Result<Vec<f32>> {
+    check_granted(&[&file_path])?;
+    tokio::task::spawn_blocking(move || crate::audio::peaks::extract_peaks(&file_path, buckets))
+        .await
+        .map_err(|e| AppError::General(format!("Peak extraction task failed: {e}")))?
+}
+
+/// Cancels any waveform peak extraction that is still running
+#[tauri::command]
+pub fn cancel_peak_extraction() -> Result<String> {
+    crate::audio::peaks::cancel_peak_extraction();
+    Ok("Peak extraction cancellation requested".to_string())
+}
+
+/// Estimates total encode wall time by encoding a short sample of the first input
+/// Returns None when FFmpeg is unavailable or no input has a known duration
+#[tauri::command]
+pub async fn estimate_encode_time(
+    file_paths: Vec<String>,
+    settings: AudioSettings,
+) -> Result<Option<crate::audio::calibration::EncodeEstimate>> {
+    check_granted(&file_paths)?;
+    tokio::task::spawn_blocking(move || {
+        let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
+        let info = crate::audio::get_file_list_info(&paths)?;
+        crate::audio::calibration::estimate_encode_time(&info.files, &settings)
+    })
+    .await
+    .map_err(|e| AppError::General(format!("Encode calibration task failed: {e}")))?
+}
+
+/// Previews the chapters processing would generate for the inputs
+/// Uses analyzed durations and tags only; FFmpeg is not run
+#[tauri::command]
+pub async fn estimate_chapters(
+    file_paths: Vec<InputFileSpec>,
+    chapter_options: crate::audio::chapters::ChapterOptions,
+) -> Result<Vec<crate::audio::chapters::Chapter>> {
+    tokio::task::spawn_blocking(move || {
+        let specs = resolve_input_order(file_paths)?;
+        let paths: Vec<PathBuf> = specs.iter().map(|spec| spec.path.clone()).collect();
+        check_granted(&paths)?;
+        let mut info = crate::audio::get_file_list_info(&paths)?;
+        attach_input_options(&mut info.files, &specs);
+        crate::audio::chapters::generate_chapters(&info.files, &chapter_options)
+    })
+    .await
+    .map_err(|e| AppError::General(format!("Chapter estimation task failed: {e}")))?
+}
+
+/// Returns the chapter table processing would write, before processing
+/// Inputs are analyzed as for a run and titles follow the settings; FFmpeg runs
+/// only to measure MP3 durations when `accurate_mp3_durations` is set
+/// `chapter_options`, when given, replace the settings' chapter options
+#[tauri::command]
+pub async fn preview_chapters(
+    file_paths: Vec<InputFileSpec>,
+    settings: AudioSettings,
+    chapter_options: Option<crate::audio::chapters::ChapterOptions>,
+) -> Result<Vec<crate::metadata::ChapterInfo>> {
+    let mut settings = settings;
+    if chapter_options.is_some() {
+        settings.chapters = chapter_options;
+    }
+    tokio::task::spawn_blocking(move || {
+        let specs = resolve_input_order(file_paths)?;
+        let paths: Vec<PathBuf> = specs.iter().map(|spec| spec.path.clone()).collect();
+        check_granted(&paths)?;
+        let mut info = crate::audio::get_file_list_info(&paths)?;
+        attach_input_options(&mut info.files, &specs);
+        crate::audio::chapters::preview_chapters(&info.files, &settings)
+    })
+    .await
+    .map_err(|e| AppError::General(format!("Chapter preview task failed: {e}")))?
+}
+
+/// Suggests chapter starts for one long recording from its silences
+/// Returns the silences found too, so the threshold can be tuned
+#[tauri::command]
+pub async fn suggest_chapters_from_silence(
+    file_path: String,
+    min_silence_secs: f64,
+    threshold_db: f64,
+) -> Result<crate::audio::analysis::SilenceSuggestions> {
+    check_granted(&[&file_path])?;
+    tokio::task::spawn_blocking(move || {
+        crate::audio::analysis::suggest_chapters_from_silence(Path::new(&file_path), min_silence_secs, threshold_db)
+    })
+    .await
+    .map_err(|e| AppError::General(format!("Silence detection task failed: {e}")))?
+}
+
+/// Cancels any encode speed calibration that is still running
+#[tauri::command]
+pub fn cancel_encode_calibration() -> Result<String> {
+    crate::audio::calibration::cancel_calibration();
+    Ok("Encode calibration cancellation requested".to_string())
+}
+
+
+/// Sanitizes a generated output filename for the target filesystems
+/// Strict mode also applies Windows/SMB rules on macOS and Linux
+#[tauri::command]
+pub fn sanitize_filename(name: String, strict_cross_platform: bool) -> Result<String> {
+    use crate::audio::filename::{sanitize_filename, FilenameTarget};
+    Ok(sanitize_filename(&name, FilenameTarget::from_preference(strict_cross_platform)))
+}
+
+/// Sets the directory presets write into and relative output paths resolve against
+#[tauri::command]
+pub fn set_default_output_dir(dir: String) -> Result<String> {
+    check_granted(&[&dir])?;
+    crate::audio::output_dir::set_default_output_dir(Path::new(&dir))?;
+    Ok(dir)
+}
+
+/// Gets the directory presets write into
+#[tauri::command]
+pub fn get_default_output_dir() -> Result<String> {
+    Ok(crate::audio::output_dir::default_output_dir().to_string_lossy().to_string())
+}
+
+/// Sets the locale result strings and summaries are formatted for
+/// Returns the supported locale the tag resolved to
+#[tauri::command]
+pub fn set_display_locale(locale: String) -> Result<String> {
+    Ok(crate::audio::display_format::set_display_locale(&locale)?.tag().to_string())
+}
+
+/// Sets the executable run after each successful book, or clears it with null
+/// The executable must have been opened in the app, like any other command path
+#[tauri::command]
+pub fn set_post_process_hook(hook: Option<String>) -> Result<()> {
+    if let Some(hook) = &hook {
+        check_granted(&[hook])?;
+    }
+    crate::audio::post_hook::set_post_process_hook(hook.as_deref().map(Path::new))
+}
+
+/// Gets the executable run after each successful book, if set
+#[tauri::command]
+pub fn get_post_process_hook() -> Result<Option<String>> {
+    Ok(crate::audio::post_hook::post_process_hook().map(|hook| hook.to_string_lossy().to_string()))
+}
+
+/// Sets how many seconds analyzing one file may take, or restores the default with null
+/// Files over the limit are marked `timeout` instead of holding up the scan
+#[tauri::command]
+pub fn set_analysis_timeout(seconds: Option<u64>) -> Result<()> {
+    crate::audio::file_list::set_analysis_timeout(seconds.map(std::time::Duration::from_secs))
+}
+
+/// Sets the words that mark a disc folder or filename ("CD", "Disc"), or restores the defaults with null
+/// Markers are matched case-insensitively before the disc number
+#[tauri::command]
+pub fn set_disc_markers(markers: Option<Vec<String>>) -> Result<()> {
+    crate::audio::disc_order::set_disc_markers(markers)
+}
+
+/// Grants access to a path the user picked in a file dialog
+/// A directory covers everything below it; returns the canonical path granted
+#[tauri::command]
+pub fn register_granted_path(path: String) -> Result<String> {
+    let Some(policy) = crate::util::path_policy::path_policy() else {
+        return Ok(path);
+    };
+    Ok(policy.grant(Path::new(&path))?.to_string_lossy().to_string())
+}
+
+/// Whether the check that command paths were opened in the app is off
+/// Only the path policy config file in the app data dir turns it off
+#[tauri::command]
+pub fn get_permissive_path_access() -> Result<bool> {
+    Ok(crate::util::path_policy::path_policy().is_some_and(|policy| policy.is_permissive()))
+}
+
+/// Sets the metadata profile layered under every new book's metadata
+/// Locked fields win over tags inherited from the inputs
+#[tauri::command]
+pub fn update_default_metadata_profile(profile: crate::metadata::profile::MetadataProfile) -> Result<()> {
+    crate::metadata::profile::set_default_metadata_profile(profile)
+}
+
+/// Gets the metadata profile layered under every new book's metadata
+#[tauri::command]
+pub fn get_default_metadata_profile() -> Result<crate::metadata::profile::MetadataProfile> {
+    Ok(crate::metadata::profile::default_metadata_profile())
+}
+
+/// Infers book metadata from the tags of all inputs, for prefilling the metadata panel
+/// Reports the fields the inputs disagree on; `strategy` defaults to FirstFile
+#[tauri::command]
+pub fn infer_book_metadata(
+    file_paths: Vec<String>,
+    strategy: Option<crate::metadata::inference::MergeStrategy>,
+) -> Result<crate::metadata::inference::InferredMetadata> {
+    check_granted(&file_paths)?;
+    let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
+    let files = crate::audio::analyze_file_list(&paths, AnalysisDepth::Quick)?.files;
+    Ok(crate::metadata::inference::derive_metadata_from_inputs(&files, strategy.unwrap_or_default()))
+}
 
 /// Validates audio processing settings
 /// Checks bitrate, sample rate, and output path validity
@@ -339,16 +784,78 @@ pub fn validate_audio_settings(settings: AudioSettings) -> Result<String> {
     Ok("Settings are valid".to_string())
 }
 
+/// Validates book metadata before it is written
+/// Checks the ISBN check digit and the ASIN shape
+#[tauri::command]
+pub fn validate_metadata(metadata: AudiobookMetadata) -> Result<String> {
+    crate::metadata::identifiers::validate_metadata(&metadata)?;
+    Ok("Metadata is valid".to_string())
+}
+
+/// Previews the automatic decisions a run with these inputs and settings would make
+/// Processing makes the same decisions, so the preview matches the run; checks
+/// that would need FFmpeg to decode audio are listed as deferred instead of run
+#[tauri::command]
+pub async fn resolve_plan(
+    file_paths: Vec<InputFileSpec>,
+    settings: AudioSettings,
+    chapters: Option<Vec<ChapterSpec>>,
+) -> Result<crate::audio::plan_resolution::PlanResolution> {
+    let mut settings = settings;
+    settings.chapter_specs = chapters;
+    tokio::task::spawn_blocking(move || {
+        let specs = resolve_input_order(file_paths)?;
+        let paths: Vec<PathBuf> = specs.iter().map(|spec| spec.path.clone()).collect();
+        check_granted(&paths)?;
+        let mut info = crate::audio::get_file_list_info(&paths)?;
+        attach_input_options(&mut info.files, &specs);
+        crate::audio::plan_resolution::preview_plan(info.files, &settings)
+    })
+    .await
+    .map_err(|e| AppError::General(format!("Plan resolution task failed: {e}")))?
+}
+
 /// Processes multiple audio files into a single M4B audiobook
 /// Merges files with specified settings and optional metadata
+/// Each input is a path string or an object with per-file options
+/// An explicit chapter list replaces the generated chapters; input indices refer to `file_paths`
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn process_audiobook_files(
     window: tauri::Window,
     state: tauri::State<'_, crate::ProcessingState>,
-    file_paths: Vec<String>,
+    file_paths: Vec<InputFileSpec>,
     settings: AudioSettings,
-    metadata: Option<AudiobookMetadata>
+    metadata: Option<AudiobookMetadata>,
+    label: Option<String>,
+    notes: Option<String>,
+    chapters: Option<Vec<ChapterSpec>>,
 ) -> Result<String> {
+    let mut settings = settings;
+    settings.chapter_specs = chapters;
+    let preferences = crate::audio::preferences::PreferenceSnapshot::current();
+    run_audiobook(window, state, file_paths, settings, metadata, label, notes, preferences).await
+}
+
+/// Processes one book with the preferences captured for it
+#[allow(clippy::too_many_arguments)]
+async fn run_audiobook(
+    window: tauri::Window,
+    state: tauri::State<'_, crate::ProcessingState>,
+    file_paths: Vec<InputFileSpec>,
+    settings: AudioSettings,
+    metadata: Option<AudiobookMetadata>,
+    label: Option<String>,
+    notes: Option<String>,
+    preferences: crate::audio::preferences::PreferenceSnapshot,
+) -> Result<String> {
+    let mut settings = settings;
+    settings.output_path = crate::audio::output_dir::resolve_output_path(&settings.output_path)?;
+    let specs = resolve_input_order(file_paths)?;
+    let paths: Vec<PathBuf> = specs.iter().map(|spec| spec.path.clone()).collect();
+    check_granted(&paths)?;
+    check_granted(&[&settings.output_path])?;
+
     // Set processing state
     {
         let mut is_processing = state.is_processing.lock()
@@ -358,19 +865,24 @@ pub async fn process_audiobook_files(
         let mut is_cancelled = state.is_cancelled.lock()
             .map_err(|_| AppError::InvalidInput("Failed to acquire cancellation lock".to_string()))?;
         *is_cancelled = false;
+        
+        let mut cancel_reason = state.cancel_reason.lock()
+            .map_err(|_| AppError::InvalidInput("Failed to acquire cancellation lock".to_string()))?;
+        *cancel_reason = None;
     }
     
     // Validate and get file information
-    let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
-    let file_info = crate::audio::get_file_list_info(&paths)?;
+    let mut file_info = crate::audio::get_file_list_info(&paths)?;
+    attach_input_options(&mut file_info.files, &specs);
     
     // Process the audiobook with progress events
-    #[allow(deprecated)]
-    let result = crate::audio::process_audiobook_with_events(
-        window,
-        state.clone(),
+    let session = crate::audio::processor::create_session_from_legacy_state(&state)?;
+    session.set_label(JobLabel::new(label, notes, &settings.output_path));
+    let context = crate::audio::ProcessingContext::for_window(window, session, settings)
+        .with_preferences(preferences);
+    let result = crate::audio::processor::process_audiobook_with_context(
+        context,
         file_info.files,
-        settings,
         metadata
     ).await;
     
@@ -384,16 +896,212 @@ pub async fn process_audiobook_files(
     result
 }
 
+/// Adds a batch of books to the job queue and returns their job IDs
+/// With series_autonumber, series and index are filled in queue order
+#[tauri::command]
+pub fn enqueue_audiobook_batch(
+    jobs: Vec<crate::audio::jobs::AudiobookJob>,
+    series_autonumber: Option<crate::audio::jobs::SeriesAutoNumber>,
+) -> Result<Vec<String>> {
+    for job in &jobs {
+        check_granted(&job.file_paths)?;
+        check_granted(&[crate::audio::output_dir::resolve_output_path(&job.settings.output_path)?])?;
+    }
+    crate::audio::jobs::enqueue_batch(jobs, series_autonumber.as_ref())
+}
+
+/// Adds a single book to the job queue and returns its job ID
+#[tauri::command]
+pub fn enqueue_audiobook_job(
+    file_paths: Vec<String>,
+    settings: AudioSettings,
+    metadata: Option<AudiobookMetadata>,
+    label: Option<String>,
+    notes: Option<String>,
+) -> Result<String> {
+    check_granted(&file_paths)?;
+    check_granted(&[crate::audio::output_dir::resolve_output_path(&settings.output_path)?])?;
+    let job = crate::audio::jobs::AudiobookJob { file_paths, settings, metadata, label, notes };
+    let ids = crate::audio::jobs::enqueue_batch(vec![job], None)?;
+    ids.into_iter().next()
+        .ok_or_else(|| AppError::General("Job was not queued".to_string()))
+}
+
+/// Lists jobs waiting in the queue
+#[tauri::command]
+pub fn get_queued_jobs() -> Result<Vec<crate::audio::jobs::QueuedJob>> {
+    crate::audio::jobs::queued_jobs()
+}
+
+/// Gives a queued job the preferences as set now
+/// Queued jobs otherwise keep the preferences in effect when they were queued
+#[tauri::command]
+pub fn refresh_queued_job_settings(job_id: String) -> Result<()> {
+    crate::audio::jobs::refresh_job_preferences(&job_id)
+}
+
+/// Forecasts disk space for each queued job, counting earlier jobs on the same volume
+#[tauri::command]
+pub fn get_queue_forecast() -> Result<Vec<crate::audio::jobs::SpaceForecast>> {
+    crate::audio::jobs::forecast_queued_jobs()
+}
+
+/// Processes queued jobs one after another until the queue is empty
+/// A job that fails is kept with its error (see `get_failed_jobs`) and the
+/// queue moves on; a cancelled job goes back to the front and the queue stops.
+/// A job the output volume can't hold alongside the running ones is
+/// warned about, or moved to the back with `disk_space_policy` Defer
+#[tauri::command]
+pub async fn process_audiobook_queue(
+    window: tauri::Window,
+    state: tauri::State<'_, crate::ProcessingState>,
+    disk_space_policy: Option<crate::audio::jobs::DiskSpacePolicy>,
+) -> Result<Vec<String>> {
+    use crate::audio::jobs::{
+        defer_job, fail_job, next_job, preflight_job, queued_jobs, return_job, start_running, DiskSpacePolicy,
+    };
+    let policy = disk_space_policy.unwrap_or_default();
+    let mut results = Vec::new();
+    let mut deferred_in_a_row = 0;
+    while let Some(queued) = next_job()? {
+        let (estimate, forecast) = match preflight_job(&queued) {
+            Ok(preflight) => preflight,
+            Err(e) => {
+                fail_job(queued, &e)?;
+                continue;
+            }
+        };
+        if let Some(warning) = forecast.warning() {
+            log::warn!("Job {}: {warning}", queued.id);
+            if policy == DiskSpacePolicy::Defer {
+                let waiting = queued_jobs()?.len();
+                defer_job(queued)?;
+                deferred_in_a_row += 1;
+                if deferred_in_a_row > waiting {
+                    log::warn!("Every queued job is deferred for disk space; stopping the queue");
+                    break;
+                }
+                continue;
+            }
+        }
+        deferred_in_a_row = 0;
+        log::info!("Processing queued job {}", queued.id);
+        let job = queued.job.clone();
+        let result = match start_running(estimate) {
+            Ok(_running) => run_audiobook(
+                window.clone(),
+                state.clone(),
+                job.file_paths.into_iter().map(|path| InputFileSpec::from(PathBuf::from(path))).collect(),
+                job.settings,
+                job.metadata,
+                job.label,
+                job.notes,
+                queued.preferences.clone(),
+            ).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(output) => results.push(output),
+            Err(e @ AppError::Cancelled(_)) => {
+                log::info!("Queued job {} stopped ({e}); leaving it at the front of the queue", queued.id);
+                return_job(queued)?;
+                break;
+            }
+            Err(e) => fail_job(queued, &e)?,
+        }
+    }
+    Ok(results)
+}
+
+/// Returns the queued jobs that failed, with their errors
+#[tauri::command]
+pub fn get_failed_jobs() -> Result<Vec<crate::audio::jobs::FailedJob>> {
+    crate::audio::jobs::failed_jobs()
+}
+
+/// Moves a failed job back to the end of the queue
+#[tauri::command]
+pub fn retry_failed_job(job_id: String) -> Result<()> {
+    crate::audio::jobs::retry_failed_job(&job_id)
+}
+
+/// Returns past processing runs, oldest first
+#[tauri::command]
+pub fn get_processing_history() -> Result<Vec<crate::audio::job_history::HistoryRecord>> {
+    crate::audio::job_history::processing_history()
+}
+
+/// Finds a previous successful run with the same inputs, settings and metadata
+/// Returns its history record when the output is still in place
+#[tauri::command]
+pub fn check_already_processed(
+    file_paths: Vec<String>,
+    settings: AudioSettings,
+    metadata: Option<AudiobookMetadata>,
+) -> Result<Option<crate::audio::job_history::HistoryRecord>> {
+    check_granted(&file_paths)?;
+    crate::audio::plan_hash::check_already_processed(&file_paths, &settings, metadata.as_ref())
+}
+
+/// Returns stage timings for a processing session
+/// Uses the most recent session when no ID is given
+#[tauri::command]
+pub fn get_session_timeline(session_id: Option<String>) -> Result<Vec<crate::audio::timeline::StageTiming>> {
+    crate::audio::timeline::session_timeline(session_id.as_deref())
+}
+
+/// Emits a snapshot of a session's progress so far, for late subscribers
+/// Live `processing-progress` events keep arriving afterwards; defaults to the latest session
+#[tauri::command]
+pub fn subscribe_progress(
+    window: tauri::Window,
+    session_id: Option<String>,
+) -> Result<crate::audio::progress_snapshot::ProgressSnapshot> {
+    use tauri::Emitter;
+    let snapshot = crate::audio::progress_snapshot::progress_snapshot(session_id.as_deref())?;
+    window.emit("processing-progress-snapshot", &snapshot)
+        .map_err(|e| AppError::General(format!("Failed to emit progress snapshot: {e}")))?;
+    Ok(snapshot)
+}
+
+/// Gets the latest progress of a session, defaulting to the latest session
+#[tauri::command]
+pub fn get_processing_progress(session_id: Option<String>) -> Result<Option<crate::audio::ProcessingProgress>> {
+    Ok(crate::audio::progress_snapshot::progress_snapshot(session_id.as_deref())?.progress)
+}
+
 /// Cancels the current audio processing operation
-/// Sets the cancellation flag in the shared processing state
+/// Sets the cancellation flag in the shared processing state and on running sessions
 #[tauri::command]
 pub fn cancel_processing(state: tauri::State<crate::ProcessingState>) -> Result<String> {
-    let mut is_cancelled = state.is_cancelled.lock()
-        .map_err(|_| AppError::InvalidInput("Failed to acquire cancellation lock".to_string()))?;
-    *is_cancelled = true;
+    {
+        let mut is_cancelled = state.is_cancelled.lock()
+            .map_err(|_| AppError::InvalidInput("Failed to acquire cancellation lock".to_string()))?;
+        *is_cancelled = true;
+        let mut cancel_reason = state.cancel_reason.lock()
+            .map_err(|_| AppError::InvalidInput("Failed to acquire cancellation lock".to_string()))?;
+        cancel_reason.get_or_insert(CancelReason::UserRequested);
+    }
+    crate::audio::session::cancel_all_active_sessions(CancelReason::UserRequested)?;
     Ok("Processing cancellation requested".to_string())
 }
 
+/// Cancels one running session by ID
+#[tauri::command]
+pub fn cancel_session(session_id: String) -> Result<String> {
+    crate::audio::session::cancel_active_session(&session_id, CancelReason::UserRequested)?;
+    Ok(format!("Cancellation requested for session {session_id}"))
+}
+
+/// Stops one running session after its current unit of work
+/// Segmented merges stop before the next segment and report `soft_stopped`;
+/// a single-pass merge has no boundary and completes
+#[tauri::command]
+pub fn soft_stop_processing(session_id: String) -> Result<String> {
+    crate::audio::session::soft_stop_active_session(&session_id)?;
+    Ok(format!("Soft stop requested for session {session_id}"))
+}
+
 #[cfg(test)]
 mod audio_tests {
     use super::*;
@@ -401,7 +1109,7 @@ mod audio_tests {
 
     #[test]
     fn test_analyze_audio_files_empty() {
-        let result = analyze_audio_files(vec![]);
+        let result = analyze_audio_files(vec![], None);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("No files provided"));
     }
@@ -409,13 +1117,37 @@ mod audio_tests {
     #[test]
     fn test_analyze_audio_files_nonexistent() {
         let files = vec!["nonexistent.mp3".to_string()];
-        let result = analyze_audio_files(files).unwrap();
+        let result = analyze_audio_files(files, None).unwrap();
         assert_eq!(result.files.len(), 1);
         assert!(!result.files[0].is_valid);
         assert_eq!(result.valid_count, 0);
         assert_eq!(result.invalid_count, 1);
     }
 
+    #[tokio::test]
+    async fn test_detect_dual_mono_empty() {
+        let result = detect_dual_mono(vec![]).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_extract_peaks_nonexistent() {
+        let result = extract_peaks("nonexistent.mp3".to_string(), 100).await;
+        assert!(matches!(result, Err(AppError::FileValidation(_))));
+    }
+
+    #[test]
+    fn test_sanitize_filename_strict() {
+        let result = sanitize_filename(r#"He said: "Go!""#.to_string(), true).unwrap();
+        assert_eq!(result, "He said_ _Go!_");
+    }
+
+    #[test]
+    fn test_get_session_timeline_unknown_session() {
+        let result = get_session_timeline(Some("not-a-session".to_string()));
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    }
+
     #[test]
     fn test_validate_audio_settings_valid() {
         let temp_dir = TempDir::new().unwrap();
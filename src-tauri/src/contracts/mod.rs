@@ -0,0 +1,125 @@
+//! Types that cross the IPC boundary
+//!
+//! Everything the frontend sends or receives is re-exported here, each with
+//! a golden JSON fixture under `fixtures/`. The round-trip tests fail on any
+//! wire-format change (a renamed field, a new field, a different enum
+//! shape), so such a change has to update the fixture and the TypeScript
+//! declarations in `typescript` together, and shows up in review.
+//!
+//! `src/types/contracts.ts` is generated from those declarations with
+//! `cargo run --features ts-export --bin generate_ts_types`.
+
+mod typescript;
+
+pub use crate::audio::chapters::{ChapterMode, ChapterOptions, DuplicateTitleStyle};
+pub use crate::audio::file_list::{FileListInfo, SkippedInput};
+pub use crate::audio::input_spec::{InputOptions, TrimSpec};
+pub use crate::audio::progress::ProgressEvent;
+pub use crate::audio::timeline::{StageTiming, TimelineStage};
+pub use crate::audio::{
+    AnalysisDepth, AudioFile, AudioSettings, ChannelConfig, FileErrorKind, InvalidInputPolicy, ProcessingProgress,
+    ProcessingStage, SampleRateConfig, SyncStatus,
+};
+pub use crate::errors::{CancelReason, ErrorCode, ErrorPayload};
+pub use crate::ffmpeg::BinaryOrigin;
+pub use crate::metadata::cover_payload::CoverArt;
+pub use crate::metadata::AudiobookMetadata;
+pub use typescript::typescript_definitions;
+
+#[cfg(test)]
+mod tests {
+    use super::typescript::{declaration, TsBody};
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+
+    /// Golden fixtures, named after the TypeScript declaration they match
+    const FIXTURES: &[(&str, &str)] = &[
+        ("AudioFile", include_str!("fixtures/audio_file.json")),
+        ("FileListInfo", include_str!("fixtures/file_list_info.json")),
+        ("AudioSettings", include_str!("fixtures/audio_settings.json")),
+        ("AudiobookMetadata", include_str!("fixtures/audiobook_metadata.json")),
+        ("CoverArt", include_str!("fixtures/cover_art.json")),
+        ("ProgressEvent", include_str!("fixtures/progress_event.json")),
+        ("ProcessingProgress", include_str!("fixtures/processing_progress.json")),
+        ("ErrorPayload", include_str!("fixtures/error_payload.json")),
+    ];
+
+    fn fixture(name: &str) -> Value {
+        let (_, json) = FIXTURES.iter().find(|(fixture, _)| *fixture == name).unwrap();
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// Deserializes the fixture and checks it serializes back unchanged
+    fn assert_round_trip<T: Serialize + DeserializeOwned>(name: &str) {
+        let golden = fixture(name);
+        let typed: T = serde_json::from_value(golden.clone())
+            .unwrap_or_else(|e| panic!("{name} fixture no longer deserializes: {e}"));
+        assert_eq!(serde_json::to_value(&typed).unwrap(), golden, "{name} wire format changed");
+    }
+
+    #[test]
+    fn test_round_trips() {
+        assert_round_trip::<AudioFile>("AudioFile");
+        assert_round_trip::<FileListInfo>("FileListInfo");
+        assert_round_trip::<AudioSettings>("AudioSettings");
+        assert_round_trip::<AudiobookMetadata>("AudiobookMetadata");
+        assert_round_trip::<CoverArt>("CoverArt");
+        assert_round_trip::<ProgressEvent>("ProgressEvent");
+        assert_round_trip::<ProcessingProgress>("ProcessingProgress");
+        assert_round_trip::<ErrorPayload>("ErrorPayload");
+    }
+
+    #[test]
+    fn test_enum_shapes() {
+        let json = |value: Value| value.to_string();
+        assert_eq!(json(serde_json::to_value(SampleRateConfig::Auto).unwrap()), r#""auto""#);
+        assert_eq!(json(serde_json::to_value(SampleRateConfig::Explicit(22050)).unwrap()), r#"{"explicit":22050}"#);
+        assert_eq!(json(serde_json::to_value(ChannelConfig::Mono).unwrap()), r#""Mono""#);
+        assert_eq!(json(serde_json::to_value(InvalidInputPolicy::SkipWithWarning).unwrap()), r#""SkipWithWarning""#);
+        assert_eq!(json(serde_json::to_value(SyncStatus::StillSyncing).unwrap()), r#""still_syncing""#);
+        assert_eq!(json(serde_json::to_value(AnalysisDepth::Quick).unwrap()), r#""Quick""#);
+        assert_eq!(json(serde_json::to_value(FileErrorKind::DrmProtected).unwrap()), r#""drm_protected""#);
+        assert_eq!(json(serde_json::to_value(TimelineStage::WritingMetadata).unwrap()), r#""writing_metadata""#);
+        assert_eq!(json(serde_json::to_value(ErrorCode::FileInUse).unwrap()), r#""file_in_use""#);
+        assert_eq!(json(serde_json::to_value(BinaryOrigin::Override).unwrap()), r#""override""#);
+    }
+
+    /// Checks an object has exactly the declared fields, recursing into
+    /// fields whose type is another declared interface
+    fn assert_matches_declaration(name: &str, value: &Value) {
+        let Some(TsBody::Interface(fields)) = declaration(name).map(|decl| &decl.body) else {
+            return;
+        };
+        let object = value.as_object().unwrap_or_else(|| panic!("{name} fixture is not an object"));
+        let declared: BTreeSet<&str> = fields.iter().map(|field| field.name).collect();
+        let actual: BTreeSet<&str> = object.keys().map(String::as_str).collect();
+        assert_eq!(actual, declared, "{name} TypeScript declaration differs from the fixture");
+        for field in *fields {
+            let element_type = field.ty.trim_end_matches("[]");
+            match &object[field.name] {
+                Value::Array(items) => items.iter().for_each(|item| assert_matches_declaration(element_type, item)),
+                value => assert_matches_declaration(element_type, value),
+            }
+        }
+    }
+
+    #[test]
+    fn test_typescript_declarations_match_fixtures() {
+        for (name, _) in FIXTURES {
+            assert!(declaration(name).is_some(), "{name} has no TypeScript declaration");
+            assert_matches_declaration(name, &fixture(name));
+        }
+    }
+
+    #[test]
+    fn test_generated_typescript_is_current() {
+        let committed = include_str!("../../../src/types/contracts.ts");
+        assert!(
+            committed == typescript_definitions(),
+            "src/types/contracts.ts is stale; run `cargo run --features ts-export --bin generate_ts_types`"
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! TypeScript declarations of the IPC contracts
+//!
+//! Declarations are kept by hand next to the fixtures rather than derived,
+//! so no codegen dependency is needed; the contract tests check every
+//! declared interface against its golden fixture, field by field.
+
+/// One exported TypeScript declaration
+pub struct TsDecl {
+    /// Type name, matching the Rust type
+    pub name: &'static str,
+    /// Doc comment
+    pub doc: &'static str,
+    /// Interface fields or alias target
+    pub body: TsBody,
+}
+
+/// Shape of a declaration
+pub enum TsBody {
+    /// `export interface Name { ... }`
+    Interface(&'static [TsField]),
+    /// `export type Name = ...;`
+    Alias(&'static str),
+}
+
+/// One interface field
+pub struct TsField {
+    /// Wire name
+    pub name: &'static str,
+    /// TypeScript type
+    pub ty: &'static str,
+    /// Whether the field may be absent or null
+    pub optional: bool,
+}
+
+const fn field(name: &'static str, ty: &'static str) -> TsField {
+    TsField { name, ty, optional: false }
+}
+
+const fn optional(name: &'static str, ty: &'static str) -> TsField {
+    TsField { name, ty, optional: true }
+}
+
+const DECLARATIONS: &[TsDecl] = &[
+    TsDecl {
+        name: "AudioFile",
+        doc: "An analyzed input file",
+        body: TsBody::Interface(&[
+            field("path", "string"),
+            optional("size", "number"),
+            optional("duration", "number"),
+            optional("headerDuration", "number"),
+            optional("format", "string"),
+            optional("codec", "string"),
+            optional("bitrate", "number"),
+            optional("sampleRate", "number"),
+            optional("channels", "number"),
+            optional("title", "string"),
+            optional("chapters", "ChapterInfo[]"),
+            field("isValid", "boolean"),
+            optional("error", "string"),
+            optional("errorKind", "FileErrorKind"),
+            optional("dualMono", "boolean"),
+            optional("syncStatus", "SyncStatus"),
+            optional("options", "InputOptions"),
+            optional("analysisDepth", "AnalysisDepth"),
+        ]),
+    },
+    TsDecl {
+        name: "FileErrorKind",
+        doc: "Why a file failed validation",
+        body: TsBody::Alias(
+            "'not_found' | 'unsupported' | 'corrupt' | 'drm_protected' | 'permission_denied' | 'still_syncing' | 'timeout' | 'unknown'",
+        ),
+    },
+    TsDecl {
+        name: "AnalysisDepth",
+        doc: "How much of each file analysis reads",
+        body: TsBody::Alias("'Quick' | 'Full'"),
+    },
+    TsDecl {
+        name: "SyncStatus",
+        doc: "Why a file could not be analyzed yet",
+        body: TsBody::Alias("'still_syncing' | 'dataless'"),
+    },
+    TsDecl {
+        name: "InputOptions",
+        doc: "Per-file processing options",
+        body: TsBody::Interface(&[
+            optional("gainDb", "number"),
+            optional("trim", "TrimSpec"),
+            optional("chapterTitle", "string"),
+        ]),
+    },
+    TsDecl {
+        name: "TrimSpec",
+        doc: "Portion of an input to keep, in seconds from its start",
+        body: TsBody::Interface(&[
+            optional("startSecs", "number"),
+            optional("endSecs", "number"),
+        ]),
+    },
+    TsDecl {
+        name: "FileListInfo",
+        doc: "Summary of an analyzed file list",
+        body: TsBody::Interface(&[
+            field("files", "AudioFile[]"),
+            field("totalDuration", "number"),
+            field("totalSize", "number"),
+            field("validCount", "number"),
+            field("invalidCount", "number"),
+            field("errorCounts", "Partial<Record<FileErrorKind, number>>"),
+            optional("inputStats", "InputDurationStats"),
+            field("discGroups", "DiscGroup[]"),
+        ]),
+    },
+    TsDecl {
+        name: "AudioSettings",
+        doc: "Output encoding and processing settings",
+        body: TsBody::Interface(&[
+            field("bitrate", "number"),
+            field("channels", "ChannelConfig"),
+            field("sampleRate", "SampleRateConfig"),
+            field("outputPath", "string"),
+            optional("segmentThreshold", "number"),
+            optional("strictCrossPlatform", "boolean"),
+            optional("autoDualMono", "boolean"),
+            optional("accurateMp3Durations", "boolean"),
+            optional("enforceCoverQuality", "boolean"),
+            optional("generatePlaceholderCover", "boolean"),
+            optional("invalidInputPolicy", "InvalidInputPolicy"),
+            optional("probeSizeMb", "number"),
+            optional("analyzeDurationSecs", "number"),
+            optional("gaplessJoin", "boolean"),
+            optional("bitrateExcessFactor", "number"),
+            optional("strictBitrateCheck", "boolean"),
+            optional("copyTranscripts", "boolean"),
+            optional("stripTrackScopedMetadata", "boolean"),
+            optional("chapters", "ChapterOptions"),
+            optional("chapterTitleSource", "ChapterTitleSource"),
+            optional("allowM4aExtension", "boolean"),
+            optional("verifyOutput", "boolean"),
+            optional("reproducible", "boolean"),
+            optional("strictMetadataFields", "boolean"),
+            optional("exportCue", "boolean"),
+            optional("appendChapterListToDescription", "boolean"),
+            optional("chapterListTimestampFormat", "ChapterTimestampFormat"),
+            optional("chapterFormat", "ChapterFormat"),
+            optional("writeEncodingInfo", "boolean"),
+            optional("autoCoverFromInput", "boolean"),
+            optional("maxCoverBytes", "number"),
+            optional("strictCoverSize", "boolean"),
+            optional("metadataSidecar", "SidecarFormat"),
+        ]),
+    },
+    TsDecl {
+        name: "ChannelConfig",
+        doc: "Output channel layout",
+        body: TsBody::Alias("'Mono' | 'Stereo'"),
+    },
+    TsDecl {
+        name: "SampleRateConfig",
+        doc: "Output sample rate, detected or explicit in Hz",
+        body: TsBody::Alias("'auto' | { explicit: number }"),
+    },
+    TsDecl {
+        name: "ChapterOptions",
+        doc: "How chapters are generated",
+        body: TsBody::Interface(&[
+            optional("mode", "ChapterMode"),
+            optional("intervalMinutes", "number"),
+            optional("titleTemplate", "string"),
+            optional("duplicateStyle", "DuplicateTitleStyle"),
+        ]),
+    },
+    TsDecl {
+        name: "ChapterMode",
+        doc: "Where chapter marks are placed",
+        body: TsBody::Alias("'PerFile' | 'FixedInterval'"),
+    },
+    TsDecl {
+        name: "ChapterTitleSource",
+        doc: "What per-file chapter titles are made from when no template is set",
+        body: TsBody::Alias("'Filename' | 'TagTitle' | 'Numbered'"),
+    },
+    TsDecl {
+        name: "ChapterTimestampFormat",
+        doc: "How chapter starts are written in a description's chapter list",
+        body: TsBody::Alias("'Auto' | 'Hours' | 'Minutes'"),
+    },
+    TsDecl {
+        name: "SidecarFormat",
+        doc: "File format of the metadata sidecar",
+        body: TsBody::Alias("'Json' | 'Opf'"),
+    },
+    TsDecl {
+        name: "ChapterFormat",
+        doc: "Which MP4 chapter atoms are written",
+        body: TsBody::Alias("'Ffmetadata' | 'Chpl' | 'Both'"),
+    },
+    TsDecl {
+        name: "DuplicateTitleStyle",
+        doc: "How repeated chapter titles are made unique",
+        body: TsBody::Alias("'SuffixNumber' | 'PrefixIndex'"),
+    },
+    TsDecl {
+        name: "InvalidInputPolicy",
+        doc: "Handling of invalid inputs when processing starts",
+        body: TsBody::Alias("'Fail' | 'SkipWithWarning'"),
+    },
+    TsDecl {
+        name: "AudiobookMetadata",
+        doc: "Book-level tags (snake_case on the wire)",
+        body: TsBody::Interface(&[
+            optional("title", "string"),
+            optional("subtitle", "string"),
+            optional("author", "string"),
+            optional("album", "string"),
+            optional("narrator", "string"),
+            optional("year", "number"),
+            optional("genre", "string"),
+            optional("description", "string"),
+            optional("series", "string"),
+            optional("series_index", "number"),
+            optional("track_number", "number"),
+            optional("track_total", "number"),
+            optional("disc_number", "number"),
+            optional("disc_total", "number"),
+            optional("sort_title", "string"),
+            optional("sort_author", "string"),
+            optional("isbn", "string"),
+            optional("asin", "string"),
+            optional("cover_art", "number[]"),
+            optional("tag_sources", "string[]"),
+            optional("chapters", "ChapterInfo[]"),
+            optional("encoding_info", "EncodingInfo"),
+        ]),
+    },
+    TsDecl {
+        name: "CoverArt",
+        doc: "Cover image as base64 with its MIME type, ready for a data URL",
+        body: TsBody::Interface(&[
+            field("mime", "string"),
+            field("dataBase64", "string"),
+            optional("width", "number"),
+            optional("height", "number"),
+        ]),
+    },
+    TsDecl {
+        name: "EncodingInfo",
+        doc: "How a file was encoded, as read from its encoder tag",
+        body: TsBody::Interface(&[
+            field("tool", "string"),
+            optional("codec", "string"),
+            optional("bitrate_kbps", "number"),
+            optional("profile", "string"),
+            optional("sample_rate", "number"),
+            optional("channels", "number"),
+            optional("normalization", "string"),
+        ]),
+    },
+    TsDecl {
+        name: "ChapterInfo",
+        doc: "A chapter read from an existing file",
+        body: TsBody::Interface(&[
+            field("title", "string"),
+            field("startMs", "number"),
+            field("endMs", "number"),
+        ]),
+    },
+    TsDecl {
+        name: "ProcessingStage",
+        doc: "Stage of a processing run",
+        body: TsBody::Alias("'analyzing' | 'converting' | 'merging' | 'writing_metadata' | 'completed' | 'failed'"),
+    },
+    TsDecl {
+        name: "ProcessingProgress",
+        doc: "Progress of a session, as returned by get_processing_progress",
+        body: TsBody::Interface(&[
+            field("stage", "ProcessingStage"),
+            optional("reason", "string"),
+            field("progress", "number"),
+            optional("current_file", "string"),
+            field("files_completed", "number"),
+            field("total_files", "number"),
+            optional("eta_seconds", "number"),
+            optional("speed", "number"),
+            optional("bytes_written", "number"),
+        ]),
+    },
+    TsDecl {
+        name: "ProgressEvent",
+        doc: "Payload of the processing-progress event",
+        body: TsBody::Interface(&[
+            field("stage", "ProcessingStage"),
+            optional("reason", "string"),
+            field("percentage", "number"),
+            field("message", "string"),
+            optional("current_file", "string"),
+            optional("eta_seconds", "number"),
+            optional("timeline", "StageTiming[]"),
+            optional("skipped_inputs", "SkippedInput[]"),
+            optional("label", "string"),
+            optional("notes", "string"),
+            optional("cancel_reason", "CancelReason"),
+            optional("metadata_pending", "boolean"),
+            optional("warnings", "string[]"),
+            optional("input_stats", "InputDurationStats"),
+        ]),
+    },
+    TsDecl {
+        name: "StageTiming",
+        doc: "Start and duration of one pipeline stage",
+        body: TsBody::Interface(&[
+            field("stage", "TimelineStage"),
+            field("startedAtMs", "number"),
+            field("durationMs", "number"),
+            field("finished", "boolean"),
+        ]),
+    },
+    TsDecl {
+        name: "TimelineStage",
+        doc: "Timed pipeline stage",
+        body: TsBody::Alias("'analyzing' | 'converting' | 'merging' | 'writing_metadata' | 'publish' | 'cleanup'"),
+    },
+    TsDecl {
+        name: "SkippedInput",
+        doc: "An input left out under the SkipWithWarning policy",
+        body: TsBody::Interface(&[
+            field("path", "string"),
+            optional("error", "string"),
+        ]),
+    },
+    TsDecl {
+        name: "InputDurationStats",
+        doc: "Count and spread of input durations, in seconds",
+        body: TsBody::Interface(&[
+            field("count", "number"),
+            field("minSecs", "number"),
+            field("maxSecs", "number"),
+            field("meanSecs", "number"),
+            field("medianSecs", "number"),
+            field("totalSecs", "number"),
+        ]),
+    },
+    TsDecl {
+        name: "DiscGroup",
+        doc: "Files of one disc in a multi-disc input list",
+        body: TsBody::Interface(&[
+            field("disc", "number"),
+            field("fileCount", "number"),
+        ]),
+    },
+    TsDecl {
+        name: "CancelReason",
+        doc: "Which code path stopped a run",
+        body: TsBody::Alias("'user_requested' | 'watchdog' | 'quota' | 'timeout' | 'shutdown' | 'soft_stopped'"),
+    },
+    TsDecl {
+        name: "ErrorPayload",
+        doc: "Structured form of a backend error",
+        body: TsBody::Interface(&[
+            field("code", "ErrorCode"),
+            field("message", "string"),
+            optional("cancelReason", "CancelReason"),
+            optional("ffmpegOrigin", "BinaryOrigin"),
+        ]),
+    },
+    TsDecl {
+        name: "BinaryOrigin",
+        doc: "Where the FFmpeg binary in use came from",
+        body: TsBody::Alias("'bundled' | 'legacy' | 'system' | 'override'"),
+    },
+    TsDecl {
+        name: "ErrorCode",
+        doc: "Kind of a backend error",
+        body: TsBody::Alias(
+            "'ffmpeg' | 'file_validation' | 'invalid_input' | 'file_in_use' | 'path_not_granted' | 'cancelled' | 'io' | 'metadata' \
+             | 'process_termination' | 'temp_directory_creation' | 'resource_cleanup' | 'general'",
+        ),
+    },
+];
+
+/// The declaration named `name`, if any
+#[cfg(test)]
+pub fn declaration(name: &str) -> Option<&'static TsDecl> {
+    DECLARATIONS.iter().find(|decl| decl.name == name)
+}
+
+/// Contents of `src/types/contracts.ts`
+pub fn typescript_definitions() -> String {
+    let mut out = String::from(
+        "// Generated from src-tauri/src/contracts by `cargo run --features ts-export --bin generate_ts_types`.\n\
+         // Do not edit by hand.\n",
+    );
+    for decl in DECLARATIONS {
+        out.push_str(&format!("\n/** {} */\n", decl.doc));
+        match &decl.body {
+            TsBody::Interface(fields) => {
+                out.push_str(&format!("export interface {} {{\n", decl.name));
+                for field in *fields {
+                    let marker = if field.optional { "?" } else { "" };
+                    out.push_str(&format!("  {}{marker}: {};\n", field.name, field.ty));
+                }
+                out.push_str("}\n");
+            }
+            TsBody::Alias(target) => out.push_str(&format!("export type {} = {target};\n", decl.name)),
+        }
+    }
+    out
+}
@@ -1,5 +1,37 @@
 use thiserror::Error;
-use crate::ffmpeg::FFmpegError;
+use crate::ffmpeg::{BinaryOrigin, FFmpegError};
+use serde::{Deserialize, Serialize};
+
+/// Which code path stopped a processing run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelReason {
+    /// The user pressed cancel
+    UserRequested,
+    /// A watchdog found the run stuck
+    Watchdog,
+    /// A disk or resource quota would be exceeded
+    Quota,
+    /// The run exceeded its time limit
+    Timeout,
+    /// The app is shutting down
+    Shutdown,
+    /// The user asked to stop once the unit in progress was done
+    SoftStopped,
+}
+
+impl std::fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::UserRequested => "cancelled by user",
+            Self::Watchdog => "stopped by watchdog",
+            Self::Quota => "quota exceeded",
+            Self::Timeout => "timed out",
+            Self::Shutdown => "application shutting down",
+            Self::SoftStopped => "stopped after the current unit",
+        })
+    }
+}
 
 /// Application-wide error type for structured error handling
 #[derive(Error, Debug)]
@@ -13,6 +45,15 @@ pub enum AppError {
     #[error("Invalid input: {0}")]
     InvalidInput(String),
     
+    #[error("FILE_IN_USE: File is open in another application: {0}")]
+    FileInUse(String),
+    
+    #[error("PATH_NOT_GRANTED: Path is outside the files and folders opened in the app: {0}")]
+    PathNotGranted(String),
+    
+    #[error("Processing was cancelled: {0}")]
+    Cancelled(CancelReason),
+    
     #[error("IO operation failed: {0}")]
     Io(#[from] std::io::Error),
     
@@ -34,6 +75,83 @@ pub enum AppError {
 
 pub type Result<T> = std::result::Result<T, AppError>;
 
+impl AppError {
+    /// The cancellation reason, if this error is a cancellation
+    pub fn cancel_reason(&self) -> Option<CancelReason> {
+        match self {
+            Self::Cancelled(reason) => Some(*reason),
+            _ => None,
+        }
+    }
+
+    /// Origin of the FFmpeg binary, if this error records it
+    pub fn ffmpeg_origin(&self) -> Option<BinaryOrigin> {
+        match self {
+            Self::FFmpeg(error) => error.origin(),
+            _ => None,
+        }
+    }
+}
+
+/// Kind of an `AppError`, stable across message wording changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Ffmpeg,
+    FileValidation,
+    InvalidInput,
+    FileInUse,
+    PathNotGranted,
+    Cancelled,
+    Io,
+    Metadata,
+    ProcessTermination,
+    TempDirectoryCreation,
+    ResourceCleanup,
+    General,
+}
+
+/// Structured form of an `AppError` for the frontend
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorPayload {
+    /// Error kind
+    pub code: ErrorCode,
+    /// Full error message, as sent in command results
+    pub message: String,
+    /// Why the run stopped, for cancellations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancel_reason: Option<CancelReason>,
+    /// Where the failing FFmpeg binary came from, for FFmpeg errors that record it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ffmpeg_origin: Option<BinaryOrigin>,
+}
+
+impl From<&AppError> for ErrorPayload {
+    fn from(error: &AppError) -> Self {
+        let code = match error {
+            AppError::FFmpeg(_) => ErrorCode::Ffmpeg,
+            AppError::FileValidation(_) => ErrorCode::FileValidation,
+            AppError::InvalidInput(_) => ErrorCode::InvalidInput,
+            AppError::FileInUse(_) => ErrorCode::FileInUse,
+            AppError::PathNotGranted(_) => ErrorCode::PathNotGranted,
+            AppError::Cancelled(_) => ErrorCode::Cancelled,
+            AppError::Io(_) => ErrorCode::Io,
+            AppError::Metadata(_) => ErrorCode::Metadata,
+            AppError::ProcessTermination(_) => ErrorCode::ProcessTermination,
+            AppError::TempDirectoryCreation(_) => ErrorCode::TempDirectoryCreation,
+            AppError::ResourceCleanup(_) => ErrorCode::ResourceCleanup,
+            AppError::General(_) => ErrorCode::General,
+        };
+        Self {
+            code,
+            message: error.to_string(),
+            cancel_reason: error.cancel_reason(),
+            ffmpeg_origin: error.ffmpeg_origin(),
+        }
+    }
+}
+
 /// Convert AppError to string for Tauri command results
 impl From<AppError> for String {
     fn from(error: AppError) -> Self {
@@ -42,6 +160,7 @@ impl From<AppError> for String {
 }
 
 /// Convert AppError to Tauri InvokeError for command integration
+#[cfg(feature = "gui")]
 impl From<AppError> for tauri::ipc::InvokeError {
     fn from(error: AppError) -> Self {
         tauri::ipc::InvokeError::from_anyhow(anyhow::anyhow!(error))
@@ -65,4 +184,37 @@ mod tests {
         let app_error = AppError::from(ffmpeg_error);
         assert!(matches!(app_error, AppError::FFmpeg(_)));
     }
+
+    #[test]
+    fn test_cancelled_error_carries_reason() {
+        let error = AppError::Cancelled(CancelReason::Watchdog);
+        assert_eq!(error.cancel_reason(), Some(CancelReason::Watchdog));
+        assert_eq!(error.to_string(), "Processing was cancelled: stopped by watchdog");
+        assert_eq!(AppError::General("x".to_string()).cancel_reason(), None);
+        assert_eq!(serde_json::to_string(&CancelReason::UserRequested).unwrap(), "\"user_requested\"");
+    }
+
+    #[test]
+    fn test_error_payload() {
+        let payload = ErrorPayload::from(&AppError::Cancelled(CancelReason::Timeout));
+        assert_eq!(payload.code, ErrorCode::Cancelled);
+        assert_eq!(payload.cancel_reason, Some(CancelReason::Timeout));
+        let payload = ErrorPayload::from(&AppError::FileInUse("/book.m4b".to_string()));
+        assert_eq!(payload.code, ErrorCode::FileInUse);
+        assert!(payload.message.starts_with("FILE_IN_USE"));
+        assert_eq!(payload.cancel_reason, None);
+        assert_eq!(payload.ffmpeg_origin, None);
+        let payload = ErrorPayload::from(&AppError::PathNotGranted("/etc/passwd".to_string()));
+        assert_eq!(payload.code, ErrorCode::PathNotGranted);
+        assert!(payload.message.starts_with("PATH_NOT_GRANTED"));
+
+        let error = AppError::FFmpeg(FFmpegError::EncoderUnavailable {
+            encoder: "libfdk_aac".to_string(),
+            origin: BinaryOrigin::System,
+        });
+        let payload = ErrorPayload::from(&error);
+        assert_eq!(payload.code, ErrorCode::Ffmpeg);
+        assert_eq!(payload.ffmpeg_origin, Some(BinaryOrigin::System));
+        assert_eq!(serde_json::to_value(&payload).unwrap()["ffmpegOrigin"], "system");
+    }
 }
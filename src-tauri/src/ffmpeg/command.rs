@@ -1,6 +1,23 @@
+use serde::Serialize;
 use std::path::PathBuf;
 use std::process::Command;
-use super::{FFmpegError, Result, locate_ffmpeg};
+use super::{BinaryOrigin, FFmpegError, Result, locate_ffmpeg, locate_ffmpeg_with_origin};
+
+/// Which FFmpeg is in use and whether it can encode, for support diagnostics
+///
+/// Gathered locally on request; nothing is sent anywhere.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FFmpegDiagnostics {
+    pub path: PathBuf,
+    pub origin: BinaryOrigin,
+    /// First line of `ffmpeg -version`, if it ran
+    pub version: Option<String>,
+    /// Encoder the app needs
+    pub encoder: String,
+    /// Whether the binary lists that encoder
+    pub encoder_available: bool,
+}
 
 pub struct FFmpegCommand {
     binary_path: PathBuf,
@@ -171,6 +188,23 @@ impl FFmpegCommand {
     }
 }
 
+/// Reports the located FFmpeg, its origin, and whether it has `encoder`
+pub fn diagnostics(encoder: &str) -> Result<FFmpegDiagnostics> {
+    let (path, origin) = locate_ffmpeg_with_origin()?;
+    let version = FFmpegCommand::version().ok();
+    let output = Command::new(&path)
+        .args(["-hide_banner", "-encoders"])
+        .output()
+        .map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))?;
+    let encoder_available = lists_encoder(&String::from_utf8_lossy(&output.stdout), encoder);
+    Ok(FFmpegDiagnostics { path, origin, version, encoder: encoder.to_string(), encoder_available })
+}
+
+/// Whether `ffmpeg -encoders` output lists `encoder` (" A....D name  description")
+fn lists_encoder(output: &str, encoder: &str) -> bool {
+    output.lines().any(|line| line.split_whitespace().nth(1) == Some(encoder))
+}
+
 /// Parse FFmpeg version from output
 fn parse_version(output: &str) -> Result<String> {
     let first_line = output
@@ -234,6 +268,13 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_lists_encoder() {
+        let output = "Encoders:\n ------\n A....D aac                  AAC (Advanced Audio Coding)\n A....D libfdk_aac           Fraunhofer FDK AAC\n";
+        assert!(lists_encoder(output, "libfdk_aac"));
+        assert!(!lists_encoder(&output.replace("libfdk_aac", "libmp3lame"), "libfdk_aac"));
+    }
+    
     #[test]
     fn test_parse_version_invalid() {
         let invalid_output = "not a version string";
@@ -1,66 +1,137 @@
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 use thiserror::Error;
 
 pub mod command;
+pub mod probe;
+
+/// FFmpeg binary chosen in preferences, if any
+static FFMPEG_OVERRIDE: OnceLock<Mutex<Option<PathBuf>>> = OnceLock::new();
+
+/// Where the FFmpeg binary in use came from
+///
+/// Bundled builds ship libfdk_aac; system packages usually don't, so the
+/// origin explains most encoder failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryOrigin {
+    /// `ffmpeg-universal` shipped in the app bundle
+    Bundled,
+    /// `binaries/ffmpeg` next to the executable
+    Legacy,
+    /// Found on PATH or in a common install location
+    System,
+    /// Chosen in preferences
+    Override,
+}
+
+impl std::fmt::Display for BinaryOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Bundled => "bundled",
+            Self::Legacy => "legacy bundled",
+            Self::System => "system",
+            Self::Override => "override",
+        })
+    }
+}
 
 #[derive(Error, Debug)]
 pub enum FFmpegError {
     #[error("FFmpeg binary not found. Please install FFmpeg or place it in the binaries directory")]
     BinaryNotFound,
     
+    #[error("FFmpeg was found at {} but it isn't runnable ({reason}). It may be damaged or built for another architecture", path.display())]
+    BinaryInvalid { path: PathBuf, reason: String },
+    
+    #[error("FFprobe binary not found. Please install FFmpeg (which ships ffprobe) or place it in the binaries directory")]
+    ProbeNotFound,
+    
     #[error("Failed to execute FFmpeg: {0}")]
     ExecutionFailed(String),
     
     #[error("FFmpeg output parsing failed: {0}")]
     ParseError(String),
     
+    #[error("Failed to execute the {origin} FFmpeg: {message}")]
+    ProcessFailed { message: String, origin: BinaryOrigin },
+    
+    #[error("The {origin} FFmpeg has no '{encoder}' encoder. Choose an FFmpeg built with {encoder} in preferences, or use the app's bundled FFmpeg")]
+    EncoderUnavailable { encoder: String, origin: BinaryOrigin },
+}
+
+impl FFmpegError {
+    /// Origin of the binary that failed, when the error records it
+    pub fn origin(&self) -> Option<BinaryOrigin> {
+        match self {
+            Self::ProcessFailed { origin, .. } | Self::EncoderUnavailable { origin, .. } => Some(*origin),
+            _ => None,
+        }
+    }
+}
+
+/// Translates an FFmpeg "Unknown encoder 'name'" stderr line into a targeted error
+pub fn unknown_encoder_error(line: &str, origin: BinaryOrigin) -> Option<FFmpegError> {
+    let rest = &line[line.find("Unknown encoder")? + "Unknown encoder".len()..];
+    let encoder = rest.trim().trim_matches(['\'', '"']).trim();
+    (!encoder.is_empty()).then(|| FFmpegError::EncoderUnavailable { encoder: encoder.to_string(), origin })
+}
+
+/// Stores the FFmpeg binary to use instead of the bundled or system one, or clears it with None
+pub fn set_ffmpeg_override(path: Option<&Path>) {
+    if let Ok(mut current) = FFMPEG_OVERRIDE.get_or_init(Default::default).lock() {
+        *current = path.map(Path::to_path_buf);
+    }
+}
+
+/// The FFmpeg binary set in preferences, if any
+pub fn ffmpeg_override() -> Option<PathBuf> {
+    FFMPEG_OVERRIDE.get_or_init(Default::default).lock().ok()?.clone()
 }
 
 pub type Result<T> = std::result::Result<T, FFmpegError>;
 
 /// Locate the FFmpeg binary
-/// Checks in order:
-/// 1. Bundled binary in app bundle (macOS distribution)
-/// 2. Bundled binary in binaries directory (development)
-/// 3. System PATH
-/// 4. Common macOS locations
 pub fn locate_ffmpeg() -> Result<PathBuf> {
+    locate_ffmpeg_with_origin().map(|(path, _)| path)
+}
+
+/// Locate the FFmpeg binary and where it came from
+/// Checks in order:
+/// 1. Override chosen in preferences
+/// 2. Bundled binary in app bundle (macOS distribution)
+/// 3. Bundled binary in binaries directory (legacy and development)
+/// 4. System PATH
+/// 5. Common macOS locations
+///
+/// A candidate that exists but can't be run is skipped for the next one;
+/// if none is usable, the first such candidate is reported as
+/// `BinaryInvalid` rather than `BinaryNotFound`.
+pub fn locate_ffmpeg_with_origin() -> Result<(PathBuf, BinaryOrigin)> {
+    select_binary(ffmpeg_candidates(), true)
+}
+
+/// Every place FFmpeg may be, in search order
+fn ffmpeg_candidates() -> Vec<(PathBuf, BinaryOrigin)> {
+    let mut candidates = Vec::new();
+    if let Some(path) = ffmpeg_override() {
+        candidates.push((path, BinaryOrigin::Override));
+    }
+
     // Check bundled binary in app bundle first (for distributed apps)
     if let Ok(exe_path) = std::env::current_exe() {
         if let Some(app_dir) = exe_path.parent() {
-            // Check for the external binary bundled by Tauri
-            let bundled_external = app_dir.join("ffmpeg-universal");
-            if bundled_external.exists() {
-                return Ok(bundled_external);
-            }
-            
-            // Check legacy location (binaries/ffmpeg)
-            let bundled_legacy = app_dir.join("binaries").join("ffmpeg");
-            if bundled_legacy.exists() {
-                return Ok(bundled_legacy);
-            }
-        }
-    }
-    
-    // Check development location (binaries directory relative to project root)
-    let bundled = std::env::current_exe()
-        .ok()
-        .and_then(|exe| exe.parent().map(|p| p.to_path_buf()))
-        .map(|mut p| {
-            p.push("binaries");
-            p.push("ffmpeg");
-            p
-        });
-    
-    if let Some(path) = bundled {
-        if path.exists() {
-            return Ok(path);
+            // The external binary bundled by Tauri
+            candidates.push((app_dir.join("ffmpeg-universal"), BinaryOrigin::Bundled));
+            // Legacy location (binaries/ffmpeg), also used in development
+            candidates.push((app_dir.join("binaries").join("ffmpeg"), BinaryOrigin::Legacy));
         }
     }
     
     // Check system PATH
     if let Ok(path) = which::which("ffmpeg") {
-        return Ok(path);
+        candidates.push((path, BinaryOrigin::System));
     }
     
     // Check common macOS locations
@@ -69,6 +140,114 @@ pub fn locate_ffmpeg() -> Result<PathBuf> {
         "/opt/homebrew/bin/ffmpeg",
         "/usr/bin/ffmpeg",
     ];
+    candidates.extend(common_paths.iter().map(|path| (PathBuf::from(path), BinaryOrigin::System)));
+    candidates
+}
+
+/// First usable binary among `candidates`
+///
+/// Missing candidates are passed over silently. Present ones are checked
+/// by metadata; with `spawn_check`, the first to pass is also run with
+/// `-version` once, which catches a wrong-architecture build.
+fn select_binary(
+    candidates: impl IntoIterator<Item = (PathBuf, BinaryOrigin)>,
+    spawn_check: bool,
+) -> Result<(PathBuf, BinaryOrigin)> {
+    let mut first_invalid = None;
+    let mut spawned = false;
+    for (path, origin) in candidates {
+        if !path.exists() {
+            continue;
+        }
+        let mut problem = unusable_reason(&path);
+        if problem.is_none() && spawn_check && !spawned {
+            spawned = true;
+            problem = version_check_failure(&path);
+        }
+        match problem {
+            None => return Ok((path, origin)),
+            Some(reason) => {
+                log::warn!("Skipping FFmpeg candidate {}: {reason}", path.display());
+                first_invalid.get_or_insert((path, reason));
+            }
+        }
+    }
+    Err(match first_invalid {
+        Some((path, reason)) => FFmpegError::BinaryInvalid { path, reason },
+        None => FFmpegError::BinaryNotFound,
+    })
+}
+
+/// Why the file at `path` can't be an executable, judged by its metadata
+fn unusable_reason(path: &Path) -> Option<String> {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => return Some(format!("cannot read it: {e}")),
+    };
+    if !metadata.is_file() {
+        return Some("not a file".to_string());
+    }
+    if metadata.len() == 0 {
+        return Some("the file is empty".to_string());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Some("the file is not executable".to_string());
+        }
+    }
+    None
+}
+
+/// Paths whose `-version` run has succeeded, so the check runs once per binary
+static VERIFIED_BINARIES: OnceLock<Mutex<Vec<PathBuf>>> = OnceLock::new();
+
+/// Why running `path -version` failed, or None when it ran cleanly
+fn version_check_failure(path: &Path) -> Option<String> {
+    let verified = VERIFIED_BINARIES.get_or_init(Default::default);
+    if verified.lock().is_ok_and(|paths| paths.iter().any(|p| p == path)) {
+        return None;
+    }
+    let failure = match std::process::Command::new(path)
+        .arg("-version")
+        .stdin(std::process::Stdio::null())
+        .output()
+    {
+        Ok(output) if output.status.success() => None,
+        Ok(output) => Some(format!("'-version' exited with {}", output.status)),
+        Err(e) => Some(format!("it could not be started: {e}")),
+    };
+    if failure.is_none() {
+        if let Ok(mut paths) = verified.lock() {
+            paths.push(path.to_path_buf());
+        }
+    }
+    failure
+}
+
+/// Locate the FFprobe binary
+/// Checks in order:
+/// 1. Next to the located FFmpeg binary (bundled builds ship both)
+/// 2. System PATH
+/// 3. Common macOS locations
+pub fn locate_ffprobe() -> Result<PathBuf> {
+    if let Some(dir) = locate_ffmpeg().ok().as_deref().and_then(|p| p.parent()) {
+        let sibling = dir.join(format!("ffprobe{}", std::env::consts::EXE_SUFFIX));
+        if sibling.exists() {
+            return Ok(sibling);
+        }
+    }
+    
+    if let Ok(path) = which::which("ffprobe") {
+        return Ok(path);
+    }
+    
+    let common_paths = [
+        "/usr/local/bin/ffprobe",
+        "/opt/homebrew/bin/ffprobe",
+        "/usr/bin/ffprobe",
+    ];
     
     for path in &common_paths {
         let path = PathBuf::from(path);
@@ -77,7 +256,7 @@ pub fn locate_ffmpeg() -> Result<PathBuf> {
         }
     }
     
-    Err(FFmpegError::BinaryNotFound)
+    Err(FFmpegError::ProbeNotFound)
 }
 
 #[cfg(test)]
@@ -95,4 +274,84 @@ mod tests {
             assert!(path.exists() || path.to_str().map_or(false, |s| s.contains("ffmpeg")));
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_unknown_encoder_translation() {
+        let line = "[aost#0:0 @ 0x600] Unknown encoder 'libfdk_aac'";
+        let error = unknown_encoder_error(line, BinaryOrigin::System).unwrap();
+        assert_eq!(error.origin(), Some(BinaryOrigin::System));
+        let message = error.to_string();
+        assert!(message.starts_with("The system FFmpeg has no 'libfdk_aac' encoder"));
+        assert!(message.contains("in preferences"));
+        assert!(message.contains("bundled FFmpeg"));
+
+        assert!(unknown_encoder_error("Error selecting an encoder", BinaryOrigin::System).is_none());
+        assert!(unknown_encoder_error("Unknown encoder ''", BinaryOrigin::Bundled).is_none());
+        assert_eq!(FFmpegError::BinaryNotFound.origin(), None);
+    }
+
+    /// Writes a file in `dir`, marked executable on Unix
+    fn write_binary(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn test_empty_candidate_falls_through_to_next_location() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let empty = write_binary(temp_dir.path(), "ffmpeg-universal", "");
+        let system = write_binary(temp_dir.path(), "ffmpeg", "#!/bin/sh\n");
+        let missing = temp_dir.path().join("binaries").join("ffmpeg");
+
+        let candidates = vec![
+            (missing.clone(), BinaryOrigin::Legacy),
+            (empty.clone(), BinaryOrigin::Bundled),
+            (system.clone(), BinaryOrigin::System),
+        ];
+        let (path, origin) = select_binary(candidates, false).unwrap();
+        assert_eq!((path, origin), (system, BinaryOrigin::System));
+
+        let error = select_binary(vec![(missing.clone(), BinaryOrigin::Legacy), (empty.clone(), BinaryOrigin::Bundled)], false)
+            .unwrap_err();
+        assert!(matches!(&error, FFmpegError::BinaryInvalid { path, reason } if *path == empty && reason.contains("empty")));
+        assert!(error.to_string().contains("isn't runnable"));
+
+        let error = select_binary(vec![(missing, BinaryOrigin::Legacy)], false).unwrap_err();
+        assert!(matches!(error, FFmpegError::BinaryNotFound));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unrunnable_candidates_are_skipped() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let not_executable = write_binary(temp_dir.path(), "override-ffmpeg", "#!/bin/sh\n");
+        std::fs::set_permissions(&not_executable, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let broken = write_binary(temp_dir.path(), "ffmpeg-universal", "#!/bin/sh\nexit 1\n");
+        let working = write_binary(temp_dir.path(), "ffmpeg", "#!/bin/sh\nexit 0\n");
+
+        let candidates = vec![
+            (not_executable.clone(), BinaryOrigin::Override),
+            (broken, BinaryOrigin::Bundled),
+            (working.clone(), BinaryOrigin::System),
+        ];
+        // Only the first candidate passing the metadata check is run
+        let error = select_binary(candidates.clone()[..2].to_vec(), true).unwrap_err();
+        assert!(matches!(error, FFmpegError::BinaryInvalid { path, reason } if path == not_executable && reason.contains("not executable")));
+        assert!(select_binary(candidates, true).is_ok_and(|(path, _)| path == working));
+    }
+
+    #[test]
+    fn test_locate_ffprobe() {
+        // Like FFmpeg, ffprobe may be missing; only check a found path
+        if let Ok(path) = locate_ffprobe() {
+            assert!(path.exists());
+        }
+    }
+}
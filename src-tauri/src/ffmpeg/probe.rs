@@ -0,0 +1,190 @@
+//! Raw FFprobe access for debugging unusual input files
+
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use super::{FFmpegError, Result, locate_ffprobe};
+use crate::audio::constants::PROBE_POLL_INTERVAL_MS;
+
+/// Arguments for a full JSON report: format, streams and chapters
+const PROBE_ARGS: [&str; 7] = ["-v", "error", "-show_format", "-show_streams", "-show_chapters", "-of", "json"];
+
+/// Runs ffprobe on a file and returns its JSON report untouched
+/// Includes format, stream and chapter sections
+pub fn probe_raw(path: &Path) -> Result<serde_json::Value> {
+    let binary = locate_ffprobe()?;
+    
+    let output = Command::new(&binary)
+        .args(PROBE_ARGS)
+        .arg(path)
+        .output()
+        .map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))?;
+    
+    if !output.status.success() {
+        return Err(FFmpegError::ExecutionFailed(
+            String::from_utf8_lossy(&output.stderr).to_string()
+        ));
+    }
+    
+    parse_probe_output(&output.stdout)
+}
+
+/// Kills and reaps the child when dropped, so no early return leaves ffprobe running
+struct ChildGuard(Child);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if matches!(self.0.try_wait(), Ok(None)) {
+            let _ = self.0.kill();
+        }
+        let _ = self.0.wait();
+    }
+}
+
+/// Like `probe_raw`, but ffprobe is killed once `timeout` passes
+///
+/// A damaged file can keep ffprobe busy for minutes; past the timeout the
+/// probe fails with `ExecutionFailed` rather than waiting.
+pub fn probe_raw_within(path: &Path, timeout: Duration) -> Result<serde_json::Value> {
+    let binary = locate_ffprobe()?;
+    let child = Command::new(&binary)
+        .args(PROBE_ARGS)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))?;
+    let mut guard = ChildGuard(child);
+    // Drain stdout concurrently so a large report can't block ffprobe on a full pipe
+    let stdout_reader = guard.0.stdout.take().map(|mut stdout| std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = stdout.read_to_end(&mut buffer);
+        buffer
+    }));
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = guard.0.try_wait().map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            return Err(FFmpegError::ExecutionFailed(format!(
+                "ffprobe timed out after {:.1} s on {}", timeout.as_secs_f64(), path.display()
+            )));
+        }
+        std::thread::sleep(Duration::from_millis(PROBE_POLL_INTERVAL_MS));
+    };
+    let stdout = stdout_reader.and_then(|reader| reader.join().ok()).unwrap_or_default();
+    if !status.success() {
+        return Err(FFmpegError::ExecutionFailed(format!("ffprobe exited with {status}")));
+    }
+    parse_probe_output(&stdout)
+}
+
+/// Returns the codec name of the first audio stream, if ffprobe reports one within `timeout`
+pub fn probe_codec_name(path: &Path, timeout: Duration) -> Result<Option<String>> {
+    Ok(audio_codec_name(&probe_raw_within(path, timeout)?))
+}
+
+/// Finds the first audio stream's codec name in an ffprobe report
+fn audio_codec_name(report: &serde_json::Value) -> Option<String> {
+    report["streams"].as_array()?
+        .iter()
+        .find(|stream| stream["codec_type"] == "audio")?
+        ["codec_name"].as_str()
+        .map(str::to_string)
+}
+
+/// Returns the codec tag of the first audio stream (e.g. "mp4a", "drms"), if ffprobe reports one within `timeout`
+pub fn probe_codec_tag(path: &Path, timeout: Duration) -> Result<Option<String>> {
+    Ok(audio_codec_tag(&probe_raw_within(path, timeout)?))
+}
+
+/// Finds the first audio stream's codec tag in an ffprobe report
+fn audio_codec_tag(report: &serde_json::Value) -> Option<String> {
+    report["streams"].as_array()?
+        .iter()
+        .find(|stream| stream["codec_type"] == "audio")?
+        ["codec_tag_string"].as_str()
+        .map(str::to_string)
+}
+
+/// Returns the first audio stream's start time in seconds, if ffprobe reports one
+///
+/// A positive start time means the decoder skips encoder priming samples
+/// (e.g. LAME's 1105 samples); a negative one means priming samples are
+/// still present with pre-zero timestamps (AAC with an edit list).
+pub fn probe_start_time(path: &Path) -> Result<Option<f64>> {
+    Ok(audio_start_time(&probe_raw(path)?))
+}
+
+/// Finds the first audio stream's start time in an ffprobe report
+fn audio_start_time(report: &serde_json::Value) -> Option<f64> {
+    report["streams"].as_array()?
+        .iter()
+        .find(|stream| stream["codec_type"] == "audio")?
+        ["start_time"].as_str()?
+        .parse::<f64>().ok()
+        .filter(|secs| secs.is_finite())
+}
+
+/// Parse ffprobe's JSON output
+fn parse_probe_output(stdout: &[u8]) -> Result<serde_json::Value> {
+    serde_json::from_slice(stdout)
+        .map_err(|e| FFmpegError::ParseError(format!("Invalid ffprobe JSON: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_probe_output_passthrough() {
+        let json = br#"{"streams":[{"codec_name":"mp3","x_custom":1}],"format":{},"chapters":[]}"#;
+        let value = parse_probe_output(json).unwrap();
+        assert_eq!(value["streams"][0]["codec_name"], "mp3");
+        assert_eq!(value["streams"][0]["x_custom"], 1);
+    }
+
+    #[test]
+    fn test_audio_codec_name_skips_video_streams() {
+        let json = br#"{"streams":[{"codec_type":"video","codec_name":"mjpeg"},{"codec_type":"audio","codec_name":"alac"}]}"#;
+        let value = parse_probe_output(json).unwrap();
+        assert_eq!(audio_codec_name(&value).as_deref(), Some("alac"));
+        assert_eq!(audio_codec_name(&serde_json::json!({"streams": []})), None);
+        let encrypted = serde_json::json!({"streams": [{"codec_type": "audio", "codec_name": "aac", "codec_tag_string": "drms"}]});
+        assert_eq!(audio_codec_tag(&encrypted).as_deref(), Some("drms"));
+    }
+
+    #[test]
+    fn test_audio_start_time() {
+        let report = |start: &str| serde_json::json!({
+            "streams": [{"codec_type": "video", "start_time": "5.000000"}, {"codec_type": "audio", "start_time": start}]
+        });
+        assert_eq!(audio_start_time(&report("0.025057")), Some(0.025057));
+        assert_eq!(audio_start_time(&report("-0.021333")), Some(-0.021333));
+        assert_eq!(audio_start_time(&report("0.000000")), Some(0.0));
+        assert_eq!(audio_start_time(&report("N/A")), None);
+        assert_eq!(audio_start_time(&serde_json::json!({"streams": [{"codec_type": "audio"}]})), None);
+    }
+
+    #[test]
+    fn test_parse_probe_output_invalid() {
+        let result = parse_probe_output(b"not json");
+        assert!(matches!(result, Err(FFmpegError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_probe_raw_real_file() {
+        let path = Path::new("../media/01 - Introduction.mp3");
+        if !path.exists() || locate_ffprobe().is_err() {
+            eprintln!("Skipping test - media file or ffprobe not found");
+            return;
+        }
+        let value = probe_raw(path).unwrap();
+        assert!(value["streams"].is_array());
+        assert!(value["format"].is_object());
+    }
+}
@@ -3,15 +3,21 @@
 #![deny(clippy::unwrap_used)]
 #![warn(clippy::too_many_lines)]
 
+mod atomic_write;
+#[cfg(feature = "gui")]
 mod commands;
-mod errors;
-mod ffmpeg;
-mod metadata;
-mod audio;
+pub mod contracts;
+pub mod errors;
+pub mod ffmpeg;
+pub mod metadata;
+pub mod audio;
+pub mod util;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "gui"))]
 mod tests_integration;
 
+#[cfg(feature = "gui")]
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use audio::ProcessingProgress;
 
@@ -21,8 +27,11 @@ pub struct ProcessingState {
     pub is_processing: Arc<Mutex<bool>>,
     pub is_cancelled: Arc<Mutex<bool>>,
     pub progress: Arc<Mutex<Option<ProcessingProgress>>>,
+    /// Why processing was cancelled, set together with `is_cancelled`
+    pub cancel_reason: Arc<Mutex<Option<errors::CancelReason>>>,
 }
 
+#[cfg(feature = "gui")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Initialize logging with INFO level for production
@@ -37,21 +46,139 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .manage(processing_state)
+        .setup(setup_app)
+        .on_window_event(|_window, event| {
+            if let tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) = event {
+                grant_dropped_paths(paths);
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             commands::ping,
             commands::echo,
             commands::validate_files,
             commands::get_ffmpeg_version,
+            commands::get_ffmpeg_diagnostics,
+            commands::run_self_test,
+            commands::set_ffmpeg_override,
+            commands::probe_file_raw,
             commands::merge_audio_files,
             commands::read_audio_metadata,
+            commands::read_cover_art,
             commands::write_audio_metadata,
             commands::write_cover_art,
+            commands::remove_cover_art,
+            commands::write_audio_chapters,
+            commands::read_cue_sheet,
+            commands::retry_metadata_write,
+            commands::undo_metadata_write,
+            commands::is_file_in_use,
+            commands::assess_cover_art,
+            commands::find_local_cover,
             commands::load_cover_art_file,
+            commands::load_description_file,
             commands::analyze_audio_files,
+            commands::detect_dual_mono,
+            commands::measure_mp3_durations,
+            commands::extract_peaks,
+            commands::cancel_peak_extraction,
+            commands::estimate_encode_time,
+            commands::cancel_encode_calibration,
+            commands::estimate_chapters,
+            commands::preview_chapters,
+            commands::suggest_chapters_from_silence,
+            commands::sanitize_filename,
+            commands::set_default_output_dir,
+            commands::get_default_output_dir,
+            commands::set_display_locale,
+            commands::set_post_process_hook,
+            commands::get_post_process_hook,
+            commands::set_analysis_timeout,
+            commands::set_disc_markers,
+            commands::update_default_metadata_profile,
+            commands::get_default_metadata_profile,
+            commands::infer_book_metadata,
+            commands::register_granted_path,
+            commands::get_permissive_path_access,
             commands::validate_audio_settings,
+            commands::validate_metadata,
+            commands::resolve_plan,
             commands::process_audiobook_files,
-            commands::cancel_processing
+            commands::enqueue_audiobook_job,
+            commands::enqueue_audiobook_batch,
+            commands::get_queued_jobs,
+            commands::refresh_queued_job_settings,
+            commands::get_queue_forecast,
+            commands::process_audiobook_queue,
+            commands::get_failed_jobs,
+            commands::retry_failed_job,
+            commands::get_processing_history,
+            commands::check_already_processed,
+            commands::cancel_processing,
+            commands::cancel_session,
+            commands::soft_stop_processing,
+            commands::get_session_timeline,
+            commands::subscribe_progress,
+            commands::get_processing_progress
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::ExitRequested { api, .. } = event {
+                handle_exit_requested(app_handle, &api);
+            }
+        });
+}
+
+/// Opens the history stores, verification queue and path policy
+#[cfg(feature = "gui")]
+fn setup_app(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
+    use tauri::Manager;
+    let data_dir = app.path().app_data_dir();
+    match &data_dir {
+        Ok(dir) => {
+            audio::job_history::init_processing_history(dir);
+            metadata::history::init_global_history(dir);
+        }
+        Err(e) => log::warn!("History disabled, no app data dir: {e}"),
+    }
+    audio::verification::init_verification_queue(Arc::new(app.handle().clone()));
+    util::path_policy::init_path_policy(data_dir.ok().as_deref());
+    Ok(())
+}
+
+/// Grants the files and folders dropped on the window
+#[cfg(feature = "gui")]
+fn grant_dropped_paths(paths: &[std::path::PathBuf]) {
+    let Some(policy) = util::path_policy::path_policy() else {
+        return;
+    };
+    for path in paths {
+        if let Err(e) = policy.grant(path) {
+            log::warn!("Could not grant dropped path {}: {e}", path.display());
+        }
+    }
+}
+
+/// Set once the first exit request has started the shutdown sequence
+#[cfg(feature = "gui")]
+static SHUTDOWN_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Holds the exit until running sessions are cancelled and state is flushed
+///
+/// The shutdown runs on a worker thread; when it finishes it exits again,
+/// and that second request goes through.
+#[cfg(feature = "gui")]
+fn handle_exit_requested(app_handle: &tauri::AppHandle, api: &tauri::ExitRequestApi) {
+    if SHUTDOWN_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    api.prevent_exit();
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || {
+        audio::shutdown::ShutdownCoordinator::new().run(
+            &audio::shutdown::ActiveSessions,
+            audio::job_history::flush_processing_history,
+        );
+        app_handle.exit(0);
+    });
 }
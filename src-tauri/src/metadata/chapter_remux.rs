@@ -0,0 +1,246 @@
+//! Chapter remuxing of M4B files
+
+use super::chpl::write_chpl;
+use super::file_in_use::ensure_not_in_use;
+use super::write_lock::with_path_lock;
+use super::ChapterInfo;
+use crate::atomic_write::atomic_replace_with;
+use crate::audio::chapters::{ffmetadata, Chapter, ChapterFormat};
+use crate::audio::constants::{FFMPEG_M4B_MUXER, FFMPEG_METADATA_FORMAT, TEMP_DIR_NAME};
+use crate::errors::{AppError, Result};
+use crate::ffmpeg::{locate_ffmpeg, FFmpegError};
+use lofty::file::TaggedFileExt;
+use lofty::prelude::TagExt;
+use lofty::probe::Probe;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Replaces the chapters of an M4B file without re-encoding its audio
+///
+/// Lofty can't write MP4 chapters, so FFmpeg stream-copies the audio into a
+/// temp file with chapters from an ffmetadata file, and the temp file
+/// replaces the original. The original tag, pictures included, is copied
+/// onto the remuxed file first. `format` decides whether FFmpeg muxes the
+/// chapters, a Nero `chpl` atom is added, or both. An empty list removes
+/// all chapters.
+///
+/// # Errors
+/// `InvalidInput` when a chapter ends before it starts or overlaps the previous one
+pub fn write_chapters<P: AsRef<Path>>(file_path: P, chapters: &[ChapterInfo], format: ChapterFormat) -> Result<()> {
+    let path = file_path.as_ref();
+    validate_chapters(chapters)?;
+    if !path.exists() {
+        return Err(AppError::FileValidation(
+            format!("File not found: {}", path.display())
+        ));
+    }
+    ensure_not_in_use(path)?;
+
+    with_path_lock(path, || {
+        let tag = Probe::open(path)?.read()?.primary_tag().cloned();
+        let metadata_dir = std::env::temp_dir().join(TEMP_DIR_NAME);
+        std::fs::create_dir_all(&metadata_dir)?;
+        let metadata_file = metadata_dir.join(format!("chapters-{}.txt", uuid::Uuid::new_v4()));
+        let muxed: &[ChapterInfo] = if format.muxes_with_ffmpeg() { chapters } else { &[] };
+        std::fs::write(&metadata_file, ffmetadata(&muxed.iter().map(Chapter::from).collect::<Vec<_>>()))?;
+
+        let result = atomic_replace_with(path, |temp| {
+            remux_with_chapters(path, &metadata_file, temp)?;
+            if let Some(tag) = &tag {
+                tag.save_to_path(temp, Default::default())?;
+            }
+            if format.writes_chpl() {
+                write_chpl(temp, chapters)?;
+            }
+            Ok(())
+        });
+        let _ = std::fs::remove_file(&metadata_file);
+        result
+    })
+}
+
+/// Chapters must each end after they start and not overlap the one before
+fn validate_chapters(chapters: &[ChapterInfo]) -> Result<()> {
+    for (index, chapter) in chapters.iter().enumerate() {
+        if chapter.end_ms <= chapter.start_ms {
+            return Err(AppError::InvalidInput(format!(
+                "Chapter {} '{}' ends at {} ms, not after its start at {} ms",
+                index + 1, chapter.title, chapter.end_ms, chapter.start_ms
+            )));
+        }
+        if let Some(previous) = index.checked_sub(1).map(|i| &chapters[i]) {
+            if chapter.start_ms < previous.end_ms {
+                return Err(AppError::InvalidInput(format!(
+                    "Chapter {} '{}' starts at {} ms, before chapter {} ends at {} ms",
+                    index + 1, chapter.title, chapter.start_ms, index, previous.end_ms
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Stream-copies the audio of `input` into `output` with the chapters in `metadata_file`
+fn remux_with_chapters(input: &Path, metadata_file: &Path, output: &Path) -> Result<()> {
+    let result = Command::new(locate_ffmpeg()?)
+        .args(["-v", "error", "-nostdin", "-i"])
+        .arg(input)
+        .args(["-f", FFMPEG_METADATA_FORMAT, "-i"])
+        .arg(metadata_file)
+        .args(["-map", "0:a", "-map_metadata", "-1", "-map_chapters", "1", "-c", "copy", "-f", FFMPEG_M4B_MUXER, "-y"])
+        .arg(output)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| FFmpegError::ExecutionFailed(e.to_string()))?;
+    if !result.status.success() {
+        let errors = String::from_utf8_lossy(&result.stderr).trim().to_string();
+        return Err(FFmpegError::ExecutionFailed(format!("Chapter remux failed: {errors}")).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::writer::fixtures::picture_fingerprint;
+    use crate::metadata::writer::{write_cover_art, write_metadata};
+    use crate::metadata::AudiobookMetadata;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn chapter(title: &str, start_ms: u64, end_ms: u64) -> ChapterInfo {
+        ChapterInfo { title: title.to_string(), start_ms, end_ms }
+    }
+
+    #[test]
+    fn test_validate_chapters() {
+        assert!(validate_chapters(&[]).is_ok());
+        assert!(validate_chapters(&[chapter("One", 0, 1000), chapter("Two", 1000, 2500)]).is_ok());
+
+        let rejected = [
+            vec![chapter("Empty", 500, 500)],
+            vec![chapter("One", 0, 1000), chapter("Overlap", 900, 2000)],
+            vec![chapter("Two", 1000, 2000), chapter("One", 0, 1000)],
+        ];
+        for chapters in rejected {
+            assert!(matches!(validate_chapters(&chapters), Err(AppError::InvalidInput(_))), "{chapters:?}");
+        }
+    }
+
+    #[test]
+    fn test_write_chapters_keeps_audio_tags_and_pictures() {
+        let Ok(ffmpeg) = locate_ffmpeg() else {
+            eprintln!("Skipping test - FFmpeg not found");
+            return;
+        };
+        let temp_dir = TempDir::new().unwrap();
+        let book = temp_dir.path().join("book.m4b");
+        let status = Command::new(&ffmpeg)
+            .args(["-v", "error", "-f", "lavfi", "-i", "sine=frequency=440:duration=3", "-c:a", "aac", "-y"])
+            .arg(&book)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let metadata = AudiobookMetadata { title: Some("Dune".to_string()), ..AudiobookMetadata::new() };
+        write_metadata(&book, &metadata).unwrap();
+        write_cover_art(&book, &[0xFF, 0xD8, 0xFF, 0xE0, 1, 2, 3]).unwrap();
+        let audio_md5 = |path: &Path| {
+            let out = Command::new(&ffmpeg)
+                .args(["-v", "error", "-i"])
+                .arg(path)
+                .args(["-map", "0:a", "-c", "copy", "-f", "md5", "-"])
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&out.stdout).to_string()
+        };
+        let audio_before = audio_md5(&book);
+        let pictures_before = picture_fingerprint(&book);
+
+        let chapters = vec![chapter("Arrival", 0, 1500), chapter("Departure", 1500, 3000)];
+        write_chapters(&book, &chapters, ChapterFormat::Ffmetadata).unwrap();
+
+        let read = crate::metadata::read_metadata(&book).unwrap();
+        assert_eq!(read.title.as_deref(), Some("Dune"));
+        assert_eq!(picture_fingerprint(&book), pictures_before);
+        assert_eq!(audio_md5(&book), audio_before);
+        let titles: Vec<&str> = read.chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["Arrival", "Departure"]);
+
+        let overlapping = [chapter("One", 0, 2000), chapter("Two", 1000, 3000)];
+        assert!(matches!(write_chapters(&book, &overlapping, ChapterFormat::Ffmetadata), Err(AppError::InvalidInput(_))));
+        assert_eq!(crate::metadata::read_metadata(&book).unwrap().chapters.len(), 2);
+    }
+
+    #[test]
+    fn test_write_chapters_both_formats() {
+        use crate::metadata::chpl::fixtures::find_box;
+        let Ok(ffmpeg) = locate_ffmpeg() else {
+            eprintln!("Skipping test - FFmpeg not found");
+            return;
+        };
+        let temp_dir = TempDir::new().unwrap();
+        let book = temp_dir.path().join("book.m4b");
+        let status = Command::new(&ffmpeg)
+            .args(["-v", "error", "-f", "lavfi", "-i", "sine=frequency=440:duration=3", "-c:a", "aac", "-y"])
+            .arg(&book)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let chapters = vec![chapter("Arrival", 0, 1500), chapter("Departure", 1500, 3000)];
+        write_chapters(&book, &chapters, ChapterFormat::Both).unwrap();
+        let probed = crate::metadata::read_metadata(&book).unwrap().chapters;
+        assert_eq!(probed.iter().map(|c| c.title.as_str()).collect::<Vec<_>>(), ["Arrival", "Departure"]);
+        assert!(find_box(&fs::read(&book).unwrap(), &[b"moov", b"udta", b"chpl"]).is_some());
+
+        write_chapters(&book, &chapters, ChapterFormat::Chpl).unwrap();
+        assert!(find_box(&fs::read(&book).unwrap(), &[b"moov", b"udta", b"chpl"]).is_some());
+    }
+
+    #[test]
+    fn test_combined_round_trip_fields_and_cover() {
+        let Ok(ffmpeg) = locate_ffmpeg() else {
+            eprintln!("Skipping test - FFmpeg not found");
+            return;
+        };
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("roundtrip.m4b");
+        let status = Command::new(&ffmpeg)
+            .args(["-v", "error", "-f", "lavfi", "-i", "sine=frequency=440:duration=3", "-c:a", "aac", "-y"])
+            .arg(&file_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut metadata = AudiobookMetadata::new();
+        metadata.title = Some("Round Trip".to_string());
+        metadata.author = Some("Guard Rail".to_string());
+        metadata.narrator = Some("Narrator".to_string());
+        metadata.year = Some(2021);
+        let cover = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x10, 0x20];
+        let chapters = vec![chapter("Arrival", 0, 1500), chapter("Departure", 1500, 3000)];
+        let chapter_titles = |path: &Path| crate::metadata::read_metadata(path).unwrap().chapters
+            .into_iter().map(|c| c.title).collect::<Vec<_>>();
+
+        write_metadata(&file_path, &metadata).unwrap();
+        write_chapters(&file_path, &chapters, ChapterFormat::Ffmetadata).unwrap();
+        // A cover write keeps the chapters
+        write_cover_art(&file_path, &cover).unwrap();
+        assert_eq!(chapter_titles(&file_path), ["Arrival", "Departure"]);
+
+        let result = crate::metadata::read_metadata(&file_path).unwrap();
+        assert_eq!(result.title, metadata.title);
+        assert_eq!(result.author, metadata.author);
+        assert_eq!(result.narrator, metadata.narrator);
+        assert_eq!(result.year, metadata.year);
+        assert_eq!(result.cover_art, Some(cover.clone()));
+
+        // A later metadata write keeps both the chapters and the cover
+        metadata.title = Some("Round Trip, Revised".to_string());
+        write_metadata(&file_path, &metadata).unwrap();
+        let result = crate::metadata::read_metadata(&file_path).unwrap();
+        assert_eq!(result.title, metadata.title);
+        assert_eq!(result.cover_art, Some(cover));
+        assert_eq!(chapter_titles(&file_path), ["Arrival", "Departure"]);
+    }
+}
@@ -0,0 +1,367 @@
+//! Nero `chpl` chapter atoms in MP4 files
+//!
+//! FFmpeg muxes MP4 chapters as a QuickTime chapter track; some players
+//! only read the Nero chapter list in `moov/udta/chpl` instead. This writes
+//! that atom directly: `moov` is rebuilt with a fresh `chpl` in its `udta`,
+//! and when `moov` sits before `mdat` the chunk offsets are shifted by the
+//! size change so the samples still line up. The rest of the file is
+//! copied as is.
+
+use super::ChapterInfo;
+use crate::atomic_write::atomic_replace_with;
+use crate::errors::{AppError, Result};
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+
+/// The chapter count is a single byte
+const CHPL_MAX_CHAPTERS: usize = 255;
+
+/// Titles are prefixed with a single-byte length
+const CHPL_MAX_TITLE_BYTES: usize = 255;
+
+/// Boxes on the way from `moov` to the chunk offset tables
+const SAMPLE_TABLE_PATH: [&[u8; 4]; 4] = [b"trak", b"mdia", b"minf", b"stbl"];
+
+/// A box inside a parent: its type, header length and byte range
+#[derive(Debug, Clone)]
+struct Mp4Box {
+    kind: [u8; 4],
+    header_len: usize,
+    range: Range<usize>,
+}
+
+/// Type, header length and total length of the box at the start of `data`
+///
+/// `remaining` is the space left in the parent; a size of 0 extends to it.
+fn parse_header(data: &[u8], remaining: u64) -> Option<([u8; 4], usize, u64)> {
+    let size = u32::from_be_bytes(data.get(0..4)?.try_into().ok()?);
+    let kind: [u8; 4] = data.get(4..8)?.try_into().ok()?;
+    let (header_len, len) = match size {
+        0 => (8, remaining),
+        1 => (16, u64::from_be_bytes(data.get(8..16)?.try_into().ok()?)),
+        size => (8, u64::from(size)),
+    };
+    (len >= header_len as u64 && len <= remaining).then_some((kind, header_len, len))
+}
+
+/// Boxes directly inside `data`; trailing bytes too short for a header are ignored
+fn children(data: &[u8]) -> Result<Vec<Mp4Box>> {
+    let mut boxes = Vec::new();
+    let mut offset = 0;
+    while data.len() - offset >= 8 {
+        let (kind, header_len, len) = parse_header(&data[offset..], (data.len() - offset) as u64)
+            .ok_or_else(|| malformed(&format!("bad box header at offset {offset}")))?;
+        let end = offset + len as usize;
+        boxes.push(Mp4Box { kind, header_len, range: offset..end });
+        offset = end;
+    }
+    Ok(boxes)
+}
+
+fn malformed(detail: &str) -> AppError {
+    AppError::FileValidation(format!("Malformed MP4 file: {detail}"))
+}
+
+/// A box of type `kind` around `body`
+fn wrap_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(body.len() + 16);
+    if let Ok(size) = u32::try_from(body.len() + 8) {
+        bytes.extend(size.to_be_bytes());
+        bytes.extend(kind);
+    } else {
+        bytes.extend(1u32.to_be_bytes());
+        bytes.extend(kind);
+        bytes.extend(((body.len() + 16) as u64).to_be_bytes());
+    }
+    bytes.extend(body);
+    bytes
+}
+
+/// Encodes a Nero chapter list
+///
+/// Version 1 layout: a reserved word, an 8-bit count, then per chapter the
+/// start in 100 ns units and a length-prefixed UTF-8 title. Like FFmpeg,
+/// only the first 255 chapters fit.
+pub fn chpl_box(chapters: &[ChapterInfo]) -> Vec<u8> {
+    if chapters.len() > CHPL_MAX_CHAPTERS {
+        log::warn!("chpl holds {CHPL_MAX_CHAPTERS} chapters; dropping the last {}", chapters.len() - CHPL_MAX_CHAPTERS);
+    }
+    let chapters = &chapters[..chapters.len().min(CHPL_MAX_CHAPTERS)];
+    let mut body = vec![1, 0, 0, 0, 0, 0, 0, 0, chapters.len() as u8];
+    for chapter in chapters {
+        body.extend(chapter.start_ms.saturating_mul(10_000).to_be_bytes());
+        let mut end = chapter.title.len().min(CHPL_MAX_TITLE_BYTES);
+        while !chapter.title.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.push(end as u8);
+        body.extend(&chapter.title.as_bytes()[..end]);
+    }
+    wrap_box(b"chpl", &body)
+}
+
+/// `udta` without any `chpl`, plus `chpl` when given
+fn rebuild_udta(body: &[u8], chpl: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut rebuilt = Vec::with_capacity(body.len() + chpl.map_or(0, <[u8]>::len));
+    for child in children(body)?.iter().filter(|child| &child.kind != b"chpl") {
+        rebuilt.extend(&body[child.range.clone()]);
+    }
+    rebuilt.extend(chpl.unwrap_or_default());
+    Ok(wrap_box(b"udta", &rebuilt))
+}
+
+/// `moov` with its `udta` rebuilt; a `udta` is added when missing and `chpl` is given
+fn rebuild_moov(body: &[u8], chpl: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut rebuilt = Vec::with_capacity(body.len() + chpl.map_or(0, <[u8]>::len) + 8);
+    let mut has_udta = false;
+    for child in children(body)? {
+        let bytes = &body[child.range.clone()];
+        if &child.kind == b"udta" {
+            has_udta = true;
+            rebuilt.extend(rebuild_udta(&bytes[child.header_len..], chpl)?);
+        } else {
+            rebuilt.extend(bytes);
+        }
+    }
+    if let (false, Some(chpl)) = (has_udta, chpl) {
+        rebuilt.extend(wrap_box(b"udta", chpl));
+    }
+    Ok(wrap_box(b"moov", &rebuilt))
+}
+
+/// Start of every `stco`/`co64` table body inside `data`, with whether it is 64-bit
+fn chunk_offset_tables(data: &[u8], base: usize, depth: usize, tables: &mut Vec<(usize, bool)>) -> Result<()> {
+    for child in children(data)? {
+        let body_start = child.range.start + child.header_len;
+        match (&child.kind, SAMPLE_TABLE_PATH.get(depth)) {
+            (b"stco", None) => tables.push((base + body_start, false)),
+            (b"co64", None) => tables.push((base + body_start, true)),
+            (kind, Some(expected)) if kind == *expected => {
+                chunk_offset_tables(&data[body_start..child.range.end], base + body_start, depth + 1, tables)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Shifts chunk offsets at or past `from` by `delta`, in a rebuilt `moov`
+fn shift_chunk_offsets(moov: &mut [u8], from: u64, delta: i64) -> Result<()> {
+    let (_, header_len, _) = parse_header(moov, moov.len() as u64).ok_or_else(|| malformed("bad moov header"))?;
+    let mut tables = Vec::new();
+    chunk_offset_tables(&moov[header_len..], header_len, 0, &mut tables)?;
+    for (start, wide) in tables {
+        let count_bytes = moov.get(start + 4..start + 8).ok_or_else(|| malformed("truncated chunk offset table"))?;
+        let count = u32::from_be_bytes([count_bytes[0], count_bytes[1], count_bytes[2], count_bytes[3]]) as usize;
+        let width = if wide { 8 } else { 4 };
+        let entries = moov.get_mut(start + 8..start + 8 + count * width)
+            .ok_or_else(|| malformed("truncated chunk offset table"))?;
+        for entry in entries.chunks_exact_mut(width) {
+            let offset = entry.iter().fold(0u64, |offset, byte| (offset << 8) | u64::from(*byte));
+            if offset < from {
+                continue;
+            }
+            let shifted = offset.checked_add_signed(delta).ok_or_else(|| malformed("chunk offset out of range"))?;
+            if wide {
+                entry.copy_from_slice(&shifted.to_be_bytes());
+            } else {
+                let shifted = u32::try_from(shifted)
+                    .map_err(|_| malformed("chunk offset no longer fits a 32-bit table"))?;
+                entry.copy_from_slice(&shifted.to_be_bytes());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Offset and bytes of the top-level `moov` box
+fn read_moov(file: &mut File, file_len: u64) -> Result<(u64, Vec<u8>)> {
+    let mut offset = 0;
+    while file_len - offset >= 8 {
+        let mut head = [0u8; 16];
+        let available = (file_len - offset).min(16) as usize;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut head[..available])?;
+        let (kind, _, len) = parse_header(&head[..available], file_len - offset)
+            .ok_or_else(|| malformed(&format!("bad box header at offset {offset}")))?;
+        if &kind == b"moov" {
+            let mut moov = vec![0u8; len as usize];
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut moov)?;
+            return Ok((offset, moov));
+        }
+        offset += len;
+    }
+    Err(malformed("no moov box"))
+}
+
+/// Replaces the `chpl` atom of an MP4 file; an empty list removes it
+pub fn write_chpl<P: AsRef<Path>>(file_path: P, chapters: &[ChapterInfo]) -> Result<()> {
+    let path = file_path.as_ref();
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let (moov_start, moov) = read_moov(&mut file, file_len)?;
+    let (_, header_len, _) = parse_header(&moov, moov.len() as u64).ok_or_else(|| malformed("bad moov header"))?;
+
+    let chpl = (!chapters.is_empty()).then(|| chpl_box(chapters));
+    let mut rebuilt = rebuild_moov(&moov[header_len..], chpl.as_deref())?;
+    let moov_end = moov_start + moov.len() as u64;
+    let delta = rebuilt.len() as i64 - moov.len() as i64;
+    if moov_end < file_len && delta != 0 {
+        shift_chunk_offsets(&mut rebuilt, moov_end, delta)?;
+    }
+
+    atomic_replace_with(path, |temp| {
+        let mut out = BufWriter::new(File::create(temp)?);
+        file.seek(SeekFrom::Start(0))?;
+        std::io::copy(&mut (&mut file).take(moov_start), &mut out)?;
+        out.write_all(&rebuilt)?;
+        file.seek(SeekFrom::Start(moov_end))?;
+        std::io::copy(&mut file, &mut out)?;
+        out.flush()?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+pub mod fixtures {
+    use super::{children, wrap_box};
+
+    /// Bytes the fixture's single chunk starts with
+    pub const SAMPLE_MARKER: &[u8] = b"AUDIO-SAMPLES";
+
+    /// Body of the box at `path` below the top level of `data`
+    pub fn find_box<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+        let (first, rest) = path.split_first()?;
+        let child = children(data).ok()?.into_iter().find(|child| &child.kind == *first)?;
+        let body = &data[child.range.start + child.header_len..child.range.end];
+        if rest.is_empty() { Some(body) } else { find_box(body, rest) }
+    }
+
+    /// Number of boxes of type `kind` directly inside `data`
+    pub fn count_boxes(data: &[u8], kind: &[u8; 4]) -> usize {
+        children(data).map_or(0, |boxes| boxes.iter().filter(|child| &child.kind == kind).count())
+    }
+
+    /// A minimal MP4 with one chunk, `moov` before (`faststart`) or after `mdat`
+    ///
+    /// The chunk offset is correct for the layout, so it points at `SAMPLE_MARKER`.
+    pub fn mp4(faststart: bool, udta: bool) -> Vec<u8> {
+        let ftyp = wrap_box(b"ftyp", b"M4A \0\0\0\0M4A mp42isom");
+        let mdat = wrap_box(b"mdat", SAMPLE_MARKER);
+        let moov = |chunk_offset: u32| {
+            let stco = wrap_box(b"stco", &[&[0u8; 4][..], &1u32.to_be_bytes(), &chunk_offset.to_be_bytes()].concat());
+            let mdhd = wrap_box(b"mdhd", &[&[0u8; 12][..], &1000u32.to_be_bytes(), &[0u8; 8]].concat());
+            let hdlr = wrap_box(b"hdlr", &[&[0u8; 8][..], b"soun", &[0u8; 13]].concat());
+            let minf = wrap_box(b"minf", &wrap_box(b"stbl", &stco));
+            let trak = wrap_box(b"trak", &wrap_box(b"mdia", &[mdhd, hdlr, minf].concat()));
+            let mut body = [wrap_box(b"mvhd", &[0u8; 100]), trak].concat();
+            if udta {
+                body.extend(wrap_box(b"udta", &wrap_box(b"meta", &[0u8; 12])));
+            }
+            wrap_box(b"moov", &body)
+        };
+        let placeholder = moov(0).len();
+        if faststart {
+            let offset = (ftyp.len() + placeholder + 8) as u32;
+            [ftyp, moov(offset), mdat].concat()
+        } else {
+            let offset = (ftyp.len() + 8) as u32;
+            [ftyp, mdat, moov(offset)].concat()
+        }
+    }
+
+    /// Offset in the fixture's single `stco` entry
+    pub fn chunk_offset(data: &[u8]) -> usize {
+        let stco = find_box(data, &[b"moov", b"trak", b"mdia", b"minf", b"stbl", b"stco"]).unwrap_or_default();
+        stco.get(8..12).map_or(0, |bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{chunk_offset, count_boxes, find_box, mp4, SAMPLE_MARKER};
+    use super::*;
+    use tempfile::TempDir;
+
+    fn chapter(title: &str, start_ms: u64, end_ms: u64) -> ChapterInfo {
+        ChapterInfo { title: title.to_string(), start_ms, end_ms }
+    }
+
+    /// (start in 100 ns units, title) of each entry of a `chpl` body
+    fn chpl_entries(body: &[u8]) -> Vec<(u64, String)> {
+        let mut entries = Vec::new();
+        let mut offset = 9;
+        for _ in 0..body[8] {
+            let start = u64::from_be_bytes(body[offset..offset + 8].try_into().unwrap());
+            let len = body[offset + 8] as usize;
+            entries.push((start, String::from_utf8(body[offset + 9..offset + 9 + len].to_vec()).unwrap()));
+            offset += 9 + len;
+        }
+        entries
+    }
+
+    fn write_fixture(dir: &TempDir, faststart: bool, udta: bool) -> std::path::PathBuf {
+        let path = dir.path().join("book.m4b");
+        std::fs::write(&path, mp4(faststart, udta)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_chpl_written_into_udta() {
+        let dir = TempDir::new().unwrap();
+        let path = write_fixture(&dir, false, true);
+        let chapters = [chapter("Prologue", 0, 61_500), chapter("Arrakis", 61_500, 120_000)];
+        write_chpl(&path, &chapters).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        let chpl = find_box(&data, &[b"moov", b"udta", b"chpl"]).expect("chpl atom");
+        assert_eq!(chpl[0], 1);
+        assert_eq!(chpl_entries(chpl), [(0, "Prologue".to_string()), (615_000_000, "Arrakis".to_string())]);
+        assert!(find_box(&data, &[b"moov", b"udta", b"meta"]).is_some());
+        assert!(data[chunk_offset(&data)..].starts_with(SAMPLE_MARKER));
+
+        // Rewriting replaces the atom; an empty list removes it
+        write_chpl(&path, &chapters[..1]).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(count_boxes(find_box(&data, &[b"moov", b"udta"]).unwrap(), b"chpl"), 1);
+        write_chpl(&path, &[]).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        assert!(find_box(&data, &[b"moov", b"udta", b"chpl"]).is_none());
+        assert_eq!(data, mp4(false, true));
+    }
+
+    #[test]
+    fn test_chunk_offsets_follow_a_grown_moov() {
+        let dir = TempDir::new().unwrap();
+        let path = write_fixture(&dir, true, false);
+        let before = chunk_offset(&std::fs::read(&path).unwrap());
+        write_chpl(&path, &[chapter("One", 0, 1_000), chapter("Two", 1_000, 2_000)]).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        assert!(find_box(&data, &[b"moov", b"udta", b"chpl"]).is_some());
+        assert!(chunk_offset(&data) > before);
+        assert!(data[chunk_offset(&data)..].starts_with(SAMPLE_MARKER));
+    }
+
+    #[test]
+    fn test_chpl_caps_count_and_title_length() {
+        let long_title = "é".repeat(200);
+        let chapters: Vec<ChapterInfo> = (0..300).map(|index| chapter(&long_title, index * 1_000, (index + 1) * 1_000)).collect();
+        let chpl = chpl_box(&chapters);
+        let entries = chpl_entries(&chpl[8..]);
+        assert_eq!(entries.len(), CHPL_MAX_CHAPTERS);
+        assert_eq!(entries[0].1.len(), 254);
+        assert!(write_chpl(Path::new("/nonexistent.m4b"), &chapters).is_err());
+    }
+
+    #[test]
+    fn test_rejects_files_without_moov() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("not.m4b");
+        std::fs::write(&path, b"plain text, no boxes at all").unwrap();
+        assert!(matches!(write_chpl(&path, &[chapter("One", 0, 1_000)]), Err(AppError::FileValidation(_))));
+    }
+}
@@ -0,0 +1,206 @@
+//! Cover art conversion to JPEG before embedding
+//!
+//! Covers picked in the app may be JPEG, PNG or WebP, but MP4 cover atoms
+//! can only declare JPEG or PNG, and some players render nothing but JPEG.
+//! Covers that aren't JPEG are converted with FFmpeg, so the declared MIME
+//! type always matches the bytes. A PNG FFmpeg can't convert is embedded
+//! as a PNG; any other cover that can't be converted is refused.
+//!
+//! Processing also keeps the embedded cover under a size limit: a cover
+//! over it is re-encoded as JPEG at decreasing sizes until one fits.
+
+use super::cover_info::{image_format, ImageFormat};
+use crate::audio::constants::{COVER_DOWNSCALE_SIDES, COVER_JPEG_QSCALE, TEMP_DIR_NAME};
+use crate::errors::{AppError, Result};
+use crate::ffmpeg::{locate_ffmpeg, FFmpegError};
+use lofty::picture::MimeType;
+use std::process::{Command, Stdio};
+
+/// Cover bytes ready to embed and the MIME type they are declared as
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddableCover {
+    pub data: Vec<u8>,
+    pub mime_type: MimeType,
+}
+
+/// Converts cover art to JPEG unless it already is one
+pub fn embeddable_cover(data: &[u8]) -> Result<EmbeddableCover> {
+    embeddable_cover_with(data, convert_to_jpeg)
+}
+
+fn embeddable_cover_with(data: &[u8], convert: fn(&[u8]) -> Result<Vec<u8>>) -> Result<EmbeddableCover> {
+    let format = image_format(data);
+    if format == Some(ImageFormat::Jpeg) {
+        return Ok(EmbeddableCover { data: data.to_vec(), mime_type: MimeType::Jpeg });
+    }
+    let error = match convert(data) {
+        Ok(jpeg) if image_format(&jpeg) == Some(ImageFormat::Jpeg) => {
+            log::info!("Converted {} cover art to JPEG", describe(format));
+            return Ok(EmbeddableCover { data: jpeg, mime_type: MimeType::Jpeg });
+        }
+        Ok(_) => AppError::General("FFmpeg did not produce a JPEG".to_string()),
+        Err(e) => e,
+    };
+    if format == Some(ImageFormat::Png) {
+        log::warn!("Embedding the cover as PNG, it could not be converted to JPEG: {error}");
+        return Ok(EmbeddableCover { data: data.to_vec(), mime_type: MimeType::Png });
+    }
+    Err(AppError::InvalidInput(format!("Cannot convert {} cover art to JPEG: {error}", describe(format))))
+}
+
+/// Keeps cover art within `limit` bytes; None when it already fits
+///
+/// With `strict` an oversized cover is refused instead of reduced.
+pub fn fit_cover_to_limit(data: &[u8], limit: u64, strict: bool) -> Result<Option<Vec<u8>>> {
+    fit_cover_with(data, limit, strict, transcode_to_jpeg)
+}
+
+fn fit_cover_with(
+    data: &[u8],
+    limit: u64,
+    strict: bool,
+    transcode: fn(&[u8], Option<u32>) -> Result<Vec<u8>>,
+) -> Result<Option<Vec<u8>>> {
+    let size = data.len() as u64;
+    if size <= limit {
+        return Ok(None);
+    }
+    if strict {
+        return Err(AppError::InvalidInput(format!(
+            "Cover art is {}, over the {} limit for embedded artwork",
+            megabytes(size), megabytes(limit)
+        )));
+    }
+    for side in COVER_DOWNSCALE_SIDES {
+        let reduced = transcode(data, Some(side))?;
+        if reduced.len() as u64 <= limit {
+            log::info!("Cover art fits at {side}px: {} -> {}", megabytes(size), megabytes(reduced.len() as u64));
+            return Ok(Some(reduced));
+        }
+    }
+    Err(AppError::InvalidInput(format!(
+        "Cover art is {} and can't be reduced under the {} limit for embedded artwork",
+        megabytes(size), megabytes(limit)
+    )))
+}
+
+/// Byte count in MB with one decimal, for messages
+pub fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+fn describe(format: Option<ImageFormat>) -> &'static str {
+    match format {
+        Some(ImageFormat::Jpeg) => "JPEG",
+        Some(ImageFormat::Png) => "PNG",
+        Some(ImageFormat::Webp) => "WebP",
+        None => "unrecognized",
+    }
+}
+
+fn convert_to_jpeg(data: &[u8]) -> Result<Vec<u8>> {
+    transcode_to_jpeg(data, None)
+}
+
+/// Decodes an image with FFmpeg and encodes its first frame as JPEG,
+/// scaled down to fit `max_side` when given
+fn transcode_to_jpeg(data: &[u8], max_side: Option<u32>) -> Result<Vec<u8>> {
+    let dir = std::env::temp_dir().join(TEMP_DIR_NAME);
+    std::fs::create_dir_all(&dir)?;
+    let id = uuid::Uuid::new_v4();
+    let (input, output) = (dir.join(format!("cover-{id}.img")), dir.join(format!("cover-{id}.jpg")));
+    std::fs::write(&input, data)?;
+    let mut command = Command::new(locate_ffmpeg()?);
+    command.args(["-v", "error", "-nostdin", "-i"]).arg(&input);
+    if let Some(side) = max_side {
+        command.args(["-vf", &format!("scale='min(iw,{side})':'min(ih,{side})':force_original_aspect_ratio=decrease")]);
+    }
+    let result = command
+        .args(["-frames:v", "1", "-c:v", "mjpeg", "-q:v", COVER_JPEG_QSCALE, "-f", "image2", "-y"])
+        .arg(&output)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| FFmpegError::ExecutionFailed(e.to_string()));
+    let converted = match result {
+        Ok(result) if result.status.success() => std::fs::read(&output).map_err(AppError::from),
+        Ok(result) => Err(FFmpegError::ExecutionFailed(String::from_utf8_lossy(&result.stderr).trim().to_string()).into()),
+        Err(e) => Err(e.into()),
+    };
+    let _ = std::fs::remove_file(&input);
+    let _ = std::fs::remove_file(&output);
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_HEADER: [u8; 12] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13];
+    const WEBP_HEADER: [u8; 12] = [b'R', b'I', b'F', b'F', 4, 0, 0, 0, b'W', b'E', b'B', b'P'];
+
+    fn failing(_: &[u8]) -> Result<Vec<u8>> {
+        Err(AppError::General("no FFmpeg".to_string()))
+    }
+
+    fn converting(_: &[u8]) -> Result<Vec<u8>> {
+        Ok(vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00])
+    }
+
+    /// Stands in for FFmpeg: a JPEG of one byte per pixel of the longest side
+    fn scaling(_: &[u8], max_side: Option<u32>) -> Result<Vec<u8>> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.resize(max_side.unwrap_or(4000) as usize, 0);
+        Ok(jpeg)
+    }
+
+    #[test]
+    fn test_cover_under_limit_passes_through() {
+        let cover = vec![0xFF; 1000];
+        assert_eq!(fit_cover_with(&cover, 1000, false, scaling).unwrap(), None);
+        assert_eq!(fit_cover_with(&cover, 1000, true, scaling).unwrap(), None);
+    }
+
+    #[test]
+    fn test_oversized_cover_is_reduced_until_it_fits() {
+        let cover = vec![0x89; 5000];
+        let reduced = fit_cover_with(&cover, 1500, false, scaling).unwrap().unwrap();
+        // 3000 and 2000 are still too large
+        assert_eq!(reduced.len(), 1400);
+        assert_eq!(image_format(&reduced), Some(ImageFormat::Jpeg));
+        let too_small = fit_cover_with(&cover, 100, false, scaling).unwrap_err();
+        assert!(too_small.to_string().contains("can't be reduced"));
+    }
+
+    #[test]
+    fn test_strict_limit_rejects_oversized_cover() {
+        let cover = vec![0x89; 3 * 1024 * 1024];
+        let error = fit_cover_with(&cover, 2 * 1024 * 1024, true, scaling).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid input: Cover art is 3.0 MB, over the 2.0 MB limit for embedded artwork");
+    }
+
+    #[test]
+    fn test_jpeg_is_embedded_unchanged() {
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0x01];
+        let cover = embeddable_cover_with(&jpeg, failing).unwrap();
+        assert_eq!(cover, EmbeddableCover { data: jpeg.to_vec(), mime_type: MimeType::Jpeg });
+    }
+
+    #[test]
+    fn test_png_and_webp_are_converted() {
+        for image in [&PNG_HEADER, &WEBP_HEADER] {
+            let cover = embeddable_cover_with(image, converting).unwrap();
+            assert_eq!(cover.mime_type, MimeType::Jpeg);
+            assert_eq!(image_format(&cover.data), Some(ImageFormat::Jpeg));
+        }
+    }
+
+    #[test]
+    fn test_unconvertible_covers() {
+        // PNG is still a valid MP4 cover, declared as what it is
+        let png = embeddable_cover_with(&PNG_HEADER, failing).unwrap();
+        assert_eq!(png, EmbeddableCover { data: PNG_HEADER.to_vec(), mime_type: MimeType::Png });
+        let webp = embeddable_cover_with(&WEBP_HEADER, failing).unwrap_err();
+        assert!(webp.to_string().contains("WebP"));
+        assert!(embeddable_cover_with(b"not an image", failing).is_err());
+    }
+}
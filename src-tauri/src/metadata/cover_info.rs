@@ -0,0 +1,196 @@
+//! Cover image header decoding
+//!
+//! Reads format and pixel dimensions straight from JPEG, PNG and WebP
+//! headers, so cover checks don't need a full image decoder.
+
+use serde::{Deserialize, Serialize};
+
+/// Image container formats accepted as cover art
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+/// Format and size read from an image header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageInfo {
+    /// Total pixel count, used to compare candidate covers
+    pub fn area(&self) -> u64 {
+        u64::from(self.width) * u64::from(self.height)
+    }
+}
+
+/// Format of image bytes from their signature alone, or None if unrecognized
+pub fn image_format(data: &[u8]) -> Option<ImageFormat> {
+    if data.starts_with(&[0xFF, 0xD8]) {
+        Some(ImageFormat::Jpeg)
+    } else if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some(ImageFormat::Png)
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some(ImageFormat::Webp)
+    } else {
+        None
+    }
+}
+
+/// Reads format and dimensions from image bytes, or None if unrecognized
+pub fn read_image_info(data: &[u8]) -> Option<ImageInfo> {
+    match image_format(data)? {
+        ImageFormat::Jpeg => read_jpeg(data),
+        ImageFormat::Png => read_png(data),
+        ImageFormat::Webp => read_webp(data),
+    }
+}
+
+fn be_u16(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 2)?;
+    Some(u32::from(u16::from_be_bytes([bytes[0], bytes[1]])))
+}
+
+fn le_u16(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 2)?;
+    Some(u32::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+}
+
+fn le_u24(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+/// Walks JPEG markers until the first start-of-frame segment
+fn read_jpeg(data: &[u8]) -> Option<ImageInfo> {
+    let mut pos = 2;
+    loop {
+        // Skip fill bytes before the marker code
+        while data.get(pos)? == &0xFF && data.get(pos + 1)? == &0xFF {
+            pos += 1;
+        }
+        if data.get(pos)? != &0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        pos += 2;
+        if marker == 0x01 || (0xD0..=0xD9).contains(&marker) {
+            continue;
+        }
+        let length = be_u16(data, pos)? as usize;
+        // SOF0-SOF15, excluding DHT (C4), JPG (C8) and DAC (CC)
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let height = be_u16(data, pos + 3)?;
+            let width = be_u16(data, pos + 5)?;
+            return Some(ImageInfo { format: ImageFormat::Jpeg, width, height });
+        }
+        pos += length;
+    }
+}
+
+/// Reads the IHDR chunk that always follows the PNG signature
+fn read_png(data: &[u8]) -> Option<ImageInfo> {
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+    Some(ImageInfo { format: ImageFormat::Png, width, height })
+}
+
+/// Reads the first WebP chunk (lossy, lossless or extended)
+fn read_webp(data: &[u8]) -> Option<ImageInfo> {
+    let (width, height) = match data.get(12..16)? {
+        b"VP8 " => {
+            if data.get(23..26)? != [0x9D, 0x01, 0x2A] {
+                return None;
+            }
+            (le_u16(data, 26)? & 0x3FFF, le_u16(data, 28)? & 0x3FFF)
+        }
+        b"VP8L" => {
+            if data.get(20)? != &0x2F {
+                return None;
+            }
+            let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+            (1 + (bits & 0x3FFF), 1 + ((bits >> 14) & 0x3FFF))
+        }
+        b"VP8X" => (1 + le_u24(data, 24)?, 1 + le_u24(data, 27)?),
+        _ => return None,
+    };
+    Some(ImageInfo { format: ImageFormat::Webp, width, height })
+}
+
+/// Minimal image headers for tests elsewhere in the crate
+#[cfg(test)]
+pub mod fixtures {
+    /// JPEG with an APP0 segment followed by a baseline SOF0
+    pub fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10];
+        data.extend_from_slice(b"JFIF\0\x01\x01\x00\x00\x01\x00\x01\x00\x00");
+        data.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x11, 0x08]);
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&[0x03, 0x01, 0x22, 0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01]);
+        data.extend_from_slice(&[0xFF, 0xD9]);
+        data
+    }
+
+    /// PNG signature plus IHDR chunk
+    pub fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D];
+        data.extend_from_slice(b"IHDR");
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[0x08, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        data
+    }
+
+    /// Extended-format WebP header (VP8X)
+    pub fn webp(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"RIFF\x00\x00\x00\x00WEBPVP8X\x0A\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        data.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        data.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jpeg_dimensions() {
+        let info = read_image_info(&fixtures::jpeg(1400, 1200)).unwrap();
+        assert_eq!(info, ImageInfo { format: ImageFormat::Jpeg, width: 1400, height: 1200 });
+    }
+
+    #[test]
+    fn test_png_dimensions() {
+        let info = read_image_info(&fixtures::png(500, 600)).unwrap();
+        assert_eq!(info, ImageInfo { format: ImageFormat::Png, width: 500, height: 600 });
+    }
+
+    #[test]
+    fn test_webp_dimensions() {
+        let info = read_image_info(&fixtures::webp(3000, 2000)).unwrap();
+        assert_eq!(info, ImageInfo { format: ImageFormat::Webp, width: 3000, height: 2000 });
+
+        let mut lossless = b"RIFF\x00\x00\x00\x00WEBPVP8L\x00\x00\x00\x00\x2F".to_vec();
+        let bits: u32 = (640 - 1) | ((480 - 1) << 14);
+        lossless.extend_from_slice(&bits.to_le_bytes());
+        let info = read_image_info(&lossless).unwrap();
+        assert_eq!((info.width, info.height), (640, 480));
+    }
+
+    #[test]
+    fn test_truncated_or_unknown_data() {
+        assert!(read_image_info(&[0xFF, 0xD8, 0xFF]).is_none());
+        assert!(read_image_info(b"GIF89a").is_none());
+        assert!(read_image_info(&fixtures::png(10, 10)[..20]).is_none());
+    }
+}
@@ -0,0 +1,105 @@
+//! Cover art in the form the frontend displays it
+//!
+//! `AudiobookMetadata.cover_art` is raw bytes, which cross IPC as a JSON
+//! array of numbers. `CoverArt` carries the same bytes as base64 with the
+//! MIME type, so `data:{mime};base64,{dataBase64}` is ready for an `<img>`,
+//! along with the dimensions read from the image header.
+
+use super::cover_info::{image_format, read_image_info, ImageFormat};
+use super::reader::read_metadata;
+use crate::errors::{AppError, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Cover image as base64 with its MIME type and size
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverArt {
+    /// MIME type from the image signature; `application/octet-stream` if unrecognized
+    pub mime: String,
+    /// Image bytes, standard padded base64
+    pub data_base64: String,
+    /// Width in pixels, when the header could be read
+    pub width: Option<u32>,
+    /// Height in pixels, when the header could be read
+    pub height: Option<u32>,
+}
+
+impl CoverArt {
+    /// Describes cover bytes for display
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let info = read_image_info(data);
+        let mime = match image_format(data) {
+            Some(ImageFormat::Jpeg) => "image/jpeg",
+            Some(ImageFormat::Png) => "image/png",
+            Some(ImageFormat::Webp) => "image/webp",
+            None => "application/octet-stream",
+        };
+        Self {
+            mime: mime.to_string(),
+            data_base64: STANDARD.encode(data),
+            width: info.as_ref().map(|info| info.width),
+            height: info.as_ref().map(|info| info.height),
+        }
+    }
+
+    /// The original image bytes
+    ///
+    /// # Errors
+    /// `InvalidInput` when `data_base64` isn't valid padded base64
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        STANDARD.decode(&self.data_base64)
+            .map_err(|e| AppError::InvalidInput(format!("Invalid base64 cover data: {e}")))
+    }
+}
+
+/// Reads a file's cover art for display; None when it has none
+pub fn read_cover_art<P: AsRef<Path>>(file_path: P) -> Result<Option<CoverArt>> {
+    let metadata = read_metadata(file_path)?;
+    Ok(metadata.cover_art.as_deref().map(CoverArt::from_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::cover_info::fixtures;
+
+    #[test]
+    fn test_cover_round_trips_through_base64() {
+        let jpeg = fixtures::jpeg(600, 400);
+        let cover = CoverArt::from_bytes(&jpeg);
+        assert_eq!(cover.mime, "image/jpeg");
+        assert_eq!((cover.width, cover.height), (Some(600), Some(400)));
+        assert_eq!(cover.bytes().unwrap(), jpeg);
+
+        // Through JSON as the frontend receives it
+        let json = serde_json::to_value(&cover).unwrap();
+        assert!(json["dataBase64"].is_string());
+        let received: CoverArt = serde_json::from_value(json).unwrap();
+        assert_eq!(received.bytes().unwrap(), jpeg);
+    }
+
+    #[test]
+    fn test_unrecognized_bytes_still_round_trip() {
+        let data = b"not an image".to_vec();
+        let cover = CoverArt::from_bytes(&data);
+        assert_eq!(cover.mime, "application/octet-stream");
+        assert_eq!((cover.width, cover.height), (None, None));
+        assert_eq!(cover.bytes().unwrap(), data);
+    }
+
+    #[test]
+    fn test_malformed_base64_is_rejected() {
+        let mut cover = CoverArt::from_bytes(b"fo");
+        assert_eq!(cover.data_base64, "Zm8=");
+        cover.data_base64 = "Zm8".to_string();
+        assert!(matches!(cover.bytes(), Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_read_cover_art_of_missing_file() {
+        assert!(read_cover_art("nonexistent.m4b").is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Cover art quality checks
+//!
+//! Players render tiny or stretched covers badly. The assessment flags
+//! covers that are too small, noticeably non-square or very large, so the
+//! UI can warn before embedding and processing can refuse them when the
+//! `enforce_cover_quality` setting is on.
+
+use super::cover_info::{read_image_info, ImageFormat};
+use crate::audio::constants::{COVER_MAX_ASPECT_DEVIATION, COVER_MAX_BYTES, COVER_MIN_DIMENSION};
+use crate::errors::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Problems found with a cover image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverWarning {
+    /// Shorter side is below the minimum dimension
+    TooSmall,
+    /// Aspect ratio deviates from square beyond the tolerance
+    NotSquare,
+    /// Encoded image exceeds the size limit
+    TooLarge,
+}
+
+impl fmt::Display for CoverWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoverWarning::TooSmall => write!(f, "smaller than {COVER_MIN_DIMENSION}px"),
+            CoverWarning::NotSquare => write!(f, "not square"),
+            CoverWarning::TooLarge => write!(f, "larger than {} MB", COVER_MAX_BYTES / (1024 * 1024)),
+        }
+    }
+}
+
+/// Dimensions, size and warnings for a cover image
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverAssessment {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Width divided by height
+    pub aspect_ratio: f64,
+    pub byte_size: usize,
+    pub warnings: Vec<CoverWarning>,
+}
+
+/// Reads a cover's header and checks it against the quality limits
+pub fn assess_cover_art(data: &[u8]) -> Result<CoverAssessment> {
+    let info = read_image_info(data).ok_or_else(|| AppError::InvalidInput(
+        "Unrecognized cover image format (expected JPEG, PNG or WebP)".to_string()
+    ))?;
+    if info.width == 0 || info.height == 0 {
+        return Err(AppError::InvalidInput("Cover image has zero dimensions".to_string()));
+    }
+
+    let mut warnings = Vec::new();
+    if info.width.min(info.height) < COVER_MIN_DIMENSION {
+        warnings.push(CoverWarning::TooSmall);
+    }
+    let (long, short) = (info.width.max(info.height), info.width.min(info.height));
+    if f64::from(long) / f64::from(short) - 1.0 > COVER_MAX_ASPECT_DEVIATION {
+        warnings.push(CoverWarning::NotSquare);
+    }
+    if data.len() > COVER_MAX_BYTES {
+        warnings.push(CoverWarning::TooLarge);
+    }
+
+    Ok(CoverAssessment {
+        format: info.format,
+        width: info.width,
+        height: info.height,
+        aspect_ratio: f64::from(info.width) / f64::from(info.height),
+        byte_size: data.len(),
+        warnings,
+    })
+}
+
+/// Fails if the cover has any quality warning
+pub fn enforce_cover_quality(data: &[u8]) -> Result<()> {
+    let assessment = assess_cover_art(data)?;
+    if assessment.warnings.is_empty() {
+        return Ok(());
+    }
+    let problems: Vec<String> = assessment.warnings.iter().map(ToString::to_string).collect();
+    Err(AppError::InvalidInput(format!(
+        "Cover art rejected ({}x{}): {}",
+        assessment.width, assessment.height, problems.join(", ")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::cover_info::fixtures;
+
+    #[test]
+    fn test_good_cover_has_no_warnings() {
+        let assessment = assess_cover_art(&fixtures::jpeg(1400, 1400)).unwrap();
+        assert_eq!(assessment.format, ImageFormat::Jpeg);
+        assert_eq!(assessment.aspect_ratio, 1.0);
+        assert!(assessment.warnings.is_empty());
+        assert!(enforce_cover_quality(&fixtures::jpeg(1400, 1400)).is_ok());
+    }
+
+    #[test]
+    fn test_too_small() {
+        let assessment = assess_cover_art(&fixtures::png(200, 200)).unwrap();
+        assert_eq!(assessment.warnings, vec![CoverWarning::TooSmall]);
+        assert!(assess_cover_art(&fixtures::png(500, 500)).unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn test_non_square_beyond_tolerance() {
+        let assessment = assess_cover_art(&fixtures::jpeg(1000, 1500)).unwrap();
+        assert_eq!(assessment.warnings, vec![CoverWarning::NotSquare]);
+        // 4% off square is tolerated
+        assert!(assess_cover_art(&fixtures::jpeg(1040, 1000)).unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn test_too_large() {
+        let mut data = fixtures::webp(1000, 1000);
+        data.resize(COVER_MAX_BYTES + 1, 0);
+        let assessment = assess_cover_art(&data).unwrap();
+        assert_eq!(assessment.warnings, vec![CoverWarning::TooLarge]);
+        assert_eq!(assessment.byte_size, COVER_MAX_BYTES + 1);
+    }
+
+    #[test]
+    fn test_enforcement_and_unrecognized_data() {
+        let err = enforce_cover_quality(&fixtures::jpeg(300, 600)).unwrap_err();
+        assert!(err.to_string().contains("not square"));
+        assert!(assess_cover_art(b"GIF89a").is_err());
+    }
+}
@@ -0,0 +1,205 @@
+//! CUE sheet import
+//!
+//! Reads the tracks of a CUE sheet as an explicit chapter list, so a single
+//! large file keeps the breakpoints that came with it. Tracks of the first
+//! FILE become chapters at their `INDEX 01` time; a later FILE (one input
+//! per file) may hold only a single track, which then spans that input.
+//! Values are quoted from the first to the last quote on the line, so titles
+//! with embedded quotes survive, and `\"` is read as a quote.
+
+use crate::audio::chapters::ChapterSpec;
+use crate::audio::constants::CUE_FRAMES_PER_SECOND;
+use crate::errors::{AppError, Result};
+use std::path::Path;
+
+/// A TRACK being read, with the line it started on
+#[derive(Debug)]
+struct CueTrack {
+    line: usize,
+    number: u32,
+    file_index: usize,
+    title: Option<String>,
+    start_secs: Option<f64>,
+}
+
+/// Reads the chapters of the CUE sheet at `path`
+pub fn parse_cue_sheet<P: AsRef<Path>>(path: P) -> Result<Vec<ChapterSpec>> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)?;
+    let text = String::from_utf8_lossy(&bytes);
+    parse_cue(text.trim_start_matches('\u{feff}'))
+}
+
+/// Parses CUE sheet text into chapters
+pub fn parse_cue(text: &str) -> Result<Vec<ChapterSpec>> {
+    let mut file_count = 0;
+    let mut tracks: Vec<CueTrack> = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let content = raw.trim();
+        let (keyword, rest) = content.split_once(char::is_whitespace).unwrap_or((content, ""));
+        let rest = rest.trim();
+        match keyword.to_ascii_uppercase().as_str() {
+            "FILE" => {
+                let (name, file_type) = split_file(rest)
+                    .ok_or_else(|| malformed(line, "FILE needs a quoted filename and a type, e.g. FILE \"book.mp3\" MP3"))?;
+                log::debug!("CUE file {name} ({file_type})");
+                file_count += 1;
+            }
+            "TRACK" => {
+                if file_count == 0 {
+                    return Err(malformed(line, "TRACK comes before any FILE"));
+                }
+                let number = rest.split_whitespace().next()
+                    .and_then(|number| number.parse().ok())
+                    .ok_or_else(|| malformed(line, "TRACK needs a number, e.g. TRACK 01 AUDIO"))?;
+                tracks.push(CueTrack { line, number, file_index: file_count - 1, title: None, start_secs: None });
+            }
+            "TITLE" => {
+                let title = unquote(rest).ok_or_else(|| malformed(line, "TITLE needs a value"))?;
+                if let Some(track) = tracks.last_mut() {
+                    track.title = Some(title);
+                }
+            }
+            "INDEX" => {
+                let track = tracks.last_mut().ok_or_else(|| malformed(line, "INDEX comes before any TRACK"))?;
+                let mut fields = rest.split_whitespace();
+                let (Some(number), Some(time), None) = (fields.next(), fields.next(), fields.next()) else {
+                    return Err(malformed(line, "INDEX needs a number and a time, e.g. INDEX 01 00:00:00"));
+                };
+                let number: u32 = number.parse().map_err(|_| malformed(line, &format!("INDEX number '{number}' is not a number")))?;
+                let secs = parse_time(time)
+                    .ok_or_else(|| malformed(line, &format!("INDEX time '{time}' is not MM:SS:FF")))?;
+                if number == 1 {
+                    track.start_secs = Some(secs);
+                }
+            }
+            _ => {}
+        }
+    }
+    tracks_to_chapters(tracks)
+}
+
+fn tracks_to_chapters(tracks: Vec<CueTrack>) -> Result<Vec<ChapterSpec>> {
+    if tracks.is_empty() {
+        return Err(AppError::InvalidInput("CUE sheet has no tracks".to_string()));
+    }
+    let tracks_in_file = |file_index: usize| tracks.iter().filter(|track| track.file_index == file_index).count();
+    tracks.iter()
+        .map(|track| {
+            let start_secs = track.start_secs
+                .ok_or_else(|| malformed(track.line, &format!("TRACK {:02} has no INDEX 01", track.number)))?;
+            let title = track.title.clone().unwrap_or_else(|| format!("Track {:02}", track.number));
+            if track.file_index == 0 {
+                return Ok(ChapterSpec { title, start_secs: Some(start_secs), input_indices: None });
+            }
+            if tracks_in_file(track.file_index) > 1 || start_secs > 0.0 {
+                return Err(malformed(
+                    track.line,
+                    &format!("TRACK {:02} shares a later FILE with other tracks or starts inside it; only one track per additional file is supported", track.number),
+                ));
+            }
+            Ok(ChapterSpec { title, start_secs: None, input_indices: Some(vec![track.file_index]) })
+        })
+        .collect()
+}
+
+fn malformed(line: usize, reason: &str) -> AppError {
+    AppError::InvalidInput(format!("Malformed CUE sheet at line {line}: {reason}"))
+}
+
+/// Filename and type of a FILE line's arguments
+fn split_file(rest: &str) -> Option<(String, &str)> {
+    let (name, file_type) = match rest.rfind('"') {
+        Some(end) if rest.starts_with('"') && end > 0 => (unquote(&rest[..=end])?, rest[end + 1..].trim()),
+        _ => {
+            let (name, file_type) = rest.rsplit_once(char::is_whitespace)?;
+            (name.trim().to_string(), file_type.trim())
+        }
+    };
+    (!name.is_empty() && !file_type.is_empty()).then_some((name, file_type))
+}
+
+/// Value between the first and last quote, unescaped; unquoted values are taken as they are
+fn unquote(value: &str) -> Option<String> {
+    let value = value.trim();
+    let text = match (value.find('"'), value.rfind('"')) {
+        (Some(start), Some(end)) if end > start => value[start + 1..end].replace("\\\"", "\"").replace("\\\\", "\\"),
+        (Some(_), _) => return None,
+        _ => value.to_string(),
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// Seconds for an `MM:SS:FF` time
+fn parse_time(time: &str) -> Option<f64> {
+    let mut parts = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (Some(Some(minutes)), Some(Some(seconds)), Some(Some(frames)), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return None;
+    };
+    if seconds >= 60 || frames >= CUE_FRAMES_PER_SECOND {
+        return None;
+    }
+    Some((minutes * 60 + seconds) as f64 + frames as f64 / CUE_FRAMES_PER_SECOND as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::cue_sheet::cue_sheet;
+    use crate::audio::chapters::Chapter;
+
+    fn starts(chapters: &[ChapterSpec]) -> Vec<Option<f64>> {
+        chapters.iter().map(|chapter| chapter.start_secs).collect()
+    }
+
+    #[test]
+    fn test_parse_single_file_with_quotes() {
+        let text = "\u{feff}REM GENRE Audiobook\n\
+                    PERFORMER \"Frank Herbert\"\n\
+                    TITLE \"Dune\"\n\
+                    FILE \"Dune \"Unabridged\".wav\" WAVE\n  \
+                      TRACK 01 AUDIO\n    TITLE \"Opening\"\n    INDEX 00 00:00:00\n    INDEX 01 00:00:00\n  \
+                      track 02 audio\n    TITLE \"The \"Long\" Night\"\n    INDEX 01 01:01:38\n  \
+                      TRACK 03 AUDIO\n    INDEX 01 62:05:03\n";
+        let chapters = parse_cue(text.trim_start_matches('\u{feff}')).unwrap();
+        let titles: Vec<&str> = chapters.iter().map(|chapter| chapter.title.as_str()).collect();
+        assert_eq!(titles, ["Opening", "The \"Long\" Night", "Track 03"]);
+        assert_eq!(chapters[1].start_secs, Some(61.0 + 38.0 / 75.0));
+        assert_eq!(chapters[2].start_secs, Some(3725.0 + 3.0 / 75.0));
+        assert!(chapters.iter().all(|chapter| chapter.input_indices.is_none()));
+    }
+
+    #[test]
+    fn test_round_trips_exported_sheet_and_mp3_files() {
+        let exported = [
+            Chapter { title: "One \"1\"".to_string(), start_secs: 0.0, end_secs: 30.0 },
+            Chapter { title: "Two".to_string(), start_secs: 30.0, end_secs: 60.0 },
+        ];
+        let chapters = parse_cue(&cue_sheet(&exported, None, "Book.m4b")).unwrap();
+        assert_eq!(chapters[0].title, "One \"1\"");
+        assert_eq!(starts(&chapters), [Some(0.0), Some(30.0)]);
+
+        let text = "FILE \"01.mp3\" MP3\nTRACK 01 AUDIO\nTITLE Intro\nINDEX 01 00:00:00\n\
+                    FILE 02.mp3 MP3\nTRACK 02 AUDIO\nTITLE \"Part One\"\nINDEX 01 00:00:00\n";
+        let chapters = parse_cue(text).unwrap();
+        assert_eq!(chapters[0].title, "Intro");
+        assert_eq!(chapters[1].input_indices, Some(vec![1]));
+        assert_eq!(chapters[1].start_secs, None);
+    }
+
+    #[test]
+    fn test_malformed_lines_name_the_line() {
+        let error = |text: &str| parse_cue(text).unwrap_err().to_string();
+        assert!(error("TRACK 01 AUDIO\n").contains("line 1: TRACK comes before any FILE"));
+        assert!(error("FILE \"a.wav\"\n").contains("line 1: FILE needs"));
+        assert!(error("FILE \"a.wav\" WAVE\nTRACK xx AUDIO\n").contains("line 2: TRACK needs a number"));
+        assert!(error("FILE \"a.wav\" WAVE\nTRACK 01 AUDIO\nINDEX 01 00:75:00\n").contains("line 3: INDEX time '00:75:00'"));
+        assert!(error("FILE \"a.wav\" WAVE\nTRACK 01 AUDIO\nINDEX 01\n").contains("line 3: INDEX needs"));
+        assert!(error("FILE \"a.wav\" WAVE\nTRACK 01 AUDIO\nTITLE \"x\n").contains("line 3: TITLE needs"));
+        assert!(error("FILE \"a.wav\" WAVE\n\nTRACK 01 AUDIO\nTITLE x\n").contains("line 3: TRACK 01 has no INDEX 01"));
+        assert!(error("FILE \"a.wav\" WAVE\nFILE \"b.wav\" WAVE\nTRACK 01 AUDIO\nINDEX 01 00:00:00\nTRACK 02 AUDIO\nINDEX 01 00:10:00\n")
+            .contains("line 3: TRACK 01 shares a later FILE"));
+        assert_eq!(error("REM nothing\n"), "Invalid input: CUE sheet has no tracks");
+    }
+}
@@ -0,0 +1,411 @@
+//! Book description import from text and Markdown files, and the chapter
+//! list appended to it
+//!
+//! Markdown is reduced to plain text (headings, emphasis, links, list
+//! markers and code fences go, their text stays) because tag fields and
+//! players show descriptions verbatim. For players without chapter
+//! navigation the chapters can be listed at the end of the description,
+//! one `12:34 Title` line each.
+
+use super::text::normalize_whitespace;
+use super::ChapterInfo;
+use crate::audio::chapters::ChapterTimestampFormat;
+use crate::audio::constants::DESCRIPTION_FILE_MAX_BYTES;
+use crate::errors::{AppError, Result};
+use std::path::Path;
+
+/// Reads a `.txt` or `.md` description file and returns it as clean plain text
+pub fn load_description_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let extension = path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default();
+    let is_markdown = match extension.as_str() {
+        "md" | "markdown" => true,
+        "txt" => false,
+        _ => return Err(AppError::InvalidInput(
+            format!("Description must be a .txt or .md file: {}", path.display())
+        )),
+    };
+    if !path.is_file() {
+        return Err(AppError::FileValidation(format!("Description file not found: {}", path.display())));
+    }
+    let size = std::fs::metadata(path)?.len();
+    if size > DESCRIPTION_FILE_MAX_BYTES {
+        return Err(AppError::InvalidInput(format!(
+            "Description file is too large ({size} bytes, limit {DESCRIPTION_FILE_MAX_BYTES})"
+        )));
+    }
+
+    let text = String::from_utf8(std::fs::read(path)?).map_err(|_| AppError::FileValidation(
+        format!("Description file is not UTF-8 text: {}", path.display())
+    ))?;
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(&text);
+    Ok(if is_markdown {
+        normalize_whitespace(&strip_markdown(text))
+    } else {
+        normalize_whitespace(text)
+    })
+}
+
+/// Converts Markdown to plain text, keeping line structure
+pub fn strip_markdown(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            lines.push(line.to_string());
+        } else if is_rule(trimmed) {
+            lines.push(String::new());
+        } else if !is_reference_definition(trimmed) {
+            lines.push(strip_inline(strip_block_prefix(trimmed)));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Thematic breaks and setext heading underlines
+fn is_rule(line: &str) -> bool {
+    let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.len() >= 3
+        && ['-', '*', '_', '='].iter().any(|&marker| compact.chars().all(|c| c == marker))
+}
+
+/// `[label]: https://...` lines that only define link targets
+fn is_reference_definition(line: &str) -> bool {
+    line.starts_with('[') && line.find("]:").is_some_and(|end| !line[1..end].contains('['))
+}
+
+/// Removes quote, heading and bullet markers from the start of a line
+fn strip_block_prefix(mut line: &str) -> &str {
+    while let Some(rest) = line.strip_prefix('>') {
+        line = rest.trim_start();
+    }
+    let hashes = line.chars().take_while(|&c| c == '#').count();
+    if (1..=6).contains(&hashes) && line[hashes..].starts_with([' ', '\t']) {
+        return line[hashes..].trim().trim_end_matches('#').trim_end();
+    }
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            let rest = rest.trim_start();
+            return ["[ ] ", "[x] ", "[X] "].iter()
+                .find_map(|task| rest.strip_prefix(task))
+                .unwrap_or(rest);
+        }
+    }
+    line
+}
+
+/// Removes inline code, link and emphasis syntax
+fn strip_inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if i + 1 < chars.len() && chars[i + 1].is_ascii_punctuation() => {
+                out.push(chars[i + 1]);
+                i += 2;
+            }
+            '`' => match find_from(&chars, i + 1, '`') {
+                Some(end) => {
+                    out.extend(&chars[i + 1..end]);
+                    i = end + 1;
+                }
+                None => {
+                    out.push(c);
+                    i += 1;
+                }
+            },
+            '!' if chars.get(i + 1) == Some(&'[') => match parse_link(&chars, i + 1) {
+                Some((label, next)) => {
+                    out.push_str(&strip_inline(&label));
+                    i = next;
+                }
+                None => {
+                    out.push(c);
+                    i += 1;
+                }
+            },
+            '[' => match parse_link(&chars, i) {
+                Some((label, next)) => {
+                    out.push_str(&strip_inline(&label));
+                    i = next;
+                }
+                None => {
+                    out.push(c);
+                    i += 1;
+                }
+            },
+            '<' => match find_from(&chars, i + 1, '>') {
+                Some(end) if is_autolink(&chars[i + 1..end]) => {
+                    out.extend(&chars[i + 1..end]);
+                    i = end + 1;
+                }
+                _ => {
+                    out.push(c);
+                    i += 1;
+                }
+            },
+            '*' | '_' | '~' => {
+                let run = chars[i..].iter().take_while(|&&x| x == c).count();
+                let before = i.checked_sub(1).map(|p| chars[p]);
+                let after = chars.get(i + run).copied();
+                if !is_emphasis_delimiter(c, run, before, after) {
+                    out.extend(&chars[i..i + run]);
+                }
+                i += run;
+            }
+            _ => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn find_from(chars: &[char], start: usize, target: char) -> Option<usize> {
+    chars.get(start..)?.iter().position(|&c| c == target).map(|pos| start + pos)
+}
+
+/// Parses `[label](target)` or `[label][ref]` starting at `[`, returning
+/// the label and the index after the link
+fn parse_link(chars: &[char], open: usize) -> Option<(String, usize)> {
+    let mut depth = 0;
+    let mut close = None;
+    for (offset, &c) in chars[open..].iter().enumerate() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(open + offset);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let close = close?;
+    let label: String = chars[open + 1..close].iter().collect();
+    let closing = match chars.get(close + 1) {
+        Some('(') => ')',
+        Some('[') => ']',
+        _ => return None,
+    };
+    let end = find_from(chars, close + 2, closing)?;
+    Some((label, end + 1))
+}
+
+fn is_autolink(inner: &[char]) -> bool {
+    let inner: String = inner.iter().collect();
+    !inner.contains(char::is_whitespace)
+        && ["http://", "https://", "mailto:"].iter().any(|scheme| inner.starts_with(scheme))
+}
+
+/// Whether a run of `*`, `_` or `~` marks emphasis rather than literal text
+fn is_emphasis_delimiter(marker: char, run: usize, before: Option<char>, after: Option<char>) -> bool {
+    let is_space = |c: Option<char>| c.is_none_or(char::is_whitespace);
+    if is_space(before) && is_space(after) {
+        return false;
+    }
+    match marker {
+        '~' => run >= 2,
+        // snake_case and similar words keep their underscores
+        '_' => !(before.is_some_and(char::is_alphanumeric) && after.is_some_and(char::is_alphanumeric)),
+        _ => run <= 3,
+    }
+}
+
+/// Description with a chapter list appended, at most `max_chars` long
+///
+/// The synopsis is never shortened. When the whole list doesn't fit, the
+/// chapters that don't are dropped from the end and counted in a closing
+/// line; when not even the first one fits, or there are no chapters, the
+/// description is returned as it was.
+pub fn append_chapter_list(
+    description: Option<&str>,
+    chapters: &[ChapterInfo],
+    format: ChapterTimestampFormat,
+    max_chars: usize,
+) -> Option<String> {
+    let synopsis = description.map(str::trim_end).filter(|synopsis| !synopsis.is_empty());
+    let unchanged = || description.map(str::to_string);
+    if chapters.is_empty() {
+        return unchanged();
+    }
+    let with_hours = match format {
+        ChapterTimestampFormat::Auto => chapters.iter().any(|chapter| chapter.start_ms >= 3_600_000),
+        ChapterTimestampFormat::Hours => true,
+        ChapterTimestampFormat::Minutes => false,
+    };
+    let lines: Vec<String> = chapters.iter()
+        .map(|chapter| format!("{} {}", chapter_timestamp(chapter.start_ms, format, with_hours), chapter.title.trim()))
+        .collect();
+
+    let mut text = match synopsis {
+        Some(synopsis) => format!("{synopsis}\n\nChapters:"),
+        None => "Chapters:".to_string(),
+    };
+    let line_lengths: Vec<usize> = lines.iter().map(|line| line.chars().count() + 1).collect();
+    let list_length = |kept: usize| line_lengths[..kept].iter().sum::<usize>() + remainder_line(lines.len() - kept).chars().count();
+    let heading_length = text.chars().count();
+    let Some(kept) = (1..=lines.len()).rev().find(|&kept| heading_length + list_length(kept) <= max_chars) else {
+        return unchanged();
+    };
+    if kept < lines.len() {
+        log::info!("Listing {kept} of {} chapters to keep the description under {max_chars} characters", lines.len());
+    }
+    for line in &lines[..kept] {
+        text.push('\n');
+        text.push_str(line);
+    }
+    text.push_str(&remainder_line(lines.len() - kept));
+    Some(text)
+}
+
+/// Closing line counting the chapters left out of the list, with its line break
+fn remainder_line(omitted: usize) -> String {
+    match omitted {
+        0 => String::new(),
+        1 => "\n…and 1 more chapter".to_string(),
+        omitted => format!("\n…and {omitted} more chapters"),
+    }
+}
+
+/// A chapter start in the list's format, in whole seconds
+fn chapter_timestamp(start_ms: u64, format: ChapterTimestampFormat, with_hours: bool) -> String {
+    let seconds = start_ms / 1000;
+    match format {
+        ChapterTimestampFormat::Hours => format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60),
+        _ if with_hours => format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60),
+        _ => format!("{:02}:{:02}", seconds / 60, seconds % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn chapters(count: usize, length_ms: u64) -> Vec<ChapterInfo> {
+        (0..count as u64)
+            .map(|index| ChapterInfo {
+                title: format!("Chapter {}", index + 1),
+                start_ms: index * length_ms,
+                end_ms: (index + 1) * length_ms,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chapter_list_formats_hour_long_books() {
+        let short = chapters(3, 754_000);
+        let text = append_chapter_list(Some("A desert planet.\n"), &short, ChapterTimestampFormat::Auto, 4000).unwrap();
+        assert_eq!(text, "A desert planet.\n\nChapters:\n00:00 Chapter 1\n12:34 Chapter 2\n25:08 Chapter 3");
+
+        // Past an hour every line gets hours, so the columns line up
+        let long = chapters(6, 754_000);
+        let text = append_chapter_list(None, &long, ChapterTimestampFormat::Auto, 4000).unwrap();
+        assert!(text.starts_with("Chapters:\n0:00:00 Chapter 1\n0:12:34 Chapter 2\n"));
+        assert!(text.ends_with("\n1:02:50 Chapter 6"));
+
+        let text = append_chapter_list(None, &long, ChapterTimestampFormat::Hours, 4000).unwrap();
+        assert!(text.ends_with("\n01:02:50 Chapter 6"));
+        let text = append_chapter_list(None, &long, ChapterTimestampFormat::Minutes, 4000).unwrap();
+        assert!(text.ends_with("\n62:50 Chapter 6"));
+    }
+
+    #[test]
+    fn test_chapter_list_truncated_not_synopsis() {
+        let synopsis = "Paul Atreides comes of age on Arrakis. ".repeat(40);
+        let many = chapters(150, 300_000);
+        let text = append_chapter_list(Some(&synopsis), &many, ChapterTimestampFormat::Auto, 4000).unwrap();
+        assert!(text.chars().count() <= 4000);
+        assert!(text.starts_with(synopsis.trim_end()));
+        let listed = text.lines().filter(|line| line.contains(" Chapter ")).count();
+        assert!(listed > 50 && listed < 150, "listed {listed}");
+        assert!(text.ends_with(&format!("\n…and {} more chapters", 150 - listed)));
+
+        // Everything fits under a larger cap
+        let text = append_chapter_list(Some(&synopsis), &many, ChapterTimestampFormat::Auto, 100_000).unwrap();
+        assert!(text.ends_with("\n12:25:00 Chapter 150"));
+
+        // A synopsis already at the cap is left alone
+        let long_synopsis = "x".repeat(4000);
+        assert_eq!(append_chapter_list(Some(&long_synopsis), &many, ChapterTimestampFormat::Auto, 4000), Some(long_synopsis));
+        assert_eq!(append_chapter_list(None, &[], ChapterTimestampFormat::Auto, 4000), None);
+    }
+
+    #[test]
+    fn test_links_become_text() {
+        assert_eq!(
+            strip_markdown("Read [the first book](https://example.com/a) and ![cover](c.jpg)."),
+            "Read the first book and cover."
+        );
+        assert_eq!(strip_markdown("See [**Dune**][1].\n[1]: https://example.com"), "See Dune.");
+        assert_eq!(strip_markdown("Contact <mailto:me@example.com>"), "Contact mailto:me@example.com");
+    }
+
+    #[test]
+    fn test_headings_and_emphasis() {
+        let markdown = "# Dune ##\n\nA *desert* planet, **Arrakis**, and ~~spice~~ _melange_.\nsnake_case stays, 5 * 3 too";
+        assert_eq!(
+            strip_markdown(markdown),
+            "Dune\n\nA desert planet, Arrakis, and spice melange.\nsnake_case stays, 5 * 3 too"
+        );
+    }
+
+    #[test]
+    fn test_lists_and_quotes() {
+        let markdown = "- Paul\n* Jessica\n+ [x] Leto\n1. Chapter one\n> Fear is the *mind-killer*.";
+        assert_eq!(
+            strip_markdown(markdown),
+            "Paul\nJessica\nLeto\n1. Chapter one\nFear is the mind-killer."
+        );
+    }
+
+    #[test]
+    fn test_code_fences_keep_content() {
+        let markdown = "Intro\n```text\nkeep *this* as is\n```\nUse `--flag` here\n---\nEnd";
+        assert_eq!(strip_markdown(markdown), "Intro\nkeep *this* as is\nUse --flag here\n\nEnd");
+    }
+
+    #[test]
+    fn test_load_markdown_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("synopsis.md");
+        std::fs::write(&file, "\u{FEFF}## Synopsis\r\n\r\n\r\nA  *great*   book.\r\n").unwrap();
+        assert_eq!(load_description_file(&file).unwrap(), "Synopsis\n\nA great book.");
+    }
+
+    #[test]
+    fn test_plain_text_is_not_stripped() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("synopsis.txt");
+        std::fs::write(&file, "# not a heading   in text\n").unwrap();
+        assert_eq!(load_description_file(&file).unwrap(), "# not a heading in text");
+    }
+
+    #[test]
+    fn test_rejects_binary_large_and_other_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let binary = temp_dir.path().join("binary.txt");
+        std::fs::write(&binary, [0xFF, 0xFE, 0x00, 0x80]).unwrap();
+        assert!(matches!(load_description_file(&binary), Err(AppError::FileValidation(_))));
+
+        let large = temp_dir.path().join("large.md");
+        std::fs::write(&large, "a".repeat(DESCRIPTION_FILE_MAX_BYTES as usize + 1)).unwrap();
+        assert!(matches!(load_description_file(&large), Err(AppError::InvalidInput(_))));
+
+        assert!(matches!(load_description_file(temp_dir.path().join("cover.jpg")), Err(AppError::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,142 @@
+//! Best-effort detection of files held open by other applications
+//!
+//! Retagging rewrites the whole file, which fails halfway through when a
+//! player has the book open. Detection is platform specific and never
+//! conclusive, so an unknown answer never blocks a write.
+
+use crate::errors::{AppError, Result};
+use std::path::Path;
+
+/// Checks whether another process has the file open
+///
+/// Returns None when this platform (or this process's permissions) can't tell.
+pub fn file_in_use(path: &Path) -> Option<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        proc_scan(path).or_else(|| lock_probe(path))
+    }
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        lock_probe(path)
+    }
+    #[cfg(windows)]
+    {
+        exclusive_open_probe(path)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Fails with a file-in-use error when another process has the file open
+pub fn ensure_not_in_use(path: &Path) -> Result<()> {
+    if file_in_use(path) == Some(true) {
+        return Err(AppError::FileInUse(path.display().to_string()));
+    }
+    Ok(())
+}
+
+/// Looks for the file among every other process's open descriptors
+#[cfg(target_os = "linux")]
+fn proc_scan(path: &Path) -> Option<bool> {
+    let target = path.canonicalize().ok()?;
+    let own_pid = std::process::id().to_string();
+    let mut inspected_any = false;
+
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let name = entry.file_name();
+        let Some(pid) = name.to_str().filter(|n| n.bytes().all(|b| b.is_ascii_digit())) else { continue };
+        if pid == own_pid {
+            continue;
+        }
+        // Other users' processes aren't readable without privileges
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else { continue };
+        inspected_any = true;
+        if fds.flatten().any(|fd| std::fs::read_link(fd.path()).is_ok_and(|link| link == target)) {
+            return Some(true);
+        }
+    }
+
+    inspected_any.then_some(false)
+}
+
+/// Tries to take an exclusive advisory lock; only sees cooperating lockers
+#[cfg(unix)]
+fn lock_probe(path: &Path) -> Option<bool> {
+    let file = std::fs::File::open(path).ok()?;
+    match file.try_lock() {
+        Ok(()) => {
+            let _ = file.unlock();
+            Some(false)
+        }
+        Err(std::fs::TryLockError::WouldBlock) => Some(true),
+        Err(std::fs::TryLockError::Error(_)) => None,
+    }
+}
+
+/// Opens the file without sharing; a sharing violation means it's in use
+#[cfg(windows)]
+fn exclusive_open_probe(path: &Path) -> Option<bool> {
+    use std::os::windows::fs::OpenOptionsExt;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+
+    match std::fs::OpenOptions::new().read(true).write(true).share_mode(0).open(path) {
+        Ok(_) => Some(false),
+        Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Some(true),
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_missing_file_is_unknown_and_not_blocking() {
+        let path = Path::new("nonexistent/book.m4b");
+        assert_eq!(file_in_use(path), None);
+        assert!(ensure_not_in_use(path).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lock_probe_sees_held_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("book.m4b");
+        std::fs::write(&path, b"data").unwrap();
+        assert_eq!(lock_probe(&path), Some(false));
+
+        let holder = std::fs::File::open(&path).unwrap();
+        holder.lock().unwrap();
+        assert_eq!(lock_probe(&path), Some(true));
+        holder.unlock().unwrap();
+        assert_eq!(lock_probe(&path), Some(false));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_proc_scan_sees_other_process() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("book.m4b");
+        std::fs::write(&path, b"data").unwrap();
+        assert_eq!(proc_scan(&path), Some(false));
+
+        let Ok(mut child) = std::process::Command::new("sleep")
+            .arg("10")
+            .stdin(std::fs::File::open(&path).unwrap())
+            .spawn()
+        else {
+            eprintln!("Skipping test - sleep not available");
+            return;
+        };
+        let detected = proc_scan(&path);
+        let _ = child.kill();
+        let _ = child.wait();
+
+        assert_eq!(detected, Some(true));
+        assert!(matches!(ensure_not_in_use(&path), Ok(())));
+    }
+}
@@ -0,0 +1,368 @@
+//! Metadata snapshot history for undoing tag writes
+//!
+//! Before user-initiated tag writes, the current metadata of the target file
+//! is recorded in a per-file history under the app data directory so the
+//! last write can be reverted with `undo_metadata_write`.
+
+use super::AudiobookMetadata;
+use super::reader::read_metadata;
+use super::writer::{remove_cover_art, write_cover_art, write_metadata};
+use crate::atomic_write::atomic_write_json;
+use crate::errors::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory name for snapshot files inside the app data directory
+pub const HISTORY_DIR_NAME: &str = "metadata-history";
+
+/// Maximum number of snapshots retained per file
+pub const MAX_SNAPSHOTS_PER_FILE: usize = 10;
+
+/// Covers larger than this are not stored in snapshots
+pub const MAX_SNAPSHOT_COVER_BYTES: usize = 2 * 1024 * 1024;
+
+/// Process-wide history, initialized once the app data directory is known
+static GLOBAL_HISTORY: OnceLock<MetadataHistory> = OnceLock::new();
+
+/// A single recorded state of a file's metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataSnapshot {
+    /// Unix timestamp (seconds) when the snapshot was taken
+    pub taken_at: u64,
+    /// Metadata as it was before the write
+    pub metadata: AudiobookMetadata,
+    /// True when the cover exceeded the size cap and was not stored
+    pub cover_omitted: bool,
+}
+
+/// On-disk history for one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileHistory {
+    /// Canonical path of the tracked file
+    path: PathBuf,
+    /// Snapshots, oldest first
+    snapshots: Vec<MetadataSnapshot>,
+}
+
+/// Snapshot store rooted at a history directory
+#[derive(Debug, Clone)]
+pub struct MetadataHistory {
+    root: PathBuf,
+}
+
+impl MetadataHistory {
+    /// Creates a history store rooted at the given directory
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self { root: root.as_ref().to_path_buf() }
+    }
+
+    /// Records the current metadata of a file before it is overwritten
+    pub fn snapshot<P: AsRef<Path>>(&self, file_path: P) -> Result<()> {
+        let canonical = canonicalize_existing(file_path.as_ref())?;
+        let mut metadata = read_metadata(&canonical)?;
+
+        let cover_omitted = metadata.cover_art.as_ref()
+            .is_some_and(|cover| cover.len() > MAX_SNAPSHOT_COVER_BYTES);
+        if cover_omitted {
+            metadata.cover_art = None;
+        }
+
+        let mut history = self.load(&canonical)?
+            .unwrap_or_else(|| FileHistory { path: canonical.clone(), snapshots: Vec::new() });
+        history.snapshots.push(MetadataSnapshot {
+            taken_at: unix_now(),
+            metadata,
+            cover_omitted,
+        });
+        if history.snapshots.len() > MAX_SNAPSHOTS_PER_FILE {
+            let excess = history.snapshots.len() - MAX_SNAPSHOTS_PER_FILE;
+            history.snapshots.drain(..excess);
+        }
+
+        self.save(&history)
+    }
+
+    /// Restores the most recent snapshot and removes it from the history
+    pub fn undo<P: AsRef<Path>>(&self, file_path: P) -> Result<AudiobookMetadata> {
+        let canonical = canonicalize_existing(file_path.as_ref())?;
+        let mut history = self.load(&canonical)?
+            .filter(|history| !history.snapshots.is_empty())
+            .ok_or_else(|| AppError::InvalidInput(
+                format!("No metadata history to undo for: {}", canonical.display())
+            ))?;
+
+        let snapshot = history.snapshots.pop()
+            .ok_or_else(|| AppError::General("Metadata history unexpectedly empty".to_string()))?;
+        restore_snapshot(&canonical, &snapshot)?;
+        self.save(&history)?;
+
+        Ok(snapshot.metadata)
+    }
+
+    /// Returns the number of snapshots stored for a file
+    #[cfg(test)]
+    pub fn snapshot_count<P: AsRef<Path>>(&self, file_path: P) -> Result<usize> {
+        let canonical = canonicalize_existing(file_path.as_ref())?;
+        Ok(self.load(&canonical)?.map_or(0, |history| history.snapshots.len()))
+    }
+
+    /// Removes histories whose tracked file no longer exists
+    ///
+    /// Returns the number of history files removed.
+    pub fn prune_missing(&self) -> Result<usize> {
+        if !self.root.exists() {
+            return Ok(0);
+        }
+
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let stale = match read_history_file(&path) {
+                Ok(history) => !history.path.exists(),
+                Err(e) => {
+                    log::warn!("Discarding unreadable metadata history '{}': {e}", path.display());
+                    true
+                }
+            };
+            if stale {
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Loads the history for a canonical path, if any
+    fn load(&self, canonical: &Path) -> Result<Option<FileHistory>> {
+        let history_file = self.history_file(canonical);
+        if !history_file.exists() {
+            return Ok(None);
+        }
+        read_history_file(&history_file).map(Some)
+    }
+
+    /// Persists a history, removing the file once it holds no snapshots
+    fn save(&self, history: &FileHistory) -> Result<()> {
+        let history_file = self.history_file(&history.path);
+        if history.snapshots.is_empty() {
+            if history_file.exists() {
+                fs::remove_file(&history_file)?;
+            }
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.root)?;
+        atomic_write_json(&history_file, history)
+    }
+
+    /// Returns the history file path for a canonical path
+    fn history_file(&self, canonical: &Path) -> PathBuf {
+        self.root.join(format!("{}.json", path_key(canonical)))
+    }
+}
+
+/// Initializes the process-wide history under the given app data directory
+pub fn init_global_history<P: AsRef<Path>>(app_data_dir: P) {
+    let history = MetadataHistory::new(app_data_dir.as_ref().join(HISTORY_DIR_NAME));
+    match history.prune_missing() {
+        Ok(0) => {}
+        Ok(count) => log::info!("Pruned {count} metadata histories for deleted files"),
+        Err(e) => log::warn!("Failed to prune metadata history: {e}"),
+    }
+    if GLOBAL_HISTORY.set(history).is_err() {
+        log::warn!("Metadata history already initialized");
+    }
+}
+
+/// Returns the process-wide history if it has been initialized
+pub fn global_history() -> Option<&'static MetadataHistory> {
+    GLOBAL_HISTORY.get()
+}
+
+/// Snapshots a file before a write if history is available
+///
+/// Failures are logged rather than returned so a broken history never
+/// blocks the write itself.
+pub fn snapshot_before_write<P: AsRef<Path>>(file_path: P) {
+    let path = file_path.as_ref();
+    let Some(history) = global_history() else {
+        return;
+    };
+    if !path.exists() {
+        return;
+    }
+    if let Err(e) = history.snapshot(path) {
+        log::warn!("Failed to snapshot metadata for '{}': {e}", path.display());
+    }
+}
+
+/// Writes a snapshot's metadata and cover back to the file
+fn restore_snapshot(path: &Path, snapshot: &MetadataSnapshot) -> Result<()> {
+    // Pictures are cleared so the restored cover is the only one; keep the
+    // current cover when the snapshot could not store one
+    let cover = if snapshot.cover_omitted {
+        read_metadata(path)?.cover_art
+    } else {
+        snapshot.metadata.cover_art.clone()
+    };
+
+    write_metadata(path, &snapshot.metadata)?;
+    remove_cover_art(path, true)?;
+    if let Some(cover) = cover {
+        write_cover_art(path, &cover)?;
+    }
+    Ok(())
+}
+
+/// Reads and parses a history file
+fn read_history_file(path: &Path) -> Result<FileHistory> {
+    let bytes = fs::read(path)?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| AppError::General(format!("Corrupt metadata history '{}': {e}", path.display())))
+}
+
+/// Canonicalizes a path, reporting missing files as validation errors
+fn canonicalize_existing(path: &Path) -> Result<PathBuf> {
+    if !path.exists() {
+        return Err(AppError::FileValidation(
+            format!("File not found: {}", path.display())
+        ));
+    }
+    Ok(fs::canonicalize(path)?)
+}
+
+/// Stable FNV-1a hash of the path, used as the history file name
+fn path_key(canonical: &Path) -> String {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let hash = canonical.to_string_lossy().bytes().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+    format!("{hash:016x}")
+}
+
+/// Current time as Unix seconds
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const TEST_MEDIA_FILE: &str = "../media/01 - Introduction.mp3";
+    const FAKE_JPEG: [u8; 8] = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46];
+
+    /// Copies the test MP3 into a temp dir, or None when media is missing
+    fn copy_test_media(temp_dir: &TempDir) -> Option<PathBuf> {
+        let source = Path::new(TEST_MEDIA_FILE);
+        if !source.exists() {
+            eprintln!("Skipping test - media file not found: {TEST_MEDIA_FILE}");
+            return None;
+        }
+        let target = temp_dir.path().join("book.mp3");
+        fs::copy(source, &target).unwrap();
+        Some(target)
+    }
+
+    fn picture_count(path: &Path) -> usize {
+        use lofty::prelude::TaggedFileExt;
+        let tagged_file = lofty::probe::Probe::open(path).unwrap().read().unwrap();
+        tagged_file.primary_tag().map_or(0, |tag| tag.pictures().len())
+    }
+
+    #[test]
+    fn test_path_key_is_stable() {
+        let key = path_key(Path::new("/books/a.m4b"));
+        assert_eq!(key.len(), 16);
+        assert_eq!(key, path_key(Path::new("/books/a.m4b")));
+        assert_ne!(key, path_key(Path::new("/books/b.m4b")));
+    }
+
+    #[test]
+    fn test_snapshot_nonexistent_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let history = MetadataHistory::new(temp_dir.path().join(HISTORY_DIR_NAME));
+        let result = history.snapshot("nonexistent.m4b");
+        assert!(matches!(result, Err(AppError::FileValidation(_))));
+    }
+
+    #[test]
+    fn test_undo_without_history() {
+        let temp_dir = TempDir::new().unwrap();
+        let Some(file) = copy_test_media(&temp_dir) else { return };
+        let history = MetadataHistory::new(temp_dir.path().join(HISTORY_DIR_NAME));
+
+        let result = history.undo(&file);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("No metadata history"));
+    }
+
+    #[test]
+    fn test_write_modify_undo_restores_metadata_and_cover() {
+        let temp_dir = TempDir::new().unwrap();
+        let Some(file) = copy_test_media(&temp_dir) else { return };
+        let history = MetadataHistory::new(temp_dir.path().join(HISTORY_DIR_NAME));
+
+        let mut original = AudiobookMetadata::new();
+        original.title = Some("Original Title".to_string());
+        original.author = Some("Original Author".to_string());
+        write_metadata(&file, &original).unwrap();
+        write_cover_art(&file, &FAKE_JPEG).unwrap();
+
+        history.snapshot(&file).unwrap();
+        let mut modified = AudiobookMetadata::new();
+        modified.title = Some("Overwritten".to_string());
+        write_metadata(&file, &modified).unwrap();
+        assert_eq!(read_metadata(&file).unwrap().cover_art.as_deref(), Some(&FAKE_JPEG[..]));
+        let replacement = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x47];
+        write_cover_art(&file, &replacement).unwrap();
+        assert_eq!(read_metadata(&file).unwrap().cover_art.as_deref(), Some(&replacement[..]));
+
+        let restored = history.undo(&file).unwrap();
+        assert_eq!(restored.title.as_deref(), Some("Original Title"));
+
+        let reread = read_metadata(&file).unwrap();
+        assert_eq!(reread.title.as_deref(), Some("Original Title"));
+        assert_eq!(reread.author.as_deref(), Some("Original Author"));
+        assert_eq!(reread.cover_art.as_deref(), Some(&FAKE_JPEG[..]));
+        assert_eq!(picture_count(&file), 1);
+        assert_eq!(history.snapshot_count(&file).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_retention_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let Some(file) = copy_test_media(&temp_dir) else { return };
+        let history = MetadataHistory::new(temp_dir.path().join(HISTORY_DIR_NAME));
+
+        for _ in 0..MAX_SNAPSHOTS_PER_FILE + 3 {
+            history.snapshot(&file).unwrap();
+        }
+        assert_eq!(history.snapshot_count(&file).unwrap(), MAX_SNAPSHOTS_PER_FILE);
+    }
+
+    #[test]
+    fn test_prune_missing_removes_deleted_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let Some(file) = copy_test_media(&temp_dir) else { return };
+        let history = MetadataHistory::new(temp_dir.path().join(HISTORY_DIR_NAME));
+
+        history.snapshot(&file).unwrap();
+        assert_eq!(history.prune_missing().unwrap(), 0);
+
+        fs::remove_file(&file).unwrap();
+        assert_eq!(history.prune_missing().unwrap(), 1);
+    }
+}
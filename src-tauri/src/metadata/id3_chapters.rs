@@ -0,0 +1,312 @@
+//! ID3v2 chapter frames
+//!
+//! Podcast-style MP3s mark chapters with `CHAP` frames (the ID3v2 Chapter
+//! Frame Addendum): an element ID, start and end times in milliseconds, and
+//! sub-frames of which `TIT2` holds the title. Lofty doesn't expose them, so
+//! the tag at the start of the file is parsed here. `CTOC` frames only order
+//! and nest the chapters; start times give the same order for the flat list
+//! a book needs, so they are skipped.
+
+use super::ChapterInfo;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Length of the tag header and of each frame header
+const HEADER_LEN: usize = 10;
+
+/// Chapters from the ID3v2 tag at the start of `path`, ordered by start time
+///
+/// A file without a tag or without `CHAP` frames, or one that can't be
+/// read, gives an empty list rather than an error.
+pub fn read_id3_chapters(path: &Path) -> Vec<ChapterInfo> {
+    match read_tag(path) {
+        Ok(tag) => parse_id3_chapters(&tag),
+        Err(e) => {
+            log::debug!("Cannot read ID3 chapters of {}: {e}", path.display());
+            Vec::new()
+        }
+    }
+}
+
+/// The raw ID3v2 tag at the start of `path`, header included; empty without one
+fn read_tag(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; HEADER_LEN];
+    if file.read_exact(&mut header).is_err() || !header.starts_with(b"ID3") {
+        return Ok(Vec::new());
+    }
+    let mut tag = header.to_vec();
+    file.take(synchsafe(&header[6..10]) as u64).read_to_end(&mut tag)?;
+    Ok(tag)
+}
+
+/// Chapters in a raw ID3v2.3 or ID3v2.4 tag, ordered by start time
+///
+/// Untitled chapters are named "Chapter N" by position.
+pub fn parse_id3_chapters(tag: &[u8]) -> Vec<ChapterInfo> {
+    let Some(header) = tag.get(..HEADER_LEN).filter(|header| header.starts_with(b"ID3")) else {
+        return Vec::new();
+    };
+    let (major, flags) = (header[3], header[5]);
+    if !(3..=4).contains(&major) {
+        return Vec::new();
+    }
+    // A truncated tag still yields the frames that are complete
+    let size = synchsafe(&header[6..10]) as usize;
+    let body = &tag[HEADER_LEN..tag.len().min(HEADER_LEN + size)];
+    // ID3v2.3 unsynchronises the whole tag, ID3v2.4 each frame
+    let body = if major == 3 && flags & 0x80 != 0 { Cow::Owned(resynchronise(body)) } else { Cow::Borrowed(body) };
+    let Some(body) = skip_extended_header(&body, major, flags) else {
+        return Vec::new();
+    };
+
+    let mut chapters: Vec<ChapterInfo> = frames(body, major).iter()
+        .filter(|frame| frame.id == b"CHAP")
+        .filter_map(|frame| chapter(&frame.data, major))
+        .collect();
+    super::reader::order_chapters(&mut chapters);
+    chapters
+}
+
+/// One frame with its data decoded as far as the frame flags require
+struct Frame<'a> {
+    id: &'a [u8],
+    data: Cow<'a, [u8]>,
+}
+
+/// Frames in `bytes`, stopping at padding or a frame running past the end
+///
+/// Compressed and encrypted frames are left out.
+fn frames(mut bytes: &[u8], major: u8) -> Vec<Frame<'_>> {
+    let mut frames = Vec::new();
+    while bytes.len() >= HEADER_LEN {
+        let id = &bytes[..4];
+        if !id.iter().all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit()) {
+            break;
+        }
+        let size = match major {
+            4 => synchsafe(&bytes[4..8]),
+            _ => u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        } as usize;
+        let format_flags = bytes[9];
+        let Some(data) = bytes.get(HEADER_LEN..HEADER_LEN + size) else {
+            break;
+        };
+        if let Some(data) = frame_data(data, major, format_flags) {
+            frames.push(Frame { id, data });
+        }
+        bytes = &bytes[HEADER_LEN + size..];
+    }
+    frames
+}
+
+/// Frame contents without the ID3v2.4 data length indicator or unsynchronisation
+fn frame_data(data: &[u8], major: u8, format_flags: u8) -> Option<Cow<'_, [u8]>> {
+    if major == 4 {
+        // Compression 0x08, encryption 0x04
+        if format_flags & 0x0c != 0 {
+            return None;
+        }
+        let data = if format_flags & 0x01 != 0 { data.get(4..)? } else { data };
+        Some(if format_flags & 0x02 != 0 { Cow::Owned(resynchronise(data)) } else { Cow::Borrowed(data) })
+    } else {
+        // Compression 0x80, encryption 0x40
+        (format_flags & 0xc0 == 0).then_some(Cow::Borrowed(data))
+    }
+}
+
+/// A `CHAP` frame: element ID, start and end in ms, two byte offsets, then sub-frames
+fn chapter(data: &[u8], major: u8) -> Option<ChapterInfo> {
+    let element_id_end = data.iter().position(|&byte| byte == 0)?;
+    let times = data.get(element_id_end + 1..)?;
+    let start_ms = u32::from_be_bytes(times.get(0..4)?.try_into().ok()?) as u64;
+    let end_ms = u32::from_be_bytes(times.get(4..8)?.try_into().ok()?) as u64;
+    let title = times.get(16..)
+        .map(|sub_frames| frames(sub_frames, major))
+        .and_then(|sub_frames| sub_frames.iter().find(|frame| frame.id == b"TIT2").map(|frame| decode_text(&frame.data)))
+        .unwrap_or_default();
+    Some(ChapterInfo { title, start_ms, end_ms: end_ms.max(start_ms) })
+}
+
+/// Text of a text frame, up to its first terminator
+fn decode_text(data: &[u8]) -> String {
+    let Some((&encoding, text)) = data.split_first() else {
+        return String::new();
+    };
+    let text = match encoding {
+        0 => text.iter().take_while(|&&byte| byte != 0).map(|&byte| byte as char).collect(),
+        1 => match text {
+            [0xff, 0xfe, rest @ ..] => utf16(rest, u16::from_le_bytes),
+            [0xfe, 0xff, rest @ ..] => utf16(rest, u16::from_be_bytes),
+            _ => utf16(text, u16::from_be_bytes),
+        },
+        2 => utf16(text, u16::from_be_bytes),
+        _ => String::from_utf8_lossy(text.split(|&byte| byte == 0).next().unwrap_or_default()).to_string(),
+    };
+    text.trim().to_string()
+}
+
+fn utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2)
+        .map(|pair| unit([pair[0], pair[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Tag body after the extended header, if the tag has one
+fn skip_extended_header(body: &[u8], major: u8, flags: u8) -> Option<&[u8]> {
+    if flags & 0x40 == 0 {
+        return Some(body);
+    }
+    let size_bytes = body.get(..4)?;
+    // ID3v2.3 counts the size bytes separately, ID3v2.4 includes them
+    let size = match major {
+        4 => synchsafe(size_bytes) as usize,
+        _ => u32::from_be_bytes(size_bytes.try_into().ok()?) as usize + 4,
+    };
+    body.get(size..)
+}
+
+/// Undoes unsynchronisation: every `FF 00` becomes `FF`
+fn resynchronise(bytes: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(bytes.len());
+    for (index, &byte) in bytes.iter().enumerate() {
+        if byte == 0 && index > 0 && bytes[index - 1] == 0xff {
+            continue;
+        }
+        decoded.push(byte);
+    }
+    decoded
+}
+
+/// A 28-bit integer stored 7 bits per byte
+fn synchsafe(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |size, &byte| (size << 7) | (byte & 0x7f) as u32)
+}
+
+/// Builders for synthetic ID3v2 tags
+#[cfg(test)]
+pub mod fixtures {
+    fn synchsafe_bytes(size: usize) -> [u8; 4] {
+        [(size >> 21) as u8 & 0x7f, (size >> 14) as u8 & 0x7f, (size >> 7) as u8 & 0x7f, size as u8 & 0x7f]
+    }
+
+    /// A frame with the frame size encoded for ID3v2.`major`
+    pub fn frame(id: &[u8; 4], data: &[u8], major: u8) -> Vec<u8> {
+        let mut frame = id.to_vec();
+        match major {
+            4 => frame.extend(synchsafe_bytes(data.len())),
+            _ => frame.extend((data.len() as u32).to_be_bytes()),
+        }
+        frame.extend([0, 0]);
+        frame.extend(data);
+        frame
+    }
+
+    /// A `CHAP` frame with an ISO-8859-1 `TIT2` title, or none
+    pub fn chap(element_id: &str, start_ms: u32, end_ms: u32, title: Option<&str>, major: u8) -> Vec<u8> {
+        let mut data = element_id.as_bytes().to_vec();
+        data.push(0);
+        data.extend(start_ms.to_be_bytes());
+        data.extend(end_ms.to_be_bytes());
+        data.extend([0xff; 8]);
+        if let Some(title) = title {
+            let mut text = vec![0];
+            text.extend(title.as_bytes());
+            data.extend(frame(b"TIT2", &text, major));
+        }
+        frame(b"CHAP", &data, major)
+    }
+
+    /// A complete ID3v2.`major` tag holding `frames`, with some padding
+    pub fn tag(frames: &[Vec<u8>], major: u8) -> Vec<u8> {
+        let mut body: Vec<u8> = frames.concat();
+        body.extend([0; 16]);
+        let mut tag = vec![b'I', b'D', b'3', major, 0, 0];
+        tag.extend(synchsafe_bytes(body.len()));
+        tag.extend(body);
+        tag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{chap, frame, tag};
+    use super::*;
+
+    fn info(title: &str, start_ms: u64, end_ms: u64) -> ChapterInfo {
+        ChapterInfo { title: title.to_string(), start_ms, end_ms }
+    }
+
+    #[test]
+    fn test_parses_chap_frames_in_both_versions() {
+        for major in [3, 4] {
+            let tag = tag(&[
+                frame(b"TIT2", b"\0Episode 12", major),
+                chap("ch1", 95_000, 240_000, Some("Interview"), major),
+                frame(b"CTOC", b"toc\0\x03\x02ch0\0ch1\0", major),
+                chap("ch0", 0, 95_000, Some("Intro"), major),
+                chap("ch2", 240_000, 300_000, None, major),
+            ], major);
+            assert_eq!(parse_id3_chapters(&tag), [
+                info("Intro", 0, 95_000),
+                info("Interview", 95_000, 240_000),
+                info("Chapter 3", 240_000, 300_000),
+            ], "ID3v2.{major}");
+        }
+    }
+
+    #[test]
+    fn test_tags_without_chapters() {
+        assert!(parse_id3_chapters(&tag(&[frame(b"TIT2", b"\0Episode", 3)], 3)).is_empty());
+        assert!(parse_id3_chapters(b"fLaC").is_empty());
+        assert!(parse_id3_chapters(&[]).is_empty());
+
+        // A frame running past the end of the tag ends parsing but keeps earlier chapters
+        let truncated = tag(&[chap("ch0", 0, 1_000, Some("One"), 4), b"CHAP\0\0\x7f\x7f\0\0".to_vec()], 4);
+        assert_eq!(parse_id3_chapters(&truncated), [info("One", 0, 1_000)]);
+    }
+
+    #[test]
+    fn test_decodes_text_encodings() {
+        assert_eq!(decode_text(b"\0Caf\xe9\0junk"), "Café");
+        assert_eq!(decode_text(b"\x01\xff\xfeO\0n\0e\0\0\0"), "One");
+        assert_eq!(decode_text(b"\x02\0T\0w\0o"), "Two");
+        assert_eq!(decode_text("\x03Über ".as_bytes()), "Über");
+        assert_eq!(decode_text(b""), "");
+    }
+
+    #[test]
+    fn test_unsynchronised_and_extended_header_tags() {
+        // ID3v2.3 with whole-tag unsynchronisation: FF bytes are followed by a stuffed 00
+        let mut stuffed = Vec::new();
+        for byte in chap("c", 0xff00, 0x1_ff00, Some("Sync"), 3) {
+            stuffed.push(byte);
+            if byte == 0xff {
+                stuffed.push(0);
+            }
+        }
+        let mut unsynced = tag(&[stuffed], 3);
+        unsynced[5] = 0x80;
+        assert_eq!(parse_id3_chapters(&unsynced), [info("Sync", 0xff00, 0x1_ff00)]);
+
+        // ID3v2.4 extended header of 6 bytes, size included
+        let mut extended = tag(&[vec![0, 0, 0, 6, 1, 0], chap("e", 500, 1_500, Some("Ext"), 4)], 4);
+        extended[5] = 0x40;
+        assert_eq!(parse_id3_chapters(&extended), [info("Ext", 500, 1_500)]);
+    }
+
+    #[test]
+    fn test_read_id3_chapters_from_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("episode.mp3");
+        let mut bytes = tag(&[chap("ch0", 0, 60_000, Some("Cold Open"), 3)], 3);
+        bytes.extend([0xff, 0xfb, 0x90, 0x00]);
+        std::fs::write(&path, bytes).unwrap();
+        assert_eq!(read_id3_chapters(&path), [info("Cold Open", 0, 60_000)]);
+        assert!(read_id3_chapters(&temp_dir.path().join("missing.mp3")).is_empty());
+    }
+}
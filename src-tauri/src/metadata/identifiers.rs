@@ -0,0 +1,116 @@
+//! Book identifiers (ISBN and ASIN)
+//!
+//! Audiobookshelf matches books on these, so a mistyped identifier is
+//! worse than none: it is refused before anything is written. ISBNs may be
+//! written with hyphens or spaces; the check digit is verified on the
+//! digits alone.
+
+use super::AudiobookMetadata;
+use crate::errors::{AppError, Result};
+
+/// Checks the identifier fields of `metadata`
+///
+/// Fails with `InvalidInput` naming the first field that is malformed.
+pub fn validate_metadata(metadata: &AudiobookMetadata) -> Result<()> {
+    if let Some(isbn) = &metadata.isbn {
+        if !is_valid_isbn(isbn) {
+            return Err(AppError::InvalidInput(format!(
+                "Invalid isbn '{isbn}': expected an ISBN-10 or ISBN-13 with a valid check digit"
+            )));
+        }
+    }
+    if let Some(asin) = &metadata.asin {
+        if !is_valid_asin(asin) {
+            return Err(AppError::InvalidInput(format!(
+                "Invalid asin '{asin}': expected 10 letters and digits"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `isbn` is an ISBN-10 or ISBN-13 whose check digit matches
+pub fn is_valid_isbn(isbn: &str) -> bool {
+    let compact: Vec<char> = isbn.chars().filter(|c| !matches!(c, '-' | ' ')).collect();
+    match compact.len() {
+        10 => is_valid_isbn10(&compact),
+        13 => is_valid_isbn13(&compact),
+        _ => false,
+    }
+}
+
+/// Weights 10 down to 1; the last character may be `X` for 10
+fn is_valid_isbn10(chars: &[char]) -> bool {
+    let mut sum = 0;
+    for (index, &c) in chars.iter().enumerate() {
+        let value = match c {
+            'X' | 'x' if index == 9 => 10,
+            _ => match c.to_digit(10) {
+                Some(digit) => digit,
+                None => return false,
+            },
+        };
+        sum += value * (10 - index as u32);
+    }
+    sum % 11 == 0
+}
+
+/// Weights alternate 1 and 3
+fn is_valid_isbn13(chars: &[char]) -> bool {
+    let mut sum = 0;
+    for (index, c) in chars.iter().enumerate() {
+        let Some(digit) = c.to_digit(10) else {
+            return false;
+        };
+        sum += if index % 2 == 0 { digit } else { digit * 3 };
+    }
+    sum % 10 == 0
+}
+
+/// Whether `asin` has the shape of an Amazon ASIN: 10 ASCII letters and digits
+pub fn is_valid_asin(asin: &str) -> bool {
+    asin.len() == 10 && asin.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isbn_check_digits() {
+        assert!(is_valid_isbn("0441013597"));
+        assert!(is_valid_isbn("0-8044-2957-X"));
+        assert!(is_valid_isbn("978-0-441-01359-3"));
+        assert!(is_valid_isbn("978 0441013593"));
+        assert!(!is_valid_isbn("0441013598"));
+        assert!(!is_valid_isbn("9780441013594"));
+        assert!(!is_valid_isbn("X441013597"));
+        assert!(!is_valid_isbn("97804410135"));
+        assert!(!is_valid_isbn(""));
+    }
+
+    #[test]
+    fn test_asin_shape() {
+        assert!(is_valid_asin("B002V0QK4C"));
+        assert!(is_valid_asin("0441013597"));
+        assert!(!is_valid_asin("B002V0QK4"));
+        assert!(!is_valid_asin("B002V0QK4C1"));
+        assert!(!is_valid_asin("B002V-QK4C"));
+    }
+
+    #[test]
+    fn test_validate_metadata_names_the_field() {
+        assert!(validate_metadata(&AudiobookMetadata::new()).is_ok());
+        let valid = AudiobookMetadata {
+            isbn: Some("978-0-441-01359-3".to_string()),
+            asin: Some("B002V0QK4C".to_string()),
+            ..AudiobookMetadata::new()
+        };
+        assert!(validate_metadata(&valid).is_ok());
+
+        let bad_isbn = AudiobookMetadata { isbn: Some("9780441013594".to_string()), ..valid.clone() };
+        assert!(matches!(validate_metadata(&bad_isbn), Err(AppError::InvalidInput(message)) if message.starts_with("Invalid isbn")));
+        let bad_asin = AudiobookMetadata { asin: Some("B002".to_string()), ..valid };
+        assert!(matches!(validate_metadata(&bad_asin), Err(AppError::InvalidInput(message)) if message.starts_with("Invalid asin")));
+    }
+}
@@ -0,0 +1,229 @@
+//! Book metadata inferred from the tags of all inputs
+//!
+//! Books ripped from CDs or split into chapters often disagree between
+//! files: one file has a typo in the album, a few lack the narrator. The
+//! encode copies the first input's tags; this looks at every input so the
+//! metadata panel can be prefilled with a chosen strategy and the
+//! disagreements shown for review. Only book-level fields are inferred;
+//! titles and track numbers differ per file by design. The book title is
+//! taken from the inferred album, as the encode does.
+
+use super::reader::read_metadata;
+use super::writer::MetadataField;
+use super::AudiobookMetadata;
+use crate::audio::AudioFile;
+use serde::{Deserialize, Serialize};
+
+/// How a field is chosen when inputs disagree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergeStrategy {
+    /// The first input's value, as the encode copies it; unset when the first input lacks it
+    #[default]
+    FirstFile,
+    /// The value most inputs have; ties go to the value seen first
+    MostCommon,
+    /// Only a value every input has; anything less leaves the field unset
+    Intersection,
+}
+
+/// A field whose inputs disagree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataConflict {
+    /// Field the inputs disagree on
+    pub field: MetadataField,
+    /// Distinct values in input order
+    pub values: Vec<String>,
+    /// Value the strategy picked, if any
+    pub chosen: Option<String>,
+}
+
+/// Metadata inferred from the inputs, with the disagreements behind it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InferredMetadata {
+    /// Book-level fields the strategy settled on
+    pub metadata: AudiobookMetadata,
+    /// Fields with more than one distinct value, in field order
+    pub conflicts: Vec<MetadataConflict>,
+}
+
+/// Fields inferred across inputs
+const BOOK_FIELDS: [MetadataField; 10] = [
+    MetadataField::Album,
+    MetadataField::Author,
+    MetadataField::Narrator,
+    MetadataField::Year,
+    MetadataField::Genre,
+    MetadataField::Series,
+    MetadataField::SeriesIndex,
+    MetadataField::SortAuthor,
+    MetadataField::Isbn,
+    MetadataField::Asin,
+];
+
+/// Reads the tags of every valid input and merges them with `strategy`
+///
+/// Inputs whose tags can't be read are left out, as if not in the list.
+pub fn derive_metadata_from_inputs(files: &[AudioFile], strategy: MergeStrategy) -> InferredMetadata {
+    let tags: Vec<AudiobookMetadata> = files.iter()
+        .filter(|file| file.is_valid)
+        .filter_map(|file| read_metadata(&file.path)
+            .inspect_err(|e| log::debug!("Leaving {} out of metadata inference: {e}", file.path.display()))
+            .ok())
+        .collect();
+    infer_from_tags(&tags, strategy)
+}
+
+/// Merges already-read tags with `strategy`
+pub fn infer_from_tags(tags: &[AudiobookMetadata], strategy: MergeStrategy) -> InferredMetadata {
+    let mut metadata = AudiobookMetadata::new();
+    let mut conflicts = Vec::new();
+    for field in BOOK_FIELDS {
+        let seen: Vec<Option<String>> = tags.iter().map(|tag| field_text(tag, field)).collect();
+        let values = distinct_values(&seen);
+        let chosen = choose(&seen, &values, strategy);
+        if let Some(value) = &chosen {
+            set_field(&mut metadata, field, value);
+        }
+        if values.len() > 1 {
+            conflicts.push(MetadataConflict { field, values, chosen });
+        }
+    }
+    metadata.title = metadata.album.clone();
+    InferredMetadata { metadata, conflicts }
+}
+
+/// Non-empty values in first-seen order, without repeats
+fn distinct_values(seen: &[Option<String>]) -> Vec<String> {
+    let mut values: Vec<String> = Vec::new();
+    for value in seen.iter().flatten() {
+        if !values.contains(value) {
+            values.push(value.clone());
+        }
+    }
+    values
+}
+
+/// The value `strategy` picks from one field's values across inputs
+fn choose(seen: &[Option<String>], values: &[String], strategy: MergeStrategy) -> Option<String> {
+    match strategy {
+        MergeStrategy::FirstFile => seen.first().cloned().flatten(),
+        MergeStrategy::MostCommon => {
+            let count = |value: &String| seen.iter().flatten().filter(|seen| *seen == value).count();
+            // `max_by_key` keeps the last maximum, so the reversed list leaves the first-seen value on ties
+            values.iter().rev().max_by_key(|value| count(value)).cloned()
+        }
+        MergeStrategy::Intersection => match values {
+            [only] if seen.iter().all(Option::is_some) => Some(only.clone()),
+            _ => None,
+        },
+    }
+}
+
+/// A book-level field as trimmed text; empty counts as missing
+fn field_text(metadata: &AudiobookMetadata, field: MetadataField) -> Option<String> {
+    let text = match field {
+        MetadataField::Album => metadata.album.clone(),
+        MetadataField::Author => metadata.author.clone(),
+        MetadataField::Narrator => metadata.narrator.clone(),
+        MetadataField::Year => metadata.year.map(|year| year.to_string()),
+        MetadataField::Genre => metadata.genre.clone(),
+        MetadataField::Series => metadata.series.clone(),
+        MetadataField::SeriesIndex => metadata.series_index.map(|index| index.to_string()),
+        MetadataField::SortAuthor => metadata.sort_author.clone(),
+        MetadataField::Isbn => metadata.isbn.clone(),
+        MetadataField::Asin => metadata.asin.clone(),
+        _ => None,
+    };
+    text.map(|text| text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+/// Sets a book-level field from text produced by `field_text`
+fn set_field(metadata: &mut AudiobookMetadata, field: MetadataField, value: &str) {
+    let text = Some(value.to_string());
+    match field {
+        MetadataField::Album => metadata.album = text,
+        MetadataField::Author => metadata.author = text,
+        MetadataField::Narrator => metadata.narrator = text,
+        MetadataField::Year => metadata.year = value.parse().ok(),
+        MetadataField::Genre => metadata.genre = text,
+        MetadataField::Series => metadata.series = text,
+        MetadataField::SeriesIndex => metadata.series_index = value.parse().ok(),
+        MetadataField::SortAuthor => metadata.sort_author = text,
+        MetadataField::Isbn => metadata.isbn = text,
+        MetadataField::Asin => metadata.asin = text,
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(album: Option<&str>, narrator: Option<&str>) -> AudiobookMetadata {
+        AudiobookMetadata {
+            title: Some("Track".to_string()),
+            album: album.map(str::to_string),
+            author: Some("Frank Herbert".to_string()),
+            narrator: narrator.map(str::to_string),
+            ..AudiobookMetadata::new()
+        }
+    }
+
+    #[test]
+    fn test_strategies_on_disagreeing_inputs() {
+        let inputs = [
+            tags(Some("Dune "), None),
+            tags(Some("Dune (Unabridged)"), Some("Scott Brick")),
+            tags(Some("Dune"), Some("Scott Brick")),
+        ];
+
+        let first = infer_from_tags(&inputs, MergeStrategy::FirstFile);
+        assert_eq!(first.metadata.album.as_deref(), Some("Dune"));
+        assert_eq!(first.metadata.title.as_deref(), Some("Dune"));
+        assert_eq!(first.metadata.narrator, None);
+        assert_eq!(first.conflicts, vec![MetadataConflict {
+            field: MetadataField::Album,
+            values: vec!["Dune".to_string(), "Dune (Unabridged)".to_string()],
+            chosen: Some("Dune".to_string()),
+        }]);
+
+        let common = infer_from_tags(&inputs, MergeStrategy::MostCommon);
+        assert_eq!(common.metadata.album.as_deref(), Some("Dune"));
+        assert_eq!(common.metadata.narrator.as_deref(), Some("Scott Brick"));
+
+        let intersection = infer_from_tags(&inputs, MergeStrategy::Intersection);
+        assert_eq!(intersection.metadata.album, None);
+        assert_eq!(intersection.metadata.author.as_deref(), Some("Frank Herbert"));
+        // Missing in one input: no disagreement, but not in every input either
+        assert_eq!(intersection.metadata.narrator, None);
+        assert_eq!(intersection.conflicts.len(), 1);
+        assert_eq!(intersection.conflicts[0].chosen, None);
+    }
+
+    #[test]
+    fn test_most_common_tie_goes_to_first_seen() {
+        let inputs = [
+            tags(Some("Dune Messiah"), None),
+            tags(Some("Dune"), None),
+            tags(Some("Dune"), None),
+            tags(Some("Dune Messiah"), None),
+        ];
+        let inferred = infer_from_tags(&inputs, MergeStrategy::MostCommon);
+        assert_eq!(inferred.metadata.album.as_deref(), Some("Dune Messiah"));
+        assert_eq!(inferred.conflicts[0].values, vec!["Dune Messiah".to_string(), "Dune".to_string()]);
+    }
+
+    #[test]
+    fn test_fully_missing_fields_stay_unset() {
+        let inputs = [tags(None, None), tags(Some("  "), None)];
+        for strategy in [MergeStrategy::FirstFile, MergeStrategy::MostCommon, MergeStrategy::Intersection] {
+            let inferred = infer_from_tags(&inputs, strategy);
+            assert_eq!((inferred.metadata.album, inferred.metadata.title, inferred.metadata.genre), (None, None, None));
+            assert!(inferred.conflicts.is_empty(), "{strategy:?}");
+        }
+        let empty = infer_from_tags(&[], MergeStrategy::Intersection);
+        assert_eq!(empty.metadata.author, None);
+    }
+}
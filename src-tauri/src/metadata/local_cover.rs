@@ -0,0 +1,137 @@
+//! Local cover lookup from folder conventions
+//!
+//! Source folders often ship a `cover.jpg` or `folder.jpg` next to the
+//! audio. This looks in the inputs' parent directories only; there is no
+//! network lookup.
+
+use super::cover_info::read_image_info;
+use crate::errors::Result;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Conventional cover file stems, most preferred first
+const COVER_STEMS: &[&str] = &["cover", "folder", "front"];
+
+/// Image extensions a local cover may use
+const COVER_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// A conventional cover file and how it ranks
+#[derive(Debug)]
+struct CoverCandidate {
+    path: PathBuf,
+    area: u64,
+    stem_rank: usize,
+}
+
+/// Finds the best conventional cover image next to the given inputs
+///
+/// Candidates are ranked by pixel area, then by filename convention
+/// (cover, folder, front). Unreadable images rank below readable ones.
+pub fn find_local_cover<P: AsRef<Path>>(file_paths: &[P]) -> Result<Option<PathBuf>> {
+    let directories: BTreeSet<PathBuf> = file_paths.iter()
+        .filter_map(|p| p.as_ref().parent().map(Path::to_path_buf))
+        .collect();
+
+    let mut best: Option<CoverCandidate> = None;
+    for directory in directories {
+        let Ok(entries) = std::fs::read_dir(&directory) else { continue };
+        for entry in entries.flatten() {
+            let Some(candidate) = inspect_candidate(&entry.path()) else { continue };
+            let better = best.as_ref().is_none_or(|current| {
+                (candidate.area, std::cmp::Reverse(candidate.stem_rank))
+                    > (current.area, std::cmp::Reverse(current.stem_rank))
+            });
+            if better {
+                best = Some(candidate);
+            }
+        }
+    }
+
+    Ok(best.map(|candidate| candidate.path))
+}
+
+/// Returns a candidate if the path has a conventional cover name
+fn inspect_candidate(path: &Path) -> Option<CoverCandidate> {
+    if !path.is_file() {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?.to_lowercase();
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let stem_rank = COVER_STEMS.iter().position(|s| *s == stem)?;
+    if !COVER_EXTENSIONS.contains(&extension.as_str()) {
+        return None;
+    }
+
+    let area = std::fs::read(path).ok()
+        .and_then(|data| read_image_info(&data))
+        .map(|info| info.area())
+        .unwrap_or(0);
+    Some(CoverCandidate { path: path.to_path_buf(), area, stem_rank })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::cover_info::fixtures;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn book_dir(temp_dir: &TempDir, name: &str) -> PathBuf {
+        let dir = temp_dir.path().join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("01.mp3"), b"audio").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_no_candidates() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = book_dir(&temp_dir, "book");
+        fs::write(dir.join("notes.jpg"), fixtures::jpeg(1000, 1000)).unwrap();
+        fs::write(dir.join("cover.txt"), b"not an image").unwrap();
+
+        assert_eq!(find_local_cover(&[dir.join("01.mp3")]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_case_insensitive_match() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = book_dir(&temp_dir, "book");
+        fs::write(dir.join("Folder.JPG"), fixtures::jpeg(600, 600)).unwrap();
+
+        assert_eq!(find_local_cover(&[dir.join("01.mp3")]).unwrap(), Some(dir.join("Folder.JPG")));
+    }
+
+    #[test]
+    fn test_prefers_largest_resolution_across_folders() {
+        let temp_dir = TempDir::new().unwrap();
+        let disc1 = book_dir(&temp_dir, "disc1");
+        let disc2 = book_dir(&temp_dir, "disc2");
+        fs::write(disc1.join("cover.jpg"), fixtures::jpeg(500, 500)).unwrap();
+        fs::write(disc1.join("folder.png"), fixtures::png(800, 800)).unwrap();
+        fs::write(disc2.join("cover.webp"), fixtures::webp(1400, 1400)).unwrap();
+
+        let inputs = [disc1.join("01.mp3"), disc2.join("01.mp3")];
+        assert_eq!(find_local_cover(&inputs).unwrap(), Some(disc2.join("cover.webp")));
+    }
+
+    #[test]
+    fn test_equal_size_prefers_cover_name() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = book_dir(&temp_dir, "book");
+        fs::write(dir.join("folder.jpg"), fixtures::jpeg(500, 500)).unwrap();
+        fs::write(dir.join("cover.png"), fixtures::png(500, 500)).unwrap();
+
+        assert_eq!(find_local_cover(&[dir.join("01.mp3")]).unwrap(), Some(dir.join("cover.png")));
+    }
+
+    #[test]
+    fn test_unreadable_image_ranks_last() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = book_dir(&temp_dir, "book");
+        fs::write(dir.join("cover.jpg"), b"corrupt").unwrap();
+        fs::write(dir.join("front.jpg"), fixtures::jpeg(300, 300)).unwrap();
+
+        assert_eq!(find_local_cover(&[dir.join("01.mp3")]).unwrap(), Some(dir.join("front.jpg")));
+    }
+}
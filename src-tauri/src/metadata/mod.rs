@@ -3,16 +3,40 @@
 //! This module provides functionality to read and write metadata
 //! from/to audio files using the Lofty crate.
 
+use provenance::EncodingInfo;
 use serde::{Deserialize, Serialize};
 
+pub mod chapter_remux;
+pub mod chpl;
+pub mod cover_convert;
+pub mod cover_payload;
+pub mod cover_info;
+pub mod cover_quality;
+pub mod cue;
+pub mod description;
+pub mod file_in_use;
+pub mod history;
+pub mod identifiers;
+pub mod inference;
+pub mod id3_chapters;
+pub mod local_cover;
+pub mod normalize;
+pub mod placeholder_cover;
+pub mod profile;
+pub mod provenance;
 pub mod reader;
+pub mod text;
 pub mod writer;
+pub mod write_retry;
+pub mod write_lock;
 
 /// Represents audiobook metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudiobookMetadata {
     /// Title of the audiobook
     pub title: Option<String>,
+    /// Subtitle of the book
+    pub subtitle: Option<String>,
     /// Author of the book
     pub author: Option<String>,
     /// Album name (book/series name)
@@ -25,9 +49,50 @@ pub struct AudiobookMetadata {
     pub genre: Option<String>,
     /// Description or synopsis
     pub description: Option<String>,
+    /// Series name
+    pub series: Option<String>,
+    /// Position within the series (fractional for novellas, e.g. 1.5)
+    pub series_index: Option<f32>,
+    /// Track number of this part
+    pub track_number: Option<u32>,
+    /// Total number of tracks
+    pub track_total: Option<u32>,
+    /// Disc number of this part
+    pub disc_number: Option<u32>,
+    /// Total number of discs
+    pub disc_total: Option<u32>,
+    /// Title used for sorting (e.g. "Hobbit, The")
+    pub sort_title: Option<String>,
+    /// Author used for sorting (e.g. "Tolkien, J.R.R.")
+    pub sort_author: Option<String>,
+    /// ISBN-10 or ISBN-13, hyphens allowed
+    pub isbn: Option<String>,
+    /// Amazon/Audible ASIN
+    pub asin: Option<String>,
     /// Cover art as raw bytes
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cover_art: Option<Vec<u8>>,
+    /// Tag formats the fields were read from, in merge order (e.g. "ID3v2", "ID3v1")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tag_sources: Vec<String>,
+    /// Chapters in the file, ordered by start time; `write_metadata` ignores it, `write_chapters` replaces them
+    #[serde(default)]
+    pub chapters: Vec<ChapterInfo>,
+    /// Encoder settings from the tool tag; when set, `write_metadata` replaces the tool tag with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding_info: Option<EncodingInfo>,
+}
+
+/// A chapter read from an existing file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterInfo {
+    /// Chapter title
+    pub title: String,
+    /// Start in milliseconds from the start of the file
+    pub start_ms: u64,
+    /// End in milliseconds from the start of the file
+    pub end_ms: u64,
 }
 
 impl AudiobookMetadata {
@@ -35,13 +100,27 @@ impl AudiobookMetadata {
     pub fn new() -> Self {
         Self {
             title: None,
+            subtitle: None,
             author: None,
             album: None,
             narrator: None,
             year: None,
             genre: None,
             description: None,
+            series: None,
+            series_index: None,
+            track_number: None,
+            track_total: None,
+            disc_number: None,
+            disc_total: None,
+            sort_title: None,
+            sort_author: None,
+            isbn: None,
+            asin: None,
             cover_art: None,
+            tag_sources: Vec::new(),
+            chapters: Vec::new(),
+            encoding_info: None,
         }
     }
 }
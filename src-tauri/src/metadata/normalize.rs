@@ -0,0 +1,104 @@
+//! Cleanup applied to metadata before it is written
+//!
+//! Text fields are trimmed and a field that is blank after trimming counts
+//! as unset, so no empty tag item is written. A year of 0 or one outside
+//! `MIN_METADATA_YEAR..=MAX_METADATA_YEAR` is refused rather than written.
+
+use super::AudiobookMetadata;
+use crate::audio::constants::{MAX_METADATA_YEAR, MIN_METADATA_YEAR};
+use crate::errors::{AppError, Result};
+
+/// Returns `metadata` as it will be written
+///
+/// Fails with `InvalidInput` when the year is out of range.
+pub fn normalize_metadata(metadata: &AudiobookMetadata) -> Result<AudiobookMetadata> {
+    if let Some(year) = metadata.year {
+        if !(MIN_METADATA_YEAR..=MAX_METADATA_YEAR).contains(&year) {
+            return Err(AppError::InvalidInput(format!(
+                "Invalid year {year}: expected a year from {MIN_METADATA_YEAR} to {MAX_METADATA_YEAR}"
+            )));
+        }
+    }
+    Ok(trim_text_fields(metadata))
+}
+
+/// Returns `metadata` with its text fields trimmed and blank ones unset
+///
+/// Metadata layering runs this on every layer, so a field left blank
+/// gives way to the layers below instead of hiding them.
+pub fn trim_text_fields(metadata: &AudiobookMetadata) -> AudiobookMetadata {
+    let mut normalized = metadata.clone();
+    for field in [
+        &mut normalized.title,
+        &mut normalized.subtitle,
+        &mut normalized.author,
+        &mut normalized.album,
+        &mut normalized.narrator,
+        &mut normalized.genre,
+        &mut normalized.description,
+        &mut normalized.series,
+        &mut normalized.sort_title,
+        &mut normalized.sort_author,
+        &mut normalized.isbn,
+        &mut normalized.asin,
+    ] {
+        *field = field.take().and_then(|text| non_blank(&text));
+    }
+    normalized
+}
+
+fn non_blank(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> AudiobookMetadata {
+        let mut metadata = AudiobookMetadata::new();
+        metadata.title = Some("The Hobbit".to_string());
+        metadata
+    }
+
+    #[test]
+    fn test_trims_text_fields() {
+        let mut input = metadata();
+        input.title = Some("  The Hobbit \t".to_string());
+        input.author = Some("\nJ.R.R. Tolkien ".to_string());
+        input.asin = Some(" B0099SNFE8 ".to_string());
+        let normalized = normalize_metadata(&input).unwrap();
+        assert_eq!(normalized.title.as_deref(), Some("The Hobbit"));
+        assert_eq!(normalized.author.as_deref(), Some("J.R.R. Tolkien"));
+        assert_eq!(normalized.asin.as_deref(), Some("B0099SNFE8"));
+    }
+
+    #[test]
+    fn test_blank_text_fields_become_unset() {
+        let mut input = metadata();
+        input.narrator = Some(String::new());
+        input.genre = Some("   ".to_string());
+        input.description = Some("\n\t".to_string());
+        let normalized = normalize_metadata(&input).unwrap();
+        assert_eq!(normalized.narrator, None);
+        assert_eq!(normalized.genre, None);
+        assert_eq!(normalized.description, None);
+        assert_eq!(normalized.title.as_deref(), Some("The Hobbit"));
+    }
+
+    #[test]
+    fn test_rejects_implausible_years() {
+        for year in [0, MIN_METADATA_YEAR - 1, MAX_METADATA_YEAR + 1] {
+            let mut input = metadata();
+            input.year = Some(year);
+            let error = normalize_metadata(&input).unwrap_err();
+            assert!(matches!(&error, AppError::InvalidInput(message) if message.contains(&year.to_string())));
+        }
+        for year in [MIN_METADATA_YEAR, 1937, MAX_METADATA_YEAR] {
+            let mut input = metadata();
+            input.year = Some(year);
+            assert_eq!(normalize_metadata(&input).unwrap().year, Some(year));
+        }
+    }
+}
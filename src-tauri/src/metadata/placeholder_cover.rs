@@ -0,0 +1,366 @@
+//! Placeholder cover rendering
+//!
+//! Books with no artwork anywhere get a plain square cover: a solid
+//! background whose colour comes from a hash of the title, with the title
+//! and author drawn in a built-in 5×7 pixel font and encoded as a baseline
+//! JPEG. Nothing depends on the clock or system fonts, so the same text
+//! always gives the same bytes.
+//!
+//! The font is upper-case ASCII plus some punctuation. Latin letters with
+//! diacritics are folded to their base letters (`É` → `E`, `ß` → `SS`,
+//! `Æ` → `AE`), typographic quotes and dashes to their ASCII forms; anything
+//! else, such as CJK or Cyrillic, is drawn as `?`.
+
+use crate::audio::constants::{
+    PLACEHOLDER_COVER_JPEG_QUALITY, PLACEHOLDER_COVER_MARGIN, PLACEHOLDER_COVER_SIZE,
+};
+use crate::errors::{AppError, Result};
+use jpeg_encoder::{ColorType, Encoder};
+use sha2::{Digest, Sha256};
+
+/// Glyph width and height in font pixels
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+/// Horizontal advance and line height in font pixels
+const GLYPH_ADVANCE: usize = GLYPH_WIDTH + 1;
+const LINE_ADVANCE: usize = GLYPH_HEIGHT + 3;
+
+/// Title font scales, largest first; the first one the title fits at wins
+const TITLE_SCALES: [usize; 3] = [16, 12, 9];
+
+/// Author font scale and the most author lines drawn
+const AUTHOR_SCALE: usize = 7;
+const AUTHOR_MAX_LINES: usize = 2;
+
+/// Fraction of the cover height, from the top, the title is centred in
+const TITLE_AREA_BOTTOM: f64 = 0.72;
+
+/// Rule between title and author: width and thickness in pixels
+const RULE_WIDTH: usize = 240;
+const RULE_THICKNESS: usize = 8;
+
+/// Text and rule colour
+const TEXT_COLOR: [u8; 3] = [244, 240, 232];
+
+/// Renders a placeholder cover for a book as JPEG bytes
+///
+/// # Errors
+/// `General` if the JPEG encoder rejects the canvas
+pub fn render_placeholder_cover(title: &str, author: Option<&str>) -> Result<Vec<u8>> {
+    let size = PLACEHOLDER_COVER_SIZE as usize;
+    let margin = PLACEHOLDER_COVER_MARGIN as usize;
+    let mut canvas = Canvas::new(size, background_color(title));
+    let text_width = size - 2 * margin;
+
+    let (title_lines, title_scale) = layout_title(title, text_width, size);
+    let title_height = block_height(title_lines.len(), title_scale);
+    let title_area = (size as f64 * TITLE_AREA_BOTTOM) as usize - margin;
+    let title_top = margin + title_area.saturating_sub(title_height) / 2;
+    canvas.draw_lines(&title_lines, title_scale, title_top);
+
+    let author_lines = author.map(str::trim).filter(|a| !a.is_empty())
+        .map(|a| truncate_lines(wrap(a, text_width / (GLYPH_ADVANCE * AUTHOR_SCALE)), AUTHOR_MAX_LINES))
+        .unwrap_or_default();
+    let author_top = size - margin - block_height(author_lines.len(), AUTHOR_SCALE);
+    canvas.draw_lines(&author_lines, AUTHOR_SCALE, author_top);
+
+    let rule_top = (title_top + title_height).max(size * 3 / 4);
+    canvas.fill_rect((size - RULE_WIDTH) / 2, rule_top, RULE_WIDTH, RULE_THICKNESS, TEXT_COLOR);
+
+    canvas.encode_jpeg(PLACEHOLDER_COVER_JPEG_QUALITY)
+}
+
+/// Muted background colour picked from a hash of the title
+fn background_color(title: &str) -> [u8; 3] {
+    let digest = Sha256::digest(title.trim().as_bytes());
+    let hue = f64::from(u16::from_be_bytes([digest[0], digest[1]]) % 360);
+    hsv_to_rgb(hue, 0.45, 0.38)
+}
+
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> [u8; 3] {
+    let chroma = value * saturation;
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let offset = value - chroma;
+    let channel = |c: f64| ((c + offset) * 255.0).round() as u8;
+    [channel(r), channel(g), channel(b)]
+}
+
+/// Title lines and the largest scale they fit at
+fn layout_title(title: &str, text_width: usize, size: usize) -> (Vec<String>, usize) {
+    let title = if title.trim().is_empty() { "Untitled" } else { title };
+    let area = (size as f64 * TITLE_AREA_BOTTOM) as usize - PLACEHOLDER_COVER_MARGIN as usize;
+    let mut fitted = None;
+    for scale in TITLE_SCALES {
+        let lines = wrap(title, text_width / (GLYPH_ADVANCE * scale));
+        let max_lines = area / (LINE_ADVANCE * scale);
+        if lines.len() <= max_lines {
+            return (lines, scale);
+        }
+        fitted = Some((truncate_lines(lines, max_lines), scale));
+    }
+    fitted.unwrap_or_default()
+}
+
+/// Height in pixels of `lines` lines of text, without trailing line gap
+fn block_height(lines: usize, scale: usize) -> usize {
+    if lines == 0 {
+        return 0;
+    }
+    ((lines - 1) * LINE_ADVANCE + GLYPH_HEIGHT) * scale
+}
+
+/// Upper-cases text and word-wraps it to `max_chars` per line
+///
+/// Words longer than a line are split. Characters are folded to ASCII where
+/// they have an obvious equivalent; the rest become `?`.
+fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let word: Vec<char> = word.chars().flat_map(char::to_uppercase)
+            .flat_map(|c| fold_to_ascii(c).map_or_else(|| vec![c], |ascii| ascii.chars().collect()))
+            .map(|c| if glyph(c).is_some() { c } else { '?' })
+            .collect();
+        for piece in word.chunks(max_chars) {
+            let used = line.chars().count();
+            if used > 0 && used + 1 + piece.len() > max_chars {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.extend(piece);
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Keeps the first `max_lines` lines, marking the cut with `...`
+fn truncate_lines(mut lines: Vec<String>, max_lines: usize) -> Vec<String> {
+    if lines.len() <= max_lines || max_lines == 0 {
+        lines.truncate(max_lines);
+        return lines;
+    }
+    lines.truncate(max_lines);
+    if let Some(last) = lines.last_mut() {
+        let width = last.chars().count();
+        let kept: String = last.chars().take(width.saturating_sub(3)).collect();
+        *last = format!("{}...", kept.trim_end());
+    }
+    lines
+}
+
+/// ASCII spelling of an upper-case character the font lacks
+///
+/// Covers Latin-1 and Latin Extended-A letters and common typographic
+/// punctuation; None for anything else.
+fn fold_to_ascii(c: char) -> Option<&'static str> {
+    Some(match c {
+        'À'..='Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+        'Æ' => "AE",
+        'Ç' | 'Ć' | 'Ĉ' | 'Ċ' | 'Č' => "C",
+        'Ð' | 'Ď' | 'Đ' => "D",
+        'È'..='Ë' | 'Ē' | 'Ĕ' | 'Ė' | 'Ę' | 'Ě' => "E",
+        'Ĝ' | 'Ğ' | 'Ġ' | 'Ģ' => "G",
+        'Ĥ' | 'Ħ' => "H",
+        'Ì'..='Ï' | 'Ĩ' | 'Ī' | 'Ĭ' | 'Į' | 'İ' => "I",
+        'Ĳ' => "IJ",
+        'Ĵ' => "J",
+        'Ķ' => "K",
+        'Ĺ' | 'Ļ' | 'Ľ' | 'Ŀ' | 'Ł' => "L",
+        'Ñ' | 'Ń' | 'Ņ' | 'Ň' => "N",
+        'Ò'..='Ö' | 'Ø' | 'Ō' | 'Ŏ' | 'Ő' => "O",
+        'Œ' => "OE",
+        'Ŕ' | 'Ŗ' | 'Ř' => "R",
+        'Ś' | 'Ŝ' | 'Ş' | 'Š' => "S",
+        'ẞ' => "SS",
+        'Ţ' | 'Ť' | 'Ŧ' => "T",
+        'Þ' => "TH",
+        'Ù'..='Ü' | 'Ũ' | 'Ū' | 'Ŭ' | 'Ů' | 'Ű' | 'Ų' => "U",
+        'Ŵ' => "W",
+        'Ý' | 'Ÿ' | 'Ŷ' => "Y",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        '‘' | '‚' | '′' => "'",
+        '“' | '”' | '„' | '«' | '»' | '″' => "\"",
+        '‐' | '‑' | '‒' | '–' | '—' | '―' => "-",
+        '…' => "...",
+        _ => return None,
+    })
+}
+
+/// RGB pixel buffer, one square image
+struct Canvas {
+    size: usize,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Canvas {
+    fn new(size: usize, background: [u8; 3]) -> Self {
+        Self { size, pixels: vec![background; size * size] }
+    }
+
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: [u8; 3]) {
+        for row in y..(y + height).min(self.size) {
+            for col in x..(x + width).min(self.size) {
+                self.pixels[row * self.size + col] = color;
+            }
+        }
+    }
+
+    /// Draws each line horizontally centred, the first line's top at `top`
+    fn draw_lines(&mut self, lines: &[String], scale: usize, top: usize) {
+        for (index, line) in lines.iter().enumerate() {
+            let chars = line.chars().count();
+            let width = (chars * GLYPH_ADVANCE).saturating_sub(1) * scale;
+            let left = self.size.saturating_sub(width) / 2;
+            let y = top + index * LINE_ADVANCE * scale;
+            for (position, c) in line.chars().enumerate() {
+                let x = left + position * GLYPH_ADVANCE * scale;
+                self.draw_glyph(c, x, y, scale);
+            }
+        }
+    }
+
+    /// Encodes the canvas as a baseline JPEG
+    fn encode_jpeg(&self, quality: u8) -> Result<Vec<u8>> {
+        let size = u16::try_from(self.size)
+            .map_err(|_| AppError::General(format!("Placeholder cover too large: {}px", self.size)))?;
+        let data: Vec<u8> = self.pixels.iter().flatten().copied().collect();
+        let mut jpeg = Vec::new();
+        Encoder::new(&mut jpeg, quality).encode(&data, size, size, ColorType::Rgb)
+            .map_err(|e| AppError::General(format!("Failed to encode placeholder cover: {e}")))?;
+        Ok(jpeg)
+    }
+
+    fn draw_glyph(&mut self, c: char, x: usize, y: usize, scale: usize) {
+        let Some(rows) = glyph(c) else { return };
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (0x10 >> col) != 0 {
+                    self.fill_rect(x + col * scale, y + row * scale, scale, scale, TEXT_COLOR);
+                }
+            }
+        }
+    }
+}
+
+/// 5×7 bitmap of a character, one byte per row, high bit on the left
+fn glyph(c: char) -> Option<[u8; 7]> {
+    Some(match c {
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        ';' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '?' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '\'' | '’' => [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '"' => [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '&' => [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::cover_info::{read_image_info, ImageFormat};
+
+    #[test]
+    fn test_renders_square_jpeg() {
+        let cover = render_placeholder_cover("The Long Way Home", Some("A. N. Author")).unwrap();
+        let info = read_image_info(&cover).unwrap();
+        assert_eq!(info.format, ImageFormat::Jpeg);
+        assert_eq!((info.width, info.height), (PLACEHOLDER_COVER_SIZE, PLACEHOLDER_COVER_SIZE));
+        assert!(cover.ends_with(&[0xFF, 0xD9]));
+    }
+
+    #[test]
+    fn test_rendering_is_deterministic() {
+        let first = render_placeholder_cover("Déjà Vu: A Novel", Some("Author")).unwrap();
+        let second = render_placeholder_cover("Déjà Vu: A Novel", Some("Author")).unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, render_placeholder_cover("Another Book", Some("Author")).unwrap());
+    }
+
+    #[test]
+    fn test_long_titles_shrink_then_truncate() {
+        let size = PLACEHOLDER_COVER_SIZE as usize;
+        let width = size - 2 * PLACEHOLDER_COVER_MARGIN as usize;
+        assert_eq!(layout_title("Short", width, size).1, TITLE_SCALES[0]);
+
+        let long = "word ".repeat(200);
+        let (lines, scale) = layout_title(&long, width, size);
+        assert_eq!(scale, TITLE_SCALES[TITLE_SCALES.len() - 1]);
+        assert!(lines.last().unwrap().ends_with("..."));
+    }
+
+    #[test]
+    fn test_wrap_splits_long_words_and_replaces_unknown_characters() {
+        assert_eq!(wrap("abcdefgh ij", 4), vec!["ABCD", "EFGH", "IJ"]);
+        assert_eq!(wrap("née 東京", 10), vec!["NEE ??"]);
+    }
+
+    #[test]
+    fn test_non_ascii_text_folds_to_the_font() {
+        assert_eq!(wrap("Déjà Vu", 20), vec!["DEJA VU"]);
+        assert_eq!(wrap("Straße Æsop Œuvre Łódź", 40), vec!["STRASSE AESOP OEUVRE LODZ"]);
+        assert_eq!(wrap("“Quoted” — l’été…", 40), vec!["\"QUOTED\" - L’ETE..."]);
+        assert_eq!(wrap("Война и мир", 40), vec!["????? ? ???"]);
+    }
+}
@@ -0,0 +1,199 @@
+//! Default metadata profile preference and metadata layering
+//!
+//! Users who narrate or publish their own books fill in the same fields for
+//! every book. The profile holds those defaults and is the lowest layer of
+//! the metadata a run writes: fields given with the request win, then the
+//! book-level tags inherited from the first input, then the profile.
+//! Fields listed as locked in the profile move above the inherited tags,
+//! so a narrator's own name replaces whatever the source files carry, while
+//! still giving way to fields typed in for the book.
+
+use super::identifiers::validate_metadata;
+use super::normalize::trim_text_fields;
+use super::writer::MetadataField;
+use super::AudiobookMetadata;
+use crate::errors::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, OnceLock};
+
+/// Defaults applied to every new book
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataProfile {
+    /// Field values; unset fields add nothing
+    pub metadata: AudiobookMetadata,
+    /// Fields whose profile value wins over tags inherited from the inputs
+    #[serde(default)]
+    pub locked_fields: Vec<MetadataField>,
+}
+
+impl MetadataProfile {
+    /// Whether applying the profile can change anything
+    pub fn is_empty(&self) -> bool {
+        serde_json::to_value(&self.metadata).ok() == serde_json::to_value(AudiobookMetadata::new()).ok()
+    }
+
+    fn is_locked(&self, field: MetadataField) -> bool {
+        self.locked_fields.contains(&field)
+    }
+}
+
+/// Metadata profile chosen in preferences
+static DEFAULT_METADATA_PROFILE: OnceLock<Mutex<MetadataProfile>> = OnceLock::new();
+
+/// Stores the default metadata profile
+///
+/// A malformed ISBN or ASIN in the profile is refused, since it would
+/// otherwise fail every book it is applied to.
+pub fn set_default_metadata_profile(profile: MetadataProfile) -> Result<()> {
+    validate_metadata(&profile.metadata)?;
+    let mut current = DEFAULT_METADATA_PROFILE.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Metadata profile preference poisoned".to_string()))?;
+    *current = profile;
+    Ok(())
+}
+
+/// The metadata profile set in preferences, empty by default
+pub fn default_metadata_profile() -> MetadataProfile {
+    DEFAULT_METADATA_PROFILE.get_or_init(Default::default).lock()
+        .map(|profile| profile.clone())
+        .unwrap_or_default()
+}
+
+/// Layers requested metadata, inherited tags and the profile into one
+///
+/// Every field goes through `pick`; cover art has no lock and keeps the
+/// plain order. Text fields of each layer are trimmed first and blank ones
+/// count as unset, so an empty form field doesn't hide the layers below.
+/// Chapters and tag sources come from the request only; the encoding info
+/// describes a file already encoded, so none is carried over.
+pub fn merge_metadata(
+    requested: Option<&AudiobookMetadata>,
+    inherited: Option<&AudiobookMetadata>,
+    profile: &MetadataProfile,
+) -> AudiobookMetadata {
+    let requested = &requested.map(trim_text_fields).unwrap_or_default();
+    let inherited = &inherited.map(trim_text_fields).unwrap_or_default();
+    let defaults = &trim_text_fields(&profile.metadata);
+    let locked = |field| profile.is_locked(field);
+    AudiobookMetadata {
+        title: pick(&requested.title, &inherited.title, &defaults.title, locked(MetadataField::Title)),
+        subtitle: pick(&requested.subtitle, &inherited.subtitle, &defaults.subtitle, locked(MetadataField::Subtitle)),
+        author: pick(&requested.author, &inherited.author, &defaults.author, locked(MetadataField::Author)),
+        album: pick(&requested.album, &inherited.album, &defaults.album, locked(MetadataField::Album)),
+        narrator: pick(&requested.narrator, &inherited.narrator, &defaults.narrator, locked(MetadataField::Narrator)),
+        year: pick(&requested.year, &inherited.year, &defaults.year, locked(MetadataField::Year)),
+        genre: pick(&requested.genre, &inherited.genre, &defaults.genre, locked(MetadataField::Genre)),
+        description: pick(&requested.description, &inherited.description, &defaults.description, locked(MetadataField::Description)),
+        series: pick(&requested.series, &inherited.series, &defaults.series, locked(MetadataField::Series)),
+        series_index: pick(&requested.series_index, &inherited.series_index, &defaults.series_index, locked(MetadataField::SeriesIndex)),
+        track_number: pick(&requested.track_number, &inherited.track_number, &defaults.track_number, locked(MetadataField::TrackNumber)),
+        track_total: pick(&requested.track_total, &inherited.track_total, &defaults.track_total, locked(MetadataField::TrackTotal)),
+        disc_number: pick(&requested.disc_number, &inherited.disc_number, &defaults.disc_number, locked(MetadataField::DiscNumber)),
+        disc_total: pick(&requested.disc_total, &inherited.disc_total, &defaults.disc_total, locked(MetadataField::DiscTotal)),
+        sort_title: pick(&requested.sort_title, &inherited.sort_title, &defaults.sort_title, locked(MetadataField::SortTitle)),
+        sort_author: pick(&requested.sort_author, &inherited.sort_author, &defaults.sort_author, locked(MetadataField::SortAuthor)),
+        isbn: pick(&requested.isbn, &inherited.isbn, &defaults.isbn, locked(MetadataField::Isbn)),
+        asin: pick(&requested.asin, &inherited.asin, &defaults.asin, locked(MetadataField::Asin)),
+        cover_art: pick(&requested.cover_art, &inherited.cover_art, &defaults.cover_art, false),
+        tag_sources: requested.tag_sources.clone(),
+        chapters: requested.chapters.clone(),
+        encoding_info: None,
+    }
+}
+
+/// Merge precedence for one field
+///
+/// The requested value always wins. Below it, the inherited tag beats the
+/// profile unless the field is locked, which swaps the two.
+fn pick<T: Clone>(requested: &Option<T>, inherited: &Option<T>, profile: &Option<T>, locked: bool) -> Option<T> {
+    let (upper, lower) = if locked { (profile, inherited) } else { (inherited, profile) };
+    requested.as_ref().or(upper.as_ref()).or(lower.as_ref()).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_truth_table() {
+        let value = |set: bool, name: &'static str| set.then_some(name);
+        for bits in 0..16u8 {
+            let (requested, inherited, profile, locked) = (bits & 8 != 0, bits & 4 != 0, bits & 2 != 0, bits & 1 != 0);
+            let expected = match (requested, inherited, profile, locked) {
+                (true, _, _, _) => Some("requested"),
+                (false, _, true, true) => Some("profile"),
+                (false, true, _, _) => Some("inherited"),
+                (false, false, true, false) => Some("profile"),
+                (false, false, false, _) => None,
+            };
+            let picked = pick(
+                &value(requested, "requested"),
+                &value(inherited, "inherited"),
+                &value(profile, "profile"),
+                locked,
+            );
+            assert_eq!(picked, expected, "requested={requested} inherited={inherited} profile={profile} locked={locked}");
+        }
+    }
+
+    #[test]
+    fn test_merge_layers_profile_under_inherited_tags() {
+        let profile = MetadataProfile {
+            metadata: AudiobookMetadata {
+                narrator: Some("Jane Reader".to_string()),
+                genre: Some("Memoir".to_string()),
+                author: Some("Jane Reader".to_string()),
+                ..AudiobookMetadata::new()
+            },
+            locked_fields: vec![MetadataField::Narrator],
+        };
+        let inherited = AudiobookMetadata {
+            title: Some("Track 01".to_string()),
+            narrator: Some("Audio Studio".to_string()),
+            genre: Some("Audiobook".to_string()),
+            ..AudiobookMetadata::new()
+        };
+        let requested = AudiobookMetadata { title: Some("My Life".to_string()), ..AudiobookMetadata::new() };
+
+        let merged = merge_metadata(Some(&requested), Some(&inherited), &profile);
+        assert_eq!(merged.title.as_deref(), Some("My Life"));
+        assert_eq!(merged.narrator.as_deref(), Some("Jane Reader"));
+        assert_eq!(merged.genre.as_deref(), Some("Audiobook"));
+        assert_eq!(merged.author.as_deref(), Some("Jane Reader"));
+
+        let merged = merge_metadata(None, None, &profile);
+        assert_eq!(merged.genre.as_deref(), Some("Memoir"));
+        assert!(!profile.is_empty());
+        assert!(MetadataProfile::default().is_empty());
+    }
+
+    #[test]
+    fn test_blank_requested_fields_give_way_to_lower_layers() {
+        let profile = MetadataProfile {
+            metadata: AudiobookMetadata { narrator: Some("Jane Reader".to_string()), ..AudiobookMetadata::new() },
+            locked_fields: Vec::new(),
+        };
+        let inherited = AudiobookMetadata { genre: Some(" Memoir ".to_string()), ..AudiobookMetadata::new() };
+        let requested = AudiobookMetadata {
+            title: Some(" My Life ".to_string()),
+            narrator: Some(String::new()),
+            genre: Some("  ".to_string()),
+            ..AudiobookMetadata::new()
+        };
+
+        let merged = merge_metadata(Some(&requested), Some(&inherited), &profile);
+        assert_eq!(merged.title.as_deref(), Some("My Life"));
+        assert_eq!(merged.narrator.as_deref(), Some("Jane Reader"));
+        assert_eq!(merged.genre.as_deref(), Some("Memoir"));
+    }
+
+    #[test]
+    fn test_profile_with_malformed_identifier_is_refused() {
+        let profile = MetadataProfile {
+            metadata: AudiobookMetadata { isbn: Some("123".to_string()), ..AudiobookMetadata::new() },
+            locked_fields: Vec::new(),
+        };
+        assert!(matches!(set_default_metadata_profile(profile), Err(AppError::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,158 @@
+//! Encoder settings recorded in the output's tool tag
+//!
+//! The finished book carries a compact note of how it was encoded in the
+//! encoder tag (MP4 `©too`, ID3v2 TSSE), e.g.
+//! `Audiobook Boss 0.1.0 (codec=libfdk_aac; bitrate=64k; profile=aac_low;
+//! sample_rate=22050; channels=1; normalization=none)`.
+//! Reading parses it back. Tags written by other tools, or by versions
+//! that wrote fewer settings, read as far as they go: unknown keys are
+//! skipped and a plain tool string such as `Lavf60.16.100` keeps only the
+//! tool.
+
+use crate::audio::constants::{FFMPEG_AUDIO_CODEC, FFMPEG_AUDIO_PROFILE, PROVENANCE_TOOL_NAME};
+use crate::audio::{AudioSettings, SampleRateConfig};
+use serde::{Deserialize, Serialize};
+
+/// How a file was encoded, as read from its encoder tag
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncodingInfo {
+    /// Application and version, or the whole tag when it isn't ours
+    pub tool: String,
+    /// FFmpeg encoder, e.g. "libfdk_aac"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+    /// Target bitrate in kbps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bitrate_kbps: Option<u32>,
+    /// Encoder profile, e.g. "aac_low"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Output sample rate in Hz
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    /// Output channel count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u8>,
+    /// Loudness normalization applied; "none" when levels were left alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<String>,
+}
+
+impl EncodingInfo {
+    /// What this version of the app encodes with `settings`
+    ///
+    /// `settings` should be resolved, so an `auto` sample rate is known.
+    pub fn for_settings(settings: &AudioSettings) -> Self {
+        Self {
+            tool: format!("{PROVENANCE_TOOL_NAME} {}", env!("CARGO_PKG_VERSION")),
+            codec: Some(FFMPEG_AUDIO_CODEC.to_string()),
+            bitrate_kbps: Some(settings.bitrate),
+            profile: Some(FFMPEG_AUDIO_PROFILE.to_string()),
+            sample_rate: match settings.sample_rate {
+                SampleRateConfig::Explicit(rate) => Some(rate),
+                SampleRateConfig::Auto => None,
+            },
+            channels: Some(settings.channels.channel_count()),
+            normalization: Some("none".to_string()),
+        }
+    }
+
+    /// Tag text for this info; a tool-only info is just the tool
+    pub fn to_tag_text(&self) -> String {
+        let pairs: Vec<String> = [
+            ("codec", self.codec.clone()),
+            ("bitrate", self.bitrate_kbps.map(|kbps| format!("{kbps}k"))),
+            ("profile", self.profile.clone()),
+            ("sample_rate", self.sample_rate.map(|rate| rate.to_string())),
+            ("channels", self.channels.map(|channels| channels.to_string())),
+            ("normalization", self.normalization.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|value| format!("{key}={value}")))
+        .collect();
+        if pairs.is_empty() {
+            self.tool.clone()
+        } else {
+            format!("{} ({})", self.tool, pairs.join("; "))
+        }
+    }
+
+    /// Parses encoder tag text; None for empty text
+    ///
+    /// Settings are read from a trailing `(key=value; ...)` group. Without
+    /// one, or when the group has no `key=value` pair (`iTunes 12.1 (Mac)`),
+    /// the whole text is the tool.
+    pub fn from_tag_text(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        let mut info = Self { tool: text.to_string(), ..Self::default() };
+        let Some((tool, settings)) = text.strip_suffix(')').and_then(|rest| rest.rsplit_once(" (")) else {
+            return Some(info);
+        };
+        let pairs: Vec<(&str, &str)> = settings.split(';')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect();
+        if pairs.is_empty() {
+            return Some(info);
+        }
+        info.tool = tool.trim().to_string();
+        for (key, value) in pairs {
+            match key {
+                "codec" => info.codec = non_empty(value),
+                "bitrate" => info.bitrate_kbps = value.trim_end_matches("kbps").trim_end_matches('k').parse().ok(),
+                "profile" => info.profile = non_empty(value),
+                "sample_rate" => info.sample_rate = value.trim_end_matches("Hz").parse().ok(),
+                "channels" => info.channels = value.parse().ok(),
+                "normalization" => info.normalization = non_empty(value),
+                _ => log::debug!("Ignoring unknown encoder tag setting '{key}'"),
+            }
+        }
+        Some(info)
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::ChannelConfig;
+
+    #[test]
+    fn test_settings_round_trip_through_tag_text() {
+        let mut settings = AudioSettings::default();
+        settings.bitrate = 64;
+        settings.sample_rate = SampleRateConfig::Explicit(22050);
+        settings.channels = ChannelConfig::Mono;
+        let info = EncodingInfo::for_settings(&settings);
+        let text = info.to_tag_text();
+        assert!(text.starts_with(&format!("{PROVENANCE_TOOL_NAME} ")));
+        assert!(text.ends_with("(codec=libfdk_aac; bitrate=64k; profile=aac_low; sample_rate=22050; channels=1; normalization=none)"));
+        assert_eq!(EncodingInfo::from_tag_text(&text), Some(info));
+    }
+
+    #[test]
+    fn test_foreign_and_older_tool_strings() {
+        let lavf = EncodingInfo::from_tag_text("Lavf60.16.100").unwrap();
+        assert_eq!(lavf, EncodingInfo { tool: "Lavf60.16.100".to_string(), ..EncodingInfo::default() });
+        assert_eq!(lavf.to_tag_text(), "Lavf60.16.100");
+
+        let itunes = EncodingInfo::from_tag_text("iTunes 12.1 (Mac)").unwrap();
+        assert_eq!(itunes.tool, "iTunes 12.1 (Mac)");
+        assert_eq!(itunes.codec, None);
+
+        // Fewer settings, a unit suffix, an unknown key and a malformed value
+        let older = EncodingInfo::from_tag_text("Audiobook Boss 0.0.9 (codec=libfdk_aac; bitrate=96kbps; vbr=3; channels=two)").unwrap();
+        assert_eq!(older.tool, "Audiobook Boss 0.0.9");
+        assert_eq!(older.codec.as_deref(), Some("libfdk_aac"));
+        assert_eq!(older.bitrate_kbps, Some(96));
+        assert_eq!((older.channels, older.sample_rate, older.normalization), (None, None, None));
+
+        assert_eq!(EncodingInfo::from_tag_text("  "), None);
+    }
+}
@@ -1,13 +1,24 @@
 //! Metadata reading functionality
 
-use super::AudiobookMetadata;
+use super::id3_chapters::read_id3_chapters;
+use super::provenance::EncodingInfo;
+use super::{AudiobookMetadata, ChapterInfo};
+use crate::audio::constants::{
+    ASIN_TAG_KEY, ISBN_TAG_KEY, MP4_ASIN_ATOM, MP4_ISBN_ATOM, MP4_SERIES_ATOM, MP4_SERIES_PART_ATOM,
+};
 use crate::errors::{AppError, Result};
+use lofty::file::{FileType, TaggedFile};
+use lofty::picture::PictureType;
 use lofty::prelude::{Accessor, ItemKey, TaggedFileExt};
 use lofty::probe::Probe;
-use lofty::tag::Tag;
+use lofty::tag::{Tag, TagType};
+use std::borrow::Cow;
 use std::path::Path;
 
 /// Reads metadata from an audio file
+///
+/// Every tag in the file is read and merged: a field missing or empty in
+/// the primary tag is taken from the next tag that has it.
 pub fn read_metadata<P: AsRef<Path>>(file_path: P) -> Result<AudiobookMetadata> {
     let path = file_path.as_ref();
     
@@ -20,45 +31,291 @@ pub fn read_metadata<P: AsRef<Path>>(file_path: P) -> Result<AudiobookMetadata>
     let tagged_file = Probe::open(path)?
         .read()?;
     
-    let tag = tagged_file.primary_tag()
-        .or_else(|| tagged_file.first_tag());
-    
     let mut metadata = AudiobookMetadata::new();
     
-    if let Some(tag) = tag {
-        extract_tag_data(tag, &mut metadata);
+    for tag in tags_in_merge_order(&tagged_file) {
+        merge_tag_data(tag, &mut metadata);
+        metadata.tag_sources.push(tag_label(tag.tag_type()));
     }
+    metadata.chapters = match tagged_file.file_type() {
+        FileType::Mp4 => read_chapters(path),
+        FileType::Mpeg => read_id3_chapters(path),
+        _ => Vec::new(),
+    };
     
     Ok(metadata)
 }
 
-/// Extracts data from a tag into the metadata struct
-fn extract_tag_data(tag: &Tag, metadata: &mut AudiobookMetadata) {
-    metadata.title = tag.title().map(|s| s.to_string());
-    metadata.author = tag.artist().map(|s| s.to_string());
-    metadata.album = tag.album().map(|s| s.to_string());
-    if let Some(item) = tag.get(&ItemKey::AlbumArtist) {
-        metadata.narrator = Some(item.value().text().unwrap_or("").to_string());
+/// Chapters of an MP4/M4B file, read with ffprobe
+///
+/// Lofty doesn't read MP4 chapter tracks, so ffprobe does. A file without
+/// chapters, or a failed probe, gives an empty list rather than an error.
+pub fn read_chapters(path: &Path) -> Vec<ChapterInfo> {
+    match crate::ffmpeg::probe::probe_raw(path) {
+        Ok(report) => chapters_from_probe(&report),
+        Err(e) => {
+            log::debug!("Cannot read chapters of {}: {e}", path.display());
+            Vec::new()
+        }
+    }
+}
+
+/// Chapters in an ffprobe `-show_chapters` report, ordered by start time
+fn chapters_from_probe(report: &serde_json::Value) -> Vec<ChapterInfo> {
+    let millis = |value: &serde_json::Value| value.as_str()
+        .and_then(|secs| secs.parse::<f64>().ok())
+        .filter(|secs| secs.is_finite())
+        .map(|secs| (secs.max(0.0) * 1000.0).round() as u64);
+    let mut chapters: Vec<ChapterInfo> = report["chapters"].as_array()
+        .map(|chapters| chapters.iter().filter_map(|chapter| {
+            let start_ms = millis(&chapter["start_time"])?;
+            Some(ChapterInfo {
+                title: chapter["tags"]["title"].as_str().unwrap_or_default().to_string(),
+                start_ms,
+                end_ms: millis(&chapter["end_time"]).unwrap_or(start_ms),
+            })
+        }).collect())
+        .unwrap_or_default();
+    order_chapters(&mut chapters);
+    chapters
+}
+
+/// Sorts chapters by start time and names untitled ones "Chapter N" by position
+pub fn order_chapters(chapters: &mut [ChapterInfo]) {
+    chapters.sort_by_key(|chapter| chapter.start_ms);
+    for (index, chapter) in chapters.iter_mut().enumerate() {
+        if chapter.title.trim().is_empty() {
+            chapter.title = format!("Chapter {}", index + 1);
+        }
+    }
+}
+
+/// Primary tag first, then the others in file order, with ID3v1 last
+/// because its fields are truncated to 30 bytes
+fn tags_in_merge_order(tagged_file: &TaggedFile) -> Vec<&Tag> {
+    let primary = tagged_file.primary_tag_type();
+    let mut tags: Vec<&Tag> = tagged_file.tags().iter().collect();
+    tags.sort_by_key(|tag| (tag.tag_type() != primary, tag.tag_type() == TagType::Id3v1));
+    tags
+}
+
+/// Fills fields still missing in `metadata` from `tag`; empty values count as missing
+pub fn merge_tag_data(tag: &Tag, metadata: &mut AudiobookMetadata) {
+    fill(&mut metadata.title, tag.title());
+    fill(&mut metadata.subtitle, tag.get_string(&ItemKey::TrackSubtitle).map(Cow::Borrowed));
+    fill(&mut metadata.author, tag.artist());
+    fill(&mut metadata.album, tag.album());
+    fill(&mut metadata.narrator, tag.get_string(&ItemKey::AlbumArtist).map(Cow::Borrowed));
+    if metadata.year.is_none() {
+        metadata.year = tag.year().filter(|&year| year > 0);
     }
-    metadata.year = tag.year();
-    metadata.genre = tag.genre().map(|s| s.to_string());
+    fill(&mut metadata.genre, tag.genre());
     
-    // Extract description from comment
-    metadata.description = tag.comment().map(|s| s.to_string());
+    fill(&mut metadata.description, description(tag));
+    fill(&mut metadata.series, series_text(tag, MP4_SERIES_ATOM, ItemKey::Movement).map(Cow::Borrowed));
+    if metadata.series_index.is_none() {
+        metadata.series_index = series_text(tag, MP4_SERIES_PART_ATOM, ItemKey::MovementNumber)
+            .and_then(parse_series_index);
+    }
+    fill_number(&mut metadata.track_number, tag.track());
+    fill_number(&mut metadata.track_total, tag.track_total());
+    fill_number(&mut metadata.disc_number, tag.disk());
+    fill_number(&mut metadata.disc_total, tag.disk_total());
+    fill(&mut metadata.sort_title, tag.get_string(&ItemKey::TrackTitleSortOrder).map(Cow::Borrowed));
+    fill(&mut metadata.sort_author, tag.get_string(&ItemKey::TrackArtistSortOrder).map(Cow::Borrowed));
+    fill(&mut metadata.isbn, identifier_text(tag, MP4_ISBN_ATOM, ISBN_TAG_KEY).map(Cow::Borrowed));
+    fill(&mut metadata.asin, identifier_text(tag, MP4_ASIN_ATOM, ASIN_TAG_KEY).map(Cow::Borrowed));
+    if metadata.encoding_info.is_none() {
+        // Lofty reads an ID3v2 TSSE frame back as encoder settings, though it writes software there
+        metadata.encoding_info = tag.get_string(&ItemKey::EncoderSoftware)
+            .or_else(|| tag.get_string(&ItemKey::EncoderSettings))
+            .and_then(EncodingInfo::from_tag_text);
+    }
     
-    // Extract cover art
-    let pictures = tag.pictures();
-    if let Some(picture) = pictures.first() {
-        metadata.cover_art = Some(picture.data().to_vec());
+    // Extract cover art, preferring the front cover over other pictures
+    if metadata.cover_art.is_none() {
+        let pictures = tag.pictures();
+        metadata.cover_art = pictures.iter().find(|picture| picture.pic_type() == PictureType::CoverFront)
+            .or(pictures.first())
+            .map(|picture| picture.data().to_vec());
+    }
+}
+
+/// Description from the comment or, when longer, the long-description field
+///
+/// Long descriptions are written as a truncated comment plus the full text
+/// in the MP4 long description (ldes) or an ID3v2 USLT frame. USLT only
+/// counts when it continues the comment, so real lyrics are not mistaken
+/// for a description.
+fn description(tag: &Tag) -> Option<Cow<'_, str>> {
+    let comment = tag.comment();
+    let long = tag.get_string(&ItemKey::PodcastDescription).or_else(|| {
+        tag.get_string(&ItemKey::Lyrics)
+            .filter(|lyrics| comment.as_deref().is_some_and(|comment| lyrics.starts_with(comment)))
+    });
+    match (comment, long) {
+        (Some(comment), Some(long)) if comment.len() >= long.len() => Some(comment),
+        (comment, long) => long.map(Cow::Borrowed).or(comment),
+    }
+}
+
+/// Series value from the freeform MP4 atom, or else the movement tag
+fn series_text<'a>(tag: &'a Tag, freeform: &str, movement: ItemKey) -> Option<&'a str> {
+    tag.get_string(&ItemKey::Unknown(freeform.to_string()))
+        .or_else(|| tag.get_string(&movement))
+}
+
+/// Identifier from the freeform MP4 atom, or else the plain key other formats use
+fn identifier_text<'a>(tag: &'a Tag, freeform: &str, key: &str) -> Option<&'a str> {
+    tag.get_string(&ItemKey::Unknown(freeform.to_string()))
+        .or_else(|| tag.get_string(&ItemKey::Unknown(key.to_string())))
+}
+
+/// Series position from tag text such as `1.5` or a movement number `2/7`
+fn parse_series_index(text: &str) -> Option<f32> {
+    let number = text.split('/').next()?.trim();
+    number.parse::<f32>().ok().filter(|index| index.is_finite() && *index >= 0.0)
+}
+
+fn fill(field: &mut Option<String>, value: Option<Cow<'_, str>>) {
+    if field.is_none() {
+        *field = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    }
+}
+
+/// Fills a missing track or disc number; zero counts as missing
+fn fill_number(field: &mut Option<u32>, value: Option<u32>) {
+    if field.is_none() {
+        *field = value.filter(|&number| number > 0);
+    }
+}
+
+/// Display name of a tag format
+fn tag_label(tag_type: TagType) -> String {
+    match tag_type {
+        TagType::Id3v1 => "ID3v1".to_string(),
+        TagType::Id3v2 => "ID3v2".to_string(),
+        TagType::Ape => "APE".to_string(),
+        TagType::Mp4Ilst => "MP4".to_string(),
+        TagType::VorbisComments => "Vorbis".to_string(),
+        TagType::RiffInfo => "RIFF INFO".to_string(),
+        TagType::AiffText => "AIFF".to_string(),
+        other => format!("{other:?}"),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use lofty::config::WriteOptions;
+    use lofty::id3::v2::Id3v2Tag;
+    use lofty::prelude::TagExt;
     use std::fs;
     use tempfile::TempDir;
 
+    /// Silent MPEG-1 Layer III frames (128 kbps, 44.1 kHz)
+    fn mp3_frames() -> Vec<u8> {
+        let mut frame = vec![0u8; 417];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        frame.repeat(8)
+    }
+
+    /// 128-byte ID3v1 footer
+    fn id3v1_footer(title: &str, artist: &str, album: &str, year: &str) -> Vec<u8> {
+        let field = |text: &str, len: usize| {
+            let mut bytes = text.as_bytes().to_vec();
+            bytes.resize(len, 0);
+            bytes
+        };
+        let mut footer = b"TAG".to_vec();
+        footer.extend(field(title, 30));
+        footer.extend(field(artist, 30));
+        footer.extend(field(album, 30));
+        footer.extend(field(year, 4));
+        footer.extend(field("", 30));
+        footer.push(255);
+        footer
+    }
+
+    fn write_id3v2(path: &Path, title: &str) {
+        let mut tag = Id3v2Tag::default();
+        tag.set_title(title.to_string());
+        tag.save_to_path(path, WriteOptions::default()).unwrap();
+    }
+
+    #[test]
+    fn test_read_id3v1_only_mp3() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("old_rip.mp3");
+        let mut data = mp3_frames();
+        data.extend(id3v1_footer("Dune", "Frank Herbert", "Dune Saga", "1965"));
+        fs::write(&file_path, data).unwrap();
+
+        let metadata = read_metadata(&file_path).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Dune"));
+        assert_eq!(metadata.author.as_deref(), Some("Frank Herbert"));
+        assert_eq!(metadata.album.as_deref(), Some("Dune Saga"));
+        assert_eq!(metadata.year, Some(1965));
+        assert_eq!(metadata.tag_sources, ["ID3v1"]);
+    }
+
+    #[test]
+    fn test_id3v1_fills_fields_missing_from_id3v2() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("both.mp3");
+        let mut data = mp3_frames();
+        data.extend(id3v1_footer("Dune (v1)", "Frank Herbert", "", ""));
+        fs::write(&file_path, data).unwrap();
+        write_id3v2(&file_path, "Dune");
+
+        let metadata = read_metadata(&file_path).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Dune"));
+        assert_eq!(metadata.author.as_deref(), Some("Frank Herbert"));
+        assert_eq!(metadata.album, None);
+        assert_eq!(metadata.tag_sources, ["ID3v2", "ID3v1"]);
+    }
+
+    #[test]
+    fn test_read_id3v2_only_mp3() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("modern.mp3");
+        fs::write(&file_path, mp3_frames()).unwrap();
+        write_id3v2(&file_path, "Dune");
+
+        let metadata = read_metadata(&file_path).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Dune"));
+        assert_eq!(metadata.author, None);
+        assert_eq!(metadata.tag_sources, ["ID3v2"]);
+    }
+
+    #[test]
+    fn test_chapters_from_probe_sorted_by_start() {
+        let report = serde_json::json!({"chapters": [
+            {"start_time": "61.500000", "end_time": "120.000000", "tags": {"title": "Part Two"}},
+            {"start_time": "0.000000", "end_time": "61.500000", "tags": {"title": "Part One"}},
+            {"start_time": "120.000000", "end_time": "130.250000"},
+            {"start_time": "N/A", "end_time": "1.0"}
+        ]});
+        let chapters = chapters_from_probe(&report);
+        assert_eq!(chapters, [
+            ChapterInfo { title: "Part One".to_string(), start_ms: 0, end_ms: 61_500 },
+            ChapterInfo { title: "Part Two".to_string(), start_ms: 61_500, end_ms: 120_000 },
+            ChapterInfo { title: "Chapter 3".to_string(), start_ms: 120_000, end_ms: 130_250 },
+        ]);
+        assert!(chapters_from_probe(&serde_json::json!({"chapters": []})).is_empty());
+        assert!(chapters_from_probe(&serde_json::json!({})).is_empty());
+        assert_eq!(serde_json::to_value(&chapters[0]).unwrap()["startMs"], 0);
+    }
+
+    #[test]
+    fn test_mp3_has_no_chapters() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("plain.mp3");
+        fs::write(&file_path, mp3_frames()).unwrap();
+        write_id3v2(&file_path, "Dune");
+        assert!(read_metadata(&file_path).unwrap().chapters.is_empty());
+    }
+
     #[test]
     fn test_read_nonexistent_file() {
         let result = read_metadata("nonexistent.m4b");
@@ -74,4 +331,20 @@ mod tests {
         let result = read_metadata(&file_path);
         assert!(matches!(result, Err(AppError::Metadata(_))));
     }
+
+    #[test]
+    fn test_series_from_movement_tags() {
+        let mut tag = Tag::new(TagType::Id3v2);
+        tag.insert_text(ItemKey::Movement, "Discworld".to_string());
+        tag.insert_text(ItemKey::MovementNumber, "4/41".to_string());
+        tag.insert_text(ItemKey::TrackSubtitle, "A Discworld Novel".to_string());
+        let mut metadata = AudiobookMetadata::new();
+        merge_tag_data(&tag, &mut metadata);
+        assert_eq!(metadata.series.as_deref(), Some("Discworld"));
+        assert_eq!(metadata.series_index, Some(4.0));
+        assert_eq!(metadata.subtitle.as_deref(), Some("A Discworld Novel"));
+        assert_eq!(parse_series_index("1.5"), Some(1.5));
+        assert_eq!(parse_series_index("n/a"), None);
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,38 @@
+//! Text cleanup shared by metadata fields
+
+/// Normalizes whitespace in free text such as descriptions
+///
+/// Runs of spaces and tabs collapse to one space, lines are trimmed,
+/// Windows line endings become `\n` and blank-line runs shrink to a single
+/// paragraph break.
+pub fn normalize_whitespace(text: &str) -> String {
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(current.join("\n"));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current.join("\n"));
+    }
+    paragraphs.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_whitespace() {
+        let text = "  First \t line\r\nsecond   line\r\n\r\n\r\n\n  Next paragraph  \n\n";
+        assert_eq!(normalize_whitespace(text), "First line\nsecond line\n\nNext paragraph");
+        assert_eq!(normalize_whitespace(" \n\t\n"), "");
+    }
+}
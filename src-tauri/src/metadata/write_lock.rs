@@ -0,0 +1,114 @@
+//! Per-path locking for tag writes
+//!
+//! Lofty rewrites the whole tag on save, so two writes to the same file
+//! racing each other can interleave and corrupt it. Writers hold the lock
+//! for a file's canonical path for the duration of their read-modify-save
+//! cycle; writes to different files still run in parallel.
+
+use crate::errors::{AppError, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+/// Registry of live per-path locks, keyed by canonical path
+type LockRegistry = Mutex<HashMap<PathBuf, Weak<Mutex<()>>>>;
+
+static REGISTRY: OnceLock<LockRegistry> = OnceLock::new();
+
+/// Runs `operation` while holding the write lock for `path`
+///
+/// Callers for the same file queue up behind each other and each one
+/// re-reads the file inside `operation`, so no write is based on a stale tag.
+pub fn with_path_lock<T, F>(path: &Path, operation: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    let lock = lock_for(path)?;
+    let _guard = lock.lock()
+        .map_err(|_| AppError::General(format!(
+            "Metadata write lock poisoned for: {}", path.display()
+        )))?;
+    operation()
+}
+
+/// Returns the shared lock for a path, creating it if needed
+fn lock_for(path: &Path) -> Result<Arc<Mutex<()>>> {
+    let key = lock_key(path);
+    let mut registry = REGISTRY.get_or_init(Default::default).lock()
+        .map_err(|_| AppError::General("Metadata lock registry poisoned".to_string()))?;
+
+    // Drop entries whose writers have all finished
+    registry.retain(|_, lock| lock.strong_count() > 0);
+
+    if let Some(existing) = registry.get(&key).and_then(Weak::upgrade) {
+        return Ok(existing);
+    }
+    let lock = Arc::new(Mutex::new(()));
+    registry.insert(key, Arc::downgrade(&lock));
+    Ok(lock)
+}
+
+/// Canonical path used as the registry key, falling back to the raw path
+fn lock_key(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_same_path_serializes() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("book.m4b");
+        std::fs::write(&file, b"data").unwrap();
+
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_active = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..8).map(|_| {
+            let file = file.clone();
+            let active = Arc::clone(&active);
+            let max_active = Arc::clone(&max_active);
+            thread::spawn(move || {
+                with_path_lock(&file, || {
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_active.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(5));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                })
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+        assert_eq!(max_active.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_different_paths_get_different_locks() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("a.m4b");
+        let second = temp_dir.path().join("b.m4b");
+        std::fs::write(&first, b"a").unwrap();
+        std::fs::write(&second, b"b").unwrap();
+
+        let lock_a = lock_for(&first).unwrap();
+        let lock_b = lock_for(&second).unwrap();
+        assert!(!Arc::ptr_eq(&lock_a, &lock_b));
+        assert!(Arc::ptr_eq(&lock_a, &lock_for(&first).unwrap()));
+    }
+
+    #[test]
+    fn test_operation_error_is_returned() {
+        let result: Result<()> = with_path_lock(Path::new("missing.m4b"), || {
+            Err(AppError::InvalidInput("boom".to_string()))
+        });
+        assert!(result.unwrap_err().to_string().contains("boom"));
+    }
+}
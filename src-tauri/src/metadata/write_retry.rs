@@ -0,0 +1,136 @@
+//! Retrying tag writes on briefly busy files
+//!
+//! Right after an M4B is created, Spotlight, antivirus scanners or sync
+//! clients often open it for a moment. A write landing in that window fails
+//! with a sharing violation even though the same write succeeds shortly
+//! after, so busy errors get a few spaced-out retries.
+
+use super::writer::{write_cover_art, write_metadata_with, MetadataWriteOptions, MetadataWriteReport};
+use super::AudiobookMetadata;
+use crate::audio::constants::{METADATA_RETRY_DELAY_MS, METADATA_WRITE_ATTEMPTS};
+use crate::errors::{AppError, Result};
+use std::path::Path;
+use std::time::Duration;
+
+/// Whether an error means another process briefly holds the file
+pub fn is_busy_error(error: &AppError) -> bool {
+    match error {
+        AppError::FileInUse(_) => true,
+        AppError::Io(e) => is_busy_io(e),
+        AppError::Metadata(e) => matches!(e.kind(), lofty::error::ErrorKind::Io(io) if is_busy_io(io)),
+        _ => false,
+    }
+}
+
+fn is_busy_io(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    #[cfg(windows)]
+    {
+        const ERROR_SHARING_VIOLATION: i32 = 32;
+        const ERROR_LOCK_VIOLATION: i32 = 33;
+        if matches!(error.raw_os_error(), Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)) {
+            return true;
+        }
+    }
+    matches!(error.kind(), ErrorKind::ResourceBusy | ErrorKind::ExecutableFileBusy | ErrorKind::WouldBlock)
+}
+
+/// Runs `operation`, retrying busy errors with an increasing delay
+///
+/// Makes at most `METADATA_WRITE_ATTEMPTS` attempts; any other error is
+/// returned immediately. `sleep` is injectable for tests.
+pub fn with_busy_retry<T, F, S>(mut operation: F, mut sleep: S) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+    S: FnMut(Duration),
+{
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Err(e) if attempt < METADATA_WRITE_ATTEMPTS && is_busy_error(&e) => {
+                let delay = Duration::from_millis(METADATA_RETRY_DELAY_MS * u64::from(attempt));
+                log::warn!("File busy on attempt {attempt}/{METADATA_WRITE_ATTEMPTS}, retrying in {delay:?}: {e}");
+                sleep(delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Writes metadata and its cover art (if any), retrying while the file is busy
+///
+/// The report lists fields the container could not hold, unless `options`
+/// is strict, in which case they fail the write.
+pub fn write_tags_with_retry<P: AsRef<Path>>(
+    path: P,
+    metadata: &AudiobookMetadata,
+    options: MetadataWriteOptions,
+) -> Result<MetadataWriteReport> {
+    let path = path.as_ref();
+    with_busy_retry(|| write_tags(path, metadata, options), std::thread::sleep)
+}
+
+/// The cover goes on after the other tags; one already embedded is not added again
+fn write_tags(path: &Path, metadata: &AudiobookMetadata, options: MetadataWriteOptions) -> Result<MetadataWriteReport> {
+    let report = write_metadata_with(path, metadata, options)?;
+    if let Some(cover) = &metadata.cover_art {
+        write_cover_art(path, cover)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn busy() -> AppError {
+        AppError::Io(std::io::Error::from(std::io::ErrorKind::ResourceBusy))
+    }
+
+    #[test]
+    fn test_busy_errors() {
+        assert!(is_busy_error(&busy()));
+        assert!(is_busy_error(&AppError::FileInUse("book.m4b".to_string())));
+        let lofty_busy = lofty::error::LoftyError::new(lofty::error::ErrorKind::Io(
+            std::io::Error::from(std::io::ErrorKind::ResourceBusy),
+        ));
+        assert!(is_busy_error(&AppError::Metadata(lofty_busy)));
+        assert!(!is_busy_error(&AppError::Io(std::io::Error::from(std::io::ErrorKind::NotFound))));
+        assert!(!is_busy_error(&AppError::General("busy".to_string())));
+    }
+
+    #[test]
+    fn test_writer_failing_twice_then_succeeding() {
+        let mut calls = 0;
+        let mut delays = Vec::new();
+        let result = with_busy_retry(
+            || {
+                calls += 1;
+                if calls <= 2 { Err(busy()) } else { Ok(calls) }
+            },
+            |delay| delays.push(delay),
+        );
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(delays, [Duration::from_millis(METADATA_RETRY_DELAY_MS), Duration::from_millis(2 * METADATA_RETRY_DELAY_MS)]);
+    }
+
+    #[test]
+    fn test_gives_up_after_max_attempts() {
+        let mut calls = 0;
+        let result: Result<()> = with_busy_retry(|| { calls += 1; Err(busy()) }, |_| {});
+        assert!(is_busy_error(&result.unwrap_err()));
+        assert_eq!(calls, METADATA_WRITE_ATTEMPTS);
+    }
+
+    #[test]
+    fn test_other_errors_are_not_retried() {
+        let mut calls = 0;
+        let result: Result<()> = with_busy_retry(
+            || { calls += 1; Err(AppError::FileValidation("missing".to_string())) },
+            |_| panic!("should not sleep"),
+        );
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,215 @@
+//! Fields whose tag items depend on the container
+
+use super::fields::{text_item, TagWriter};
+use super::{DescriptionField, MetadataField, MetadataWriteReport};
+use crate::audio::constants::{
+    ASIN_TAG_KEY, DESCRIPTION_ID3V2_COMMENT_MAX_CHARS, DESCRIPTION_MP4_COMMENT_MAX_CHARS, ISBN_TAG_KEY,
+    MP4_ASIN_ATOM, MP4_ISBN_ATOM, MP4_SERIES_ATOM, MP4_SERIES_PART_ATOM,
+};
+use crate::metadata::AudiobookMetadata;
+use lofty::prelude::ItemKey;
+use lofty::tag::TagType;
+
+/// How long a comment the container's players read, and where the full text goes
+fn long_description_rule(tag_type: TagType) -> Option<(usize, ItemKey, DescriptionField)> {
+    match tag_type {
+        TagType::Mp4Ilst => Some((DESCRIPTION_MP4_COMMENT_MAX_CHARS, ItemKey::PodcastDescription, DescriptionField::LongDescription)),
+        TagType::Id3v2 => Some((DESCRIPTION_ID3V2_COMMENT_MAX_CHARS, ItemKey::Lyrics, DescriptionField::Lyrics)),
+        _ => None,
+    }
+}
+
+/// Writes the description to the comment, splitting overflow into the
+/// container's long-description field
+pub(super) fn write_description<T: TagWriter + ?Sized>(tag: &mut T, description: &str, report: &mut MetadataWriteReport) {
+    let rule = long_description_rule(tag.tag_type())
+        .filter(|(max_chars, _, _)| description.chars().count() > *max_chars);
+    let comment = match rule {
+        Some((max_chars, _, _)) => description.chars().take(max_chars).collect(),
+        None => description.to_string(),
+    };
+    if !tag.insert_item(text_item(ItemKey::Comment, comment)) {
+        report.failed_fields.push(MetadataField::Description);
+        return;
+    }
+    report.description_fields.push(DescriptionField::Comment);
+    if let Some((_, long_key, long_field)) = rule {
+        report.description_truncated = true;
+        if tag.insert_item(text_item(long_key, description.to_string())) {
+            report.description_fields.push(long_field);
+        } else {
+            report.failed_fields.push(MetadataField::Description);
+        }
+    }
+}
+
+/// Writes series name and position
+///
+/// MP4 gets the freeform `SERIES` and `SERIES-PART` atoms plus the movement
+/// name. The movement number is left out there, since players expect an
+/// integer atom and the position may be fractional. Other formats get the
+/// movement name and number.
+pub(super) fn write_series<T: TagWriter + ?Sized>(tag: &mut T, metadata: &AudiobookMetadata, report: &mut MetadataWriteReport) {
+    let mp4 = tag.tag_type() == TagType::Mp4Ilst;
+    let index = metadata.series_index.map(|index| index.to_string());
+    let freeform = |atom: &str| ItemKey::Unknown(atom.to_string());
+    let fields = [
+        (MetadataField::Series, &metadata.series, freeform(MP4_SERIES_ATOM), Some(ItemKey::Movement)),
+        (MetadataField::SeriesIndex, &index, freeform(MP4_SERIES_PART_ATOM), (!mp4).then_some(ItemKey::MovementNumber)),
+    ];
+    for (field, value, atom, movement) in fields {
+        let Some(value) = value else {
+            continue;
+        };
+        let mut written = mp4 && tag.insert_item(text_item(atom, value.clone()));
+        if let Some(key) = movement {
+            written |= tag.insert_item(text_item(key, value.clone()));
+        }
+        if !written {
+            report.failed_fields.push(field);
+        }
+    }
+}
+
+/// Writes the ISBN and ASIN
+///
+/// MP4 gets the freeform iTunes atoms; other formats get plain `ISBN` and
+/// `ASIN` keys where they allow free-form keys, and report the field as
+/// not written otherwise.
+pub(super) fn write_identifiers<T: TagWriter + ?Sized>(tag: &mut T, metadata: &AudiobookMetadata, report: &mut MetadataWriteReport) {
+    let mp4 = tag.tag_type() == TagType::Mp4Ilst;
+    let fields = [
+        (MetadataField::Isbn, &metadata.isbn, MP4_ISBN_ATOM, ISBN_TAG_KEY),
+        (MetadataField::Asin, &metadata.asin, MP4_ASIN_ATOM, ASIN_TAG_KEY),
+    ];
+    for (field, value, atom, key) in fields {
+        let Some(value) = value else {
+            continue;
+        };
+        let key = if mp4 { atom } else { key };
+        if !tag.insert_item(text_item(ItemKey::Unknown(key.to_string()), value.clone())) {
+            report.failed_fields.push(field);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::AppError;
+    use crate::metadata::writer::fields::update_tag_data;
+    use crate::metadata::writer::fixtures::{copy_test_media, tagged_m4b};
+    use crate::metadata::writer::{write_metadata, MetadataWriteOptions};
+    use lofty::prelude::{Accessor, TaggedFileExt};
+    use lofty::probe::Probe;
+    use lofty::tag::Tag;
+    use tempfile::TempDir;
+    use std::fs;
+
+    fn long_description() -> String {
+        (0..5000).map(|i| char::from(b'a' + (i % 26) as u8)).collect()
+    }
+
+    #[test]
+    fn test_long_description_round_trip_mp3() {
+        let temp_dir = TempDir::new().unwrap();
+        let Some(file_path) = copy_test_media(&temp_dir, "description.mp3") else { return };
+        let description = long_description();
+        let mut metadata = AudiobookMetadata::new();
+        metadata.description = Some(description.clone());
+
+        let report = write_metadata(&file_path, &metadata).unwrap();
+        assert_eq!(report.description_fields, [DescriptionField::Comment, DescriptionField::Lyrics]);
+        assert!(report.description_truncated);
+        let tagged_file = Probe::open(&file_path).unwrap().read().unwrap();
+        let tag = tagged_file.primary_tag().unwrap();
+        assert_eq!(tag.comment().unwrap().chars().count(), DESCRIPTION_ID3V2_COMMENT_MAX_CHARS);
+        assert_eq!(crate::metadata::read_metadata(&file_path).unwrap().description, Some(description));
+
+        metadata.description = Some("Short blurb".to_string());
+        let report = write_metadata(&file_path, &metadata).unwrap();
+        assert_eq!(report.description_fields, [DescriptionField::Comment]);
+        assert!(!report.description_truncated);
+        assert_eq!(crate::metadata::read_metadata(&file_path).unwrap().description.as_deref(), Some("Short blurb"));
+    }
+
+    #[test]
+    fn test_long_description_round_trip_mp4() {
+        use lofty::mp4::Ilst;
+
+        let description = long_description();
+        let mut metadata = AudiobookMetadata::new();
+        metadata.description = Some(description.clone());
+        let mut tag = Tag::new(TagType::Mp4Ilst);
+        let report = update_tag_data(&mut tag, &metadata, MetadataWriteOptions::default()).unwrap();
+        assert_eq!(report.description_fields, [DescriptionField::Comment, DescriptionField::LongDescription]);
+
+        // Through the ilst atoms and back, as a save and read would
+        let ilst = Ilst::from(tag);
+        assert!(ilst.get(&lofty::mp4::AtomIdent::Fourcc(*b"ldes")).is_some());
+        let tag = Tag::from(ilst);
+        assert_eq!(tag.comment().unwrap().chars().count(), DESCRIPTION_MP4_COMMENT_MAX_CHARS);
+        let mut read = AudiobookMetadata::new();
+        crate::metadata::reader::merge_tag_data(&tag, &mut read);
+        assert_eq!(read.description, Some(description));
+    }
+
+    #[test]
+    fn test_series_and_subtitle_round_trip_m4b() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = tagged_m4b(&temp_dir);
+        let mut metadata = AudiobookMetadata::new();
+        metadata.title = Some("The Vital Abyss".to_string());
+        metadata.subtitle = Some("An Expanse Novella".to_string());
+        metadata.series = Some("The Expanse".to_string());
+        metadata.series_index = Some(5.5);
+
+        let report = write_metadata(&file_path, &metadata).unwrap();
+        assert!(report.failed_fields.is_empty());
+        let result = crate::metadata::read_metadata(&file_path).unwrap();
+        assert_eq!(result.title, metadata.title);
+        assert_eq!(result.subtitle, metadata.subtitle);
+        assert_eq!(result.series, metadata.series);
+        assert_eq!(result.series_index, Some(5.5));
+
+        let tagged_file = Probe::open(&file_path).unwrap().read().unwrap();
+        let tag = tagged_file.primary_tag().unwrap();
+        let freeform = |atom: &str| tag.get_string(&ItemKey::Unknown(atom.to_string())).map(str::to_string);
+        assert_eq!(freeform(MP4_SERIES_ATOM).as_deref(), Some("The Expanse"));
+        assert_eq!(freeform(MP4_SERIES_PART_ATOM).as_deref(), Some("5.5"));
+        assert_eq!(tag.get_string(&ItemKey::Movement), Some("The Expanse"));
+
+        metadata.series_index = Some(2.0);
+        write_metadata(&file_path, &metadata).unwrap();
+        assert_eq!(crate::metadata::read_metadata(&file_path).unwrap().series_index, Some(2.0));
+    }
+
+    #[test]
+    fn test_identifiers_round_trip_m4b() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = tagged_m4b(&temp_dir);
+        let untagged = crate::metadata::read_metadata(&file_path).unwrap();
+        assert_eq!((untagged.isbn, untagged.asin), (None, None));
+
+        let metadata = AudiobookMetadata {
+            title: Some("Dune".to_string()),
+            isbn: Some("978-0-441-01359-3".to_string()),
+            asin: Some("B002V1OF70".to_string()),
+            ..AudiobookMetadata::new()
+        };
+        assert!(write_metadata(&file_path, &metadata).unwrap().failed_fields.is_empty());
+        let result = crate::metadata::read_metadata(&file_path).unwrap();
+        assert_eq!(result.isbn, metadata.isbn);
+        assert_eq!(result.asin, metadata.asin);
+        let tagged_file = Probe::open(&file_path).unwrap().read().unwrap();
+        let tag = tagged_file.primary_tag().unwrap();
+        assert_eq!(tag.get_string(&ItemKey::Unknown(MP4_ISBN_ATOM.to_string())), Some("978-0-441-01359-3"));
+        assert_eq!(tag.get_string(&ItemKey::Unknown(MP4_ASIN_ATOM.to_string())), Some("B002V1OF70"));
+
+        let before = fs::read(&file_path).unwrap();
+        let invalid = AudiobookMetadata { isbn: Some("978-0-441-01359-4".to_string()), ..metadata };
+        let error = write_metadata(&file_path, &invalid).unwrap_err();
+        assert!(matches!(&error, AppError::InvalidInput(message) if message.contains("isbn")));
+        assert_eq!(fs::read(&file_path).unwrap(), before);
+    }
+}
@@ -0,0 +1,217 @@
+//! Cover art writing
+
+use super::{is_m4b, write_audiobook_atoms};
+use crate::errors::{AppError, Result};
+use crate::metadata::cover_convert::embeddable_cover;
+use crate::metadata::file_in_use::ensure_not_in_use;
+use crate::metadata::write_lock::with_path_lock;
+use lofty::file::{AudioFile, TaggedFileExt};
+use lofty::picture::{Picture, PictureType};
+use lofty::probe::Probe;
+use lofty::tag::TagType;
+use std::path::Path;
+
+/// Writes cover art to an M4B file
+///
+/// PNG and WebP covers are converted to JPEG first (see `cover_convert`).
+/// The new cover replaces the front cover; other pictures are kept. MP4
+/// pictures carry no type, so there every picture counts as the cover.
+/// Writing the cover already embedded leaves the file as it is.
+pub fn write_cover_art<P: AsRef<Path>>(
+    file_path: P,
+    cover_data: &[u8],
+) -> Result<()> {
+    let path = file_path.as_ref();
+    
+    if !path.exists() {
+        return Err(AppError::FileValidation(
+            format!("File not found: {}", path.display())
+        ));
+    }
+    ensure_not_in_use(path)?;
+    let cover = embeddable_cover(cover_data)?;
+    
+    with_path_lock(path, || {
+        let mut tagged_file = Probe::open(path)?
+            .read()?;
+        
+        let tag = tagged_file.primary_tag_mut()
+            .ok_or_else(|| AppError::Metadata(
+                lofty::error::LoftyError::new(lofty::error::ErrorKind::UnknownFormat)
+            ))?;
+        
+        let replaces_all = tag.tag_type() == TagType::Mp4Ilst;
+        let is_cover = |picture: &Picture| replaces_all || picture.pic_type() == PictureType::CoverFront;
+        let covers: Vec<&Picture> = tag.pictures().iter().filter(|picture| is_cover(picture)).collect();
+        if matches!(covers[..], [existing] if existing.data() == cover.data) {
+            return Ok(());
+        }
+        if replaces_all {
+            while !tag.pictures().is_empty() {
+                tag.remove_picture(0);
+            }
+        } else {
+            tag.remove_picture_type(PictureType::CoverFront);
+        }
+        let picture = Picture::new_unchecked(
+            PictureType::CoverFront,
+            Some(cover.mime_type.clone()),
+            None,
+            cover.data.clone(),
+        );
+        
+        tag.push_picture(picture);
+        tagged_file.save_to_path(path, Default::default())?;
+        if is_m4b(path) {
+            write_audiobook_atoms(path)?;
+        }
+        
+        Ok(())
+    })
+}
+
+/// Removes embedded cover art, leaving every other tag as it is
+///
+/// Front covers are removed, or every picture with `all_pictures`. MP4
+/// pictures carry no type, so there every picture counts as the cover. A
+/// file with no picture to remove is left untouched.
+pub fn remove_cover_art<P: AsRef<Path>>(file_path: P, all_pictures: bool) -> Result<()> {
+    let path = file_path.as_ref();
+
+    if !path.exists() {
+        return Err(AppError::FileValidation(
+            format!("File not found: {}", path.display())
+        ));
+    }
+    ensure_not_in_use(path)?;
+
+    with_path_lock(path, || {
+        let mut tagged_file = Probe::open(path)?
+            .read()?;
+        let Some(tag) = tagged_file.primary_tag_mut() else {
+            return Ok(());
+        };
+        let removes_all = all_pictures || tag.tag_type() == TagType::Mp4Ilst;
+        let count = tag.pictures().len();
+        if removes_all {
+            while !tag.pictures().is_empty() {
+                tag.remove_picture(0);
+            }
+        } else {
+            tag.remove_picture_type(PictureType::CoverFront);
+        }
+        if tag.pictures().len() == count {
+            return Ok(());
+        }
+        tagged_file.save_to_path(path, Default::default())?;
+        if is_m4b(path) {
+            write_audiobook_atoms(path)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::writer::fixtures::{copy_test_media, picture_fingerprint, tagged_m4b};
+    use crate::ffmpeg::locate_ffmpeg;
+    use lofty::prelude::ItemKey;
+    use std::fs;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_cover_to_nonexistent_file() {
+        let cover_data = vec![0u8; 100];
+        let result = write_cover_art("nonexistent.m4b", &cover_data);
+        assert!(matches!(result, Err(AppError::FileValidation(_))));
+    }
+
+    #[test]
+    fn test_write_cover_replaces_front_cover_and_keeps_other_pictures() {
+        let temp_dir = TempDir::new().unwrap();
+        let Some(file_path) = copy_test_media(&temp_dir, "pictures.mp3") else { return };
+        let others: Vec<_> = picture_fingerprint(&file_path).into_iter()
+            .filter(|(pic_type, _)| *pic_type != PictureType::CoverFront)
+            .collect();
+
+        let first = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x01];
+        let second = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x02];
+        write_cover_art(&file_path, &first).unwrap();
+        write_cover_art(&file_path, &second).unwrap();
+
+        let after = picture_fingerprint(&file_path);
+        assert_eq!(&after[..others.len()], &others[..]);
+        assert_eq!(&after[others.len()..], &[(PictureType::CoverFront, second.clone())]);
+        assert_eq!(crate::metadata::read_metadata(&file_path).unwrap().cover_art, Some(second));
+
+        let book_dir = TempDir::new().unwrap();
+        let book = tagged_m4b(&book_dir);
+        write_cover_art(&book, &first).unwrap();
+        write_cover_art(&book, &[0xFF, 0xD8, 0xFF, 0xE0, 0x03]).unwrap();
+        assert_eq!(picture_fingerprint(&book).len(), 1);
+        assert_eq!(crate::metadata::read_metadata(&book).unwrap().cover_art, Some(vec![0xFF, 0xD8, 0xFF, 0xE0, 0x03]));
+    }
+
+    #[test]
+    fn test_remove_cover_art_keeps_other_tags() {
+        let temp_dir = TempDir::new().unwrap();
+        let book = tagged_m4b(&temp_dir);
+        // Nothing to remove yet
+        remove_cover_art(&book, false).unwrap();
+
+        write_cover_art(&book, &[0xFF, 0xD8, 0xFF, 0xE0, 0x01]).unwrap();
+        assert_eq!(picture_fingerprint(&book).len(), 1);
+        remove_cover_art(&book, false).unwrap();
+        assert!(picture_fingerprint(&book).is_empty());
+        let tagged_file = Probe::open(&book).unwrap().read().unwrap();
+        let tag = tagged_file.primary_tag().unwrap();
+        assert_eq!(tag.get_string(&ItemKey::EncoderSoftware), Some("Lavf"));
+
+        let Some(mp3) = copy_test_media(&temp_dir, "remove.mp3") else { return };
+        write_cover_art(&mp3, &[0xFF, 0xD8, 0xFF, 0xE0, 0x02]).unwrap();
+        remove_cover_art(&mp3, true).unwrap();
+        assert!(picture_fingerprint(&mp3).is_empty());
+        assert!(remove_cover_art(temp_dir.path().join("missing.m4b"), false).is_err());
+    }
+
+    #[test]
+    fn test_cover_formats_are_stored_as_jpeg() {
+        use crate::metadata::cover_info::{image_format, ImageFormat};
+        use lofty::picture::MimeType;
+        let temp_dir = TempDir::new().unwrap();
+        let stored = |path: &Path| {
+            let tagged_file = Probe::open(path).unwrap().read().unwrap();
+            let picture = tagged_file.primary_tag().unwrap().pictures().last().unwrap().clone();
+            (picture.mime_type().cloned(), image_format(picture.data()))
+        };
+
+        let jpeg_book = tagged_m4b(&temp_dir);
+        let jpeg = crate::metadata::placeholder_cover::render_placeholder_cover("Dune", None).unwrap();
+        write_cover_art(&jpeg_book, &jpeg).unwrap();
+        assert_eq!(stored(&jpeg_book), (Some(MimeType::Jpeg), Some(ImageFormat::Jpeg)));
+        assert_eq!(picture_fingerprint(&jpeg_book).last().map(|(_, data)| data.clone()), Some(jpeg.clone()));
+
+        let Ok(ffmpeg) = locate_ffmpeg() else {
+            eprintln!("Skipping PNG and WebP covers - FFmpeg not found");
+            return;
+        };
+        for (name, codec) in [("cover.png", "png"), ("cover.webp", "libwebp")] {
+            let image = temp_dir.path().join(name);
+            let status = Command::new(&ffmpeg)
+                .args(["-v", "error", "-f", "lavfi", "-i", "color=c=navy:s=64x64", "-frames:v", "1", "-c:v", codec, "-y"])
+                .arg(&image)
+                .status()
+                .unwrap();
+            if !status.success() {
+                eprintln!("Skipping {name} - FFmpeg can't encode it");
+                continue;
+            }
+            let book_dir = TempDir::new().unwrap();
+            let book = tagged_m4b(&book_dir);
+            write_cover_art(&book, &fs::read(&image).unwrap()).unwrap();
+            assert_eq!(stored(&book), (Some(MimeType::Jpeg), Some(ImageFormat::Jpeg)), "{name}");
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! Tag items a metadata write manages
+
+use super::container_fields::{write_description, write_identifiers, write_series};
+use super::{MetadataField, MetadataWriteOptions, MetadataWriteReport};
+use crate::audio::constants::{
+    ASIN_TAG_KEY, ISBN_TAG_KEY, MP4_ASIN_ATOM, MP4_ISBN_ATOM, MP4_SERIES_ATOM, MP4_SERIES_PART_ATOM,
+};
+use crate::errors::{AppError, Result};
+use crate::metadata::AudiobookMetadata;
+use lofty::prelude::ItemKey;
+use lofty::tag::{ItemValue, Tag, TagItem, TagType};
+
+/// Tag operations a metadata write goes through, so tests can stand in a
+/// tag that rejects items
+pub(super) trait TagWriter {
+    fn tag_type(&self) -> TagType;
+    /// Text of the first item with `key`
+    fn text(&self, key: &ItemKey) -> Option<&str>;
+    /// Removes every item with `key`
+    fn remove_key(&mut self, key: &ItemKey);
+    /// Removes every item, keeping pictures
+    fn remove_all_items(&mut self);
+    /// Inserts `item` in place of items with its key; false when the tag can't hold it
+    fn insert_item(&mut self, item: TagItem) -> bool;
+}
+
+impl TagWriter for Tag {
+    fn tag_type(&self) -> TagType {
+        Tag::tag_type(self)
+    }
+
+    fn text(&self, key: &ItemKey) -> Option<&str> {
+        self.get_string(key)
+    }
+
+    fn remove_key(&mut self, key: &ItemKey) {
+        Tag::remove_key(self, key);
+    }
+
+    fn remove_all_items(&mut self) {
+        self.retain(|_| false);
+    }
+
+    fn insert_item(&mut self, item: TagItem) -> bool {
+        // Freeform MP4 atoms have no ItemKey, so `insert` would refuse them
+        if Tag::tag_type(self) == TagType::Mp4Ilst
+            && matches!(item.key(), ItemKey::Unknown(key) if key.starts_with("----:"))
+        {
+            self.insert_unchecked(item);
+            return true;
+        }
+        self.insert(item)
+    }
+}
+
+/// Tag items a metadata write replaces; everything else in the tag is kept
+const MANAGED_KEYS: [ItemKey; 18] = [
+    ItemKey::TrackTitle,
+    ItemKey::TrackSubtitle,
+    ItemKey::TrackArtist,
+    ItemKey::AlbumTitle,
+    ItemKey::AlbumArtist,
+    ItemKey::Year,
+    ItemKey::RecordingDate,
+    ItemKey::Genre,
+    ItemKey::TrackNumber,
+    ItemKey::TrackTotal,
+    ItemKey::DiscNumber,
+    ItemKey::DiscTotal,
+    ItemKey::TrackTitleSortOrder,
+    ItemKey::TrackArtistSortOrder,
+    ItemKey::Comment,
+    ItemKey::PodcastDescription,
+    ItemKey::Movement,
+    ItemKey::MovementNumber,
+];
+
+/// Freeform and plain keys a metadata write replaces, besides `MANAGED_KEYS`
+const MANAGED_CUSTOM_KEYS: [&str; 6] = [
+    MP4_SERIES_ATOM, MP4_SERIES_PART_ATOM, MP4_ISBN_ATOM, MP4_ASIN_ATOM, ISBN_TAG_KEY, ASIN_TAG_KEY,
+];
+
+/// Tag items stamped with the software or time of a run
+const RUN_SPECIFIC_KEYS: [ItemKey; 4] = [
+    ItemKey::EncoderSoftware,
+    ItemKey::EncoderSettings,
+    ItemKey::EncodingTime,
+    ItemKey::TaggingTime,
+];
+
+/// Updates tag data from metadata struct
+///
+/// Managed items are removed first, so a field left unset is removed from
+/// the file. Each field is then written on its own; fields the tag can't
+/// hold are listed in the report, or fail the write when `strict` is set.
+pub(super) fn update_tag_data<T: TagWriter + ?Sized>(
+    tag: &mut T,
+    metadata: &AudiobookMetadata,
+    options: MetadataWriteOptions,
+) -> Result<MetadataWriteReport> {
+    if options.clear_unknown {
+        tag.remove_all_items();
+    } else {
+        remove_managed_items(tag);
+    }
+    
+    let mut report = MetadataWriteReport::default();
+    let year = metadata.year.map(|year| year.to_string());
+    // Zero means "not set" for track and disc numbers, so no "0" item is written
+    let number = |value: Option<u32>| value.filter(|&number| number > 0).map(|number| number.to_string());
+    let (track_number, track_total) = (number(metadata.track_number), number(metadata.track_total));
+    let (disc_number, disc_total) = (number(metadata.disc_number), number(metadata.disc_total));
+    let fields: [(MetadataField, &Option<String>, &[ItemKey]); 13] = [
+        (MetadataField::Title, &metadata.title, &[ItemKey::TrackTitle]),
+        (MetadataField::Subtitle, &metadata.subtitle, &[ItemKey::TrackSubtitle]),
+        (MetadataField::Author, &metadata.author, &[ItemKey::TrackArtist]),
+        (MetadataField::Album, &metadata.album, &[ItemKey::AlbumTitle]),
+        (MetadataField::Narrator, &metadata.narrator, &[ItemKey::AlbumArtist]),
+        // Some formats have a dedicated year item, others keep it in the recording date
+        (MetadataField::Year, &year, &[ItemKey::Year, ItemKey::RecordingDate]),
+        (MetadataField::Genre, &metadata.genre, &[ItemKey::Genre]),
+        (MetadataField::TrackNumber, &track_number, &[ItemKey::TrackNumber]),
+        (MetadataField::TrackTotal, &track_total, &[ItemKey::TrackTotal]),
+        (MetadataField::DiscNumber, &disc_number, &[ItemKey::DiscNumber]),
+        (MetadataField::DiscTotal, &disc_total, &[ItemKey::DiscTotal]),
+        (MetadataField::SortTitle, &metadata.sort_title, &[ItemKey::TrackTitleSortOrder]),
+        (MetadataField::SortAuthor, &metadata.sort_author, &[ItemKey::TrackArtistSortOrder]),
+    ];
+    for (field, value, keys) in fields {
+        let Some(value) = value else {
+            continue;
+        };
+        if !keys.iter().any(|key| tag.insert_item(text_item(key.clone(), value.clone()))) {
+            report.failed_fields.push(field);
+        }
+    }
+    if let Some(description) = &metadata.description {
+        write_description(tag, description, &mut report);
+    }
+    write_series(tag, metadata, &mut report);
+    write_identifiers(tag, metadata, &mut report);
+    
+    if options.strict && !report.failed_fields.is_empty() {
+        let fields: Vec<&str> = report.failed_fields.iter().map(|field| field.name()).collect();
+        return Err(AppError::InvalidInput(format!(
+            "Output container cannot hold metadata field(s): {}", fields.join(", ")
+        )));
+    }
+    Ok(report)
+}
+
+/// Removes the items a metadata write sets, keeping pictures and everything else
+///
+/// An ID3v2 USLT frame is only removed when it continues the comment, i.e.
+/// holds the overflow of a long description rather than real lyrics.
+fn remove_managed_items<T: TagWriter + ?Sized>(tag: &mut T) {
+    let overflow = tag.text(&ItemKey::Lyrics)
+        .zip(tag.text(&ItemKey::Comment))
+        .is_some_and(|(lyrics, comment)| lyrics.starts_with(comment));
+    if overflow {
+        tag.remove_key(&ItemKey::Lyrics);
+    }
+    for key in &MANAGED_KEYS {
+        tag.remove_key(key);
+    }
+    for key in MANAGED_CUSTOM_KEYS {
+        tag.remove_key(&ItemKey::Unknown(key.to_string()));
+    }
+}
+
+pub(super) fn text_item(key: ItemKey, value: String) -> TagItem {
+    TagItem::new(key, ItemValue::Text(value))
+}
+
+/// Removes run-specific items and sorts the rest by key
+///
+/// The sort is stable, so items sharing a key keep their relative order.
+pub(super) fn canonicalize_tag(tag: &mut Tag) {
+    let mut items: Vec<TagItem> = tag.items()
+        .filter(|item| !RUN_SPECIFIC_KEYS.contains(item.key()))
+        .cloned()
+        .collect();
+    items.sort_by_cached_key(|item| format!("{:?}", item.key()));
+    tag.retain(|_| false);
+    for item in items {
+        tag.push_unchecked(item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::writer::fixtures::tagged_m4b;
+    use crate::metadata::writer::write_metadata;
+    use lofty::prelude::Accessor;
+    use tempfile::TempDir;
+
+    /// Fails the write when any field is rejected
+    const STRICT: MetadataWriteOptions = MetadataWriteOptions { reproducible: false, strict: true, clear_unknown: false };
+
+    /// Tag that refuses items with the given keys, like a container without those atoms
+    struct RejectingTag {
+        tag: Tag,
+        rejected: Vec<ItemKey>,
+    }
+
+    impl TagWriter for RejectingTag {
+        fn tag_type(&self) -> TagType {
+            self.tag.tag_type()
+        }
+
+        fn text(&self, key: &ItemKey) -> Option<&str> {
+            self.tag.get_string(key)
+        }
+
+        fn remove_key(&mut self, key: &ItemKey) {
+            self.tag.remove_key(key);
+        }
+
+        fn remove_all_items(&mut self) {
+            self.tag.retain(|_| false);
+        }
+
+        fn insert_item(&mut self, item: TagItem) -> bool {
+            !self.rejected.contains(item.key()) && self.tag.insert(item)
+        }
+    }
+
+    #[test]
+    fn test_partial_write_skips_rejected_fields() {
+        let metadata = AudiobookMetadata {
+            title: Some("Dune".to_string()),
+            author: Some("Frank Herbert".to_string()),
+            year: Some(1965),
+            genre: Some("Science Fiction".to_string()),
+            description: Some("Desert planet".to_string()),
+            ..AudiobookMetadata::new()
+        };
+        let mut tag = RejectingTag {
+            tag: Tag::new(TagType::Mp4Ilst),
+            rejected: vec![ItemKey::Year, ItemKey::RecordingDate, ItemKey::Comment],
+        };
+        tag.tag.set_album("Stale".to_string());
+
+        let report = update_tag_data(&mut tag, &metadata, MetadataWriteOptions::default()).unwrap();
+        assert_eq!(report.failed_fields, [MetadataField::Year, MetadataField::Description]);
+        assert!(report.description_fields.is_empty());
+        assert_eq!(report.warnings().len(), 2);
+        assert!(report.warnings()[0].contains("'year'"));
+        assert_eq!(tag.tag.title().as_deref(), Some("Dune"));
+        assert_eq!(tag.tag.artist().as_deref(), Some("Frank Herbert"));
+        assert_eq!(tag.tag.genre().as_deref(), Some("Science Fiction"));
+        assert_eq!(tag.tag.album(), None);
+        assert_eq!(tag.tag.year(), None);
+
+        let error = update_tag_data(&mut tag, &metadata, STRICT).unwrap_err();
+        assert!(matches!(&error, AppError::InvalidInput(message) if message.ends_with("year, description")));
+
+        tag.rejected.clear();
+        let report = update_tag_data(&mut tag, &metadata, STRICT).unwrap();
+        assert!(report.failed_fields.is_empty());
+        assert_eq!(tag.tag.year(), Some(1965));
+        assert_eq!(serde_json::to_value(&report).unwrap().get("failedFields"), None);
+    }
+
+    #[test]
+    fn test_canonicalize_tag_fixed_order_without_run_items() {
+        let keys = |tag: &Tag| tag.items().map(|item| item.key().clone()).collect::<Vec<_>>();
+        let text = |key: ItemKey, value: &str| TagItem::new(key, ItemValue::Text(value.to_string()));
+        let mut first = Tag::new(TagType::Id3v2);
+        first.insert(text(ItemKey::Genre, "Science Fiction"));
+        first.insert(text(ItemKey::EncodingTime, "2024-01-01T00:00:00"));
+        first.insert(text(ItemKey::TrackTitle, "Dune"));
+        first.insert(text(ItemKey::EncoderSoftware, "Lavf60.3.100"));
+        let mut second = Tag::new(TagType::Id3v2);
+        second.insert(text(ItemKey::TrackTitle, "Dune"));
+        second.insert(text(ItemKey::Genre, "Science Fiction"));
+
+        canonicalize_tag(&mut first);
+        canonicalize_tag(&mut second);
+        assert_eq!(keys(&first), [ItemKey::Genre, ItemKey::TrackTitle]);
+        assert_eq!(keys(&first), keys(&second));
+    }
+
+    #[test]
+    fn test_track_disc_and_sort_round_trip_m4b() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = tagged_m4b(&temp_dir);
+        let metadata = AudiobookMetadata {
+            title: Some("The Hobbit".to_string()),
+            track_number: Some(3),
+            track_total: Some(12),
+            disc_number: Some(1),
+            disc_total: Some(2),
+            sort_title: Some("Hobbit, The".to_string()),
+            sort_author: Some("Tolkien, J.R.R.".to_string()),
+            ..AudiobookMetadata::new()
+        };
+
+        let report = write_metadata(&file_path, &metadata).unwrap();
+        assert!(report.failed_fields.is_empty());
+        let result = crate::metadata::read_metadata(&file_path).unwrap();
+        assert_eq!(result.track_number, Some(3));
+        assert_eq!(result.track_total, Some(12));
+        assert_eq!(result.disc_number, Some(1));
+        assert_eq!(result.disc_total, Some(2));
+        assert_eq!(result.sort_title, metadata.sort_title);
+        assert_eq!(result.sort_author, metadata.sort_author);
+
+        // Rewriting other fields keeps them, since the cleared tag is repopulated
+        write_metadata(&file_path, &AudiobookMetadata { title: Some("There and Back Again".to_string()), ..metadata }).unwrap();
+        let result = crate::metadata::read_metadata(&file_path).unwrap();
+        assert_eq!(result.track_number, Some(3));
+        assert_eq!(result.sort_author.as_deref(), Some("Tolkien, J.R.R."));
+    }
+
+    #[test]
+    fn test_zero_or_absent_numbers_write_no_items() {
+        let metadata = AudiobookMetadata {
+            title: Some("Dune".to_string()),
+            track_number: Some(0),
+            disc_number: Some(0),
+            disc_total: Some(0),
+            ..AudiobookMetadata::new()
+        };
+        for tag_type in [TagType::Id3v2, TagType::Mp4Ilst] {
+            let mut tag = Tag::new(tag_type);
+            tag.insert_text(ItemKey::TrackNumber, "7".to_string());
+            let report = update_tag_data(&mut tag, &metadata, STRICT).unwrap();
+            assert!(report.failed_fields.is_empty());
+            for key in [ItemKey::TrackNumber, ItemKey::TrackTotal, ItemKey::DiscNumber, ItemKey::DiscTotal] {
+                assert_eq!(tag.get_string(&key), None, "{tag_type:?} {key:?}");
+            }
+            assert_eq!(tag.title().as_deref(), Some("Dune"));
+        }
+    }
+}
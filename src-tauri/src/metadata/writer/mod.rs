@@ -0,0 +1,318 @@
+//! Metadata writing functionality
+//!
+//! Invariant for every writer in this module and in `chapter_remux`: a write
+//! only touches the data it owns. Writing chapters must not remove or reorder
+//! embedded pictures, and writing a cover replaces only the front cover, never
+//! chapters or other pictures. Writing metadata replaces only the tag items it
+//! manages and leaves pictures and items it doesn't model (encoder,
+//! ReplayGain, ...) alone. The round-trip tests guard this for all writer changes.
+//!
+//! Field writing lives in `fields` and `container_fields`, cover art in `cover`.
+
+mod container_fields;
+mod cover;
+mod fields;
+mod report;
+
+pub use cover::{remove_cover_art, write_cover_art};
+pub use report::{DescriptionField, MetadataField, MetadataWriteOptions, MetadataWriteReport};
+
+use super::AudiobookMetadata;
+use super::file_in_use::ensure_not_in_use;
+use super::identifiers::validate_metadata;
+use super::normalize::normalize_metadata;
+use super::write_lock::with_path_lock;
+use crate::audio::constants::{MP4_GAPLESS_ATOM, MP4_MEDIA_KIND_ATOM, MP4_MEDIA_KIND_AUDIOBOOK, MP4_SIGNED_INT_CODE};
+use crate::errors::{AppError, Result};
+use fields::{canonicalize_tag, update_tag_data};
+use lofty::config::{ParseOptions, WriteOptions};
+use lofty::file::AudioFile;
+use lofty::mp4::{Atom, AtomData, AtomIdent, Ilst, Mp4File};
+use lofty::prelude::{ItemKey, TaggedFileExt};
+use lofty::probe::Probe;
+use std::path::Path;
+
+/// Writes metadata to an existing M4B file
+///
+/// The report records which fields the description went into.
+/// Fields the container can't hold are skipped and listed in the report.
+pub fn write_metadata<P: AsRef<Path>>(
+    file_path: P,
+    metadata: &AudiobookMetadata,
+) -> Result<MetadataWriteReport> {
+    write_metadata_with(file_path, metadata, MetadataWriteOptions::default())
+}
+
+/// Writes metadata like `write_metadata`, with the tag handling in `options`
+///
+/// Text fields are trimmed and blank ones left unset. An out-of-range
+/// year or a malformed ISBN or ASIN fails the write before the file is touched.
+/// Encoding info, when set, replaces the encoder tag, even in reproducible mode.
+pub fn write_metadata_with<P: AsRef<Path>>(
+    file_path: P,
+    metadata: &AudiobookMetadata,
+    options: MetadataWriteOptions,
+) -> Result<MetadataWriteReport> {
+    let path = file_path.as_ref();
+    let metadata = &normalize_metadata(metadata)?;
+    validate_metadata(metadata)?;
+    
+    if !path.exists() {
+        return Err(AppError::FileValidation(
+            format!("File not found: {}", path.display())
+        ));
+    }
+    ensure_not_in_use(path)?;
+    
+    with_path_lock(path, || {
+        let mut tagged_file = Probe::open(path)?
+            .read()?;
+        
+        let tag = tagged_file.primary_tag_mut()
+            .ok_or_else(|| AppError::Metadata(
+                lofty::error::LoftyError::new(lofty::error::ErrorKind::UnknownFormat)
+            ))?;
+        
+        let report = update_tag_data(tag, metadata, options)?;
+        if options.reproducible {
+            canonicalize_tag(tag);
+        }
+        // After canonicalizing, which drops encoder items: these depend on the settings, not the run
+        if let Some(info) = &metadata.encoding_info {
+            tag.insert_text(ItemKey::EncoderSoftware, info.to_tag_text());
+        }
+        tagged_file.save_to_path(path, Default::default())?;
+        if is_m4b(path) {
+            write_audiobook_atoms(path)?;
+        }
+        
+        Ok(report)
+    })
+}
+
+/// Whether `path` names an M4B file
+fn is_m4b(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("m4b"))
+}
+
+/// Marks an M4B as an audiobook for Apple Books and iTunes
+///
+/// Sets the `stik` media kind to audiobook and the `pgap` gapless flag.
+/// The generic tag has no items for either and drops them when saved, so
+/// they are set on the file's ilst after each tag write.
+fn write_audiobook_atoms(path: &Path) -> Result<()> {
+    let mut mp4 = Mp4File::read_from(&mut std::fs::File::open(path)?, ParseOptions::new())?;
+    if mp4.ilst().is_none() {
+        mp4.set_ilst(Ilst::new());
+    }
+    if let Some(ilst) = mp4.ilst_mut() {
+        // Both are one-byte integers; lofty writes its integer types as four
+        // bytes, which it then reads back as a false pgap, so the data is raw
+        let one_byte = |value: u8| AtomData::Unknown { code: MP4_SIGNED_INT_CODE, data: vec![value] };
+        ilst.replace_atom(Atom::new(AtomIdent::Fourcc(MP4_MEDIA_KIND_ATOM), one_byte(MP4_MEDIA_KIND_AUDIOBOOK)));
+        ilst.replace_atom(Atom::new(AtomIdent::Fourcc(MP4_GAPLESS_ATOM), one_byte(1)));
+    }
+    mp4.save_to_path(path, WriteOptions::default())?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod fixtures {
+    use lofty::file::AudioFile;
+    use lofty::picture::PictureType;
+    use lofty::prelude::{ItemKey, TaggedFileExt};
+    use lofty::probe::Probe;
+    use lofty::tag::{Tag, TagType};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tempfile::TempDir;
+
+    /// Copies the test MP3 into a temp dir, or None when media is missing
+    pub fn copy_test_media(temp_dir: &TempDir, name: &str) -> Option<PathBuf> {
+        let source = Path::new("../media/01 - Introduction.mp3");
+        if !source.exists() {
+            eprintln!("Skipping test - media file not found");
+            return None;
+        }
+        let target = temp_dir.path().join(name);
+        fs::copy(source, &target).unwrap();
+        Some(target)
+    }
+
+    /// Pictures of the primary tag as (type, bytes), in stored order
+    pub fn picture_fingerprint(path: &Path) -> Vec<(PictureType, Vec<u8>)> {
+        let tagged_file = Probe::open(path).unwrap().read().unwrap();
+        tagged_file.primary_tag()
+            .map(|tag| tag.pictures().iter()
+                .map(|p| (p.pic_type(), p.data().to_vec()))
+                .collect())
+            .unwrap_or_default()
+    }
+
+    /// The minimal M4B fixture with an empty ilst, as an encode leaves it
+    pub fn tagged_m4b(temp_dir: &TempDir) -> PathBuf {
+        let path = temp_dir.path().join("book.m4b");
+        fs::write(&path, crate::metadata::chpl::fixtures::mp4(true, false)).unwrap();
+        let mut tagged_file = Probe::open(&path).unwrap().read().unwrap();
+        let mut tag = Tag::new(TagType::Mp4Ilst);
+        tag.insert_text(ItemKey::EncoderSoftware, "Lavf".to_string());
+        tagged_file.insert_tag(tag);
+        tagged_file.save_to_path(&path, Default::default()).unwrap();
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::fixtures::{copy_test_media, tagged_m4b};
+    use lofty::prelude::Accessor;
+    use tempfile::TempDir;
+    use std::fs;
+
+    #[test]
+    fn test_write_to_nonexistent_file() {
+        let metadata = AudiobookMetadata::new();
+        let result = write_metadata("nonexistent.m4b", &metadata);
+        assert!(matches!(result, Err(AppError::FileValidation(_))));
+    }
+
+    #[test]
+    fn test_write_metadata_invalid_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("invalid.txt");
+        fs::write(&file_path, b"not audio").unwrap();
+        
+        let metadata = AudiobookMetadata::new();
+        let result = write_metadata(&file_path, &metadata);
+        assert!(matches!(result, Err(AppError::Metadata(_))));
+    }
+
+    #[test]
+    fn test_concurrent_writes_to_same_file_stay_consistent() {
+        let source = Path::new("../media/01 - Introduction.mp3");
+        if !source.exists() {
+            eprintln!("Skipping stress test - media file not found");
+            return;
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("stress.mp3");
+        fs::copy(source, &file_path).unwrap();
+
+        let titles: Vec<String> = (0..20).map(|i| format!("Title {i}")).collect();
+        let handles: Vec<_> = titles.iter().cloned().map(|title| {
+            let file_path = file_path.clone();
+            std::thread::spawn(move || {
+                let mut metadata = AudiobookMetadata::new();
+                metadata.title = Some(title.clone());
+                metadata.author = Some(format!("Author of {title}"));
+                write_metadata(&file_path, &metadata)
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+
+        let result = crate::metadata::read_metadata(&file_path).unwrap();
+        let title = result.title.expect("final tag should have a title");
+        assert!(titles.contains(&title));
+        assert_eq!(result.author, Some(format!("Author of {title}")));
+    }
+
+    #[test]
+    fn test_metadata_write_keeps_cover_and_unknown_items() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = tagged_m4b(&temp_dir);
+        let cover = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x01, 0x02];
+        write_cover_art(&file_path, &cover).unwrap();
+        write_cover_art(&file_path, &cover).unwrap();
+
+        let metadata = AudiobookMetadata { title: Some("Dune".to_string()), ..AudiobookMetadata::new() };
+        write_metadata(&file_path, &metadata).unwrap();
+        let tagged_file = Probe::open(&file_path).unwrap().read().unwrap();
+        let tag = tagged_file.primary_tag().unwrap();
+        assert_eq!(tag.title().as_deref(), Some("Dune"));
+        assert_eq!(tag.pictures().len(), 1);
+        assert_eq!(tag.pictures()[0].data(), cover.as_slice());
+        assert_eq!(tag.get_string(&ItemKey::EncoderSoftware), Some("Lavf"));
+
+        // Managed fields left unset are removed
+        write_metadata(&file_path, &AudiobookMetadata { author: Some("Frank Herbert".to_string()), ..AudiobookMetadata::new() }).unwrap();
+        let result = crate::metadata::read_metadata(&file_path).unwrap();
+        assert_eq!((result.title, result.author.as_deref()), (None, Some("Frank Herbert")));
+
+        let options = MetadataWriteOptions { clear_unknown: true, ..MetadataWriteOptions::default() };
+        write_metadata_with(&file_path, &metadata, options).unwrap();
+        let tagged_file = Probe::open(&file_path).unwrap().read().unwrap();
+        let tag = tagged_file.primary_tag().unwrap();
+        assert_eq!(tag.get_string(&ItemKey::EncoderSoftware), None);
+        assert_eq!(tag.pictures().len(), 1);
+    }
+
+    /// The `stik` and `pgap` atoms of an MP4 file, as stored
+    fn audiobook_atoms(path: &Path) -> (Option<AtomData>, Option<AtomData>) {
+        let mp4 = Mp4File::read_from(&mut fs::File::open(path).unwrap(), ParseOptions::new()).unwrap();
+        let ilst = mp4.ilst().unwrap();
+        let atom = |fourcc| ilst.get(&AtomIdent::Fourcc(fourcc)).and_then(|atom| atom.data().next().cloned());
+        (atom(MP4_MEDIA_KIND_ATOM), atom(MP4_GAPLESS_ATOM))
+    }
+
+    #[test]
+    fn test_m4b_writes_mark_file_as_gapless_audiobook() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = tagged_m4b(&temp_dir);
+        let audiobook = (Some(AtomData::SignedInteger(i32::from(MP4_MEDIA_KIND_AUDIOBOOK))), Some(AtomData::Bool(true)));
+
+        let metadata = AudiobookMetadata { title: Some("Dune".to_string()), ..AudiobookMetadata::new() };
+        write_metadata(&file_path, &metadata).unwrap();
+        assert_eq!(audiobook_atoms(&file_path), audiobook);
+        assert_eq!(crate::metadata::read_metadata(&file_path).unwrap().title.as_deref(), Some("Dune"));
+
+        // A later tag save goes through the generic tag, which would drop them
+        write_cover_art(&file_path, &[0xFF, 0xD8, 0xFF, 0xE0, 0x01]).unwrap();
+        assert_eq!(audiobook_atoms(&file_path), audiobook);
+    }
+
+    #[test]
+    fn test_encoding_info_round_trip() {
+        use crate::metadata::provenance::EncodingInfo;
+        let temp_dir = TempDir::new().unwrap();
+        let info = EncodingInfo::for_settings(&crate::audio::AudioSettings::default());
+        let metadata = AudiobookMetadata { encoding_info: Some(info.clone()), ..AudiobookMetadata::new() };
+
+        let m4b = tagged_m4b(&temp_dir);
+        assert_eq!(crate::metadata::read_metadata(&m4b).unwrap().encoding_info.map(|info| info.tool), Some("Lavf".to_string()));
+        // Reproducible writes drop the encoder item FFmpeg left, but keep the settings
+        let options = MetadataWriteOptions { reproducible: true, ..MetadataWriteOptions::default() };
+        write_metadata_with(&m4b, &metadata, options).unwrap();
+        assert_eq!(crate::metadata::read_metadata(&m4b).unwrap().encoding_info, Some(info.clone()));
+
+        let Some(mp3) = copy_test_media(&temp_dir, "provenance.mp3") else { return };
+        write_metadata(&mp3, &metadata).unwrap();
+        assert_eq!(crate::metadata::read_metadata(&mp3).unwrap().encoding_info, Some(info));
+    }
+
+    #[test]
+    fn test_write_normalizes_text_and_rejects_year_zero() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = tagged_m4b(&temp_dir);
+        let metadata = AudiobookMetadata {
+            title: Some("  Dune ".to_string()),
+            author: Some("   ".to_string()),
+            asin: Some(" B002V1OF70\n".to_string()),
+            year: Some(1965),
+            ..AudiobookMetadata::new()
+        };
+        assert!(write_metadata(&file_path, &metadata).unwrap().failed_fields.is_empty());
+        let result = crate::metadata::read_metadata(&file_path).unwrap();
+        assert_eq!(result.title.as_deref(), Some("Dune"));
+        assert_eq!(result.author, None);
+        assert_eq!(result.asin.as_deref(), Some("B002V1OF70"));
+
+        let before = fs::read(&file_path).unwrap();
+        let invalid = AudiobookMetadata { year: Some(0), ..metadata };
+        let error = write_metadata(&file_path, &invalid).unwrap_err();
+        assert!(matches!(&error, AppError::InvalidInput(message) if message.contains("year")));
+        assert_eq!(fs::read(&file_path).unwrap(), before);
+    }
+}
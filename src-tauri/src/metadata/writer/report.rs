@@ -0,0 +1,98 @@
+//! What a metadata write reports and how it treats the tag
+
+use serde::{Deserialize, Serialize};
+
+/// Tag field a description was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DescriptionField {
+    /// Comment (MP4 ©cmt, ID3v2 COMM, ...)
+    Comment,
+    /// MP4 long description atom (ldes)
+    LongDescription,
+    /// ID3v2 unsynchronized lyrics frame (USLT)
+    Lyrics,
+}
+
+/// Book-level metadata field, as named in write reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataField {
+    Title,
+    Subtitle,
+    Author,
+    Album,
+    Narrator,
+    Year,
+    Genre,
+    Description,
+    Series,
+    SeriesIndex,
+    TrackNumber,
+    TrackTotal,
+    DiscNumber,
+    DiscTotal,
+    SortTitle,
+    SortAuthor,
+    Isbn,
+    Asin,
+}
+
+impl MetadataField {
+    /// Lowercase field name for messages
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Subtitle => "subtitle",
+            Self::Author => "author",
+            Self::Album => "album",
+            Self::Narrator => "narrator",
+            Self::Year => "year",
+            Self::Genre => "genre",
+            Self::Description => "description",
+            Self::Series => "series",
+            Self::SeriesIndex => "series index",
+            Self::TrackNumber => "track number",
+            Self::TrackTotal => "track total",
+            Self::DiscNumber => "disc number",
+            Self::DiscTotal => "disc total",
+            Self::SortTitle => "sort title",
+            Self::SortAuthor => "sort author",
+            Self::Isbn => "isbn",
+            Self::Asin => "asin",
+        }
+    }
+}
+
+/// Fields a metadata write used where the container decides
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataWriteReport {
+    /// Fields holding the description, comment first
+    pub description_fields: Vec<DescriptionField>,
+    /// Whether the comment holds only the start of the description
+    pub description_truncated: bool,
+    /// Fields the container could not hold; everything else was written
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_fields: Vec<MetadataField>,
+}
+
+impl MetadataWriteReport {
+    /// One warning per field that was not written
+    pub fn warnings(&self) -> Vec<String> {
+        self.failed_fields.iter()
+            .map(|field| format!("Metadata field '{}' is not supported by the output container and was not written", field.name()))
+            .collect()
+    }
+}
+
+/// How a metadata write treats the tag
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetadataWriteOptions {
+    /// Drop encoder and timestamp items and write the rest in a fixed order
+    pub reproducible: bool,
+    /// Fail the whole write, leaving the file untouched, when any field can't be written
+    pub strict: bool,
+    /// Drop every tag item, not just the managed ones, before writing (pictures are kept)
+    pub clear_unknown: bool,
+}
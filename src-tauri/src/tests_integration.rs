@@ -6,7 +6,8 @@
 //! DO NOT MODIFY THESE TESTS - they document how the system works now.
 //! Any changes should only be made if the current behavior is incorrect.
 
-use crate::audio::{AudioSettings, ChannelConfig, SampleRateConfig};
+use crate::audio::{AudioSettings, ChannelConfig, InvalidInputPolicy, SampleRateConfig};
+use crate::audio::chapters::{ChapterFormat, ChapterTimestampFormat, ChapterTitleSource};
 use crate::commands::{validate_files, analyze_audio_files, validate_audio_settings, read_audio_metadata};
 use crate::errors::{AppError, Result};
 use crate::metadata::AudiobookMetadata;
@@ -24,6 +25,36 @@ fn create_test_settings(output_path: PathBuf) -> AudioSettings {
         channels: ChannelConfig::Mono,
         sample_rate: SampleRateConfig::Auto,
         output_path,
+        segment_threshold: None,
+        strict_cross_platform: false,
+        auto_dual_mono: false,
+        accurate_mp3_durations: false,
+        enforce_cover_quality: false,
+        generate_placeholder_cover: false,
+        invalid_input_policy: InvalidInputPolicy::Fail,
+        probe_size_mb: None,
+        analyze_duration_secs: None,
+        gapless_join: true,
+        bitrate_excess_factor: None,
+        strict_bitrate_check: false,
+        copy_transcripts: false,
+        strip_track_scoped_metadata: true,
+        chapters: None,
+        chapter_title_source: ChapterTitleSource::TagTitle,
+        chapter_specs: None,
+        allow_m4a_extension: false,
+        verify_output: false,
+        reproducible: false,
+        strict_metadata_fields: false,
+        export_cue: false,
+        append_chapter_list_to_description: false,
+        chapter_list_timestamp_format: ChapterTimestampFormat::Auto,
+        chapter_format: ChapterFormat::Ffmetadata,
+        write_encoding_info: true,
+        auto_cover_from_input: true,
+        max_cover_bytes: None,
+        strict_cover_size: false,
+        metadata_sidecar: None,
     }
 }
 
@@ -34,6 +65,7 @@ fn create_mock_processing_state() -> crate::ProcessingState {
         is_processing: Arc::new(Mutex::new(false)),
         is_cancelled: Arc::new(Mutex::new(false)),
         progress: Arc::new(Mutex::new(None)),
+        cancel_reason: Arc::new(Mutex::new(None)),
     }
 }
 
@@ -82,7 +114,7 @@ mod integration_tests {
         assert!(validation_result.unwrap().contains("Successfully validated 1 files"));
 
         // Step 2: Analyze the audio file
-        let analysis_result = analyze_audio_files(files);
+        let analysis_result = analyze_audio_files(files, None);
         assert!(analysis_result.is_ok(), "File analysis should succeed");
         
         let file_info = analysis_result.unwrap();
@@ -102,7 +134,7 @@ mod integration_tests {
         assert_eq!(settings_validation.unwrap(), "Settings are valid");
 
         // Step 4: Read metadata from input file
-        let metadata_result = read_audio_metadata(media_path.to_string_lossy().to_string());
+        let metadata_result = read_audio_metadata(media_path.to_string_lossy().to_string(), None);
         assert!(metadata_result.is_ok(), "Should be able to read metadata");
         
         let input_metadata = metadata_result.unwrap();
@@ -172,7 +204,7 @@ mod integration_tests {
         };
 
         // Read current metadata
-        let metadata_result = read_audio_metadata(media_path.to_string_lossy().to_string());
+        let metadata_result = read_audio_metadata(media_path.to_string_lossy().to_string(), None);
         assert!(metadata_result.is_ok(), "Should be able to read metadata from test file");
 
         let original_metadata = metadata_result.unwrap();
@@ -215,7 +247,7 @@ mod integration_tests {
 
         // Test analysis of invalid files
         let invalid_files = vec!["nonexistent.mp3".to_string()];
-        let analysis_result = analyze_audio_files(invalid_files);
+        let analysis_result = analyze_audio_files(invalid_files, None);
         assert!(analysis_result.is_ok(), "Analysis should succeed but mark files as invalid");
         
         let file_info = analysis_result.unwrap();
@@ -242,7 +274,7 @@ mod integration_tests {
         assert!(settings_result.unwrap_err().to_string().contains(".m4b"));
 
         // Test metadata reading from invalid file
-        let metadata_result = read_audio_metadata("nonexistent.mp3".to_string());
+        let metadata_result = read_audio_metadata("nonexistent.mp3".to_string(), None);
         assert!(metadata_result.is_err(), "Should fail for nonexistent file");
         assert!(metadata_result.unwrap_err().to_string().contains("File not found"));
     }
@@ -257,7 +289,7 @@ mod integration_tests {
             let validation_result = validate_files(files.clone());
             assert!(validation_result.is_ok(), "Valid file should pass validation");
 
-            let analysis_result = analyze_audio_files(files);
+            let analysis_result = analyze_audio_files(files, None);
             assert!(analysis_result.is_ok(), "Valid file should be analyzable");
             
             let file_info = analysis_result.unwrap();
@@ -287,7 +319,7 @@ mod integration_tests {
         std::fs::write(&fake_audio, b"not audio content").unwrap();
         
         let files = vec![fake_audio.to_string_lossy().to_string()];
-        let analysis_result = analyze_audio_files(files);
+        let analysis_result = analyze_audio_files(files, None);
         assert!(analysis_result.is_ok(), "Analysis should succeed even for invalid files");
         
         let file_info = analysis_result.unwrap();
@@ -304,13 +336,13 @@ mod integration_tests {
         eprintln!("  Size: {:?} bytes", audio_file.size);
 
         // Test empty file list
-        let empty_result = analyze_audio_files(vec![]);
+        let empty_result = analyze_audio_files(vec![], None);
         assert!(empty_result.is_err(), "Empty file list should fail");
         assert!(empty_result.unwrap_err().to_string().contains("No files provided"));
 
         // Test nonexistent file
         let nonexistent_files = vec!["totally_nonexistent.mp3".to_string()];
-        let nonexistent_result = analyze_audio_files(nonexistent_files);
+        let nonexistent_result = analyze_audio_files(nonexistent_files, None);
         assert!(nonexistent_result.is_ok(), "Analysis should succeed for nonexistent files");
         
         let file_info = nonexistent_result.unwrap();
@@ -409,4 +441,235 @@ mod integration_tests {
         
         eprintln!("Temporary file handling behavior is captured through public API tests");
     }
-}
\ No newline at end of file
+
+    /// Re-encoding a single chaptered M4B keeps its chapters
+    #[tokio::test]
+    async fn test_single_m4b_reencode_keeps_chapters() {
+        use crate::audio::constants::FFMPEG_AUDIO_CODEC;
+        use crate::audio::progress_sink::null_sink;
+        use crate::audio::session::ProcessingSession;
+        use crate::audio::ProcessingContext;
+
+        let Ok(ffmpeg) = crate::ffmpeg::locate_ffmpeg() else {
+            eprintln!("Skipping test - FFmpeg not found");
+            return;
+        };
+        let encoders = std::process::Command::new(&ffmpeg).args(["-hide_banner", "-encoders"]).output().unwrap();
+        if !String::from_utf8_lossy(&encoders.stdout).contains(FFMPEG_AUDIO_CODEC) {
+            eprintln!("Skipping test - FFmpeg without {FFMPEG_AUDIO_CODEC}");
+            return;
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let chapters = temp_dir.path().join("chapters.txt");
+        std::fs::write(&chapters, ";FFMETADATA1\n\
+            [CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=2000\ntitle=Opening Credits\n\
+            [CHAPTER]\nTIMEBASE=1/1000\nSTART=2000\nEND=4000\ntitle=Chapter One\n\
+            [CHAPTER]\nTIMEBASE=1/1000\nSTART=4000\nEND=6000\ntitle=Chapter Two\n").unwrap();
+        let source = temp_dir.path().join("Book.m4b");
+        let status = std::process::Command::new(&ffmpeg)
+            .args(["-v", "error", "-f", "lavfi", "-i", "sine=frequency=440:duration=6", "-i"])
+            .arg(&chapters)
+            .args(["-map", "0:a", "-map_chapters", "1", "-c:a", FFMPEG_AUDIO_CODEC, "-b:a", "128k", "-y"])
+            .arg(&source)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let output = temp_dir.path().join("Book (64k).m4b");
+        let files = analyze_audio_files(vec![source.to_string_lossy().to_string()], None).unwrap().files;
+        let session = Arc::new(ProcessingSession::new());
+        let context = ProcessingContext::new(null_sink(), session, create_test_settings(output.clone()));
+        crate::audio::processor::process_audiobook_with_context(context, files, None).await.unwrap();
+
+        let titles: Vec<String> = read_audio_metadata(output.to_string_lossy().to_string(), None).unwrap()
+            .chapters.into_iter().map(|chapter| chapter.title).collect();
+        assert_eq!(titles, ["Opening Credits", "Chapter One", "Chapter Two"]);
+    }
+
+    /// Artwork embedded in an MP3 input carries into a book without a picked cover
+    #[tokio::test]
+    async fn test_input_cover_art_carries_into_output() {
+        use crate::audio::constants::FFMPEG_AUDIO_CODEC;
+        use crate::audio::progress_sink::null_sink;
+        use crate::audio::session::ProcessingSession;
+        use crate::audio::ProcessingContext;
+        use crate::metadata::placeholder_cover::render_placeholder_cover;
+        use crate::metadata::writer::write_cover_art;
+
+        let Ok(media_path) = verify_test_media_exists() else {
+            eprintln!("Skipping test - media file not found: {TEST_MEDIA_FILE}");
+            return;
+        };
+        let Ok(ffmpeg) = crate::ffmpeg::locate_ffmpeg() else {
+            eprintln!("Skipping test - FFmpeg not found");
+            return;
+        };
+        let encoders = std::process::Command::new(&ffmpeg).args(["-hide_banner", "-encoders"]).output().unwrap();
+        if !String::from_utf8_lossy(&encoders.stdout).contains(FFMPEG_AUDIO_CODEC) {
+            eprintln!("Skipping test - FFmpeg without {FFMPEG_AUDIO_CODEC}");
+            return;
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("01 - Introduction.mp3");
+        std::fs::copy(&media_path, &source).unwrap();
+        let cover = render_placeholder_cover("Dune", Some("Frank Herbert")).unwrap();
+        write_cover_art(&source, &cover).unwrap();
+
+        let output = temp_dir.path().join("Dune.m4b");
+        let files = analyze_audio_files(vec![source.to_string_lossy().to_string()], None).unwrap().files;
+        let session = Arc::new(ProcessingSession::new());
+        let context = ProcessingContext::new(null_sink(), session, create_test_settings(output.clone()));
+        crate::audio::processor::process_audiobook_with_context(context, files, None).await.unwrap();
+
+        let metadata = read_audio_metadata(output.to_string_lossy().to_string(), None).unwrap();
+        assert_eq!(metadata.cover_art.as_deref(), Some(cover.as_slice()));
+    }
+
+    /// A soft stop during a segmented merge ends the run at the next segment boundary
+    #[tokio::test]
+    async fn test_soft_stop_ends_segmented_merge_between_segments() {
+        use crate::audio::constants::FFMPEG_AUDIO_CODEC;
+        use crate::audio::progress_sink::ProgressSink;
+        use crate::audio::session::ProcessingSession;
+        use crate::audio::ProcessingContext;
+        use crate::errors::{AppError, CancelReason};
+
+        /// Requests a soft stop as soon as the first segment reports progress
+        #[derive(Debug)]
+        struct SoftStopSink(Arc<ProcessingSession>);
+
+        impl ProgressSink for SoftStopSink {
+            fn emit_value(&self, _event: &str, _payload: serde_json::Value) -> std::result::Result<(), String> {
+                self.0.request_soft_stop();
+                Ok(())
+            }
+        }
+
+        let Ok(ffmpeg) = crate::ffmpeg::locate_ffmpeg() else {
+            eprintln!("Skipping test - FFmpeg not found");
+            return;
+        };
+        let encoders = std::process::Command::new(&ffmpeg).args(["-hide_banner", "-encoders"]).output().unwrap();
+        if !String::from_utf8_lossy(&encoders.stdout).contains(FFMPEG_AUDIO_CODEC) {
+            eprintln!("Skipping test - FFmpeg without {FFMPEG_AUDIO_CODEC}");
+            return;
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut inputs = Vec::new();
+        for part in 1..=4 {
+            let input = temp_dir.path().join(format!("{part:02}.m4a"));
+            let status = std::process::Command::new(&ffmpeg)
+                .args(["-v", "error", "-f", "lavfi", "-i", "sine=frequency=440:duration=2", "-c:a", FFMPEG_AUDIO_CODEC, "-y"])
+                .arg(&input)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            inputs.push(input.to_string_lossy().to_string());
+        }
+
+        let output = temp_dir.path().join("Book.m4b");
+        let mut settings = create_test_settings(output.clone());
+        settings.segment_threshold = Some(2);
+        let files = analyze_audio_files(inputs, None).unwrap().files;
+        let session = Arc::new(ProcessingSession::new());
+        let sink = Arc::new(SoftStopSink(Arc::clone(&session)));
+        let context = ProcessingContext::new(sink, Arc::clone(&session), settings);
+        let error = crate::audio::processor::process_audiobook_with_context(context, files, None).await.unwrap_err();
+
+        assert!(matches!(error, AppError::Cancelled(CancelReason::SoftStopped)), "{error}");
+        assert_eq!(session.cancel_reason(), Some(CancelReason::SoftStopped));
+        assert!(!output.exists());
+    }
+
+    /// A segmented merge through the whole pipeline: duration, chapter marks across
+    /// segment boundaries and progress that never goes backwards
+    #[tokio::test]
+    async fn test_segmented_merge_of_micro_fixtures_end_to_end() {
+        use crate::audio::constants::FFMPEG_AUDIO_CODEC;
+        use crate::audio::progress_sink::ProgressSink;
+        use crate::audio::session::ProcessingSession;
+        use crate::audio::ProcessingContext;
+        use lofty::file::AudioFile as _;
+
+        /// Keeps the percentage of every progress event
+        #[derive(Debug, Default)]
+        struct PercentageSink(Mutex<Vec<f64>>);
+
+        impl ProgressSink for PercentageSink {
+            fn emit_value(&self, event: &str, payload: serde_json::Value) -> std::result::Result<(), String> {
+                if let (Some(percentage), "processing-progress") = (payload["percentage"].as_f64(), event) {
+                    self.0.lock().map_err(|e| e.to_string())?.push(percentage);
+                }
+                Ok(())
+            }
+        }
+
+        let Ok(ffmpeg) = crate::ffmpeg::locate_ffmpeg() else {
+            eprintln!("Skipping test - FFmpeg not found");
+            return;
+        };
+        let encoders = std::process::Command::new(&ffmpeg).args(["-hide_banner", "-encoders"]).output().unwrap();
+        if !String::from_utf8_lossy(&encoders.stdout).contains(FFMPEG_AUDIO_CODEC) {
+            eprintln!("Skipping test - FFmpeg without {FFMPEG_AUDIO_CODEC}");
+            return;
+        }
+
+        // Twelve half-second parts in segments of four: boundaries at 2 s and 4 s
+        const PARTS: usize = 12;
+        const PART_SECONDS: f64 = 0.5;
+        let temp_dir = TempDir::new().unwrap();
+        let mut inputs = Vec::new();
+        for part in 1..=PARTS {
+            let input = temp_dir.path().join(format!("{part:02}.m4a"));
+            let source = format!("sine=frequency={}:duration={PART_SECONDS}", 200 + 40 * part);
+            let status = std::process::Command::new(&ffmpeg)
+                .args(["-v", "error", "-f", "lavfi", "-i", &source, "-c:a", FFMPEG_AUDIO_CODEC, "-y"])
+                .arg(&input)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            inputs.push(input.to_string_lossy().to_string());
+        }
+
+        let output = temp_dir.path().join("Book.m4b");
+        let mut settings = create_test_settings(output.clone());
+        settings.segment_threshold = Some(4);
+        let files = analyze_audio_files(inputs, None).unwrap().files;
+        let expected_starts: Vec<f64> = files.iter()
+            .scan(0.0, |start, file| {
+                let this = *start;
+                *start += file.duration.unwrap();
+                Some(this)
+            })
+            .collect();
+        let total: f64 = files.iter().map(|file| file.duration.unwrap()).sum();
+
+        let sink = Arc::new(PercentageSink::default());
+        let session = Arc::new(ProcessingSession::new());
+        let context = ProcessingContext::new(sink.clone(), session, settings);
+        crate::audio::processor::process_audiobook_with_context(context, files, None).await.unwrap();
+
+        let tagged = lofty::probe::Probe::open(&output).unwrap().read().unwrap();
+        let duration = tagged.properties().duration().as_secs_f64();
+        assert!((duration - total).abs() < 0.25, "duration {duration}, inputs {total}");
+
+        let chapters = read_audio_metadata(output.to_string_lossy().to_string(), None).unwrap().chapters;
+        assert_eq!(chapters.len(), PARTS);
+        for (chapter, expected) in chapters.iter().zip(&expected_starts) {
+            let start = chapter.start_ms as f64 / 1000.0;
+            assert!((start - expected).abs() < 0.1, "{} starts at {start}, expected {expected}", chapter.title);
+        }
+        for pair in chapters.windows(2) {
+            assert_eq!(pair[0].end_ms, pair[1].start_ms);
+        }
+
+        let percentages = sink.0.lock().unwrap().clone();
+        assert_eq!(percentages.last(), Some(&100.0));
+        for pair in percentages.windows(2) {
+            assert!(pair[1] >= pair[0], "progress went back from {} to {}", pair[0], pair[1]);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Streaming file hashing
+//!
+//! Inputs can be several GB, so files are read in `HASH_CHUNK_BYTES`
+//! chunks instead of all at once. Cancellation is checked and progress
+//! reported between chunks.
+
+use crate::audio::constants::HASH_CHUNK_BYTES;
+use crate::errors::{AppError, CancelReason, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Digest used for a file hash
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    /// SHA-256, hex encoded
+    #[default]
+    Sha256,
+}
+
+/// Hashes a file in chunks, returning the lowercase hex digest
+///
+/// `on_progress` gets the bytes hashed so far and the file size after
+/// every chunk.
+///
+/// # Errors
+/// `Cancelled` when `is_cancelled` returns true between chunks
+pub fn hash_file<C, P>(path: &Path, algorithm: HashAlgorithm, is_cancelled: C, on_progress: P) -> Result<String>
+where
+    C: Fn() -> bool,
+    P: FnMut(u64, u64),
+{
+    let file = File::open(path)
+        .map_err(|e| AppError::FileValidation(format!("Cannot open {} for hashing: {e}", path.display())))?;
+    let total = file.metadata()?.len();
+    hash_reader(file, total, algorithm, HASH_CHUNK_BYTES, is_cancelled, on_progress)
+}
+
+/// SHA-256 of in-memory data, hex encoded
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Reads `reader` in `chunk_bytes` chunks into the digest
+fn hash_reader<R, C, P>(
+    mut reader: R,
+    total: u64,
+    algorithm: HashAlgorithm,
+    chunk_bytes: usize,
+    is_cancelled: C,
+    mut on_progress: P,
+) -> Result<String>
+where
+    R: Read,
+    C: Fn() -> bool,
+    P: FnMut(u64, u64),
+{
+    let mut hasher = match algorithm {
+        HashAlgorithm::Sha256 => Sha256::new(),
+    };
+    let mut buffer = vec![0u8; chunk_bytes];
+    let mut hashed = 0u64;
+    loop {
+        if is_cancelled() {
+            return Err(AppError::Cancelled(CancelReason::UserRequested));
+        }
+        let read = read_chunk(&mut reader, &mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        hashed += read as u64;
+        on_progress(hashed, total);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Fills `buffer` unless the reader ends first; returns the bytes read
+fn read_chunk<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn test_known_digests() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let abc = temp_dir.path().join("abc.txt");
+        std::fs::write(&abc, b"abc").unwrap();
+        let empty = temp_dir.path().join("empty.txt");
+        std::fs::write(&empty, b"").unwrap();
+
+        assert_eq!(hash_file(&abc, HashAlgorithm::Sha256, || false, |_, _| {}).unwrap(), ABC_SHA256);
+        assert_eq!(hash_file(&empty, HashAlgorithm::Sha256, || false, |_, _| {}).unwrap(), EMPTY_SHA256);
+        assert_eq!(sha256_hex(b"abc"), ABC_SHA256);
+        assert!(hash_file(&temp_dir.path().join("missing"), HashAlgorithm::Sha256, || false, |_, _| {}).is_err());
+    }
+
+    #[test]
+    fn test_chunked_digest_matches_whole_and_reports_each_chunk() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let mut progress = Vec::new();
+        let digest = hash_reader(&data[..], data.len() as u64, HashAlgorithm::Sha256, 4096, || false, |done, total| {
+            progress.push((done, total));
+        })
+        .unwrap();
+
+        assert_eq!(digest, sha256_hex(&data));
+        assert_eq!(progress, [(4096, 10_000), (8192, 10_000), (10_000, 10_000)]);
+    }
+
+    #[test]
+    fn test_cancellation_mid_file() {
+        let data = vec![7u8; 10_000];
+        let chunks = Cell::new(0);
+        let result = hash_reader(&data[..], data.len() as u64, HashAlgorithm::Sha256, 1024, || chunks.get() >= 2, |_, _| {
+            chunks.set(chunks.get() + 1);
+        });
+
+        assert!(matches!(result, Err(AppError::Cancelled(CancelReason::UserRequested))));
+        assert_eq!(chunks.get(), 2);
+    }
+}
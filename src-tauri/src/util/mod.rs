@@ -0,0 +1,4 @@
+//! Helpers shared by the audio and metadata modules
+
+pub mod hashing;
+pub mod path_policy;
@@ -0,0 +1,241 @@
+//! Paths the user has granted the app access to
+//!
+//! Commands take paths from the webview, so a compromised frontend could
+//! otherwise read or overwrite any file the user can. Paths the user picks
+//! in a file dialog or drops on the window are granted; a granted directory
+//! covers everything below it. Every path a command accepts must resolve
+//! inside a grant: symlinks are resolved and `..` can't climb out, and a
+//! path that doesn't exist yet (an output) is checked through its nearest
+//! existing ancestor. Power users can turn the check off with
+//! `{"permissivePathAccess": true}` in `path-policy.json` in the app data
+//! directory. The file is read once at startup and no command changes it,
+//! so the webview can't switch the check off.
+
+use crate::audio::constants::PATH_POLICY_CONFIG_FILENAME;
+use crate::errors::{AppError, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// Process-wide policy used by commands
+static PATH_POLICY: OnceLock<PathPolicy> = OnceLock::new();
+
+/// Granted roots and whether they are enforced
+#[derive(Debug, Default)]
+pub struct PathPolicy {
+    /// Canonical granted paths
+    roots: Mutex<Vec<PathBuf>>,
+    /// Accept every path; the preference for power users
+    permissive: AtomicBool,
+}
+
+impl PathPolicy {
+    /// Grants `path` and, for a directory, everything below it
+    ///
+    /// Returns the canonical path recorded. The path itself need not exist
+    /// (a save dialog result), but its parent must.
+    pub fn grant(&self, path: &Path) -> Result<PathBuf> {
+        if !path.is_absolute() {
+            return Err(AppError::InvalidInput(format!("Granted path must be absolute, got: {}", path.display())));
+        }
+        let resolved = resolve(path)?;
+        let mut roots = self.roots.lock()
+            .map_err(|_| AppError::General("Path policy poisoned".to_string()))?;
+        if !roots.contains(&resolved) {
+            log::info!("Granted access to {}", resolved.display());
+            roots.push(resolved.clone());
+        }
+        Ok(resolved)
+    }
+
+    /// Accepts every path when set
+    pub fn set_permissive(&self, permissive: bool) {
+        self.permissive.store(permissive, Ordering::SeqCst);
+    }
+
+    /// Whether every path is accepted
+    pub fn is_permissive(&self) -> bool {
+        self.permissive.load(Ordering::SeqCst)
+    }
+
+    /// Fails unless `path` resolves inside a granted root
+    pub fn check(&self, path: &Path) -> Result<()> {
+        if self.is_permissive() {
+            return Ok(());
+        }
+        let denied = || AppError::PathNotGranted(path.display().to_string());
+        if !path.is_absolute() {
+            return Err(denied());
+        }
+        let resolved = resolve(path).map_err(|_| denied())?;
+        let roots = self.roots.lock()
+            .map_err(|_| AppError::General("Path policy poisoned".to_string()))?;
+        if roots.iter().any(|root| resolved.starts_with(root)) {
+            Ok(())
+        } else {
+            log::warn!("Rejected path outside granted roots: {}", resolved.display());
+            Err(denied())
+        }
+    }
+
+    /// Checks every path in `paths`
+    pub fn check_all<P: AsRef<Path>>(&self, paths: &[P]) -> Result<()> {
+        paths.iter().try_for_each(|path| self.check(path.as_ref()))
+    }
+}
+
+/// Preference file the user edits by hand
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PathPolicyConfig {
+    #[serde(default)]
+    permissive_path_access: bool,
+}
+
+/// Whether the config file in `config_dir` turns the check off
+///
+/// A missing file means enforced; an unreadable one is logged and also
+/// means enforced.
+pub fn permissive_preference(config_dir: &Path) -> bool {
+    let path = config_dir.join(PATH_POLICY_CONFIG_FILENAME);
+    let Ok(bytes) = std::fs::read(&path) else {
+        return false;
+    };
+    match serde_json::from_slice::<PathPolicyConfig>(&bytes) {
+        Ok(config) => config.permissive_path_access,
+        Err(e) => {
+            log::warn!("Ignoring malformed path policy config '{}': {e}", path.display());
+            false
+        }
+    }
+}
+
+/// Starts enforcing the process-wide policy; called once at app startup
+///
+/// The check is off only when the config file in `config_dir` says so.
+pub fn init_path_policy(config_dir: Option<&Path>) {
+    let policy = PathPolicy::default();
+    if config_dir.is_some_and(permissive_preference) {
+        log::warn!("Permissive path access enabled by {PATH_POLICY_CONFIG_FILENAME}");
+        policy.set_permissive(true);
+    }
+    if PATH_POLICY.set(policy).is_err() {
+        log::warn!("Path policy already initialized");
+    }
+}
+
+/// Returns the process-wide policy if it has been initialized
+pub fn path_policy() -> Option<&'static PathPolicy> {
+    PATH_POLICY.get()
+}
+
+/// Checks `paths` against the process-wide policy
+///
+/// Without an initialized policy (tests, library use) every path is accepted.
+pub fn check_granted<P: AsRef<Path>>(paths: &[P]) -> Result<()> {
+    match path_policy() {
+        Some(policy) => policy.check_all(paths),
+        None => Ok(()),
+    }
+}
+
+/// Canonical form of `path`, resolving symlinks through its nearest existing ancestor
+///
+/// The part below that ancestor may only hold plain names, so `..` can't
+/// step back out of it.
+fn resolve(path: &Path) -> Result<PathBuf> {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    while !existing.exists() {
+        // `file_name` is None for a trailing `..`
+        let (Some(name), Some(parent)) = (existing.file_name(), existing.parent()) else {
+            return Err(AppError::InvalidInput(format!("Path steps out of its folder: {}", path.display())));
+        };
+        missing.push(name.to_os_string());
+        existing = parent;
+    }
+    let mut resolved = std::fs::canonicalize(existing)?;
+    resolved.extend(missing.iter().rev());
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn granted_library() -> (TempDir, PathPolicy) {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("library").join("dune")).unwrap();
+        std::fs::write(temp_dir.path().join("library").join("dune").join("01.mp3"), b"audio").unwrap();
+        std::fs::write(temp_dir.path().join("secret.txt"), b"secret").unwrap();
+        let policy = PathPolicy::default();
+        policy.grant(&temp_dir.path().join("library")).unwrap();
+        (temp_dir, policy)
+    }
+
+    #[test]
+    fn test_grant_covers_descendants_and_new_outputs() {
+        let (temp_dir, policy) = granted_library();
+        let library = temp_dir.path().join("library");
+        assert!(policy.check(&library.join("dune").join("01.mp3")).is_ok());
+        assert!(policy.check(&library.join("dune").join("Dune.m4b")).is_ok());
+        assert!(policy.check(&library.join("new").join("Dune.m4b")).is_ok());
+
+        let error = policy.check(&temp_dir.path().join("secret.txt")).unwrap_err();
+        assert!(matches!(error, AppError::PathNotGranted(_)));
+        assert!(policy.check(Path::new("library/dune/01.mp3")).is_err());
+
+        let save_target = temp_dir.path().join("Book.m4b");
+        policy.grant(&save_target).unwrap();
+        assert!(policy.check(&save_target).is_ok());
+        assert!(policy.check(&temp_dir.path().join("Other.m4b")).is_err());
+        assert!(policy.grant(Path::new("relative")).is_err());
+    }
+
+    #[test]
+    fn test_traversal_cannot_escape_root() {
+        let (temp_dir, policy) = granted_library();
+        let library = temp_dir.path().join("library");
+        assert!(policy.check(&library.join("..").join("secret.txt")).is_err());
+        assert!(policy.check(&library.join("dune").join("..").join("..").join("secret.txt")).is_err());
+        assert!(policy.check(&library.join("missing").join("..").join("..").join("secret.txt")).is_err());
+        assert!(policy.check(&library.join("dune").join("..").join("dune").join("01.mp3")).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_cannot_escape_root() {
+        let (temp_dir, policy) = granted_library();
+        let library = temp_dir.path().join("library");
+        std::os::unix::fs::symlink(temp_dir.path().join("secret.txt"), library.join("innocent.mp3")).unwrap();
+        std::os::unix::fs::symlink(temp_dir.path(), library.join("outside")).unwrap();
+        assert!(policy.check(&library.join("innocent.mp3")).is_err());
+        assert!(policy.check(&library.join("outside").join("secret.txt")).is_err());
+        assert!(policy.check(&library.join("outside").join("new.m4b")).is_err());
+    }
+
+    #[test]
+    fn test_permissive_preference_from_config_file() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(!permissive_preference(temp_dir.path()));
+        let config = temp_dir.path().join(PATH_POLICY_CONFIG_FILENAME);
+        std::fs::write(&config, br#"{"permissivePathAccess": true}"#).unwrap();
+        assert!(permissive_preference(temp_dir.path()));
+        std::fs::write(&config, b"{}").unwrap();
+        assert!(!permissive_preference(temp_dir.path()));
+        std::fs::write(&config, b"not json").unwrap();
+        assert!(!permissive_preference(temp_dir.path()));
+    }
+
+    #[test]
+    fn test_permissive_accepts_everything() {
+        let (temp_dir, policy) = granted_library();
+        policy.set_permissive(true);
+        assert!(policy.check(&temp_dir.path().join("secret.txt")).is_ok());
+        assert!(policy.check_all(&["relative.mp3"]).is_ok());
+        policy.set_permissive(false);
+        assert!(policy.check_all(&[temp_dir.path().join("secret.txt")]).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { AudiobookMetadata } from "./types/metadata";
-import type { FileListInfo, AudioSettings } from "./types/audio";
+import type { AudiobookMetadata, ChapterInfo, CoverArt, CoverAssessment, InferredMetadata, MergeStrategy, MetadataProfile, MetadataWriteReport } from "./types/metadata";
+import type { ProgressSnapshotEvent } from "./types/events";
+import type { AnalysisDepth, DiskSpacePolicy, FileListInfo, AudioSettings, AudiobookJob, SeriesAutoNumber, EncodeEstimate, ProcessingProgress, InputFileSpec, Chapter, ChapterFormat, ChapterOptions, ChapterSpec, SilenceSuggestions, PlanResolution, FFmpegDiagnostics, SelfTestReport, SpaceForecast } from "./types/audio";
 import { initFileImport } from "./ui/fileImport";
 import { displayFileList, currentFileList, clearAllFiles, toggleFileSort } from "./ui/fileList";
 import { initOutputPanel, getCurrentAudioSettings, onFileListChange, onMetadataChange } from "./ui/outputPanel";
@@ -13,21 +14,81 @@ import { initCoverArt, getCurrentCoverArt, setCoverArt, clearCoverArt } from "./
   echo: (input: string) => invoke('echo', { input }),
   validateFiles: (paths: string[]) => invoke('validate_files', { filePaths: paths }),
   getFFmpegVersion: () => invoke('get_ffmpeg_version'),
+  getFFmpegDiagnostics: () => invoke<FFmpegDiagnostics>('get_ffmpeg_diagnostics'),
+  runSelfTest: () => invoke<SelfTestReport>('run_self_test'),
+  setFFmpegOverride: (path: string | null) => invoke('set_ffmpeg_override', { path }),
+  probeFileRaw: (filePath: string) => invoke('probe_file_raw', { filePath }),
   mergeAudioFiles: (file1: string, file2: string) => invoke('merge_audio_files', { file1, file2 }),
   
   // Metadata commands
-  readMetadata: (filePath: string) => invoke<AudiobookMetadata>('read_audio_metadata', { filePath: filePath }),
-  writeMetadata: (filePath: string, metadata: AudiobookMetadata) => 
-    invoke('write_audio_metadata', { filePath: filePath, metadata }),
+  readMetadata: (filePath: string, includeCover?: boolean) =>
+    invoke<AudiobookMetadata>('read_audio_metadata', { filePath: filePath, includeCover }),
+  readCoverArt: (filePath: string) => invoke<CoverArt | null>('read_cover_art', { filePath }),
+  writeMetadata: (filePath: string, metadata: AudiobookMetadata, strict?: boolean, clearUnknown?: boolean) => 
+    invoke<MetadataWriteReport>('write_audio_metadata', { filePath: filePath, metadata, strict, clearUnknown }),
   writeCoverArt: (filePath: string, coverData: number[]) => 
     invoke('write_cover_art', { filePath: filePath, coverData: coverData }),
+  removeCoverArt: (filePath: string, allPictures?: boolean) => invoke('remove_cover_art', { filePath, allPictures }),
+  writeChapters: (filePath: string, chapters: ChapterInfo[], chapterFormat?: ChapterFormat) =>
+    invoke('write_audio_chapters', { filePath, chapters, chapterFormat }),
+  readCueSheet: (filePath: string) => invoke<ChapterSpec[]>('read_cue_sheet', { filePath }),
+  retryMetadataWrite: (outputPath: string, metadata: AudiobookMetadata, reproducible?: boolean, strict?: boolean) =>
+    invoke<MetadataWriteReport>('retry_metadata_write', { outputPath, metadata, reproducible, strict }),
+  undoMetadataWrite: (filePath: string) => invoke<AudiobookMetadata>('undo_metadata_write', { filePath }),
   loadCoverArtFile: (filePath: string) => invoke('load_cover_art_file', { filePath }),
+  loadDescriptionFile: (filePath: string) => invoke<string>('load_description_file', { filePath }),
+  isFileInUse: (filePath: string) => invoke<boolean | null>('is_file_in_use', { filePath }),
+  assessCoverArt: (data: number[]) => invoke<CoverAssessment>('assess_cover_art', { data }),
+  findLocalCover: (filePaths: string[]) => invoke<string | null>('find_local_cover', { filePaths }),
   
   // Audio processing commands
-  analyzeAudioFiles: (filePaths: string[]) => invoke<FileListInfo>('analyze_audio_files', { filePaths: filePaths }),
+  analyzeAudioFiles: (filePaths: string[], depth?: AnalysisDepth) =>
+    invoke<FileListInfo>('analyze_audio_files', { filePaths: filePaths, depth }),
+  detectDualMono: (filePaths: string[]) => invoke<FileListInfo>('detect_dual_mono', { filePaths }),
+  measureMp3Durations: (filePaths: string[]) => invoke<FileListInfo>('measure_mp3_durations', { filePaths }),
+  extractPeaks: (filePath: string, buckets: number) => invoke<number[]>('extract_peaks', { filePath, buckets }),
+  cancelPeakExtraction: () => invoke('cancel_peak_extraction'),
+  estimateEncodeTime: (filePaths: string[], settings: AudioSettings) =>
+    invoke<EncodeEstimate | null>('estimate_encode_time', { filePaths, settings }),
+  cancelEncodeCalibration: () => invoke('cancel_encode_calibration'),
+  estimateChapters: (filePaths: InputFileSpec[], chapterOptions: ChapterOptions) =>
+    invoke<Chapter[]>('estimate_chapters', { filePaths, chapterOptions }),
+  previewChapters: (filePaths: InputFileSpec[], settings: AudioSettings, chapterOptions?: ChapterOptions) =>
+    invoke<ChapterInfo[]>('preview_chapters', { filePaths, settings, chapterOptions: chapterOptions ?? null }),
+  suggestChaptersFromSilence: (filePath: string, minSilenceSecs: number, thresholdDb: number) =>
+    invoke<SilenceSuggestions>('suggest_chapters_from_silence', { filePath, minSilenceSecs, thresholdDb }),
+  sanitizeFilename: (name: string, strictCrossPlatform: boolean) =>
+    invoke<string>('sanitize_filename', { name, strictCrossPlatform }),
+  setDefaultOutputDir: (dir: string) => invoke<string>('set_default_output_dir', { dir }),
+  getDefaultOutputDir: () => invoke<string>('get_default_output_dir'),
+  setDisplayLocale: (locale: string) => invoke<string>('set_display_locale', { locale }),
+  setPostProcessHook: (hook: string | null) => invoke('set_post_process_hook', { hook }),
+  getPostProcessHook: () => invoke<string | null>('get_post_process_hook'),
+  setAnalysisTimeout: (seconds?: number) => invoke('set_analysis_timeout', { seconds }),
+  setDiscMarkers: (markers?: string[]) => invoke('set_disc_markers', { markers }),
+  updateDefaultMetadataProfile: (profile: MetadataProfile) => invoke('update_default_metadata_profile', { profile }),
+  getDefaultMetadataProfile: () => invoke<MetadataProfile>('get_default_metadata_profile'),
+  inferBookMetadata: (filePaths: string[], strategy?: MergeStrategy) =>
+    invoke<InferredMetadata>('infer_book_metadata', { filePaths, strategy }),
+  registerGrantedPath: (path: string) => invoke<string>('register_granted_path', { path }),
+  getPermissivePathAccess: () => invoke<boolean>('get_permissive_path_access'),
   validateAudioSettings: (settings: AudioSettings) => invoke('validate_audio_settings', { settings }),
-  processAudiobook: (filePaths: string[], settings: AudioSettings, metadata?: AudiobookMetadata) => 
-    invoke('process_audiobook_files', { filePaths: filePaths, settings, metadata }),
+  validateMetadata: (metadata: AudiobookMetadata) => invoke<string>('validate_metadata', { metadata }),
+  resolvePlan: (filePaths: InputFileSpec[], settings: AudioSettings, chapters?: ChapterSpec[]) =>
+    invoke<PlanResolution>('resolve_plan', { filePaths, settings, chapters }),
+  processAudiobook: (filePaths: InputFileSpec[], settings: AudioSettings, metadata?: AudiobookMetadata, label?: string, notes?: string, chapters?: ChapterSpec[]) => 
+    invoke('process_audiobook_files', { filePaths: filePaths, settings, metadata, label, notes, chapters }),
+  enqueueAudiobookJob: (filePaths: string[], settings: AudioSettings, metadata?: AudiobookMetadata, label?: string, notes?: string) =>
+    invoke<string>('enqueue_audiobook_job', { filePaths, settings, metadata, label, notes }),
+  enqueueAudiobookBatch: (jobs: AudiobookJob[], seriesAutonumber?: SeriesAutoNumber) =>
+    invoke<string[]>('enqueue_audiobook_batch', { jobs, seriesAutonumber }),
+  getQueuedJobs: () => invoke('get_queued_jobs'),
+  refreshQueuedJobSettings: (jobId: string) => invoke('refresh_queued_job_settings', { jobId }),
+  getQueueForecast: () => invoke<SpaceForecast[]>('get_queue_forecast'),
+  processAudiobookQueue: (diskSpacePolicy?: DiskSpacePolicy) =>
+    invoke<string[]>('process_audiobook_queue', { diskSpacePolicy }),
+  getFailedJobs: () => invoke('get_failed_jobs'),
+  retryFailedJob: (jobId: string) => invoke('retry_failed_job', { jobId }),
 
   // UI test functions
   testDisplayList: (fileListInfo: FileListInfo) => displayFileList(fileListInfo),
@@ -52,6 +113,14 @@ import { initCoverArt, getCurrentCoverArt, setCoverArt, clearCoverArt } from "./
   
   // Status panel test functions
   cancelProcessing: () => invoke('cancel_processing'),
+  cancelSession: (sessionId: string) => invoke('cancel_session', { sessionId }),
+  softStopProcessing: (sessionId: string) => invoke('soft_stop_processing', { sessionId }),
+  getSessionTimeline: (sessionId?: string) => invoke('get_session_timeline', { sessionId }),
+  subscribeProgress: (sessionId?: string) => invoke<ProgressSnapshotEvent>('subscribe_progress', { sessionId }),
+  getProcessingProgress: (sessionId?: string) => invoke<ProcessingProgress | null>('get_processing_progress', { sessionId }),
+  getProcessingHistory: () => invoke('get_processing_history'),
+  checkAlreadyProcessed: (filePaths: string[], settings: AudioSettings, metadata?: AudiobookMetadata) =>
+    invoke('check_already_processed', { filePaths, settings, metadata }),
   
   // Cover art test functions
   getCurrentCoverArt: () => getCurrentCoverArt(),
@@ -78,13 +147,62 @@ console.log('  window.testCommands.ping()');
 console.log('  window.testCommands.echo(input)');
 console.log('  window.testCommands.validateFiles(paths)');
 console.log('  window.testCommands.getFFmpegVersion()');
+console.log('  window.testCommands.getFFmpegDiagnostics()');
+console.log('  window.testCommands.runSelfTest()');
+console.log('  window.testCommands.setFFmpegOverride(path | null)');
+console.log('  window.testCommands.probeFileRaw(filePath)');
 console.log('  window.testCommands.mergeAudioFiles(file1, file2)');
-console.log('  window.testCommands.readMetadata(filePath)');
-console.log('  window.testCommands.writeMetadata(filePath, metadata)');
+console.log('  window.testCommands.readMetadata(filePath, includeCover?)');
+console.log('  window.testCommands.readCoverArt(filePath)');
+console.log('  window.testCommands.writeMetadata(filePath, metadata, strict?, clearUnknown?)');
 console.log('  window.testCommands.writeCoverArt(filePath, coverData)');
-console.log('  window.testCommands.analyzeAudioFiles(filePaths)');
+console.log('  window.testCommands.removeCoverArt(filePath, allPictures?)');
+console.log('  window.testCommands.writeChapters(filePath, chapters, chapterFormat?)');
+console.log('  window.testCommands.readCueSheet(filePath)');
+console.log('  window.testCommands.retryMetadataWrite(outputPath, metadata, reproducible?, strict?)');
+console.log('  window.testCommands.undoMetadataWrite(filePath)');
+console.log('  window.testCommands.analyzeAudioFiles(filePaths, depth?)');
+console.log('  window.testCommands.detectDualMono(filePaths)');
+console.log('  window.testCommands.measureMp3Durations(filePaths)');
+console.log('  window.testCommands.extractPeaks(filePath, buckets)');
+console.log('  window.testCommands.cancelPeakExtraction()');
+console.log('  window.testCommands.estimateEncodeTime(filePaths, settings)');
+console.log('  window.testCommands.cancelEncodeCalibration()');
+console.log('  window.testCommands.estimateChapters(filePaths, { mode: "FixedInterval", intervalMinutes: 15 })');
+console.log('  window.testCommands.previewChapters(filePaths, settings, chapterOptions?)');
+console.log('  window.testCommands.suggestChaptersFromSilence(filePath, 2, -30)');
+console.log('  window.testCommands.sanitizeFilename(name, strictCrossPlatform)');
+console.log('  window.testCommands.setDefaultOutputDir(dir)');
+console.log('  window.testCommands.getDefaultOutputDir()');
+console.log('  window.testCommands.setDisplayLocale("de-DE")');
+console.log('  window.testCommands.setPostProcessHook(hook)');
+console.log('  window.testCommands.getPostProcessHook()');
+console.log('  window.testCommands.setAnalysisTimeout(seconds?)');
+console.log('  window.testCommands.setDiscMarkers(markers?)');
+console.log('  window.testCommands.updateDefaultMetadataProfile(profile)');
+console.log('  window.testCommands.getDefaultMetadataProfile()');
+console.log('  window.testCommands.inferBookMetadata(filePaths, strategy?)');
+console.log('  window.testCommands.registerGrantedPath(path)');
+console.log('  window.testCommands.getPermissivePathAccess()');
 console.log('  window.testCommands.validateAudioSettings(settings)');
-console.log('  window.testCommands.processAudiobook(filePaths, settings, metadata?)');
+console.log('  window.testCommands.validateMetadata(metadata)');
+console.log('  window.testCommands.resolvePlan(filePaths, settings, chapters?)');
+console.log('  window.testCommands.processAudiobook(filePaths, settings, metadata?, label?, notes?, chapters?)');
+console.log('  window.testCommands.enqueueAudiobookJob(filePaths, settings, metadata?, label?, notes?)');
+console.log('  window.testCommands.enqueueAudiobookBatch(jobs, seriesAutonumber?)');
+console.log('  window.testCommands.getQueuedJobs()');
+console.log('  window.testCommands.refreshQueuedJobSettings(jobId)');
+console.log('  window.testCommands.getQueueForecast()');
+console.log('  window.testCommands.processAudiobookQueue(diskSpacePolicy?)');
+console.log('  window.testCommands.getFailedJobs()');
+console.log('  window.testCommands.retryFailedJob(jobId)');
+console.log('  window.testCommands.cancelSession(sessionId)');
+console.log('  window.testCommands.softStopProcessing(sessionId)');
+console.log('  window.testCommands.getSessionTimeline(sessionId?)');
+console.log('  window.testCommands.subscribeProgress(sessionId?)');
+console.log('  window.testCommands.getProcessingProgress(sessionId?)');
+console.log('  window.testCommands.getProcessingHistory()');
+console.log('  window.testCommands.checkAlreadyProcessed(filePaths, settings, metadata?)');
 console.log('  window.testCommands.testDisplayList(fileListInfo)');
 console.log('  window.testCommands.getCurrentFileList()');
 console.log('  window.testCommands.clearFiles()');
@@ -93,6 +211,10 @@ console.log('  window.testCommands.triggerFileListChange()');
 console.log('  window.testCommands.triggerMetadataChange()');
 console.log('  window.testCommands.testArtThumbnail()');
 console.log('  window.testCommands.loadCoverArtFile(filePath)');
+console.log('  window.testCommands.loadDescriptionFile(filePath)');
+console.log('  window.testCommands.isFileInUse(filePath)');
+console.log('  window.testCommands.assessCoverArt(data)');
+console.log('  window.testCommands.findLocalCover(filePaths)');
 console.log('  window.testCommands.getCurrentCoverArt()');
 console.log('  window.testCommands.setCoverArt(coverArtBytes)');
 console.log('  window.testCommands.clearCoverArt()');
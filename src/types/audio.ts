@@ -1,23 +1,95 @@
 // TypeScript interfaces for audio processing
 
+import type { AudiobookMetadata, ChapterInfo } from "./metadata";
+import type { SkippedInput } from "./events";
+
 export interface AudioFile {
   path: string;
   size?: number;
   duration?: number;
+  /** Duration the MP3 header implied, when `duration` was measured by decoding or counting frames */
+  headerDuration?: number;
   format?: string;
+  codec?: string;
   bitrate?: number;
   sampleRate?: number;
   channels?: number;
+  /** Title tag, used for chapter titles */
+  title?: string;
+  /** Chapters embedded in the file (ID3 CHAP frames), used instead of its per-file chapter */
+  chapters?: ChapterInfo[];
   isValid: boolean;
   error?: string;
+  errorKind?: FileErrorKind;
+  dualMono?: boolean;
+  syncStatus?: SyncStatus;
+  options?: InputOptions;
+  /** Quick when only size and tags were read; duration and validity then await a Full analysis */
+  analysisDepth?: AnalysisDepth;
+}
+
+/** How much of each file analysis reads */
+export type AnalysisDepth = 'Quick' | 'Full';
+
+/** Portion of an input to keep, in seconds from its start */
+export interface TrimSpec {
+  startSecs?: number;
+  endSecs?: number;
 }
 
+/** Per-file processing options */
+export interface InputOptions {
+  gainDb?: number;
+  trim?: TrimSpec;
+  chapterTitle?: string;
+}
+
+/** A processing input: a bare path or a path with per-file options */
+export type InputFileSpec = string | ({ path: string; order?: number } & InputOptions);
+
+/** Set when a file is not fully on disk yet; re-run analysis later */
+export type SyncStatus = 'still_syncing' | 'dataless';
+
+/** Why a file failed validation; `error` has the detail */
+export type FileErrorKind =
+  | 'not_found'
+  | 'unsupported'
+  | 'corrupt'
+  | 'drm_protected'
+  | 'permission_denied'
+  | 'still_syncing'
+  | 'timeout'
+  | 'unknown';
+
 export interface FileListInfo {
   files: AudioFile[];
   totalDuration: number;
   totalSize: number;
   validCount: number;
   invalidCount: number;
+  /** Invalid files of each kind */
+  errorCounts: Partial<Record<FileErrorKind, number>>;
+  /** Duration spread of the valid files */
+  inputStats?: InputDurationStats;
+  /** Files per disc when the inputs span two or more disc folders */
+  discGroups: DiscGroup[];
+}
+
+/** Files of one disc in a multi-disc input list */
+export interface DiscGroup {
+  disc: number;
+  fileCount: number;
+}
+
+/** Count and spread of input durations, in seconds */
+export interface InputDurationStats {
+  count: number;
+  minSecs: number;
+  maxSecs: number;
+  meanSecs: number;
+  /** Average of the two middle inputs for an even count */
+  medianSecs: number;
+  totalSecs: number;
 }
 
 export interface AudioSettings {
@@ -25,6 +97,224 @@ export interface AudioSettings {
   channels: ChannelConfig;
   sampleRate: SampleRateConfig;
   outputPath: string;
+  segmentThreshold?: number;
+  strictCrossPlatform?: boolean;
+  autoDualMono?: boolean;
+  /** Decode MP3s without a Xing/VBRI header to plan with their true duration */
+  accurateMp3Durations?: boolean;
+  enforceCoverQuality?: boolean;
+  /** Render a title and author cover when the book has no artwork anywhere */
+  generatePlaceholderCover?: boolean;
+  invalidInputPolicy?: InvalidInputPolicy;
+  probeSizeMb?: number;
+  analyzeDurationSecs?: number;
+  gaplessJoin?: boolean;
+  bitrateExcessFactor?: number;
+  strictBitrateCheck?: boolean;
+  copyTranscripts?: boolean;
+  stripTrackScopedMetadata?: boolean;
+  /** Chapter generation; omitted or null gives a multi-file book one chapter per file */
+  chapters?: ChapterOptions;
+  /** Per-file chapter titles when no template is set; defaults to 'TagTitle' (filename when untagged) */
+  chapterTitleSource?: ChapterTitleSource;
+  /** Accept a .m4a output path (same content as .m4b) for players that reject .m4b */
+  allowM4aExtension?: boolean;
+  /** Decode the finished book in the background; result arrives as verification-complete */
+  verifyOutput?: boolean;
+  /** Byte-identical output for identical inputs and settings */
+  reproducible?: boolean;
+  /** Fail the run when the output container can't hold a metadata field, instead of warning */
+  strictMetadataFields?: boolean;
+  /** Write a CUE sheet with a track per chapter next to the output */
+  exportCue?: boolean;
+  /** List the chapters at the end of the description, for players without chapter navigation */
+  appendChapterListToDescription?: boolean;
+  /** Timestamps in that list; 'Auto' adds hours only for books past an hour */
+  chapterListTimestampFormat?: ChapterTimestampFormat;
+  /** MP4 chapter atoms: FFmpeg's (Apple Books), a Nero chpl atom (some Android players), or both */
+  chapterFormat?: ChapterFormat;
+  /** Record the app version and encoder settings in the output's encoder tag (default true) */
+  writeEncodingInfo?: boolean;
+  /** Embed the first input's artwork when no cover was picked (default true) */
+  autoCoverFromInput?: boolean;
+  /** Largest cover art embedded, in bytes (default 5 MB) */
+  maxCoverBytes?: number;
+  /** Refuse a cover over the size limit instead of shrinking it */
+  strictCoverSize?: boolean;
+  /** Write the book's metadata next to the output as JSON (Audiobookshelf) or OPF */
+  metadataSidecar?: SidecarFormat;
+}
+
+export type InvalidInputPolicy = 'Fail' | 'SkipWithWarning';
+
+export interface AudiobookJob {
+  file_paths: string[];
+  settings: AudioSettings;
+  metadata?: AudiobookMetadata;
+  label?: string;
+  notes?: string;
+}
+
+/** Fills series and series_index across a batch in queue order */
+export interface SeriesAutoNumber {
+  series: string;
+  start: number;
+}
+
+/** What process_audiobook_queue does with a job the output volume can't hold */
+export type DiskSpacePolicy = 'Warn' | 'Defer';
+
+/** Disk space forecast for one queued job, from get_queue_forecast */
+export interface SpaceForecast {
+  jobId: string;
+  /** Mount point or drive of the output; absent when unknown */
+  volume?: string;
+  /** This job's output plus running and earlier queued jobs on the same volume */
+  requiredBytes: number;
+  availableBytes?: number;
+  /** Whether the required bytes and a 1 GiB reserve fit */
+  fits: boolean;
+}
+
+export type EncodeSpeedClass = 'fast' | 'moderate' | 'slow';
+
+/** Result of the pre-run encode speed calibration */
+export interface EncodeEstimate {
+  /** Seconds of audio encoded per second of wall time */
+  speedFactor: number;
+  speedClass: EncodeSpeedClass;
+  estimatedWallSeconds: number;
+}
+
+/** A quiet stretch found by silence detection */
+export interface SilenceSpan {
+  startSecs: number;
+  endSecs: number;
+  durationSecs: number;
+}
+
+/** Chapter starts suggested from the silences of one long recording */
+export interface SilenceSuggestions {
+  /** Ascending; the first is always 0 */
+  chapterStarts: number[];
+  silences: SilenceSpan[];
+  /** Silences found before edges were dropped and the cap applied */
+  detectedCount: number;
+  /** Whether shorter silences were dropped to stay under the cap */
+  truncated: boolean;
+}
+
+export type ChapterMode = 'PerFile' | 'FixedInterval';
+
+export type ChapterTitleSource = 'Filename' | 'TagTitle' | 'Numbered';
+
+export type DuplicateTitleStyle = 'SuffixNumber' | 'PrefixIndex';
+
+/** 'Auto' is MM:SS, or H:MM:SS once a chapter starts past an hour; 'Hours' is HH:MM:SS; 'Minutes' is MM:SS past 59 */
+export type ChapterTimestampFormat = 'Auto' | 'Hours' | 'Minutes';
+
+/** 'Ffmetadata' keeps FFmpeg's chapters; 'Chpl' writes only a Nero chpl atom; 'Both' writes both */
+export type ChapterFormat = 'Ffmetadata' | 'Chpl' | 'Both';
+
+/** 'Json' writes Audiobookshelf's metadata.json shape; 'Opf' an OPF package */
+export type SidecarFormat = 'Json' | 'Opf';
+
+/** How chapters are generated; omitted fields use the backend defaults */
+export interface ChapterOptions {
+  mode?: ChapterMode;
+  intervalMinutes?: number;
+  /** Placeholders: {n}, {nn}, {title}, {filename} */
+  titleTemplate?: string;
+  duplicateStyle?: DuplicateTitleStyle;
+}
+
+/** A chapter of an explicit list: set either startSecs or inputIndices */
+export interface ChapterSpec {
+  title: string;
+  /** Runs until the next chapter starts */
+  startSecs?: number;
+  /** Consecutive indices into the request's inputs */
+  inputIndices?: number[];
+}
+
+export interface Chapter {
+  title: string;
+  startSecs: number;
+  endSecs: number;
+}
+
+/** Automatic decisions a run would make, from resolve_plan */
+export interface PlanResolution {
+  sampleRate: number;
+  /** The sample rate was detected from the inputs ('auto') */
+  sampleRateDetected: boolean;
+  channels: ChannelConfig;
+  /** Stereo output switched to mono because every stereo input is dual-mono */
+  monoFromDualMono: boolean;
+  inputMode: MergeInputMode;
+  /** One entry per segment; empty when the list isn't segmented */
+  segmentInputModes: MergeInputMode[];
+  sourceBitrateKbps?: number;
+  /** Chapters written into the book; empty keeps a single input's own */
+  chapters: Chapter[];
+  skippedInputs: SkippedInput[];
+  /** Duration spread of the inputs that will be encoded */
+  inputStats?: InputDurationStats;
+  /** Expected output size from the total duration and bitrate */
+  projectedOutputBytes: number;
+  /** Network-share destinations are copied to once instead of renamed into */
+  publishStrategy: PublishStrategy;
+  /** Checks that need FFmpeg to decode audio, left for the run */
+  deferredChecks: DeferredCheck[];
+  warnings: string[];
+}
+
+/** Decode-based check the plan preview leaves for the run */
+export type DeferredCheck = 'DualMonoDetection' | 'Mp3DurationMeasurement';
+
+/** How the finished output is moved to its destination */
+export type PublishStrategy = 'Rename' | 'CopyOnce';
+
+/** Concat demuxer for matching inputs, filter graph for mixed ones */
+export type MergeInputMode = 'ConcatDemuxer' | 'ConcatFilter';
+
+/** Where the FFmpeg binary in use came from */
+export type BinaryOrigin = 'bundled' | 'legacy' | 'system' | 'override';
+
+/** Which FFmpeg is in use, from get_ffmpeg_diagnostics */
+export interface FFmpegDiagnostics {
+  path: string;
+  origin: BinaryOrigin;
+  /** First line of `ffmpeg -version` */
+  version?: string;
+  /** Encoder the app needs (libfdk_aac) */
+  encoder: string;
+  encoderAvailable: boolean;
+}
+
+export type SelfTestStep =
+  | 'synthesize_inputs'
+  | 'generate_cover'
+  | 'analyze'
+  | 'process'
+  | 'verify_duration'
+  | 'verify_tags'
+  | 'cleanup';
+
+export interface SelfTestStepOutcome {
+  step: SelfTestStep;
+  passed: boolean;
+  durationMs: number;
+  /** Why the step failed */
+  error?: string;
+}
+
+/** Result of run_self_test; steps after a failure are not run, except cleanup */
+export interface SelfTestReport {
+  passed: boolean;
+  /** First step that failed */
+  failedStep?: SelfTestStep;
+  steps: SelfTestStepOutcome[];
 }
 
 export type SampleRateConfig = 'auto' | { explicit: number };
@@ -33,42 +323,48 @@ export type ChannelConfig = 'Mono' | 'Stereo';
 
 export interface ProcessingProgress {
   stage: ProcessingStage;
+  /** Failure reason, only present when stage is 'failed' */
+  reason?: string;
   progress: number;
-  currentFile?: string;
-  filesCompleted: number;
-  totalFiles: number;
-  etaSeconds?: number;
+  current_file?: string;
+  files_completed: number;
+  total_files: number;
+  eta_seconds?: number;
+  /** Smoothed encoding speed as a multiple of realtime */
+  speed?: number;
+  /** Bytes written to the current output so far */
+  bytes_written?: number;
 }
 
 export type ProcessingStage = 
-  | 'Analyzing'
-  | 'Converting' 
-  | 'Merging'
-  | 'WritingMetadata'
-  | 'Completed'
-  | { Failed: string };
-
-// Audio settings presets
+  | 'analyzing'
+  | 'converting' 
+  | 'merging'
+  | 'writing_metadata'
+  | 'completed'
+  | 'failed';
+
+// Audio settings presets; output paths must be absolute, so presets take the output directory
 export const AudioPresets = {
-  audiobook: (): AudioSettings => ({
+  audiobook: (outputDir: string): AudioSettings => ({
     bitrate: 64,
     channels: 'Mono',
     sampleRate: { explicit: 22050 },
-    outputPath: 'audiobook.m4b'
+    outputPath: `${outputDir}/audiobook.m4b`
   }),
   
-  highQuality: (): AudioSettings => ({
+  highQuality: (outputDir: string): AudioSettings => ({
     bitrate: 128,
     channels: 'Stereo', 
     sampleRate: { explicit: 44100 },
-    outputPath: 'audiobook_hq.m4b'
+    outputPath: `${outputDir}/audiobook_hq.m4b`
   }),
   
-  lowBandwidth: (): AudioSettings => ({
+  lowBandwidth: (outputDir: string): AudioSettings => ({
     bitrate: 32,
     channels: 'Mono',
     sampleRate: { explicit: 16000 },
-    outputPath: 'audiobook_low.m4b'
+    outputPath: `${outputDir}/audiobook_low.m4b`
   })
 };
 
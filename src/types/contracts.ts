@@ -0,0 +1,266 @@
+// Generated from src-tauri/src/contracts by `cargo run --features ts-export --bin generate_ts_types`.
+// Do not edit by hand.
+
+/** An analyzed input file */
+export interface AudioFile {
+  path: string;
+  size?: number;
+  duration?: number;
+  headerDuration?: number;
+  format?: string;
+  codec?: string;
+  bitrate?: number;
+  sampleRate?: number;
+  channels?: number;
+  title?: string;
+  chapters?: ChapterInfo[];
+  isValid: boolean;
+  error?: string;
+  errorKind?: FileErrorKind;
+  dualMono?: boolean;
+  syncStatus?: SyncStatus;
+  options?: InputOptions;
+  analysisDepth?: AnalysisDepth;
+}
+
+/** Why a file failed validation */
+export type FileErrorKind = 'not_found' | 'unsupported' | 'corrupt' | 'drm_protected' | 'permission_denied' | 'still_syncing' | 'timeout' | 'unknown';
+
+/** How much of each file analysis reads */
+export type AnalysisDepth = 'Quick' | 'Full';
+
+/** Why a file could not be analyzed yet */
+export type SyncStatus = 'still_syncing' | 'dataless';
+
+/** Per-file processing options */
+export interface InputOptions {
+  gainDb?: number;
+  trim?: TrimSpec;
+  chapterTitle?: string;
+}
+
+/** Portion of an input to keep, in seconds from its start */
+export interface TrimSpec {
+  startSecs?: number;
+  endSecs?: number;
+}
+
+/** Summary of an analyzed file list */
+export interface FileListInfo {
+  files: AudioFile[];
+  totalDuration: number;
+  totalSize: number;
+  validCount: number;
+  invalidCount: number;
+  errorCounts: Partial<Record<FileErrorKind, number>>;
+  inputStats?: InputDurationStats;
+  discGroups: DiscGroup[];
+}
+
+/** Output encoding and processing settings */
+export interface AudioSettings {
+  bitrate: number;
+  channels: ChannelConfig;
+  sampleRate: SampleRateConfig;
+  outputPath: string;
+  segmentThreshold?: number;
+  strictCrossPlatform?: boolean;
+  autoDualMono?: boolean;
+  accurateMp3Durations?: boolean;
+  enforceCoverQuality?: boolean;
+  generatePlaceholderCover?: boolean;
+  invalidInputPolicy?: InvalidInputPolicy;
+  probeSizeMb?: number;
+  analyzeDurationSecs?: number;
+  gaplessJoin?: boolean;
+  bitrateExcessFactor?: number;
+  strictBitrateCheck?: boolean;
+  copyTranscripts?: boolean;
+  stripTrackScopedMetadata?: boolean;
+  chapters?: ChapterOptions;
+  chapterTitleSource?: ChapterTitleSource;
+  allowM4aExtension?: boolean;
+  verifyOutput?: boolean;
+  reproducible?: boolean;
+  strictMetadataFields?: boolean;
+  exportCue?: boolean;
+  appendChapterListToDescription?: boolean;
+  chapterListTimestampFormat?: ChapterTimestampFormat;
+  chapterFormat?: ChapterFormat;
+  writeEncodingInfo?: boolean;
+  autoCoverFromInput?: boolean;
+  maxCoverBytes?: number;
+  strictCoverSize?: boolean;
+  metadataSidecar?: SidecarFormat;
+}
+
+/** Output channel layout */
+export type ChannelConfig = 'Mono' | 'Stereo';
+
+/** Output sample rate, detected or explicit in Hz */
+export type SampleRateConfig = 'auto' | { explicit: number };
+
+/** How chapters are generated */
+export interface ChapterOptions {
+  mode?: ChapterMode;
+  intervalMinutes?: number;
+  titleTemplate?: string;
+  duplicateStyle?: DuplicateTitleStyle;
+}
+
+/** Where chapter marks are placed */
+export type ChapterMode = 'PerFile' | 'FixedInterval';
+
+/** What per-file chapter titles are made from when no template is set */
+export type ChapterTitleSource = 'Filename' | 'TagTitle' | 'Numbered';
+
+/** How chapter starts are written in a description's chapter list */
+export type ChapterTimestampFormat = 'Auto' | 'Hours' | 'Minutes';
+
+/** File format of the metadata sidecar */
+export type SidecarFormat = 'Json' | 'Opf';
+
+/** Which MP4 chapter atoms are written */
+export type ChapterFormat = 'Ffmetadata' | 'Chpl' | 'Both';
+
+/** How repeated chapter titles are made unique */
+export type DuplicateTitleStyle = 'SuffixNumber' | 'PrefixIndex';
+
+/** Handling of invalid inputs when processing starts */
+export type InvalidInputPolicy = 'Fail' | 'SkipWithWarning';
+
+/** Book-level tags (snake_case on the wire) */
+export interface AudiobookMetadata {
+  title?: string;
+  subtitle?: string;
+  author?: string;
+  album?: string;
+  narrator?: string;
+  year?: number;
+  genre?: string;
+  description?: string;
+  series?: string;
+  series_index?: number;
+  track_number?: number;
+  track_total?: number;
+  disc_number?: number;
+  disc_total?: number;
+  sort_title?: string;
+  sort_author?: string;
+  isbn?: string;
+  asin?: string;
+  cover_art?: number[];
+  tag_sources?: string[];
+  chapters?: ChapterInfo[];
+  encoding_info?: EncodingInfo;
+}
+
+/** Cover image as base64 with its MIME type, ready for a data URL */
+export interface CoverArt {
+  mime: string;
+  dataBase64: string;
+  width?: number;
+  height?: number;
+}
+
+/** How a file was encoded, as read from its encoder tag */
+export interface EncodingInfo {
+  tool: string;
+  codec?: string;
+  bitrate_kbps?: number;
+  profile?: string;
+  sample_rate?: number;
+  channels?: number;
+  normalization?: string;
+}
+
+/** A chapter read from an existing file */
+export interface ChapterInfo {
+  title: string;
+  startMs: number;
+  endMs: number;
+}
+
+/** Stage of a processing run */
+export type ProcessingStage = 'analyzing' | 'converting' | 'merging' | 'writing_metadata' | 'completed' | 'failed';
+
+/** Progress of a session, as returned by get_processing_progress */
+export interface ProcessingProgress {
+  stage: ProcessingStage;
+  reason?: string;
+  progress: number;
+  current_file?: string;
+  files_completed: number;
+  total_files: number;
+  eta_seconds?: number;
+  speed?: number;
+  bytes_written?: number;
+}
+
+/** Payload of the processing-progress event */
+export interface ProgressEvent {
+  stage: ProcessingStage;
+  reason?: string;
+  percentage: number;
+  message: string;
+  current_file?: string;
+  eta_seconds?: number;
+  timeline?: StageTiming[];
+  skipped_inputs?: SkippedInput[];
+  label?: string;
+  notes?: string;
+  cancel_reason?: CancelReason;
+  metadata_pending?: boolean;
+  warnings?: string[];
+  input_stats?: InputDurationStats;
+}
+
+/** Start and duration of one pipeline stage */
+export interface StageTiming {
+  stage: TimelineStage;
+  startedAtMs: number;
+  durationMs: number;
+  finished: boolean;
+}
+
+/** Timed pipeline stage */
+export type TimelineStage = 'analyzing' | 'converting' | 'merging' | 'writing_metadata' | 'publish' | 'cleanup';
+
+/** An input left out under the SkipWithWarning policy */
+export interface SkippedInput {
+  path: string;
+  error?: string;
+}
+
+/** Count and spread of input durations, in seconds */
+export interface InputDurationStats {
+  count: number;
+  minSecs: number;
+  maxSecs: number;
+  meanSecs: number;
+  medianSecs: number;
+  totalSecs: number;
+}
+
+/** Files of one disc in a multi-disc input list */
+export interface DiscGroup {
+  disc: number;
+  fileCount: number;
+}
+
+/** Which code path stopped a run */
+export type CancelReason = 'user_requested' | 'watchdog' | 'quota' | 'timeout' | 'shutdown' | 'soft_stopped';
+
+/** Structured form of a backend error */
+export interface ErrorPayload {
+  code: ErrorCode;
+  message: string;
+  cancelReason?: CancelReason;
+  ffmpegOrigin?: BinaryOrigin;
+}
+
+/** Where the FFmpeg binary in use came from */
+export type BinaryOrigin = 'bundled' | 'legacy' | 'system' | 'override';
+
+/** Kind of a backend error */
+export type ErrorCode = 'ffmpeg' | 'file_validation' | 'invalid_input' | 'file_in_use' | 'path_not_granted' | 'cancelled' | 'io' | 'metadata' | 'process_termination' | 'temp_directory_creation' | 'resource_cleanup' | 'general';
@@ -9,6 +9,8 @@
  * Purpose: Preserve exact behavior during refactoring
  */
 
+import type { InputDurationStats, ProcessingProgress } from "./audio";
+
 // ============================================================================
 // PROCESSING EVENTS (Backend → Frontend)
 // ============================================================================
@@ -27,7 +29,10 @@
  */
 export interface ProcessingProgressEvent {
     /** Processing stage identifier */
-    stage: 'analyzing' | 'converting' | 'merging' | 'writing' | 'completed' | 'failed' | 'cancelled';
+    stage: 'analyzing' | 'converting' | 'merging' | 'writing_metadata' | 'completed' | 'failed' | 'cancelled';
+    
+    /** Failure reason, only present when stage is 'failed' */
+    reason?: string;
     
     /** Progress percentage (0.0 to 100.0) */
     percentage: number;
@@ -40,6 +45,120 @@ export interface ProcessingProgressEvent {
     
     /** Estimated time remaining in seconds (optional) */
     eta_seconds?: number;
+    
+    /** Per-stage timings, only present on the completion event */
+    timeline?: StageTiming[];
+    
+    /** Inputs skipped by the SkipWithWarning policy, only on the completion event */
+    skipped_inputs?: SkippedInput[];
+    
+    /** Job label (defaults to the output filename stem) */
+    label?: string;
+    
+    /** Job notes */
+    notes?: string;
+    
+    /** Why the run was cancelled, only on a failed event caused by cancellation */
+    cancel_reason?: CancelReason;
+    
+    /** Set on the completion event when the output was kept without its tags; retry with retry_metadata_write */
+    metadata_pending?: boolean;
+
+    /** Warnings of a completed run, such as metadata fields the container could not hold */
+    warnings?: string[];
+
+    /** Duration spread of the book's inputs, only on the completion event */
+    input_stats?: InputDurationStats;
+}
+
+/**
+ * Code path that cancelled a run
+ * 
+ * Source: src-tauri/src/errors.rs (CancelReason enum)
+ */
+export type CancelReason = 'user_requested' | 'watchdog' | 'quota' | 'timeout' | 'shutdown' | 'soft_stopped';
+
+/**
+ * An invalid input left out of processing
+ * 
+ * Source: src-tauri/src/audio/file_list.rs (SkippedInput struct)
+ */
+export interface SkippedInput {
+    path: string;
+    error?: string;
+}
+
+/**
+ * Warning raised during a run, also listed in the completion event's warnings
+ * 
+ * Source: src-tauri/src/audio/processor.rs (ProcessingWarning, report_warning)
+ */
+export interface ProcessingWarningEvent {
+    message: string;
+    /** Inputs left out by the skip policy; empty for other warnings */
+    skippedInputs: SkippedInput[];
+}
+
+/**
+ * Timing of one pipeline stage, relative to the session start
+ * 
+ * Source: src-tauri/src/audio/timeline.rs (StageTiming struct)
+ * Also returned by invoke('get_session_timeline')
+ */
+export interface StageTiming {
+    stage: 'analyzing' | 'converting' | 'merging' | 'writing_metadata' | 'publish' | 'cleanup';
+    startedAtMs: number;
+    durationMs: number;
+    finished: boolean;
+}
+
+/**
+ * Progress so far for a session, sent once to a late subscriber
+ * 
+ * Source: src-tauri/src/audio/progress_snapshot.rs (ProgressSnapshot struct)
+ * Emitted by invoke('subscribe_progress'), which also returns it
+ */
+export interface ProgressSnapshotEvent {
+    sessionId: string;
+    /** Latest progress, absent until the first progress event */
+    progress?: ProcessingProgress | null;
+    timeline: StageTiming[];
+    warnings: string[];
+    /** Recent telemetry points, oldest first, to redraw the speed graph */
+    telemetry: TelemetryPoint[];
+}
+
+/**
+ * One point of the live speed and bitrate graph, at most every 2 seconds
+ * 
+ * Source: src-tauri/src/audio/telemetry.rs (TelemetryPoint struct)
+ * Emitted while FFmpeg encodes; a session keeps the last 1,800 for snapshots
+ */
+export interface TelemetryPoint {
+    /** Unix timestamp (milliseconds) */
+    timestampMs: number;
+    /** Smoothed speed as a multiple of realtime */
+    speed?: number | null;
+    bitrateKbps?: number | null;
+    bytesWritten?: number | null;
+}
+
+/**
+ * Result of decoding a finished book in the background
+ * 
+ * Source: src-tauri/src/audio/verification.rs (VerificationComplete struct)
+ * Emitted after a successful run with verifyOutput set, once no encode is running
+ */
+export interface VerificationCompleteEvent {
+    sessionId: string;
+    outputPath: string;
+    outcome: {
+        passed: boolean;
+        /** Decoder output, only present when verification failed */
+        error?: string;
+        /** Unix timestamp (seconds) */
+        verifiedAt: number;
+    };
 }
 
 // ============================================================================
@@ -77,6 +196,18 @@ export interface TauriFileDropEvents {
 export interface ApplicationEvents extends TauriFileDropEvents {
     /** Progress updates during audiobook processing */
     'processing-progress': ProcessingProgressEvent;
+    
+    /** Non-fatal problems found while preparing a run */
+    'processing-warning': ProcessingWarningEvent;
+    
+    /** One-shot state for a subscriber that joined mid-run */
+    'processing-progress-snapshot': ProgressSnapshotEvent;
+    
+    /** Encoder speed, bitrate and output size for the live graph */
+    'processing-telemetry': TelemetryPoint;
+    
+    /** Background verification of a finished book */
+    'verification-complete': VerificationCompleteEvent;
 }
 
 // ============================================================================
@@ -185,7 +316,7 @@ export function isProcessingProgressEvent(
         typeof e.stage === 'string' &&
         typeof e.percentage === 'number' &&
         typeof e.message === 'string' &&
-        ['analyzing', 'converting', 'merging', 'writing', 'completed', 'failed', 'cancelled'].includes(e.stage)
+        ['analyzing', 'converting', 'merging', 'writing_metadata', 'completed', 'failed', 'cancelled'].includes(e.stage)
     );
 }
 
@@ -8,6 +8,8 @@
 export interface AudiobookMetadata {
   /** Title of the audiobook */
   title?: string;
+  /** Subtitle of the book */
+  subtitle?: string;
   /** Author of the book */
   author?: string;
   /** Album name (book/series name) */
@@ -20,12 +22,67 @@ export interface AudiobookMetadata {
   genre?: string;
   /** Series name */
   series?: string;
+  /** Position within the series (may be fractional, e.g. 1.5) */
+  series_index?: number;
+  /** Track number of this part */
+  track_number?: number;
+  /** Total number of tracks */
+  track_total?: number;
+  /** Disc number of this part */
+  disc_number?: number;
+  /** Total number of discs */
+  disc_total?: number;
+  /** Title used for sorting (e.g. "Hobbit, The") */
+  sort_title?: string;
+  /** Author used for sorting (e.g. "Tolkien, J.R.R.") */
+  sort_author?: string;
+  /** ISBN-10 or ISBN-13, hyphens allowed */
+  isbn?: string;
+  /** Amazon/Audible ASIN */
+  asin?: string;
   /** Description or synopsis */
   description?: string;
   /** Cover art as base64 encoded string (optional in responses) */
   coverArt?: string;
   /** Cover art as raw bytes from backend (snake_case field name) */
   cover_art?: number[];
+  /** Tag formats the fields were read from, in merge order (e.g. "ID3v2", "ID3v1") */
+  tag_sources?: string[];
+  /** Chapters of an existing M4B, ordered by start time; ignored when writing */
+  chapters?: ChapterInfo[];
+  /** Encoder settings from the tool tag; when set, writing replaces the tool tag with it */
+  encoding_info?: EncodingInfo;
+}
+
+/**
+ * How a file was encoded, as read from its encoder tag (`©too`/TSSE).
+ * Foreign tool strings leave only `tool` set.
+ */
+export interface EncodingInfo {
+  /** Application and version, or the whole tag when it isn't ours */
+  tool: string;
+  /** FFmpeg encoder, e.g. "libfdk_aac" */
+  codec?: string;
+  /** Target bitrate in kbps */
+  bitrate_kbps?: number;
+  /** Encoder profile, e.g. "aac_low" */
+  profile?: string;
+  /** Output sample rate in Hz */
+  sample_rate?: number;
+  /** Output channel count */
+  channels?: number;
+  /** Loudness normalization applied; "none" when levels were left alone */
+  normalization?: string;
+}
+
+/**
+ * A chapter read from an existing file, or written with write_audio_chapters
+ * (in order and not overlapping)
+ */
+export interface ChapterInfo {
+  title: string;
+  startMs: number;
+  endMs: number;
 }
 
 /**
@@ -45,6 +102,63 @@ export interface WriteMetadataParams {
   metadata: AudiobookMetadata;
 }
 
+/**
+ * Tag field a description was written to
+ */
+export type DescriptionField = 'comment' | 'long_description' | 'lyrics';
+
+/**
+ * Book-level metadata field, as named in write reports
+ */
+export type MetadataField =
+  | 'title' | 'subtitle' | 'author' | 'album' | 'narrator' | 'year' | 'genre' | 'description'
+  | 'series' | 'series_index' | 'track_number' | 'track_total' | 'disc_number' | 'disc_total'
+  | 'sort_title' | 'sort_author' | 'isbn' | 'asin';
+
+/**
+ * Defaults layered under every new book's metadata, from get_default_metadata_profile.
+ * Requested fields win, then tags inherited from the inputs, then the profile;
+ * locked fields beat the inherited tags.
+ */
+export interface MetadataProfile {
+  metadata: AudiobookMetadata;
+  lockedFields?: MetadataField[];
+}
+
+/**
+ * How infer_book_metadata picks a field the inputs disagree on: the first
+ * input's value, the most common value (ties go to the first seen), or
+ * only a value every input shares
+ */
+export type MergeStrategy = 'FirstFile' | 'MostCommon' | 'Intersection';
+
+/** A field the inputs disagree on */
+export interface MetadataConflict {
+  field: MetadataField;
+  /** Distinct values in input order */
+  values: string[];
+  /** Value the strategy picked, if any */
+  chosen?: string;
+}
+
+/** Book-level metadata inferred from all inputs, from infer_book_metadata */
+export interface InferredMetadata {
+  metadata: AudiobookMetadata;
+  conflicts: MetadataConflict[];
+}
+
+/**
+ * Result of write_audio_metadata; long descriptions keep the start in the
+ * comment and the full text in the MP4 long description or an ID3v2 USLT frame
+ */
+export interface MetadataWriteReport {
+  descriptionFields: DescriptionField[];
+  /** The comment holds only the start of the description */
+  descriptionTruncated: boolean;
+  /** Fields the container could not hold; everything else was written */
+  failedFields?: MetadataField[];
+}
+
 /**
  * Parameters for writing cover art
  */
@@ -52,3 +166,43 @@ export interface WriteCoverArtParams {
   filePath: string;
   coverData: number[]; // byte array
 }
+
+/**
+ * Cover art as returned by read_cover_art
+ */
+export interface CoverArt {
+  /** MIME type from the image signature */
+  mime: string;
+  /** Image bytes, standard padded base64 */
+  dataBase64: string;
+  width?: number;
+  height?: number;
+}
+
+/** Data URL for showing a cover in an <img> */
+export function coverDataUrl(cover: CoverArt): string {
+  return `data:${cover.mime};base64,${cover.dataBase64}`;
+}
+
+/** Decodes a cover back to the byte array the write commands take */
+export function coverBytes(cover: CoverArt): number[] {
+  return Array.from(atob(cover.dataBase64), (c) => c.charCodeAt(0));
+}
+
+/**
+ * Quality warning codes from assess_cover_art
+ */
+export type CoverWarning = 'too_small' | 'not_square' | 'too_large';
+
+/**
+ * Cover art dimensions, size and quality warnings
+ */
+export interface CoverAssessment {
+  format: 'jpeg' | 'png' | 'webp';
+  width: number;
+  height: number;
+  /** Width divided by height */
+  aspectRatio: number;
+  byteSize: number;
+  warnings: CoverWarning[];
+}
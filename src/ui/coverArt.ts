@@ -64,6 +64,7 @@ async function handleLoadCoverArt(): Promise<void> {
         if (!selectedFile || typeof selectedFile !== 'string') {
             return; // User cancelled
         }
+        await invoke('register_granted_path', { path: selectedFile });
 
         // Load image data from backend
         const imageData = await invoke<number[]>('load_cover_art_file', { 
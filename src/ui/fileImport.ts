@@ -58,10 +58,12 @@ async function handleClickToSelect(): Promise<void> {
             }]
         });
         
-        if (Array.isArray(selected) && selected.length > 0) {
-            await processFilePaths(selected);
-        } else if (typeof selected === 'string') {
-            await processFilePaths([selected]);
+        const paths = Array.isArray(selected) ? selected : typeof selected === 'string' ? [selected] : [];
+        for (const path of paths) {
+            await invoke('register_granted_path', { path });
+        }
+        if (paths.length > 0) {
+            await processFilePaths(paths);
         }
     } catch (error) {
         showError(`Failed to open file dialog: ${error}`);
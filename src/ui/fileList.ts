@@ -2,6 +2,7 @@ import { AudioFile, FileListInfo, formatDuration, formatFileSize } from '../type
 import { invoke } from '@tauri-apps/api/core';
 import { onFileListChange } from './outputPanel';
 import { setCoverArt } from './coverArt';
+import { AudiobookMetadata, CoverArt, coverBytes } from '../types/metadata';
 
 let currentFileList: FileListInfo | null = null;
 let selectedFileIndex: number = -1;
@@ -158,14 +159,15 @@ function updateFileProperties(file: AudioFile): void {
 
 async function loadFileMetadata(filePath: string): Promise<void> {
     try {
-        const metadata = await invoke('read_audio_metadata', { filePath: filePath });
-        populateMetadataForm(metadata);
+        const metadata = await invoke<AudiobookMetadata>('read_audio_metadata', { filePath, includeCover: false });
+        const cover = await invoke<CoverArt | null>('read_cover_art', { filePath });
+        populateMetadataForm(metadata, cover ? coverBytes(cover) : null);
     } catch (error) {
         console.warn('Failed to load metadata:', error);
     }
 }
 
-function populateMetadataForm(metadata: any): void {
+function populateMetadataForm(metadata: AudiobookMetadata, cover: number[] | null): void {
     const titleEl = document.getElementById('meta-title') as HTMLInputElement;
     const authorEl = document.getElementById('meta-author') as HTMLInputElement;
     const albumEl = document.getElementById('meta-album') as HTMLInputElement;
@@ -183,7 +185,7 @@ function populateMetadataForm(metadata: any): void {
     if (descriptionEl && metadata.description) descriptionEl.value = metadata.description;
 
     // Handle cover art display - use the new cover art module
-    setCoverArt(metadata.cover_art || null);
+    setCoverArt(cover);
 }
 
 
@@ -1,4 +1,4 @@
-// import { invoke } from "@tauri-apps/api/core";
+import { invoke } from "@tauri-apps/api/core";
 import { open } from "@tauri-apps/plugin-dialog";
 import type { AudioSettings, ChannelConfig, SampleRateConfig } from "../types/audio";
 import type { AudiobookMetadata } from "../types/metadata";
@@ -130,6 +130,7 @@ async function handleDirectoryBrowse(): Promise<void> {
     });
 
     if (selectedPath && typeof selectedPath === 'string') {
+      await invoke('register_granted_path', { path: selectedPath });
       currentState.outputDirectory = selectedPath;
       updateOutputPath();
     }
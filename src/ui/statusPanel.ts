@@ -2,7 +2,7 @@ import { listen } from '@tauri-apps/api/event';
 import { invoke } from '@tauri-apps/api/core';
 import { currentFileList } from './fileList';
 import { getCurrentAudioSettings } from './outputPanel';
-import { AudiobookMetadata } from '../types/metadata';
+import { CoverArt, coverDataUrl } from '../types/metadata';
 
 interface ProgressEvent {
     stage: string;
@@ -13,7 +13,7 @@ interface ProgressEvent {
 }
 
 interface ProcessingStatus {
-    stage: 'idle' | 'analyzing' | 'converting' | 'merging' | 'writing' | 'completed' | 'cancelled' | 'failed';
+    stage: 'idle' | 'analyzing' | 'converting' | 'merging' | 'writing_metadata' | 'completed' | 'cancelled' | 'failed';
     percentage: number;
     message: string;
     currentFile?: string;
@@ -227,7 +227,7 @@ export class StatusPanel {
                 return 'Converting';
             case 'merging':
                 return 'Merging';
-            case 'writing':
+            case 'writing_metadata':
                 return 'Writing Metadata';
             case 'completed':
                 return 'Completed';
@@ -284,37 +284,6 @@ export class StatusPanel {
         this.resetArtThumbnail();
     }
 
-    private convertBytesToDataUrl(bytes: number[]): string {
-        // Convert number array to Uint8Array
-        const uint8Array = new Uint8Array(bytes);
-        
-        // Detect image format from magic bytes
-        let mimeType = 'image/jpeg'; // default fallback
-        if (uint8Array.length >= 4) {
-            // PNG: 89 50 4E 47
-            if (uint8Array[0] === 0x89 && uint8Array[1] === 0x50 && uint8Array[2] === 0x4E && uint8Array[3] === 0x47) {
-                mimeType = 'image/png';
-            }
-            // JPEG: FF D8 FF
-            else if (uint8Array[0] === 0xFF && uint8Array[1] === 0xD8 && uint8Array[2] === 0xFF) {
-                mimeType = 'image/jpeg';
-            }
-            // WebP: 52 49 46 46 ... 57 45 42 50
-            else if (uint8Array[0] === 0x52 && uint8Array[1] === 0x49 && uint8Array[2] === 0x46 && uint8Array[3] === 0x46) {
-                mimeType = 'image/webp';
-            }
-        }
-        
-        // Convert to base64
-        let binary = '';
-        uint8Array.forEach(byte => {
-            binary += String.fromCharCode(byte);
-        });
-        const base64 = btoa(binary);
-        
-        return `data:${mimeType};base64,${base64}`;
-    }
-
     private async updateArtThumbnail(): Promise<void> {
         if (!currentFileList || !currentFileList.files.length) {
             this.resetArtThumbnail();
@@ -329,15 +298,13 @@ export class StatusPanel {
         }
 
         try {
-            // Load metadata with cover art
-            const metadata = await invoke<AudiobookMetadata>('read_audio_metadata', { 
+            // The backend sends the cover as base64 with its MIME type
+            const cover = await invoke<CoverArt | null>('read_cover_art', { 
                 filePath: firstValidFile.path 
             });
             
-            // Check for cover art data (backend returns as cover_art field with number array)
-            if (metadata.cover_art && metadata.cover_art.length > 0) {
-                const dataUrl = this.convertBytesToDataUrl(metadata.cover_art);
-                this.displayCoverArt(dataUrl);
+            if (cover) {
+                this.displayCoverArt(coverDataUrl(cover));
             } else {
                 this.resetArtThumbnail();
             }